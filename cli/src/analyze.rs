@@ -0,0 +1,186 @@
+//! Statistics of how a text corpus uses a font, for `--analyze`.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+use ttf_parser::Face;
+
+use crate::{output_format, read_font, run, text, Args};
+
+/// The scripts that characters are broken down by, with the blocks of each.
+/// Characters in none of them count as "Other".
+const SCRIPTS: [(&str, &[(char, char)]); 16] = [
+    (
+        "Latin",
+        &[
+            ('A', 'Z'),
+            ('a', 'z'),
+            ('\u{00AA}', '\u{00AA}'),
+            ('\u{00BA}', '\u{00BA}'),
+            ('\u{00C0}', '\u{00D6}'),
+            ('\u{00D8}', '\u{00F6}'),
+            ('\u{00F8}', '\u{024F}'),
+            ('\u{1E00}', '\u{1EFF}'),
+            ('\u{2C60}', '\u{2C7F}'),
+            ('\u{A720}', '\u{A7FF}'),
+            ('\u{FF21}', '\u{FF3A}'),
+            ('\u{FF41}', '\u{FF5A}'),
+        ],
+    ),
+    ("Greek", &[('\u{0370}', '\u{03FF}'), ('\u{1F00}', '\u{1FFF}')]),
+    (
+        "Cyrillic",
+        &[('\u{0400}', '\u{052F}'), ('\u{2DE0}', '\u{2DFF}'), ('\u{A640}', '\u{A69F}')],
+    ),
+    ("Armenian", &[('\u{0530}', '\u{058F}')]),
+    ("Hebrew", &[('\u{0590}', '\u{05FF}')]),
+    (
+        "Arabic",
+        &[
+            ('\u{0600}', '\u{06FF}'),
+            ('\u{0750}', '\u{077F}'),
+            ('\u{08A0}', '\u{08FF}'),
+            ('\u{FB50}', '\u{FDFF}'),
+            ('\u{FE70}', '\u{FEFE}'),
+        ],
+    ),
+    ("Devanagari", &[('\u{0900}', '\u{097F}')]),
+    ("Bengali", &[('\u{0980}', '\u{09FF}')]),
+    ("Thai", &[('\u{0E00}', '\u{0E7F}')]),
+    ("Georgian", &[('\u{10A0}', '\u{10FF}')]),
+    (
+        "Hangul",
+        &[('\u{1100}', '\u{11FF}'), ('\u{3130}', '\u{318F}'), ('\u{AC00}', '\u{D7AF}')],
+    ),
+    ("Hiragana", &[('\u{3040}', '\u{309F}')]),
+    ("Katakana", &[('\u{30A0}', '\u{30FF}'), ('\u{31F0}', '\u{31FF}')]),
+    (
+        "Han",
+        &[
+            ('\u{2E80}', '\u{2FDF}'),
+            ('\u{3400}', '\u{4DBF}'),
+            ('\u{4E00}', '\u{9FFF}'),
+            ('\u{F900}', '\u{FAFF}'),
+            ('\u{20000}', '\u{3FFFF}'),
+        ],
+    ),
+    ("Inherited", &[('\u{0300}', '\u{036F}'), ('\u{1AB0}', '\u{1AFF}')]),
+    (
+        "Common",
+        &[
+            ('\u{0020}', '\u{0040}'),
+            ('\u{005B}', '\u{0060}'),
+            ('\u{007B}', '\u{00BF}'),
+            ('\u{00D7}', '\u{00D7}'),
+            ('\u{00F7}', '\u{00F7}'),
+            ('\u{2000}', '\u{2BFF}'),
+            ('\u{3000}', '\u{303F}'),
+            ('\u{FF00}', '\u{FF20}'),
+            ('\u{FF3B}', '\u{FF40}'),
+            ('\u{FF5B}', '\u{FFEF}'),
+        ],
+    ),
+];
+
+/// The script of a character, following [`SCRIPTS`].
+fn script(c: char) -> &'static str {
+    SCRIPTS
+        .iter()
+        .find(|(_, ranges)| ranges.iter().any(|&(start, end)| (start..=end).contains(&c)))
+        .map_or("Other", |&(name, _)| name)
+}
+
+/// Analyze the corpus of `--text` and `--text-file` and the subset it needs,
+/// for `--analyze`.
+///
+/// The subset is made once per format, with all other options as given, so
+/// that the sizes are those the same options would produce.
+pub fn analyze(args: &Args) -> Value {
+    let mut corpus = args.text.clone();
+    for path in &args.text_file {
+        corpus.push(text::read(path).expect("could not read text file"));
+    }
+    let mut counts: BTreeMap<char, usize> = BTreeMap::new();
+    for c in corpus.iter().flat_map(|text| text.chars()) {
+        if !c.is_control() {
+            *counts.entry(c).or_default() += 1;
+        }
+    }
+
+    let (_, font_data) = read_font(args);
+    let face =
+        Face::parse(&font_data, args.face_index()).expect("could not parse font file");
+    let glyph = |c: char| face.glyph_index(c).map(|g| g.0);
+
+    let mut frequencies: Vec<(char, usize)> = counts.into_iter().collect();
+    frequencies.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    // The distinct characters, occurrences and missing characters by script.
+    let mut scripts: Vec<(&str, usize, usize, Vec<char>)> = vec![];
+    for &(c, count) in &frequencies {
+        let name = script(c);
+        let i = match scripts.iter().position(|s| s.0 == name) {
+            Some(i) => i,
+            None => {
+                scripts.push((name, 0, 0, vec![]));
+                scripts.len() - 1
+            }
+        };
+        scripts[i].1 += 1;
+        scripts[i].2 += count;
+        if glyph(c).is_none() {
+            scripts[i].3.push(c);
+        }
+    }
+    scripts.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(b.0)));
+
+    let mut formats: Vec<&str> = args
+        .output
+        .iter()
+        .map(|output| output_format(args, Some(output)))
+        .collect();
+    if formats.is_empty() {
+        formats = vec!["ttf", "woff2"];
+    }
+    formats.sort_unstable();
+    formats.dedup();
+
+    let mut report = None;
+    let mut sizes = vec![];
+    for format in formats {
+        let args = Args {
+            output: vec![],
+            format: Some(format.into()),
+            dry_run: true,
+            ..args.clone()
+        };
+        let (input_size, results, found) = run(&args);
+        let size = results[0].len();
+        sizes.push(json!({
+            "format": format,
+            "size": size,
+            "percent": 100 * size / input_size,
+        }));
+        report.get_or_insert(found);
+    }
+    let report = report.unwrap();
+
+    let frequencies: Vec<_> = frequencies
+        .iter()
+        .map(|&(c, count)| json!({ "char": c, "count": count, "glyph": glyph(c) }))
+        .collect();
+    let scripts: Vec<_> = scripts
+        .iter()
+        .map(|(name, chars, count, missing)| {
+            json!({ "script": name, "chars": chars, "count": count, "missing": missing })
+        })
+        .collect();
+    json!({
+        "input": args.input,
+        "chars": frequencies.len(),
+        "frequencies": frequencies,
+        "scripts": scripts,
+        "glyphs": report.glyphs.len(),
+        "sizes": sizes,
+    })
+}
@@ -0,0 +1,104 @@
+//! Decoding fonts given as base64 or as `data:` URIs, for inputs like
+//! `data:font/woff2;base64,d09GMg...` and `--input-base64`.
+
+/// Decode base64 in the standard or the URL-safe alphabet. Whitespace is
+/// ignored and the padding is optional.
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut buffer = 0_u32;
+    let mut bits = 0;
+    let mut padding = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => {
+                padding += 1;
+                continue;
+            }
+            _ => return Err(format!("invalid base64 character {:?}", c as char)),
+        };
+        if padding > 0 {
+            return Err("base64 data continues after padding".into());
+        }
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    // Leftover bits that don't make a byte are zero padding.
+    if bits >= 6 || padding > 2 {
+        return Err("truncated base64 data".into());
+    }
+    Ok(out)
+}
+
+/// Decode the data of a `data:` URI, which is either base64 or
+/// percent-encoded. The media type isn't checked, since fonts are labeled
+/// inconsistently, e.g. `font/woff2` or `application/x-font-woff`.
+pub fn decode_uri(uri: &str) -> Result<Vec<u8>, String> {
+    let rest = uri.trim().strip_prefix("data:").ok_or("not a data URI")?;
+    let (header, data) = rest.split_once(',').ok_or("data URI without data")?;
+    if header.split(';').any(|param| param.eq_ignore_ascii_case("base64")) {
+        return decode(data);
+    }
+
+    let mut out = vec![];
+    let mut bytes = data.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'%' {
+            out.push(byte);
+            continue;
+        }
+        // Two hex digits, which `from_str_radix` alone doesn't ensure as it
+        // accepts a sign and a single digit.
+        let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+        let valid = hex.len() == 2 && hex.iter().all(u8::is_ascii_hexdigit);
+        let hex = std::str::from_utf8(&hex).ok().filter(|_| valid);
+        let byte = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        out.push(byte.ok_or("invalid percent escape")?);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("").unwrap(), b"");
+        assert_eq!(decode("Zm9vYmFy").unwrap(), b"foobar");
+        assert_eq!(decode("Zm9vYg==").unwrap(), b"foob");
+        assert_eq!(decode("Zm9vYg").unwrap(), b"foob");
+        assert_eq!(decode("Zm9vYmE=").unwrap(), b"fooba");
+        assert_eq!(decode(" Zm9v\nYmE\t").unwrap(), b"fooba");
+        assert_eq!(decode("+/8=").unwrap(), decode("-_8").unwrap());
+        assert_eq!(decode("-_8").unwrap(), [0xFB, 0xFF]);
+
+        assert_eq!(decode("Zm9v!"), Err("invalid base64 character '!'".into()));
+        assert_eq!(decode("Zm9vYg=a"), Err("base64 data continues after padding".into()));
+        assert_eq!(decode("Zm9vY"), Err("truncated base64 data".into()));
+        assert_eq!(decode("Zm9vYg==="), Err("truncated base64 data".into()));
+        assert!(decode("Zm9vYmFyé").is_err());
+    }
+
+    #[test]
+    fn test_decode_uri() {
+        let uri = "data:font/woff2;base64,d09GMg==";
+        assert_eq!(decode_uri(uri).unwrap(), b"wOF2");
+        assert_eq!(decode_uri("data:;BASE64,d09GMg").unwrap(), b"wOF2");
+        assert_eq!(decode_uri("data:font/ttf,%00%01ab%7e").unwrap(), b"\x00\x01ab~");
+        assert_eq!(decode_uri("font/ttf,abc"), Err("not a data URI".into()));
+        assert_eq!(decode_uri("data:font/ttf"), Err("data URI without data".into()));
+        assert!(decode_uri("data:font/ttf,%0").is_err());
+        assert!(decode_uri("data:font/ttf,%zz").is_err());
+        assert!(decode_uri("data:font/ttf,%+1").is_err());
+        assert!(decode_uri("data:font/ttf;base64,d09G!").is_err());
+    }
+}
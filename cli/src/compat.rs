@@ -0,0 +1,192 @@
+//! Option values in the syntax of other subsetters, so that this tool can
+//! stand in for them in existing build scripts.
+
+use std::ops::RangeInclusive;
+
+/// Parse a list of codepoints in `pyftsubset` syntax, e.g. `U+0041-005A,61`,
+/// or `*` for all codepoints. Entries are separated by commas or whitespace
+/// and are hexadecimal with an optional `U+` or `0x` prefix.
+pub fn parse_unicodes(arg: &str) -> Result<Vec<RangeInclusive<u32>>, String> {
+    parse_ranges(arg, 0x10FFFF, |s| {
+        let s = ["U+", "u+", "0x", "0X"]
+            .iter()
+            .find_map(|prefix| s.strip_prefix(prefix))
+            .unwrap_or(s);
+        u32::from_str_radix(s, 16).ok()
+    })
+}
+
+/// Parse a list of glyph IDs in `pyftsubset` syntax, e.g. `0-10,12`, or `*`
+/// for all glyph IDs.
+pub fn parse_gids(arg: &str) -> Result<Vec<RangeInclusive<u32>>, String> {
+    parse_ranges(arg, u16::MAX.into(), |s| s.parse().ok())
+}
+
+/// Parse a list of values and inclusive ranges of them.
+fn parse_ranges(
+    arg: &str,
+    max: u32,
+    parse: impl Fn(&str) -> Option<u32>,
+) -> Result<Vec<RangeInclusive<u32>>, String> {
+    arg.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            if entry == "*" {
+                return Ok(0..=max);
+            }
+            let (start, end) = entry.split_once('-').unwrap_or((entry, entry));
+            match (parse(start), parse(end)) {
+                (Some(start), Some(end)) if start <= end && end <= max => Ok(start..=end),
+                _ => Err(format!("invalid range {entry:?}")),
+            }
+        })
+        .collect()
+}
+
+/// hb-subset flags that don't apply to this tool and are ignored, since glyph
+/// IDs are always retained and layout tables are always dropped.
+const HB_IGNORED_FLAGS: &[&str] = &[
+    "--retain-gids",
+    "--desubroutinize",
+    "--notdef-outline",
+    "--no-layout-closure",
+    "--passthrough-tables",
+    "--no-prune-unicode-ranges",
+];
+
+/// hb-subset options with a value that are ignored for the same reasons.
+const HB_IGNORED_OPTIONS: &[&str] = &[
+    "--layout-features",
+    "--layout-features+",
+    "--layout-features-",
+    "--layout-scripts",
+    "--layout-scripts+",
+    "--layout-scripts-",
+    "--name-IDs",
+    "--name-IDs+",
+    "--name-IDs-",
+    "--name-languages",
+    "--name-languages+",
+    "--name-languages-",
+];
+
+/// Translate command line arguments in `hb-subset` syntax, e.g.
+/// `hb-subset --unicodes=* --drop-tables+=GSUB,GPOS -o out.ttf in.ttf`, into
+/// the arguments of this tool.
+///
+/// The values of hb-subset options can be given as `--option=value` or
+/// `--option value`, and the `+=` forms add to the value like in hb-subset.
+/// The optional positional text after the font file selects characters like
+/// `--text`. Other options are passed through and need to be given as
+/// `--option=value`.
+pub fn hb_args(argv: impl IntoIterator<Item = String>) -> Result<Vec<String>, String> {
+    let mut argv = argv.into_iter();
+    let mut args: Vec<String> = argv.next().into_iter().collect();
+    let mut positional = vec![];
+    let mut font_file = None;
+    let (mut unicodes, mut gids, mut text, mut drop_tables) =
+        (vec![], vec![], String::new(), vec![]);
+
+    while let Some(arg) = argv.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            positional.push(arg);
+            continue;
+        }
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let name = match name {
+            "-u" => "--unicodes",
+            "-g" => "--gids",
+            "-t" => "--text",
+            "-o" => "--output-file",
+            name => name,
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| argv.next())
+                .ok_or_else(|| format!("missing value for {name}"))
+        };
+        let read = |path: String| {
+            std::fs::read_to_string(&path).map_err(|err| format!("{path}: {err}"))
+        };
+        match name {
+            "--hb-compat" => {}
+            "--unicodes" | "--unicodes+" => unicodes.push(value()?),
+            "--unicodes-file" => unicodes.push(read(value()?)?),
+            "--gids" | "--gids+" => gids.push(value()?),
+            "--gids-file" => gids.push(read(value()?)?),
+            "--text" | "--text+" => text.push_str(&value()?),
+            "--text-file" => text.push_str(read(value()?)?.trim_end_matches('\n')),
+            "--drop-tables" | "--drop-tables+" => drop_tables.push(value()?),
+            "--font-file" => font_file = Some(value()?),
+            "--output-file" => args.push(format!("--output={}", value()?)),
+            "--unicodes-" | "--gids-" | "--drop-tables-" | "--glyphs"
+            | "--glyphs-file" => {
+                return Err(format!("{name} is not supported"));
+            }
+            name if HB_IGNORED_FLAGS.contains(&name) => {}
+            name if HB_IGNORED_OPTIONS.contains(&name) => {
+                value()?;
+            }
+            _ => args.push(arg.clone()),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    args.extend(font_file.or_else(|| positional.next()));
+    text.extend(positional);
+    if !unicodes.is_empty() {
+        args.push(format!("--unicodes={}", unicodes.join(",")));
+    }
+    if !gids.is_empty() {
+        args.push(format!("--gids={}", gids.join(",")));
+    }
+    if !text.is_empty() {
+        args.push(format!("--text={text}"));
+    }
+    for tables in drop_tables {
+        let tags = tables.split(|c: char| c == ',' || c.is_whitespace());
+        let tags = tags.filter(|tag| !tag.is_empty());
+        args.extend(tags.map(|tag| format!("--drop-tables={tag}")));
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_unicodes() {
+        assert_eq!(
+            parse_unicodes("U+0041-005A, 61 0x62,u+63").unwrap(),
+            [0x41..=0x5A, 0x61..=0x61, 0x62..=0x62, 0x63..=0x63]
+        );
+        assert_eq!(parse_unicodes("*").unwrap(), [0..=0x10FFFF]);
+        assert_eq!(parse_unicodes(" ,").unwrap(), []);
+
+        // Overlapping ranges are kept as given and surrogates are left to the
+        // caller, while reversed and out-of-range ones are rejected.
+        assert_eq!(parse_unicodes("41-5A,50-60").unwrap(), [0x41..=0x5A, 0x50..=0x60]);
+        assert_eq!(parse_unicodes("D800").unwrap(), [0xD800..=0xD800]);
+        assert_eq!(parse_unicodes("5A-41"), Err("invalid range \"5A-41\"".into()));
+        assert!(parse_unicodes("10FFFF").is_ok());
+        assert!(parse_unicodes("110000").is_err());
+        assert!(parse_unicodes("10FFFF-110000").is_err());
+        assert!(parse_unicodes("U+41-").is_err());
+        assert!(parse_unicodes("G").is_err());
+        assert!(parse_unicodes("41-50-60").is_err());
+    }
+
+    #[test]
+    fn test_parse_gids() {
+        assert_eq!(parse_gids("0-10,12").unwrap(), [0..=10, 12..=12]);
+        assert_eq!(parse_gids("*").unwrap(), [0..=65535]);
+        assert!(parse_gids("65536").is_err());
+        assert!(parse_gids("10-0").is_err());
+        assert!(parse_gids("0x10").is_err());
+    }
+}
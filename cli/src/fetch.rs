@@ -0,0 +1,42 @@
+//! Fetching fonts from `http://` and `https://` URLs, with the `http` feature.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Limits on fetching a font, so that a slow or huge response can't hold up
+/// or exhaust the process.
+#[derive(Debug, Copy, Clone)]
+pub struct Limits {
+    /// The maximum size of the response body in bytes.
+    pub max_size: u64,
+    /// The time the whole request may take, from resolving the host to
+    /// reading the last byte.
+    pub timeout: Duration,
+}
+
+/// Fetch the font at the URL. Responses are kept per URL, since the input is
+/// read again for each format and face.
+pub fn fetch(url: &str, limits: Limits) -> Result<Arc<Vec<u8>>, String> {
+    static FETCHED: OnceLock<Mutex<HashMap<String, Arc<Vec<u8>>>>> = OnceLock::new();
+    let fetched = FETCHED.get_or_init(Mutex::default);
+    if let Some(data) = fetched.lock().unwrap().get(url) {
+        return Ok(data.clone());
+    }
+
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(limits.timeout))
+        .build()
+        .into();
+    let mut response = agent.get(url).call().map_err(|err| err.to_string())?;
+    let data = response
+        .body_mut()
+        .with_config()
+        .limit(limits.max_size)
+        .read_to_vec()
+        .map_err(|err| err.to_string())?;
+
+    let data = Arc::new(data);
+    fetched.lock().unwrap().insert(url.into(), data.clone());
+    Ok(data)
+}
@@ -0,0 +1,1737 @@
+mod analyze;
+mod base64;
+mod compat;
+#[cfg(feature = "http")]
+mod fetch;
+mod options;
+mod pipe;
+mod preset;
+mod text;
+
+use std::{
+    collections::HashSet,
+    io::{IsTerminal as _, Write as _},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use clap::{CommandFactory, Parser};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use subsetter::{
+    build_collection, closure_by_composite, closure_by_feature, face_count,
+    isolate_glyph, layout_features, layout_scripts, named_instances, obfuscation_map,
+    replace_faces, ttf_to_woff, ttf_to_woff2_with_blocks, variation_axes, woff2_blocks,
+    woff2_to_ttf, woff_blocks, woff_to_ttf, Instancer, Integrity, LookupPolicy,
+    MetricsOverride, Obfuscation, Padding, Profile, PuaKey, ReverseCmap, RustBrotli,
+    Severity, Tag, Warning, WoffBlocks,
+};
+use ttf_parser::Face;
+
+/// Simple program to greet a person
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// The font file to subset, a `data:` URI with the font, e.g.
+    /// "data:font/woff2;base64,d09GMg...", or an "http://" or "https://" URL
+    /// to fetch it from if built with the `http` feature
+    #[arg(required_unless_present_any = [
+        "batch_stdin", "pipe", "options_json", "completions", "input_base64"
+    ])]
+    input: Option<PathBuf>,
+    /// Read the font to subset as base64 or as a `data:` URI from the given
+    /// file, or from stdin with "-"
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["input", "batch_stdin", "pipe"])]
+    input_base64: Option<PathBuf>,
+    /// The maximum size in bytes of a font fetched from a URL
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "BYTES", default_value = "67108864")]
+    fetch_max_size: u64,
+    /// The time in seconds that fetching a font from a URL may take
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "SECONDS", default_value = "30")]
+    fetch_timeout: u64,
+    /// The output file to write the subsetted font to, or "-" for stdout. Can
+    /// be given multiple times to write several formats at once. If not
+    /// specified, the subsetted font is written to stdout
+    #[arg(short, long, alias = "output-file")]
+    output: Vec<PathBuf>,
+    /// The output format, either "woff2", "woff" or "ttf". If not specified,
+    /// the output format is inferred from each output file's extension, and
+    /// is TTF for other extensions, files without one and stdout. WOFF2 and
+    /// WOFF outputs keep the metadata and private data of WOFF2 and WOFF
+    /// inputs
+    #[arg(short, long, alias = "flavor", value_parser = ["woff2", "woff", "ttf"])]
+    format: Option<String>,
+    /// The face to subset from a font collection (`.ttc` or `.otc` file),
+    /// 0 by default. Several faces, e.g. "0,2", are subsetted one by one and
+    /// written as a new TTF collection that stores identical tables only once
+    #[arg(long, value_name = "INDEX", value_delimiter = ',')]
+    face_index: Vec<u32>,
+    /// Write the whole input collection back out with the faces of
+    /// --face-index subsetted and the other faces unmodified. Tables that the
+    /// unmodified faces share stay shared
+    #[arg(long, requires = "face_index", default_value = "false")]
+    keep_other_faces: bool,
+
+    // The selection options below can be combined and repeated, and the
+    // subset keeps the union of what they select.
+    /// The glyphs to subset
+    #[arg(short, long, value_delimiter = ',', num_args = 1..)]
+    glyphs: Vec<u32>,
+    /// The characters to subset, as a literal list. Only the glyphs of exactly
+    /// these characters are kept
+    #[arg(short, long)]
+    chars: Vec<String>,
+    /// The text to subset for, e.g. the content of a page. Unlike --chars,
+    /// this also keeps the space, the composed and decomposed forms of
+    /// accented letters and the glyphs that the ccmp, liga, clig and rlig
+    /// features produce
+    #[arg(short, long)]
+    text: Vec<String>,
+    /// Read the text to subset for from a file, like --text, or from all
+    /// files in a directory
+    #[arg(long, value_name = "PATH")]
+    text_file: Vec<PathBuf>,
+    /// The codepoints to subset in pyftsubset syntax, as hexadecimal values
+    /// and ranges with an optional "U+" or "0x" prefix, e.g.
+    /// "U+0000-00FF,U+2013,U+20AC", or "*" for all characters of the font
+    #[arg(long, value_name = "RANGES", value_parser = parse_unicodes)]
+    unicodes: Vec<Unicodes>,
+    /// The glyph IDs to subset in pyftsubset syntax, e.g. "0-10,12"
+    #[arg(long, value_parser = parse_gids)]
+    gids: Vec<Gids>,
+    /// Subset to named character sets like those of Google Fonts, e.g.
+    /// "latin,latin-ext". Available are ascii, latin, latin-ext, greek,
+    /// cyrillic and vietnamese
+    #[arg(long, value_name = "NAMES", value_delimiter = ',')]
+    preset: Vec<String>,
+    /// Keep the glyphs that the given GSUB features produce from the requested
+    /// glyphs, e.g. "liga,calt", or "*" for all features of the font. Layout
+    /// tables are still dropped unless --keep-gsub is given, so this is for
+    /// text shaped with the input font
+    #[arg(long, value_name = "FEATURES")]
+    layout_features: Option<String>,
+    /// Close over the given features only for rules whose input glyphs are
+    /// all requested, e.g. "calt", for fonts whose contextual lookups pull
+    /// in too many glyphs
+    #[arg(long, value_name = "FEATURES")]
+    strict_features: Option<String>,
+    /// Pin the axes of a variable font to a static instance before
+    /// subsetting, e.g. "wght=700,wdth=100", or limit them to a range to keep
+    /// the font variable, e.g. "wght=400:700"
+    #[arg(long, value_name = "AXES")]
+    instance: Option<String>,
+    /// Print the glyphs that each GSUB feature adds to the requested glyphs,
+    /// e.g. "liga: f_f, f_i", to find features worth leaving out, and the
+    /// components that composite glyphs add, e.g. "eacute: e, acute"
+    #[arg(long, default_value = "false")]
+    closure_report: bool,
+    /// Print the GSUB and GPOS features with the number and approximate size
+    /// of their lookups, e.g. "GPOS kern: 2 lookups, 5120 bytes", instead of
+    /// subsetting
+    #[arg(long, default_value = "false")]
+    list_features: bool,
+    /// Print the faces of a font collection with their full names, e.g. "1:
+    /// Noto Serif Bold", instead of subsetting
+    #[arg(long, default_value = "false")]
+    list_faces: bool,
+    /// Print the features that each script and language system of GSUB and
+    /// GPOS enables, e.g. "GSUB latn/ROM: ccmp, liga", instead of subsetting
+    #[arg(long, default_value = "false")]
+    list_scripts: bool,
+    /// Print the variation axes with their range and default, e.g. "wght
+    /// 100..900, default 400 (Weight)", instead of subsetting
+    #[arg(long, default_value = "false")]
+    list_axes: bool,
+    /// Print the named instances of a variable font with their coordinates in
+    /// the syntax of `--instance`, e.g. "Bold: wght=700", instead of
+    /// subsetting
+    #[arg(long, default_value = "false")]
+    list_instances: bool,
+    /// Print a JSON description of all options with their types, defaults,
+    /// possible values and conflicts instead of subsetting, for tools that
+    /// wrap this program
+    #[arg(long, default_value = "false")]
+    options_json: bool,
+    /// Print a completion script for the given shell instead of subsetting,
+    /// e.g. "bash" or "zsh"
+    #[arg(long, value_name = "SHELL")]
+    completions: Option<clap_complete::Shell>,
+    /// Print a JSON report of how a text corpus given with --text or
+    /// --text-file uses the font instead of subsetting: the frequencies of the
+    /// characters, a breakdown by script with the characters the font lacks,
+    /// the number of retained glyphs and the subset's size in each output
+    /// format, or as TTF and WOFF2 without outputs
+    #[arg(long, conflicts_with_all = ["batch_stdin", "pipe"], default_value = "false")]
+    analyze: bool,
+    /// Whether to map the glyphs to PUA codepoints
+    #[arg(long, default_value = "false")]
+    glyphs_to_pua: bool,
+    /// How to assign the PUA codepoints: "glyph-id" maps glyph `x` to
+    /// U+F0000 + x (the default), while "glyph-name" and "codepoint" derive
+    /// them from the glyph name or the glyph's character in the input, so that
+    /// they stay the same when glyphs are added to the font
+    #[arg(long, value_name = "KEY", requires = "glyphs_to_pua")]
+    pua_key: Option<PuaKeyArg>,
+    /// Whether to subset all glyphs, in this case this tool acts as a simple
+    /// format converter
+    #[arg(long, short, default_value = "false")]
+    all: bool,
+    /// Whether to record the subsetter version and a hash of the options in a
+    /// private table of the output
+    #[arg(long, default_value = "false")]
+    version_stamp: bool,
+    /// Override the advance width of a glyph, e.g. "space=500". The glyph can
+    /// be given as a glyph ID, a glyph name or a single character
+    #[arg(long, value_name = "GLYPH=ADVANCE")]
+    set_advance: Vec<String>,
+    /// Override the left side bearing of a glyph, e.g. "A=20". The glyph can
+    /// be given as a glyph ID, a glyph name or a single character
+    #[arg(long, value_name = "GLYPH=LSB")]
+    set_lsb: Vec<String>,
+    /// Make sure that U+0020 is mapped, creating an empty space glyph with the
+    /// given advance (or the font's average advance) if the font lacks one
+    #[arg(long, value_name = "ADVANCE", num_args = 0..=1)]
+    ensure_space: Option<Option<u16>>,
+    /// Keep only cmap, head, hhea, hmtx, maxp and the outlines, dropping all
+    /// other tables and TrueType instructions
+    #[arg(long, default_value = "false")]
+    minimal: bool,
+    /// Whether to also keep OS/2 and reduced name and post tables in minimal
+    /// fonts, as expected by browsers and operating systems
+    #[arg(long, requires = "minimal", default_value = "false")]
+    minimal_keep_names: bool,
+    /// Drop the description, vendor and designer URL, license and sample text
+    /// records of the name table
+    #[arg(long, default_value = "false")]
+    prune_names: bool,
+    /// Keep only the name records with the given language IDs, e.g.
+    /// "0x409,0" for US English on Windows and English on Macintosh
+    #[arg(long, value_name = "IDS", value_delimiter = ',')]
+    name_languages: Vec<String>,
+    /// Append a suffix to the family names, e.g. "Subset", so that the subset
+    /// doesn't clash with the full font when both are installed
+    #[arg(long, value_name = "SUFFIX")]
+    family_suffix: Option<String>,
+    /// Drop the glyph names by rewriting the post table to version 3, which
+    /// saves an index entry per glyph of the font
+    #[arg(long, default_value = "false")]
+    strip_glyph_names: bool,
+    /// Keep the GSUB table subsetted to the retained glyphs, and GDEF. Combine
+    /// with --layout-features to keep the glyphs the features produce
+    #[arg(long, default_value = "false")]
+    keep_gsub: bool,
+    /// Keep the GPOS table subsetted to the retained glyphs, and GDEF, so that
+    /// kerning and mark positioning keep working
+    #[arg(long, default_value = "false")]
+    keep_gpos: bool,
+    /// What to do with GSUB and GPOS lookups that can't be subsetted: "fail"
+    /// (the default), "keep-table" to copy the whole table verbatim or
+    /// "drop-lookup"
+    #[arg(long, value_name = "POLICY")]
+    unsupported_lookups: Option<LookupPolicyArg>,
+    /// Keep the Apple Advanced Typography tables like morx verbatim. This
+    /// keeps all glyphs, since they may be substituted by these tables
+    #[arg(long, default_value = "false")]
+    keep_aat: bool,
+    /// Convert the default ligature and noncontextual substitutions of a morx
+    /// table to a GSUB table if the font has none
+    #[arg(long, default_value = "false")]
+    convert_morx: bool,
+    /// Keep the Graphite tables verbatim. This keeps all glyphs, since they
+    /// may be substituted by these tables
+    #[arg(long, default_value = "false")]
+    keep_graphite: bool,
+    /// Keep the variation tables of variable fonts, subsetting gvar, HVAR and
+    /// VVAR to the retained glyphs, so that the output stays variable
+    #[arg(long, default_value = "false")]
+    keep_variations: bool,
+    /// Keep the COLR color glyphs and their CPAL palettes as well as the SVG
+    /// glyphs, subsetted to the retained glyphs along with the glyphs their
+    /// layers are filled with
+    #[arg(long, default_value = "false")]
+    keep_color: bool,
+    /// Keep the embedded bitmaps of the CBDT, EBDT and sbix tables, subsetted
+    /// to the retained glyphs
+    #[arg(long, default_value = "false")]
+    keep_bitmaps: bool,
+    /// Remove TrueType hinting from outputs in the given formats, e.g. "woff2"
+    /// to strip it from web fonts while keeping it in TTF outputs
+    #[arg(
+        long,
+        value_name = "FORMATS",
+        value_delimiter = ',',
+        value_parser = ["woff2", "woff", "ttf"]
+    )]
+    strip_hinting: Vec<String>,
+    /// Remove TrueType hinting from all outputs: the fpgm, prep, cvt and gasp
+    /// tables, the glyph instructions and the instruction limits in maxp
+    #[arg(long, conflicts_with = "strip_hinting", default_value = "false")]
+    no_hinting: bool,
+    /// Keep the vertical metrics for vertical layout, synthesizing them with
+    /// the given advance height (or the typographic ascender minus descender)
+    /// if the font lacks them. Also keeps the glyphs of the `vert` feature
+    #[arg(long, value_name = "ADVANCE", num_args = 0..=1)]
+    synthesize_vertical: Option<Option<u16>>,
+    /// Don't keep the glyphs that the font's `ccmp` feature produces from the
+    /// requested characters, like precomposed accented letters. They are kept
+    /// by default when subsetting to characters
+    #[arg(long, default_value = "false")]
+    no_ccmp_closure: bool,
+    /// Fail on malformed font data, like overlapping cmap groups, instead of
+    /// repairing it
+    #[arg(long, default_value = "false")]
+    strict: bool,
+    /// Skip requested glyph IDs that the font doesn't have with a warning,
+    /// instead of failing
+    #[arg(long, default_value = "false")]
+    lenient: bool,
+    /// Renumber the retained glyphs to consecutive IDs and remove the others
+    /// entirely, instead of preserving the glyph IDs of the input
+    #[arg(long, default_value = "false")]
+    remap_glyphs: bool,
+    /// With --remap-glyphs, give the listed glyph IDs the first new IDs in
+    /// this order, in pyftsubset syntax, e.g. "70,68-69"
+    #[arg(long, value_name = "GIDS", value_parser = parse_gids)]
+    glyph_order: Option<Gids>,
+    /// How to pad tables: "aligned" to four bytes (the default), "minimal"
+    /// for no padding or "source" to match the input font
+    #[arg(long)]
+    padding: Option<PaddingArg>,
+    /// Write a JSON listing of each output table's byte range and whether it
+    /// was copied, rewritten or synthesized to the given file
+    #[arg(long, value_name = "FILE")]
+    provenance: Option<PathBuf>,
+    /// Fail if the outline of a retained glyph takes more than the given
+    /// number of bytes in the output, listing the offending glyphs
+    #[arg(long, value_name = "BYTES")]
+    max_glyph_bytes: Option<usize>,
+    /// Fail if an output isn't at least the given percentage smaller than the
+    /// input file
+    #[arg(long, value_name = "PERCENT")]
+    min_reduction: Option<f64>,
+    /// Fail if an output in the same format as the given previous output is
+    /// larger than it, by more than `--max-growth` percent if given
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+    /// How many percent larger than the `--baseline` an output may be
+    #[arg(long, value_name = "PERCENT", requires = "baseline")]
+    max_growth: Option<f64>,
+    /// Write a JSON object mapping each retained glyph to the characters of
+    /// the source font that map to it, to find out why a glyph was kept.
+    /// Glyphs only kept as components of other glyphs have no characters
+    #[arg(long, value_name = "FILE")]
+    glyph_report: Option<PathBuf>,
+    /// Write the SHA-256 hash of each output, excluding the checksum
+    /// adjustment in its head table, to the given file as a JSON object keyed
+    /// by the output paths, with "-" for stdout
+    #[arg(long, value_name = "FILE")]
+    integrity: Option<PathBuf>,
+    /// Drop the given tables, e.g. "name,post", even if they would be kept
+    #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+    drop_tables: Vec<String>,
+    /// Copy the given private tables unmodified, e.g. "MYTB", and list them
+    /// in the report
+    #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+    passthrough: Vec<String>,
+    /// Accept hb-subset's command line syntax, e.g. "--unicodes=*",
+    /// "--gids-file=FILE" and "--drop-tables+=GSUB,GPOS". See
+    /// `compat::hb_args` for the details
+    #[arg(long, default_value = "false")]
+    hb_compat: bool,
+    /// Subset and convert the font without writing any files, and print a JSON
+    /// report of the output sizes, the retained glyphs with their characters
+    /// and the warnings instead
+    #[arg(long, conflicts_with_all = ["batch_stdin", "pipe"], default_value = "false")]
+    dry_run: bool,
+    /// Produce a font with only the given glyph and print a trace of every
+    /// decision affecting it, for debugging. The glyph can be given as a glyph
+    /// ID, a glyph name or a single character. Other subsetting options are
+    /// ignored
+    #[arg(long, value_name = "GLYPH", conflicts_with_all = ["glyphs", "chars", "text", "all"])]
+    isolate_glyph: Option<String>,
+    /// Compress WOFF2 output with the given number of threads, trading some
+    /// size for speed. With `--batch-stdin`, this is the default for jobs that
+    /// don't set it, so that a single large job doesn't hold up the end of a
+    /// batch
+    #[arg(long, value_name = "THREADS")]
+    brotli_threads: Option<usize>,
+    /// The Brotli quality of WOFF2 outputs, from 0 (fastest) to 11 (smallest)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value = "11",
+        value_parser = clap::value_parser!(u8).range(0..=11)
+    )]
+    woff2_quality: u8,
+    /// Attach the XML file as extended metadata, like license or vendor
+    /// information, to WOFF2 and WOFF outputs, replacing the metadata of the
+    /// input
+    #[arg(long, value_name = "PATH")]
+    woff_metadata: Option<PathBuf>,
+    /// Move all mapped characters to random Private Use Area codepoints,
+    /// using the given seed
+    #[arg(long, value_name = "SEED")]
+    obfuscate: Option<u64>,
+    /// Whether to also remove the naming table and glyph names when
+    /// obfuscating
+    #[arg(long, requires = "obfuscate", default_value = "false")]
+    obfuscate_strip_names: bool,
+    /// Write the obfuscation permutation to the given file as a JSON object
+    /// mapping original to obfuscated characters
+    #[arg(long, value_name = "FILE", requires = "obfuscate")]
+    obfuscation_map: Option<PathBuf>,
+    /// Read jobs from stdin, one JSON object per line, and write a JSON result
+    /// line per job to stdout. See `Job` for the format of a job. Other
+    /// arguments are ignored
+    #[arg(long, conflicts_with = "input", default_value = "false")]
+    batch_stdin: bool,
+    /// The number of batch jobs to process in parallel
+    #[arg(long, value_name = "N", requires = "batch_stdin", default_value = "1")]
+    jobs: usize,
+    /// Write the result lines of the failed batch jobs to the given file as a
+    /// JSON array, each with the `line` of the job on stdin. A batch with
+    /// failures still processes all jobs, but exits with a nonzero status
+    #[arg(long, value_name = "FILE", requires = "batch_stdin")]
+    failures: Option<PathBuf>,
+    /// Serve subsetting requests in length-prefixed binary frames on stdin and
+    /// stdout until stdin is closed. See the `pipe` module for the protocol.
+    /// Other arguments are ignored
+    #[arg(long, conflicts_with_all = ["input", "batch_stdin"], default_value = "false")]
+    pipe: bool,
+}
+
+/// The values of `--pua-key`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum PuaKeyArg {
+    GlyphId,
+    GlyphName,
+    Codepoint,
+}
+
+/// The values of `--unsupported-lookups`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum LookupPolicyArg {
+    Fail,
+    KeepTable,
+    DropLookup,
+}
+
+/// The values of `--padding`.
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+enum PaddingArg {
+    Aligned,
+    Minimal,
+    Source,
+}
+
+/// A job read by `--batch-stdin`, e.g.
+/// `{"id": 1, "input": "a.ttf", "output": "a.woff2", "chars": "abc",
+/// "options": {"minimal": true, "padding": "minimal"}}`.
+///
+/// The options are the long command line flags without the leading dashes.
+/// Boolean options are set when true, array values are joined with commas.
+/// Unless set, `brotli_threads` defaults to the value for the whole batch.
+#[derive(Deserialize)]
+struct Job {
+    /// An arbitrary value echoed in the result line.
+    id: Option<Value>,
+    input: PathBuf,
+    output: PathBuf,
+    format: Option<String>,
+    chars: Option<String>,
+    text: Option<String>,
+    glyphs: Option<Vec<u32>>,
+    #[serde(default)]
+    all: bool,
+    #[serde(default)]
+    options: Map<String, Value>,
+}
+
+impl Job {
+    /// Convert the job into command line arguments.
+    fn args(&self, brotli_threads: Option<usize>) -> Result<Args, String> {
+        let mut argv = vec!["subsetter".into(), self.input.display().to_string()];
+        argv.extend(["--output".into(), self.output.display().to_string()]);
+        if let Some(format) = &self.format {
+            argv.extend(["--format".into(), format.clone()]);
+        }
+        if let Some(chars) = &self.chars {
+            argv.push(format!("--chars={chars}"));
+        }
+        if let Some(text) = &self.text {
+            argv.push(format!("--text={text}"));
+        }
+        if let Some(glyphs) = &self.glyphs {
+            let glyphs: Vec<_> = glyphs.iter().map(u32::to_string).collect();
+            argv.push(format!("--glyphs={}", glyphs.join(",")));
+        }
+        if self.all {
+            argv.push("--all".into());
+        }
+        for (key, value) in &self.options {
+            let flag = format!("--{}", key.replace('_', "-"));
+            match value {
+                Value::Bool(true) => argv.push(flag),
+                Value::Bool(false) | Value::Null => {}
+                Value::Array(items) => {
+                    let items: Vec<_> = items.iter().map(plain).collect();
+                    argv.push(format!("{flag}={}", items.join(",")));
+                }
+                value => argv.push(format!("{flag}={}", plain(value))),
+            }
+        }
+        let has_threads = self
+            .options
+            .keys()
+            .any(|key| key.replace('-', "_") == "brotli_threads");
+        if let (Some(threads), false) = (brotli_threads, has_threads) {
+            argv.push(format!("--brotli-threads={threads}"));
+        }
+        let args = Args::try_parse_from(argv).map_err(|err| err.to_string())?;
+        if args.batch_stdin {
+            return Err("batch jobs cannot start batches".into());
+        }
+        Ok(args)
+    }
+}
+
+/// Format a JSON value as a command line argument.
+fn plain(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Resolve a glyph given as a glyph ID, a glyph name or a single character.
+fn resolve_glyph(face: &Face, glyph: &str) -> Option<u16> {
+    if let Ok(id) = glyph.parse::<u16>() {
+        return Some(id);
+    }
+    if let Some(id) = face.glyph_index_by_name(glyph) {
+        return Some(id.0);
+    }
+    let mut chars = glyph.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => face.glyph_index(c).map(|id| id.0),
+        _ => None,
+    }
+}
+
+/// The codepoint ranges of a `--unicodes` value.
+#[derive(Debug, Clone)]
+struct Unicodes(Vec<RangeInclusive<u32>>);
+
+/// Parse and validate a `--unicodes` value. It must list at least one
+/// codepoint, and single codepoints must be characters, not surrogates.
+/// Surrogates within ranges are skipped.
+fn parse_unicodes(arg: &str) -> Result<Unicodes, String> {
+    let ranges = compat::parse_unicodes(arg)?;
+    if ranges.is_empty() {
+        return Err("no codepoints given".into());
+    }
+    for range in &ranges {
+        let start = *range.start();
+        if start == *range.end() && char::from_u32(start).is_none() {
+            return Err(format!("U+{start:04X} is a surrogate, not a character"));
+        }
+    }
+    Ok(Unicodes(ranges))
+}
+
+/// The glyph ID ranges of a `--gids` or `--glyph-order` value.
+#[derive(Debug, Clone)]
+struct Gids(Vec<RangeInclusive<u32>>);
+
+/// Parse a `--gids` or `--glyph-order` value.
+fn parse_gids(arg: &str) -> Result<Gids, String> {
+    compat::parse_gids(arg).map(Gids)
+}
+
+/// Parse a metric override of the form `GLYPH=VALUE`.
+fn parse_override<T: std::str::FromStr>(face: &Face, arg: &str) -> (u16, T) {
+    let (glyph, value) = arg.rsplit_once('=').expect("expected GLYPH=VALUE");
+    let glyph = resolve_glyph(face, glyph).expect("could not find glyph");
+    let value = value.parse().ok().expect("invalid metric value");
+    (glyph, value)
+}
+
+/// Name sorted glyphs, joining runs of at least three consecutive glyphs into
+/// ranges like "a.sc..z.sc".
+fn glyph_ranges(face: &Face, glyphs: &[u16]) -> String {
+    let name = |glyph: u16| match face.glyph_name(ttf_parser::GlyphId(glyph)) {
+        Some(name) => name.to_string(),
+        None => format!("gid{glyph}"),
+    };
+    let mut runs: Vec<(u16, u16)> = vec![];
+    for &glyph in glyphs {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == glyph => *end = glyph,
+            _ => runs.push((glyph, glyph)),
+        }
+    }
+    let mut parts = vec![];
+    for (start, end) in runs {
+        match end - start {
+            0 => parts.push(name(start)),
+            1 => parts.extend([name(start), name(end)]),
+            _ => parts.push(format!("{}..{}", name(start), name(end))),
+        }
+    }
+    parts.join(", ")
+}
+
+/// Convert a font to WOFF2 with the given blocks, compressing with the quality
+/// and number of threads of the arguments.
+fn to_woff2(data: &[u8], args: &Args, blocks: &WoffBlocks) -> Vec<u8> {
+    let compressor = RustBrotli {
+        quality: args.woff2_quality,
+        threads: args.brotli_threads.unwrap_or(1),
+    };
+    ttf_to_woff2_with_blocks(data, &compressor, blocks)
+        .expect("could not convert TTF to WOFF2")
+}
+
+/// What a subsetting run found besides the font, for `--dry-run`.
+#[derive(Default)]
+struct Report {
+    /// The retained glyphs with the characters that map to them. Only
+    /// collected for `--glyph-report` and `--dry-run`.
+    glyphs: Vec<(u16, Vec<char>)>,
+    /// The selection options with the number of glyphs that each selects and
+    /// how many of those no other option selects.
+    sources: Vec<(&'static str, usize, usize)>,
+    warnings: Vec<Warning>,
+}
+
+impl Report {
+    /// Add the findings of a run for another output format.
+    fn merge(&mut self, other: Report) {
+        if self.glyphs.is_empty() {
+            self.glyphs = other.glyphs;
+        }
+        if self.sources.is_empty() {
+            self.sources = other.sources;
+        }
+        for warning in other.warnings {
+            if !self.warnings.contains(&warning) {
+                self.warnings.push(warning);
+            }
+        }
+    }
+
+    /// The retained glyphs as a JSON object that maps each glyph to its
+    /// characters.
+    fn glyphs_json(&self) -> Value {
+        let glyphs = self.glyphs.iter().map(|(glyph, chars)| {
+            let chars = chars.iter().map(|c| c.to_string().into()).collect();
+            (glyph.to_string(), Value::Array(chars))
+        });
+        Value::Object(glyphs.collect())
+    }
+
+    /// Format the report as JSON, together with the sizes of the outputs.
+    fn to_json(&self, args: &Args, input_size: usize, results: &[Vec<u8>]) -> Value {
+        let outputs: Vec<_> = results
+            .iter()
+            .enumerate()
+            .map(|(i, result)| {
+                let output = args.output.get(i).map(PathBuf::as_path);
+                json!({
+                    "path": output,
+                    "format": output_format(args, output),
+                    "size": result.len(),
+                    "percent": 100 * result.len() / input_size,
+                })
+            })
+            .collect();
+        let sources: Vec<_> = self
+            .sources
+            .iter()
+            .map(|&(source, glyphs, only)| {
+                json!({ "source": source, "glyphs": glyphs, "only": only })
+            })
+            .collect();
+        let warnings: Vec<_> = self
+            .warnings
+            .iter()
+            .map(|warning| {
+                let severity = match warning.severity() {
+                    Severity::Warning => "warning",
+                    Severity::Info => "info",
+                };
+                json!({ "severity": severity, "message": warning.to_string() })
+            })
+            .collect();
+        json!({
+            "input": args.input,
+            "input_size": input_size,
+            "outputs": outputs,
+            "glyphs": self.glyphs_json(),
+            "sources": sources,
+            "warnings": warnings,
+        })
+    }
+}
+
+/// Collect the glyphs that each given selection option selects, by the name
+/// of the option.
+fn select(args: &Args, face: &Face) -> Vec<(&'static str, HashSet<u16>)> {
+    let mut sources = vec![];
+    let chars = |chars: &mut dyn Iterator<Item = char>, warn: bool| {
+        let mut glyphs = HashSet::new();
+        for ch in chars {
+            match face.glyph_index(ch) {
+                Some(g) => {
+                    glyphs.insert(g.0);
+                }
+                None if warn => eprintln!("warning: the font has no glyph for {ch:?}"),
+                None => {}
+            }
+        }
+        glyphs
+    };
+    let text = |text: &str| {
+        let mut glyphs = chars(&mut text::content(text).into_iter(), true);
+        glyphs.extend(chars(&mut text::implied(text).into_iter(), false));
+        glyphs
+    };
+    let unicodes = |unicodes: &Unicodes| {
+        let mut glyphs = HashSet::new();
+        for range in unicodes.0.iter().cloned() {
+            let single = range.start() == range.end();
+            glyphs.extend(chars(&mut range.filter_map(char::from_u32), single));
+        }
+        glyphs
+    };
+
+    // Glyph IDs that the font doesn't have. Ranges of IDs are cut off at the
+    // end of the font instead.
+    let num_glyphs = u32::from(face.number_of_glyphs());
+    let mut missing = vec![];
+    if !args.glyphs.is_empty() {
+        missing.extend(args.glyphs.iter().filter(|&&g| g >= num_glyphs));
+        let glyphs = args.glyphs.iter().filter(|&&g| g < num_glyphs);
+        sources.push(("glyphs", glyphs.map(|&g| g as u16).collect()));
+    }
+    if !args.gids.is_empty() {
+        let mut glyphs = HashSet::new();
+        for gids in &args.gids {
+            for range in gids.0.iter().cloned() {
+                if range.start() == range.end() && *range.start() >= num_glyphs {
+                    missing.push(*range.start());
+                }
+                glyphs.extend(range.filter(|&g| g < num_glyphs).map(|g| g as u16));
+            }
+        }
+        sources.push(("gids", glyphs));
+    }
+    if !missing.is_empty() {
+        missing.sort_unstable();
+        missing.dedup();
+        let list: Vec<_> = missing.iter().map(u32::to_string).collect();
+        let message = format!(
+            "the font has {num_glyphs} glyphs, but glyph IDs {} were requested",
+            list.join(", ")
+        );
+        match args.lenient {
+            true => eprintln!("warning: {message}, skipping them"),
+            false => fail(message),
+        }
+    }
+    if !args.chars.is_empty() {
+        let glyphs = chars(&mut args.chars.iter().flat_map(|c| c.chars()), true);
+        sources.push(("chars", glyphs));
+    }
+    if !args.text.is_empty() {
+        sources.push(("text", args.text.iter().flat_map(|t| text(t)).collect()));
+    }
+    if !args.text_file.is_empty() {
+        let glyphs = args
+            .text_file
+            .iter()
+            .flat_map(|path| text(&text::read(path).expect("could not read text file")));
+        sources.push(("text-file", glyphs.collect()));
+    }
+    if !args.unicodes.is_empty() {
+        let glyphs = args.unicodes.iter().flat_map(unicodes);
+        sources.push(("unicodes", glyphs.collect()));
+    }
+    if !args.preset.is_empty() {
+        let glyphs = args.preset.iter().flat_map(|name| {
+            let preset = preset::unicodes(name).expect("invalid preset");
+            let ranges = compat::parse_unicodes(preset).expect("invalid unicodes");
+            let mut all = ranges.into_iter().flatten().filter_map(char::from_u32);
+            chars(&mut all, false)
+        });
+        sources.push(("preset", glyphs.collect()));
+    }
+    if args.all {
+        sources.push(("all", (0..face.number_of_glyphs()).collect()));
+    }
+    sources
+}
+
+/// The ligature features that shapers apply by default, whose glyphs `--text`
+/// keeps.
+const TEXT_FEATURES: [[u8; 4]; 3] = [*b"liga", *b"clig", *b"rlig"];
+
+/// Subset the font according to the arguments.
+fn subset(
+    args: &Args,
+    font_data: &[u8],
+    index: u32,
+    face: &Face,
+    strip_hinting: bool,
+) -> (Vec<u8>, Report) {
+    let sources = select(args, face);
+    let glyphs: HashSet<u16> = sources
+        .iter()
+        .flat_map(|(_, glyphs)| glyphs.iter().copied())
+        .collect();
+    let glyphs = glyphs.into_iter().collect::<Vec<_>>();
+    let profile =
+        if args.glyphs_to_pua { Profile::web(&glyphs) } else { Profile::pdf(&glyphs) };
+    let mut profile = profile.version_stamp(args.version_stamp);
+    for arg in &args.set_advance {
+        let (glyph, advance) = parse_override(face, arg);
+        let metrics = MetricsOverride { advance: Some(advance), lsb: None };
+        profile = profile.override_metrics(glyph, metrics);
+    }
+    if let Some(advance) = args.ensure_space {
+        profile = profile.ensure_space(advance);
+    }
+    for arg in &args.set_lsb {
+        let (glyph, lsb) = parse_override(face, arg);
+        let metrics = MetricsOverride { advance: None, lsb: Some(lsb) };
+        profile = profile.override_metrics(glyph, metrics);
+    }
+    if args.minimal {
+        profile = profile.minimal(args.minimal_keep_names);
+    }
+    let languages: Vec<u16> = args
+        .name_languages
+        .iter()
+        .map(|id| match id.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => id.parse(),
+        })
+        .collect::<Result<_, _>>()
+        .expect("invalid language ID");
+    profile = profile.prune_names(args.prune_names).name_languages(&languages);
+    if let Some(suffix) = &args.family_suffix {
+        profile = profile.family_suffix(suffix);
+    }
+    profile = profile.strip_glyph_names(args.strip_glyph_names);
+    profile = profile.keep_gsub(args.keep_gsub).keep_gpos(args.keep_gpos);
+    if let Some(policy) = args.unsupported_lookups {
+        profile = profile.unsupported_lookups(match policy {
+            LookupPolicyArg::Fail => LookupPolicy::Fail,
+            LookupPolicyArg::KeepTable => LookupPolicy::KeepTable,
+            LookupPolicyArg::DropLookup => LookupPolicy::DropLookup,
+        });
+    }
+    profile = profile.keep_aat(args.keep_aat).keep_graphite(args.keep_graphite);
+    profile = profile.convert_morx(args.convert_morx);
+    profile = profile
+        .keep_variations(args.keep_variations)
+        .keep_color(args.keep_color)
+        .keep_bitmaps(args.keep_bitmaps);
+    profile = profile.strip_hinting(strip_hinting).strict(args.strict);
+    profile = profile.remap_glyphs(args.remap_glyphs);
+    let order: Vec<u16> = match &args.glyph_order {
+        Some(order) => {
+            order.0.iter().cloned().flatten().map(|glyph| glyph as u16).collect()
+        }
+        None => vec![],
+    };
+    profile = profile.glyph_order(&order);
+    let is_text = !args.text.is_empty() || !args.text_file.is_empty();
+    if is_text || !args.unicodes.is_empty() || !args.preset.is_empty() {
+        profile = profile.ccmp_closure(true);
+    }
+    if let Some(advance) = args.synthesize_vertical {
+        profile = profile.synthesize_vertical(advance);
+    }
+    if args.no_ccmp_closure {
+        profile = profile.ccmp_closure(false);
+    }
+    if let Some(key) = args.pua_key {
+        profile = profile.pua_key(match key {
+            PuaKeyArg::GlyphId => PuaKey::GlyphId,
+            PuaKeyArg::GlyphName => PuaKey::GlyphName,
+            PuaKeyArg::Codepoint => PuaKey::Codepoint,
+        });
+    }
+    if let Some(padding) = args.padding {
+        profile = profile.padding(match padding {
+            PaddingArg::Aligned => Padding::Aligned,
+            PaddingArg::Minimal => Padding::Minimal,
+            PaddingArg::Source => Padding::Source,
+        });
+    }
+    let tags: Vec<Tag> = args
+        .drop_tables
+        .iter()
+        .map(|tag| tag.parse().expect("invalid table tag"))
+        .collect();
+    profile = profile.drop_tables(&tags);
+    let tags: Vec<Tag> = args
+        .passthrough
+        .iter()
+        .map(|tag| tag.parse().expect("invalid table tag"))
+        .collect();
+    profile = profile.passthrough_tables(&tags);
+    let mut features: Vec<Tag> = match args.layout_features.as_deref() {
+        Some("*") => {
+            let gsub = face.tables().gsub;
+            let features = gsub.into_iter().flat_map(|gsub| gsub.features);
+            features.map(|feature| Tag(feature.tag.to_bytes())).collect()
+        }
+        Some(features) => features
+            .split(',')
+            .map(|tag| tag.parse().expect("invalid feature tag"))
+            .collect(),
+        None => vec![],
+    };
+    if is_text {
+        features.extend(TEXT_FEATURES.map(Tag));
+    }
+    profile = profile.layout_closure(&features);
+    let strict: Vec<Tag> = args
+        .strict_features
+        .iter()
+        .flat_map(|features| features.split(','))
+        .map(|tag| tag.parse().expect("invalid feature tag"))
+        .collect();
+    profile = profile.strict_closure(&strict);
+    if let Some(seed) = args.obfuscate {
+        let strip_names = args.obfuscate_strip_names;
+        profile = profile.obfuscate(Obfuscation { seed, strip_names });
+    }
+    if args.closure_report {
+        let added =
+            closure_by_feature(font_data, index, &profile).expect("could not close");
+        for (tag, glyphs) in added {
+            eprintln!("{tag}: {}", glyph_ranges(face, &glyphs));
+        }
+        let added =
+            closure_by_composite(font_data, index, &profile).expect("could not close");
+        for (composite, glyphs) in added {
+            eprintln!(
+                "{}: {}",
+                glyph_ranges(face, &[composite]),
+                glyph_ranges(face, &glyphs)
+            );
+        }
+    }
+    let plan = subsetter::plan(font_data, index, profile).expect("could not plan subset");
+    let sources = sources
+        .iter()
+        .enumerate()
+        .map(|(i, (source, glyphs))| {
+            let others = || sources.iter().enumerate().filter(move |&(j, _)| j != i);
+            let only = glyphs
+                .iter()
+                .filter(|glyph| others().all(|(_, (_, other))| !other.contains(glyph)))
+                .count();
+            (*source, glyphs.len(), only)
+        })
+        .collect();
+    let mut report = Report { sources, ..Report::default() };
+    if let Some(path) = args.obfuscation_map.as_ref().filter(|_| !args.dry_run) {
+        let map = obfuscation_map(font_data, index, &plan).expect("could not obfuscate");
+        let map: Map<String, Value> = map
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string().into()))
+            .collect();
+        let json = serde_json::to_string_pretty(&map).unwrap();
+        std::fs::write(path, json).expect("could not write obfuscation map");
+    }
+    if args.glyph_report.is_some() || args.dry_run {
+        let reverse = ReverseCmap::new(font_data, index).expect("could not read cmap");
+        report.glyphs = plan
+            .glyphs()
+            .iter()
+            .map(|&glyph| (glyph, reverse.chars_for_glyph(glyph).collect()))
+            .collect();
+    }
+    if let Some(path) = args.glyph_report.as_ref().filter(|_| !args.dry_run) {
+        let json = serde_json::to_string_pretty(&report.glyphs_json()).unwrap();
+        std::fs::write(path, json).expect("could not write glyph report");
+    }
+    let result = subsetter::subset_to_result(font_data, index, &plan)
+        .expect("could not subset font");
+    let warnings = result.warnings().to_vec();
+    for &warning in &warnings {
+        match warning.severity() {
+            Severity::Warning => eprintln!("warning: {warning}"),
+            Severity::Info => eprintln!("info: {warning}"),
+        }
+    }
+    report.warnings = warnings;
+    if let Some(provenance) = result.provenance() {
+        if let Some(path) = args.provenance.as_ref().filter(|_| !args.dry_run) {
+            std::fs::write(path, provenance.to_json())
+                .expect("could not write provenance");
+        }
+        if let Some(max) = args.max_glyph_bytes {
+            let over: Vec<_> = provenance
+                .glyphs
+                .iter()
+                .filter(|&&(_, size)| size > max)
+                .map(|(glyph, size)| {
+                    format!("glyph {glyph} takes {size} bytes, more than {max}")
+                })
+                .collect();
+            if !over.is_empty() {
+                fail(over.join("\n"));
+            }
+        }
+    }
+    (result.into_font(), report)
+}
+
+/// The output path that stands for stdout.
+const STDOUT: &str = "-";
+
+/// The format of an output, either "woff2", "woff" or "ttf".
+fn output_format(args: &Args, output: Option<&Path>) -> &'static str {
+    let extension = output.and_then(Path::extension).and_then(|ext| ext.to_str());
+    match args.format.as_deref().or(extension) {
+        Some("woff2") => "woff2",
+        Some("woff") => "woff",
+        _ => "ttf",
+    }
+}
+
+impl Args {
+    /// The face to subset, or the first of several.
+    fn face_index(&self) -> u32 {
+        self.face_index.first().copied().unwrap_or(0)
+    }
+}
+
+/// Read the input as it is stored, from a file, a `data:` URI or base64, and
+/// return it with its format, "woff2", "woff" or "ttf".
+///
+/// The format of files follows their extension, while that of encoded and
+/// fetched fonts is sniffed from their signature.
+fn read_input(args: &Args) -> (Vec<u8>, &'static str) {
+    let encoded = match (&args.input, &args.input_base64) {
+        (Some(input), _) => match input.to_str() {
+            Some(uri) if uri.starts_with("data:") => Some(base64::decode_uri(uri)),
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                Some(fetch_url(args, url))
+            }
+            _ => {
+                let data = std::fs::read(input).expect("could not read font file");
+                let format = match input.extension() {
+                    Some(ext) if ext == "woff2" => "woff2",
+                    Some(ext) if ext == "woff" => "woff",
+                    _ => "ttf",
+                };
+                return (data, format);
+            }
+        },
+        (None, Some(path)) => {
+            let text = match path.as_os_str() == "-" {
+                true => read_stdin().to_string(),
+                false => {
+                    std::fs::read_to_string(path).expect("could not read base64 file")
+                }
+            };
+            Some(match text.trim_start().starts_with("data:") {
+                true => base64::decode_uri(&text),
+                false => base64::decode(&text),
+            })
+        }
+        (None, None) => None,
+    };
+    let data = encoded
+        .expect("no input file")
+        .unwrap_or_else(|err| panic!("could not read font: {err}"));
+    let format = match data.get(..4) {
+        Some(b"wOF2") => "woff2",
+        Some(b"wOFF") => "woff",
+        _ => "ttf",
+    };
+    (data, format)
+}
+
+/// Fetch the input font from a URL, which needs the `http` feature.
+#[cfg(feature = "http")]
+fn fetch_url(args: &Args, url: &str) -> Result<Vec<u8>, String> {
+    let limits = fetch::Limits {
+        max_size: args.fetch_max_size,
+        timeout: std::time::Duration::from_secs(args.fetch_timeout),
+    };
+    fetch::fetch(url, limits).map(|data| data.to_vec())
+}
+
+/// Fetch the input font from a URL, which needs the `http` feature.
+#[cfg(not(feature = "http"))]
+fn fetch_url(_: &Args, url: &str) -> Result<Vec<u8>, String> {
+    Err(format!("fetching {url} requires the `http` feature"))
+}
+
+/// All of stdin as text. It is read on the first call and kept, since the
+/// input is read again for each format and face.
+fn read_stdin() -> &'static str {
+    static STDIN: OnceLock<String> = OnceLock::new();
+    STDIN.get_or_init(|| {
+        std::io::read_to_string(std::io::stdin()).expect("could not read from stdin")
+    })
+}
+
+/// Read the input font, converting it from WOFF2 or WOFF if needed, and
+/// return its size as stored and the font.
+fn read_font(args: &Args) -> (usize, Vec<u8>) {
+    let (mut font_data, format) = read_input(args);
+    let size = font_data.len();
+    if format == "woff2" {
+        font_data = woff2_to_ttf(&font_data).expect("could not convert WOFF2 to TTF");
+    } else if format == "woff" {
+        font_data = woff_to_ttf(&font_data).expect("could not convert WOFF to TTF");
+    }
+    (size, font_data)
+}
+
+/// The metadata and private data of the input font if it is a WOFF2 or WOFF
+/// file, with the metadata given by `--woff-metadata` instead if set, for
+/// WOFF2 and WOFF outputs.
+fn read_woff_blocks(args: &Args) -> WoffBlocks {
+    let mut blocks = match read_input(args) {
+        (font_data, "woff2") => {
+            woff2_blocks(&font_data).expect("could not read WOFF2 blocks")
+        }
+        (font_data, "woff") => {
+            woff_blocks(&font_data).expect("could not read WOFF blocks")
+        }
+        _ => WoffBlocks::default(),
+    };
+    if let Some(path) = &args.woff_metadata {
+        let metadata = std::fs::read(path).expect("could not read WOFF metadata");
+        std::str::from_utf8(&metadata).expect("WOFF metadata must be UTF-8 XML");
+        blocks.metadata = metadata;
+    }
+    blocks
+}
+
+/// Print the faces, layout features, scripts, variation axes or named
+/// instances of the input font.
+fn list(args: &Args) {
+    let (_, font_data) = read_font(args);
+    let index = args.face_index();
+    if args.list_faces {
+        let count = face_count(&font_data).expect("could not read faces");
+        for i in 0..count {
+            let face = Face::parse(&font_data, i).expect("could not parse font file");
+            let name = face
+                .names()
+                .into_iter()
+                .filter(|name| name.name_id == ttf_parser::name_id::FULL_NAME)
+                .find_map(|name| name.to_string());
+            println!("{i}: {}", name.as_deref().unwrap_or("unnamed"));
+        }
+    }
+    if args.list_features {
+        let features =
+            layout_features(&font_data, index).expect("could not read features");
+        for feature in features {
+            let plural = if feature.lookups == 1 { "" } else { "s" };
+            println!(
+                "{} {}: {} lookup{plural}, {} bytes",
+                feature.table, feature.tag, feature.lookups, feature.size
+            );
+        }
+    }
+    if args.list_scripts {
+        let scripts = layout_scripts(&font_data, index).expect("could not read scripts");
+        for script in scripts {
+            let system = match script.language {
+                Some(language) => {
+                    format!("{}/{}", script.script, language.to_string().trim_end())
+                }
+                None => script.script.to_string(),
+            };
+            let features: Vec<_> = script.features.iter().map(Tag::to_string).collect();
+            println!("{} {system}: {}", script.table, features.join(", "));
+        }
+    }
+    if args.list_axes {
+        let axes = variation_axes(&font_data, index).expect("could not read axes");
+        for axis in axes {
+            let mut line = format!(
+                "{} {}..{}, default {}",
+                axis.tag, axis.min, axis.max, axis.default
+            );
+            if let Some(name) = axis.name {
+                line += &format!(" ({name})");
+            }
+            if axis.hidden {
+                line += ", hidden";
+            }
+            println!("{line}");
+        }
+    }
+    if args.list_instances {
+        let instances =
+            named_instances(&font_data, index).expect("could not read instances");
+        for instance in instances {
+            let coordinates: Vec<_> = instance
+                .coordinates
+                .iter()
+                .map(|(tag, value)| format!("{tag}={value}"))
+                .collect();
+            let name = instance.name.as_deref().unwrap_or("unnamed");
+            println!("{name}: {}", coordinates.join(","));
+        }
+    }
+}
+
+/// Read the input font, subset and convert it according to the arguments, and
+/// return the input size, the resulting font for each output, or a single
+/// font if there are no outputs, and the report of the runs.
+fn run(args: &Args) -> (usize, Vec<Vec<u8>>, Report) {
+    if args.face_index.len() > 1 || args.keep_other_faces {
+        return run_collection(args);
+    }
+    let (initial_size, mut font_data) = read_font(args);
+    let mut index = args.face_index();
+    if let Some(axes) = &args.instance {
+        let mut instancer = Instancer::new();
+        for axis in axes.split(',') {
+            let (tag, value) = axis.split_once('=').expect("invalid axis position");
+            let tag = tag.parse().expect("invalid axis tag");
+            let parse = |v: &str| v.parse::<f32>().expect("invalid axis position");
+            instancer = match value.split_once(':') {
+                Some((min, max)) => instancer.limit(tag, parse(min), parse(max)),
+                None => instancer.pin(tag, parse(value)),
+            };
+        }
+        font_data = instancer
+            .instantiate(&font_data, index)
+            .expect("could not instance font");
+        index = 0;
+    }
+    let face = Face::parse(&font_data, index).expect("could not parse font file");
+
+    let outputs: Vec<Option<&Path>> = if args.output.is_empty() {
+        vec![None]
+    } else {
+        args.output.iter().map(|output| Some(output.as_path())).collect()
+    };
+
+    let formats: Vec<&str> =
+        outputs.iter().map(|&output| output_format(args, output)).collect();
+    let blocks = match formats.contains(&"woff") || formats.contains(&"woff2") {
+        true => read_woff_blocks(args),
+        false => WoffBlocks::default(),
+    };
+    let blocks = &blocks;
+
+    // Outputs with the same format share the work. The WOFF2 encoding runs on
+    // its own thread, so that it overlaps with subsetting for other formats.
+    let mut report = Report::default();
+    let done: Vec<(&str, Vec<u8>)> = std::thread::scope(|scope| {
+        let mut pending = vec![];
+        for &format in &formats {
+            if pending.iter().any(|(f, _)| *f == format) {
+                continue;
+            }
+            let strip_hinting =
+                args.no_hinting || args.strip_hinting.iter().any(|f| f == format);
+            let result = match &args.isolate_glyph {
+                Some(glyph) => {
+                    let glyph =
+                        resolve_glyph(&face, glyph).expect("could not find glyph");
+                    let (result, trace) = isolate_glyph(&font_data, index, glyph)
+                        .expect("could not isolate glyph");
+                    eprint!("{trace}");
+                    result
+                }
+                None => {
+                    let (result, found) =
+                        subset(args, &font_data, index, &face, strip_hinting);
+                    report.merge(found);
+                    result
+                }
+            };
+            let handle = scope.spawn(move || match format {
+                "woff2" => to_woff2(&result, args, blocks),
+                "woff" => {
+                    ttf_to_woff(&result, blocks).expect("could not convert TTF to WOFF")
+                }
+                _ => result,
+            });
+            pending.push((format, handle));
+        }
+        pending
+            .into_iter()
+            .map(|(format, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|payload| std::panic::resume_unwind(payload));
+                (format, result)
+            })
+            .collect()
+    });
+
+    let results: Vec<Vec<u8>> = formats
+        .iter()
+        .map(|&format| done.iter().find(|(f, _)| *f == format).unwrap().1.clone())
+        .collect();
+    check_sizes(args, initial_size, &formats, &results);
+    (initial_size, results, report)
+}
+
+/// Subset each face of `--face-index` with the other arguments and combine the
+/// subsets into a collection, like `run` does for a single face. With
+/// `--keep-other-faces`, the subsets replace their faces in the input
+/// collection instead.
+fn run_collection(args: &Args) -> (usize, Vec<Vec<u8>>, Report) {
+    let formats: Vec<&str> = args
+        .output
+        .iter()
+        .map(|output| output_format(args, Some(output)))
+        .collect();
+    assert!(
+        formats
+            .iter()
+            .chain(args.format.as_deref().iter())
+            .all(|&f| f == "ttf"),
+        "collections can only be written as TTF"
+    );
+    assert!(
+        args.provenance.is_none()
+            && args.glyph_report.is_none()
+            && args.obfuscation_map.is_none(),
+        "reports can only be written for a single face"
+    );
+
+    let mut input_size = 0;
+    let mut report = Report::default();
+    let mut fonts = vec![];
+    for &index in &args.face_index {
+        let args = Args {
+            output: vec![],
+            face_index: vec![index],
+            keep_other_faces: false,
+            min_reduction: None,
+            baseline: None,
+            ..args.clone()
+        };
+        let (size, mut results, found) = run(&args);
+        input_size = size;
+        report.merge(found);
+        fonts.push(results.remove(0));
+    }
+    let fonts: Vec<&[u8]> = fonts.iter().map(Vec::as_slice).collect();
+    let collection = match args.keep_other_faces {
+        true => {
+            let (_, data) = read_font(args);
+            let replacements: Vec<(u32, &[u8])> =
+                args.face_index.iter().copied().zip(fonts).collect();
+            replace_faces(&data, &replacements).expect("could not replace faces")
+        }
+        false => build_collection(&fonts).expect("could not build collection"),
+    };
+
+    let results = vec![collection; formats.len().max(1)];
+    check_sizes(args, input_size, &formats, &results);
+    (input_size, results, report)
+}
+
+/// Fail if the outputs aren't enough smaller than the input or grew too much
+/// compared to the baseline.
+fn check_sizes(args: &Args, input_size: usize, formats: &[&str], results: &[Vec<u8>]) {
+    let percent = |size: usize, of: usize| 100.0 * size as f64 / of as f64;
+    let mut errors = vec![];
+    if let Some(min) = args.min_reduction {
+        for result in results {
+            let reduction = 100.0 - percent(result.len(), input_size);
+            if reduction < min {
+                errors.push(format!(
+                    "output of {} bytes is only {reduction:.1}% smaller than the input \
+                     of {input_size} bytes, less than {min}%",
+                    result.len()
+                ));
+            }
+        }
+    }
+    if let Some(baseline) = &args.baseline {
+        let size = std::fs::metadata(baseline).expect("could not read baseline").len();
+        let format = match baseline.extension() {
+            Some(ext) if ext == "woff2" => "woff2",
+            Some(ext) if ext == "woff" => "woff",
+            _ => "ttf",
+        };
+        let max = args.max_growth.unwrap_or(0.0);
+        let mut compared = false;
+        for (result, _) in results.iter().zip(formats).filter(|(_, &f)| f == format) {
+            compared = true;
+            let growth = percent(result.len(), size as usize) - 100.0;
+            if growth > max {
+                errors.push(format!(
+                    "output of {} bytes is {growth:.1}% larger than the baseline of \
+                     {size} bytes, more than {max}%",
+                    result.len()
+                ));
+            }
+        }
+        if !compared {
+            errors.push(format!("no output has the {format} format of the baseline"));
+        }
+    }
+    if !errors.is_empty() {
+        fail(errors.join("\n"));
+    }
+}
+
+/// The hashes of the outputs for `--integrity`, keyed by the output paths, or
+/// by "-" for stdout.
+fn integrity(args: &Args, results: &[Vec<u8>]) -> Value {
+    let outputs = args.output.iter().map(|output| output.display().to_string());
+    let outputs = outputs.chain(args.output.is_empty().then(|| STDOUT.into()));
+    let entries = outputs.zip(results).map(|(output, result)| {
+        (output, json!({ "sha256": Integrity::compute(result).hex() }))
+    });
+    Value::Object(entries.collect())
+}
+
+/// Write a font to stdout, where it goes without outputs or with `--output -`.
+///
+/// Rust doesn't translate line endings on any platform, so binary data
+/// arrives intact on Windows as well when stdout is redirected. A console
+/// would mangle it though, so writing to a terminal is refused.
+fn write_stdout(data: &[u8]) {
+    let stdout = std::io::stdout();
+    assert!(
+        !stdout.is_terminal(),
+        "refusing to write a binary font to the terminal, redirect stdout or give --output"
+    );
+    let mut w = std::io::BufWriter::new(stdout.lock());
+    w.write_all(data)
+        .and_then(|_| w.flush())
+        .expect("could not write subsetted font");
+}
+
+/// An error in the request rather than a bug, which ends the program with
+/// exit status 2, or fails the job in a batch.
+struct Failure(String);
+
+/// Stop with the given error message, which may span several lines.
+///
+/// This unwinds without running the panic hook, so that `main` and batch
+/// jobs can report the message without a panic message and backtrace.
+fn fail(message: String) -> ! {
+    std::panic::resume_unwind(Box::new(Failure(message)))
+}
+
+/// Run one job from a batch and describe its outcome.
+fn run_job(line: &str, brotli_threads: Option<usize>) -> Value {
+    let job: Job = match serde_json::from_str(line) {
+        Ok(job) => job,
+        Err(err) => return json!({ "ok": false, "error": err.to_string() }),
+    };
+    let outcome = job.args(brotli_threads).and_then(|args| {
+        std::panic::catch_unwind(|| {
+            let (input_size, results, _) = run(&args);
+            std::fs::write(&job.output, &results[0])
+                .expect("could not write subsetted font");
+            (input_size, results[0].len())
+        })
+        .map_err(|payload| {
+            payload
+                .downcast_ref::<Failure>()
+                .map(|failure| failure.0.clone())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| "unknown error".into())
+        })
+    });
+    match outcome {
+        Ok((input_size, output_size)) => json!({
+            "id": job.id,
+            "ok": true,
+            "output": job.output,
+            "input_size": input_size,
+            "output_size": output_size,
+        }),
+        Err(error) => json!({ "id": job.id, "ok": false, "error": error }),
+    }
+}
+
+/// Process jobs from stdin with the given number of threads, writing a result
+/// line per job as soon as it is finished. Returns the result lines of the
+/// failed jobs.
+fn batch(jobs: usize, brotli_threads: Option<usize>) -> Vec<Value> {
+    // Failures are reported in the result lines instead.
+    std::panic::set_hook(Box::new(|_| {}));
+    let stdin = std::io::stdin();
+    let lines = Mutex::new(0);
+    let failures = Mutex::new(vec![]);
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| loop {
+                // Count the lines while reading so that failures can be
+                // traced back to their job.
+                let (number, line) = {
+                    let mut lines = lines.lock().unwrap();
+                    let mut line = String::new();
+                    if stdin.read_line(&mut line).expect("could not read from stdin") == 0
+                    {
+                        break;
+                    }
+                    *lines += 1;
+                    (*lines, line)
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let result = run_job(&line, brotli_threads);
+                let mut stdout = std::io::stdout().lock();
+                writeln!(stdout, "{result}").expect("could not write result");
+                stdout.flush().expect("could not write result");
+                drop(stdout);
+
+                if result["ok"] == false {
+                    let mut failure = result;
+                    failure["line"] = json!(number);
+                    failures.lock().unwrap().push(failure);
+                }
+            });
+        }
+    });
+
+    let mut failures = failures.into_inner().unwrap();
+    failures.sort_by_key(|failure| failure["line"].as_u64());
+    failures
+}
+
+fn main() {
+    let args = if std::env::args_os().any(|arg| arg == "--hb-compat") {
+        let argv =
+            compat::hb_args(std::env::args()).unwrap_or_else(|err| panic!("{err}"));
+        Args::parse_from(argv)
+    } else {
+        Args::parse()
+    };
+    if args.options_json {
+        let json = options::schema(Args::command());
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+    if let Some(shell) = args.completions {
+        let mut command = Args::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        return;
+    }
+    if args.batch_stdin {
+        let failures = batch(args.jobs, args.brotli_threads);
+        if let Some(path) = &args.failures {
+            let json = serde_json::to_string_pretty(&failures).unwrap();
+            std::fs::write(path, json).expect("could not write failures");
+        }
+        if !failures.is_empty() {
+            eprintln!("{} of the batch jobs failed", failures.len());
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.pipe {
+        pipe::serve();
+        return;
+    }
+    if args.list_faces
+        || args.list_features
+        || args.list_scripts
+        || args.list_axes
+        || args.list_instances
+    {
+        list(&args);
+        return;
+    }
+    if args.analyze {
+        let json = analyze::analyze(&args);
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+    let to_stdout = args.output.iter().filter(|&output| output == STDOUT).count();
+    assert!(to_stdout <= 1, "only one output can go to stdout");
+    let (initial_size, results, report) = std::panic::catch_unwind(|| run(&args))
+        .unwrap_or_else(|payload| match payload.downcast::<Failure>() {
+            Ok(failure) => {
+                for line in failure.0.lines() {
+                    eprintln!("error: {line}");
+                }
+                std::process::exit(2);
+            }
+            Err(payload) => std::panic::resume_unwind(payload),
+        });
+    if args.dry_run {
+        let json = report.to_json(&args, initial_size, &results);
+        println!("{}", serde_json::to_string_pretty(&json).unwrap());
+        return;
+    }
+    if args.output.is_empty() {
+        write_stdout(&results[0]);
+    }
+    if let Some(path) = &args.integrity {
+        let json = serde_json::to_string_pretty(&integrity(&args, &results)).unwrap();
+        std::fs::write(path, json).expect("could not write integrity report");
+    }
+    for (output, result) in args.output.iter().zip(results) {
+        if output == STDOUT {
+            write_stdout(&result);
+        } else {
+            std::fs::write(output, &result).expect("could not write subsetted font");
+        }
+        let summary = format!(
+            "subsetted from {initial_size} to {} bytes ({}%)",
+            result.len(),
+            100 * result.len() / initial_size
+        );
+        // The summary would end up in the font on stdout.
+        match to_stdout {
+            0 => println!("{summary}"),
+            _ => eprintln!("{summary}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The message of the failure that `f` ends with.
+    fn failure<T>(f: impl FnOnce() -> T) -> String {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+            Ok(_) => panic!("expected a failure"),
+            Err(payload) => payload.downcast::<Failure>().map(|f| f.0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_missing_glyphs() {
+        let data = std::fs::read("../fonts/NotoSans-Regular.ttf").unwrap();
+        let face = Face::parse(&data, 0).unwrap();
+        let args = |lenient: bool| {
+            let mut argv = vec!["subsetter", "font.ttf", "--glyphs=68,5000"];
+            argv.extend(["--gids=3,3700-3800,6000", "--gids=5000"]);
+            argv.extend(lenient.then_some("--lenient"));
+            Args::parse_from(argv)
+        };
+
+        // Single IDs beyond the end of the font fail, while ranges are cut off.
+        let message = failure(|| select(&args(false), &face));
+        assert_eq!(
+            message,
+            "the font has 3748 glyphs, but glyph IDs 5000, 6000 were requested"
+        );
+
+        let sources = select(&args(true), &face);
+        assert_eq!(sources[0], ("glyphs", HashSet::from([68])));
+        assert_eq!(sources[1].0, "gids");
+        assert_eq!(sources[1].1.len(), 1 + 48);
+        assert!(sources[1].1.iter().all(|&g| g == 3 || (3700..3748).contains(&g)));
+    }
+
+    #[test]
+    fn test_size_budgets() {
+        let data = std::fs::read("../fonts/NotoSans-Regular.ttf").unwrap();
+        let face = Face::parse(&data, 0).unwrap();
+        let args = |extra: &str| {
+            Args::parse_from(["subsetter", "font.ttf", "--glyphs=3,68", extra])
+        };
+
+        let args68 = args("--max-glyph-bytes=60");
+        let message = failure(|| subset(&args68, &data, 0, &face, false));
+        assert!(message.starts_with("glyph 68 takes "), "{message}");
+        assert!(!message.contains('\n'));
+        let (result, _) = subset(&args("--max-glyph-bytes=1000"), &data, 0, &face, false);
+
+        let results = [result];
+        check_sizes(&args("--min-reduction=90"), data.len(), &["ttf"], &results);
+        let message = failure(|| {
+            check_sizes(&args("--min-reduction=99.9"), data.len(), &["ttf"], &results)
+        });
+        assert!(message.contains("less than 99.9%"), "{message}");
+        let baseline = args("--baseline=../fonts/NotoSans-Regular.ttf");
+        check_sizes(&baseline, data.len(), &["ttf"], &results);
+        let message = failure(|| check_sizes(&baseline, data.len(), &["woff"], &results));
+        assert_eq!(message, "no output has the ttf format of the baseline");
+    }
+
+    #[test]
+    fn test_output_format() {
+        let args = Args::parse_from(["subsetter", "font.ttf"]);
+        let format = |output: &str| output_format(&args, Some(Path::new(output)));
+        assert_eq!(format("out.woff2"), "woff2");
+        assert_eq!(format("out.woff"), "woff");
+        assert_eq!(format("out.otf"), "ttf");
+        assert_eq!(format("out"), "ttf");
+        assert_eq!(format(".woff2"), "ttf");
+        assert_eq!(format(STDOUT), "ttf");
+        assert_eq!(output_format(&args, None), "ttf");
+
+        let args = Args::parse_from(["subsetter", "font.ttf", "--format=woff"]);
+        assert_eq!(output_format(&args, Some(Path::new("out"))), "woff");
+        assert_eq!(output_format(&args, Some(Path::new("out.woff2"))), "woff");
+        assert!(Args::try_parse_from(["subsetter", "font.ttf", "--format=otf"]).is_err());
+        let strip = |formats| Args::try_parse_from(["subsetter", "font.ttf", formats]);
+        assert_eq!(
+            strip("--strip-hinting=woff2,ttf").unwrap().strip_hinting,
+            ["woff2", "ttf"]
+        );
+        assert!(strip("--strip-hinting=woff2,otf").is_err());
+    }
+
+    #[test]
+    fn test_integrity() {
+        let results = [b"ttf".to_vec(), b"woff2".to_vec()];
+        let hash = |i: usize| Integrity::compute(&results[i]).hex();
+
+        let args = Args::parse_from(["subsetter", "font.ttf", "-o", "a.ttf", "-o", "-"]);
+        let json = integrity(&args, &results);
+        assert_eq!(
+            json,
+            json!({ "a.ttf": { "sha256": hash(0) }, "-": { "sha256": hash(1) } })
+        );
+
+        let args = Args::parse_from(["subsetter", "font.ttf"]);
+        assert_eq!(
+            integrity(&args, &results[..1]),
+            json!({ "-": { "sha256": hash(0) } })
+        );
+    }
+
+    #[test]
+    fn test_parse_unicodes() {
+        let ranges = |arg| parse_unicodes(arg).map(|unicodes| unicodes.0);
+        assert_eq!(ranges("U+D7FF-E000"), Ok(vec![0xD7FF..=0xE000]));
+        assert_eq!(ranges("DFFF"), Err("U+DFFF is a surrogate, not a character".into()));
+        assert_eq!(ranges(""), Err("no codepoints given".into()));
+        assert!(ranges("110000").is_err());
+        assert!(
+            Args::try_parse_from(["subsetter", "font.ttf", "--unicodes=D800"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_gids() {
+        let parse = |arg| Args::try_parse_from(["subsetter", "font.ttf", arg]);
+        let args = parse("--glyph-order=70,68-69").unwrap();
+        assert_eq!(args.glyph_order.unwrap().0, [70..=70, 68..=69]);
+        for arg in ["--gids=0-x", "--gids=10-0", "--glyph-order=65536"] {
+            let err = parse(arg).unwrap_err();
+            assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+            assert_eq!(err.exit_code(), 2);
+        }
+    }
+}
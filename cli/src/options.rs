@@ -0,0 +1,125 @@
+//! The schema of the command line options, for `--options-json`.
+
+use std::any::TypeId;
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, Command};
+use serde_json::{json, Value};
+
+/// The JSON type of an option's values.
+fn value_type(arg: &Arg) -> &'static str {
+    let id = arg.get_value_parser().type_id();
+    if !arg.get_action().takes_values() || id == TypeId::of::<bool>() {
+        "boolean"
+    } else if [
+        TypeId::of::<u8>(),
+        TypeId::of::<u16>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<usize>(),
+    ]
+    .iter()
+    .any(|t| id == *t)
+    {
+        "integer"
+    } else if id == TypeId::of::<f64>() {
+        "number"
+    } else if id == TypeId::of::<PathBuf>() {
+        "path"
+    } else {
+        "string"
+    }
+}
+
+/// Convert a default value to the JSON type of the option.
+fn default_value(ty: &str, value: &str) -> Value {
+    let parsed = match ty {
+        "boolean" => value.parse::<bool>().ok().map(Value::from),
+        "integer" => value.parse::<u64>().ok().map(Value::from),
+        "number" => value.parse::<f64>().ok().map(Value::from),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| value.into())
+}
+
+/// Describe the options of the command as JSON: for each option its name,
+/// short flag and aliases, the type of its values, whether it can be given
+/// several times or without a value, its default and possible values, the
+/// options it conflicts with and its help text.
+///
+/// The options are listed in the order of `--help`. Conflicts are listed on
+/// both sides, even if only one of the options declares them.
+pub fn schema(mut command: Command) -> Value {
+    command.build();
+    let args: Vec<&Arg> = command.get_arguments().collect();
+    let name = |arg: &Arg| match arg.get_long() {
+        Some(long) => long.to_string(),
+        None => arg.get_id().to_string(),
+    };
+
+    let options: Vec<Value> = args
+        .iter()
+        .map(|&arg| {
+            let ty = value_type(arg);
+            let mut conflicts: Vec<String> =
+                command.get_arg_conflicts_with(arg).into_iter().map(name).collect();
+            for &other in &args {
+                let declared = command.get_arg_conflicts_with(other);
+                if declared.iter().any(|a| a.get_id() == arg.get_id()) {
+                    conflicts.push(name(other));
+                }
+            }
+            conflicts.sort();
+            conflicts.dedup();
+
+            let multiple = matches!(arg.get_action(), ArgAction::Append);
+            let optional_value = arg
+                .get_num_args()
+                .is_some_and(|range| range.min_values() == 0 && range.takes_values());
+            let defaults: Vec<Value> = arg
+                .get_default_values()
+                .iter()
+                .map(|value| default_value(ty, &value.to_string_lossy()))
+                .collect();
+            let default = match (multiple, defaults.len()) {
+                (false, 1) => defaults[0].clone(),
+                (false, _) => Value::Null,
+                (true, _) => defaults.into(),
+            };
+            let possible: Vec<String> = match ty {
+                "boolean" => vec![],
+                _ => arg
+                    .get_possible_values()
+                    .iter()
+                    .map(|value| value.get_name().to_string())
+                    .collect(),
+            };
+            let value_name = arg
+                .get_value_names()
+                .and_then(|names| names.first())
+                .map(|name| name.to_string());
+
+            json!({
+                "name": name(arg),
+                "short": arg.get_short().map(String::from),
+                "aliases": arg.get_all_aliases().unwrap_or_default(),
+                "positional": arg.is_positional(),
+                "type": ty,
+                "value_name": value_name,
+                "multiple": multiple,
+                "delimiter": arg.get_value_delimiter().map(String::from),
+                "optional_value": optional_value,
+                "default": default,
+                "possible_values": possible,
+                "conflicts": conflicts,
+                "help": arg.get_help().map(|help| help.to_string()),
+            })
+        })
+        .collect();
+
+    json!({
+        "name": command.get_name(),
+        "version": command.get_version(),
+        "options": options,
+    })
+}
@@ -0,0 +1,409 @@
+//! A persistent stdio protocol with length-prefixed binary frames.
+//!
+//! Each request and response is a frame consisting of a big-endian `u32`
+//! length followed by that many bytes. A request frame starts with a `u8`
+//! request kind:
+//!
+//! - `0` subsets a font sent with the request. It is followed by the options,
+//!   the `u32` length of the font data, the font data (OpenType or WOFF2) and
+//!   the characters to keep as UTF-8, filling the rest of the frame.
+//! - `1` registers the font data (OpenType or WOFF2) filling the rest of the
+//!   frame and responds with a `u32` handle for it.
+//! - `2` subsets a registered font. It is followed by the options, a `u32`
+//!   handle and the characters to keep as UTF-8.
+//! - `3` releases the font with the `u32` handle that follows.
+//!
+//! The options of a subset request are a `u8` output format (`0` for TTF and
+//! `1` for WOFF2), a `u8` profile (`0` for PDF and `1` for web) and a `u8`
+//! Brotli quality of WOFF2 outputs from 0 to 11.
+//!
+//! A response frame contains a `u8` status, `0` for success and `1` for
+//! failure, followed by the result or a UTF-8 error message. Requests larger
+//! than [`MAX_FRAME`] are skipped and answered with an error. The process exits
+//! when stdin is closed.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use subsetter::{ttf_to_woff2, woff2_to_ttf, Profile, SubsetCache};
+use ttf_parser::Face;
+
+/// The largest request frame in bytes that is processed.
+pub const MAX_FRAME: u32 = 256 * 1024 * 1024;
+
+/// The size in bytes of the artifacts cached for registered fonts.
+const CACHE_CAPACITY: usize = 64 * 1024 * 1024;
+
+/// Subset a font sent with the request.
+const SUBSET: u8 = 0;
+/// Register a font for later requests.
+const REGISTER: u8 = 1;
+/// Subset a registered font.
+const SUBSET_REGISTERED: u8 = 2;
+/// Release a registered font.
+const RELEASE: u8 = 3;
+
+/// Serve requests from stdin until it is closed.
+pub fn serve() {
+    let mut input = BufReader::new(std::io::stdin().lock());
+    let mut output = BufWriter::new(std::io::stdout().lock());
+    let mut registry = Registry::default();
+    while let Some(request) =
+        read_frame(&mut input, MAX_FRAME).expect("could not read request")
+    {
+        let (status, payload) =
+            match request.and_then(|request| registry.handle(&request)) {
+                Ok(font) => (0, font),
+                Err(message) => (1, message.into_bytes()),
+            };
+        let len = u32::try_from(payload.len() + 1).expect("response is too large");
+        output
+            .write_all(&len.to_be_bytes())
+            .expect("could not write response");
+        output.write_all(&[status]).expect("could not write response");
+        output.write_all(&payload).expect("could not write response");
+        output.flush().expect("could not write response");
+    }
+}
+
+/// Read a frame, returning `None` if the input ended before it.
+///
+/// A frame larger than `max` bytes is skipped and yields an error message
+/// instead of its contents.
+fn read_frame(
+    input: &mut impl Read,
+    max: u32,
+) -> std::io::Result<Option<Result<Vec<u8>, String>>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_be_bytes(len);
+    if len > max {
+        let skipped = std::io::copy(&mut input.take(len.into()), &mut std::io::sink())?;
+        if skipped < len.into() {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        return Ok(Some(Err(format!(
+            "request of {len} bytes exceeds the maximum of {max} bytes"
+        ))));
+    }
+    let mut frame = vec![0; len as usize];
+    input.read_exact(&mut frame)?;
+    Ok(Some(Ok(frame)))
+}
+
+/// Fonts registered by earlier requests, so that requests for them only need
+/// to send the characters.
+struct Registry {
+    /// The registered fonts by handle.
+    fonts: HashMap<u32, Font>,
+    /// The artifacts of registered fonts reused across requests, keyed by
+    /// handle.
+    cache: SubsetCache,
+    /// The next handle to hand out.
+    next: u32,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            fonts: HashMap::new(),
+            cache: SubsetCache::new(CACHE_CAPACITY),
+            next: 0,
+        }
+    }
+}
+
+/// A decoded font with its character to glyph mapping.
+struct Font {
+    /// The OpenType font data.
+    data: Vec<u8>,
+    /// The glyphs of the characters the font maps.
+    glyphs: HashMap<char, u16>,
+}
+
+impl Font {
+    /// Decode and parse font data.
+    fn new(data: &[u8]) -> Result<Self, String> {
+        let data = decode(data)?.into_owned();
+        let face = Face::parse(&data, 0).map_err(|err| err.to_string())?;
+        let mut glyphs = HashMap::new();
+        let subtables = face.tables().cmap.into_iter().flat_map(|cmap| cmap.subtables);
+        for subtable in subtables.filter(|subtable| subtable.is_unicode()) {
+            subtable.codepoints(|c| {
+                let glyph = subtable.glyph_index(c);
+                if let (Some(c), Some(glyph)) = (char::from_u32(c), glyph) {
+                    glyphs.entry(c).or_insert(glyph.0);
+                }
+            });
+        }
+        Ok(Self { data, glyphs })
+    }
+
+    /// The glyphs of the given characters that the font maps.
+    fn glyphs(&self, chars: &str) -> Vec<u16> {
+        chars.chars().filter_map(|c| self.glyphs.get(&c).copied()).collect()
+    }
+}
+
+/// The options of a subset request.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Options {
+    /// Whether to output WOFF2 instead of TTF.
+    woff2: bool,
+    /// Whether to use the web profile instead of the PDF profile.
+    web: bool,
+    /// The Brotli quality of WOFF2 outputs.
+    quality: u8,
+}
+
+impl Options {
+    /// The profile for the given glyphs.
+    fn profile<'a>(&self, glyphs: &'a [u16]) -> Profile<'a> {
+        if self.web {
+            Profile::web(glyphs)
+        } else {
+            Profile::pdf(glyphs)
+        }
+    }
+
+    /// Convert the subsetted font to the output format.
+    fn output(&self, font: Vec<u8>) -> Result<Vec<u8>, String> {
+        if self.woff2 {
+            ttf_to_woff2(&font, self.quality).map_err(|err| err.to_string())
+        } else {
+            Ok(font)
+        }
+    }
+}
+
+impl Registry {
+    /// Process a request and return the response payload.
+    fn handle(&mut self, request: &[u8]) -> Result<Vec<u8>, String> {
+        let mut r = FrameReader(request);
+        match r.u8()? {
+            SUBSET => {
+                let options = r.options()?;
+                let len = r.u32()? as usize;
+                let font = Font::new(r.take(len)?)?;
+                let glyphs = font.glyphs(r.chars()?);
+                let result = subsetter::subset(&font.data, 0, options.profile(&glyphs))
+                    .map_err(|err| err.to_string())?;
+                options.output(result)
+            }
+            REGISTER => {
+                let font = Font::new(r.0)?;
+                let handle = self.next;
+                self.next = self.next.checked_add(1).ok_or("too many fonts")?;
+                self.fonts.insert(handle, font);
+                Ok(handle.to_be_bytes().to_vec())
+            }
+            SUBSET_REGISTERED => {
+                let options = r.options()?;
+                let handle = r.u32()?;
+                let font = self.fonts.get(&handle).ok_or_else(|| unknown(handle))?;
+                let glyphs = font.glyphs(r.chars()?);
+                let result = self
+                    .cache
+                    .subset_by_id(handle.into(), &font.data, 0, options.profile(&glyphs))
+                    .map_err(|err| err.to_string())?;
+                options.output(result)
+            }
+            RELEASE => {
+                let handle = r.u32()?;
+                self.fonts.remove(&handle).ok_or_else(|| unknown(handle))?;
+                Ok(vec![])
+            }
+            kind => Err(format!("unknown request kind {kind}")),
+        }
+    }
+}
+
+/// The error message for a handle that isn't registered.
+fn unknown(handle: u32) -> String {
+    format!("unknown font handle {handle}")
+}
+
+/// Decode the font data if it is a WOFF2 file.
+fn decode(font: &[u8]) -> Result<Cow<'_, [u8]>, String> {
+    if font.starts_with(b"wOF2") {
+        woff2_to_ttf(font).map(Cow::Owned).map_err(|err| err.to_string())
+    } else {
+        Ok(Cow::Borrowed(font))
+    }
+}
+
+/// Reads big-endian values from a request frame.
+struct FrameReader<'a>(&'a [u8]);
+
+impl<'a> FrameReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < len {
+            return Err("request is truncated".into());
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Read an output format, returning whether it is WOFF2.
+    fn format(&mut self) -> Result<bool, String> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            format => Err(format!("unknown output format {format}")),
+        }
+    }
+
+    /// Read the options of a subset request.
+    fn options(&mut self) -> Result<Options, String> {
+        let woff2 = self.format()?;
+        let web = match self.u8()? {
+            0 => false,
+            1 => true,
+            profile => return Err(format!("unknown profile {profile}")),
+        };
+        let quality = self.u8()?;
+        if quality > 11 {
+            return Err(format!("invalid WOFF2 quality {quality}"));
+        }
+        Ok(Options { woff2, web, quality })
+    }
+
+    /// Read the characters filling the rest of the frame.
+    fn chars(&mut self) -> Result<&'a str, String> {
+        std::str::from_utf8(std::mem::take(&mut self.0)).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A request of the given kind with the given fields.
+    fn request(kind: u8, fields: &[&[u8]]) -> Vec<u8> {
+        let mut request = vec![kind];
+        fields.iter().for_each(|field| request.extend_from_slice(field));
+        request
+    }
+
+    /// Ignores the outlines of glyphs.
+    struct Outline;
+
+    impl ttf_parser::OutlineBuilder for Outline {
+        fn move_to(&mut self, _: f32, _: f32) {}
+        fn line_to(&mut self, _: f32, _: f32) {}
+        fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {}
+        fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {}
+        fn close(&mut self) {}
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut input: &[u8] = &[0, 0, 0, 2, 7, 8, 0, 0, 0, 0];
+        assert_eq!(read_frame(&mut input, 8).unwrap(), Some(Ok(vec![7, 8])));
+        assert_eq!(read_frame(&mut input, 8).unwrap(), Some(Ok(vec![])));
+        assert_eq!(read_frame(&mut input, 8).unwrap(), None);
+
+        // A frame that ends early is an error rather than the end of input.
+        let mut input: &[u8] = &[0, 0, 0, 3, 7];
+        assert!(read_frame(&mut input, 8).is_err());
+
+        // An oversized frame is skipped, so the next one is still read.
+        let mut input: &[u8] = &[0, 0, 0, 9, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 0, 0, 1, 9];
+        let message = "request of 9 bytes exceeds the maximum of 8 bytes".into();
+        assert_eq!(read_frame(&mut input, 8).unwrap(), Some(Err(message)));
+        assert_eq!(read_frame(&mut input, 8).unwrap(), Some(Ok(vec![9])));
+        let mut input: &[u8] = &[0, 0, 0, 9, 1];
+        assert!(read_frame(&mut input, 8).is_err());
+
+        let mut r = FrameReader(&[1, 0, 0, 1, 0, 2, b'a']);
+        assert_eq!(r.format(), Ok(true));
+        assert_eq!(r.u32(), Ok(256));
+        assert_eq!(r.format(), Err("unknown output format 2".into()));
+        assert_eq!(r.chars(), Ok("a"));
+        assert_eq!(r.u8(), Err("request is truncated".into()));
+        assert_eq!(FrameReader(&[0, 0, 1]).u32(), Err("request is truncated".into()));
+        assert!(FrameReader(&[0xFF]).chars().is_err());
+
+        let options = Options { woff2: true, web: true, quality: 5 };
+        assert_eq!(FrameReader(&[1, 1, 5]).options(), Ok(options));
+        assert_eq!(FrameReader(&[0, 2, 5]).options(), Err("unknown profile 2".into()));
+        assert_eq!(
+            FrameReader(&[0, 0, 12]).options(),
+            Err("invalid WOFF2 quality 12".into())
+        );
+    }
+
+    #[test]
+    fn test_requests() {
+        let font = std::fs::read("../fonts/NotoSans-Regular.ttf").unwrap();
+        let len = (font.len() as u32).to_be_bytes();
+        let mut registry = Registry::default();
+
+        let sent = registry
+            .handle(&request(SUBSET, &[&[0, 0, 11], &len, &font, b"ab"]))
+            .unwrap();
+        let face = Face::parse(&sent, 0).unwrap();
+        let glyph = |c| face.glyph_index(c).unwrap();
+        assert!(face.outline_glyph(glyph('a'), &mut Outline).is_some());
+        assert!(face.outline_glyph(glyph('c'), &mut Outline).is_none());
+
+        let truncated = request(SUBSET, &[&[0, 0, 11], &len, &font[..100]]);
+        assert_eq!(registry.handle(&truncated), Err("request is truncated".into()));
+        assert_eq!(registry.handle(&[]), Err("request is truncated".into()));
+        assert_eq!(registry.handle(&[4]), Err("unknown request kind 4".into()));
+        let format = request(SUBSET, &[&[2, 0, 11], &len, &font]);
+        assert_eq!(registry.handle(&format), Err("unknown output format 2".into()));
+    }
+
+    #[test]
+    fn test_registry() {
+        let font = std::fs::read("../fonts/NotoSans-Regular.ttf").unwrap();
+        let len = (font.len() as u32).to_be_bytes();
+        let mut registry = Registry::default();
+        let sent = registry.handle(&request(SUBSET, &[&[0, 0, 11], &len, &font, b"ab"]));
+
+        // Register, subset, release and subset again with the stale handle.
+        let handle = registry.handle(&request(REGISTER, &[&font])).unwrap();
+        assert_eq!(handle, [0, 0, 0, 0]);
+        let subset = request(SUBSET_REGISTERED, &[&[0, 0, 11], &handle, b"ab"]);
+        assert_eq!(registry.handle(&subset), sent);
+        let woff2 = request(SUBSET_REGISTERED, &[&[1, 0, 11], &handle, b"ab"]);
+        assert!(registry.handle(&woff2).unwrap().starts_with(b"wOF2"));
+        assert_eq!(registry.cache.stats().hits, 1);
+
+        // The web profile maps the glyphs into the PUA, the PDF profile not.
+        let web = request(SUBSET_REGISTERED, &[&[0, 1, 11], &handle, b"ab"]);
+        let web = registry.handle(&web).unwrap();
+        let pua = char::from_u32(0xF0000 + 68).unwrap();
+        assert!(Face::parse(&web, 0).unwrap().glyph_index(pua).is_some());
+        let pdf = sent.clone().unwrap();
+        assert!(Face::parse(&pdf, 0).unwrap().glyph_index(pua).is_none());
+        assert_eq!(registry.handle(&request(RELEASE, &[&handle])), Ok(vec![]));
+        let stale = Err("unknown font handle 0".into());
+        assert_eq!(registry.handle(&subset), stale);
+        assert_eq!(registry.handle(&request(RELEASE, &[&handle])), stale);
+        assert_eq!(
+            registry.handle(&request(RELEASE, &[&handle[..2]])),
+            Err("request is truncated".into())
+        );
+
+        // Handles aren't reused, and data that isn't a font isn't registered.
+        let handle = registry.handle(&request(REGISTER, &[&font])).unwrap();
+        assert_eq!(handle, [0, 0, 0, 1]);
+        assert!(registry.handle(&request(REGISTER, &[b"font"])).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! Named character sets for `--preset`.
+
+/// The presets and their codepoints in `pyftsubset` syntax, following the
+/// script subsets of Google Fonts.
+const PRESETS: [(&str, &str); 6] = [
+    ("ascii", "20-7E"),
+    (
+        "latin",
+        "0-FF,131,152-153,2BB-2BC,2C6,2DA,2DC,304,308,329,2000-206F,2074,20AC,\
+         2122,2191,2193,2212,2215,FEFF,FFFD",
+    ),
+    (
+        "latin-ext",
+        "100-2AF,304,308,329,1E00-1E9F,1EF2-1EFF,2020,20A0-20AB,20AD-20C0,2113,\
+         2C60-2C7F,A720-A7FF",
+    ),
+    ("greek", "370-377,37A-37F,384-38A,38C,38E-3A1,3A3-3FF"),
+    ("cyrillic", "301,400-45F,490-491,4B0-4B1,2116"),
+    (
+        "vietnamese",
+        "102-103,110-111,128-129,168-169,1A0-1A1,1AF-1B0,300-301,303-304,\
+         308-309,323,329,1EA0-1EF9,20AB",
+    ),
+];
+
+/// The codepoints of a preset in `pyftsubset` syntax.
+pub fn unicodes(name: &str) -> Result<&'static str, String> {
+    PRESETS
+        .iter()
+        .find(|&&(preset, _)| preset == name)
+        .map(|&(_, unicodes)| unicodes)
+        .ok_or_else(|| {
+            let names: Vec<_> = PRESETS.iter().map(|&(preset, _)| preset).collect();
+            format!("unknown preset {name:?}, expected one of {}", names.join(", "))
+        })
+}
@@ -0,0 +1,12 @@
+//! Subsets arbitrary bytes as a font with the built-in profiles.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use subsetter::{subset, Profile};
+
+fuzz_target!(|data: &[u8]| {
+    let glyphs: Vec<u16> = (0..64).collect();
+    let _ = subset(data, 0, Profile::pdf(&glyphs));
+    let _ = subset(data, 0, Profile::web(&glyphs));
+});
@@ -0,0 +1,17 @@
+//! Subsets arbitrary bytes as a font with an arbitrary plan.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use subsetter::{subset_with_plan, Plan};
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    plan: Plan,
+    font: &'a [u8],
+}
+
+fuzz_target!(|input: Input| {
+    let _ = subset_with_plan(input.font, 0, &input.plan);
+});
@@ -0,0 +1,447 @@
+use std::collections::BTreeSet;
+
+use super::*;
+
+/// Add the glyphs that retained bitmap glyphs are made of to the plan: the
+/// components of composite bitmaps in `EBDT`, and the glyphs whose images
+/// `dupe` glyphs in `sbix` reuse.
+pub(crate) fn discover(ctx: &mut Context) -> Result<()> {
+    let mut references = vec![];
+    for (location, data) in [(Tag::CBLC, Tag::CBDT), (Tag::EBLC, Tag::EBDT)] {
+        if ctx.plan.table(location) != TableAction::Subset {
+            continue;
+        }
+        let (Some(location), Some(data)) =
+            (ctx.face.table(location), ctx.face.table(data))
+        else {
+            continue;
+        };
+        for strike in parse(location, data)? {
+            for bitmap in strike.bitmaps {
+                for (_, component) in components(&bitmap)? {
+                    references.push((bitmap.glyph, component));
+                }
+            }
+        }
+    }
+    if ctx.plan.table(Tag::SBIX) == TableAction::Subset {
+        if let Some(sbix) = ctx.face.table(Tag::SBIX) {
+            for strike in parse_sbix(sbix, ctx.num_glyphs)? {
+                for (glyph, image) in strike.images.iter().enumerate() {
+                    if let Some(target) = dupe(image)? {
+                        references.push((glyph as u16, target));
+                    }
+                }
+            }
+        }
+    }
+
+    // Components may be composite themselves, so repeat until the set of
+    // glyphs doesn't grow anymore.
+    let requested: BTreeSet<u16> = ctx.plan.glyphs.iter().copied().collect();
+    let mut glyphs = requested.clone();
+    loop {
+        let count = glyphs.len();
+        for &(glyph, reference) in &references {
+            if glyphs.contains(&glyph) && reference < ctx.num_glyphs {
+                glyphs.insert(reference);
+            }
+        }
+        if glyphs.len() == count {
+            break;
+        }
+    }
+
+    ctx.plan.glyphs.extend(glyphs.difference(&requested));
+    Ok(())
+}
+
+/// Subset a `CBLC` or `EBLC` table and its `CBDT` or `EBDT` data table to the
+/// retained glyphs.
+///
+/// The bitmaps of removed glyphs are dropped and so are strikes without any
+/// retained bitmap. The index subtables are rewritten in format 1, or in
+/// format 5 for bitmaps with shared metrics. Both tables are dropped if no
+/// bitmap glyph is retained.
+pub(crate) fn subset(ctx: &mut Context, tag: Tag) -> Result<()> {
+    let data_tag = data_tag(tag);
+    if ctx.plan.table(data_tag) == TableAction::Drop {
+        return Ok(());
+    }
+    let location = ctx.expect_table(tag)?;
+    let data = ctx.expect_table(data_tag)?;
+    let subset = &ctx.subset;
+    let tables =
+        rewrite(location, data, |glyph| subset.contains(&glyph).then_some(glyph))?;
+    if let Some((location, data)) = tables {
+        ctx.push(tag, location);
+        ctx.push(data_tag, data);
+    }
+    Ok(())
+}
+
+/// Renumber the glyphs of a subsetted `CBLC` or `EBLC` table and its data
+/// table.
+pub(crate) fn remap(
+    location: &[u8],
+    data: &[u8],
+    new_id: impl Fn(u16) -> Option<u16>,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    rewrite(location, data, new_id)?.ok_or(Error::InvalidData)
+}
+
+/// The data table that belongs to a location table.
+pub(crate) fn data_tag(location: Tag) -> Tag {
+    if location == Tag::CBLC {
+        Tag::CBDT
+    } else {
+        Tag::EBDT
+    }
+}
+
+/// Subset the `sbix` table to the retained glyphs.
+///
+/// The images of removed glyphs are emptied, while their entries in the
+/// offset arrays remain. Strikes without any retained image are dropped, and
+/// so is the table if no strike remains.
+pub(crate) fn subset_sbix(ctx: &mut Context) -> Result<()> {
+    let sbix = ctx.expect_table(Tag::SBIX)?;
+    let glyphs: Vec<Option<u16>> = (0..ctx.num_glyphs)
+        .map(|glyph| ctx.subset.contains(&glyph).then_some(glyph))
+        .collect();
+    if let Some(sbix) = rewrite_sbix(sbix, ctx.num_glyphs, &glyphs, Some)? {
+        ctx.push(Tag::SBIX, sbix);
+    }
+    Ok(())
+}
+
+/// Renumber the glyphs of a subsetted `sbix` table, keeping the images of the
+/// glyphs in the given order.
+pub(crate) fn remap_sbix(
+    sbix: &[u8],
+    num_glyphs: u16,
+    order: &[u16],
+    new_id: impl Fn(u16) -> Option<u16>,
+) -> Result<Vec<u8>> {
+    let glyphs: Vec<Option<u16>> = order.iter().copied().map(Some).collect();
+    rewrite_sbix(sbix, num_glyphs, &glyphs, new_id)?.ok_or(Error::InvalidData)
+}
+
+/// A strike of a `CBLC` or `EBLC` table.
+struct Strike<'a> {
+    /// The `BitmapSize` record.
+    record: &'a [u8],
+    /// The bitmaps of the strike.
+    bitmaps: Vec<Bitmap<'a>>,
+}
+
+/// The bitmap of a glyph in a strike.
+struct Bitmap<'a> {
+    glyph: u16,
+    /// The format of the image data.
+    format: u16,
+    /// The image data, including the metrics if the image has its own.
+    data: Cow<'a, [u8]>,
+    /// The big glyph metrics that the bitmaps of an index subtable in format
+    /// 2 or 5 share.
+    metrics: Option<&'a [u8]>,
+}
+
+/// Parse the strikes of a location table with the images in its data table.
+fn parse<'a>(location: &'a [u8], data: &'a [u8]) -> Result<Vec<Strike<'a>>> {
+    let count = u32::read_at(location, 4)? as usize;
+    let image = |offset: usize, len: usize| {
+        data.get(offset..offset + len)
+            .ok_or(Error::InvalidOffset)
+            .map(Cow::Borrowed)
+    };
+
+    let mut strikes = vec![];
+    for i in 0..count {
+        let record = location.get(8 + 48 * i..56 + 48 * i).ok_or(Error::MissingData)?;
+        let array = u32::read_at(record, 0)? as usize;
+        let subtables = u32::read_at(record, 8)? as usize;
+        let mut bitmaps = vec![];
+        for k in 0..subtables {
+            let first = u16::read_at(location, array + 8 * k)?;
+            let last = u16::read_at(location, array + 8 * k + 2)?;
+            let at = array + u32::read_at(location, array + 8 * k + 4)? as usize;
+            let index_format = u16::read_at(location, at)?;
+            let format = u16::read_at(location, at + 2)?;
+            let offset = u32::read_at(location, at + 4)? as usize;
+            let mut push = |glyph: u16, start: usize, end: usize, metrics| {
+                if start < end {
+                    let data = image(offset + start, end - start)?;
+                    bitmaps.push(Bitmap { glyph, format, data, metrics });
+                }
+                Ok::<_, Error>(())
+            };
+
+            let glyphs = first..=last.max(first);
+            match index_format {
+                1 | 3 => {
+                    let read = |i: usize| match index_format {
+                        1 => u32::read_at(location, at + 8 + 4 * i).map(|v| v as usize),
+                        _ => u16::read_at(location, at + 8 + 2 * i).map(usize::from),
+                    };
+                    for (i, glyph) in glyphs.enumerate() {
+                        push(glyph, read(i)?, read(i + 1)?, None)?;
+                    }
+                }
+                2 => {
+                    let size = u32::read_at(location, at + 8)? as usize;
+                    let metrics =
+                        location.get(at + 12..at + 20).ok_or(Error::MissingData)?;
+                    for (i, glyph) in glyphs.enumerate() {
+                        push(glyph, i * size, (i + 1) * size, Some(metrics))?;
+                    }
+                }
+                4 => {
+                    let count = u32::read_at(location, at + 8)? as usize;
+                    let pair = |i: usize| -> Result<(u16, usize)> {
+                        let glyph = u16::read_at(location, at + 12 + 4 * i)?;
+                        let offset = u16::read_at(location, at + 14 + 4 * i)?;
+                        Ok((glyph, usize::from(offset)))
+                    };
+                    for i in 0..count {
+                        let ((glyph, start), (_, end)) = (pair(i)?, pair(i + 1)?);
+                        push(glyph, start, end, None)?;
+                    }
+                }
+                5 => {
+                    let size = u32::read_at(location, at + 8)? as usize;
+                    let metrics =
+                        location.get(at + 12..at + 20).ok_or(Error::MissingData)?;
+                    let count = u32::read_at(location, at + 20)? as usize;
+                    for i in 0..count {
+                        let glyph = u16::read_at(location, at + 24 + 2 * i)?;
+                        push(glyph, i * size, (i + 1) * size, Some(metrics))?;
+                    }
+                }
+                _ => return Err(Error::InvalidData),
+            }
+        }
+        strikes.push(Strike { record, bitmaps });
+    }
+    Ok(strikes)
+}
+
+/// The positions and glyph IDs of the components of a composite bitmap.
+fn components(bitmap: &Bitmap) -> Result<Vec<(usize, u16)>> {
+    // The small metrics of format 8 are followed by a padding byte.
+    let at = match bitmap.format {
+        8 => 6,
+        9 => 8,
+        _ => return Ok(vec![]),
+    };
+    let count = usize::from(u16::read_at(&bitmap.data, at)?);
+    (0..count)
+        .map(|i| {
+            let at = at + 2 + 4 * i;
+            Ok((at, u16::read_at(&bitmap.data, at)?))
+        })
+        .collect()
+}
+
+/// Write a location table and its data table with the bitmaps of the glyphs
+/// that have a new ID, or none if there are no such bitmaps.
+fn rewrite(
+    location: &[u8],
+    data: &[u8],
+    new_id: impl Fn(u16) -> Option<u16>,
+) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let mut strikes = parse(location, data)?;
+    for strike in &mut strikes {
+        let mut bitmaps = vec![];
+        for mut bitmap in std::mem::take(&mut strike.bitmaps) {
+            let Some(glyph) = new_id(bitmap.glyph) else { continue };
+            for (at, component) in components(&bitmap)? {
+                let component = new_id(component).ok_or(Error::InvalidData)?;
+                bitmap.data.to_mut()[at..at + 2]
+                    .copy_from_slice(&component.to_be_bytes());
+            }
+            bitmaps.push(Bitmap { glyph, ..bitmap });
+        }
+        bitmaps.sort_by_key(|bitmap| bitmap.glyph);
+        strike.bitmaps = bitmaps;
+    }
+    strikes.retain(|strike| !strike.bitmaps.is_empty());
+    if strikes.is_empty() {
+        return Ok(None);
+    }
+
+    // The version of the data table.
+    let mut images = Writer::new();
+    images.give(data.get(..4).ok_or(Error::MissingData)?);
+
+    let mut records = Writer::new();
+    let mut arrays = Writer::new();
+    let mut at = 8 + 48 * strikes.len();
+    for strike in &strikes {
+        // Split the bitmaps into runs that share an index subtable: glyphs
+        // with consecutive IDs and their own metrics, or glyphs with the same
+        // shared metrics and size.
+        let runs: Vec<&[Bitmap]> = strike
+            .bitmaps
+            .chunk_by(|a, b| {
+                a.format == b.format
+                    && a.metrics == b.metrics
+                    && match a.metrics {
+                        Some(_) => a.data.len() == b.data.len(),
+                        None => a.glyph + 1 == b.glyph,
+                    }
+            })
+            .collect();
+
+        let mut subtables = Writer::new();
+        let array_len = 8 * runs.len();
+        let mut array = Writer::new();
+        for run in &runs {
+            let first = run[0].glyph;
+            let last = run[run.len() - 1].glyph;
+            array.write::<u16>(first);
+            array.write::<u16>(last);
+            array.write::<u32>((array_len + subtables.len()) as u32);
+
+            let offset = images.len();
+            subtables.write::<u16>(if run[0].metrics.is_some() { 5 } else { 1 });
+            subtables.write::<u16>(run[0].format);
+            subtables.write::<u32>(offset as u32);
+            match run[0].metrics {
+                Some(metrics) => {
+                    subtables.write::<u32>(run[0].data.len() as u32);
+                    subtables.give(metrics);
+                    subtables.write::<u32>(run.len() as u32);
+                    for bitmap in *run {
+                        subtables.write::<u16>(bitmap.glyph);
+                        images.give(&bitmap.data);
+                    }
+                    subtables.align(4);
+                }
+                None => {
+                    for bitmap in *run {
+                        subtables.write::<u32>((images.len() - offset) as u32);
+                        images.give(&bitmap.data);
+                    }
+                    subtables.write::<u32>((images.len() - offset) as u32);
+                }
+            }
+        }
+        let subtables = subtables.finish();
+        let array = array.finish();
+
+        let record = strike.record;
+        records.write::<u32>(at as u32);
+        records.write::<u32>((array.len() + subtables.len()) as u32);
+        records.write::<u32>(runs.len() as u32);
+        records.give(&record[12..40]);
+        records.write::<u16>(strike.bitmaps[0].glyph);
+        records.write::<u16>(strike.bitmaps[strike.bitmaps.len() - 1].glyph);
+        records.give(&record[44..48]);
+        arrays.give(&array);
+        arrays.give(&subtables);
+        at += array.len() + subtables.len();
+    }
+
+    let mut w = Writer::new();
+    w.give(location.get(..4).ok_or(Error::MissingData)?);
+    w.write::<u32>(strikes.len() as u32);
+    w.give(&records.finish());
+    w.give(&arrays.finish());
+    Ok(Some((w.finish(), images.finish())))
+}
+
+/// A strike of an `sbix` table.
+struct SbixStrike<'a> {
+    ppem: u16,
+    ppi: u16,
+    /// The image of each glyph, empty for glyphs without one.
+    images: Vec<&'a [u8]>,
+}
+
+/// Parse the strikes of an `sbix` table.
+fn parse_sbix(sbix: &[u8], num_glyphs: u16) -> Result<Vec<SbixStrike<'_>>> {
+    let count = u32::read_at(sbix, 4)? as usize;
+    (0..count)
+        .map(|i| {
+            let at = u32::read_at(sbix, 8 + 4 * i)? as usize;
+            let strike = sbix.get(at..).ok_or(Error::InvalidOffset)?;
+            let offset =
+                |glyph: usize| u32::read_at(strike, 4 + 4 * glyph).map(|v| v as usize);
+            let images = (0..usize::from(num_glyphs))
+                .map(|glyph| {
+                    let (start, end) = (offset(glyph)?, offset(glyph + 1)?);
+                    match start < end {
+                        true => strike.get(start..end).ok_or(Error::InvalidOffset),
+                        false => Ok(&[][..]),
+                    }
+                })
+                .collect::<Result<_>>()?;
+            let ppem = u16::read_at(strike, 0)?;
+            let ppi = u16::read_at(strike, 2)?;
+            Ok(SbixStrike { ppem, ppi, images })
+        })
+        .collect()
+}
+
+/// The glyph whose image a `dupe` image reuses.
+fn dupe(image: &[u8]) -> Result<Option<u16>> {
+    match image.get(4..8) {
+        Some(b"dupe") => u16::read_at(image, 8).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Write an `sbix` table whose new glyph `i` has the images of the old glyph
+/// `glyphs[i]`, or none if no strike has any of those images.
+fn rewrite_sbix(
+    sbix: &[u8],
+    num_glyphs: u16,
+    glyphs: &[Option<u16>],
+    new_id: impl Fn(u16) -> Option<u16>,
+) -> Result<Option<Vec<u8>>> {
+    let mut strikes = vec![];
+    for strike in parse_sbix(sbix, num_glyphs)? {
+        let mut images: Vec<Cow<[u8]>> = vec![];
+        for glyph in glyphs {
+            let image = glyph
+                .and_then(|glyph| strike.images.get(usize::from(glyph)).copied())
+                .unwrap_or_default();
+            let mut image = Cow::Borrowed(image);
+            if let Some(target) = dupe(&image)? {
+                let target = new_id(target).ok_or(Error::InvalidData)?;
+                image.to_mut()[8..10].copy_from_slice(&target.to_be_bytes());
+            }
+            images.push(image);
+        }
+        if images.iter().any(|image| !image.is_empty()) {
+            strikes.push((strike.ppem, strike.ppi, images));
+        }
+    }
+    if strikes.is_empty() {
+        return Ok(None);
+    }
+
+    let mut w = Writer::new();
+    w.give(sbix.get(..4).ok_or(Error::MissingData)?);
+    w.write::<u32>(strikes.len() as u32);
+    let mut offset = 8 + 4 * strikes.len();
+    for (_, _, images) in &strikes {
+        w.write::<u32>(offset as u32);
+        offset += 4 + 4 * (images.len() + 1);
+        offset += images.iter().map(|image| image.len()).sum::<usize>();
+    }
+    for (ppem, ppi, images) in &strikes {
+        w.write::<u16>(*ppem);
+        w.write::<u16>(*ppi);
+        let mut offset = 4 + 4 * (images.len() + 1);
+        for image in images.iter().map(|image| image.len()).chain([0]) {
+            w.write::<u32>(offset as u32);
+            offset += image;
+        }
+        for image in images {
+            w.give(image);
+        }
+    }
+    Ok(Some(w.finish()))
+}
@@ -0,0 +1,257 @@
+//! Reuse of glyph set independent work across subsets of the same font.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::*;
+
+/// Caches intermediate artifacts that don't depend on the glyph set, so that
+/// repeatedly subsetting the same font is cheaper.
+///
+/// Currently, this caches the decompressed data of WOFF inputs and WOFF2
+/// inputs (with the `woff2` feature), the parsed character mappings of the
+/// `cmap` table and the parsed glyph offsets of the `loca` table.
+///
+/// Fonts are identified either by an ID the caller assigns with
+/// [`subset_by_id`](Self::subset_by_id), or by a hash of their data with
+/// [`subset`](Self::subset), which has to read the whole font on every call.
+/// Together with the face index, this forms the key of the cached artifacts.
+/// The cache keeps the most recently used fonts and evicts the least recently
+/// used ones once the cached artifacts exceed the capacity.
+///
+/// ```
+/// use subsetter::{Profile, SubsetCache};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let data = std::fs::read("fonts/NotoSans-Regular.ttf")?;
+/// let mut cache = SubsetCache::new(16 * 1024 * 1024);
+/// for glyphs in [&[68, 69][..], &[70, 71]] {
+///     cache.subset_by_id(1, &data, 0, Profile::pdf(glyphs))?;
+/// }
+/// assert_eq!(cache.stats().hits, 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SubsetCache {
+    /// The maximum total size of the cached artifacts in bytes.
+    capacity: usize,
+    /// The cached fonts, least recently used first.
+    entries: Vec<Entry>,
+    /// Counters for the cache's activity.
+    stats: CacheStats,
+}
+
+/// Statistics about a [`SubsetCache`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct CacheStats {
+    /// How often a font was found in the cache.
+    pub hits: u64,
+    /// How often a font was not found in the cache.
+    pub misses: u64,
+    /// How many fonts were evicted to stay within the capacity.
+    pub evictions: u64,
+    /// The number of fonts currently in the cache.
+    pub entries: usize,
+    /// The total size of the currently cached artifacts in bytes.
+    pub bytes: usize,
+}
+
+/// Identifies a font face in the cache.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Key {
+    /// A hash of the font data, with the face index.
+    Hash(u64, u32),
+    /// An ID assigned by the caller, with the face index.
+    Id(u64, u32),
+}
+
+/// The cached artifacts of a single font face.
+#[derive(Debug, Clone)]
+struct Entry {
+    /// The font face the artifacts belong to.
+    key: Key,
+    /// The decompressed font, if the input was compressed.
+    font: Option<Arc<Vec<u8>>>,
+    /// The parsed character mappings, once they were needed.
+    mappings: Option<Arc<Vec<(u32, u16)>>>,
+    /// The parsed glyph offsets of the loca table, once they were needed.
+    loca: Option<Arc<Vec<u32>>>,
+}
+
+impl Entry {
+    /// The approximate memory used by the artifacts.
+    fn size(&self) -> usize {
+        self.font.as_ref().map_or(0, |font| font.len())
+            + self
+                .mappings
+                .as_ref()
+                .map_or(0, |mappings| mappings.len() * std::mem::size_of::<(u32, u16)>())
+            + self.loca.as_ref().map_or(0, |loca| loca.len() * 4)
+    }
+}
+
+impl SubsetCache {
+    /// Create an empty cache that holds at most `capacity` bytes of artifacts.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: vec![],
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Subset a font face like [`subset`](crate::subset), reusing cached
+    /// artifacts.
+    ///
+    /// Unlike [`subset`](crate::subset), this also accepts WOFF data, and WOFF2
+    /// data if the `woff2` feature is enabled. The font is identified by a
+    /// hash of its data, so prefer [`subset_by_id`](Self::subset_by_id) for
+    /// large fonts.
+    pub fn subset(
+        &mut self,
+        data: &[u8],
+        index: u32,
+        profile: Profile,
+    ) -> Result<Vec<u8>> {
+        let key = Key::Hash(hash(data), index);
+        self.run(key, data, index, |face| Plan::new(face, &profile))
+    }
+
+    /// Subset a font face like [`subset`](Self::subset), but identify the
+    /// font by an ID of the caller's choosing instead of hashing its data.
+    ///
+    /// The caller must pass the same data for the same ID, otherwise the
+    /// artifacts of a different font are reused.
+    pub fn subset_by_id(
+        &mut self,
+        id: u64,
+        data: &[u8],
+        index: u32,
+        profile: Profile,
+    ) -> Result<Vec<u8>> {
+        let key = Key::Id(id, index);
+        self.run(key, data, index, |face| Plan::new(face, &profile))
+    }
+
+    /// Subset a font face like [`subset_with_plan`](crate::subset_with_plan),
+    /// reusing cached artifacts.
+    pub fn subset_with_plan(
+        &mut self,
+        data: &[u8],
+        index: u32,
+        plan: &Plan,
+    ) -> Result<Vec<u8>> {
+        let key = Key::Hash(hash(data), index);
+        self.run(key, data, index, |_| plan.clone())
+    }
+
+    /// Subset a font face like [`subset_with_plan`](Self::subset_with_plan),
+    /// but identify the font by an ID like [`subset_by_id`](Self::subset_by_id).
+    pub fn subset_with_plan_by_id(
+        &mut self,
+        id: u64,
+        data: &[u8],
+        index: u32,
+        plan: &Plan,
+    ) -> Result<Vec<u8>> {
+        self.run(Key::Id(id, index), data, index, |_| plan.clone())
+    }
+
+    /// Statistics about the cache's activity and current contents.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            bytes: self.entries.iter().map(Entry::size).sum(),
+            ..self.stats
+        }
+    }
+
+    /// Remove all cached artifacts. The counters are kept.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn run(
+        &mut self,
+        key: Key,
+        data: &[u8],
+        index: u32,
+        plan: impl FnOnce(&Face) -> Plan,
+    ) -> Result<Vec<u8>> {
+        let entry = self.lookup(key, data)?;
+        let font = entry.font.as_ref().map_or(data, |font| font.as_slice());
+        let face = parse(font, index)?;
+        let plan = plan(&face);
+
+        // The loca offsets are needed while preparing, so they're handed over
+        // before that and the mappings only afterwards.
+        let mut ctx = prepare_with_loca(face, plan, entry.loca.clone())?;
+        ctx.mappings = entry.mappings.clone();
+        ctx.build_tables()?;
+
+        let mappings = ctx.mappings.clone().filter(|_| entry.mappings.is_none());
+        let loca = ctx.loca.clone().filter(|_| entry.loca.is_none());
+        if mappings.is_some() || loca.is_some() {
+            if let Some(cached) = self.entries.iter_mut().find(|e| e.key == key) {
+                cached.mappings = cached.mappings.take().or(mappings);
+                cached.loca = cached.loca.take().or(loca);
+            }
+            self.evict();
+        }
+
+        Ok(ctx.construct())
+    }
+
+    /// Find or create the entry for a font face and mark it as most recently
+    /// used.
+    fn lookup(&mut self, key: Key, data: &[u8]) -> Result<Entry> {
+        if let Some(i) = self.entries.iter().position(|entry| entry.key == key) {
+            self.stats.hits += 1;
+            let entry = self.entries.remove(i);
+            self.entries.push(entry.clone());
+            return Ok(entry);
+        }
+
+        self.stats.misses += 1;
+        let entry = Entry {
+            key,
+            font: decompress(data)?.map(Arc::new),
+            mappings: None,
+            loca: None,
+        };
+        self.entries.push(entry.clone());
+        self.evict();
+        Ok(entry)
+    }
+
+    /// Evict the least recently used entries until the cache fits its
+    /// capacity. An entry that alone exceeds the capacity isn't retained.
+    fn evict(&mut self) {
+        let mut bytes: usize = self.entries.iter().map(Entry::size).sum();
+        while bytes > self.capacity && !self.entries.is_empty() {
+            bytes -= self.entries.remove(0).size();
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+/// Hash the font data to identify it when the caller doesn't.
+fn hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Decompress the font data if it is a WOFF file, or a WOFF2 file with the
+/// `woff2` feature.
+fn decompress(data: &[u8]) -> Result<Option<Vec<u8>>> {
+    if data.starts_with(b"wOFF") {
+        return woff1::woff_to_ttf(data).map(Some);
+    }
+    #[cfg(feature = "woff2")]
+    if data.starts_with(b"wOF2") {
+        return woff::woff2_to_ttf(data).map(Some);
+    }
+    Ok(None)
+}
@@ -0,0 +1,385 @@
+use super::*;
+
+/// A CFF2 table.
+struct Table<'a> {
+    top: Dict<'a>,
+    global_subrs: Index<Opaque<'a>>,
+    char_strings: Index<Opaque<'a>>,
+    variation_store: Option<Opaque<'a>>,
+    select: Option<Opaque<'a>>,
+    array: Index<Dict<'a>>,
+    private: Vec<PrivateData<'a>>,
+}
+
+/// Recorded offsets that will be written into DICTs.
+struct Offsets {
+    char_strings: usize,
+    variation_store: Option<usize>,
+    select: Option<usize>,
+    array: usize,
+    private: Vec<PrivateOffsets>,
+}
+
+/// Subset the CFF2 table by removing glyph data for unused glyphs.
+///
+/// The Private DICTs may contain `blend` operators, which are kept as they
+/// are, and the Item Variation Store is copied unchanged since the retained
+/// charstrings still refer to all of its regions.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let cff2 = ctx.expect_table(Tag::CFF2)?;
+
+    // Check version.
+    let major = u8::read_at(cff2, 0)?;
+    if major != 2 {
+        ctx.push(Tag::CFF2, cff2);
+        return Ok(());
+    }
+
+    // Parse CFF2 table.
+    let mut table = read_cff2_table(cff2)?;
+
+    // Subset the char strings. An empty charstring is valid in CFF2, which
+    // has no `endchar` operator.
+    for glyph in 0..ctx.num_glyphs {
+        if !ctx.subset.contains(&glyph) {
+            *table
+                .char_strings
+                .get_mut(glyph as usize)
+                .ok_or(Error::InvalidOffset)? = Opaque(&[]);
+        }
+    }
+
+    // Remove subroutines for unused Private DICTs.
+    let used = used_font_dicts(ctx, table.select.as_ref())?;
+    for (i, private) in table.private.iter_mut().enumerate() {
+        if !used.contains(&i) {
+            private.subrs = None;
+        }
+    }
+
+    ctx.push(Tag::CFF2, construct_cff2_table(&mut table));
+
+    Ok(())
+}
+
+/// Renumber the glyphs of a subsetted CFF2 table, so that the glyph with the
+/// new ID `i` is the glyph `order[i]`.
+pub(crate) fn remap(cff2: &[u8], order: &[u16]) -> Result<Vec<u8>> {
+    // Other versions are copied verbatim and can't be renumbered.
+    if u8::read_at(cff2, 0)? != 2 {
+        return Err(Error::InvalidData);
+    }
+
+    let mut table = read_cff2_table(cff2)?;
+
+    let mut char_strings = vec![];
+    for &glyph in order {
+        let string = table.char_strings.get(usize::from(glyph));
+        char_strings.push(Opaque(string.ok_or(Error::InvalidOffset)?.0));
+    }
+    table.char_strings = Index(char_strings);
+
+    // The reordered FD Select is always written with ranges.
+    let mut select = vec![];
+    if let Some(Opaque(data)) = &table.select {
+        let fds = order
+            .iter()
+            .map(|&glyph| font_dict(data, usize::from(glyph)))
+            .collect::<Result<Vec<_>>>()?;
+        select = write_fd_select(&fds);
+        table.select = Some(Opaque(&select));
+    }
+
+    Ok(construct_cff2_table(&mut table))
+}
+
+/// Construct a new CFF2 table.
+fn construct_cff2_table(table: &mut Table) -> Vec<u8> {
+    let mut sub_cff2 = vec![];
+    let mut offsets = Offsets {
+        char_strings: 0,
+        variation_store: table.variation_store.as_ref().map(|_| 0),
+        select: table.select.as_ref().map(|_| 0),
+        array: 0,
+        private: table
+            .private
+            .iter()
+            .map(|private| PrivateOffsets {
+                dict: 0..0,
+                subrs: private.subrs.as_ref().map(|_| 0),
+            })
+            .collect(),
+    };
+
+    // Write twice because we first need to find out the offsets of various data
+    // structures.
+    for _ in 0..2 {
+        let mut w = Writer::new();
+        insert_offsets(table, &offsets);
+        write_cff2_table(&mut w, table, &mut offsets);
+        sub_cff2 = w.finish();
+    }
+
+    sub_cff2
+}
+
+/// Parse a CFF2 table.
+fn read_cff2_table(cff2: &[u8]) -> Result<Table<'_>> {
+    // Read header.
+    let mut r = Reader::new(cff2);
+    r.read::<u8>()?;
+    r.read::<u8>()?;
+    let header_size = r.read::<u8>()? as usize;
+    let top_size = r.read::<u16>()? as usize;
+
+    // The Top DICT isn't stored in an INDEX, but directly after the header.
+    let top_data = cff2
+        .get(header_size..header_size + top_size)
+        .ok_or(Error::InvalidOffset)?;
+    let top = Dict::read_at(top_data, 0)?;
+
+    let mut r =
+        Reader::new(cff2.get(header_size + top_size..).ok_or(Error::InvalidOffset)?);
+    let global_subrs = Index::read_wide(&mut r)?;
+
+    // Read the glyph descriptions.
+    let char_strings = {
+        let offset = top.get_offset(top::CHAR_STRINGS).ok_or(Error::MissingData)?;
+        read_index(cff2, offset)?
+    };
+
+    // Read the Item Variation Store, which is prefixed with its length.
+    let mut variation_store = None;
+    if let Some(offset) = top.get_offset(top::VARIATION_STORE) {
+        let len = u16::read_at(cff2, offset)? as usize;
+        let data = cff2.get(offset..offset + 2 + len).ok_or(Error::InvalidOffset)?;
+        variation_store = Some(Opaque(data));
+    }
+
+    // Read FD Select data structure, which is optional with a single Font DICT.
+    let mut select = None;
+    if let Some(offset) = top.get_offset(top::FD_SELECT) {
+        let sub = cff2.get(offset..).ok_or(Error::InvalidOffset)?;
+        select = Some(read_fd_select(sub, char_strings.len())?);
+    }
+
+    // Read FD Array.
+    let array: Index<Dict> = {
+        let offset = top.get_offset(top::FD_ARRAY).ok_or(Error::MissingData)?;
+        read_index(cff2, offset)?
+    };
+
+    // Read Private DICTs.
+    let mut private = vec![];
+    for dict in array.iter() {
+        let range = dict.get_range(top::PRIVATE).ok_or(Error::MissingData)?;
+        let start = range.start;
+        let sub = cff2.get(range).ok_or(Error::InvalidOffset)?;
+        let dict = Dict::read_at(sub, 0)?;
+
+        let mut subrs = None;
+        if let Some(offset) = dict.get_offset(private::SUBRS) {
+            subrs = Some(read_index(cff2, start + offset)?);
+        }
+
+        private.push(PrivateData { dict, subrs });
+    }
+
+    Ok(Table {
+        top,
+        global_subrs,
+        char_strings,
+        variation_store,
+        select,
+        array,
+        private,
+    })
+}
+
+/// Write a new CFF2 table.
+fn write_cff2_table(w: &mut Writer, table: &Table, offsets: &mut Offsets) {
+    let mut top = Writer::new();
+    top.write_ref(&table.top);
+    let top = top.finish();
+
+    // Write header.
+    w.write::<u8>(2);
+    w.write::<u8>(0);
+    w.write::<u8>(5);
+    w.write::<u16>(top.len() as u16);
+    w.inspect("Header");
+
+    w.give(&top);
+    w.inspect("Top DICT");
+
+    table.global_subrs.write_wide(w);
+    w.inspect("Global Subroutine INDEX");
+
+    // Write char strings.
+    offsets.char_strings = w.len();
+    table.char_strings.write_wide(w);
+    w.inspect("Charstring INDEX");
+
+    // Write the variation store.
+    if let Some(store) = &table.variation_store {
+        offsets.variation_store = Some(w.len());
+        w.write_ref(store);
+        w.inspect("Variation Store");
+    }
+
+    // Write FD Select.
+    if let Some(select) = &table.select {
+        offsets.select = Some(w.len());
+        w.write_ref(select);
+        w.inspect("FD Select");
+    }
+
+    // Write FD Array.
+    offsets.array = w.len();
+    table.array.write_wide(w);
+    w.inspect("FD Array");
+
+    // Write Private DICTs.
+    for (private, offsets) in table.private.iter().zip(&mut offsets.private) {
+        offsets.dict.start = w.len();
+        w.write_ref(&private.dict);
+        offsets.dict.end = w.len();
+        w.inspect("Private DICT");
+
+        // Write local subroutines.
+        if let Some(subrs) = &private.subrs {
+            offsets.subrs = Some(w.len() - offsets.dict.start);
+            subrs.write_wide(w);
+            w.inspect("Local Subroutine INDEX");
+        }
+    }
+}
+
+/// Insert the offsets of various parts of the font into the relevant DICTs.
+fn insert_offsets(table: &mut Table, offsets: &Offsets) {
+    table.top.set_offset(top::CHAR_STRINGS, offsets.char_strings);
+
+    if let Some(offset) = offsets.variation_store {
+        table.top.set_offset(top::VARIATION_STORE, offset);
+    }
+
+    if let Some(offset) = offsets.select {
+        table.top.set_offset(top::FD_SELECT, offset);
+    }
+
+    table.top.set_offset(top::FD_ARRAY, offsets.array);
+
+    for (dict, offsets) in table.array.iter_mut().zip(&offsets.private) {
+        dict.set_range(top::PRIVATE, &offsets.dict);
+    }
+
+    for (private, offsets) in table.private.iter_mut().zip(&offsets.private) {
+        match offsets.subrs {
+            Some(offset) => private.dict.set_offset(private::SUBRS, offset),
+            None => private.dict.remove(private::SUBRS),
+        }
+    }
+}
+
+/// Read a CFF2 INDEX at the given offset.
+fn read_index<'a, T: Structure<'a>>(cff2: &'a [u8], offset: usize) -> Result<Index<T>> {
+    let mut r = Reader::new(cff2.get(offset..).ok_or(Error::InvalidOffset)?);
+    Index::read_wide(&mut r)
+}
+
+/// Read the FD Select data structure.
+fn read_fd_select(data: &[u8], num_glyphs: usize) -> Result<Opaque<'_>> {
+    let len = match u8::read_at(data, 0)? {
+        0 => 1 + num_glyphs,
+        3 => 1 + 2 + 3 * u16::read_at(data, 1)? as usize + 2,
+        4 => 1 + 4 + 6 * u32::read_at(data, 1)? as usize + 4,
+        _ => return Err(Error::InvalidData),
+    };
+    Ok(Opaque(data.get(..len).ok_or(Error::InvalidOffset)?))
+}
+
+/// Determine the indices of the Font DICTs used by the retained glyphs.
+fn used_font_dicts(ctx: &Context, select: Option<&Opaque>) -> Result<HashSet<usize>> {
+    let Some(Opaque(select)) = select else {
+        return Ok(HashSet::from([0]));
+    };
+
+    let mut used = HashSet::new();
+    for &glyph in &ctx.subset {
+        used.insert(font_dict(select, usize::from(glyph))?);
+    }
+
+    Ok(used)
+}
+
+/// Look up the index of the Font DICT of a glyph in the FD Select data.
+fn font_dict(select: &[u8], glyph: usize) -> Result<usize> {
+    match u8::read_at(select, 0)? {
+        0 => Ok(usize::from(u8::read_at(select, 1 + glyph)?)),
+        3 => {
+            let count = u16::read_at(select, 1)? as usize;
+            let ranges = (0..count).map(|i| {
+                let first = u16::read_at(select, 3 + 3 * i)? as usize;
+                let fd = u8::read_at(select, 5 + 3 * i)? as usize;
+                Ok((first, fd))
+            });
+            find_range(ranges, glyph)
+        }
+        _ => {
+            let count = u32::read_at(select, 1)? as usize;
+            let ranges = (0..count).map(|i| {
+                let first = u32::read_at(select, 5 + 6 * i)? as usize;
+                let fd = u16::read_at(select, 9 + 6 * i)? as usize;
+                Ok((first, fd))
+            });
+            find_range(ranges, glyph)
+        }
+    }
+}
+
+/// Write FD Select data for the given Font DICT indices of all glyphs, in
+/// format 3 if all indices fit into a byte and format 4 otherwise.
+fn write_fd_select(fds: &[usize]) -> Vec<u8> {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for (glyph, &fd) in fds.iter().enumerate() {
+        if ranges.last().is_none_or(|&(_, last)| last != fd) {
+            ranges.push((glyph, fd));
+        }
+    }
+
+    let mut w = Writer::new();
+    if fds.iter().all(|&fd| fd <= usize::from(u8::MAX)) {
+        w.write::<u8>(3);
+        w.write::<u16>(ranges.len() as u16);
+        for (first, fd) in ranges {
+            w.write::<u16>(first as u16);
+            w.write::<u8>(fd as u8);
+        }
+        w.write::<u16>(fds.len() as u16);
+    } else {
+        w.write::<u8>(4);
+        w.write::<u32>(ranges.len() as u32);
+        for (first, fd) in ranges {
+            w.write::<u32>(first as u32);
+            w.write::<u16>(fd as u16);
+        }
+        w.write::<u32>(fds.len() as u32);
+    }
+    w.finish()
+}
+
+/// Find the Font DICT of the last range starting at or before the glyph.
+fn find_range(
+    ranges: impl Iterator<Item = Result<(usize, usize)>>,
+    glyph: usize,
+) -> Result<usize> {
+    let mut found = None;
+    for range in ranges {
+        let (first, fd) = range?;
+        if first > glyph {
+            break;
+        }
+        found = Some(fd);
+    }
+    found.ok_or(Error::InvalidData)
+}
@@ -37,6 +37,10 @@ impl<'a> Dict<'a> {
         self.0.retain(|pair| ops.contains(&pair.op));
     }
 
+    pub fn remove(&mut self, op: Op) {
+        self.0.retain(|pair| pair.op != op);
+    }
+
     pub fn set(&mut self, op: Op, operands: Vec<Operand<'a>>) {
         if let Some(pair) = self.0.iter_mut().find(|pair| pair.op == op) {
             pair.operands = operands;
@@ -91,7 +95,7 @@ impl<'a> Structure<'a> for Pair<'a> {
         let mut operands = vec![];
         loop {
             match r.data().first().ok_or(Error::MissingData)? {
-                0..=21 => break,
+                0..=27 => break,
                 28..=30 | 32..=254 => operands.push(r.read::<Operand>()?),
                 _ => r.skip(1)?,
             }
@@ -122,7 +126,7 @@ impl Structure<'_> for Op {
         let b0 = r.read::<u8>()?;
         match b0 {
             12 => Ok(Self(b0, r.read::<u8>()?)),
-            0..=21 => Ok(Self(b0, 0)),
+            0..=27 => Ok(Self(b0, 0)),
             _ => panic!("cannot read operator here"),
         }
     }
@@ -250,6 +254,9 @@ pub mod top {
     pub const FD_ARRAY: Op = Op(12, 36);
     pub const FD_SELECT: Op = Op(12, 37);
     pub const FONT_NAME: Op = Op(12, 38);
+
+    // CFF2 fonts.
+    pub const VARIATION_STORE: Op = Op(24, 0);
 }
 
 /// Private DICT operators.
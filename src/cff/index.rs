@@ -19,19 +19,32 @@ impl<T> Index<T> {
     }
 }
 
-impl<'a, T> Structure<'a> for Index<T>
+impl<'a, T> Index<T>
 where
     T: Structure<'a>,
 {
-    fn read(r: &mut Reader<'a>) -> Result<Self> {
+    /// Read an INDEX with a 32-bit count, as used in CFF2 tables.
+    pub fn read_wide(r: &mut Reader<'a>) -> Result<Self> {
+        Self::read_with_count(r, 4)
+    }
+
+    /// Write an INDEX with a 32-bit count, as used in CFF2 tables.
+    pub fn write_wide(&self, w: &mut Writer) {
+        self.write_with_count(w, 4);
+    }
+
+    fn read_with_count(r: &mut Reader<'a>, count_size: usize) -> Result<Self> {
         let data = r.data();
-        let count = r.read::<u16>()? as usize;
+        let count = match count_size {
+            4 => r.read::<u32>()? as usize,
+            _ => r.read::<u16>()? as usize,
+        };
         if count == 0 {
             return Ok(Self(vec![]));
         }
 
         let offsize = r.read::<Offsize>()? as usize;
-        let base = 3 + offsize * (count + 1) - 1;
+        let base = count_size + 1 + offsize * (count + 1) - 1;
         let mut read_offset = || {
             let mut bytes: [u8; 4] = [0; 4];
             bytes[4 - offsize..4].copy_from_slice(r.take(offsize)?);
@@ -53,8 +66,11 @@ where
         Ok(Self(objects))
     }
 
-    fn write(&self, w: &mut Writer) {
-        w.write::<u16>(self.0.len() as u16);
+    fn write_with_count(&self, w: &mut Writer, count_size: usize) {
+        match count_size {
+            4 => w.write::<u32>(self.0.len() as u32),
+            _ => w.write::<u16>(self.0.len() as u16),
+        }
         if self.0.is_empty() {
             return;
         }
@@ -82,6 +98,19 @@ where
     }
 }
 
+impl<'a, T> Structure<'a> for Index<T>
+where
+    T: Structure<'a>,
+{
+    fn read(r: &mut Reader<'a>) -> Result<Self> {
+        Self::read_with_count(r, 2)
+    }
+
+    fn write(&self, w: &mut Writer) {
+        self.write_with_count(w, 2);
+    }
+}
+
 impl<T: Debug> Debug for Index<T> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.debug_list().entries(&self.0).finish()
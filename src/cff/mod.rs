@@ -1,3 +1,4 @@
+pub(crate) mod cff2;
 mod dict;
 mod index;
 
@@ -61,11 +62,13 @@ struct CidOffsets {
 }
 
 /// Find all glyphs referenced through components.
-/// CFF doesn't used component glyphs, so it's just the profile's set.
+/// CFF doesn't used component glyphs, so it's just the plan's set plus the
+/// .notdef glyph.
 ///
 /// TODO: What about seac?
 pub(crate) fn discover(ctx: &mut Context) {
-    ctx.subset = ctx.profile.glyphs.iter().copied().collect();
+    ctx.subset = ctx.plan.glyphs.iter().copied().collect();
+    ctx.subset.insert(0);
 }
 
 /// Subset the CFF table by removing glyph data for unused glyphs.
@@ -86,16 +89,115 @@ pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
     // Subset the char strings.
     subset_char_strings(ctx, &mut table.char_strings)?;
 
-    // Subset Top and Private DICT.
+    // Subset data specific to CID-keyed fonts.
+    if let Some(cid) = &mut table.cid {
+        subset_font_dicts(ctx, cid)?;
+    }
+
+    // Subset Top and Private DICTs and construct a new CFF table.
+    retain_dicts(&mut table);
+    ctx.push(Tag::CFF, construct_cff_table(&mut table));
+
+    Ok(())
+}
+
+/// Renumber the glyphs of a subsetted CFF table, so that the glyph with the
+/// new ID `i` is the glyph `order[i]`.
+///
+/// The charset is rewritten in the new order. The encoding is dropped, since
+/// it refers to glyph IDs as well and OpenType consumers use the `cmap` table
+/// instead.
+pub(crate) fn remap(ctx: &Context, cff: &[u8], order: &[u16]) -> Result<Vec<u8>> {
+    // Other versions are copied verbatim and can't be renumbered.
+    if u8::read_at(cff, 0)? != 1 {
+        return Err(Error::InvalidData);
+    }
+
+    let mut table = read_cff_table(ctx, cff)?;
+    let reorder = |glyphs: &[u8]| {
+        order
+            .iter()
+            .map(|&glyph| glyphs.get(usize::from(glyph)).copied())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::MissingData)
+    };
+
+    let mut char_strings = vec![];
+    for &glyph in order {
+        let string = table.char_strings.get(usize::from(glyph));
+        char_strings.push(Opaque(string.ok_or(Error::InvalidOffset)?.0));
+    }
+    table.char_strings = Index(char_strings);
+
+    // Without a charset, the glyphs have the predefined ISOAdobe names, whose
+    // string IDs equal the glyph IDs.
+    let ids = match &table.charset {
+        Some(charset) => read_charset_ids(charset.0, ctx.num_glyphs)?,
+        None => (0..ctx.num_glyphs).collect(),
+    };
+    let mut charset = Writer::new();
+    charset.write::<u8>(0);
+    for &glyph in order.iter().skip(1) {
+        charset.write::<u16>(*ids.get(usize::from(glyph)).ok_or(Error::MissingData)?);
+    }
+    let charset = charset.finish();
+    table.charset = Some(Opaque(&charset));
+    table.encoding = None;
+
+    if let Some(cid) = &mut table.cid {
+        cid.select = FdSelect(Cow::Owned(reorder(&cid.select.0)?));
+    }
+
+    retain_dicts(&mut table);
+    Ok(construct_cff_table(&mut table))
+}
+
+/// Read the glyph names of a CFF table, in the same form as
+/// [`post::names`](crate::post::names). Standard strings are identified by
+/// their string ID. CID-keyed fonts have no glyph names.
+pub(crate) fn glyph_names<'a>(
+    ctx: &Context,
+    cff: &'a [u8],
+) -> Result<post::GlyphNames<'a>> {
+    if u8::read_at(cff, 0)? != 1 {
+        return Ok(vec![]);
+    }
+
+    let table = read_cff_table(ctx, cff)?;
+    if table.cid.is_some() {
+        return Ok(vec![]);
+    }
+
+    // Without a charset, the glyphs have the predefined ISOAdobe names.
+    let ids = match &table.charset {
+        Some(charset) => read_charset_ids(charset.0, ctx.num_glyphs)?,
+        None => (0..ctx.num_glyphs).collect(),
+    };
+
+    let mut names = vec![];
+    for sid in ids {
+        names.push(match sid {
+            0 => None,
+            1..=390 => Some(Cow::Owned(vec![0, (sid >> 8) as u8, sid as u8])),
+            _ => {
+                let name = table.strings.get(usize::from(sid - 391));
+                Some(Cow::Borrowed(name.ok_or(Error::InvalidOffset)?.0))
+            }
+        });
+    }
+
+    Ok(names)
+}
+
+/// Reduce the Top, Font and Private DICTs to the operators worth keeping. The
+/// offsets are inserted again when the table is constructed.
+fn retain_dicts(table: &mut Table) {
     table.top.retain(top::KEEP);
     if let Some(private) = &mut table.private {
         private.dict.retain(private::KEEP);
     }
 
-    // Subset data specific to CID-keyed fonts.
     if let Some(cid) = &mut table.cid {
-        subset_font_dicts(ctx, cid)?;
-
         for dict in cid.array.iter_mut() {
             dict.retain(top::KEEP);
         }
@@ -104,23 +206,23 @@ pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
             private.dict.retain(private::KEEP);
         }
     }
+}
 
-    // Construct a new CFF table.
+/// Construct a new CFF table.
+fn construct_cff_table(table: &mut Table) -> Vec<u8> {
     let mut sub_cff = vec![];
-    let mut offsets = create_offsets(&table);
+    let mut offsets = create_offsets(table);
 
     // Write twice because we first need to find out the offsets of various data
     // structures.
     for _ in 0..2 {
         let mut w = Writer::new();
-        insert_offsets(&mut table, &offsets);
-        write_cff_table(&mut w, &table, &mut offsets);
+        insert_offsets(table, &offsets);
+        write_cff_table(&mut w, table, &mut offsets);
         sub_cff = w.finish();
     }
 
-    ctx.push(Tag::CFF, sub_cff);
-
-    Ok(())
+    sub_cff
 }
 
 /// Subset the glyph descriptions.
@@ -140,7 +242,7 @@ fn subset_char_strings(ctx: &Context, strings: &mut Index<Opaque>) -> Result<()>
 fn subset_font_dicts(ctx: &Context, cid: &mut CidData) -> Result<()> {
     // Determine which subroutine indices to keep.
     let mut kept_subrs = HashSet::new();
-    for &glyph in ctx.profile.glyphs {
+    for &glyph in &ctx.subset {
         kept_subrs
             .insert(*cid.select.0.get(usize::from(glyph)).ok_or(Error::MissingData)?);
     }
@@ -155,6 +257,18 @@ fn subset_font_dicts(ctx: &Context, cid: &mut CidData) -> Result<()> {
     Ok(())
 }
 
+/// The size of each glyph's charstring in a CFF table, not counting the
+/// subroutines it calls.
+pub(crate) fn char_string_sizes(cff: &[u8]) -> Result<Vec<usize>> {
+    let header_size = u8::read_at(cff, 2)? as usize;
+    let mut r = Reader::new(cff.get(header_size..).ok_or(Error::InvalidOffset)?);
+    r.read::<Index<Opaque>>()?;
+    let top = r.read::<Index<Dict>>()?.into_one().ok_or(Error::MissingData)?;
+    let offset = top.get_offset(top::CHAR_STRINGS).ok_or(Error::MissingData)?;
+    let char_strings = Index::<Opaque>::read_at(cff, offset)?;
+    Ok(char_strings.0.iter().map(|string| string.0.len()).collect())
+}
+
 /// Parse a CFF table.
 fn read_cff_table<'a>(ctx: &Context, cff: &'a [u8]) -> Result<Table<'a>> {
     // Skip header.
@@ -410,6 +524,30 @@ fn read_charset(data: &[u8], num_glyphs: u16) -> Result<Opaque<'_>> {
     Ok(Opaque(data.get(..len).ok_or(Error::InvalidOffset)?))
 }
 
+/// Read the string IDs (or CIDs for CID-keyed fonts) of all glyphs from a
+/// charset. The .notdef glyph has none and gets zero.
+fn read_charset_ids(data: &[u8], num_glyphs: u16) -> Result<Vec<u16>> {
+    let mut r = Reader::new(data);
+    let mut ids = vec![0];
+    let format = r.read::<u8>()?;
+    while ids.len() < usize::from(num_glyphs) {
+        match format {
+            0 => ids.push(r.read::<u16>()?),
+            1 | 2 => {
+                let first = r.read::<u16>()?;
+                let left = match format {
+                    1 => u16::from(r.read::<u8>()?),
+                    _ => r.read::<u16>()?,
+                };
+                ids.extend((0..=left).map(|i| first.wrapping_add(i)));
+            }
+            _ => return Err(Error::InvalidData),
+        }
+    }
+    ids.truncate(usize::from(num_glyphs));
+    Ok(ids)
+}
+
 /// Write a charset.
 fn write_charset(w: &mut Writer, charset: &Opaque<'_>) {
     w.write_ref(charset);
@@ -1,3 +1,4 @@
+use std::fmt::Write as _;
 use std::ptr;
 
 use super::*;
@@ -20,6 +21,26 @@ struct Subtable<'a> {
     data: Cow<'a, [u8]>,
 }
 
+impl Table<'_> {
+    /// The subtable with the given format that the most preferred Unicode
+    /// encoding record refers to. Subtables only referred to by Macintosh
+    /// records, which may be language-specific, are never chosen.
+    fn unicode_subtable(&self, format: u16) -> Option<usize> {
+        // Full repertoire records first, then BMP ones.
+        const PREFERENCE: [(u16, u16); 8] =
+            [(3, 10), (0, 6), (0, 4), (3, 1), (0, 3), (0, 2), (0, 1), (0, 0)];
+        PREFERENCE.iter().find_map(|&(platform_id, encoding_id)| {
+            self.encoding_records
+                .iter()
+                .filter(|rec| {
+                    rec.platform_id == platform_id && rec.encoding_id == encoding_id
+                })
+                .map(|rec| rec.subtable_idx)
+                .find(|&i| self.subtables[i].format == format)
+        })
+    }
+}
+
 impl<'a> Structure<'a> for Table<'a> {
     fn read(r: &mut Reader<'a>) -> Result<Self> {
         let data = r.data();
@@ -62,36 +83,50 @@ impl<'a> Structure<'a> for Table<'a> {
         Ok(Self { version, encoding_records, subtables })
     }
 
+    /// Write the table. Records that repeat the platform, encoding and
+    /// language of an earlier record are dropped, and so are subtables that no
+    /// record refers to. Records may share a subtable, which is written once.
     fn write(&self, w: &mut Writer) {
-        w.write(self.version);
-        w.write(self.subtables.len() as u16);
-        let mut sorted_indices = (0..self.encoding_records.len()).collect::<Vec<_>>();
+        let key = |rec: &EncodingRecord| {
+            (rec.platform_id, rec.encoding_id, self.subtables[rec.subtable_idx].language)
+        };
         // "The encoding record entries in the 'cmap' header must be sorted
         // first by platform ID, then by platform-specific encoding ID, and then
         // by the language field in the corresponding subtable. Each platform
         // ID, platform-specific encoding ID, and subtable language combination
         // may appear only once in the 'cmap' table."
-        sorted_indices.sort_by_key(|&i| {
-            let rec = &self.encoding_records[i];
-            (rec.platform_id, rec.encoding_id, self.subtables[rec.subtable_idx].language)
-        });
+        let mut records: Vec<&EncodingRecord> = self.encoding_records.iter().collect();
+        records.sort_by_key(|rec| key(rec));
+        records.dedup_by_key(|rec| key(rec));
+
+        // Subtables are laid out in the order the sorted records first refer
+        // to them, so the offsets of the records never decrease.
+        let mut order: Vec<usize> = vec![];
+        for rec in &records {
+            if !order.contains(&rec.subtable_idx) {
+                order.push(rec.subtable_idx);
+            }
+        }
+
         // version and n_subtables together are 4 bytes
         // each EncodingRecord is 8 bytes
-        let mut offset = 4 + 8 * self.encoding_records.len() as u32;
+        let mut offset = 4 + 8 * records.len() as u32;
         let mut offsets = vec![0; self.subtables.len()];
-        for (i, tab) in self.subtables.iter().enumerate() {
+        for &i in &order {
             offsets[i] = offset;
-            offset += tab.data.len() as u32;
+            offset += self.subtables[i].data.len() as u32;
         }
-        for i in sorted_indices {
-            let rec = &self.encoding_records[i];
+
+        w.write(self.version);
+        w.write(records.len() as u16);
+        for rec in records {
             w.write(rec.platform_id);
             w.write(rec.encoding_id);
             w.write(offsets[rec.subtable_idx]);
         }
-        for (i, tab) in self.subtables.iter().enumerate() {
+        for i in order {
             assert_eq!(offsets[i], w.len() as u32);
-            w.give(tab.data.as_ref());
+            w.give(self.subtables[i].data.as_ref());
         }
     }
 }
@@ -199,20 +234,116 @@ fn convert_subtable_4_to_12<'a>(st: &Subtable<'a>) -> Result<Subtable<'a>> {
     })
 }
 
-/// Maps all glyphs in the subtable to the Private Use Area (PUA) starting at
-/// U+F0000 (PUA-A). The subtable must be of format 12.
-fn map_glyph_to_pua_12(st: &mut Subtable<'_>, num_glyphs: u16) -> Result<()> {
-    debug_assert_eq!(st.format, 12);
+/// Set the language of all subtables that no Macintosh encoding record refers
+/// to to zero.
+///
+/// The language field only has a meaning on the Macintosh platform and must be
+/// zero for all others, but conversions and malformed fonts may carry nonzero
+/// languages into Unicode and Windows records.
+fn normalize_languages(table: &mut Table<'_>) {
+    const MACINTOSH: u16 = 1;
+    for (i, st) in table.subtables.iter_mut().enumerate() {
+        let mac = table
+            .encoding_records
+            .iter()
+            .any(|rec| rec.subtable_idx == i && rec.platform_id == MACINTOSH);
+        if mac || st.language == 0 {
+            continue;
+        }
+        let data = st.data.to_mut();
+        match st.format {
+            0 | 2 | 4 | 6 => data[4..6].fill(0),
+            8 | 10 | 12 | 13 => data[8..12].fill(0),
+            _ => continue,
+        }
+        st.language = 0;
+    }
+}
+
+/// Read the `(start_code, end_code, start_glyph_id)` groups of a subtable with
+/// format 12, or the `(start_code, end_code, glyph_id)` groups of one with
+/// format 13.
+fn read_groups_12(st: &Subtable<'_>) -> Result<Vec<(u32, u32, u32)>> {
+    debug_assert!(matches!(st.format, 12 | 13));
     let n_groups = u32::read_at(st.data.as_ref(), 12)? as usize;
     let mut groups: Vec<(u32, u32, u32)> = vec![];
-    let mut cur_group = &st.data.as_ref()[16..];
+    let mut cur_group = st.data.get(16..).ok_or(Error::MissingData)?;
     for _ in 0..n_groups {
         let start_code = u32::read_at(cur_group, 0)?;
         let end_code = u32::read_at(cur_group, 4)?;
         let start_glyph_id = u32::read_at(cur_group, 8)?;
         groups.push((start_code, end_code, start_glyph_id));
-        cur_group = &cur_group[12..];
+        cur_group = cur_group.get(12..).ok_or(Error::MissingData)?;
+    }
+    Ok(groups)
+}
+
+/// Sort groups and resolve overlaps between them, so that they can be
+/// modified. Where groups overlap, the later one wins, and adjacent groups
+/// that continue each other are merged.
+///
+/// Well-formed groups are returned unchanged. Malformed ones are an error if
+/// `strict` is set.
+fn normalize_groups(
+    groups: Vec<(u32, u32, u32)>,
+    strict: bool,
+) -> Result<Vec<(u32, u32, u32)>> {
+    if groups.iter().all(|g| g.0 <= g.1) && groups.windows(2).all(|w| w[0].1 < w[1].0) {
+        return Ok(groups);
+    }
+    if strict {
+        return Err(Error::InvalidData);
+    }
+
+    let mut disjoint: Vec<(u32, u32, u32)> = vec![];
+    for (start_code, end_code, start_glyph_id) in groups {
+        if start_code > end_code {
+            continue;
+        }
+        // Cut the groups that the new one overlaps.
+        let i = disjoint.partition_point(|g| g.1 < start_code);
+        let j = disjoint.partition_point(|g| g.0 <= end_code);
+        let mut replace_with = vec![];
+        if i < j && disjoint[i].0 < start_code {
+            let (start, _, glyph) = disjoint[i];
+            replace_with.push((start, start_code - 1, glyph));
+        }
+        replace_with.push((start_code, end_code, start_glyph_id));
+        if i < j && disjoint[j - 1].1 > end_code {
+            let (start, end, glyph) = disjoint[j - 1];
+            replace_with.push((
+                end_code + 1,
+                end,
+                glyph.wrapping_add(end_code + 1 - start),
+            ));
+        }
+        disjoint.splice(i..j, replace_with);
+    }
+
+    let mut merged: Vec<(u32, u32, u32)> = vec![];
+    for g in disjoint {
+        match merged.last_mut() {
+            Some(m) if m.1 + 1 == g.0 && m.2.checked_add(g.0 - m.0) == Some(g.2) => {
+                m.1 = g.1
+            }
+            _ => merged.push(g),
+        }
     }
+    Ok(merged)
+}
+
+/// Maps all glyphs in the subtable to the Private Use Area (PUA) starting at
+/// U+F0000 (PUA-A). The subtable must be of format 12.
+fn map_glyph_to_pua_12(
+    st: &mut Subtable<'_>,
+    num_glyphs: u16,
+    strict: bool,
+) -> Result<()> {
+    let mut groups = normalize_groups(read_groups_12(st)?, strict)?;
+    if num_glyphs == 0 {
+        return Ok(());
+    }
+
     let glyph_start_code = 0xF0000;
     let glyph_end_code = glyph_start_code + num_glyphs as u32 - 1;
 
@@ -251,6 +382,25 @@ fn map_glyph_to_pua_12(st: &mut Subtable<'_>, num_glyphs: u16) -> Result<()> {
         // Replace i_start..i_end with replace_with
         groups.splice(i_start..i_end, replace_with);
     }
+    write_groups_12(st, groups);
+    Ok(())
+}
+
+/// Add mappings to a subtable with format 12, replacing the existing mappings
+/// of their codepoints.
+fn insert_mappings_12(
+    st: &mut Subtable<'_>,
+    mappings: &[(u32, u16)],
+    strict: bool,
+) -> Result<()> {
+    let mut groups = normalize_groups(read_groups_12(st)?, strict)?;
+    groups.extend(mappings.iter().map(|&(c, glyph)| (c, c, u32::from(glyph))));
+    write_groups_12(st, normalize_groups(groups, false)?);
+    Ok(())
+}
+
+/// Replace the groups of a subtable with format 12 or 13.
+fn write_groups_12(st: &mut Subtable<'_>, groups: Vec<(u32, u32, u32)>) {
     let mut w = Writer::new();
     w.give(&st.data.as_ref()[..12]);
     w.write(groups.len() as u32);
@@ -264,36 +414,353 @@ fn map_glyph_to_pua_12(st: &mut Subtable<'_>, num_glyphs: u16) -> Result<()> {
     let length = data.len() as u32;
     data[4..8].copy_from_slice(&length.to_be_bytes());
     st.data = Cow::Owned(data);
+}
+
+/// The minimum number of consecutive characters mapped to the same glyph that
+/// [`add_many_to_one`] copies to a format 13 subtable.
+const MANY_TO_ONE: u32 = 128;
+
+/// Copy the ranges of at least [`MANY_TO_ONE`] characters that the given
+/// format 12 subtable maps to the same glyph, which take a group per
+/// character, to a format 13 subtable, where each takes a single group.
+///
+/// The ranges stay in the format 12 subtable, since shapers prefer it and
+/// don't fall back to other subtables for characters it lacks. The Unicode
+/// full repertoire record for format 13 refers to the new subtable, which
+/// keeps the ranges of the format 13 subtable it referred to before, unless
+/// they overlap the copied ones. Fonts that don't map such ranges are left
+/// unchanged.
+fn add_many_to_one(table: &mut Table<'_>, tab_12_id: usize, strict: bool) -> Result<()> {
+    let groups = normalize_groups(read_groups_12(&table.subtables[tab_12_id])?, strict)?;
+    let mut many = vec![];
+    let mut i = 0;
+    while i < groups.len() {
+        let (start, end, glyph) = groups[i];
+        let mut last = end;
+        let mut j = i + 1;
+        if start == end {
+            while groups.get(j) == Some(&(last + 1, last + 1, glyph)) {
+                last += 1;
+                j += 1;
+            }
+        }
+        if glyph != 0 && last - start >= MANY_TO_ONE - 1 && j - i > 1 {
+            many.push((start, last, glyph));
+        }
+        i = j;
+    }
+    if many.is_empty() {
+        return Ok(());
+    }
+
+    let record = table
+        .encoding_records
+        .iter()
+        .position(|rec| (rec.platform_id, rec.encoding_id) == (0, 6));
+    if let Some(st) = record
+        .map(|i| &table.subtables[table.encoding_records[i].subtable_idx])
+        .filter(|st| st.format == 13)
+    {
+        for group in read_groups_12(st)? {
+            if many.iter().all(|m| group.1 < m.0 || m.1 < group.0) {
+                many.push(group);
+            }
+        }
+        many.sort_unstable();
+    }
+
+    let mut format_13 = empty_subtable(13);
+    write_groups_12(&mut format_13, many);
+    table.subtables.push(format_13);
+    let subtable_idx = table.subtables.len() - 1;
+    match record {
+        Some(i) => table.encoding_records[i].subtable_idx = subtable_idx,
+        None => table.encoding_records.push(EncodingRecord {
+            platform_id: 0,
+            encoding_id: 6,
+            subtable_idx,
+        }),
+    }
     Ok(())
 }
 
+/// Create a subtable with format 12 or 13 without groups.
+fn empty_subtable<'a>(format: u16) -> Subtable<'a> {
+    let mut header = Writer::new();
+    header.write(format);
+    header.write(0u16); // reserved
+    header.write(16u32); // length
+    header.write(0u32); // language
+    header.write(0u32); // number of groups
+    Subtable {
+        format,
+        language: 0,
+        data: Cow::Owned(header.finish()),
+    }
+}
+
+/// Create a subtable with format 4 from groups of BMP codepoints. Each group
+/// becomes one segment with an `idDelta`.
+fn write_subtable_4<'a>(
+    language: u32,
+    groups: &[(u32, u32, u32)],
+) -> Result<Subtable<'a>> {
+    // The last segment must map 0xFFFF.
+    let mut segments: Vec<_> = groups
+        .iter()
+        .filter(|g| g.0 < 0xFFFF)
+        .map(|&(start, end, glyph)| (start as u16, end.min(0xFFFE) as u16, glyph))
+        .collect();
+    segments.push((0xFFFF, 0xFFFF, 1));
+
+    let seg_count_x2 =
+        u16::try_from(2 * segments.len()).map_err(|_| Error::InvalidData)?;
+    let search_range = (seg_count_x2 + 1).next_power_of_two() / 2;
+    let entry_selector = search_range.trailing_zeros() as u16 - 1;
+    let range_shift = seg_count_x2 - search_range;
+
+    let mut w = Writer::new();
+    w.write(4u16);
+    w.write(0u16); // length, will revisit later
+    w.write(language as u16);
+    w.write(seg_count_x2);
+    w.write(search_range);
+    w.write(entry_selector);
+    w.write(range_shift);
+    for &(_, end, _) in &segments {
+        w.write(end);
+    }
+    w.write(0u16); // reserved
+    for &(start, _, _) in &segments {
+        w.write(start);
+    }
+    for &(start, _, glyph) in &segments {
+        w.write((glyph as u16).wrapping_sub(start));
+    }
+    for _ in &segments {
+        w.write(0u16);
+    }
+
+    let mut data = w.finish();
+    let length = u16::try_from(data.len()).map_err(|_| Error::InvalidData)?;
+    data[2..4].copy_from_slice(&length.to_be_bytes());
+    Ok(Subtable { format: 4, language, data: Cow::Owned(data) })
+}
+
+/// Read the groups of a subtable with format 4 or 12.
+fn read_groups(st: &Subtable<'_>) -> Result<Option<Vec<(u32, u32, u32)>>> {
+    Ok(match st.format {
+        4 => Some(read_groups_12(&convert_subtable_4_to_12(st)?)?),
+        12 => Some(read_groups_12(st)?),
+        _ => None,
+    })
+}
+
+/// Map a single codepoint to a glyph in a subtable with format 4 or 12. The
+/// codepoint must not be mapped yet.
+fn insert_mapping(st: &mut Subtable<'_>, c: u32, glyph: u16, strict: bool) -> Result<()> {
+    let Some(groups) = read_groups(st)? else { return Ok(()) };
+    let mut groups = normalize_groups(groups, strict)?;
+    let i = groups.partition_point(|g| g.1 < c);
+    groups.insert(i, (c, c, u32::from(glyph)));
+    if st.format == 4 {
+        *st = write_subtable_4(st.language, &groups)?;
+    } else {
+        write_groups_12(st, groups);
+    }
+    Ok(())
+}
+
+/// All mappings from codepoints to glyphs in the most complete Unicode
+/// subtable, sorted by codepoint.
+pub(crate) fn mappings(data: &[u8]) -> Result<Vec<(u32, u16)>> {
+    let table = Table::read(&mut Reader::new(data))?;
+    let st = table
+        .unicode_subtable(12)
+        .or_else(|| table.unicode_subtable(4))
+        .map(|i| &table.subtables[i]);
+
+    let mut mappings = vec![];
+    if let Some(groups) = st.map(read_groups).transpose()?.flatten() {
+        for (start_code, end_code, start_glyph_id) in groups {
+            for c in start_code..=end_code.min(0x10FFFF) {
+                let glyph = start_glyph_id + (c - start_code);
+                mappings.push((c, glyph as u16));
+            }
+        }
+    }
+
+    // The terminating segment of format 4 subtables maps nothing.
+    mappings.retain(|&(c, glyph)| c != 0xFFFF || glyph != 0);
+    Ok(mappings)
+}
+
+/// An index from glyphs to the characters the `cmap` table maps to them.
+///
+/// This tells which characters a glyph serves, e.g. to find out which
+/// character pulled a large glyph into a subset.
+#[derive(Debug, Clone)]
+pub struct ReverseCmap {
+    /// The mappings, sorted by glyph and then by character.
+    entries: Vec<(u16, char)>,
+}
+
+impl ReverseCmap {
+    /// Build the index from the preferred Unicode subtable of a font face.
+    pub fn new(data: &[u8], index: u32) -> Result<Self> {
+        let face = parse(data, index)?;
+        let cmap = face.table(Tag::CMAP).ok_or(Error::MissingTable(Tag::CMAP))?;
+        let mut entries: Vec<_> = mappings(cmap)?
+            .into_iter()
+            .filter_map(|(c, glyph)| Some((glyph, char::from_u32(c)?)))
+            .collect();
+        entries.sort_unstable();
+        Ok(Self { entries })
+    }
+
+    /// The characters mapped to the glyph, in ascending order.
+    pub fn chars_for_glyph(&self, glyph: u16) -> impl Iterator<Item = char> + '_ {
+        let start = self.entries.partition_point(|&(g, _)| g < glyph);
+        self.entries[start..]
+            .iter()
+            .take_while(move |&&(g, _)| g == glyph)
+            .map(|&(_, c)| c)
+    }
+}
+
+/// Create a table with a format 4 and a format 12 subtable from mappings of
+/// codepoints to glyphs, sorted by codepoint.
+fn table_from_mappings<'a>(mappings: &[(u32, u16)]) -> Result<Table<'a>> {
+    let mut groups: Vec<(u32, u32, u32)> = vec![];
+    for &(c, glyph) in mappings {
+        match groups.last_mut() {
+            Some(g) if g.1 + 1 == c && g.2 + (c - g.0) == u32::from(glyph) => g.1 = c,
+            _ => groups.push((c, c, u32::from(glyph))),
+        }
+    }
+
+    let mut format_12 = empty_subtable(12);
+    write_groups_12(&mut format_12, groups.clone());
+
+    let record = |platform_id, encoding_id, subtable_idx| EncodingRecord {
+        platform_id,
+        encoding_id,
+        subtable_idx,
+    };
+
+    Ok(Table {
+        version: 0,
+        encoding_records: vec![
+            record(0, 3, 0),
+            record(0, 4, 1),
+            record(3, 1, 0),
+            record(3, 10, 1),
+        ],
+        subtables: vec![write_subtable_4(0, &groups)?, format_12],
+    })
+}
+
+/// Rebuild a table from the mappings of its preferred Unicode subtable with
+/// renumbered glyphs. Mappings to glyphs without a new ID are removed.
+///
+/// If the glyphs are mapped to the PUA by their ID, `pua` holds the glyph
+/// counts before and after renumbering, and the PUA range is rebuilt from the
+/// new IDs.
+pub(crate) fn remap(
+    data: &[u8],
+    map: impl Fn(u16) -> Option<u16>,
+    pua: Option<(u16, u16)>,
+) -> Result<Vec<u8>> {
+    let old_pua = pua.map_or(0..0, |(before, _)| 0xF0000..0xF0000 + u32::from(before));
+    let mut mappings: Vec<_> = mappings(data)?
+        .into_iter()
+        .filter(|(c, _)| !old_pua.contains(c))
+        .filter_map(|(c, glyph)| Some((c, map(glyph).filter(|&glyph| glyph != 0)?)))
+        .collect();
+    if let Some((_, after)) = pua {
+        mappings.extend((1..after).map(|glyph| (0xF0000 + u32::from(glyph), glyph)));
+        mappings.sort_unstable();
+    }
+    let mut writer = Writer::new();
+    table_from_mappings(&mappings)?.write(&mut writer);
+    Ok(writer.finish())
+}
+
+/// Look up the glyph of a codepoint in the preferred Unicode subtable.
+pub(crate) fn lookup(data: &[u8], c: u32) -> Result<Option<u16>> {
+    let table = Table::read(&mut Reader::new(data))?;
+    let Some(i) = table.unicode_subtable(12).or_else(|| table.unicode_subtable(4)) else {
+        return Ok(None);
+    };
+    let groups = read_groups(&table.subtables[i])?.unwrap_or_default();
+    Ok(groups
+        .iter()
+        .find(|g| (g.0..=g.1).contains(&c))
+        .map(|g| (g.2 + c - g.0) as u16)
+        .filter(|&glyph| glyph != 0))
+}
+
 pub(crate) fn map_glyphs(ctx: &mut Context) -> Result<()> {
     let data = ctx.expect_table(Tag::CMAP)?;
-    if !ctx.profile.map_glyphs {
+    let obfuscate = ctx.plan.obfuscation.is_some();
+    if !ctx.plan.map_glyphs && ctx.space.is_none() && !obfuscate {
         ctx.push(Tag::CMAP, data);
         return Ok(());
     }
-    let mut table = Table::read(&mut Reader::new(data))?;
-    let tab_12_id = match table.subtables.iter().position(|st| st.format == 12) {
+
+    let mut table = if obfuscate {
+        let mut mappings: Vec<_> = obfuscate::mappings(ctx)?
+            .into_iter()
+            .map(|(_, c, glyph)| (c, glyph))
+            .collect();
+        mappings.sort_unstable();
+        table_from_mappings(&mappings)?
+    } else {
+        Table::read(&mut Reader::new(data))?
+    };
+
+    if let Some((glyph, _)) = ctx.space {
+        for st in &mut table.subtables {
+            insert_mapping(st, 0x20, glyph, ctx.plan.strict)?;
+        }
+    }
+
+    if !ctx.plan.map_glyphs {
+        normalize_languages(&mut table);
+        let mut writer = Writer::new();
+        table.write(&mut writer);
+        ctx.push(Tag::CMAP, writer.finish());
+        return Ok(());
+    }
+    // Fonts with only a format 13 subtable, like last resort fonts, get a
+    // format 12 one for the PUA mapping.
+    let tab_12_id = match table.unicode_subtable(12) {
         Some(id) => id,
         None => {
-            let tab_4_id = table
-                .subtables
-                .iter()
-                .position(|st| st.format == 4)
-                .ok_or(Error::MissingData)?;
-            table
-                .subtables
-                .push(convert_subtable_4_to_12(&table.subtables[tab_4_id])?);
+            let st = match table.unicode_subtable(4) {
+                Some(tab_4_id) => convert_subtable_4_to_12(&table.subtables[tab_4_id])?,
+                None if table.unicode_subtable(13).is_some() => empty_subtable(12),
+                None => return Err(Error::MissingData),
+            };
+            table.subtables.push(st);
             table.subtables.len() - 1
         }
     };
 
-    if !table
-        .encoding_records
-        .iter()
-        .any(|r| r.platform_id == 0 && r.encoding_id == 4)
-    {
+    // Ranges that map to a single glyph take a group per character in the
+    // format 12 subtable, so they are also offered in a compact format 13 one.
+    add_many_to_one(&mut table, tab_12_id, ctx.plan.strict)?;
+
+    // The full repertoire records must refer to the subtable with the PUA
+    // mappings, even if they referred to another one before.
+    let mut full = false;
+    for rec in &mut table.encoding_records {
+        if matches!((rec.platform_id, rec.encoding_id), (0, 4) | (3, 10)) {
+            rec.subtable_idx = tab_12_id;
+            full |= rec.platform_id == 0;
+        }
+    }
+    if !full {
         table.encoding_records.push(EncodingRecord {
             platform_id: 0,
             encoding_id: 4,
@@ -301,10 +768,60 @@ pub(crate) fn map_glyphs(ctx: &mut Context) -> Result<()> {
         });
     }
 
-    map_glyph_to_pua_12(&mut table.subtables[tab_12_id], ctx.num_glyphs)?;
+    let st = &mut table.subtables[tab_12_id];
+    match ctx.plan.pua_key {
+        PuaKey::GlyphId => map_glyph_to_pua_12(st, ctx.num_glyphs, ctx.plan.strict)?,
+        _ => insert_mappings_12(st, &pua::assign(ctx)?, ctx.plan.strict)?,
+    }
+    normalize_languages(&mut table);
 
     let mut writer = Writer::new();
     table.write(&mut writer);
     ctx.push(Tag::CMAP, writer.finish());
     Ok(())
 }
+
+/// Write the encoding records and the groups of all format 4, 12 and 13 subtables
+/// for [`debug_dump`](crate::debug_dump).
+pub(crate) fn dump(data: &[u8], out: &mut String) -> Result<()> {
+    let table = Table::read(&mut Reader::new(data))?;
+    for (i, st) in table.subtables.iter().enumerate() {
+        let records: Vec<_> = table
+            .encoding_records
+            .iter()
+            .filter(|rec| rec.subtable_idx == i)
+            .map(|rec| format!("{}/{}", rec.platform_id, rec.encoding_id))
+            .collect();
+        writeln!(
+            out,
+            "  subtable {i}: format {}, language {}, records {}",
+            st.format,
+            st.language,
+            records.join(" ")
+        )
+        .unwrap();
+
+        if st.format == 13 {
+            for (start_code, end_code, glyph_id) in read_groups_12(st)? {
+                writeln!(
+                    out,
+                    "    U+{start_code:04X}..U+{end_code:04X} -> {glyph_id} (all)"
+                )
+                .unwrap();
+            }
+            continue;
+        }
+
+        let Some(groups) = read_groups(st)? else { continue };
+
+        for (start_code, end_code, start_glyph_id) in groups {
+            // The terminating segment of format 4 subtables maps nothing.
+            if st.format == 4 && start_code == 0xFFFF {
+                continue;
+            }
+            writeln!(out, "    U+{start_code:04X}..U+{end_code:04X} -> {start_glyph_id}")
+                .unwrap();
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// The number of faces in a font file: the number of fonts in a collection
+/// (`.ttc` or `.otc` file), or 1 for a single font.
+pub fn face_count(data: &[u8]) -> Result<u32> {
+    match Reader::new(data).read::<FontKind>()? {
+        FontKind::Collection => u32::read_at(data, 8),
+        FontKind::TrueType | FontKind::Cff => Ok(1),
+    }
+}
+
+/// Subset several faces of a font file, each with its own profile, like
+/// [`subset`] does for a single face.
+///
+/// The subsetted faces are separate fonts, in the order of the requests. They
+/// can be combined into a new collection with [`build_collection`].
+pub fn subset_faces(data: &[u8], faces: &[(u32, Profile)]) -> Result<Vec<Vec<u8>>> {
+    faces
+        .iter()
+        .map(|(index, profile)| subset_face(data, *index, profile))
+        .collect()
+}
+
+/// Subset several fonts, each with its own profile, and combine the subsets
+/// into a font collection with [`build_collection`].
+///
+/// This suits documents that embed several members of a font family, whose
+/// hinting tables (`fpgm`, `prep` and `cvt `) are often identical and then
+/// stored only once. The fonts must be single fonts, not collections.
+pub fn subset_collection(fonts: &[(&[u8], Profile)]) -> Result<Vec<u8>> {
+    let mut subsets = vec![];
+    for (data, profile) in fonts {
+        if Reader::new(data).read::<FontKind>()? == FontKind::Collection {
+            return Err(Error::Unsupported);
+        }
+        subsets.push(subset_face(data, 0, profile)?);
+    }
+    let subsets: Vec<&[u8]> = subsets.iter().map(Vec::as_slice).collect();
+    build_collection(&subsets)
+}
+
+/// Subset some faces of a font collection, each with its own profile, and
+/// write the collection back out with [`replace_faces`], keeping the other
+/// faces unmodified.
+pub fn subset_in_collection(data: &[u8], faces: &[(u32, Profile)]) -> Result<Vec<u8>> {
+    let subsets = subset_faces(data, faces)?;
+    let replacements: Vec<(u32, &[u8])> = faces
+        .iter()
+        .zip(&subsets)
+        .map(|((index, _), font)| (*index, font.as_slice()))
+        .collect();
+    replace_faces(data, &replacements)
+}
+
+/// Subset a face like [`subset`], with a borrowed profile.
+fn subset_face(data: &[u8], index: u32, profile: &Profile) -> Result<Vec<u8>> {
+    let face = parse(data, index)?;
+    let plan = Plan::new(&face, profile);
+    prepare(face, plan)?.build()
+}
+
+/// Combine fonts into a font collection, storing tables that are identical in
+/// several fonts only once.
+///
+/// The fonts must be single fonts, not collections. Their tables are kept
+/// unmodified, so each face of the collection is the same as the font it was
+/// built from.
+pub fn build_collection(fonts: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut faces = vec![];
+    for &font in fonts {
+        faces.push(parse_single(font)?);
+    }
+    write_collection(&faces)
+}
+
+/// Write a font collection back out with some of its faces replaced by other
+/// fonts, e.g. subsets of them, keeping the other faces unmodified.
+///
+/// The faces keep their positions in the collection and may mix TrueType and
+/// CFF outlines. Tables that the unmodified faces share stay shared. Replaced
+/// faces only share tables whose contents are identical, so replacing a face
+/// never changes the tables of another one, even if the two shared tables in
+/// the input. The replacements must be single fonts, not collections. If a
+/// face is listed several times, the first replacement applies.
+pub fn replace_faces(data: &[u8], replacements: &[(u32, &[u8])]) -> Result<Vec<u8>> {
+    if Reader::new(data).read::<FontKind>()? != FontKind::Collection {
+        return Err(Error::Unsupported);
+    }
+    let count = face_count(data)?;
+    if replacements.iter().any(|&(index, _)| index >= count) {
+        return Err(Error::InvalidOffset);
+    }
+
+    let mut faces = vec![];
+    for index in 0..count {
+        faces.push(match replacements.iter().find(|&&(i, _)| i == index) {
+            Some(&(_, font)) => parse_single(font)?,
+            None => parse(data, index)?,
+        });
+    }
+    write_collection(&faces)
+}
+
+/// Parse a font that must not be a collection.
+fn parse_single(font: &[u8]) -> Result<Face<'_>> {
+    if Reader::new(font).read::<FontKind>()? == FontKind::Collection {
+        return Err(Error::Unsupported);
+    }
+    parse(font, 0)
+}
+
+/// Write faces into a font collection, storing identical tables only once.
+///
+/// Each face keeps its own sfnt version, so that a collection can mix
+/// TrueType and CFF faces.
+/// Fails with [`Error::OffsetOverflow`] if the collection is too large for
+/// 32-bit offsets.
+fn write_collection(faces: &[Face]) -> Result<Vec<u8>> {
+    let to_u32 = |v: usize| u32::try_from(v).map_err(|_| Error::OffsetOverflow);
+
+    // The header with the offsets of the table directories, followed by the
+    // directories and the tables.
+    let mut w = Writer::new();
+    w.write::<FontKind>(FontKind::Collection);
+    w.write::<u16>(1);
+    w.write::<u16>(0);
+    w.write::<u32>(to_u32(faces.len())?);
+    let mut offset = 12 + 4 * faces.len();
+    for face in faces {
+        w.write::<u32>(to_u32(offset)?);
+        offset += 12 + 16 * face.tables.len();
+    }
+
+    // The offsets of the distinct tables.
+    let mut offsets: HashMap<&[u8], usize> = HashMap::new();
+    let mut tables = vec![];
+    for face in faces {
+        let count =
+            u16::try_from(face.tables.len()).map_err(|_| Error::OffsetOverflow)?;
+        let (search_range, entry_selector, range_shift) = search_params(count);
+        w.write::<FontKind>(face.kind());
+        w.write(count);
+        w.write(search_range);
+        w.write(entry_selector);
+        w.write(range_shift);
+
+        for &(tag, data) in &face.tables {
+            let start = *offsets.entry(data).or_insert_with(|| {
+                let start = offset;
+                offset = (offset + data.len()).next_multiple_of(4);
+                tables.push(data);
+                start
+            });
+            w.write(TableRecord {
+                tag,
+                checksum: checksum(data),
+                offset: to_u32(start)?,
+                length: to_u32(data.len())?,
+            });
+        }
+    }
+
+    for data in tables {
+        w.give(data);
+        w.align(4);
+    }
+
+    Ok(w.finish())
+}
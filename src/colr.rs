@@ -0,0 +1,693 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::*;
+
+/// The palette index that stands for the foreground color of the text.
+const FOREGROUND: u16 = 0xFFFF;
+
+/// Add the glyphs that the requested color glyphs are painted with to the
+/// plan: the layers of `COLRv0` glyphs, and the glyphs that the paint graphs of
+/// `COLRv1` glyphs fill or reuse as color glyphs.
+pub(crate) fn discover(ctx: &mut Context) -> Result<()> {
+    if ctx.plan.table(Tag::COLR) != TableAction::Subset {
+        return Ok(());
+    }
+    let Some(data) = ctx.face.table(Tag::COLR) else { return Ok(()) };
+    let table = Table::parse(data)?;
+
+    // Color glyphs may reuse other color glyphs, so repeat until the set of
+    // glyphs doesn't grow anymore.
+    let requested: BTreeSet<u16> = ctx.plan.glyphs.iter().copied().collect();
+    let mut glyphs = requested.clone();
+    loop {
+        let reach = table.reach(|glyph| glyphs.contains(&glyph))?;
+        let count = glyphs.len();
+        glyphs.extend(reach.glyphs.into_iter().filter(|&glyph| glyph < ctx.num_glyphs));
+        if glyphs.len() == count {
+            break;
+        }
+    }
+
+    ctx.plan.glyphs.extend(glyphs.difference(&requested));
+    Ok(())
+}
+
+/// Subset the `COLR` table to the retained glyphs.
+///
+/// The base glyph and layer records of removed `COLRv0` glyphs are dropped.
+/// Of the `COLRv1` data, only the base glyphs, layers, clip boxes and paints
+/// that the retained glyphs reach are kept. The palette indices are updated
+/// to the entries that remain in `CPAL`, and the variation data is copied
+/// unchanged. The table is dropped if no color glyph is retained.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let table = Table::parse(ctx.expect_table(Tag::COLR)?)?;
+    let entries = entries(ctx, &table)?;
+    let palette = |index: u16| match &entries {
+        Some(entries) => entries
+            .binary_search(&index)
+            .map_or(entries.len() as u16, |i| i as u16),
+        None => index,
+    };
+    let subset = &ctx.subset;
+    let colr =
+        rewrite(&table, |glyph| subset.contains(&glyph).then_some(glyph), palette)?;
+    if let Some(colr) = colr {
+        ctx.push(Tag::COLR, colr);
+    }
+    Ok(())
+}
+
+/// Renumber the glyphs of a subsetted `COLR` table.
+pub(crate) fn remap(data: &[u8], new_id: impl Fn(u16) -> Option<u16>) -> Result<Vec<u8>> {
+    let table = Table::parse(data)?;
+    rewrite(&table, new_id, |index| index)?.ok_or(Error::InvalidData)
+}
+
+/// Subset the `CPAL` table to the palette entries that the retained color
+/// glyphs use.
+///
+/// Every palette keeps the same entries, and so do the labels of the entries.
+/// Without a subsetted `COLR` table, the palettes are copied unchanged, since
+/// `SVG` glyphs may use them as well.
+pub(crate) fn subset_cpal(ctx: &mut Context) -> Result<()> {
+    let cpal = ctx.expect_table(Tag::CPAL)?;
+    let entries = match ctx.face.table(Tag::COLR) {
+        Some(colr) => entries(ctx, &Table::parse(colr)?)?,
+        None => None,
+    };
+    match entries {
+        // Without color glyphs, there is nothing left to color.
+        Some(entries) if entries.is_empty() => {}
+        Some(entries) => ctx.push(Tag::CPAL, write_cpal(cpal, &entries)?),
+        None => ctx.push(Tag::CPAL, cpal),
+    }
+    Ok(())
+}
+
+/// The palette entries that the retained color glyphs use, sorted, if both
+/// `COLR` and `CPAL` are subsetted. There are none if no color glyph is
+/// retained.
+///
+/// SVG glyphs refer to the palette entries through CSS variables, which
+/// aren't tracked, so the entries stay as they are when `SVG ` is kept.
+fn entries(ctx: &Context, table: &Table) -> Result<Option<Vec<u16>>> {
+    let Some(cpal) = ctx.face.table(Tag::CPAL) else { return Ok(None) };
+    if ctx.plan.table(Tag::COLR) != TableAction::Subset
+        || ctx.plan.table(Tag::CPAL) != TableAction::Subset
+        || ctx.face.table(Tag::SVG).is_some()
+            && ctx.plan.table(Tag::SVG) != TableAction::Drop
+    {
+        return Ok(None);
+    }
+
+    let reach = table.reach(|glyph| ctx.subset.contains(&glyph))?;
+    if reach.bases == 0 {
+        return Ok(Some(vec![]));
+    }
+
+    let count = u16::read_at(cpal, 2)?;
+    let mut entries: Vec<u16> =
+        reach.palette.into_iter().filter(|&index| index < count).collect();
+    // Some consumers reject palettes without entries, e.g. when all layers
+    // use the foreground color.
+    if entries.is_empty() && count > 0 {
+        entries.push(0);
+    }
+    Ok(Some(entries))
+}
+
+/// Write a `CPAL` table with only the given palette entries.
+fn write_cpal(cpal: &[u8], entries: &[u16]) -> Result<Vec<u8>> {
+    let version = u16::read_at(cpal, 0)?;
+    let palettes = usize::from(u16::read_at(cpal, 4)?);
+    let records = u32::read_at(cpal, 8)? as usize;
+    let firsts = (0..palettes)
+        .map(|i| u16::read_at(cpal, 12 + 2 * i).map(usize::from))
+        .collect::<Result<Vec<_>>>()?;
+    let count =
+        u16::try_from(palettes * entries.len()).map_err(|_| Error::OffsetOverflow)?;
+
+    // Version 1 adds the types and labels of the palettes, which are kept,
+    // and the labels of the entries, which are subsetted like the entries.
+    let slice = |at: usize, len: usize| cpal.get(at..at + len).ok_or(Error::MissingData);
+    let mut arrays: Vec<Option<Vec<u8>>> = vec![];
+    if version >= 1 {
+        for (i, size) in [(0, 4), (1, 2)] {
+            arrays.push(match u32::read_at(cpal, 12 + 2 * palettes + 4 * i)? {
+                0 => None,
+                at => Some(slice(at as usize, size * palettes)?.to_vec()),
+            });
+        }
+        arrays.push(match u32::read_at(cpal, 20 + 2 * palettes)? {
+            0 => None,
+            at => Some(
+                entries
+                    .iter()
+                    .map(|&index| slice(at as usize + 2 * usize::from(index), 2))
+                    .collect::<Result<Vec<_>>>()?
+                    .concat(),
+            ),
+        });
+    }
+
+    let header = 12 + 2 * palettes + 4 * arrays.len();
+    let mut w = Writer::new();
+    w.write::<u16>(version);
+    w.write::<u16>(entries.len() as u16);
+    w.write::<u16>(palettes as u16);
+    w.write::<u16>(count);
+    w.write::<u32>(header as u32);
+    for i in 0..palettes {
+        w.write::<u16>((i * entries.len()) as u16);
+    }
+    let mut offset = header + 4 * usize::from(count);
+    for array in &arrays {
+        match array {
+            Some(array) => {
+                w.write::<u32>(offset as u32);
+                offset += array.len();
+            }
+            None => w.write::<u32>(0),
+        }
+    }
+
+    for first in firsts {
+        for &index in entries {
+            let at = records + 4 * (first + usize::from(index));
+            w.give(cpal.get(at..at + 4).ok_or(Error::MissingData)?);
+        }
+    }
+    for array in arrays.iter().flatten() {
+        w.give(array);
+    }
+    Ok(w.finish())
+}
+
+/// Write a `COLR` table with the color glyphs that the `glyphs` function maps
+/// to new IDs, and their palette indices mapped through `palette`. Returns
+/// `None` if no color glyph is left.
+fn rewrite(
+    table: &Table,
+    glyphs: impl Fn(u16) -> Option<u16>,
+    palette: impl Fn(u16) -> u16,
+) -> Result<Option<Vec<u8>>> {
+    let data = table.data;
+    let reach = table.reach(|glyph| glyphs(glyph).is_some())?;
+    if reach.bases == 0 {
+        return Ok(None);
+    }
+
+    let new_glyph = |glyph: u16| glyphs(glyph).ok_or(Error::InvalidData);
+    let new_palette = |index: u16| match index {
+        FOREGROUND => FOREGROUND,
+        _ => palette(index),
+    };
+
+    // The base glyph and layer records of version 0.
+    let mut base_records = Writer::new();
+    let mut layer_records = Writer::new();
+    let (mut base_count, mut layer_count) = (0_u16, 0_u16);
+    let mut bases: Vec<_> = table
+        .base_glyphs
+        .iter()
+        .filter_map(|&(glyph, first, count)| Some((glyphs(glyph)?, first, count)))
+        .collect();
+    bases.sort_by_key(|&(new, ..)| new);
+    for (new, first, count) in bases {
+        base_records.write::<u16>(new);
+        base_records.write::<u16>(layer_count);
+        base_records.write::<u16>(count);
+        for &(layer, index) in table.layer_records(first, count)? {
+            layer_records.write::<u16>(new_glyph(layer)?);
+            layer_records.write::<u16>(new_palette(index));
+        }
+        base_count += 1;
+        layer_count = layer_count.checked_add(count).ok_or(Error::OffsetOverflow)?;
+    }
+
+    // The paints are written in their original order, so that offsets to
+    // other paints stay positive and can only get smaller.
+    let mut placed = BTreeMap::new();
+    let mut size = 0;
+    for (&at, &object) in &reach.objects {
+        placed.insert(at, size);
+        size += object.size(data, at)?;
+    }
+    let layers: Vec<usize> = reach.layers.iter().copied().collect();
+
+    let mut paints = Writer::new();
+    for (&at, &object) in &reach.objects {
+        let mut bytes = data
+            .get(at..at + object.size(data, at)?)
+            .ok_or(Error::MissingData)?
+            .to_vec();
+        let set = |bytes: &mut Vec<u8>, pos: usize, value: &[u8]| {
+            bytes[pos..pos + value.len()].copy_from_slice(value);
+        };
+        match object {
+            Object::Paint => {
+                for &field in paint_format(bytes[0])?.1 {
+                    match field {
+                        Field::Child(pos, _) => {
+                            let child = at + offset24(&bytes, pos)?;
+                            let offset = (placed[&child] - placed[&at]) as u32;
+                            set(&mut bytes, pos, &offset.to_be_bytes()[1..]);
+                        }
+                        Field::Glyph(pos) => {
+                            let glyph = new_glyph(u16::read_at(&bytes, pos)?)?;
+                            set(&mut bytes, pos, &glyph.to_be_bytes());
+                        }
+                        Field::Palette(pos) => {
+                            let index = new_palette(u16::read_at(&bytes, pos)?);
+                            set(&mut bytes, pos, &index.to_be_bytes());
+                        }
+                        Field::Layers => {
+                            let first = u32::read_at(&bytes, 2)? as usize;
+                            let first = layers.binary_search(&first).unwrap_or(0) as u32;
+                            set(&mut bytes, 2, &first.to_be_bytes());
+                        }
+                    }
+                }
+            }
+            Object::ColorLine(var) => {
+                for pos in stops(&bytes, var)? {
+                    let index = new_palette(u16::read_at(&bytes, pos)?);
+                    set(&mut bytes, pos, &index.to_be_bytes());
+                }
+            }
+            Object::Affine(_) => {}
+        }
+        paints.give(&bytes);
+    }
+    let paints = paints.finish();
+
+    // The base glyphs of version 1 and their clip boxes. Glyphs with the same
+    // clip box share a clip record as long as their IDs are consecutive.
+    let mut base_paints: Vec<_> = table
+        .base_paints
+        .iter()
+        .filter_map(|&(glyph, at)| Some((glyph, glyphs(glyph)?, at)))
+        .collect();
+    base_paints.sort_by_key(|&(_, new, _)| new);
+    let mut clips: Vec<(u16, u16, usize)> = vec![];
+    for &(glyph, new, _) in &base_paints {
+        let Some(&(_, _, clip)) = table
+            .clips
+            .iter()
+            .find(|&&(start, end, _)| (start..=end).contains(&glyph))
+        else {
+            continue;
+        };
+        match clips.last_mut() {
+            Some((_, end, prev)) if *prev == clip && *end + 1 == new => *end = new,
+            _ => clips.push((new, new, clip)),
+        }
+    }
+    let mut boxes: BTreeMap<usize, usize> =
+        clips.iter().map(|&(_, _, at)| (at, 0)).collect();
+    let mut clip_boxes = Writer::new();
+    for (&at, offset) in &mut boxes {
+        *offset = clip_boxes.len();
+        let size = match u8::read_at(data, at)? {
+            1 => 9,
+            2 => 13,
+            _ => return Err(Error::InvalidData),
+        };
+        clip_boxes.give(data.get(at..at + size).ok_or(Error::MissingData)?);
+    }
+    let clip_boxes = clip_boxes.finish();
+
+    let base_records = base_records.finish();
+    let layer_records = layer_records.finish();
+    let sized = |count: usize, size: usize| if count == 0 { 0 } else { size };
+    let header = if table.version == 0 { 14 } else { 34 };
+    let base_list = header + base_records.len() + layer_records.len();
+    let layer_list = base_list + sized(base_paints.len(), 4 + 6 * base_paints.len());
+    let clip_list = layer_list + sized(layers.len(), 4 + 4 * layers.len());
+    let paints_at =
+        clip_list + sized(clips.len(), 5 + 7 * clips.len() + clip_boxes.len());
+    let var_index_map = paints_at + paints.len();
+    let var_store = var_index_map + table.var_index_map.len();
+    let at = |present: bool, offset: usize| if present { offset as u32 } else { 0 };
+
+    let mut w = Writer::new();
+    w.write::<u16>(table.version);
+    w.write::<u16>(base_count);
+    w.write::<u32>(at(base_count > 0, header));
+    w.write::<u32>(at(layer_count > 0, header + base_records.len()));
+    w.write::<u16>(layer_count);
+    if table.version >= 1 {
+        w.write::<u32>(at(!base_paints.is_empty(), base_list));
+        w.write::<u32>(at(!layers.is_empty(), layer_list));
+        w.write::<u32>(at(!clips.is_empty(), clip_list));
+        w.write::<u32>(at(!table.var_index_map.is_empty(), var_index_map));
+        w.write::<u32>(at(!table.var_store.is_empty(), var_store));
+    }
+    w.give(&base_records);
+    w.give(&layer_records);
+    if !base_paints.is_empty() {
+        w.write::<u32>(base_paints.len() as u32);
+        for &(_, glyph, at) in &base_paints {
+            w.write::<u16>(glyph);
+            w.write::<u32>((paints_at + placed[&at] - base_list) as u32);
+        }
+    }
+    if !layers.is_empty() {
+        w.write::<u32>(layers.len() as u32);
+        for &layer in &layers {
+            let at = table.layers[layer];
+            w.write::<u32>((paints_at + placed[&at] - layer_list) as u32);
+        }
+    }
+    if !clips.is_empty() {
+        w.write::<u8>(1);
+        w.write::<u32>(clips.len() as u32);
+        for &(start, end, at) in &clips {
+            w.write::<u16>(start);
+            w.write::<u16>(end);
+            let offset = (5 + 7 * clips.len() + boxes[&at]) as u32;
+            w.give(&offset.to_be_bytes()[1..]);
+        }
+        w.give(&clip_boxes);
+    }
+    w.give(&paints);
+    w.give(table.var_index_map);
+    w.give(table.var_store);
+    Ok(Some(w.finish()))
+}
+
+/// A parsed `COLR` table.
+struct Table<'a> {
+    /// The raw table.
+    data: &'a [u8],
+    /// The version of the table.
+    version: u16,
+    /// The glyph, first layer and number of layers of each version 0 base
+    /// glyph.
+    base_glyphs: Vec<(u16, u16, u16)>,
+    /// The glyph and palette index of each version 0 layer.
+    layer_records: Vec<(u16, u16)>,
+    /// The glyph and the position of the root paint of each version 1 base
+    /// glyph.
+    base_paints: Vec<(u16, usize)>,
+    /// The positions of the paints in the layer list.
+    layers: Vec<usize>,
+    /// The first and last glyph and the position of the clip box of each clip
+    /// record.
+    clips: Vec<(u16, u16, usize)>,
+    /// The delta-set index map of the variable paints, empty if there is none.
+    var_index_map: &'a [u8],
+    /// The item variation store of the variable paints, empty if there is
+    /// none.
+    var_store: &'a [u8],
+}
+
+impl<'a> Table<'a> {
+    /// Parse the records and lists of the table. Positions are offsets from
+    /// the start of the table.
+    fn parse(data: &'a [u8]) -> Result<Self> {
+        let version = u16::read_at(data, 0)?;
+        let base_count = usize::from(u16::read_at(data, 2)?);
+        let base_at = u32::read_at(data, 4)? as usize;
+        let layer_at = u32::read_at(data, 8)? as usize;
+        let layer_count = usize::from(u16::read_at(data, 12)?);
+        let base_glyphs = (0..base_count)
+            .map(|i| {
+                let at = base_at + 6 * i;
+                Ok((
+                    u16::read_at(data, at)?,
+                    u16::read_at(data, at + 2)?,
+                    u16::read_at(data, at + 4)?,
+                ))
+            })
+            .collect::<Result<_>>()?;
+        let layer_records = (0..layer_count)
+            .map(|i| {
+                let at = layer_at + 4 * i;
+                Ok((u16::read_at(data, at)?, u16::read_at(data, at + 2)?))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut table = Self {
+            data,
+            version,
+            base_glyphs,
+            layer_records,
+            base_paints: vec![],
+            layers: vec![],
+            clips: vec![],
+            var_index_map: &[],
+            var_store: &[],
+        };
+        if version == 0 {
+            return Ok(table);
+        }
+
+        let list = u32::read_at(data, 14)? as usize;
+        if list != 0 {
+            let count = u32::read_at(data, list)? as usize;
+            table.base_paints = (0..count)
+                .map(|i| {
+                    let at = list + 4 + 6 * i;
+                    let paint = u32::read_at(data, at + 2)? as usize;
+                    Ok((u16::read_at(data, at)?, list + paint))
+                })
+                .collect::<Result<_>>()?;
+        }
+
+        let list = u32::read_at(data, 18)? as usize;
+        if list != 0 {
+            let count = u32::read_at(data, list)? as usize;
+            table.layers = (0..count)
+                .map(|i| Ok(list + u32::read_at(data, list + 4 + 4 * i)? as usize))
+                .collect::<Result<_>>()?;
+        }
+
+        let list = u32::read_at(data, 22)? as usize;
+        if list != 0 {
+            if u8::read_at(data, list)? != 1 {
+                return Err(Error::InvalidData);
+            }
+            let count = u32::read_at(data, list + 1)? as usize;
+            table.clips = (0..count)
+                .map(|i| {
+                    let at = list + 5 + 7 * i;
+                    Ok((
+                        u16::read_at(data, at)?,
+                        u16::read_at(data, at + 2)?,
+                        list + offset24(data, at + 4)?,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+        }
+
+        let at = u32::read_at(data, 26)? as usize;
+        if at != 0 {
+            table.var_index_map = delta_set_index_map(data, at)?;
+        }
+        let at = u32::read_at(data, 30)? as usize;
+        if at != 0 {
+            table.var_store = gdef::item_variation_store(data, at)?;
+        }
+
+        Ok(table)
+    }
+
+    /// The layer records of a version 0 base glyph.
+    fn layer_records(&self, first: u16, count: u16) -> Result<&[(u16, u16)]> {
+        let first = usize::from(first);
+        self.layer_records
+            .get(first..first + usize::from(count))
+            .ok_or(Error::InvalidData)
+    }
+
+    /// Find everything that the color glyphs for which `retained` returns
+    /// `true` consist of.
+    fn reach(&self, retained: impl Fn(u16) -> bool) -> Result<Reach> {
+        let mut reach = Reach::default();
+        for &(glyph, first, count) in &self.base_glyphs {
+            if retained(glyph) {
+                reach.bases += 1;
+                for &(layer, index) in self.layer_records(first, count)? {
+                    reach.glyphs.insert(layer);
+                    reach.palette.insert(index);
+                }
+            }
+        }
+
+        let mut work = vec![];
+        for &(glyph, at) in &self.base_paints {
+            if retained(glyph) {
+                reach.bases += 1;
+                work.push(at);
+            }
+        }
+
+        while let Some(at) = work.pop() {
+            if !reach.insert(at, Object::Paint)? {
+                continue;
+            }
+            for &field in paint_format(u8::read_at(self.data, at)?)?.1 {
+                match field {
+                    Field::Child(pos, Object::Paint) => {
+                        work.push(at + offset24(self.data, at + pos)?);
+                    }
+                    Field::Child(pos, object) => {
+                        let child = at + offset24(self.data, at + pos)?;
+                        if reach.insert(child, object)? {
+                            if let Object::ColorLine(var) = object {
+                                let bytes =
+                                    self.data.get(child..).ok_or(Error::InvalidOffset)?;
+                                for pos in stops(bytes, var)? {
+                                    reach.palette.insert(u16::read_at(bytes, pos)?);
+                                }
+                            }
+                        }
+                    }
+                    Field::Glyph(pos) => {
+                        reach.glyphs.insert(u16::read_at(self.data, at + pos)?);
+                    }
+                    Field::Palette(pos) => {
+                        reach.palette.insert(u16::read_at(self.data, at + pos)?);
+                    }
+                    Field::Layers => {
+                        let count = usize::from(u8::read_at(self.data, at + 1)?);
+                        let first = u32::read_at(self.data, at + 2)? as usize;
+                        for layer in first..first + count {
+                            reach.layers.insert(layer);
+                            work.push(*self.layers.get(layer).ok_or(Error::InvalidData)?);
+                        }
+                    }
+                }
+            }
+        }
+
+        reach.palette.remove(&FOREGROUND);
+        Ok(reach)
+    }
+}
+
+/// The parts of a `COLR` table that some color glyphs consist of.
+#[derive(Default)]
+struct Reach {
+    /// The number of base glyphs, counting those of both versions.
+    bases: usize,
+    /// The paints, color lines and transforms, by position.
+    objects: BTreeMap<usize, Object>,
+    /// The indices of the layers in the layer list.
+    layers: BTreeSet<usize>,
+    /// The glyphs that are filled or reused as color glyphs.
+    glyphs: BTreeSet<u16>,
+    /// The palette indices, without the foreground color.
+    palette: BTreeSet<u16>,
+}
+
+impl Reach {
+    /// Record an object at a position, returning whether it is new.
+    fn insert(&mut self, at: usize, object: Object) -> Result<bool> {
+        match self.objects.insert(at, object) {
+            Some(prev) if prev != object => Err(Error::InvalidData),
+            prev => Ok(prev.is_none()),
+        }
+    }
+}
+
+/// A subtable of the paint graph. The flags are whether the variable format
+/// is used.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Object {
+    Paint,
+    ColorLine(bool),
+    Affine(bool),
+}
+
+impl Object {
+    /// The size of the object at a position.
+    fn size(self, data: &[u8], at: usize) -> Result<usize> {
+        Ok(match self {
+            Self::Paint => paint_format(u8::read_at(data, at)?)?.0,
+            Self::ColorLine(var) => {
+                let stride = if var { 10 } else { 6 };
+                3 + stride * usize::from(u16::read_at(data, at + 1)?)
+            }
+            Self::Affine(var) => {
+                if var {
+                    28
+                } else {
+                    24
+                }
+            }
+        })
+    }
+}
+
+/// A field of a paint that refers to something else.
+#[derive(Copy, Clone)]
+enum Field {
+    /// A 24-bit offset to another object at the given position.
+    Child(usize, Object),
+    /// A glyph ID at the given position.
+    Glyph(usize),
+    /// A palette index at the given position.
+    Palette(usize),
+    /// The range of layers of `PaintColrLayers`.
+    Layers,
+}
+
+/// The size of a paint of the given format and its fields that refer to
+/// something else.
+fn paint_format(format: u8) -> Result<(usize, &'static [Field])> {
+    const PAINT: Field = Field::Child(1, Object::Paint);
+    Ok(match format {
+        1 => (6, &[Field::Layers]),
+        2 => (5, &[Field::Palette(1)]),
+        3 => (9, &[Field::Palette(1)]),
+        4 | 6 => (16, &[Field::Child(1, Object::ColorLine(false))]),
+        5 | 7 => (20, &[Field::Child(1, Object::ColorLine(true))]),
+        8 => (12, &[Field::Child(1, Object::ColorLine(false))]),
+        9 => (16, &[Field::Child(1, Object::ColorLine(true))]),
+        10 => (6, &[PAINT, Field::Glyph(4)]),
+        11 => (3, &[Field::Glyph(1)]),
+        12 => (7, &[PAINT, Field::Child(4, Object::Affine(false))]),
+        13 => (7, &[PAINT, Field::Child(4, Object::Affine(true))]),
+        20 | 24 => (6, &[PAINT]),
+        14 | 16 | 28 => (8, &[PAINT]),
+        21 | 25 => (10, &[PAINT]),
+        22 | 26 => (10, &[PAINT]),
+        15 | 17 | 18 | 29 | 30 => (12, &[PAINT]),
+        23 | 27 => (14, &[PAINT]),
+        19 | 31 => (16, &[PAINT]),
+        32 => (8, &[PAINT, Field::Child(5, Object::Paint)]),
+        _ => return Err(Error::InvalidData),
+    })
+}
+
+/// The positions of the palette indices in the stops of a color line.
+fn stops(line: &[u8], var: bool) -> Result<impl Iterator<Item = usize>> {
+    let stride = if var { 10 } else { 6 };
+    let count = usize::from(u16::read_at(line, 1)?);
+    Ok((0..count).map(move |i| 3 + stride * i + 2))
+}
+
+/// Read a 24-bit offset, which must not be null.
+fn offset24(data: &[u8], at: usize) -> Result<usize> {
+    let bytes = data.get(at..at + 3).ok_or(Error::MissingData)?;
+    match usize::from(bytes[0]) << 16 | usize::from(bytes[1]) << 8 | usize::from(bytes[2])
+    {
+        0 => Err(Error::InvalidOffset),
+        offset => Ok(offset),
+    }
+}
+
+/// Extract the delta-set index map at the given offset.
+fn delta_set_index_map(data: &[u8], at: usize) -> Result<&[u8]> {
+    let map = data.get(at..).ok_or(Error::InvalidOffset)?;
+    let entry_format = u8::read_at(map, 1)?;
+    let (header, count) = match u8::read_at(map, 0)? {
+        0 => (4, usize::from(u16::read_at(map, 2)?)),
+        1 => (6, u32::read_at(map, 2)? as usize),
+        _ => return Err(Error::InvalidData),
+    };
+    let size = usize::from((entry_format >> 4) & 0x3) + 1;
+    map.get(..header + size * count).ok_or(Error::MissingData)
+}
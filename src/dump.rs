@@ -0,0 +1,98 @@
+use std::fmt::Write as _;
+
+use super::*;
+
+/// Produce a stable, human-readable listing of a font's contents.
+///
+/// The listing contains the size of each table, the groups of the Unicode
+/// `cmap` subtables and the horizontal metrics and outline kind of each glyph.
+/// Glyphs without outlines and metrics (e.g. those removed by subsetting) are
+/// only counted, not listed. For CFF fonts, this applies to all glyphs without
+/// metrics.
+///
+/// The output is meant for snapshot testing: subsetting the same font with the
+/// same profile always yields the same listing, so any change in the listing
+/// between two versions of this crate points to a change in behavior.
+pub fn debug_dump(data: &[u8], index: u32) -> Result<String> {
+    let face = parse(data, index)?;
+    let mut out = String::new();
+
+    writeln!(out, "kind: {}", face.outlines().name()).unwrap();
+
+    writeln!(out, "tables:").unwrap();
+    for (tag, data) in &face.tables {
+        writeln!(out, "  {tag}: {} bytes", data.len()).unwrap();
+    }
+
+    if let Some(cmap) = face.table(Tag::CMAP) {
+        writeln!(out, "cmap:").unwrap();
+        cmap::dump(cmap, &mut out)?;
+    }
+
+    dump_glyphs(&face, &mut out)?;
+    Ok(out)
+}
+
+/// Write the metrics and outline kind of each glyph.
+fn dump_glyphs(face: &Face, out: &mut String) -> Result<()> {
+    let maxp = face.table(Tag::MAXP).ok_or(Error::MissingTable(Tag::MAXP))?;
+    let num_glyphs = u16::read_at(maxp, 4)?;
+
+    let outlines = face.outlines();
+    let glyf = match outlines {
+        Outlines::TrueType => Some(glyf::Table::new(face)?),
+        _ => None,
+    };
+
+    writeln!(out, "glyphs: {num_glyphs}").unwrap();
+    let mut omitted = 0;
+    for id in 0..num_glyphs {
+        let (advance, lsb) = metrics(face, id)?;
+        let outline = match &glyf {
+            Some(glyf) => outline_kind(glyf, id)?,
+            None => outlines.name(),
+        };
+
+        // CFF outlines are not inspected, so zero metrics must suffice.
+        if (outline == "empty" || glyf.is_none()) && advance == 0 && lsb == 0 {
+            omitted += 1;
+            continue;
+        }
+
+        writeln!(out, "  {id}: advance {advance}, lsb {lsb}, {outline}").unwrap();
+    }
+
+    if omitted > 0 {
+        writeln!(out, "  ({omitted} empty glyphs omitted)").unwrap();
+    }
+
+    Ok(())
+}
+
+/// The advance width and left side bearing of a glyph, or zero if the face has
+/// no horizontal metrics.
+pub(crate) fn metrics(face: &Face, id: u16) -> Result<(u16, i16)> {
+    let (Some(hhea), Some(hmtx)) = (face.table(Tag::HHEA), face.table(Tag::HMTX)) else {
+        return Ok((0, 0));
+    };
+
+    let num_h_metrics = u16::read_at(hhea, 34)?;
+    Ok(if id < num_h_metrics {
+        (u16::read_at(hmtx, 4 * id as usize)?, i16::read_at(hmtx, 4 * id as usize + 2)?)
+    } else if num_h_metrics > 0 {
+        let last = 4 * (num_h_metrics as usize - 1);
+        let offset = 4 * num_h_metrics as usize + 2 * (id - num_h_metrics) as usize;
+        (u16::read_at(hmtx, last)?, i16::read_at(hmtx, offset)?)
+    } else {
+        (0, 0)
+    })
+}
+
+/// The kind of a glyph's TrueType outline: empty, simple or composite.
+pub(crate) fn outline_kind(table: &glyf::Table, id: u16) -> Result<&'static str> {
+    Ok(match i16::read_at(table.glyph_data(id)?, 0) {
+        Err(_) => "empty",
+        Ok(n) if n < 0 => "composite",
+        Ok(_) => "simple",
+    })
+}
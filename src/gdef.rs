@@ -0,0 +1,242 @@
+use super::*;
+use crate::layout::{self, device, offset, retained, Packer, Retained};
+
+/// Subset the glyph definition table to the retained glyphs.
+///
+/// The glyph classes, attachment points, ligature carets and mark attachment
+/// classes of removed glyphs are dropped. Mark glyph sets that become empty are
+/// kept, since lookups refer to the sets by index. The variation store keeps
+/// all delta sets, since device tables refer to them by index, but loses the
+/// regions that none of them varies in. The table is dropped if nothing is
+/// left, and the remaining glyphs are referred to by their output IDs.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let glyphs = layout::retained_glyphs(ctx);
+    let gdef = ctx.expect_table(Tag::GDEF)?;
+
+    let minor = u16::read_at(gdef, 2)?;
+    let glyph_classes = subset_class_def(gdef, 4, &glyphs)?;
+    let attach_list = subset_attach_list(gdef, &glyphs)?;
+    let lig_caret_list = subset_lig_caret_list(gdef, &glyphs)?;
+    let mark_classes = subset_class_def(gdef, 10, &glyphs)?;
+    let mark_sets = match minor {
+        2.. => subset_mark_glyph_sets(gdef, &glyphs)?,
+        _ => None,
+    };
+    let var_store = match minor {
+        3.. => match u32::read_at(gdef, 14)? {
+            0 => None,
+            at => {
+                Some(variation::prune_regions(item_variation_store(gdef, at as usize)?)?)
+            }
+        },
+        _ => None,
+    };
+
+    if glyph_classes.is_none()
+        && attach_list.is_none()
+        && lig_caret_list.is_none()
+        && mark_classes.is_none()
+        && mark_sets.is_none()
+        && var_store.is_none()
+    {
+        return Ok(());
+    }
+
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.write::<u16>(minor.min(3));
+    for subtable in [glyph_classes, attach_list, lig_caret_list, mark_classes] {
+        link(&mut p, subtable);
+    }
+    if minor >= 2 {
+        link(&mut p, mark_sets);
+    }
+    if minor >= 3 {
+        p.write::<u32>(0);
+    }
+
+    // The variation store is referenced with a 32-bit offset, so it comes
+    // after the subtables the packer places.
+    let mut sub_gdef = p.finish()?;
+    if let Some(store) = var_store {
+        let at = sub_gdef.len() as u32;
+        sub_gdef[14..18].copy_from_slice(&at.to_be_bytes());
+        sub_gdef.extend_from_slice(&store);
+    }
+
+    ctx.push(Tag::GDEF, sub_gdef);
+    Ok(())
+}
+
+/// Write an offset to a subtable, or a null offset.
+fn link(p: &mut Packer, subtable: Option<Vec<u8>>) {
+    match subtable {
+        Some(subtable) => p.link(subtable),
+        None => p.null(),
+    }
+}
+
+/// Subset the class definition table at the given offset. Returns `None` if
+/// no retained glyph has a class.
+fn subset_class_def(
+    gdef: &[u8],
+    at: usize,
+    glyphs: &Retained,
+) -> Result<Option<Vec<u8>>> {
+    if u16::read_at(gdef, at)? == 0 {
+        return Ok(None);
+    }
+    let classes = layout::retained_classes(offset(gdef, at)?, glyphs)?;
+    Ok((!classes.is_empty()).then(|| layout::write_class_def(&classes)))
+}
+
+/// Subset the attachment point list. Returns `None` if no retained glyph has
+/// attachment points.
+fn subset_attach_list(gdef: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    if u16::read_at(gdef, 6)? == 0 {
+        return Ok(None);
+    }
+    let list = offset(gdef, 6)?;
+    let covered = retained(offset(list, 0)?, glyphs)?;
+    if covered.is_empty() {
+        return Ok(None);
+    }
+
+    let mut p = Packer::new();
+    p.link(write_coverage(&covered));
+    p.write::<u16>(covered.len() as u16);
+    for &(_, i) in &covered {
+        let points = offset(list, 4 + 2 * i)?;
+        let count = usize::from(u16::read_at(points, 0)?);
+        p.link(points.get(..2 + 2 * count).ok_or(Error::MissingData)?.to_vec());
+    }
+    p.finish().map(Some)
+}
+
+/// Subset the ligature caret list. Returns `None` if no retained glyph has
+/// carets.
+fn subset_lig_caret_list(gdef: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    if u16::read_at(gdef, 8)? == 0 {
+        return Ok(None);
+    }
+    let list = offset(gdef, 8)?;
+    let covered = retained(offset(list, 0)?, glyphs)?;
+    if covered.is_empty() {
+        return Ok(None);
+    }
+
+    let mut p = Packer::new();
+    p.link(write_coverage(&covered));
+    p.write::<u16>(covered.len() as u16);
+    for &(_, i) in &covered {
+        let ligature = offset(list, 4 + 2 * i)?;
+        let count = u16::read_at(ligature, 0)?;
+        let mut q = Packer::new();
+        q.write::<u16>(count);
+        for j in 0..usize::from(count) {
+            q.link(caret_value(offset(ligature, 2 + 2 * j)?)?);
+        }
+        p.link(q.finish()?);
+    }
+    p.finish().map(Some)
+}
+
+/// Extract a caret value table.
+fn caret_value(data: &[u8]) -> Result<Vec<u8>> {
+    match u16::read_at(data, 0)? {
+        1 | 2 => Ok(data.get(..4).ok_or(Error::MissingData)?.to_vec()),
+        3 => {
+            let mut p = Packer::new();
+            p.write::<u16>(3);
+            p.write::<i16>(i16::read_at(data, 2)?);
+            match u16::read_at(data, 4)? {
+                0 => p.null(),
+                _ => p.link(device(offset(data, 4)?)?),
+            }
+            p.finish()
+        }
+        _ => Err(Error::InvalidData),
+    }
+}
+
+/// Subset the coverage tables of the mark glyph sets. Returns `None` if all
+/// sets are empty.
+fn subset_mark_glyph_sets(gdef: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    if u16::read_at(gdef, 12)? == 0 {
+        return Ok(None);
+    }
+    let sets = offset(gdef, 12)?;
+    let count = u16::read_at(sets, 2)?;
+
+    let mut coverages = vec![];
+    for i in 0..usize::from(count) {
+        let at = u32::read_at(sets, 4 + 4 * i)? as usize;
+        let coverage = sets.get(at..).ok_or(Error::InvalidOffset)?;
+        coverages.push(layout::retained_coverage(coverage, glyphs)?);
+    }
+    if coverages.iter().all(Vec::is_empty) {
+        return Ok(None);
+    }
+
+    let mut w = Writer::new();
+    w.write::<u16>(1);
+    w.write::<u16>(count);
+    let mut data = vec![];
+    let mut placed: Vec<(Vec<u16>, u32)> = vec![];
+    let start = 4 + 4 * coverages.len();
+    for coverage in coverages {
+        let at = match placed.iter().find(|(glyphs, _)| *glyphs == coverage) {
+            Some(&(_, at)) => at,
+            None => {
+                let at = (start + data.len()) as u32;
+                data.extend(layout::write_coverage(&coverage));
+                placed.push((coverage, at));
+                at
+            }
+        };
+        w.write::<u32>(at);
+    }
+    w.give(&data);
+    Ok(Some(w.finish()))
+}
+
+/// Write a coverage table for glyphs with their source coverage index.
+fn write_coverage(covered: &[(u16, usize)]) -> Vec<u8> {
+    let glyphs: Vec<u16> = covered.iter().map(|&(glyph, _)| glyph).collect();
+    layout::write_coverage(&glyphs)
+}
+
+/// Extract the item variation store at the given offset, which has no length
+/// field, by finding the end of its furthest part.
+pub(crate) fn item_variation_store(gdef: &[u8], at: usize) -> Result<&[u8]> {
+    let store = gdef.get(at..).ok_or(Error::InvalidOffset)?;
+    let mut end = 8;
+
+    let regions = u32::read_at(store, 2)? as usize;
+    if regions != 0 {
+        let list = store.get(regions..).ok_or(Error::InvalidOffset)?;
+        let axes = usize::from(u16::read_at(list, 0)?);
+        let count = usize::from(u16::read_at(list, 2)?);
+        end = end.max(regions + 4 + 6 * axes * count);
+    }
+
+    let count = usize::from(u16::read_at(store, 6)?);
+    end = end.max(8 + 4 * count);
+    for i in 0..count {
+        let data_at = u32::read_at(store, 8 + 4 * i)? as usize;
+        if data_at == 0 {
+            continue;
+        }
+        let data = store.get(data_at..).ok_or(Error::InvalidOffset)?;
+        let items = usize::from(u16::read_at(data, 0)?);
+        let words = u16::read_at(data, 2)?;
+        let regions = usize::from(u16::read_at(data, 4)?);
+        // The high bit of the word count switches to 32- and 16-bit deltas.
+        let (word, short) = if words & 0x8000 != 0 { (4, 2) } else { (2, 1) };
+        let words = usize::from(words & 0x7FFF);
+        let row = word * words + short * regions.saturating_sub(words);
+        end = end.max(data_at + 6 + 2 * regions + items * row);
+    }
+
+    store.get(..end).ok_or(Error::MissingData)
+}
@@ -1,49 +1,94 @@
+use std::collections::HashMap;
+
 use super::*;
 
 /// A glyf + loca table.
-struct Table<'a> {
-    loca: &'a [u8],
+pub(crate) struct Table<'a> {
+    loca: Loca<'a>,
     glyf: &'a [u8],
-    long: bool,
+}
+
+/// The glyph offsets of a loca table.
+enum Loca<'a> {
+    /// The raw table in the short or long format.
+    Raw { data: &'a [u8], long: bool },
+    /// The offsets read from the table once, shared by repeated subsets.
+    Parsed(Arc<Vec<u32>>),
 }
 
 impl<'a> Table<'a> {
-    fn new(ctx: &Context<'a>) -> Result<Self> {
-        let loca = ctx.expect_table(Tag::LOCA)?;
-        let glyf = ctx.expect_table(Tag::GLYF)?;
-        let head = ctx.expect_table(Tag::HEAD)?;
+    pub(crate) fn new(face: &Face<'a>) -> Result<Self> {
+        let table = |tag| face.table(tag).ok_or(Error::MissingTable(tag));
+        Self::from_tables(table(Tag::LOCA)?, table(Tag::GLYF)?, table(Tag::HEAD)?)
+    }
+
+    /// Read the glyphs of the given loca, glyf and head tables.
+    pub(crate) fn from_tables(
+        loca: &'a [u8],
+        glyf: &'a [u8],
+        head: &[u8],
+    ) -> Result<Self> {
         let long = i16::read_at(head, 50)? != 0;
-        Ok(Self { loca, glyf, long })
+        Ok(Self::from_loca(loca, glyf, long))
     }
 
-    fn glyph_data(&self, id: u16) -> Result<&'a [u8]> {
-        let read_offset = |n| {
-            Ok(if self.long {
-                u32::read_at(self.loca, 4 * n)? as usize
-            } else {
-                u16::read_at(self.loca, 2 * n)? as usize * 2
-            })
-        };
+    /// Read the glyphs of the given loca and glyf tables with a known loca
+    /// format.
+    pub(crate) fn from_loca(loca: &'a [u8], glyf: &'a [u8], long: bool) -> Self {
+        Self { loca: Loca::Raw { data: loca, long }, glyf }
+    }
 
-        let from = read_offset(id as usize)?;
-        let to = read_offset(id as usize + 1)?;
+    /// Read the glyphs of the given glyf table with already parsed offsets.
+    pub(crate) fn from_offsets(offsets: Arc<Vec<u32>>, glyf: &'a [u8]) -> Self {
+        Self { loca: Loca::Parsed(offsets), glyf }
+    }
+
+    /// Read the glyph offsets of the loca table, including the final one.
+    /// A truncated table yields only the offsets it has.
+    pub(crate) fn offsets(&self, num_glyphs: u16) -> Vec<u32> {
+        (0..=num_glyphs as usize)
+            .map_while(|n| self.read_offset(n).ok())
+            .map(|offset| offset as u32)
+            .collect()
+    }
+
+    fn read_offset(&self, n: usize) -> Result<usize> {
+        Ok(match &self.loca {
+            Loca::Raw { data, long: true } => u32::read_at(data, 4 * n)? as usize,
+            Loca::Raw { data, long: false } => u16::read_at(data, 2 * n)? as usize * 2,
+            Loca::Parsed(offsets) => {
+                *offsets.get(n).ok_or(Error::InvalidOffset)? as usize
+            }
+        })
+    }
+
+    pub(crate) fn glyph_data(&self, id: u16) -> Result<&'a [u8]> {
+        let from = self.read_offset(id as usize)?;
+        let to = self.read_offset(id as usize + 1)?;
         self.glyf.get(from..to).ok_or(Error::InvalidOffset)
     }
 }
 
 /// Find all glyphs referenced through components.
 pub(crate) fn discover(ctx: &mut Context) -> Result<()> {
-    let table = Table::new(ctx)?;
+    let table = ctx.glyf()?;
 
     // Because glyphs may depend on other glyphs as components (also with
     // multiple layers of nesting), we have to process all glyphs to find
     // their components.
-    let mut iter = ctx.profile.glyphs.iter().copied();
-    let mut work = vec![0];
+    let requested: HashSet<u16> = ctx.plan.glyphs.iter().copied().collect();
+    let mut iter = ctx.plan.glyphs.clone().into_iter().map(|id| (id, None));
+    let mut work = vec![(0, None)];
 
     // Find composite glyph descriptions.
-    while let Some(id) = work.pop().or_else(|| iter.next()) {
+    while let Some((id, composite)) = work.pop().or_else(|| iter.next()) {
         if ctx.subset.insert(id) {
+            if let Some(composite) = composite {
+                if id != 0 && !requested.contains(&id) {
+                    ctx.components.push((composite, id));
+                }
+            }
+
             let mut r = Reader::new(table.glyph_data(id)?);
             if let Ok(num_contours) = r.read::<i16>() {
                 // Negative means this is a composite glyph.
@@ -54,15 +99,32 @@ pub(crate) fn discover(ctx: &mut Context) -> Result<()> {
                     r.read::<i16>()?;
                     r.read::<i16>()?;
 
-                    // Read component glyphs.
-                    work.extend(component_glyphs(r));
+                    // Read component glyphs. A component that doesn't exist
+                    // would silently break the composite.
+                    for component in component_glyphs(r) {
+                        if component >= ctx.num_glyphs {
+                            if ctx.plan.strict {
+                                return Err(Error::InvalidData);
+                            }
+                            ctx.warnings.push(Warning::ComponentMissing(id, component));
+                            continue;
+                        }
+                        work.push((component, Some(id)));
+                    }
                 }
             }
         }
     }
 
+    // A font without an outline for the .notdef glyph gets a box.
+    if table.glyph_data(0)?.is_empty() {
+        let head = ctx.expect_table(Tag::HEAD)?;
+        ctx.notdef = Some(Notdef::new(u16::read_at(head, 18)?));
+        ctx.warnings.push(Warning::NotdefSynthesized);
+    }
+
     // Compute combined size of all glyphs to select loca format.
-    let mut size = 0;
+    let mut size = ctx.notdef.map_or(0, |notdef| notdef.glyph().len());
     for &id in &ctx.subset {
         let mut len = table.glyph_data(id)?.len();
         len += (len % 2 != 0) as usize;
@@ -74,9 +136,33 @@ pub(crate) fn discover(ctx: &mut Context) -> Result<()> {
     Ok(())
 }
 
+/// Compute which glyphs a profile keeps only because they are components of
+/// its composite glyphs, like the base letters and marks of accented glyphs,
+/// grouped by the composite that references them.
+///
+/// A component that several composites reference is only listed for the first
+/// of them that the closure visits. The composites are sorted by glyph ID.
+/// Fonts with CFF outlines have no composite glyphs, so nothing is listed for
+/// them.
+pub fn closure_by_composite(
+    data: &[u8],
+    index: u32,
+    profile: &Profile,
+) -> Result<Vec<(u16, Vec<u16>)>> {
+    let face = parse(data, index)?;
+    let plan = Plan::new(&face, profile);
+    let ctx = prepare(face, plan)?;
+    let mut components = ctx.components;
+    components.sort_unstable();
+    Ok(components
+        .chunk_by(|a, b| a.0 == b.0)
+        .map(|run| (run[0].0, run.iter().map(|&(_, glyph)| glyph).collect()))
+        .collect())
+}
+
 /// Returns an iterator over the component glyphs referenced by the given
 /// `glyf` table composite glyph description.
-fn component_glyphs(mut r: Reader) -> impl Iterator<Item = u16> + '_ {
+fn component_glyphs(mut r: Reader<'_>) -> impl Iterator<Item = u16> + '_ {
     const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
     const WE_HAVE_A_SCALE: u16 = 0x0008;
     const MORE_COMPONENTS: u16 = 0x0020;
@@ -118,7 +204,7 @@ fn component_glyphs(mut r: Reader) -> impl Iterator<Item = u16> + '_ {
 
 /// Subset the glyf and loca tables by removing glyph data for unused glyphs.
 pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
-    let table = Table::new(ctx)?;
+    let table = ctx.glyf()?;
 
     let mut sub_glyf = Writer::new();
     let mut sub_loca = Writer::new();
@@ -134,9 +220,15 @@ pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
         // If the glyph shouldn't be contained in the subset, it will
         // still get a loca entry, but the glyf data is simply empty.
         write_offset(sub_glyf.len());
-        if ctx.subset.contains(&id) {
+        if let Some(notdef) = ctx.notdef.filter(|_| id == 0) {
+            sub_glyf.give(&notdef.glyph());
+        } else if ctx.subset.contains(&id) {
             let data = table.glyph_data(id)?;
-            sub_glyf.give(data);
+            if ctx.plan.strips_hinting() {
+                sub_glyf.give(&strip_instructions(data)?);
+            } else {
+                sub_glyf.give(data);
+            }
             if !ctx.long_loca {
                 sub_glyf.align(2);
             }
@@ -150,3 +242,435 @@ pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
 
     Ok(())
 }
+
+/// The deepest nesting of composite glyphs that is followed when counting
+/// their points, which also stops at cycles.
+const MAX_COMPONENT_DEPTH: u16 = 64;
+
+/// Recompute the aggregates of the retained glyphs in the subsetted head and
+/// maxp tables: the bounding box of all glyphs in `head`, and the maximum
+/// points, contours, component elements and component depth in `maxp`.
+///
+/// The profile of the source font describes all of its glyphs, which
+/// validators flag once most are gone. The limits of the instructions are
+/// left to [`maxp::subset`].
+pub(crate) fn finalize(ctx: &mut Context) -> Result<()> {
+    let table = |tag| {
+        ctx.tables
+            .iter()
+            .find(|&&(t, _)| t == tag)
+            .map(|(_, data)| data.as_ref())
+    };
+    let (Some(loca), Some(glyf), Some(head)) =
+        (table(Tag::LOCA), table(Tag::GLYF), table(Tag::HEAD))
+    else {
+        return Ok(());
+    };
+    let glyphs = Table::from_tables(loca, glyf, head)?;
+
+    let mut bbox: Option<[i16; 4]> = None;
+    let mut stats = HashMap::new();
+    let (mut points, mut contours) = (0, 0);
+    let (mut composite_points, mut composite_contours) = (0, 0);
+    let (mut elements, mut depth) = (0, 0);
+    for id in 0..ctx.num_glyphs {
+        let data = glyphs.glyph_data(id)?;
+        if data.is_empty() {
+            continue;
+        }
+        let [x_min, y_min, x_max, y_max] =
+            [2, 4, 6, 8].map(|at| i16::read_at(data, at).unwrap_or(0));
+        bbox = Some(match bbox {
+            Some([a, b, c, d]) => {
+                [a.min(x_min), b.min(y_min), c.max(x_max), d.max(y_max)]
+            }
+            None => [x_min, y_min, x_max, y_max],
+        });
+
+        let (glyph_points, glyph_contours, glyph_depth) =
+            outline_stats(&glyphs, id, 0, &mut stats)?;
+        if glyph_depth == 0 {
+            points = points.max(glyph_points);
+            contours = contours.max(glyph_contours);
+        } else {
+            composite_points = composite_points.max(glyph_points);
+            composite_contours = composite_contours.max(glyph_contours);
+            let components = component_glyphs(Reader::new(data.get(10..).unwrap_or(&[])));
+            let components = components.count();
+            elements = elements.max(components);
+            depth = depth.max(glyph_depth);
+        }
+    }
+
+    let mut updated = vec![];
+    if ctx.plan.table(Tag::HEAD) == TableAction::Subset {
+        let mut head = head.to_vec();
+        for (i, value) in bbox.unwrap_or_default().iter().enumerate() {
+            let at = 36 + 2 * i;
+            head.get_mut(at..at + 2)
+                .ok_or(Error::MissingData)?
+                .copy_from_slice(&value.to_be_bytes());
+        }
+        updated.push((Tag::HEAD, head));
+    }
+
+    // Version 0.5 has no profile.
+    let maxp =
+        table(Tag::MAXP).filter(|_| ctx.plan.table(Tag::MAXP) == TableAction::Subset);
+    if let Some(maxp) = maxp.filter(|maxp| u32::read_at(maxp, 0) == Ok(0x00010000)) {
+        let clamp = |v: usize| v.min(u16::MAX.into()) as u16;
+        let mut maxp = maxp.to_vec();
+        for (at, value) in [
+            (6, clamp(points)),
+            (8, clamp(contours)),
+            (10, clamp(composite_points)),
+            (12, clamp(composite_contours)),
+            (28, clamp(elements)),
+            (30, depth),
+        ] {
+            maxp.get_mut(at..at + 2)
+                .ok_or(Error::MissingData)?
+                .copy_from_slice(&value.to_be_bytes());
+        }
+        updated.push((Tag::MAXP, maxp));
+    }
+
+    for (tag, data) in updated {
+        ctx.tables.retain(|&(t, _)| t != tag);
+        ctx.push(tag, data);
+    }
+
+    Ok(())
+}
+
+/// The number of points and contours of a glyph, including those of its
+/// components, and how deeply its components nest, which is zero for simple
+/// glyphs.
+fn outline_stats(
+    glyphs: &Table,
+    id: u16,
+    level: u16,
+    cache: &mut HashMap<u16, (usize, usize, u16)>,
+) -> Result<(usize, usize, u16)> {
+    if let Some(&stats) = cache.get(&id) {
+        return Ok(stats);
+    }
+    // Missing components were already reported by discovery.
+    let Ok(data) = glyphs.glyph_data(id) else { return Ok((0, 0, 0)) };
+    let stats = match i16::read_at(data, 0) {
+        Err(_) | Ok(0) => (0, 0, 0),
+        Ok(num_contours) if num_contours > 0 => {
+            let count = num_contours as usize;
+            let last = u16::read_at(data, 10 + 2 * (count - 1))?;
+            (usize::from(last) + 1, count, 0)
+        }
+        Ok(_) if level >= MAX_COMPONENT_DEPTH => return Ok((0, 0, 0)),
+        Ok(_) => {
+            let (mut points, mut contours, mut depth) = (0, 0, 0);
+            let r = Reader::new(data.get(10..).ok_or(Error::MissingData)?);
+            for component in component_glyphs(r) {
+                let (p, c, d) = outline_stats(glyphs, component, level + 1, cache)?;
+                points += p;
+                contours += c;
+                depth = depth.max(d);
+            }
+            (points, contours, depth + 1)
+        }
+    };
+    cache.insert(id, stats);
+    Ok(stats)
+}
+
+/// Remove the TrueType instructions from a glyph description.
+fn strip_instructions(data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+    const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+    let mut r = Reader::new(data);
+    let Ok(num_contours) = r.read::<i16>() else { return Ok(Cow::Borrowed(data)) };
+
+    // Simple glyphs store their instructions between the contour end points
+    // and the flags.
+    if num_contours >= 0 {
+        let header = 10 + 2 * num_contours as usize;
+        r.skip(header - 2)?;
+        let len = r.read::<u16>()?;
+        r.skip(len as usize)?;
+        let mut w = Writer::new();
+        w.give(&data[..header]);
+        w.write::<u16>(0);
+        w.give(r.data());
+        return Ok(Cow::Owned(w.finish()));
+    }
+
+    // Composite glyphs store them after the last component, which signals
+    // their presence.
+    r.skip(8)?;
+    let (flags_offset, flags) = loop {
+        let offset = data.len() - r.data().len();
+        let flags = r.read::<u16>()?;
+        r.skip(if flags & ARG_1_AND_2_ARE_WORDS != 0 { 6 } else { 4 })?;
+        if flags & WE_HAVE_A_SCALE != 0 {
+            r.skip(2)?;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            r.skip(4)?;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            r.skip(8)?;
+        }
+        if flags & MORE_COMPONENTS == 0 {
+            break (offset, flags);
+        }
+    };
+
+    if flags & WE_HAVE_INSTRUCTIONS == 0 {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    let end = data.len() - r.data().len();
+    let mut stripped = data[..end].to_vec();
+    stripped[flags_offset..flags_offset + 2]
+        .copy_from_slice(&(flags & !WE_HAVE_INSTRUCTIONS).to_be_bytes());
+    Ok(Cow::Owned(stripped))
+}
+
+/// Replace the component glyph IDs of a glyph description. Simple glyphs are
+/// returned unchanged.
+pub(crate) fn remap_components(
+    data: &[u8],
+    map: impl Fn(u16) -> Result<u16>,
+) -> Result<Cow<'_, [u8]>> {
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+    let mut r = Reader::new(data);
+    match r.read::<i16>() {
+        Ok(num_contours) if num_contours < 0 => {}
+        _ => return Ok(Cow::Borrowed(data)),
+    }
+
+    r.skip(8)?;
+    let mut remapped = data.to_vec();
+    loop {
+        let offset = data.len() - r.data().len();
+        let flags = r.read::<u16>()?;
+        let component = map(r.read::<u16>()?)?;
+        remapped[offset + 2..offset + 4].copy_from_slice(&component.to_be_bytes());
+        r.skip(if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 })?;
+        if flags & WE_HAVE_A_SCALE != 0 {
+            r.skip(2)?;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            r.skip(4)?;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            r.skip(8)?;
+        }
+        if flags & MORE_COMPONENTS == 0 {
+            return Ok(Cow::Owned(remapped));
+        }
+    }
+}
+
+/// The points of a simple glyph with their coordinates and whether they are on
+/// the curve.
+pub(crate) type Points = Vec<(i32, i32, bool)>;
+
+/// Read the flags and coordinates of the given number of points of a simple
+/// glyph, and whether the first flag marks overlapping contours.
+pub(crate) fn read_points(r: &mut Reader, count: usize) -> Result<(Points, bool)> {
+    const ON_CURVE_POINT: u8 = 0x01;
+    const X_SHORT_VECTOR: u8 = 0x02;
+    const Y_SHORT_VECTOR: u8 = 0x04;
+    const REPEAT_FLAG: u8 = 0x08;
+    const X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+    const OVERLAP_SIMPLE: u8 = 0x40;
+
+    let mut flags = Vec::with_capacity(count);
+    while flags.len() < count {
+        let flag = r.read::<u8>()?;
+        flags.push(flag);
+        if flag & REPEAT_FLAG != 0 {
+            for _ in 0..r.read::<u8>()? {
+                flags.push(flag);
+            }
+        }
+    }
+    flags.truncate(count);
+
+    let mut coordinates = |short: u8, same: u8| -> Result<Vec<i32>> {
+        let mut value = 0;
+        let mut values = Vec::with_capacity(count);
+        for &flag in &flags {
+            value += if flag & short != 0 {
+                let delta = i32::from(r.read::<u8>()?);
+                if flag & same != 0 {
+                    delta
+                } else {
+                    -delta
+                }
+            } else if flag & same != 0 {
+                0
+            } else {
+                i32::from(r.read::<i16>()?)
+            };
+            values.push(value);
+        }
+        Ok(values)
+    };
+    let xs = coordinates(X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE)?;
+    let ys = coordinates(Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE)?;
+
+    let overlap = flags.first().is_some_and(|flag| flag & OVERLAP_SIMPLE != 0);
+    let points = xs
+        .into_iter()
+        .zip(ys)
+        .zip(&flags)
+        .map(|((x, y), flag)| (x, y, flag & ON_CURVE_POINT != 0))
+        .collect();
+    Ok((points, overlap))
+}
+
+/// Write the flags and coordinates of a simple glyph in the regular `glyf`
+/// encoding.
+pub(crate) fn write_points(
+    w: &mut Writer,
+    points: &[(i32, i32, bool)],
+    first_flag: Option<u8>,
+) {
+    const ON_CURVE_POINT: u8 = 0x01;
+    const X_SHORT_VECTOR: u8 = 0x02;
+    const Y_SHORT_VECTOR: u8 = 0x04;
+    const REPEAT_FLAG: u8 = 0x08;
+    const X_IS_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_IS_SAME_OR_POSITIVE: u8 = 0x20;
+
+    let mut flags: Vec<u8> = vec![];
+    let mut xs = Writer::new();
+    let mut ys = Writer::new();
+    let mut last_flag = None;
+    let mut repeats = 0;
+    let (mut x, mut y) = (0, 0);
+
+    for (i, &(px, py, on_curve)) in points.iter().enumerate() {
+        let mut flag = if on_curve { ON_CURVE_POINT } else { 0 };
+        if i == 0 {
+            flag |= first_flag.unwrap_or(0);
+        }
+
+        let mut coordinate = |delta: i32, w: &mut Writer, short: u8, same: u8| {
+            if delta == 0 {
+                flag |= same;
+            } else if (-255..=255).contains(&delta) {
+                flag |= short;
+                if delta > 0 {
+                    flag |= same;
+                }
+                w.write::<u8>(delta.unsigned_abs() as u8);
+            } else {
+                w.write::<i16>(delta as i16);
+            }
+        };
+        coordinate(px - x, &mut xs, X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE);
+        coordinate(py - y, &mut ys, Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE);
+        (x, y) = (px, py);
+
+        match last_flag {
+            Some(last) if last == flag && repeats < 255 => {
+                if repeats == 0 {
+                    *flags.last_mut().unwrap() |= REPEAT_FLAG;
+                    flags.push(1);
+                } else {
+                    *flags.last_mut().unwrap() += 1;
+                }
+                repeats += 1;
+            }
+            _ => {
+                flags.push(flag);
+                last_flag = Some(flag);
+                repeats = 0;
+            }
+        }
+    }
+
+    w.give(&flags);
+    w.give(&xs.finish());
+    w.give(&ys.finish());
+}
+
+/// A synthesized .notdef glyph in the shape of a hollow box.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Notdef {
+    /// The advance width of the glyph.
+    pub advance: u16,
+    /// The left side bearing of the glyph.
+    pub lsb: i16,
+    /// The height of the box.
+    height: i16,
+    /// The stroke width of the box.
+    stroke: i16,
+}
+
+impl Notdef {
+    /// Create a box fitting the given units per em.
+    pub fn new(units_per_em: u16) -> Self {
+        let em = units_per_em.clamp(16, 16384) as i16;
+        Self {
+            advance: (em / 2) as u16,
+            lsb: em / 20,
+            height: em * 7 / 10,
+            stroke: em / 20,
+        }
+    }
+
+    /// Encode the box as a simple glyph description.
+    pub fn glyph(&self) -> Vec<u8> {
+        let (x0, x1) = (self.lsb, self.advance as i16 - self.lsb);
+        let (y0, y1) = (0, self.height);
+        let s = self.stroke;
+
+        // The outer contour runs clockwise and the inner one counter-clockwise.
+        let points = [
+            (x0, y0),
+            (x0, y1),
+            (x1, y1),
+            (x1, y0),
+            (x0 + s, y0 + s),
+            (x1 - s, y0 + s),
+            (x1 - s, y1 - s),
+            (x0 + s, y1 - s),
+        ];
+
+        let mut w = Writer::new();
+        w.write::<i16>(2);
+        for v in [x0, y0, x1, y1] {
+            w.write::<i16>(v);
+        }
+        w.write::<u16>(3);
+        w.write::<u16>(7);
+        w.write::<u16>(0);
+
+        // All points are on the curve and use full 16-bit deltas.
+        for _ in points {
+            w.write::<u8>(0x01);
+        }
+        let mut prev = 0;
+        for (x, _) in points {
+            w.write::<i16>(x - prev);
+            prev = x;
+        }
+        prev = 0;
+        for (_, y) in points {
+            w.write::<i16>(y - prev);
+            prev = y;
+        }
+
+        w.align(2);
+        w.finish()
+    }
+}
@@ -0,0 +1,400 @@
+use super::*;
+use crate::layout::{
+    self, device, offset, retained, Classes, LookupMap, Packer, Retained,
+};
+
+/// Subset the `GPOS` table to the retained glyphs.
+///
+/// Adjustments and attachments that involve glyphs which aren't retained are
+/// removed, and so are the lookups, features and scripts that are left empty.
+/// The remaining glyphs are referred to by their output IDs.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let glyphs = layout::retained_glyphs(ctx);
+    let merge = |_, subtables| Ok(subtables);
+    layout::subset(ctx, Tag::GPOS, 9, supported, merge, |kind, subtable, lookups| {
+        subset_subtable(kind, subtable, &glyphs, lookups)
+    })
+}
+
+/// Whether positioning subtables of the given lookup type and format can be
+/// subsetted.
+fn supported(kind: u16, format: u16) -> bool {
+    matches!((kind, format), (1 | 2, 1 | 2) | (3..=6, 1) | (7 | 8, 1..=3))
+}
+
+/// Subset a positioning subtable. Returns `None` if it doesn't position any
+/// retained glyphs anymore.
+fn subset_subtable(
+    kind: u16,
+    subtable: &[u8],
+    glyphs: &Retained,
+    lookups: LookupMap,
+) -> Result<Option<Vec<u8>>> {
+    match (kind, u16::read_at(subtable, 0)?) {
+        (1, format @ (1 | 2)) => subset_single(format, subtable, glyphs),
+        (2, 1) => subset_pairs(subtable, glyphs),
+        (2, 2) => subset_class_pairs(subtable, glyphs),
+        (3, 1) => subset_cursive(subtable, glyphs),
+        (4 | 6, 1) => subset_mark_attachment(subtable, glyphs),
+        (5, 1) => subset_mark_ligature(subtable, glyphs),
+        (7 | 8, _) => layout::subset_context(kind == 8, subtable, glyphs, lookups),
+        _ => Err(Error::InvalidData),
+    }
+}
+
+/// A field of a value record.
+enum Field {
+    /// A placement or advance adjustment.
+    Value(u16),
+    /// A device or variation index table, if any.
+    Device(Option<Vec<u8>>),
+}
+
+/// The size of a value record with the given format.
+fn value_size(format: u16) -> usize {
+    2 * (format & 0xFF).count_ones() as usize
+}
+
+/// Read a value record, resolving its device offsets from the start of the
+/// given parent table.
+fn read_value(r: &mut Reader, format: u16, parent: &[u8]) -> Result<Vec<Field>> {
+    let mut fields = vec![];
+    for bit in (0..8).filter(|bit| format & (1 << bit) != 0) {
+        let value = r.read::<u16>()?;
+        fields.push(match (bit, value) {
+            (0..=3, _) => Field::Value(value),
+            (_, 0) => Field::Device(None),
+            _ => {
+                let data =
+                    parent.get(usize::from(value)..).ok_or(Error::InvalidOffset)?;
+                Field::Device(Some(device(data)?))
+            }
+        });
+    }
+    Ok(fields)
+}
+
+/// Write a value record.
+fn write_value(p: &mut Packer, fields: &[Field]) {
+    for field in fields {
+        match field {
+            Field::Value(value) => p.write::<u16>(*value),
+            Field::Device(Some(device)) => p.link(device.clone()),
+            Field::Device(None) => p.null(),
+        }
+    }
+}
+
+/// Extract the anchor table at the given offset from the start of the parent
+/// table, if any.
+fn anchor(parent: &[u8], at: usize) -> Result<Option<Vec<u8>>> {
+    if u16::read_at(parent, at)? == 0 {
+        return Ok(None);
+    }
+    let data = offset(parent, at)?;
+    let anchor = match u16::read_at(data, 0)? {
+        1 => data.get(..6).ok_or(Error::MissingData)?.to_vec(),
+        2 => data.get(..8).ok_or(Error::MissingData)?.to_vec(),
+        3 => {
+            let mut p = Packer::new();
+            p.write::<u16>(3);
+            p.write::<u16>(u16::read_at(data, 2)?);
+            p.write::<u16>(u16::read_at(data, 4)?);
+            for at in [6, 8] {
+                match u16::read_at(data, at)? {
+                    0 => p.null(),
+                    _ => p.link(device(offset(data, at)?)?),
+                }
+            }
+            p.finish()?
+        }
+        _ => return Err(Error::InvalidData),
+    };
+    Ok(Some(anchor))
+}
+
+/// Write an offset to an anchor table, or a null offset.
+fn link_anchor(p: &mut Packer, anchor: Option<Vec<u8>>) {
+    match anchor {
+        Some(anchor) => p.link(anchor),
+        None => p.null(),
+    }
+}
+
+/// Write a coverage table for glyphs with their source coverage index.
+fn write_coverage(covered: &[(u16, usize)]) -> Vec<u8> {
+    let glyphs: Vec<u16> = covered.iter().map(|&(glyph, _)| glyph).collect();
+    layout::write_coverage(&glyphs)
+}
+
+/// Subset a single adjustment subtable.
+fn subset_single(
+    format: u16,
+    subtable: &[u8],
+    glyphs: &Retained,
+) -> Result<Option<Vec<u8>>> {
+    let covered = retained(offset(subtable, 2)?, glyphs)?;
+    if covered.is_empty() {
+        return Ok(None);
+    }
+
+    let value_format = u16::read_at(subtable, 4)? & 0xFF;
+    let mut p = Packer::new();
+    p.write::<u16>(format);
+    p.link(write_coverage(&covered));
+    p.write::<u16>(value_format);
+    if format == 1 {
+        let mut r = Reader::new(subtable.get(6..).ok_or(Error::MissingData)?);
+        write_value(&mut p, &read_value(&mut r, value_format, subtable)?);
+    } else {
+        p.write::<u16>(covered.len() as u16);
+        let size = value_size(value_format);
+        for &(_, i) in &covered {
+            let data = subtable.get(8 + size * i..).ok_or(Error::MissingData)?;
+            write_value(
+                &mut p,
+                &read_value(&mut Reader::new(data), value_format, subtable)?,
+            );
+        }
+    }
+    p.finish().map(Some)
+}
+
+/// Subset a pair adjustment subtable with pairs of glyphs.
+fn subset_pairs(subtable: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    let format1 = u16::read_at(subtable, 4)? & 0xFF;
+    let format2 = u16::read_at(subtable, 6)? & 0xFF;
+
+    let mut sets = vec![];
+    for (glyph, i) in retained(offset(subtable, 2)?, glyphs)? {
+        // Device offsets are relative to the pair set.
+        let set = offset(subtable, 10 + 2 * i)?;
+        let mut r = Reader::new(set);
+        let mut pairs = vec![];
+        for _ in 0..r.read::<u16>()? {
+            let second = r.read::<u16>()?;
+            let value1 = read_value(&mut r, format1, set)?;
+            let value2 = read_value(&mut r, format2, set)?;
+            if let Some(&second) = glyphs.get(&second) {
+                pairs.push((second, value1, value2));
+            }
+        }
+        pairs.sort_by_key(|&(second, ..)| second);
+        pairs.dedup_by_key(|&mut (second, ..)| second);
+        if pairs.is_empty() {
+            continue;
+        }
+
+        let mut p = Packer::new();
+        p.write::<u16>(pairs.len() as u16);
+        for (second, value1, value2) in pairs {
+            p.write::<u16>(second);
+            write_value(&mut p, &value1);
+            write_value(&mut p, &value2);
+        }
+        sets.push(((glyph, i), p.finish()?));
+    }
+
+    if sets.is_empty() {
+        return Ok(None);
+    }
+
+    let covered: Vec<(u16, usize)> = sets.iter().map(|&(covered, _)| covered).collect();
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.link(write_coverage(&covered));
+    p.write::<u16>(format1);
+    p.write::<u16>(format2);
+    p.write::<u16>(sets.len() as u16);
+    for (_, set) in sets {
+        p.link(set);
+    }
+    p.finish().map(Some)
+}
+
+/// Subset a pair adjustment subtable with pairs of glyph classes. The classes
+/// without retained glyphs are removed from the class matrix.
+fn subset_class_pairs(subtable: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    let covered = retained(offset(subtable, 2)?, glyphs)?;
+    if covered.is_empty() {
+        return Ok(None);
+    }
+
+    let format1 = u16::read_at(subtable, 4)? & 0xFF;
+    let format2 = u16::read_at(subtable, 6)? & 0xFF;
+    let (class_def1, classes1) = compact_classes(offset(subtable, 8)?, glyphs)?;
+    let (class_def2, classes2) = compact_classes(offset(subtable, 10)?, glyphs)?;
+    let count1 = usize::from(u16::read_at(subtable, 12)?);
+    let count2 = usize::from(u16::read_at(subtable, 14)?);
+
+    let mut p = Packer::new();
+    p.write::<u16>(2);
+    p.link(write_coverage(&covered));
+    p.write::<u16>(format1);
+    p.write::<u16>(format2);
+    p.link(layout::write_class_def(&class_def1));
+    p.link(layout::write_class_def(&class_def2));
+    p.write::<u16>(classes1.len() as u16);
+    p.write::<u16>(classes2.len() as u16);
+
+    let size = value_size(format1) + value_size(format2);
+    for &class1 in &classes1 {
+        for &class2 in &classes2 {
+            let (class1, class2) = (usize::from(class1), usize::from(class2));
+            if class1 >= count1 || class2 >= count2 {
+                return Err(Error::InvalidData);
+            }
+            let at = 16 + size * (count2 * class1 + class2);
+            let mut r = Reader::new(subtable.get(at..).ok_or(Error::MissingData)?);
+            write_value(&mut p, &read_value(&mut r, format1, subtable)?);
+            write_value(&mut p, &read_value(&mut r, format2, subtable)?);
+        }
+    }
+    p.finish().map(Some)
+}
+
+/// Renumber the classes of a class definition table that have retained
+/// glyphs, keeping class 0. Returns the retained glyphs with their new class
+/// and the old class of each new one.
+fn compact_classes(data: &[u8], glyphs: &Retained) -> Result<(Classes, Vec<u16>)> {
+    let mut classes = layout::retained_classes(data, glyphs)?;
+    let mut old: Vec<u16> = classes.iter().map(|&(_, class)| class).collect();
+    old.push(0);
+    old.sort_unstable();
+    old.dedup();
+
+    for (_, class) in &mut classes {
+        *class = old.binary_search(class).map_err(|_| Error::InvalidData)? as u16;
+    }
+    Ok((classes, old))
+}
+
+/// Subset a cursive attachment subtable.
+fn subset_cursive(subtable: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    let covered = retained(offset(subtable, 2)?, glyphs)?;
+    if covered.is_empty() {
+        return Ok(None);
+    }
+
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.link(write_coverage(&covered));
+    p.write::<u16>(covered.len() as u16);
+    for &(_, i) in &covered {
+        link_anchor(&mut p, anchor(subtable, 6 + 4 * i)?);
+        link_anchor(&mut p, anchor(subtable, 8 + 4 * i)?);
+    }
+    p.finish().map(Some)
+}
+
+/// The retained marks of a mark attachment subtable, as their coverage table,
+/// their mark array and the old class of each new mark class.
+struct Marks {
+    coverage: Vec<u8>,
+    array: Vec<u8>,
+    classes: Vec<u16>,
+}
+
+/// Subset the marks of a mark attachment subtable, renumbering the classes
+/// that retained marks use.
+fn subset_marks(subtable: &[u8], glyphs: &Retained) -> Result<Option<Marks>> {
+    let covered = retained(offset(subtable, 2)?, glyphs)?;
+    let array = offset(subtable, 8)?;
+
+    let mut marks = vec![];
+    for &(_, i) in &covered {
+        let class = u16::read_at(array, 2 + 4 * i)?;
+        marks.push((class, anchor(array, 4 + 4 * i)?));
+    }
+
+    let mut classes: Vec<u16> = marks.iter().map(|&(class, _)| class).collect();
+    classes.sort_unstable();
+    classes.dedup();
+    if classes.is_empty() {
+        return Ok(None);
+    }
+
+    let mut p = Packer::new();
+    p.write::<u16>(marks.len() as u16);
+    for (class, anchor) in marks {
+        p.write::<u16>(
+            classes.binary_search(&class).map_err(|_| Error::InvalidData)? as u16
+        );
+        link_anchor(&mut p, anchor);
+    }
+
+    Ok(Some(Marks {
+        coverage: write_coverage(&covered),
+        array: p.finish()?,
+        classes,
+    }))
+}
+
+/// Subset a mark-to-base or mark-to-mark attachment subtable.
+fn subset_mark_attachment(subtable: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    let Some(marks) = subset_marks(subtable, glyphs)? else { return Ok(None) };
+    let bases = retained(offset(subtable, 4)?, glyphs)?;
+    if bases.is_empty() {
+        return Ok(None);
+    }
+
+    // Each base record has an anchor offset per mark class.
+    let count = usize::from(u16::read_at(subtable, 6)?);
+    let array = offset(subtable, 10)?;
+    let mut p = Packer::new();
+    p.write::<u16>(bases.len() as u16);
+    for &(_, i) in &bases {
+        for &class in &marks.classes {
+            let at = 2 + 2 * (count * i + usize::from(class));
+            link_anchor(&mut p, anchor(array, at)?);
+        }
+    }
+    let base_array = p.finish()?;
+
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.link(marks.coverage);
+    p.link(write_coverage(&bases));
+    p.write::<u16>(marks.classes.len() as u16);
+    p.link(marks.array);
+    p.link(base_array);
+    p.finish().map(Some)
+}
+
+/// Subset a mark-to-ligature attachment subtable.
+fn subset_mark_ligature(subtable: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    let Some(marks) = subset_marks(subtable, glyphs)? else { return Ok(None) };
+    let ligatures = retained(offset(subtable, 4)?, glyphs)?;
+    if ligatures.is_empty() {
+        return Ok(None);
+    }
+
+    // Each component record has an anchor offset per mark class.
+    let count = usize::from(u16::read_at(subtable, 6)?);
+    let array = offset(subtable, 10)?;
+    let mut p = Packer::new();
+    p.write::<u16>(ligatures.len() as u16);
+    for &(_, i) in &ligatures {
+        let attach = offset(array, 2 + 2 * i)?;
+        let components = u16::read_at(attach, 0)?;
+        let mut q = Packer::new();
+        q.write::<u16>(components);
+        for component in 0..usize::from(components) {
+            for &class in &marks.classes {
+                let at = 2 + 2 * (count * component + usize::from(class));
+                link_anchor(&mut q, anchor(attach, at)?);
+            }
+        }
+        p.link(q.finish()?);
+    }
+    let ligature_array = p.finish()?;
+
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.link(marks.coverage);
+    p.link(write_coverage(&ligatures));
+    p.write::<u16>(marks.classes.len() as u16);
+    p.link(marks.array);
+    p.link(ligature_array);
+    p.finish().map(Some)
+}
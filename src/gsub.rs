@@ -0,0 +1,774 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::layout::{self, coverage, glyph_array, offset, LookupMap, Packer, Retained};
+
+/// The glyph composition and decomposition feature.
+pub(crate) const CCMP: &[u8; 4] = b"ccmp";
+/// The vertical alternates feature.
+pub(crate) const VERT: &[u8; 4] = b"vert";
+/// The vertical alternates and rotation feature.
+pub(crate) const VRT2: &[u8; 4] = b"vrt2";
+
+/// Add the glyphs that the given features can produce from the planned glyphs
+/// to the plan, like the precomposed glyph for a base and a combining mark
+/// from `ccmp`.
+///
+/// Contextual lookups are followed without checking their context, so this
+/// may keep a few glyphs too many, but never too few. Malformed data only ends
+/// the closure early, which is reported as a warning. Features in the plan's
+/// strict closure only substitute the planned glyphs themselves, and their
+/// contextual lookups are only followed for rules whose whole input sequence
+/// is planned.
+///
+/// The added glyphs are also recorded by the feature that produced them
+/// first, in the order of the given features.
+pub(crate) fn close(ctx: &mut Context, features: &[[u8; 4]]) {
+    if features.is_empty() {
+        return;
+    }
+    let Some(gsub) = ctx.face.table(Tag::GSUB) else { return };
+    let mut glyphs: HashSet<u16> = ctx.plan.glyphs.iter().copied().collect();
+    let mut added: Vec<(Tag, Vec<u16>)> = vec![];
+    for &feature in features {
+        if !added.iter().any(|&(tag, _)| tag.0 == feature) {
+            added.push((Tag(feature), vec![]));
+        }
+    }
+    let strict: Vec<bool> = added
+        .iter()
+        .map(|(tag, _)| ctx.plan.strict_closure.contains(tag))
+        .collect();
+    if closure(gsub, ctx.num_glyphs, &mut glyphs, &mut added, &strict).is_err() {
+        ctx.warnings.push(Warning::ClosureIncomplete(Tag::GSUB));
+    }
+
+    added.retain(|(_, glyphs)| !glyphs.is_empty());
+    for (_, glyphs) in &mut added {
+        glyphs.sort_unstable();
+        ctx.plan.glyphs.extend(glyphs.iter().copied());
+    }
+    ctx.closure = added;
+}
+
+/// Extend the glyphs with the outputs of the features' lookups until nothing
+/// changes anymore, and record the new glyphs by feature. Strict features only
+/// substitute the initial glyphs.
+fn closure(
+    gsub: &[u8],
+    num_glyphs: u16,
+    glyphs: &mut HashSet<u16>,
+    added: &mut [(Tag, Vec<u16>)],
+    strict: &[bool],
+) -> Result<()> {
+    let requested = glyphs.clone();
+    let features = offset(gsub, 6)?;
+    let lookups = offset(gsub, 8)?;
+
+    let mut indices = added
+        .iter()
+        .map(|(tag, _)| feature_lookups(features, *tag))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Contextual lookups apply other lookups, which are part of the closure
+    // as well.
+    for (indices, &strict) in indices.iter_mut().zip(strict) {
+        let filter = strict.then_some(&requested);
+        let mut i = 0;
+        while let Some(&index) = indices.get(i) {
+            for (kind, subtable) in subtables(lookups, index)? {
+                for nested in nested_lookups(kind, subtable, filter)? {
+                    if !indices.contains(&nested) {
+                        indices.push(nested);
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+
+    loop {
+        let before = glyphs.len();
+        for ((indices, (_, added)), &strict) in
+            indices.iter().zip(added.iter_mut()).zip(strict)
+        {
+            for &index in indices {
+                for (kind, subtable) in subtables(lookups, index)? {
+                    let inputs = if strict { &requested } else { &*glyphs };
+                    for glyph in substitutes(kind, subtable, inputs)? {
+                        if glyph < num_glyphs && glyphs.insert(glyph) {
+                            added.push(glyph);
+                        }
+                    }
+                }
+            }
+        }
+        if glyphs.len() == before {
+            return Ok(());
+        }
+    }
+}
+
+/// Compute which glyphs the `GSUB` closure of a profile adds to its requested
+/// glyphs, grouped by the feature that produced them, like ligatures from
+/// `liga` or small capitals from `smcp`.
+///
+/// This helps to decide which features to leave out of
+/// [`Profile::layout_closure`] to shrink the subset further. A glyph that
+/// several features produce is only listed for the first of them, in the
+/// order `ccmp`, `vert`, `vrt2` and then the layout closure's features sorted
+/// by tag. Features that add no glyphs are left out.
+pub fn closure_by_feature(
+    data: &[u8],
+    index: u32,
+    profile: &Profile,
+) -> Result<Vec<(Tag, Vec<u16>)>> {
+    let face = parse(data, index)?;
+    let plan = Plan::new(&face, profile);
+    let ctx = prepare(face, plan)?;
+    Ok(ctx.closure)
+}
+
+/// The alternate glyphs a font offers for a character.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Alternates {
+    /// The character's codepoint.
+    pub codepoint: u32,
+    /// The glyph that the character is mapped to.
+    pub glyph: u16,
+    /// The alternates of the glyph by feature, in the order `aalt`, `salt`
+    /// and `ss01` to `ss20`. Features without alternates are left out.
+    pub features: Vec<(Tag, Vec<u16>)>,
+}
+
+/// List the alternate glyphs that the `aalt`, `salt` and stylistic set
+/// features offer for each mapped character of a font, sorted by codepoint.
+///
+/// Called on a subset, this lists what a font picker can present to the user
+/// without a shaping library. Only single and alternate substitutions applied
+/// directly by the features are considered, and characters without any
+/// alternates are left out.
+pub fn alternates(data: &[u8], index: u32) -> Result<Vec<Alternates>> {
+    let face = parse(data, index)?;
+    let Some(gsub) = face.table(Tag::GSUB) else { return Ok(vec![]) };
+    let num_glyphs =
+        u16::read_at(face.table(Tag::MAXP).ok_or(Error::MissingTable(Tag::MAXP))?, 4)?;
+    let cmap = match face.table(Tag::CMAP) {
+        Some(data) => cmap::mappings(data)?,
+        None => vec![],
+    };
+
+    let features = offset(gsub, 6)?;
+    let lookups = offset(gsub, 8)?;
+    let glyphs: HashSet<u16> = cmap.iter().map(|&(_, glyph)| glyph).collect();
+    let mut tags = vec![Tag(*b"aalt"), Tag(*b"salt")];
+    tags.extend((1..=20).map(|n| Tag([b's', b's', b'0' + n / 10, b'0' + n % 10])));
+
+    let mut found: HashMap<u16, Vec<(Tag, Vec<u16>)>> = HashMap::new();
+    for tag in tags {
+        for index in feature_lookups(features, tag)? {
+            for (kind, subtable) in subtables(lookups, index)? {
+                if kind != 1 && kind != 3 {
+                    continue;
+                }
+                for (glyph, i) in coverage(offset(subtable, 2)?)? {
+                    if !glyphs.contains(&glyph) {
+                        continue;
+                    }
+                    let entry = found.entry(glyph).or_default();
+                    if entry.last().is_none_or(|(last, _)| *last != tag) {
+                        entry.push((tag, vec![]));
+                    }
+                    let list = &mut entry.last_mut().unwrap().1;
+                    for alternate in substitute(kind, subtable, glyph, i, &glyphs)? {
+                        if alternate < num_glyphs
+                            && alternate != glyph
+                            && !list.contains(&alternate)
+                        {
+                            list.push(alternate);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(cmap
+        .into_iter()
+        .filter_map(|(codepoint, glyph)| {
+            let mut features = found.get(&glyph)?.clone();
+            features.retain(|(_, alternates)| !alternates.is_empty());
+            (!features.is_empty()).then_some(Alternates { codepoint, glyph, features })
+        })
+        .collect())
+}
+
+/// Select the glyph of each character under its feature set, like the small
+/// capital of `a` under `smcp`, in the order of the requests.
+///
+/// The character is looked up in the `cmap` table, and then the single and
+/// alternate substitutions of the features' lookups are applied in lookup
+/// order, taking the first alternate. Other lookups, like contextual ones,
+/// need a shaper and are not applied. Characters that aren't mapped give
+/// `None`.
+///
+/// The selected glyphs can be passed to a [`Profile`] like any other glyph.
+/// Their IDs are kept, unless the profile
+/// [remaps glyphs](Profile::remap_glyphs), in which case
+/// [`subset_with_glyph_map`](crate::subset_with_glyph_map) tells their new
+/// IDs.
+pub fn variant_glyphs(
+    data: &[u8],
+    index: u32,
+    requests: &[(char, &[Tag])],
+) -> Result<Vec<Option<u16>>> {
+    let face = parse(data, index)?;
+    let cmap = face.table(Tag::CMAP).ok_or(Error::MissingTable(Tag::CMAP))?;
+    let gsub = face.table(Tag::GSUB);
+    let none = HashSet::new();
+
+    let mut variants = vec![];
+    for &(c, features) in requests {
+        let Some(mut glyph) = cmap::lookup(cmap, u32::from(c))? else {
+            variants.push(None);
+            continue;
+        };
+        if let Some(gsub) = gsub {
+            let mut indices = vec![];
+            for &tag in features {
+                indices.extend(feature_lookups(offset(gsub, 6)?, tag)?);
+            }
+            indices.sort_unstable();
+            indices.dedup();
+            for index in indices {
+                for (kind, subtable) in subtables(offset(gsub, 8)?, index)? {
+                    if kind != 1 && kind != 3 {
+                        continue;
+                    }
+                    let covered = coverage(offset(subtable, 2)?)?;
+                    if let Some(&(_, i)) = covered.iter().find(|&&(g, _)| g == glyph) {
+                        if let Some(&output) =
+                            substitute(kind, subtable, glyph, i, &none)?.first()
+                        {
+                            glyph = output;
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        variants.push(Some(glyph));
+    }
+    Ok(variants)
+}
+
+/// The subtables of a lookup with their lookup type, resolving extension
+/// subtables.
+fn subtables(lookups: &[u8], index: u16) -> Result<Vec<(u16, &[u8])>> {
+    let lookup = offset(lookups, 2 + 2 * usize::from(index))?;
+    let kind = u16::read_at(lookup, 0)?;
+    let mut subtables = vec![];
+    for i in 0..usize::from(u16::read_at(lookup, 4)?) {
+        let subtable = offset(lookup, 6 + 2 * i)?;
+        if kind == 7 {
+            let kind = u16::read_at(subtable, 2)?;
+            let offset = u32::read_at(subtable, 4)? as usize;
+            subtables.push((kind, subtable.get(offset..).ok_or(Error::InvalidOffset)?));
+        } else {
+            subtables.push((kind, subtable));
+        }
+    }
+    Ok(subtables)
+}
+
+/// The glyphs a single, multiple, alternate or ligature substitution subtable
+/// produces from the given glyphs.
+fn substitutes(kind: u16, subtable: &[u8], glyphs: &HashSet<u16>) -> Result<Vec<u16>> {
+    if !(1..=4).contains(&kind) {
+        return Ok(vec![]);
+    }
+
+    let covered = coverage(offset(subtable, 2)?)?
+        .into_iter()
+        .filter(|(glyph, _)| glyphs.contains(glyph));
+
+    let mut outputs = vec![];
+    for (glyph, i) in covered {
+        outputs.extend(substitute(kind, subtable, glyph, i, glyphs)?);
+    }
+    Ok(outputs)
+}
+
+/// The glyphs a substitution subtable produces from the glyph at the given
+/// coverage index. Ligatures are only formed from the given glyphs.
+fn substitute(
+    kind: u16,
+    subtable: &[u8],
+    glyph: u16,
+    i: usize,
+    glyphs: &HashSet<u16>,
+) -> Result<Vec<u16>> {
+    let mut outputs = vec![];
+    match (kind, u16::read_at(subtable, 0)?) {
+        (1, 1) => {
+            let delta = i16::read_at(subtable, 4)?;
+            outputs.push(glyph.wrapping_add_signed(delta));
+        }
+        (1, 2) => outputs.push(u16::read_at(subtable, 6 + 2 * i)?),
+        // Multiple and alternate substitutions have the same layout.
+        (2 | 3, 1) => outputs.extend(glyph_array(offset(subtable, 6 + 2 * i)?)?),
+        (4, 1) => {
+            let set = offset(subtable, 6 + 2 * i)?;
+            for j in 0..usize::from(u16::read_at(set, 0)?) {
+                let ligature = offset(set, 2 + 2 * j)?;
+                let count = u16::read_at(ligature, 2)?;
+                let mut components =
+                    (1..usize::from(count)).map(|k| u16::read_at(ligature, 2 + 2 * k));
+                if components.try_fold(true, |all, c| Ok(all && glyphs.contains(&c?)))? {
+                    outputs.push(u16::read_at(ligature, 0)?);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(outputs)
+}
+
+/// The indices of the lookups of a feature. A feature may have several
+/// records, e.g. for different scripts, so the lookups of all of them are
+/// collected.
+fn feature_lookups(features: &[u8], tag: Tag) -> Result<Vec<u16>> {
+    let mut indices = vec![];
+    for i in 0..usize::from(u16::read_at(features, 0)?) {
+        let record = 2 + 6 * i;
+        if features.get(record..record + 4) == Some(&tag.0[..]) {
+            let feature = offset(features, record + 4)?;
+            for j in 0..usize::from(u16::read_at(feature, 2)?) {
+                let index = u16::read_at(feature, 4 + 2 * j)?;
+                if !indices.contains(&index) {
+                    indices.push(index);
+                }
+            }
+        }
+    }
+    Ok(indices)
+}
+
+/// The lookups a contextual or chained contextual subtable applies.
+///
+/// With a filter, only the lookups of rules whose whole input sequence can
+/// match the filter's glyphs are returned.
+fn nested_lookups(
+    kind: u16,
+    subtable: &[u8],
+    filter: Option<&HashSet<u16>>,
+) -> Result<Vec<u16>> {
+    let chained = kind == 6;
+    let mut nested = vec![];
+    match (kind, u16::read_at(subtable, 0)?) {
+        (5 | 6, format @ (1 | 2)) => {
+            // Format 2 has class definitions before the rule sets.
+            let sets = match (chained, format) {
+                (_, 1) => 4,
+                (false, _) => 6,
+                (true, _) => 10,
+            };
+            // Format 1 matches glyphs and format 2 classes. The value a set
+            // starts with is the coverage glyph of its index or the index.
+            let allowed = match filter {
+                Some(glyphs) => Some(allowed_inputs(subtable, format, chained, glyphs)?),
+                None => None,
+            };
+            let mut starts = HashMap::new();
+            if format == 1 && allowed.is_some() {
+                starts.extend(
+                    coverage(offset(subtable, 2)?)?.into_iter().map(|(g, i)| (i, g)),
+                );
+            }
+            for i in 0..usize::from(u16::read_at(subtable, sets)?) {
+                if u16::read_at(subtable, sets + 2 + 2 * i)? == 0 {
+                    continue;
+                }
+                if let Some(allowed) = &allowed {
+                    let first = match format {
+                        1 => starts.get(&i).copied(),
+                        _ => u16::try_from(i).ok(),
+                    };
+                    if !first.is_some_and(|first| allowed.contains(&first)) {
+                        continue;
+                    }
+                }
+                let set = offset(subtable, sets + 2 + 2 * i)?;
+                for j in 0..usize::from(u16::read_at(set, 0)?) {
+                    let mut r = Reader::new(offset(set, 2 + 2 * j)?);
+                    let (input, count) = if chained {
+                        skip_array(&mut r)?;
+                        let input = r.read::<u16>()?;
+                        let values = r.take(2 * usize::from(input.saturating_sub(1)))?;
+                        skip_array(&mut r)?;
+                        (values, r.read::<u16>()?)
+                    } else {
+                        let input = r.read::<u16>()?;
+                        let count = r.read::<u16>()?;
+                        (r.take(2 * usize::from(input.saturating_sub(1)))?, count)
+                    };
+                    if let Some(allowed) = &allowed {
+                        let mut values =
+                            (0..input.len() / 2).map(|k| u16::read_at(input, 2 * k));
+                        if !values.all(|value| value.is_ok_and(|v| allowed.contains(&v)))
+                        {
+                            continue;
+                        }
+                    }
+                    nested.extend(records(&mut r, count)?);
+                }
+            }
+        }
+        (5, 3) => {
+            let mut r = Reader::new(subtable.get(2..).ok_or(Error::MissingData)?);
+            let input = r.read::<u16>()?;
+            let count = r.read::<u16>()?;
+            let coverages = r.take(2 * usize::from(input))?;
+            if matches_coverages(subtable, coverages, filter)? {
+                nested.extend(records(&mut r, count)?);
+            }
+        }
+        (6, 3) => {
+            let mut r = Reader::new(subtable.get(2..).ok_or(Error::MissingData)?);
+            skip_array(&mut r)?;
+            let input = r.read::<u16>()?;
+            let coverages = r.take(2 * usize::from(input))?;
+            skip_array(&mut r)?;
+            let count = r.read::<u16>()?;
+            if matches_coverages(subtable, coverages, filter)? {
+                nested.extend(records(&mut r, count)?);
+            }
+        }
+        _ => {}
+    }
+    Ok(nested)
+}
+
+/// The input values of a format 1 or 2 contextual subtable that the glyphs can
+/// match: the glyphs themselves for format 1 and their classes for format 2.
+fn allowed_inputs(
+    subtable: &[u8],
+    format: u16,
+    chained: bool,
+    glyphs: &HashSet<u16>,
+) -> Result<HashSet<u16>> {
+    if format == 1 {
+        return Ok(glyphs.clone());
+    }
+    let classes = layout::class_def(offset(subtable, if chained { 6 } else { 4 })?)?;
+    let mut allowed: HashSet<u16> = classes
+        .iter()
+        .filter(|(glyph, _)| glyphs.contains(glyph))
+        .map(|&(_, class)| class)
+        .collect();
+    // Glyphs without a class are in class 0.
+    let classed: HashSet<u16> = classes.iter().map(|&(glyph, _)| glyph).collect();
+    if glyphs.iter().any(|glyph| !classed.contains(glyph)) {
+        allowed.insert(0);
+    }
+    Ok(allowed)
+}
+
+/// Whether each of the input coverages of a format 3 contextual subtable
+/// covers one of the filter's glyphs.
+fn matches_coverages(
+    subtable: &[u8],
+    coverages: &[u8],
+    filter: Option<&HashSet<u16>>,
+) -> Result<bool> {
+    let Some(glyphs) = filter else { return Ok(true) };
+    for i in 0..coverages.len() / 2 {
+        let at = usize::from(u16::read_at(coverages, 2 * i)?);
+        let covered = coverage(subtable.get(at..).ok_or(Error::InvalidOffset)?)?;
+        if !covered.iter().any(|(glyph, _)| glyphs.contains(glyph)) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Read the lookup indices of substitution lookup records.
+fn records(r: &mut Reader, count: u16) -> Result<Vec<u16>> {
+    let mut indices = vec![];
+    for _ in 0..count {
+        r.read::<u16>()?;
+        indices.push(r.read::<u16>()?);
+    }
+    Ok(indices)
+}
+
+/// Skip a `u16` count followed by as many `u16` values.
+fn skip_array(r: &mut Reader) -> Result<()> {
+    let count = r.read::<u16>()?;
+    r.skip(2 * usize::from(count))
+}
+
+/// Subset the `GSUB` table to the retained glyphs.
+///
+/// Substitutions whose input or output glyphs aren't retained are removed,
+/// and so are the lookups, features and scripts that are left empty. The
+/// remaining glyphs are referred to by their output IDs.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let glyphs = layout::retained_glyphs(ctx);
+    layout::subset(
+        ctx,
+        Tag::GSUB,
+        7,
+        supported,
+        merge_subtables,
+        |kind, subtable, lookups| subset_subtable(kind, subtable, &glyphs, lookups),
+    )
+}
+
+/// Whether substitution subtables of the given lookup type and format can be
+/// subsetted.
+fn supported(kind: u16, format: u16) -> bool {
+    matches!((kind, format), (1, 1 | 2) | (2..=4 | 8, 1) | (5 | 6, 1..=3))
+}
+
+/// Subset a substitution subtable. Returns `None` if it doesn't substitute any
+/// retained glyphs anymore.
+fn subset_subtable(
+    kind: u16,
+    subtable: &[u8],
+    glyphs: &Retained,
+    lookups: LookupMap,
+) -> Result<Option<Vec<u8>>> {
+    match (kind, u16::read_at(subtable, 0)?) {
+        (1, 1 | 2) => subset_single(subtable, glyphs),
+        (2 | 3, 1) => subset_sequences(kind, subtable, glyphs),
+        (4, 1) => subset_ligatures(subtable, glyphs),
+        (5 | 6, _) => layout::subset_context(kind == 6, subtable, glyphs, lookups),
+        (8, 1) => subset_reverse(subtable, glyphs),
+        _ => Err(Error::InvalidData),
+    }
+}
+
+/// Merge consecutive subtables of a lookup where the merged subtable is
+/// smaller. Only single substitutions are merged, where the first subtable
+/// that covers a glyph takes precedence, like when the lookup is applied.
+fn merge_subtables(kind: u16, subtables: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>> {
+    if kind != 1 {
+        return Ok(subtables);
+    }
+
+    let mut merged: Vec<Vec<u8>> = vec![];
+    for subtable in subtables {
+        if let Some(last) = merged.last_mut() {
+            let mut pairs = single_pairs(last)?;
+            for pair in single_pairs(&subtable)? {
+                if let Err(i) = pairs.binary_search_by_key(&pair.0, |&(glyph, _)| glyph) {
+                    pairs.insert(i, pair);
+                }
+            }
+            // Each subtable takes an offset in the lookup as well.
+            let data = write_single(&pairs)?;
+            if data.len() <= last.len() + subtable.len() + 2 {
+                *last = data;
+                continue;
+            }
+        }
+        merged.push(subtable);
+    }
+    Ok(merged)
+}
+
+/// The substitutions of a single substitution subtable, sorted by input glyph.
+fn single_pairs(subtable: &[u8]) -> Result<Vec<(u16, u16)>> {
+    let format = u16::read_at(subtable, 0)?;
+    let mut pairs = vec![];
+    for (glyph, i) in coverage(offset(subtable, 2)?)? {
+        let output = match format {
+            1 => glyph.wrapping_add_signed(i16::read_at(subtable, 4)?),
+            _ => u16::read_at(subtable, 6 + 2 * i)?,
+        };
+        pairs.push((glyph, output));
+    }
+    pairs.sort_unstable();
+    pairs.dedup_by_key(|&mut (glyph, _)| glyph);
+    Ok(pairs)
+}
+
+/// Subset a single substitution subtable.
+fn subset_single(subtable: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    let mut pairs: Vec<(u16, u16)> = single_pairs(subtable)?
+        .into_iter()
+        .filter_map(|(glyph, output)| Some((*glyphs.get(&glyph)?, *glyphs.get(&output)?)))
+        .collect();
+    pairs.sort_unstable();
+    if pairs.is_empty() {
+        return Ok(None);
+    }
+    write_single(&pairs).map(Some)
+}
+
+/// Write a single substitution subtable for substitutions sorted by input
+/// glyph, in format 1 if they all have the same delta.
+pub(crate) fn write_single(pairs: &[(u16, u16)]) -> Result<Vec<u8>> {
+    let Some(&(first, output)) = pairs.first() else { return Err(Error::InvalidData) };
+    let delta = output.wrapping_sub(first);
+    let covered: Vec<u16> = pairs.iter().map(|&(glyph, _)| glyph).collect();
+
+    let mut p = Packer::new();
+    if pairs
+        .iter()
+        .all(|&(glyph, output)| output.wrapping_sub(glyph) == delta)
+    {
+        p.write::<u16>(1);
+        p.link(layout::write_coverage(&covered));
+        p.write::<u16>(delta);
+    } else {
+        p.write::<u16>(2);
+        p.link(layout::write_coverage(&covered));
+        p.write::<u16>(pairs.len() as u16);
+        for &(_, output) in pairs {
+            p.write::<u16>(output);
+        }
+    }
+    p.finish()
+}
+
+/// Subset a multiple or alternate substitution subtable. A multiple
+/// substitution is removed unless all of its outputs are retained, while
+/// alternates are removed individually.
+fn subset_sequences(
+    kind: u16,
+    subtable: &[u8],
+    glyphs: &Retained,
+) -> Result<Option<Vec<u8>>> {
+    let mut entries = vec![];
+    for (glyph, i) in coverage(offset(subtable, 2)?)? {
+        let Some(&glyph) = glyphs.get(&glyph) else { continue };
+        let outputs = glyph_array(offset(subtable, 6 + 2 * i)?)?;
+        let count = outputs.len();
+        let outputs: Vec<u16> = outputs
+            .into_iter()
+            .filter_map(|output| glyphs.get(&output).copied())
+            .collect();
+        if kind == 2 && outputs.len() < count {
+            continue;
+        }
+        if kind == 3 && outputs.is_empty() {
+            continue;
+        }
+        entries.push((glyph, outputs));
+    }
+    entries.sort_by_key(|&(glyph, _)| glyph);
+    entries.dedup_by_key(|(glyph, _)| *glyph);
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let covered: Vec<u16> = entries.iter().map(|&(glyph, _)| glyph).collect();
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.link(layout::write_coverage(&covered));
+    p.write::<u16>(entries.len() as u16);
+    for (_, outputs) in entries {
+        p.link(layout::write_glyph_array(&outputs));
+    }
+    p.finish().map(Some)
+}
+
+/// Subset a ligature substitution subtable to the ligatures whose components
+/// and ligature glyph are retained. The ligatures of a glyph keep their order,
+/// since the first one that matches is formed.
+fn subset_ligatures(subtable: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    let mut entries = vec![];
+    for (glyph, i) in coverage(offset(subtable, 2)?)? {
+        let Some(&glyph) = glyphs.get(&glyph) else { continue };
+        let set = offset(subtable, 6 + 2 * i)?;
+        let mut ligatures = vec![];
+        'ligatures: for j in 0..usize::from(u16::read_at(set, 0)?) {
+            let ligature = offset(set, 2 + 2 * j)?;
+            let count = usize::from(u16::read_at(ligature, 2)?);
+            let mut data = ligature
+                .get(..2 + 2 * count.max(1))
+                .ok_or(Error::MissingData)?
+                .to_vec();
+            // The ligature glyph, then the components after the first one.
+            for at in (0..count).map(|k| if k == 0 { 0 } else { 2 + 2 * k }) {
+                let Some(new) = glyphs.get(&u16::read_at(&data, at)?) else {
+                    continue 'ligatures;
+                };
+                data[at..at + 2].copy_from_slice(&new.to_be_bytes());
+            }
+            ligatures.push(data);
+        }
+        if ligatures.is_empty() {
+            continue;
+        }
+
+        let mut p = Packer::new();
+        p.write::<u16>(ligatures.len() as u16);
+        for ligature in ligatures {
+            p.link(ligature);
+        }
+        entries.push((glyph, p.finish()?));
+    }
+    entries.sort_by_key(|&(glyph, _)| glyph);
+    entries.dedup_by_key(|(glyph, _)| *glyph);
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let covered: Vec<u16> = entries.iter().map(|&(glyph, _)| glyph).collect();
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.link(layout::write_coverage(&covered));
+    p.write::<u16>(entries.len() as u16);
+    for (_, set) in entries {
+        p.link(set);
+    }
+    p.finish().map(Some)
+}
+
+/// Subset a reverse chaining contextual single substitution subtable.
+fn subset_reverse(subtable: &[u8], glyphs: &Retained) -> Result<Option<Vec<u8>>> {
+    let mut r = Reader::new(subtable.get(4..).ok_or(Error::MissingData)?);
+    let Some(backtrack) = layout::subset_coverages(subtable, &mut r, glyphs)? else {
+        return Ok(None);
+    };
+    let Some(lookahead) = layout::subset_coverages(subtable, &mut r, glyphs)? else {
+        return Ok(None);
+    };
+    let count = r.read::<u16>()?;
+    let outputs = (0..count).map(|_| r.read::<u16>()).collect::<Result<Vec<_>>>()?;
+
+    let mut pairs = vec![];
+    for (glyph, i) in coverage(offset(subtable, 2)?)? {
+        let output = *outputs.get(i).ok_or(Error::MissingData)?;
+        if let (Some(&glyph), Some(&output)) = (glyphs.get(&glyph), glyphs.get(&output)) {
+            pairs.push((glyph, output));
+        }
+    }
+    pairs.sort_unstable();
+    pairs.dedup_by_key(|&mut (glyph, _)| glyph);
+
+    if pairs.is_empty() {
+        return Ok(None);
+    }
+
+    let covered: Vec<u16> = pairs.iter().map(|&(glyph, _)| glyph).collect();
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.link(layout::write_coverage(&covered));
+    for coverages in [backtrack, lookahead] {
+        p.write::<u16>(coverages.len() as u16);
+        for coverage in coverages {
+            p.link(coverage);
+        }
+    }
+    p.write::<u16>(pairs.len() as u16);
+    for (_, output) in pairs {
+        p.write::<u16>(output);
+    }
+    p.finish().map(Some)
+}
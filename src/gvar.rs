@@ -0,0 +1,203 @@
+use super::*;
+
+/// Subset the `gvar` table to the retained glyphs.
+///
+/// Like their outlines in `glyf`, the variation data of the other glyphs is
+/// removed, while their entries in the offset array remain. Shared tuples that
+/// no retained glyph references anymore are removed as well, and the offset
+/// array is written in the short format when the data is small enough.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let table = Table::parse(ctx.expect_table(Tag::GVAR)?)?;
+    let glyphs: Vec<&[u8]> = (0..table.glyphs.len())
+        .map(|id| {
+            let id = id as u16;
+            // A synthesized .notdef glyph doesn't have the original's points.
+            let synthesized = id == 0 && ctx.notdef.is_some();
+            match ctx.subset.contains(&id) && !synthesized {
+                true => table.glyphs[usize::from(id)],
+                false => &[],
+            }
+        })
+        .collect();
+    ctx.push(Tag::GVAR, compact(table.axis_count, &table.shared, &glyphs)?);
+    Ok(())
+}
+
+/// Renumber the glyphs of a subsetted `gvar` table, keeping the variation
+/// data of the glyphs in the given order.
+pub(crate) fn remap(data: &[u8], order: &[u16]) -> Result<Vec<u8>> {
+    let table = Table::parse(data)?;
+    let glyphs: Vec<&[u8]> = order
+        .iter()
+        .map(|&glyph| table.glyphs.get(usize::from(glyph)).copied().unwrap_or(&[]))
+        .collect();
+    compact(table.axis_count, &table.shared, &glyphs)
+}
+
+/// A parsed `gvar` table.
+pub(crate) struct Table<'a> {
+    /// The number of variation axes.
+    pub axis_count: u16,
+    /// The shared tuples, each with a coordinate for every axis.
+    pub shared: Vec<&'a [u8]>,
+    /// The variation data of each glyph, empty for glyphs without variations.
+    pub glyphs: Vec<&'a [u8]>,
+}
+
+impl<'a> Table<'a> {
+    /// Parse the table header and split the data into shared tuples and
+    /// glyphs.
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let axis_count = u16::read_at(data, 4)?;
+        let shared_count = usize::from(u16::read_at(data, 6)?);
+        let shared_offset = u32::read_at(data, 8)? as usize;
+        let size = 2 * usize::from(axis_count);
+        let shared = (0..shared_count)
+            .map(|i| {
+                let start = shared_offset + i * size;
+                data.get(start..start + size).ok_or(Error::InvalidOffset)
+            })
+            .collect::<Result<_>>()?;
+
+        let glyph_count = usize::from(u16::read_at(data, 12)?);
+        let long = u16::read_at(data, 14)? & 1 != 0;
+        let array = u32::read_at(data, 16)? as usize;
+        let offset = |i: usize| -> Result<usize> {
+            Ok(array
+                + match long {
+                    true => u32::read_at(data, 20 + 4 * i)? as usize,
+                    false => 2 * usize::from(u16::read_at(data, 20 + 2 * i)?),
+                })
+        };
+        let glyphs = (0..glyph_count)
+            .map(|i| {
+                let (start, end) = (offset(i)?, offset(i + 1)?);
+                match start < end {
+                    true => data.get(start..end).ok_or(Error::InvalidOffset),
+                    false => Ok(&[][..]),
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { axis_count, shared, glyphs })
+    }
+
+    /// The coordinates of the shared tuples.
+    pub fn shared_tuples(&self) -> Result<Vec<Vec<f32>>> {
+        self.shared
+            .iter()
+            .map(|tuple| {
+                (0..usize::from(self.axis_count))
+                    .map(|i| F2Dot14::read_at(tuple, 2 * i).map(F2Dot14::get))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Write a `gvar` table with only the shared tuples that the glyphs still
+/// reference, renumbering the references in the glyphs' data.
+fn compact(axis_count: u16, shared: &[&[u8]], glyphs: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut references = vec![];
+    let mut used = vec![false; shared.len()];
+    for (i, data) in glyphs.iter().enumerate() {
+        for (at, index) in shared_references(data, axis_count)? {
+            *used.get_mut(usize::from(index)).ok_or(Error::InvalidData)? = true;
+            references.push((i, at, index));
+        }
+    }
+
+    let mut new_index = vec![0; shared.len()];
+    let mut kept = vec![];
+    for (i, &tuple) in shared.iter().enumerate() {
+        if used[i] {
+            new_index[i] = kept.len() as u16;
+            kept.push(tuple);
+        }
+    }
+
+    let mut glyphs: Vec<Cow<[u8]>> =
+        glyphs.iter().map(|&data| Cow::Borrowed(data)).collect();
+    for (i, at, index) in references {
+        let data = glyphs[i].to_mut();
+        let flags = u16::from_be_bytes([data[at], data[at + 1]]) & 0xF000;
+        let value = flags | new_index[usize::from(index)];
+        data[at..at + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    let glyphs: Vec<&[u8]> = glyphs.iter().map(AsRef::as_ref).collect();
+    Ok(write(axis_count, &kept, &glyphs))
+}
+
+/// The positions and values of the shared tuple indices in the tuple variation
+/// headers of a glyph's variation data.
+fn shared_references(data: &[u8], axis_count: u16) -> Result<Vec<(usize, u16)>> {
+    const EMBEDDED_PEAK_TUPLE: u16 = 0x8000;
+    const INTERMEDIATE_REGION: u16 = 0x4000;
+    const TUPLE_INDEX_MASK: u16 = 0x0FFF;
+
+    if data.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let count = u16::read_at(data, 0)? & 0x0FFF;
+    let size = 2 * usize::from(axis_count);
+    let mut references = vec![];
+    let mut at = 4;
+    for _ in 0..count {
+        let index = u16::read_at(data, at + 2)?;
+        if index & EMBEDDED_PEAK_TUPLE == 0 {
+            references.push((at + 2, index & TUPLE_INDEX_MASK));
+        } else {
+            at += size;
+        }
+        if index & INTERMEDIATE_REGION != 0 {
+            at += 2 * size;
+        }
+        at += 4;
+    }
+    if at > data.len() {
+        return Err(Error::MissingData);
+    }
+    Ok(references)
+}
+
+/// Write a `gvar` table from its shared tuples and the variation data of each
+/// glyph.
+///
+/// The short offset format is used if the data fits, which requires padding
+/// the data of each glyph to an even length.
+pub(crate) fn write(axis_count: u16, shared: &[&[u8]], glyphs: &[&[u8]]) -> Vec<u8> {
+    let padded: usize = glyphs.iter().map(|data| data.len().next_multiple_of(2)).sum();
+    let long = padded / 2 > usize::from(u16::MAX);
+    let offsets_size = (glyphs.len() + 1) * if long { 4 } else { 2 };
+    let shared_offset = 20 + offsets_size;
+    let array = shared_offset + 2 * usize::from(axis_count) * shared.len();
+
+    let mut w = Writer::new();
+    w.write::<u32>(0x00010000);
+    w.write::<u16>(axis_count);
+    w.write::<u16>(shared.len() as u16);
+    w.write::<u32>(shared_offset as u32);
+    w.write::<u16>(glyphs.len() as u16);
+    w.write::<u16>(u16::from(long));
+    w.write::<u32>(array as u32);
+    let mut offset = 0;
+    for data in glyphs.iter().map(|data| data.len()).chain([0]) {
+        match long {
+            true => w.write::<u32>(offset as u32),
+            false => w.write::<u16>((offset / 2) as u16),
+        }
+        offset += if long { data } else { data.next_multiple_of(2) };
+    }
+    for tuple in shared {
+        w.give(tuple);
+    }
+    for data in glyphs {
+        w.give(data);
+        if !long {
+            w.align(2);
+        }
+    }
+    w.finish()
+}
@@ -0,0 +1,513 @@
+//! Reading and writing gzip streams, as used by compressed `SVG` documents,
+//! and zlib streams, as used by WOFF fonts.
+//!
+//! The compressor uses LZ77 matching with the fixed Huffman codes of DEFLATE,
+//! which is simple and compresses markup well enough.
+
+use super::*;
+
+/// The size limit of decompressed data, against decompression bombs.
+const MAX_SIZE: usize = 1 << 26;
+
+/// The size of the LZ77 window.
+const WINDOW: usize = 1 << 15;
+
+/// How many earlier positions with the same hash the compressor tries.
+const MAX_CHAIN: usize = 64;
+
+/// The base lengths of the length codes 257 to 285 and their extra bits.
+const LENGTHS: [(u16, u8); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+/// The base distances of the distance codes and their extra bits.
+const DISTANCES: [(u16, u8); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+/// The order in which the code lengths of the code length alphabet are stored.
+const CODE_LENGTH_ORDER: [usize; 19] =
+    [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Whether the data starts like a gzip stream.
+pub(crate) fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1F, 0x8B, 0x08])
+}
+
+/// Decompress a gzip stream, checking its checksum.
+pub(crate) fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if !is_gzip(data) {
+        return Err(Error::InvalidData);
+    }
+    let mut r = Reader::new(data);
+    r.skip(3)?;
+    let flags = r.read::<u8>()?;
+    r.skip(6)?;
+    if flags & FEXTRA != 0 {
+        let len = u16::from_le_bytes(r.read::<[u8; 2]>()?);
+        r.skip(usize::from(len))?;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flag & flags != 0 {
+            while r.read::<u8>()? != 0 {}
+        }
+    }
+    if flags & FHCRC != 0 {
+        r.skip(2)?;
+    }
+
+    let mut bits = Bits { data: r.data(), at: 0, buffer: 0, count: 0 };
+    let out = inflate(&mut bits)?;
+    let trailer = bits.data.get(bits.at..bits.at + 8).ok_or(Error::MissingData)?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc != crc32(&out) || size != out.len() as u32 {
+        return Err(Error::InvalidData);
+    }
+    Ok(out)
+}
+
+/// Compress data into a gzip stream.
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    // Magic, method, flags, modification time, extra flags and unknown OS.
+    let mut out = vec![0x1F, 0x8B, 0x08, 0, 0, 0, 0, 0, 0, 0xFF];
+    deflate(data, &mut out);
+    out.extend(crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Decompress a zlib stream, checking its checksum.
+pub(crate) fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>> {
+    // The compression method must be DEFLATE, without a preset dictionary.
+    let header = data.get(..2).ok_or(Error::MissingData)?;
+    if header[0] & 0x0F != 8
+        || header[1] & 0x20 != 0
+        || u16::from_be_bytes([header[0], header[1]]) % 31 != 0
+    {
+        return Err(Error::InvalidData);
+    }
+
+    let mut bits = Bits { data, at: 2, buffer: 0, count: 0 };
+    let out = inflate(&mut bits)?;
+    let trailer = bits.data.get(bits.at..bits.at + 4).ok_or(Error::MissingData)?;
+    if u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]])
+        != adler32(&out)
+    {
+        return Err(Error::InvalidData);
+    }
+    Ok(out)
+}
+
+/// Compress data into a zlib stream.
+pub(crate) fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    // DEFLATE with a 32K window and the default compression level.
+    let mut out = vec![0x78, 0x9C];
+    deflate(data, &mut out);
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// Compress data into a single DEFLATE block.
+fn deflate(data: &[u8], out: &mut Vec<u8>) {
+    let mut w = BitWriter { out, buffer: 0, count: 0 };
+    // A single final block with the fixed Huffman codes.
+    w.bits(1, 1);
+    w.bits(1, 2);
+    for token in lz77(data) {
+        match token {
+            Token::Literal(byte) => w.symbol(u16::from(byte)),
+            Token::Match(len, dist) => {
+                let i = LENGTHS.iter().rposition(|&(base, _)| base <= len).unwrap();
+                let (base, extra) = LENGTHS[i];
+                w.symbol(257 + i as u16);
+                w.bits(u32::from(len - base), extra);
+                let i = DISTANCES.iter().rposition(|&(base, _)| base <= dist).unwrap();
+                let (base, extra) = DISTANCES[i];
+                w.code(i as u32, 5);
+                w.bits(u32::from(dist - base), extra);
+            }
+        }
+    }
+    w.symbol(256);
+    w.flush();
+}
+
+/// Decode the DEFLATE blocks of a stream.
+fn inflate(bits: &mut Bits) -> Result<Vec<u8>> {
+    let mut out = vec![];
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let len = bits.read(16)?;
+                if bits.read(16)? != !len & 0xFFFF {
+                    return Err(Error::InvalidData);
+                }
+                let end = bits.at + len as usize;
+                out.extend_from_slice(
+                    bits.data.get(bits.at..end).ok_or(Error::MissingData)?,
+                );
+                bits.at = end;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let literals = Huffman::new(&lengths)?;
+                let distances = Huffman::new(&[5; 30])?;
+                inflate_block(bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = read_codes(bits)?;
+                inflate_block(bits, &mut out, &literals, &distances)?;
+            }
+            _ => return Err(Error::InvalidData),
+        }
+        if out.len() > MAX_SIZE {
+            return Err(Error::LimitExceeded);
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Read the Huffman codes of a block with dynamic codes.
+fn read_codes(bits: &mut Bits) -> Result<(Huffman, Huffman)> {
+    let literal_count = bits.read(5)? as usize + 257;
+    let distance_count = bits.read(5)? as usize + 1;
+    let length_count = bits.read(4)? as usize + 4;
+    let mut lengths = [0; 19];
+    for &i in &CODE_LENGTH_ORDER[..length_count] {
+        lengths[i] = bits.read(3)? as u8;
+    }
+    let code = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![];
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match code.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or(Error::InvalidData)?, 3 + bits.read(2)?),
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(Error::InvalidData);
+    }
+    let (literals, distances) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literals)?, Huffman::new(distances)?))
+}
+
+/// Decode the symbols of a compressed block until its end.
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = literals.decode(bits)?;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let &(base, extra) =
+                    LENGTHS.get(usize::from(symbol - 257)).ok_or(Error::InvalidData)?;
+                let len = usize::from(base) + bits.read(extra)? as usize;
+                let symbol = usize::from(distances.decode(bits)?);
+                let &(base, extra) = DISTANCES.get(symbol).ok_or(Error::InvalidData)?;
+                let dist = usize::from(base) + bits.read(extra)? as usize;
+                if dist > out.len() || out.len() + len > MAX_SIZE {
+                    return Err(Error::InvalidData);
+                }
+                let start = out.len() - dist;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// A canonical Huffman code for decoding.
+struct Huffman {
+    /// The number of codes of each length.
+    counts: [u16; 16],
+    /// The symbols, ordered by their codes.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Create the canonical code from the code length of each symbol.
+    fn new(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0; 16];
+        for &len in lengths {
+            *counts.get_mut(usize::from(len)).ok_or(Error::InvalidData)? += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = vec![];
+        for len in 1..16 {
+            let of_len = (0..lengths.len()).filter(|&i| lengths[i] == len);
+            symbols.extend(of_len.map(|i| i as u16));
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    /// Decode a symbol, reading its code bit by bit.
+    fn decode(&self, bits: &mut Bits) -> Result<u16> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.read(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                let i = (index + code - first) as usize;
+                return self.symbols.get(i).copied().ok_or(Error::InvalidData);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Error::InvalidData)
+    }
+}
+
+/// Reads the bits of a DEFLATE stream, starting with the least significant
+/// bit of each byte.
+struct Bits<'a> {
+    data: &'a [u8],
+    /// The position of the next byte to load.
+    at: usize,
+    buffer: u32,
+    /// The number of bits in the buffer.
+    count: u8,
+}
+
+impl Bits<'_> {
+    /// Read a value with the given number of bits, at most 16.
+    fn read(&mut self, n: u8) -> Result<u32> {
+        while self.count < n {
+            let byte = *self.data.get(self.at).ok_or(Error::MissingData)?;
+            self.buffer |= u32::from(byte) << self.count;
+            self.at += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Skip to the next byte boundary.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// Writes the bits of a DEFLATE stream.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    buffer: u32,
+    count: u8,
+}
+
+impl BitWriter<'_> {
+    /// Write a value with the given number of bits, least significant first.
+    fn bits(&mut self, value: u32, n: u8) {
+        self.buffer |= value << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, most significant bit first.
+    fn code(&mut self, code: u32, n: u8) {
+        self.bits(code.reverse_bits() >> (32 - n), n);
+    }
+
+    /// Write a literal or length symbol with the fixed Huffman code.
+    fn symbol(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xC0 + symbol - 280, 8),
+        }
+    }
+
+    /// Write the remaining bits, padded to a full byte.
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// A literal byte or a match of a length and a distance.
+enum Token {
+    Literal(u8),
+    Match(u16, u16),
+}
+
+/// Split the data into literals and greedy matches with earlier data.
+fn lz77(data: &[u8]) -> Vec<Token> {
+    const NONE: usize = usize::MAX;
+    let hash = |i: usize| {
+        let value = u32::from_le_bytes([data[i], data[i + 1], data[i + 2], 0]);
+        (value.wrapping_mul(2654435761) >> 17) as usize
+    };
+
+    // The last position with each hash and the previous position with the
+    // same hash as each position in the window.
+    let mut head = vec![NONE; 1 << 15];
+    let mut prev = vec![NONE; WINDOW];
+    let insert = |head: &mut [usize], prev: &mut [usize], i: usize| {
+        if i + 3 <= data.len() {
+            let h = hash(i);
+            prev[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let (mut len, mut dist) = (0, 0);
+        if i + 3 <= data.len() {
+            let max = (data.len() - i).min(258);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != NONE && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let common = data[candidate..]
+                    .iter()
+                    .zip(&data[i..i + max])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if common > len {
+                    (len, dist) = (common, i - candidate);
+                }
+                let next = prev[candidate % WINDOW];
+                if next == NONE || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if len >= 3 {
+            tokens.push(Token::Match(len as u16, dist as u16));
+            for j in i..i + len {
+                insert(&mut head, &mut prev, j);
+            }
+            i += len;
+        } else {
+            tokens.push(Token::Literal(data[i]));
+            insert(&mut head, &mut prev, i);
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// The Adler-32 checksum of zlib streams.
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    // Sums of this many bytes can't overflow before the modulo.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// The CRC-32 checksum of gzip streams.
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+        }
+        *entry = c;
+    }
+    !data.iter().fold(!0, |crc, &byte| {
+        table[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
@@ -2,12 +2,18 @@ use super::*;
 
 /// Subset the head table.
 ///
-/// Updates the loca format.
+/// Updates the loca format of TrueType outlines, and clears the flag that
+/// instructions may alter advance widths when they are stripped.
 pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
     let mut head = ctx.expect_table(Tag::HEAD)?.to_vec();
     let index_to_loc = head.get_mut(50..52).ok_or(Error::InvalidOffset)?;
-    index_to_loc[0] = 0;
-    index_to_loc[1] = ctx.long_loca as u8;
+    if ctx.outlines == Outlines::TrueType {
+        index_to_loc[0] = 0;
+        index_to_loc[1] = ctx.long_loca as u8;
+        if ctx.plan.strips_hinting() {
+            head[17] &= !0x10;
+        }
+    }
     ctx.push(Tag::HEAD, head);
     Ok(())
 }
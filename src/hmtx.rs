@@ -1,35 +1,181 @@
 use super::*;
 
+/// Overrides for the horizontal metrics of a glyph.
+///
+/// Fields that are `None` keep the value from the original font.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct MetricsOverride {
+    /// The new advance width.
+    pub advance: Option<u16>,
+    /// The new left side bearing.
+    pub lsb: Option<i16>,
+}
+
+/// Determine the number of long horizontal metrics in the original and in the
+/// subsetted font.
+///
+/// Glyphs after the last long metric share its advance width, so overriding
+/// the advance of such a glyph requires more long metrics.
+fn num_h_metrics(ctx: &Context) -> Result<(u16, u16)> {
+    let hhea = ctx.expect_table(Tag::HHEA)?;
+    let num_h_metrics = u16::read_at(hhea, 34)?;
+    let needed = ctx
+        .plan
+        .metrics
+        .iter()
+        .filter(|(glyph, metrics)| metrics.advance.is_some() && *glyph < ctx.num_glyphs)
+        .map(|&(glyph, _)| glyph)
+        .chain(ctx.space.map(|(glyph, _)| glyph))
+        .map(|glyph| glyph + 1)
+        .max()
+        .unwrap_or(0);
+    Ok((num_h_metrics, num_h_metrics.max(needed)))
+}
+
+/// Subset the hhea table.
+///
+/// Updates the number of long horizontal metrics.
+pub(crate) fn subset_hhea(ctx: &mut Context) -> Result<()> {
+    let mut hhea = ctx.expect_table(Tag::HHEA)?.to_vec();
+    let (_, num_h_metrics) = num_h_metrics(ctx)?;
+    hhea.get_mut(34..36)
+        .ok_or(Error::MissingData)?
+        .copy_from_slice(&num_h_metrics.to_be_bytes());
+    ctx.push(Tag::HHEA, hhea);
+    Ok(())
+}
+
 /// Subset the htmx table.
 ///
 /// We can't change anything about its size, but we can zero out all metrics
 /// for unused glyphs so that it compresses better when embedded into a PDF.
+/// Overridden metrics are applied as well.
 pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
-    let num_h_metrics = {
-        let hhea = ctx.expect_table(Tag::HHEA)?;
-        let mut r = Reader::new(hhea);
-        r.skip(34)?;
-        r.read::<u16>()?
-    };
+    let (num_h_metrics, mut new_num_h_metrics) = num_h_metrics(ctx)?;
 
-    let mut hmtx = ctx.expect_table(Tag::HMTX)?.to_vec();
+    // Only grow the long metrics if the hhea table is updated accordingly.
+    if ctx.plan.table(Tag::HHEA) != TableAction::Subset {
+        new_num_h_metrics = num_h_metrics;
+    }
 
-    let mut offset = 0;
-    for i in 0..num_h_metrics {
-        if !ctx.subset.contains(&i) {
-            hmtx.get_mut(offset..offset + 4).ok_or(Error::MissingData)?.fill(0);
+    let hmtx = ctx.expect_table(Tag::HMTX)?;
+    let mut sub_hmtx = Writer::new();
+    let mut advance = 0;
+    for i in 0..ctx.num_glyphs {
+        let mut lsb = if i < num_h_metrics {
+            advance = u16::read_at(hmtx, 4 * i as usize)?;
+            i16::read_at(hmtx, 4 * i as usize + 2)?
+        } else {
+            let offset = 4 * num_h_metrics as usize + 2 * (i - num_h_metrics) as usize;
+            i16::read_at(hmtx, offset)?
+        };
+
+        let mut advance = advance;
+        if let Ok(k) = ctx.plan.metrics.binary_search_by_key(&i, |&(glyph, _)| glyph) {
+            let metrics = ctx.plan.metrics[k].1;
+            if i < new_num_h_metrics {
+                advance = metrics.advance.unwrap_or(advance);
+            }
+            lsb = metrics.lsb.unwrap_or(lsb);
         }
-        offset += 4;
+
+        if let Some(notdef) = ctx.notdef.filter(|_| i == 0) {
+            advance = advance.max(notdef.advance);
+            lsb = notdef.lsb;
+        }
+
+        if let Some((_, space_advance)) = ctx.space.filter(|&(glyph, _)| glyph == i) {
+            advance = space_advance;
+            lsb = 0;
+        } else if !ctx.subset.contains(&i) {
+            advance = 0;
+            lsb = 0;
+        }
+
+        if i < new_num_h_metrics {
+            sub_hmtx.write::<u16>(advance);
+        }
+        sub_hmtx.write::<i16>(lsb);
     }
 
-    for i in num_h_metrics..ctx.num_glyphs {
-        if !ctx.subset.contains(&i) {
-            hmtx.get_mut(offset..offset + 2).ok_or(Error::MissingData)?.fill(0);
+    ctx.push(Tag::HMTX, sub_hmtx.finish());
+
+    Ok(())
+}
+
+/// Recompute the extremes of the horizontal metrics in the subsetted hhea
+/// table from the glyphs that remain in the font.
+///
+/// The side bearings and extents need glyph bounds, which are only known for
+/// TrueType outlines. CFF fonts only get their maximum advance updated.
+pub(crate) fn finalize(ctx: &mut Context) -> Result<()> {
+    if ctx.plan.table(Tag::HHEA) != TableAction::Subset {
+        return Ok(());
+    }
+
+    let table = |tag| {
+        ctx.tables
+            .iter()
+            .find(|&&(t, _)| t == tag)
+            .map(|(_, data)| data.as_ref())
+    };
+    let (Some(hhea), Some(hmtx)) = (table(Tag::HHEA), table(Tag::HMTX)) else {
+        return Ok(());
+    };
+    let glyf = match (table(Tag::LOCA), table(Tag::GLYF), table(Tag::HEAD)) {
+        (Some(loca), Some(glyf), Some(head)) => {
+            Some(glyf::Table::from_tables(loca, glyf, head)?)
+        }
+        _ => None,
+    };
+
+    let num_h_metrics = u16::read_at(hhea, 34)?;
+    let mut max_advance = 0;
+    let (mut min_lsb, mut min_rsb, mut max_extent) = (i16::MAX, i16::MAX, i16::MIN);
+    let mut advance = 0;
+    for i in 0..ctx.num_glyphs {
+        let lsb = if i < num_h_metrics {
+            advance = u16::read_at(hmtx, 4 * i as usize)?;
+            i16::read_at(hmtx, 4 * i as usize + 2)?
+        } else {
+            let offset = 4 * num_h_metrics as usize + 2 * (i - num_h_metrics) as usize;
+            i16::read_at(hmtx, offset)?
+        };
+        max_advance = max_advance.max(advance);
+
+        // Glyphs without an outline have no side bearings.
+        let Some(data) = glyf.as_ref().map(|glyf| glyf.glyph_data(i)).transpose()? else {
+            continue;
+        };
+        if data.is_empty() {
+            continue;
         }
-        offset += 2;
+        let width = i32::from(i16::read_at(data, 6)?) - i32::from(i16::read_at(data, 2)?);
+        let extent = i32::from(lsb) + width;
+        let clamp = |v: i32| v.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+        min_lsb = min_lsb.min(lsb);
+        min_rsb = min_rsb.min(clamp(i32::from(advance) - extent));
+        max_extent = max_extent.max(clamp(extent));
     }
 
-    ctx.push(Tag::HMTX, hmtx);
+    let mut hhea = hhea.to_vec();
+    let mut fields = vec![(10, max_advance.to_be_bytes())];
+    if glyf.is_some() {
+        if max_extent == i16::MIN {
+            (min_lsb, min_rsb, max_extent) = (0, 0, 0);
+        }
+        fields.push((12, min_lsb.to_be_bytes()));
+        fields.push((14, min_rsb.to_be_bytes()));
+        fields.push((16, max_extent.to_be_bytes()));
+    }
+    for (offset, value) in fields {
+        hhea.get_mut(offset..offset + 2)
+            .ok_or(Error::MissingData)?
+            .copy_from_slice(&value);
+    }
 
+    ctx.tables.retain(|&(tag, _)| tag != Tag::HHEA);
+    ctx.push(Tag::HHEA, hhea);
     Ok(())
 }
@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use super::*;
+
+/// Subset an `HVAR` or `VVAR` table to the retained glyphs.
+///
+/// The item variation store keeps only the delta sets that the retained
+/// glyphs use, and the delta-set index maps are rewritten to point into the
+/// smaller store. The glyphs whose data was removed share the delta sets of
+/// the .notdef glyph, since the maps need an entry for every glyph. Regions
+/// that none of the kept delta sets varies in are dropped from the store.
+pub(crate) fn subset(ctx: &mut Context, tag: Tag) -> Result<()> {
+    let data = ctx.expect_table(tag)?;
+    let glyphs: Vec<u16> = (0..ctx.num_glyphs)
+        .map(|glyph| if ctx.subset.contains(&glyph) { glyph } else { 0 })
+        .collect();
+    let table = rewrite(data, tag == Tag::VVAR, &glyphs)?;
+    ctx.push(tag, table);
+    Ok(())
+}
+
+/// Renumber the glyphs of a subsetted `HVAR` or `VVAR` table, keeping the
+/// delta sets of the glyphs in the given order.
+pub(crate) fn remap(data: &[u8], vertical: bool, order: &[u16]) -> Result<Vec<u8>> {
+    rewrite(data, vertical, order)
+}
+
+/// Write a table whose new glyph `i` has the delta sets of the old glyph
+/// `glyphs[i]`.
+fn rewrite(data: &[u8], vertical: bool, glyphs: &[u16]) -> Result<Vec<u8>> {
+    // The advance, side bearing and (for `VVAR`) vertical origin maps.
+    let map_count = if vertical { 4 } else { 3 };
+    let store = gdef::item_variation_store(data, u32::read_at(data, 4)? as usize)?;
+    let items = item_counts(store)?;
+
+    let mut maps: Vec<Option<Vec<(u16, u16)>>> = vec![];
+    for k in 0..map_count {
+        let offset = u32::read_at(data, 8 + 4 * k)? as usize;
+        let map = match offset {
+            // Without a map, advances use the glyph ID as the inner index.
+            0 if k == 0 => Some(glyphs.iter().map(|&glyph| (0, glyph)).collect()),
+            0 => None,
+            _ => {
+                let map = read_map(data.get(offset..).ok_or(Error::InvalidOffset)?)?;
+                // Glyphs beyond the end of a map use its last entry.
+                let last = *map.last().ok_or(Error::InvalidData)?;
+                let entry = |glyph: u16| map.get(usize::from(glyph)).copied();
+                Some(glyphs.iter().map(|&glyph| entry(glyph).unwrap_or(last)).collect())
+            }
+        };
+        maps.push(map);
+    }
+
+    // The delta sets that are still used, by outer index.
+    let mut used: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+    for &(outer, inner) in maps.iter().flatten().flatten() {
+        if items.get(usize::from(outer)).is_none_or(|&count| inner >= count) {
+            return Err(Error::InvalidData);
+        }
+        used.entry(outer).or_default().push(inner);
+    }
+    for inners in used.values_mut() {
+        inners.sort_unstable();
+        inners.dedup();
+    }
+
+    let renumber = |(outer, inner): (u16, u16)| {
+        let new_outer = used.range(..outer).count() as u16;
+        let new_inner = used[&outer].binary_search(&inner).unwrap_or(0) as u16;
+        (new_outer, new_inner)
+    };
+    let maps: Vec<Option<Vec<u8>>> = maps
+        .iter()
+        .map(|map| {
+            map.as_ref().map(|map| {
+                write_map(&map.iter().map(|&e| renumber(e)).collect::<Vec<_>>())
+            })
+        })
+        .collect();
+    let store = variation::prune_regions(&write_store(store, &used)?)?;
+
+    let mut w = Writer::new();
+    w.write::<u16>(1);
+    w.write::<u16>(0);
+    let mut offset = 8 + 4 * map_count;
+    w.write::<u32>(offset as u32);
+    offset += store.len();
+    for map in &maps {
+        match map {
+            Some(map) => {
+                w.write::<u32>(offset as u32);
+                offset += map.len();
+            }
+            None => w.write::<u32>(0),
+        }
+    }
+    w.give(&store);
+    for map in maps.iter().flatten() {
+        w.give(map);
+    }
+    Ok(w.finish())
+}
+
+/// Read the entries of a delta-set index map as outer and inner indices.
+fn read_map(data: &[u8]) -> Result<Vec<(u16, u16)>> {
+    let mut r = Reader::new(data);
+    let format = r.read::<u8>()?;
+    let entry_format = r.read::<u8>()?;
+    let count = match format {
+        0 => usize::from(r.read::<u16>()?),
+        1 => r.read::<u32>()? as usize,
+        _ => return Err(Error::InvalidData),
+    };
+    let size = usize::from((entry_format >> 4) & 0x3) + 1;
+    let inner_bits = u32::from(entry_format & 0xF) + 1;
+    (0..count)
+        .map(|_| {
+            let entry = r
+                .take(size)?
+                .iter()
+                .fold(0_u32, |entry, &byte| (entry << 8) | u32::from(byte));
+            let outer = (entry >> inner_bits) as u16;
+            let inner = (entry & ((1 << inner_bits) - 1)) as u16;
+            Ok((outer, inner))
+        })
+        .collect()
+}
+
+/// Write a delta-set index map with the smallest entries that fit. Trailing
+/// entries that repeat the last one are left out.
+fn write_map(entries: &[(u16, u16)]) -> Vec<u8> {
+    let mut count = entries.len();
+    while count > 1 && entries[count - 2] == entries[count - 1] {
+        count -= 1;
+    }
+    let entries = &entries[..count];
+
+    let bits = |value: u16| 16 - value.leading_zeros();
+    let inner_bits = entries
+        .iter()
+        .map(|&(_, inner)| bits(inner))
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let outer_bits = entries.iter().map(|&(outer, _)| bits(outer)).max().unwrap_or(0);
+    let size = (inner_bits + outer_bits).div_ceil(8).max(1);
+
+    let mut w = Writer::new();
+    w.write::<u8>(0);
+    w.write::<u8>((((size - 1) << 4) | (inner_bits - 1)) as u8);
+    w.write::<u16>(entries.len() as u16);
+    for &(outer, inner) in entries {
+        let entry = (u32::from(outer) << inner_bits) | u32::from(inner);
+        w.give(&entry.to_be_bytes()[4 - size as usize..]);
+    }
+    w.finish()
+}
+
+/// The number of delta sets in each item variation data table of a store.
+fn item_counts(store: &[u8]) -> Result<Vec<u16>> {
+    let count = usize::from(u16::read_at(store, 6)?);
+    (0..count)
+        .map(|i| match u32::read_at(store, 8 + 4 * i)? as usize {
+            0 => Ok(0),
+            at => u16::read_at(store, at),
+        })
+        .collect()
+}
+
+/// Write an item variation store with only the given delta sets, keeping the
+/// variation region list as it is.
+fn write_store(store: &[u8], used: &BTreeMap<u16, Vec<u16>>) -> Result<Vec<u8>> {
+    let list = u32::read_at(store, 2)? as usize;
+    let regions = match list {
+        0 => &[][..],
+        _ => {
+            let axes = usize::from(u16::read_at(store, list)?);
+            let count = usize::from(u16::read_at(store, list + 2)?);
+            store
+                .get(list..list + 4 + 6 * axes * count)
+                .ok_or(Error::MissingData)?
+        }
+    };
+
+    let mut tables = vec![];
+    for (&outer, inners) in used {
+        let at = u32::read_at(store, 8 + 4 * usize::from(outer))? as usize;
+        let data = store.get(at..).ok_or(Error::InvalidOffset)?;
+        let words = u16::read_at(data, 2)?;
+        let region_count = usize::from(u16::read_at(data, 4)?);
+        // The high bit of the word count switches to 32- and 16-bit deltas.
+        let (word, short) = if words & 0x8000 != 0 { (4, 2) } else { (2, 1) };
+        let wide = usize::from(words & 0x7FFF);
+        let row = word * wide + short * region_count.saturating_sub(wide);
+        let rows = 6 + 2 * region_count;
+
+        let mut w = Writer::new();
+        w.write::<u16>(inners.len() as u16);
+        w.give(data.get(2..rows).ok_or(Error::MissingData)?);
+        for &inner in inners {
+            let start = rows + row * usize::from(inner);
+            w.give(data.get(start..start + row).ok_or(Error::MissingData)?);
+        }
+        tables.push(w.finish());
+    }
+
+    let mut w = Writer::new();
+    w.write::<u16>(1);
+    let header = 8 + 4 * tables.len();
+    w.write::<u32>(if regions.is_empty() { 0 } else { header as u32 });
+    w.write::<u16>(tables.len() as u16);
+    let mut offset = header + regions.len();
+    for table in &tables {
+        w.write::<u32>(offset as u32);
+        offset += table.len();
+    }
+    w.give(regions);
+    for table in &tables {
+        w.give(table);
+    }
+    Ok(w.finish())
+}
@@ -0,0 +1,1254 @@
+//! Static and partial instances of variable fonts.
+
+use std::collections::HashMap;
+
+use super::*;
+use crate::variation::{
+    f2dot14, read_tuples, rebase, rebase_store, round, write_tuples, Limit, Tuple,
+};
+
+/// The variation tables, which a static instance doesn't have.
+const VARIATION: [Tag; 7] =
+    [Tag::AVAR, Tag::CVAR, Tag::FVAR, Tag::GVAR, Tag::HVAR, Tag::MVAR, Tag::VVAR];
+
+/// Creates an instance of a variable font with TrueType outlines by pinning
+/// its variation axes to fixed positions or limiting them to smaller ranges.
+///
+/// If all axes are pinned, the result is a static font: The outlines,
+/// advances and side bearings take the deltas of the `gvar` table at the
+/// chosen position, and the control values those of `cvar`. Axes that aren't
+/// pinned stay at their default. The variation tables (`fvar`, `gvar`,
+/// `avar`, `cvar`, `HVAR`, `VVAR` and `MVAR`) are removed, so font-wide
+/// metrics that `MVAR` varies keep their default values.
+///
+/// If an axis is [limited](Self::limit) to a range, the result stays
+/// variable. Axes that are neither pinned nor limited keep their full range,
+/// and pinned axes keep a range of just the pinned position. The variation
+/// regions, the `avar` mapping, the axes in `fvar` and the conditions of
+/// feature variations are rebased onto the limited design space, and named
+/// instances outside of it are dropped. If a range doesn't contain the
+/// default, the default moves to the nearest end of the range. Values that
+/// item variation stores vary, like kerning in `GPOS` or metrics in `MVAR`,
+/// then keep their old default.
+///
+/// Positions outside of an axis's range are clamped to it. The weight and
+/// width classes in `OS/2` follow the default of the `wght` and `wdth` axes.
+/// If the `slnt` axis is pinned or limited, the italic angle in `post` and
+/// the caret slope in `hhea` follow its default, and if `ital` or `slnt` is,
+/// so do the italic bits of `fsSelection` in `OS/2` and `macStyle` in `head`.
+/// The instance is a regular font, which can then be subsetted with the same
+/// glyph IDs.
+///
+/// ```
+/// use subsetter::{subset, Instancer, Profile, Tag};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let data = std::fs::read("fonts/NotoSans-Regular.ttf")?;
+/// let instancer = Instancer::new().pin(Tag(*b"wght"), 700.0);
+/// let instance = instancer.instantiate(&data, 0)?;
+/// let sub = subset(&instance, 0, Profile::pdf(&[68, 69, 70]))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Instancer {
+    /// The restricted axes with their range in user coordinates. Pinned axes
+    /// have an empty range.
+    axes: Vec<(Tag, f32, f32)>,
+}
+
+impl Instancer {
+    /// Create an instancer that keeps all axes at their default position.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pin an axis to a position in user coordinates, like 700 for `wght`.
+    ///
+    /// Pinning or limiting an axis again replaces the earlier position. Axes
+    /// the font doesn't have are ignored.
+    pub fn pin(self, axis: Tag, value: f32) -> Self {
+        self.limit(axis, value, value)
+    }
+
+    /// Limit an axis to a range in user coordinates, like 400 to 700 for
+    /// `wght`, keeping the font variable within it.
+    ///
+    /// Limiting or pinning an axis again replaces the earlier range. Axes the
+    /// font doesn't have are ignored.
+    pub fn limit(mut self, axis: Tag, min: f32, max: f32) -> Self {
+        self.axes.retain(|&(tag, _, _)| tag != axis);
+        self.axes.push((axis, min.min(max), min.max(max)));
+        self
+    }
+
+    /// Create the instance of the face with the given index.
+    ///
+    /// The tables of static fonts are kept as they are. Fonts with `CFF2`
+    /// outlines can't be instanced yet and yield [`Error::Unsupported`].
+    pub fn instantiate(&self, data: &[u8], index: u32) -> Result<Vec<u8>> {
+        let face = parse(data, index)?;
+        let mut tables: Vec<(Tag, Cow<[u8]>)> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+
+        let Some(fvar) = face.table(Tag::FVAR) else {
+            return Ok(construct(face.kind(), tables, 4));
+        };
+        if face.table(Tag::CFF2).is_some() {
+            return Err(Error::Unsupported);
+        }
+
+        let axes = axes(fvar)?;
+        let maps = match face.table(Tag::AVAR) {
+            Some(avar) => segment_maps(avar)?,
+            None => vec![],
+        };
+        let listed = |axis: &Axis| self.axes.iter().find(|&&(tag, _, _)| tag == axis.tag);
+        let partial = axes.iter().filter_map(listed).any(|&(_, min, max)| min < max);
+        let ranges: Vec<[f32; 3]> = axes
+            .iter()
+            .map(|axis| match listed(axis) {
+                Some(&(_, min, max)) => {
+                    let (min, max) = (axis.clamp(min), axis.clamp(max));
+                    [min, axis.default.max(min).min(max), max]
+                }
+                None if partial => [axis.min, axis.default, axis.max],
+                None => [axis.default; 3],
+            })
+            .collect();
+        let limits: Vec<Limit> = axes
+            .iter()
+            .enumerate()
+            .zip(&ranges)
+            .map(|((i, axis), range)| {
+                let [min, default, max] = range.map(|v| axis.internal(maps.get(i), v));
+                Limit { min, default, max }
+            })
+            .collect();
+
+        if let Some(gvar) = face.table(Tag::GVAR) {
+            let tuples = instance_glyf(&face, gvar, &limits, &mut tables)?;
+            if partial {
+                set(&mut tables, Tag::GVAR, Some(write_gvar(axes.len(), &tuples)?));
+            }
+        }
+        if let (Some(cvar), Some(cvt)) = (face.table(Tag::CVAR), face.table(Tag::CVT)) {
+            let (cvt, tuples) = instance_cvt(cvar, cvt, &limits)?;
+            set(&mut tables, Tag::CVT, Some(cvt));
+            let store = write_tuples(&tuples, &[], 4)?;
+            let cvar = (partial && store[..2] != [0, 0])
+                .then(|| [&[0, 1, 0, 0], &store[..]].concat());
+            set(&mut tables, Tag::CVAR, cvar);
+        }
+        if let Some(os2) = face.table(Tag::OS2) {
+            let defaults: Vec<f32> = ranges.iter().map(|range| range[1]).collect();
+            set(&mut tables, Tag::OS2, Some(instance_os2(os2, &axes, &defaults)?));
+        }
+        let default = |tag: &[u8; 4]| {
+            let i = axes.iter().position(|axis| axis.tag.0 == *tag)?;
+            listed(&axes[i]).map(|_| ranges[i][1])
+        };
+        let slant = default(b"slnt");
+        let italic = default(b"ital").map(|ital| ital >= 0.5).or(slant.map(|s| s != 0.0));
+        instance_style(&mut tables, slant, italic)?;
+
+        if !partial {
+            tables.retain(|(tag, _)| !VARIATION.contains(tag));
+            return Ok(construct(face.kind(), tables, 4));
+        }
+
+        set(&mut tables, Tag::FVAR, Some(limit_fvar(fvar, &ranges)?));
+        set(&mut tables, Tag::AVAR, limit_avar(&axes, &maps, &ranges, &limits));
+        for (tag, at, wide) in [
+            (Tag::HVAR, 4, true),
+            (Tag::VVAR, 4, true),
+            (Tag::MVAR, 10, false),
+            (Tag::GDEF, 14, true),
+            (Tag::COLR, 30, true),
+        ] {
+            let Some(table) = face.table(tag) else { continue };
+            let has_store = match tag {
+                Tag::GDEF => u16::read_at(table, 2)? >= 3,
+                Tag::COLR => u16::read_at(table, 0)? >= 1,
+                _ => true,
+            };
+            if has_store {
+                set(&mut tables, tag, Some(limit_store(table, at, wide, &limits)?));
+            }
+        }
+        for tag in [Tag::GSUB, Tag::GPOS] {
+            if let Some(table) = face.table(tag) {
+                set(&mut tables, tag, Some(limit_conditions(table, &limits)?));
+            }
+        }
+
+        Ok(construct(face.kind(), tables, 4))
+    }
+}
+
+/// A variation axis of a font, as listed by [`variation_axes`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariationAxis {
+    /// The axis's tag, like `wght`.
+    pub tag: Tag,
+    /// The minimum position in user coordinates.
+    pub min: f32,
+    /// The default position in user coordinates.
+    pub default: f32,
+    /// The maximum position in user coordinates.
+    pub max: f32,
+    /// Whether the font asks applications not to show the axis.
+    pub hidden: bool,
+    /// The axis's name from the `name` table, like "Weight".
+    pub name: Option<String>,
+}
+
+/// A named instance of a variable font, as listed by [`named_instances`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedInstance {
+    /// The instance's subfamily name from the `name` table, like "Bold".
+    pub name: Option<String>,
+    /// The position of the instance on each axis in user coordinates, in the
+    /// order of the axes.
+    pub coordinates: Vec<(Tag, f32)>,
+}
+
+/// List the variation axes of a font's `fvar` table, in their order.
+///
+/// Static fonts have no axes.
+pub fn variation_axes(data: &[u8], index: u32) -> Result<Vec<VariationAxis>> {
+    let face = parse(data, index)?;
+    let Some(fvar) = face.table(Tag::FVAR) else { return Ok(vec![]) };
+    let offset = usize::from(u16::read_at(fvar, 4)?);
+    let size = usize::from(u16::read_at(fvar, 10)?);
+    axes(fvar)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, axis)| {
+            let at = offset + i * size;
+            Ok(VariationAxis {
+                tag: axis.tag,
+                min: axis.min,
+                default: axis.default,
+                max: axis.max,
+                hidden: u16::read_at(fvar, at + 16)? & 1 != 0,
+                name: name_of(&face, u16::read_at(fvar, at + 18)?)?,
+            })
+        })
+        .collect()
+}
+
+/// List the named instances of a font's `fvar` table, in their order.
+///
+/// Their coordinates can be passed to [`Instancer::pin`] to create a static
+/// instance. Static fonts have no named instances.
+pub fn named_instances(data: &[u8], index: u32) -> Result<Vec<NamedInstance>> {
+    let face = parse(data, index)?;
+    let Some(fvar) = face.table(Tag::FVAR) else { return Ok(vec![]) };
+    let offset = usize::from(u16::read_at(fvar, 4)?);
+    let count = usize::from(u16::read_at(fvar, 8)?);
+    let size = usize::from(u16::read_at(fvar, 10)?);
+    let instance_count = usize::from(u16::read_at(fvar, 12)?);
+    let instance_size = usize::from(u16::read_at(fvar, 14)?);
+    let axes = axes(fvar)?;
+    (0..instance_count)
+        .map(|i| {
+            let at = offset + count * size + i * instance_size;
+            let coordinates = axes
+                .iter()
+                .enumerate()
+                .map(|(k, axis)| {
+                    let value = i32::read_at(fvar, at + 4 + 4 * k)?;
+                    Ok((axis.tag, value as f32 / 65536.0))
+                })
+                .collect::<Result<_>>()?;
+            Ok(NamedInstance {
+                name: name_of(&face, u16::read_at(fvar, at)?)?,
+                coordinates,
+            })
+        })
+        .collect()
+}
+
+/// Look up a name ID in the face's `name` table, if it has one.
+fn name_of(face: &Face, id: u16) -> Result<Option<String>> {
+    match face.table(Tag::NAME) {
+        Some(name) => name::lookup(name, id),
+        None => Ok(None),
+    }
+}
+
+/// A variation axis of the `fvar` table.
+struct Axis {
+    tag: Tag,
+    min: f32,
+    default: f32,
+    max: f32,
+}
+
+impl Axis {
+    /// Clamp a position in user coordinates to the range of the axis.
+    fn clamp(&self, value: f32) -> f32 {
+        value.max(self.min).min(self.max)
+    }
+
+    /// Map a position in user coordinates to the normalized range from -1 to 1,
+    /// without the `avar` mapping.
+    fn normalize(&self, value: f32) -> f32 {
+        let value = if value < self.default {
+            (value - self.default) / (self.default - self.min)
+        } else if value > self.default {
+            (value - self.default) / (self.max - self.default)
+        } else {
+            0.0
+        };
+        f2dot14(value)
+    }
+
+    /// Map a normalized coordinate without the `avar` mapping back to user
+    /// coordinates.
+    fn denormalize(&self, value: f32) -> f32 {
+        if value < 0.0 {
+            self.default + value * (self.default - self.min)
+        } else {
+            self.default + value * (self.max - self.default)
+        }
+    }
+
+    /// Map a position in user coordinates to the normalized coordinate that
+    /// the variation data refers to.
+    fn internal(&self, map: Option<&Vec<(f32, f32)>>, value: f32) -> f32 {
+        let value = self.normalize(value);
+        match map {
+            Some(map) => f2dot14(map_segments(map, value)),
+            None => value,
+        }
+    }
+}
+
+/// Read the axes of the `fvar` table.
+fn axes(fvar: &[u8]) -> Result<Vec<Axis>> {
+    let offset = usize::from(u16::read_at(fvar, 4)?);
+    let count = usize::from(u16::read_at(fvar, 8)?);
+    let size = usize::from(u16::read_at(fvar, 10)?);
+    let fixed = |at| i32::read_at(fvar, at).map(|v| v as f32 / 65536.0);
+    (0..count)
+        .map(|i| {
+            let at = offset + i * size;
+            Ok(Axis {
+                tag: Tag::read_at(fvar, at)?,
+                min: fixed(at + 4)?,
+                default: fixed(at + 8)?,
+                max: fixed(at + 12)?,
+            })
+        })
+        .collect()
+}
+
+/// Limit the axes of the `fvar` table to the given ranges in user
+/// coordinates and drop the named instances outside of them.
+fn limit_fvar(fvar: &[u8], ranges: &[[f32; 3]]) -> Result<Vec<u8>> {
+    let offset = usize::from(u16::read_at(fvar, 4)?);
+    let count = usize::from(u16::read_at(fvar, 8)?);
+    let size = usize::from(u16::read_at(fvar, 10)?);
+    let instance_count = usize::from(u16::read_at(fvar, 12)?);
+    let instance_size = usize::from(u16::read_at(fvar, 14)?);
+    let instances = offset + count * size;
+    let mut limited = fvar.get(..instances).ok_or(Error::MissingData)?.to_vec();
+
+    let fixed = |value: f32| ((value * 65536.0).round() as i32).to_be_bytes();
+    for (i, range) in ranges.iter().enumerate() {
+        for (k, &value) in range.iter().enumerate() {
+            let at = offset + i * size + 4 + 4 * k;
+            limited[at..at + 4].copy_from_slice(&fixed(value));
+        }
+    }
+
+    let mut kept = 0_u16;
+    for i in 0..instance_count {
+        let at = instances + i * instance_size;
+        let instance = fvar.get(at..at + instance_size).ok_or(Error::MissingData)?;
+        let inside = ranges.iter().enumerate().all(|(k, &[min, _, max])| {
+            let value =
+                i32::read_at(instance, 4 + 4 * k).map_or(0.0, |v| v as f32 / 65536.0);
+            min <= value && value <= max
+        });
+        if inside {
+            limited.extend_from_slice(instance);
+            kept += 1;
+        }
+    }
+    limited[12..14].copy_from_slice(&kept.to_be_bytes());
+    Ok(limited)
+}
+
+/// Read the segment maps of the `avar` table.
+fn segment_maps(avar: &[u8]) -> Result<Vec<Vec<(f32, f32)>>> {
+    let mut r = Reader::new(avar);
+    r.skip(6)?;
+    let count = r.read::<u16>()?;
+    (0..count)
+        .map(|_| {
+            (0..r.read::<u16>()?)
+                .map(|_| Ok((r.read::<F2Dot14>()?.get(), r.read::<F2Dot14>()?.get())))
+                .collect()
+        })
+        .collect()
+}
+
+/// Map a normalized coordinate with a piecewise linear segment map.
+fn map_segments(map: &[(f32, f32)], value: f32) -> f32 {
+    let (Some(&(first_from, first_to)), Some(&(last_from, last_to))) =
+        (map.first(), map.last())
+    else {
+        return value;
+    };
+    if value <= first_from {
+        return value - first_from + first_to;
+    }
+    if value >= last_from {
+        return value - last_from + last_to;
+    }
+    for pair in map.windows(2) {
+        let ((from1, to1), (from2, to2)) = (pair[0], pair[1]);
+        if value <= from2 {
+            if from1 == from2 {
+                return to2;
+            }
+            return to1 + (to2 - to1) * (value - from1) / (from2 - from1);
+        }
+    }
+    value
+}
+
+/// Create the `avar` table of the limited axes. Returns `None` if all
+/// mappings are the identity.
+///
+/// The limited axes are normalized on their new ranges, while the variation
+/// data is rebased onto them. The mapping between the two is piecewise
+/// linear with breakpoints at the old default and the old mapping's
+/// breakpoints.
+fn limit_avar(
+    axes: &[Axis],
+    maps: &[Vec<(f32, f32)>],
+    ranges: &[[f32; 3]],
+    limits: &[Limit],
+) -> Option<Vec<u8>> {
+    let mut limited_maps = vec![];
+    for (i, (axis, &[min, default, max])) in axes.iter().zip(ranges).enumerate() {
+        let limited = Axis { tag: axis.tag, min, default, max };
+        let mut positions = vec![min, default, max, axis.default];
+        if let Some(map) = maps.get(i) {
+            positions.extend(map.iter().map(|&(from, _)| axis.denormalize(from)));
+        }
+
+        let mut map = vec![(-1.0, -1.0), (0.0, 0.0), (1.0, 1.0)];
+        for value in positions {
+            if min <= value && value <= max {
+                let to = limits[i].rebase(axis.internal(maps.get(i), value));
+                map.push((limited.normalize(value), f2dot14(to)));
+            }
+        }
+        map.sort_by(|a, b| a.0.total_cmp(&b.0));
+        map.dedup_by_key(|&mut (from, _)| from);
+        limited_maps.push(map);
+    }
+
+    if limited_maps.iter().flatten().all(|&(from, to)| from == to) {
+        return None;
+    }
+
+    let mut w = Writer::new();
+    w.write::<u32>(0x00010000);
+    w.write::<u16>(0);
+    w.write::<u16>(limited_maps.len() as u16);
+    for map in limited_maps {
+        let map = match map.iter().all(|&(from, to)| from == to) {
+            true => vec![(-1.0, -1.0), (0.0, 0.0), (1.0, 1.0)],
+            false => map,
+        };
+        w.write::<u16>(map.len() as u16);
+        for (from, to) in map {
+            w.write::<i16>((from * 16384.0).round() as i16);
+            w.write::<i16>((to * 16384.0).round() as i16);
+        }
+    }
+    Some(w.finish())
+}
+
+/// Replace, add or remove a table.
+fn set(tables: &mut Vec<(Tag, Cow<[u8]>)>, tag: Tag, data: Option<Vec<u8>>) {
+    let position = tables.iter().position(|&(t, _)| t == tag);
+    match (position, data) {
+        (Some(i), Some(data)) => tables[i].1 = Cow::Owned(data),
+        (None, Some(data)) => tables.push((tag, Cow::Owned(data))),
+        (Some(i), None) => {
+            tables.remove(i);
+        }
+        (None, None) => {}
+    }
+}
+
+/// Rebase the item variation store that the offset at the given position
+/// points to. The rebased store takes the place of the old one if that was at
+/// the end of the table, and is appended otherwise.
+fn limit_store(table: &[u8], at: usize, wide: bool, limits: &[Limit]) -> Result<Vec<u8>> {
+    let offset = match wide {
+        true => u32::read_at(table, at)? as usize,
+        false => usize::from(u16::read_at(table, at)?),
+    };
+    if offset == 0 {
+        return Ok(table.to_vec());
+    }
+
+    let store = gdef::item_variation_store(table, offset)?;
+    let rebased = rebase_store(store, limits)?;
+    let end = if offset + store.len() == table.len() { offset } else { table.len() };
+    let mut limited = table[..end].to_vec();
+    match wide {
+        true => limited[at..at + 4].copy_from_slice(&(end as u32).to_be_bytes()),
+        false => {
+            let end = u16::try_from(end).map_err(|_| Error::OffsetOverflow)?;
+            limited[at..at + 2].copy_from_slice(&end.to_be_bytes());
+        }
+    }
+    limited.extend(rebased);
+    Ok(limited)
+}
+
+/// Rebase the axis ranges in the conditions of the feature variations of a
+/// `GSUB` or `GPOS` table. Conditions whose range lies outside of the limited
+/// design space get an empty range.
+fn limit_conditions(table: &[u8], limits: &[Limit]) -> Result<Vec<u8>> {
+    let mut limited = table.to_vec();
+    if u16::read_at(table, 2)? < 1 || u32::read_at(table, 10)? == 0 {
+        return Ok(limited);
+    }
+
+    let variations = u32::read_at(table, 10)? as usize;
+    let count = u32::read_at(table, variations + 4)? as usize;
+    let mut seen = HashSet::new();
+    for i in 0..count {
+        let set = u32::read_at(table, variations + 8 + 8 * i)? as usize;
+        if set == 0 {
+            continue;
+        }
+        let set = variations + set;
+        for j in 0..usize::from(u16::read_at(table, set)?) {
+            let condition = set + u32::read_at(table, set + 2 + 4 * j)? as usize;
+            if u16::read_at(table, condition)? != 1 || !seen.insert(condition) {
+                continue;
+            }
+            let axis = usize::from(u16::read_at(table, condition + 2)?);
+            let Some(&limit) = limits.get(axis) else { continue };
+            let min = F2Dot14::read_at(table, condition + 4)?.get();
+            let max = F2Dot14::read_at(table, condition + 6)?.get();
+            let (min, max) = if max < limit.min || min > limit.max {
+                (1.0, -1.0)
+            } else {
+                (limit.rebase(min), limit.rebase(max))
+            };
+            for (k, value) in [min, max].into_iter().enumerate() {
+                let value = (value * 16384.0).round() as i16;
+                limited[condition + 4 + 2 * k..condition + 6 + 2 * k]
+                    .copy_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+    Ok(limited)
+}
+
+/// Apply the `cvar` deltas at the new default to the control values. Returns
+/// the control values and the tuple variations rebased onto the limited axes.
+fn instance_cvt(
+    cvar: &[u8],
+    cvt: &[u8],
+    limits: &[Limit],
+) -> Result<(Vec<u8>, Vec<Tuple>)> {
+    let values: Vec<i16> = (0..cvt.len() / 2)
+        .map(|i| i16::read_at(cvt, 2 * i))
+        .collect::<Result<_>>()?;
+    let mut sums = vec![0.0; values.len()];
+    let mut kept = vec![];
+    for tuple in read_tuples(cvar, 4, limits.len(), &[], 1, values.len())? {
+        let (constant, rebased) = rebase(&tuple.region, limits);
+        let deltas = &tuple.deltas[0];
+        match &tuple.points {
+            Some(points) => {
+                for (&point, &delta) in points.iter().zip(deltas) {
+                    if let Some(sum) = sums.get_mut(usize::from(point)) {
+                        *sum += constant * delta;
+                    }
+                }
+            }
+            None => {
+                for (sum, &delta) in sums.iter_mut().zip(deltas) {
+                    *sum += constant * delta;
+                }
+            }
+        }
+        for (factor, region) in rebased {
+            let deltas = vec![deltas.iter().map(|&delta| factor * delta).collect()];
+            kept.push(Tuple { region, points: tuple.points.clone(), deltas });
+        }
+    }
+
+    let mut w = Writer::new();
+    for (value, sum) in values.into_iter().zip(sums) {
+        w.write::<i16>((i32::from(value) + round(sum)) as i16);
+    }
+    Ok((w.finish(), kept))
+}
+
+/// Set the weight and width classes of the `OS/2` table to the positions of
+/// the `wght` and `wdth` axes.
+fn instance_os2(os2: &[u8], axes: &[Axis], positions: &[f32]) -> Result<Vec<u8>> {
+    /// The widths of the width classes in percent of the normal width.
+    const WIDTHS: [f32; 9] = [50.0, 62.5, 75.0, 87.5, 100.0, 112.5, 125.0, 150.0, 200.0];
+
+    let mut os2 = os2.to_vec();
+    for (axis, &value) in axes.iter().zip(positions) {
+        let (at, class) = match &axis.tag.0 {
+            b"wght" => (4, value.round().clamp(1.0, 1000.0) as u16),
+            b"wdth" => {
+                let distance = |width: &f32| (width - value).abs();
+                let nearest = (0..WIDTHS.len()).min_by(|&a, &b| {
+                    distance(&WIDTHS[a]).total_cmp(&distance(&WIDTHS[b]))
+                });
+                (6, nearest.unwrap_or(4) as u16 + 1)
+            }
+            _ => continue,
+        };
+        os2.get_mut(at..at + 2)
+            .ok_or(Error::MissingData)?
+            .copy_from_slice(&class.to_be_bytes());
+    }
+    Ok(os2)
+}
+
+/// Update the italic angle and caret slope for the given slant in degrees,
+/// and the italic bits for whether the instance is italic.
+fn instance_style(
+    tables: &mut [(Tag, Cow<[u8]>)],
+    slant: Option<f32>,
+    italic: Option<bool>,
+) -> Result<()> {
+    const ITALIC: u16 = 1 << 0;
+    const BOLD: u16 = 1 << 5;
+    const REGULAR: u16 = 1 << 6;
+    const MAC_ITALIC: u16 = 1 << 1;
+
+    fn table<'a>(tables: &'a mut [(Tag, Cow<[u8]>)], tag: Tag) -> Option<&'a mut [u8]> {
+        let (_, data) = tables.iter_mut().find(|(t, _)| *t == tag)?;
+        Some(data.to_mut())
+    }
+    fn write(data: &mut [u8], at: usize, bytes: &[u8]) -> Result<()> {
+        let field = data.get_mut(at..at + bytes.len()).ok_or(Error::MissingData)?;
+        field.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    if let Some(slant) = slant {
+        let slant = slant.clamp(-90.0, 90.0);
+        if let Some(post) = table(tables, Tag::POST) {
+            write(post, 4, &((slant * 65536.0).round() as i32).to_be_bytes())?;
+        }
+        let upem = match table(tables, Tag::HEAD) {
+            Some(head) => u16::read_at(head, 18)?,
+            None => 1000,
+        };
+        if let Some(hhea) = table(tables, Tag::HHEA) {
+            // A caret leaning right has a negative angle and a positive run.
+            let (rise, run) = match slant == 0.0 {
+                true => (1, 0),
+                false => {
+                    let run = -f32::from(upem) * slant.to_radians().tan();
+                    (upem as i16, run.round().clamp(-32768.0, 32767.0) as i16)
+                }
+            };
+            write(hhea, 18, &rise.to_be_bytes())?;
+            write(hhea, 20, &run.to_be_bytes())?;
+        }
+    }
+
+    if let Some(italic) = italic {
+        if let Some(os2) = table(tables, Tag::OS2) {
+            let mut selection = u16::read_at(os2, 62)?;
+            match italic {
+                true => selection = (selection | ITALIC) & !REGULAR,
+                false if selection & BOLD == 0 => {
+                    selection = selection & !ITALIC | REGULAR
+                }
+                false => selection &= !ITALIC,
+            }
+            write(os2, 62, &selection.to_be_bytes())?;
+        }
+        if let Some(head) = table(tables, Tag::HEAD) {
+            let style = u16::read_at(head, 44)?;
+            let style = if italic { style | MAC_ITALIC } else { style & !MAC_ITALIC };
+            write(head, 44, &style.to_be_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// A glyph description whose points the `gvar` deltas move.
+enum Outline<'a> {
+    /// A glyph without data.
+    Empty,
+    /// A simple glyph with its contours' end points, instructions, points and
+    /// whether they overlap.
+    Simple {
+        end_points: Vec<u16>,
+        instructions: &'a [u8],
+        points: Vec<(i32, i32, bool)>,
+        overlap: bool,
+    },
+    /// A composite glyph with its components and the trailing instructions.
+    Composite { components: Vec<Component<'a>>, tail: &'a [u8] },
+}
+
+/// A component of a composite glyph.
+struct Component<'a> {
+    flags: u16,
+    glyph: u16,
+    /// The offset or the matched points of the component.
+    args: (i32, i32),
+    /// The scale or transformation of the component.
+    transform: &'a [u8],
+}
+
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const USE_MY_METRICS: u16 = 0x0200;
+
+impl<'a> Outline<'a> {
+    fn read(data: &'a [u8]) -> Result<Self> {
+        if data.is_empty() {
+            return Ok(Self::Empty);
+        }
+
+        let mut r = Reader::new(data);
+        let num_contours = r.read::<i16>()?;
+        r.skip(8)?;
+        if num_contours >= 0 {
+            let end_points: Vec<u16> =
+                (0..num_contours).map(|_| r.read::<u16>()).collect::<Result<_>>()?;
+            let len = usize::from(r.read::<u16>()?);
+            let instructions = r.take(len)?;
+            let count = end_points.last().map_or(0, |&end| usize::from(end) + 1);
+            let (points, overlap) = glyf::read_points(&mut r, count)?;
+            return Ok(Self::Simple { end_points, instructions, points, overlap });
+        }
+
+        let mut components = vec![];
+        loop {
+            let flags = r.read::<u16>()?;
+            let glyph = r.read::<u16>()?;
+            let args = match (flags & ARG_1_AND_2_ARE_WORDS, flags & ARGS_ARE_XY_VALUES) {
+                (0, 0) => (i32::from(r.read::<u8>()?), i32::from(r.read::<u8>()?)),
+                (0, _) => {
+                    (i32::from(r.read::<u8>()? as i8), i32::from(r.read::<u8>()? as i8))
+                }
+                (_, 0) => (i32::from(r.read::<u16>()?), i32::from(r.read::<u16>()?)),
+                _ => (i32::from(r.read::<i16>()?), i32::from(r.read::<i16>()?)),
+            };
+            let transform = r.take(if flags & WE_HAVE_A_SCALE != 0 {
+                2
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                4
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                8
+            } else {
+                0
+            })?;
+            components.push(Component { flags, glyph, args, transform });
+            if flags & MORE_COMPONENTS == 0 {
+                break;
+            }
+        }
+        Ok(Self::Composite { components, tail: r.data() })
+    }
+
+    /// The points that the deltas of the glyph move, without the phantom
+    /// points: the outline points of simple glyphs and the offsets of the
+    /// components of composite glyphs.
+    fn points(&self) -> Vec<(i32, i32)> {
+        match self {
+            Self::Empty => vec![],
+            Self::Simple { points, .. } => {
+                points.iter().map(|&(x, y, _)| (x, y)).collect()
+            }
+            Self::Composite { components, .. } => {
+                components.iter().map(|component| component.args).collect()
+            }
+        }
+    }
+
+    /// Encode the glyph with moved points. The bounding box of composite
+    /// glyphs is filled in later.
+    fn write(&self, moved: &[(i32, i32)]) -> Vec<u8> {
+        let mut w = Writer::new();
+        match self {
+            Self::Empty => {}
+            Self::Simple { end_points, instructions, points, overlap } => {
+                let points: Vec<(i32, i32, bool)> = points
+                    .iter()
+                    .zip(moved)
+                    .map(|(&(_, _, on_curve), &(x, y))| (x, y, on_curve))
+                    .collect();
+                w.write::<i16>(end_points.len() as i16);
+                for v in bbox(points.iter().map(|&(x, y, _)| (x, y))) {
+                    w.write::<i16>(v);
+                }
+                for &end in end_points {
+                    w.write::<u16>(end);
+                }
+                w.write::<u16>(instructions.len() as u16);
+                w.give(instructions);
+                glyf::write_points(&mut w, &points, overlap.then_some(0x40));
+            }
+            Self::Composite { components, tail } => {
+                w.write::<i16>(-1);
+                w.give(&[0; 8]);
+                for (component, &(x, y)) in components.iter().zip(moved) {
+                    let xy = component.flags & ARGS_ARE_XY_VALUES != 0;
+                    let (x, y) = if xy { (x, y) } else { component.args };
+                    let bytes = component.flags & ARG_1_AND_2_ARE_WORDS == 0
+                        && (!xy || [x, y].iter().all(|v| i8::try_from(*v).is_ok()));
+                    let flags = match bytes {
+                        true => component.flags,
+                        false => component.flags | ARG_1_AND_2_ARE_WORDS,
+                    };
+                    w.write::<u16>(flags);
+                    w.write::<u16>(component.glyph);
+                    if bytes {
+                        w.write::<u8>(x as u8);
+                        w.write::<u8>(y as u8);
+                    } else {
+                        w.write::<u16>(x as u16);
+                        w.write::<u16>(y as u16);
+                    }
+                    w.give(component.transform);
+                }
+                w.give(tail);
+            }
+        }
+        w.finish()
+    }
+}
+
+/// The bounding box of points as `[x_min, y_min, x_max, y_max]`.
+fn bbox(points: impl Iterator<Item = (i32, i32)>) -> [i16; 4] {
+    let mut bbox = [i32::MAX, i32::MAX, i32::MIN, i32::MIN];
+    for (x, y) in points {
+        bbox = [bbox[0].min(x), bbox[1].min(y), bbox[2].max(x), bbox[3].max(y)];
+    }
+    match bbox[0] <= bbox[2] {
+        true => bbox.map(|v| v.clamp(i16::MIN.into(), i16::MAX.into()) as i16),
+        false => [0; 4],
+    }
+}
+
+/// Apply the `gvar` deltas at the new default to the outlines and horizontal
+/// metrics and update the bounding boxes and metrics extremes in `head` and
+/// `hhea`. Returns the tuple variations of each glyph, rebased onto the
+/// limited axes.
+fn instance_glyf(
+    face: &Face,
+    gvar: &[u8],
+    limits: &[Limit],
+    tables: &mut Vec<(Tag, Cow<[u8]>)>,
+) -> Result<Vec<Vec<Tuple>>> {
+    let table = |tag| face.table(tag).ok_or(Error::MissingTable(tag));
+    let glyf = glyf::Table::new(face)?;
+    let (head, hhea, hmtx) = (table(Tag::HEAD)?, table(Tag::HHEA)?, table(Tag::HMTX)?);
+    let num_glyphs = u16::read_at(table(Tag::MAXP)?, 4)?;
+
+    let num_h_metrics = u16::read_at(hhea, 34)?;
+    let mut metrics = vec![];
+    let mut advance = 0;
+    for i in 0..usize::from(num_glyphs) {
+        let lsb = if i < usize::from(num_h_metrics) {
+            advance = u16::read_at(hmtx, 4 * i)?;
+            i16::read_at(hmtx, 4 * i + 2)?
+        } else {
+            let at =
+                4 * usize::from(num_h_metrics) + 2 * (i - usize::from(num_h_metrics));
+            i16::read_at(hmtx, at).unwrap_or(0)
+        };
+        metrics.push((advance, lsb));
+    }
+
+    let gvar = gvar::Table::parse(gvar)?;
+    let axis_count = usize::from(gvar.axis_count);
+    if axis_count != limits.len() {
+        return Err(Error::InvalidData);
+    }
+    let shared = gvar.shared_tuples()?;
+
+    // Sparse deltas are interpolated from the outline, so they have to be
+    // made explicit when the outline of the default changes.
+    let moved = limits.iter().any(|limit| limit.default != 0.0);
+    let mut glyphs = vec![];
+    let mut phantoms = vec![];
+    let mut metrics_from = vec![];
+    let mut kept = vec![];
+    for glyph in 0..num_glyphs {
+        let outline = Outline::read(glyf.glyph_data(glyph)?)?;
+        let points = outline.points();
+        let (advance, lsb) = metrics[usize::from(glyph)];
+        let x_min = match outline {
+            Outline::Empty => 0,
+            _ => i32::from(i16::read_at(glyf.glyph_data(glyph)?, 2)?),
+        };
+        let left = x_min - i32::from(lsb);
+        let right = left + i32::from(advance);
+
+        let count = points.len() + 4;
+        let mut sums = vec![(0.0, 0.0); count];
+        let mut rebased = vec![];
+        if let Some(&data) = gvar.glyphs.get(usize::from(glyph)) {
+            if !data.is_empty() {
+                for tuple in read_tuples(data, 0, axis_count, &shared, 2, count)? {
+                    let (constant, regions) = rebase(&tuple.region, limits);
+                    let deltas = match (moved, &tuple.points) {
+                        (true, Some(_)) => inferred(&outline, &points, &tuple, count),
+                        _ => tuple.deltas[0]
+                            .iter()
+                            .copied()
+                            .zip(tuple.deltas[1].iter().copied())
+                            .collect(),
+                    };
+                    if constant != 0.0 {
+                        let all = inferred(&outline, &points, &tuple, count);
+                        for (sum, (x, y)) in sums.iter_mut().zip(all) {
+                            sum.0 += constant * x;
+                            sum.1 += constant * y;
+                        }
+                    }
+                    let points = if moved { None } else { tuple.points.clone() };
+                    for (factor, region) in regions {
+                        let (xs, ys) =
+                            deltas.iter().map(|&(x, y)| (factor * x, factor * y)).unzip();
+                        rebased.push(Tuple {
+                            region,
+                            points: points.clone(),
+                            deltas: vec![xs, ys],
+                        });
+                    }
+                }
+            }
+        }
+        kept.push(rebased);
+
+        let moved: Vec<(i32, i32)> = points
+            .iter()
+            .zip(&sums)
+            .map(|(&(x, y), &(dx, dy))| (x + round(dx), y + round(dy)))
+            .collect();
+        glyphs.push(outline.write(&moved));
+        let n = points.len();
+        phantoms.push((left + round(sums[n].0), right + round(sums[n + 1].0)));
+        metrics_from.push(match &outline {
+            Outline::Composite { components, .. } => components
+                .iter()
+                .find(|component| component.flags & USE_MY_METRICS != 0)
+                .map(|component| usize::from(component.glyph)),
+            _ => None,
+        });
+    }
+
+    // Composite glyphs can take their metrics from a component.
+    let phantoms: Vec<(i32, i32)> = (0..phantoms.len())
+        .map(|glyph| {
+            let mut source = glyph;
+            for _ in 0..16 {
+                match metrics_from[source] {
+                    Some(component) if component < phantoms.len() => source = component,
+                    _ => break,
+                }
+            }
+            phantoms[source]
+        })
+        .collect();
+
+    // The bounding boxes of composite glyphs depend on their moved components.
+    let mut bboxes = vec![];
+    for glyph in 0..glyphs.len() {
+        let bbox = match i16::read_at(&glyphs[glyph], 0) {
+            Ok(num_contours) if num_contours < 0 => {
+                let bbox = bbox(transformed_points(&glyphs, glyph, 0)?.into_iter());
+                for (i, v) in bbox.iter().enumerate() {
+                    glyphs[glyph][2 + 2 * i..4 + 2 * i].copy_from_slice(&v.to_be_bytes());
+                }
+                Some(bbox)
+            }
+            Ok(_) => {
+                Some([2, 4, 6, 8].map(|at| i16::read_at(&glyphs[glyph], at).unwrap_or(0)))
+            }
+            Err(_) => None,
+        };
+        bboxes.push(bbox);
+    }
+
+    // The phantom points give the advance and the left side bearing.
+    let metrics: Vec<(u16, i16)> = phantoms
+        .iter()
+        .zip(&bboxes)
+        .zip(&metrics)
+        .map(|((&(left, right), bbox), &(_, lsb))| {
+            let advance = (right - left).clamp(0, u16::MAX.into()) as u16;
+            let lsb = bbox.map_or(lsb, |bbox| (i32::from(bbox[0]) - left) as i16);
+            (advance, lsb)
+        })
+        .collect();
+
+    let mut sub_glyf = Writer::new();
+    let mut offsets = vec![];
+    for glyph in &glyphs {
+        offsets.push(sub_glyf.len());
+        sub_glyf.give(glyph);
+        sub_glyf.align(2);
+    }
+    offsets.push(sub_glyf.len());
+    let long_loca = sub_glyf.len() / 2 > usize::from(u16::MAX);
+    let mut loca = Writer::new();
+    for offset in offsets {
+        match long_loca {
+            true => loca.write::<u32>(offset as u32),
+            false => loca.write::<u16>((offset / 2) as u16),
+        }
+    }
+
+    let mut num_long = metrics.len();
+    while num_long > 1 && metrics[num_long - 1].0 == metrics[num_long - 2].0 {
+        num_long -= 1;
+    }
+    let mut sub_hmtx = Writer::new();
+    for (i, &(advance, lsb)) in metrics.iter().enumerate() {
+        if i < num_long {
+            sub_hmtx.write::<u16>(advance);
+        }
+        sub_hmtx.write::<i16>(lsb);
+    }
+
+    let mut font_bbox = [i16::MAX, i16::MAX, i16::MIN, i16::MIN];
+    let (mut min_lsb, mut min_rsb, mut max_extent) = (i16::MAX, i16::MAX, i16::MIN);
+    for (bbox, &(advance, lsb)) in bboxes.iter().zip(&metrics) {
+        let Some(bbox) = bbox.filter(|bbox| *bbox != [0; 4]) else { continue };
+        font_bbox = [
+            font_bbox[0].min(bbox[0]),
+            font_bbox[1].min(bbox[1]),
+            font_bbox[2].max(bbox[2]),
+            font_bbox[3].max(bbox[3]),
+        ];
+        let width = i32::from(bbox[2]) - i32::from(bbox[0]);
+        let extent = i32::from(lsb) + width;
+        min_lsb = min_lsb.min(lsb);
+        min_rsb = min_rsb.min((i32::from(advance) - extent) as i16);
+        max_extent = max_extent.max(extent as i16);
+    }
+
+    let mut sub_head = head.to_vec();
+    if font_bbox[0] <= font_bbox[2] {
+        for (i, v) in font_bbox.iter().enumerate() {
+            sub_head[36 + 2 * i..38 + 2 * i].copy_from_slice(&v.to_be_bytes());
+        }
+    }
+    sub_head[50..52].copy_from_slice(&i16::from(long_loca).to_be_bytes());
+
+    let mut sub_hhea = hhea.to_vec();
+    let max_advance = metrics.iter().map(|&(advance, _)| advance).max().unwrap_or(0);
+    sub_hhea[10..12].copy_from_slice(&max_advance.to_be_bytes());
+    if min_lsb <= max_extent {
+        sub_hhea[12..14].copy_from_slice(&min_lsb.to_be_bytes());
+        sub_hhea[14..16].copy_from_slice(&min_rsb.to_be_bytes());
+        sub_hhea[16..18].copy_from_slice(&max_extent.to_be_bytes());
+    }
+    sub_hhea[34..36].copy_from_slice(&(num_long as u16).to_be_bytes());
+
+    set(tables, Tag::GLYF, Some(sub_glyf.finish()));
+    set(tables, Tag::LOCA, Some(loca.finish()));
+    set(tables, Tag::HMTX, Some(sub_hmtx.finish()));
+    set(tables, Tag::HEAD, Some(sub_head));
+    set(tables, Tag::HHEA, Some(sub_hhea));
+    Ok(kept)
+}
+
+/// Write a `gvar` table with the tuple variations of each glyph. Peaks that
+/// more than one glyph uses become shared tuples.
+fn write_gvar(axis_count: usize, glyphs: &[Vec<Tuple>]) -> Result<Vec<u8>> {
+    let mut counts: HashMap<Vec<i16>, usize> = HashMap::new();
+    for tuples in glyphs {
+        let mut peaks: Vec<Vec<i16>> = tuples
+            .iter()
+            .map(|tuple| {
+                tuple
+                    .region
+                    .iter()
+                    .map(|&(_, peak, _)| (peak * 16384.0).round() as i16)
+                    .collect()
+            })
+            .collect();
+        peaks.sort();
+        peaks.dedup();
+        for peak in peaks {
+            *counts.entry(peak).or_default() += 1;
+        }
+    }
+    let mut shared: Vec<(Vec<i16>, usize)> =
+        counts.into_iter().filter(|&(_, count)| count > 1).collect();
+    shared.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    shared.truncate(0x0FFF);
+    let shared: Vec<Vec<f32>> = shared
+        .into_iter()
+        .map(|(peak, _)| peak.into_iter().map(|v| f32::from(v) / 16384.0).collect())
+        .collect();
+
+    let mut data = vec![];
+    for tuples in glyphs {
+        let store = write_tuples(tuples, &shared, 0)?;
+        data.push(if store[..2] == [0, 0] { vec![] } else { store });
+    }
+
+    let shared: Vec<Vec<u8>> = shared
+        .iter()
+        .map(|peak| {
+            let mut w = Writer::new();
+            variation::write_tuple(&mut w, peak.iter().copied());
+            w.finish()
+        })
+        .collect();
+    let shared: Vec<&[u8]> = shared.iter().map(Vec::as_slice).collect();
+    let data: Vec<&[u8]> = data.iter().map(Vec::as_slice).collect();
+    Ok(gvar::write(axis_count as u16, &shared, &data))
+}
+
+/// The deltas of a tuple variation for all points of a glyph. Outline points
+/// of simple glyphs without explicit deltas are interpolated from their
+/// neighbors, other points have none.
+fn inferred(
+    outline: &Outline,
+    points: &[(i32, i32)],
+    tuple: &Tuple,
+    count: usize,
+) -> Vec<(f32, f32)> {
+    let (xs, ys) = (&tuple.deltas[0], &tuple.deltas[1]);
+    let Some(numbers) = &tuple.points else {
+        return xs.iter().copied().zip(ys.iter().copied()).collect();
+    };
+
+    let mut deltas: Vec<Option<(f32, f32)>> = vec![None; count];
+    for ((&point, &x), &y) in numbers.iter().zip(xs).zip(ys) {
+        if let Some(delta) = deltas.get_mut(usize::from(point)) {
+            *delta = Some((x, y));
+        }
+    }
+    if let Outline::Simple { end_points, .. } = outline {
+        interpolate(points, end_points, &mut deltas);
+    }
+    deltas.into_iter().map(|delta| delta.unwrap_or((0.0, 0.0))).collect()
+}
+
+/// Infer the deltas of untouched points in each contour from the nearest
+/// touched points before and after them.
+fn interpolate(
+    points: &[(i32, i32)],
+    end_points: &[u16],
+    deltas: &mut [Option<(f32, f32)>],
+) {
+    let mut start = 0;
+    for &end in end_points {
+        let end = usize::from(end);
+        if end >= points.len() || end < start {
+            return;
+        }
+        let touched: Vec<usize> =
+            (start..=end).filter(|&i| deltas[i].is_some()).collect();
+        for (k, &a) in touched.iter().enumerate() {
+            let b = touched[(k + 1) % touched.len()];
+            let (Some(da), Some(db)) = (deltas[a], deltas[b]) else { continue };
+            let mut i = if a == end { start } else { a + 1 };
+            while i != b {
+                let axis = |p: fn((i32, i32)) -> i32, d: fn((f32, f32)) -> f32| {
+                    iup(p(points[i]), p(points[a]), p(points[b]), d(da), d(db))
+                };
+                deltas[i] = Some((axis(|p| p.0, |d| d.0), axis(|p| p.1, |d| d.1)));
+                i = if i == end { start } else { i + 1 };
+            }
+        }
+        start = end + 1;
+    }
+}
+
+/// Interpolate the delta of a coordinate between two reference coordinates
+/// with their deltas.
+fn iup(v: i32, v1: i32, v2: i32, d1: f32, d2: f32) -> f32 {
+    if v1 == v2 {
+        return if d1 == d2 { d1 } else { 0.0 };
+    }
+    let (v1, v2, d1, d2) = if v1 < v2 { (v1, v2, d1, d2) } else { (v2, v1, d2, d1) };
+    if v <= v1 {
+        d1
+    } else if v >= v2 {
+        d2
+    } else {
+        d1 + (v - v1) as f32 * (d2 - d1) / (v2 - v1) as f32
+    }
+}
+
+/// The outline points of a glyph in the instanced `glyf` table, with the
+/// components of composite glyphs transformed and placed.
+fn transformed_points(
+    glyphs: &[Vec<u8>],
+    glyph: usize,
+    depth: u8,
+) -> Result<Vec<(i32, i32)>> {
+    let data = glyphs.get(glyph).ok_or(Error::InvalidData)?;
+    if depth > 16 {
+        return Err(Error::InvalidData);
+    }
+
+    let Outline::Composite { components, .. } = Outline::read(data)? else {
+        return Ok(Outline::read(data)?.points());
+    };
+
+    let mut points = vec![];
+    for component in components {
+        let mut child =
+            transformed_points(glyphs, usize::from(component.glyph), depth + 1)?;
+        let value = |at| F2Dot14::read_at(component.transform, at).map(F2Dot14::get);
+        let [a, b, c, d] = match component.transform.len() {
+            2 => [value(0)?, 0.0, 0.0, value(0)?],
+            4 => [value(0)?, 0.0, 0.0, value(2)?],
+            8 => [value(0)?, value(2)?, value(4)?, value(6)?],
+            _ => [1.0, 0.0, 0.0, 1.0],
+        };
+        for point in &mut child {
+            let (x, y) = (point.0 as f32, point.1 as f32);
+            *point = (round(a * x + c * y), round(b * x + d * y));
+        }
+
+        // The component is either offset or aligned on a pair of points.
+        let (dx, dy) = if component.flags & ARGS_ARE_XY_VALUES != 0 {
+            component.args
+        } else {
+            let parent = points.get(component.args.0 as usize);
+            let child = child.get(component.args.1 as usize);
+            match (parent, child) {
+                (Some(&(px, py)), Some(&(cx, cy))) => (px - cx, py - cy),
+                _ => (0, 0),
+            }
+        };
+        points.extend(child.into_iter().map(|(x, y)| (x + dx, y + dy)));
+    }
+    Ok(points)
+}
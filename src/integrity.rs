@@ -0,0 +1,136 @@
+use super::*;
+use crate::json::JsonWriter;
+
+/// A content hash of a subsetted font and an optional signature of it, so
+/// that the output of the subsetting step can be verified later.
+///
+/// The hash is the SHA-256 digest of the font with the `checkSumAdjustment`
+/// field of its `head` table zeroed. That field depends on the whole file, so
+/// excluding it lets tools patch the checksum without invalidating the hash.
+/// Data that isn't an OpenType font, like WOFF2, is hashed as is.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Integrity {
+    /// The SHA-256 digest of the font.
+    pub hash: [u8; 32],
+    /// A signature of the hash, produced by [`sign`](Self::sign).
+    pub signature: Option<Vec<u8>>,
+}
+
+impl Integrity {
+    /// Compute the content hash of a font.
+    pub fn compute(font: &[u8]) -> Self {
+        let mut data = Cow::Borrowed(font);
+        if let Ok(face) = parse(font, 0) {
+            if let Some(head) = face.table(Tag::HEAD).filter(|head| head.len() >= 12) {
+                let offset = head.as_ptr() as usize - font.as_ptr() as usize + 8;
+                data.to_mut()[offset..offset + 4].fill(0);
+            }
+        }
+        Self { hash: sha256(&data), signature: None }
+    }
+
+    /// Sign the hash with a callback, e.g. one that uses the integrator's key,
+    /// and store the signature alongside the hash.
+    pub fn sign(mut self, signer: impl FnOnce(&[u8; 32]) -> Vec<u8>) -> Self {
+        self.signature = Some(signer(&self.hash));
+        self
+    }
+
+    /// The hash as lowercase hexadecimal digits.
+    pub fn hex(&self) -> String {
+        hex(&self.hash)
+    }
+
+    /// Serialize the hash and signature to JSON, both as hexadecimal digits.
+    pub fn to_json(&self) -> String {
+        let mut w = JsonWriter::new();
+        w.begin_object(None);
+        w.string(Some("sha256"), &self.hex());
+        if let Some(signature) = &self.signature {
+            w.string(Some("signature"), &hex(signature));
+        }
+        w.end_object();
+        w.finish()
+    }
+}
+
+/// Format bytes as lowercase hexadecimal digits.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Compute the SHA-256 digest of the data.
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+        0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+        0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+        0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+        0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+        0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+        0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+        0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c,
+        0x1f83d9ab, 0x5be0cd19,
+    ];
+
+    // Pad with a one bit, zeros and the bit length to a multiple of 64 bytes.
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend((data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 =
+                w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 =
+                w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (s, v) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 32];
+    for (chunk, s) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&s.to_be_bytes());
+    }
+    digest
+}
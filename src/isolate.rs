@@ -0,0 +1,82 @@
+use std::fmt::Write as _;
+
+use super::*;
+
+/// Produce a font containing only a single glyph (plus .notdef and the
+/// components it needs), together with a trace of every decision affecting
+/// the glyph.
+///
+/// When a glyph renders wrong after subsetting, this reproduces the problem
+/// with a minimal font, and the trace shows where the glyph's outline and
+/// metrics came from.
+pub fn isolate_glyph(data: &[u8], index: u32, glyph: u16) -> Result<(Vec<u8>, String)> {
+    let plan = plan(data, index, Profile::pdf(&[glyph]))?;
+    let (output, warnings, _, provenance) = result::subset_all(data, index, &plan)?;
+
+    let mut out = String::new();
+    writeln!(out, "glyph: {glyph}").unwrap();
+    writeln!(out, "closure: {:?}", plan.glyphs()).unwrap();
+
+    writeln!(out, "tables:").unwrap();
+    for &(tag, action) in plan.tables() {
+        write!(out, "  {tag}: {}", action.name()).unwrap();
+        if let Some(table) = provenance.tables.iter().find(|t| t.tag == tag) {
+            let output = &table.output;
+            match &table.origin {
+                Origin::Verbatim(source) => write!(out, ", verbatim from {source:?}"),
+                Origin::Rewritten(source) => write!(out, ", rewritten from {source:?}"),
+                Origin::Synthesized => write!(out, ", synthesized"),
+            }
+            .unwrap();
+            write!(out, " to {output:?}").unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+
+    let source = parse(data, index)?;
+    let subsetted = parse(&output, 0)?;
+    for (name, face, bytes) in
+        [("source", &source, data), ("output", &subsetted, &output)]
+    {
+        writeln!(out, "{name}:").unwrap();
+        trace_glyph(face, bytes, glyph, &mut out)?;
+    }
+
+    writeln!(out, "warnings:").unwrap();
+    for warning in &warnings {
+        writeln!(out, "  {warning}").unwrap();
+    }
+
+    Ok((output, out))
+}
+
+/// Write where the glyph's outline is stored, its metrics and the codepoints
+/// mapped to it.
+fn trace_glyph(face: &Face, data: &[u8], glyph: u16, out: &mut String) -> Result<()> {
+    match face.outlines() {
+        Outlines::TrueType => {
+            let table = glyf::Table::new(face)?;
+            let outline = table.glyph_data(glyph)?;
+            let start = outline.as_ptr() as usize - data.as_ptr() as usize;
+            let kind = dump::outline_kind(&table, glyph)?;
+            let range = start..start + outline.len();
+            writeln!(out, "  outline: {kind}, {} bytes at {range:?}", outline.len())
+                .unwrap();
+        }
+        outlines => writeln!(out, "  outline: {}", outlines.name()).unwrap(),
+    }
+
+    let (advance, lsb) = dump::metrics(face, glyph)?;
+    writeln!(out, "  metrics: advance {advance}, lsb {lsb}").unwrap();
+
+    if let Some(cmap) = face.table(Tag::CMAP) {
+        let codepoints: Vec<_> = cmap::mappings(cmap)?
+            .into_iter()
+            .filter(|&(_, g)| g == glyph)
+            .map(|(c, _)| format!("U+{c:04X}"))
+            .collect();
+        writeln!(out, "  codepoints: {}", codepoints.join(", ")).unwrap();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,373 @@
+//! A minimal JSON reader and writer for plans and reports.
+//!
+//! The crate doesn't depend on serde, so this only supports the subset of JSON
+//! that we actually produce: objects, arrays, strings, integers and booleans.
+
+use std::fmt::Write as _;
+
+use super::{Error, Result};
+
+/// A parsed JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Look up a key in an object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Object(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Self::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match *self {
+            Self::Number(n)
+                if n.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&n) =>
+            {
+                Some(n as u32)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            Self::Number(n)
+                if n.fract() == 0.0
+                    && (i32::MIN as f64..=i32::MAX as f64).contains(&n) =>
+            {
+                Some(n as i32)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Self::Object(pairs) => Some(pairs),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a JSON document.
+pub fn parse(text: &str) -> Result<Value> {
+    let mut p = Parser { s: text.as_bytes(), i: 0 };
+    let value = p.value()?;
+    p.ws();
+    if p.i != p.s.len() {
+        return Err(Error::InvalidData);
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+
+impl Parser<'_> {
+    fn ws(&mut self) {
+        while matches!(self.s.get(self.i), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.i += 1;
+        }
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        self.ws();
+        if self.s.get(self.i) == Some(&c) {
+            self.i += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(Error::InvalidData)
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Value) -> Result<Value> {
+        if self.s[self.i..].starts_with(word.as_bytes()) {
+            self.i += word.len();
+            Ok(value)
+        } else {
+            Err(Error::InvalidData)
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.ws();
+        match self.s.get(self.i).ok_or(Error::MissingData)? {
+            b'{' => {
+                self.i += 1;
+                let mut pairs = vec![];
+                if !self.eat(b'}') {
+                    loop {
+                        self.ws();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        pairs.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Value::Object(pairs))
+            }
+            b'[' => {
+                self.i += 1;
+                let mut items = vec![];
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Value::Array(items))
+            }
+            b'"' => self.string().map(Value::String),
+            b't' => self.keyword("true", Value::Bool(true)),
+            b'f' => self.keyword("false", Value::Bool(false)),
+            b'n' => self.keyword("null", Value::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.i;
+        while matches!(
+            self.s.get(self.i),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.i += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.i])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Value::Number)
+            .ok_or(Error::InvalidData)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        if self.s.get(self.i) != Some(&b'"') {
+            return Err(Error::InvalidData);
+        }
+        self.i += 1;
+
+        let mut out = String::new();
+        loop {
+            let start = self.i;
+            while !matches!(self.s.get(self.i), Some(b'"' | b'\\') | None) {
+                self.i += 1;
+            }
+            out.push_str(
+                std::str::from_utf8(&self.s[start..self.i])
+                    .map_err(|_| Error::InvalidData)?,
+            );
+            match self.s.get(self.i).ok_or(Error::MissingData)? {
+                b'"' => {
+                    self.i += 1;
+                    return Ok(out);
+                }
+                _ => {
+                    let c = *self.s.get(self.i + 1).ok_or(Error::MissingData)?;
+                    self.i += 2;
+                    out.push(match c {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let hex = self
+                                .s
+                                .get(self.i..self.i + 4)
+                                .ok_or(Error::MissingData)?;
+                            self.i += 4;
+                            let code = std::str::from_utf8(hex)
+                                .ok()
+                                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                                .ok_or(Error::InvalidData)?;
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(Error::InvalidData),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Writes JSON with one object member or array item per line.
+pub struct JsonWriter {
+    out: String,
+    depth: usize,
+    first: bool,
+}
+
+impl JsonWriter {
+    /// Create a new, empty writer.
+    pub fn new() -> Self {
+        Self { out: String::new(), depth: 0, first: true }
+    }
+
+    /// Start a new object, optionally as the member `key` of the parent.
+    pub fn begin_object(&mut self, key: Option<&str>) {
+        self.item(key);
+        self.out.push('{');
+        self.depth += 1;
+        self.first = true;
+    }
+
+    /// Close the current object.
+    pub fn end_object(&mut self) {
+        self.close('}');
+    }
+
+    /// Write a number.
+    pub fn number(&mut self, key: Option<&str>, value: impl Into<f64>) {
+        self.item(key);
+        write!(self.out, "{}", value.into()).unwrap();
+    }
+
+    /// Write a boolean.
+    pub fn bool(&mut self, key: Option<&str>, value: bool) {
+        self.item(key);
+        self.out.push_str(if value { "true" } else { "false" });
+    }
+
+    /// Write a string.
+    pub fn string(&mut self, key: Option<&str>, value: &str) {
+        self.item(key);
+        self.quote(value);
+    }
+
+    /// Write an array of numbers on a single line.
+    pub fn numbers<T: Into<f64>>(
+        &mut self,
+        key: Option<&str>,
+        values: impl IntoIterator<Item = T>,
+    ) {
+        self.item(key);
+        self.out.push('[');
+        for (i, value) in values.into_iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            write!(self.out, "{}", value.into()).unwrap();
+        }
+        self.out.push(']');
+    }
+
+    /// Write an array of strings on a single line.
+    pub fn strings<'a>(
+        &mut self,
+        key: Option<&str>,
+        values: impl IntoIterator<Item = &'a str>,
+    ) {
+        self.item(key);
+        self.out.push('[');
+        for (i, value) in values.into_iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.quote(value);
+        }
+        self.out.push(']');
+    }
+
+    /// Return the written document.
+    pub fn finish(mut self) -> String {
+        self.out.push('\n');
+        self.out
+    }
+
+    fn item(&mut self, key: Option<&str>) {
+        if self.depth > 0 {
+            if !self.first {
+                self.out.push(',');
+            }
+            self.newline();
+        }
+        self.first = false;
+        if let Some(key) = key {
+            self.quote(key);
+            self.out.push_str(": ");
+        }
+    }
+
+    fn close(&mut self, c: char) {
+        self.depth -= 1;
+        if !self.first {
+            self.newline();
+        }
+        self.first = false;
+        self.out.push(c);
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.depth {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn quote(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\r' => self.out.push_str("\\r"),
+                '\t' => self.out.push_str("\\t"),
+                c if (c as u32) < 0x20 => {
+                    write!(self.out, "\\u{:04x}", c as u32).unwrap()
+                }
+                c => self.out.push(c),
+            }
+        }
+        self.out.push('"');
+    }
+}
@@ -0,0 +1,1245 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::*;
+
+/// Maps the index of a source lookup to its index in the output, if it is
+/// kept.
+pub(crate) type LookupMap<'a> = &'a dyn Fn(u16) -> Option<u16>;
+
+/// Merges consecutive subsetted subtables of a lookup with the given type.
+pub(crate) type Merge = fn(u16, Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>>;
+
+/// The retained glyphs by their ID in the source font, with their ID in the
+/// output. The IDs only differ if the glyphs are renumbered.
+pub(crate) type Retained = HashMap<u16, u16>;
+
+/// The retained glyphs of a context, including `.notdef`, with the IDs that
+/// the layout tables refer to them by in the output.
+pub(crate) fn retained_glyphs(ctx: &Context) -> Retained {
+    if ctx.plan.remap_glyphs {
+        return remap::new_ids(&remap::new_order(ctx));
+    }
+    ctx.subset.iter().chain([&0]).map(|&glyph| (glyph, glyph)).collect()
+}
+
+/// A lookup of a `GSUB` or `GPOS` table with extension subtables resolved.
+struct Lookup<'a> {
+    kind: u16,
+    flag: u16,
+    subtables: Vec<&'a [u8]>,
+    mark_set: Option<u16>,
+}
+
+/// Subset the script, feature and lookup lists of a `GSUB` or `GPOS` table.
+///
+/// Each subtable is subsetted with the given function, which returns `None`
+/// if the subtable doesn't apply to the retained glyphs anymore. Lookups with
+/// subtables whose type and format aren't `supported` are reported and
+/// handled according to the plan's [`LookupPolicy`]. The subsetted subtables
+/// of each lookup are then passed to `merge`, which may combine consecutive
+/// ones of the given lookup type. Lookups without subtables are dropped, then
+/// features without lookups other than `size`, and then language systems that
+/// are equivalent to their script's default one and scripts without language
+/// systems. Feature variations are dropped, since they reference features and
+/// lookups as well.
+///
+/// Lookups are promoted to extension lookups of the given type where the
+/// 16-bit offsets to their subtables would overflow, see [`write_lookups`].
+pub(crate) fn subset<F>(
+    ctx: &mut Context,
+    tag: Tag,
+    extension: u16,
+    supported: fn(u16, u16) -> bool,
+    merge: Merge,
+    subset_subtable: F,
+) -> Result<()>
+where
+    F: Fn(u16, &[u8], LookupMap) -> Result<Option<Vec<u8>>>,
+{
+    let table = ctx.expect_table(tag)?;
+    let list = offset(table, 8)?;
+    let lookups = (0..u16::read_at(list, 0)?)
+        .map(|i| read_lookup(list, i, extension))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut unsupported = vec![];
+    for (i, lookup) in lookups.iter().enumerate() {
+        let mut known = true;
+        for subtable in &lookup.subtables {
+            known &= supported(lookup.kind, u16::read_at(subtable, 0)?);
+        }
+        if !known {
+            ctx.warnings
+                .push(Warning::UnsupportedLookup(tag, i as u16, lookup.kind));
+        }
+        unsupported.push(!known);
+    }
+    if unsupported.contains(&true) {
+        match ctx.plan.lookup_policy {
+            LookupPolicy::Fail => return Err(Error::Unsupported),
+            // A table copied verbatim would refer to the old glyph IDs.
+            LookupPolicy::KeepTable
+                if retained_glyphs(ctx).iter().any(|(old, new)| old != new) =>
+            {
+                ctx.warnings.push(Warning::TableDropped(tag));
+                return Ok(());
+            }
+            LookupPolicy::KeepTable => {
+                ctx.push(tag, table);
+                return Ok(());
+            }
+            LookupPolicy::DropLookup => {}
+        }
+    }
+
+    // Contextual subtables keep their rules even if the lookups they apply are
+    // dropped, so whether a lookup is kept doesn't depend on other lookups.
+    let identity = |index: u16| Some(index);
+    let mut map = vec![];
+    let mut kept = 0;
+    for (lookup, &unsupported) in lookups.iter().zip(&unsupported) {
+        let mut applies = false;
+        for subtable in lookup.subtables.iter().filter(|_| !unsupported) {
+            if subset_subtable(lookup.kind, subtable, &identity)?.is_some() {
+                applies = true;
+                break;
+            }
+        }
+        map.push(applies.then_some(kept));
+        kept += u16::from(applies);
+    }
+
+    let lookup_map = |index: u16| map.get(usize::from(index)).copied().flatten();
+    let mut sub_lookups = vec![];
+    for (lookup, new) in lookups.iter().zip(&map) {
+        if new.is_none() {
+            continue;
+        }
+        let mut subtables = vec![];
+        for subtable in &lookup.subtables {
+            subtables.extend(subset_subtable(lookup.kind, subtable, &lookup_map)?);
+        }
+        sub_lookups.push((lookup, merge(lookup.kind, subtables)?));
+    }
+
+    let (features, feature_map) = subset_features(offset(table, 6)?, &lookup_map)?;
+    let scripts = subset_scripts(offset(table, 4)?, &feature_map)?;
+    let lookups = write_lookups(&sub_lookups, extension)?;
+    ctx.push(tag, write_header(&scripts, &features, &lookups)?);
+    Ok(())
+}
+
+/// Write a `GSUB` or `GPOS` table with the given lookups, which have no flags,
+/// and features, each with the indices of its lookups. The features are
+/// applied by default to all scripts and languages.
+///
+/// Lookups are promoted to extension lookups of the given type where needed,
+/// like in [`subset`].
+pub(crate) fn write_table(
+    features: &[([u8; 4], Vec<u16>)],
+    lookups: Vec<(u16, Vec<Vec<u8>>)>,
+    extension: u16,
+) -> Result<Vec<u8>> {
+    let mut features: Vec<&([u8; 4], Vec<u16>)> = features.iter().collect();
+    features.sort_by_key(|&(tag, _)| *tag);
+
+    let mut p = Packer::new();
+    p.write::<u16>(features.len() as u16);
+    for (tag, indices) in &features {
+        let mut q = Packer::new();
+        q.null();
+        q.write::<u16>(indices.len() as u16);
+        for &index in indices {
+            q.write::<u16>(index);
+        }
+        p.write::<[u8; 4]>(*tag);
+        p.link(q.finish()?);
+    }
+    let feature_list = p.finish()?;
+
+    // A single default script with a default language system.
+    let mut lang_sys = Packer::new();
+    lang_sys.null();
+    lang_sys.write::<u16>(0xFFFF);
+    lang_sys.write::<u16>(features.len() as u16);
+    for index in 0..features.len() as u16 {
+        lang_sys.write::<u16>(index);
+    }
+    let mut script = Packer::new();
+    script.link(lang_sys.finish()?);
+    script.write::<u16>(0);
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.write::<[u8; 4]>(*b"DFLT");
+    p.link(script.finish()?);
+    let script_list = p.finish()?;
+
+    let headers: Vec<Lookup> = lookups
+        .iter()
+        .map(|&(kind, _)| Lookup { kind, flag: 0, subtables: vec![], mark_set: None })
+        .collect();
+    let lookups: Vec<_> = headers
+        .iter()
+        .zip(lookups)
+        .map(|(lookup, (_, subtables))| (lookup, subtables))
+        .collect();
+    let lookup_list = write_lookups(&lookups, extension)?;
+    write_header(&script_list, &feature_list, &lookup_list)
+}
+
+/// Write a version 1.0 `GSUB` or `GPOS` table with the given lists.
+fn write_header(scripts: &[u8], features: &[u8], lookups: &[u8]) -> Result<Vec<u8>> {
+    let mut w = Writer::new();
+    w.write::<u16>(1);
+    w.write::<u16>(0);
+    w.write::<u16>(10);
+    w.write::<u16>(to_offset(10 + scripts.len())?);
+    w.write::<u16>(to_offset(10 + scripts.len() + features.len())?);
+    w.give(scripts);
+    w.give(features);
+    w.give(lookups);
+    Ok(w.finish())
+}
+
+/// Read a lookup, resolving its extension subtables.
+fn read_lookup(list: &[u8], index: u16, extension: u16) -> Result<Lookup<'_>> {
+    let lookup = offset(list, 2 + 2 * usize::from(index))?;
+    let mut kind = u16::read_at(lookup, 0)?;
+    let flag = u16::read_at(lookup, 2)?;
+    let count = usize::from(u16::read_at(lookup, 4)?);
+
+    let is_extension = kind == extension;
+    let mut subtables = vec![];
+    for i in 0..count {
+        let subtable = offset(lookup, 6 + 2 * i)?;
+        if is_extension {
+            kind = u16::read_at(subtable, 2)?;
+            let offset = u32::read_at(subtable, 4)? as usize;
+            subtables.push(subtable.get(offset..).ok_or(Error::InvalidOffset)?);
+        } else {
+            subtables.push(subtable);
+        }
+    }
+
+    // The mark filtering set follows the subtable offsets.
+    let mark_set = match flag & 0x10 {
+        0 => None,
+        _ => Some(u16::read_at(lookup, 6 + 2 * count)?),
+    };
+
+    Ok(Lookup { kind, flag, subtables, mark_set })
+}
+
+/// Write the lookup list.
+///
+/// The lookups come first, then the extension subtables of promoted lookups,
+/// then the subtables of the other lookups and finally those of the promoted
+/// lookups, which the extension subtables reach with 32-bit offsets. As long
+/// as the 16-bit offsets of a lookup overflow, the largest lookup up to it
+/// that isn't promoted yet is promoted to an extension lookup of the given
+/// type, which moves its subtables out of the way. If that doesn't help,
+/// writing fails with [`Error::OffsetOverflow`].
+fn write_lookups(lookups: &[(&Lookup, Vec<Vec<u8>>)], extension: u16) -> Result<Vec<u8>> {
+    let size = |i: usize| lookups[i].1.iter().map(Vec::len).sum::<usize>();
+    let mut promoted = vec![false; lookups.len()];
+    loop {
+        let placed = place_lookups(lookups, &promoted);
+        let reach = |i: usize| {
+            let [position, first, _] = placed[i];
+            let (_, subtables) = &lookups[i];
+            let last = match (promoted[i], subtables.last()) {
+                (_, None) => position,
+                (true, _) => first + 8 * (subtables.len() - 1),
+                (false, Some(last)) => first + size(i) - last.len(),
+            };
+            last - position
+        };
+        let Some(overflow) = (0..lookups.len()).find(|&i| reach(i) > 0xFFFF) else {
+            return write_placed_lookups(lookups, &promoted, &placed, extension);
+        };
+        let largest = (0..=overflow)
+            .filter(|&i| !promoted[i])
+            .max_by_key(|&i| size(i))
+            .ok_or(Error::OffsetOverflow)?;
+        promoted[largest] = true;
+    }
+}
+
+/// The positions of each lookup, of the first subtable its offsets point to
+/// and of its first actual subtable in a lookup list with the given lookups
+/// promoted to extension lookups.
+fn place_lookups(
+    lookups: &[(&Lookup, Vec<Vec<u8>>)],
+    promoted: &[bool],
+) -> Vec<[usize; 3]> {
+    let mut placed = vec![];
+    let mut at = 2 + 2 * lookups.len();
+    for (lookup, subtables) in lookups {
+        placed.push([at, 0, 0]);
+        at += 6 + 2 * subtables.len() + if lookup.mark_set.is_some() { 2 } else { 0 };
+    }
+    for (i, (_, subtables)) in lookups.iter().enumerate() {
+        if promoted[i] {
+            placed[i][1] = at;
+            at += 8 * subtables.len();
+        }
+    }
+    for pass in [false, true] {
+        for (i, (_, subtables)) in lookups.iter().enumerate() {
+            if promoted[i] == pass {
+                placed[i][2] = at;
+                if !pass {
+                    placed[i][1] = at;
+                }
+                at += subtables.iter().map(Vec::len).sum::<usize>();
+            }
+        }
+    }
+    placed
+}
+
+/// Write the lookup list with the positions from [`place_lookups`].
+fn write_placed_lookups(
+    lookups: &[(&Lookup, Vec<Vec<u8>>)],
+    promoted: &[bool],
+    placed: &[[usize; 3]],
+    extension: u16,
+) -> Result<Vec<u8>> {
+    let mut w = Writer::new();
+    w.write::<u16>(lookups.len() as u16);
+    for &[position, ..] in placed {
+        w.write::<u16>(to_offset(position)?);
+    }
+
+    for (i, (lookup, subtables)) in lookups.iter().enumerate() {
+        let [position, mut target, _] = placed[i];
+        w.write::<u16>(if promoted[i] { extension } else { lookup.kind });
+        w.write::<u16>(lookup.flag);
+        w.write::<u16>(subtables.len() as u16);
+        for subtable in subtables {
+            w.write::<u16>(to_offset(target - position)?);
+            target += if promoted[i] { 8 } else { subtable.len() };
+        }
+        if let Some(set) = lookup.mark_set {
+            w.write::<u16>(set);
+        }
+    }
+
+    for (i, (lookup, subtables)) in lookups.iter().enumerate() {
+        let [_, first, mut target] = placed[i];
+        if !promoted[i] {
+            continue;
+        }
+        for (j, subtable) in subtables.iter().enumerate() {
+            let offset = target - (first + 8 * j);
+            w.write::<u16>(1);
+            w.write::<u16>(lookup.kind);
+            w.write::<u32>(u32::try_from(offset).map_err(|_| Error::OffsetOverflow)?);
+            target += subtable.len();
+        }
+    }
+
+    for pass in [false, true] {
+        for (i, (_, subtables)) in lookups.iter().enumerate() {
+            if promoted[i] == pass {
+                subtables.iter().for_each(|subtable| w.give(subtable));
+            }
+        }
+    }
+
+    Ok(w.finish())
+}
+
+/// Subset the feature list, dropping features without lookups except for
+/// `size`, which only has parameters. Also returns the new index of each
+/// feature, if it is kept.
+fn subset_features(
+    list: &[u8],
+    lookups: LookupMap,
+) -> Result<(Vec<u8>, Vec<Option<u16>>)> {
+    let mut features = vec![];
+    let mut map = vec![];
+    for i in 0..usize::from(u16::read_at(list, 0)?) {
+        let record = 2 + 6 * i;
+        let tag: [u8; 4] = <[u8; 4]>::read_at(list, record)?;
+        let feature = offset(list, record + 4)?;
+
+        let mut indices = vec![];
+        for j in 0..usize::from(u16::read_at(feature, 2)?) {
+            indices.extend(lookups(u16::read_at(feature, 4 + 2 * j)?));
+        }
+        if indices.is_empty() && &tag != b"size" {
+            map.push(None);
+            continue;
+        }
+
+        let mut p = Packer::new();
+        match u16::read_at(feature, 0)? {
+            0 => p.null(),
+            _ => match feature_params(&tag, offset(feature, 0)?)? {
+                Some(params) => p.link(params.to_vec()),
+                None => p.null(),
+            },
+        }
+        p.write::<u16>(indices.len() as u16);
+        for index in indices {
+            p.write::<u16>(index);
+        }
+
+        map.push(Some(features.len() as u16));
+        features.push((tag, p.finish()?));
+    }
+
+    let mut p = Packer::new();
+    p.write::<u16>(features.len() as u16);
+    for (tag, feature) in features {
+        p.write(tag);
+        p.link(feature);
+    }
+    Ok((p.finish()?, map))
+}
+
+/// The parameters of a feature, if their layout is known.
+fn feature_params<'a>(tag: &[u8; 4], params: &'a [u8]) -> Result<Option<&'a [u8]>> {
+    let len = match tag {
+        b"size" => 10,
+        [b's', b's', ..] => 4,
+        [b'c', b'v', ..] => 14 + 3 * usize::from(u16::read_at(params, 12)?),
+        _ => return Ok(None),
+    };
+    params.get(..len).ok_or(Error::MissingData).map(Some)
+}
+
+/// The name IDs that the parameters of the features of a `GSUB` or `GPOS`
+/// table refer to: the subfamily name of `size`, the UI name of `ssXX` and
+/// the UI label, tooltip, sample text and parameter labels of `cvXX`. Zero
+/// stands for no name.
+pub(crate) fn feature_name_ids(table: &[u8]) -> Result<Vec<u16>> {
+    let list = offset(table, 6)?;
+    let mut ids = vec![];
+    for i in 0..usize::from(u16::read_at(list, 0)?) {
+        let record = 2 + 6 * i;
+        let tag: [u8; 4] = <[u8; 4]>::read_at(list, record)?;
+        let feature = offset(list, record + 4)?;
+        if u16::read_at(feature, 0)? == 0 {
+            continue;
+        }
+        let Some(params) = feature_params(&tag, offset(feature, 0)?)? else {
+            continue;
+        };
+        match &tag {
+            b"size" => ids.push(u16::read_at(params, 4)?),
+            [b's', b's', ..] => ids.push(u16::read_at(params, 2)?),
+            _ => {
+                for at in [2, 4, 6] {
+                    ids.push(u16::read_at(params, at)?);
+                }
+                let first = u16::read_at(params, 10)?;
+                let count = u16::read_at(params, 8)?;
+                ids.extend((0..count).map(|k| first.saturating_add(k)));
+            }
+        }
+    }
+    ids.retain(|&id| id != 0);
+    ids.sort_unstable();
+    ids.dedup();
+    Ok(ids)
+}
+
+/// A language system with its required feature and its features.
+type LangSys = (u16, Vec<u16>);
+
+/// Subset the script list to the kept features.
+fn subset_scripts(list: &[u8], features: &[Option<u16>]) -> Result<Vec<u8>> {
+    let feature = |index: u16| features.get(usize::from(index)).copied().flatten();
+    let lang_sys = |data: &[u8]| -> Result<LangSys> {
+        let required = feature(u16::read_at(data, 2)?).unwrap_or(0xFFFF);
+        let mut indices = vec![];
+        for i in 0..usize::from(u16::read_at(data, 4)?) {
+            indices.extend(feature(u16::read_at(data, 6 + 2 * i)?));
+        }
+        Ok((required, indices))
+    };
+
+    let mut scripts = vec![];
+    for i in 0..usize::from(u16::read_at(list, 0)?) {
+        let record = 2 + 6 * i;
+        let tag: [u8; 4] = <[u8; 4]>::read_at(list, record)?;
+        let script = offset(list, record + 4)?;
+
+        // An empty default language system is the same as none.
+        let empty = (0xFFFF, vec![]);
+        let default = optional(script, 0)?
+            .map(lang_sys)
+            .transpose()?
+            .filter(|default| *default != empty);
+
+        // Languages without their own language system use the default one.
+        let mut langs = vec![];
+        for j in 0..usize::from(u16::read_at(script, 2)?) {
+            let record = 4 + 6 * j;
+            let lang: [u8; 4] = <[u8; 4]>::read_at(script, record)?;
+            let sys = lang_sys(offset(script, record + 4)?)?;
+            if sys != *default.as_ref().unwrap_or(&empty) {
+                langs.push((lang, sys));
+            }
+        }
+
+        if default.is_none() && langs.is_empty() {
+            continue;
+        }
+
+        let mut p = Packer::new();
+        match &default {
+            Some(default) => p.link(write_lang_sys(default)),
+            None => p.null(),
+        }
+        p.write::<u16>(langs.len() as u16);
+        for (lang, sys) in &langs {
+            p.write(*lang);
+            p.link(write_lang_sys(sys));
+        }
+        scripts.push((tag, p.finish()?));
+    }
+
+    let mut p = Packer::new();
+    p.write::<u16>(scripts.len() as u16);
+    for (tag, script) in scripts {
+        p.write(tag);
+        p.link(script);
+    }
+    p.finish()
+}
+
+/// Write a language system table.
+fn write_lang_sys((required, features): &LangSys) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.write::<u16>(0);
+    w.write::<u16>(*required);
+    w.write::<u16>(features.len() as u16);
+    for &index in features {
+        w.write::<u16>(index);
+    }
+    w.finish()
+}
+
+/// A feature of a font's `GSUB` or `GPOS` table, as listed by
+/// [`layout_features`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LayoutFeature {
+    /// The table that holds the feature, `GSUB` or `GPOS`.
+    pub table: Tag,
+    /// The feature's tag.
+    pub tag: Tag,
+    /// The number of distinct lookups that the feature applies.
+    pub lookups: usize,
+    /// The approximate size of these lookups in bytes.
+    pub size: usize,
+}
+
+/// A language system of a font's `GSUB` or `GPOS` table, as listed by
+/// [`layout_scripts`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LayoutScript {
+    /// The table that holds the language system, `GSUB` or `GPOS`.
+    pub table: Tag,
+    /// The script's tag.
+    pub script: Tag,
+    /// The language's tag, or `None` for the script's default language
+    /// system.
+    pub language: Option<Tag>,
+    /// The tags of the features the language system enables, starting with
+    /// its required feature if it has one.
+    pub features: Vec<Tag>,
+}
+
+/// List the features of a font's `GSUB` and `GPOS` tables with the number
+/// and approximate size of their lookups, those of `GSUB` first and each
+/// table's sorted by tag.
+///
+/// This helps to decide which features are worth keeping before subsetting.
+/// Features with the same tag in several language systems are listed once.
+/// A lookup's size counts each of its subtables up to where the next
+/// subtable or lookup starts, so it includes the coverage and class
+/// definitions that usually follow the subtable, and lookups that several
+/// features share count for each of them.
+pub fn layout_features(data: &[u8], index: u32) -> Result<Vec<LayoutFeature>> {
+    let face = parse(data, index)?;
+    let mut result = vec![];
+    for tag in [Tag::GSUB, Tag::GPOS] {
+        let Some(table) = face.table(tag) else { continue };
+        let sizes = lookup_sizes(table, if tag == Tag::GSUB { 7 } else { 9 })?;
+        let list = offset(table, 6)?;
+        let mut features: BTreeMap<Tag, BTreeSet<u16>> = BTreeMap::new();
+        for i in 0..usize::from(u16::read_at(list, 0)?) {
+            let feature = offset(list, 2 + 6 * i + 4)?;
+            let lookups = features.entry(Tag::read_at(list, 2 + 6 * i)?).or_default();
+            for j in 0..usize::from(u16::read_at(feature, 2)?) {
+                lookups.insert(u16::read_at(feature, 4 + 2 * j)?);
+            }
+        }
+        for (feature, lookups) in features {
+            let size = lookups
+                .iter()
+                .map(|&i| sizes.get(usize::from(i)).copied().ok_or(Error::InvalidData))
+                .sum::<Result<usize>>()?;
+            result.push(LayoutFeature {
+                table: tag,
+                tag: feature,
+                lookups: lookups.len(),
+                size,
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// List the language systems of a font's `GSUB` and `GPOS` tables with their
+/// features, in the order of the tables' script lists.
+pub fn layout_scripts(data: &[u8], index: u32) -> Result<Vec<LayoutScript>> {
+    let face = parse(data, index)?;
+    let mut result = vec![];
+    for tag in [Tag::GSUB, Tag::GPOS] {
+        let Some(table) = face.table(tag) else { continue };
+        let features = offset(table, 6)?;
+        let feature = |index: u16| Tag::read_at(features, 2 + 6 * usize::from(index));
+        let lang_sys = |data: &[u8]| -> Result<Vec<Tag>> {
+            let mut tags = vec![];
+            match u16::read_at(data, 2)? {
+                0xFFFF => {}
+                required => tags.push(feature(required)?),
+            }
+            for i in 0..usize::from(u16::read_at(data, 4)?) {
+                tags.push(feature(u16::read_at(data, 6 + 2 * i)?)?);
+            }
+            Ok(tags)
+        };
+
+        let list = offset(table, 4)?;
+        for i in 0..usize::from(u16::read_at(list, 0)?) {
+            let script_tag = Tag::read_at(list, 2 + 6 * i)?;
+            let script = offset(list, 2 + 6 * i + 4)?;
+            if let Some(default) = optional(script, 0)? {
+                result.push(LayoutScript {
+                    table: tag,
+                    script: script_tag,
+                    language: None,
+                    features: lang_sys(default)?,
+                });
+            }
+            for j in 0..usize::from(u16::read_at(script, 2)?) {
+                result.push(LayoutScript {
+                    table: tag,
+                    script: script_tag,
+                    language: Some(Tag::read_at(script, 4 + 6 * j)?),
+                    features: lang_sys(offset(script, 4 + 6 * j + 4)?)?,
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The approximate size of each lookup of a `GSUB` or `GPOS` table, with
+/// extension lookups of the given type resolved.
+fn lookup_sizes(table: &[u8], extension: u16) -> Result<Vec<usize>> {
+    let position = |data: &[u8]| data.as_ptr() as usize - table.as_ptr() as usize;
+    let list = offset(table, 8)?;
+
+    // Where the parts of the table start, which bound the preceding part.
+    let mut starts = BTreeSet::from([table.len()]);
+    for at in [4, 6, 8] {
+        starts.insert(position(offset(table, at)?));
+    }
+    if u16::read_at(table, 2)? >= 1 {
+        if let Ok(at @ 1..) = u32::read_at(table, 10) {
+            starts.insert(at as usize);
+        }
+    }
+
+    let mut lookups = vec![];
+    for i in 0..u16::read_at(list, 0)? {
+        let lookup = offset(list, 2 + 2 * usize::from(i))?;
+        // Extension subtables count along with the subtables they point to.
+        let mut subtables = (0..usize::from(u16::read_at(lookup, 4)?))
+            .map(|j| offset(lookup, 6 + 2 * j).map(position))
+            .collect::<Result<Vec<_>>>()?;
+        let resolved = read_lookup(list, i, extension)?.subtables;
+        subtables.extend(resolved.iter().map(|subtable| position(subtable)));
+        subtables.sort_unstable();
+        subtables.dedup();
+        starts.insert(position(lookup));
+        starts.extend(&subtables);
+        lookups.push((position(lookup), subtables));
+    }
+
+    let span =
+        |start: usize| starts.range(start + 1..).next().map_or(0, |&end| end - start);
+    Ok(lookups
+        .iter()
+        .map(|(lookup, subtables)| {
+            span(*lookup) + subtables.iter().map(|&start| span(start)).sum::<usize>()
+        })
+        .collect())
+}
+
+/// Subset a (chained) sequence context subtable, i.e. a `GSUB` lookup of type
+/// 5 or 6 or a `GPOS` lookup of type 7 or 8.
+///
+/// Rules that match glyphs or classes which aren't retained are removed.
+/// Rules whose nested lookups were all dropped are kept, since they still
+/// stop later subtables from matching.
+pub(crate) fn subset_context(
+    chained: bool,
+    subtable: &[u8],
+    glyphs: &Retained,
+    lookups: LookupMap,
+) -> Result<Option<Vec<u8>>> {
+    let mut p = Packer::new();
+    match u16::read_at(subtable, 0)? {
+        1 => {
+            let new_id = |glyph| glyphs.get(&glyph).copied();
+            let mut sets = vec![];
+            for (glyph, i) in coverage(offset(subtable, 2)?)? {
+                let Some(new) = new_id(glyph) else { continue };
+                if u16::read_at(subtable, 6 + 2 * i)? == 0 {
+                    continue;
+                }
+                let set = offset(subtable, 6 + 2 * i)?;
+                if let Some(set) = subset_rule_set(chained, set, [&new_id; 3], lookups)? {
+                    sets.push((new, set));
+                }
+            }
+            if sets.is_empty() {
+                return Ok(None);
+            }
+            sets.sort_by_key(|&(glyph, _)| glyph);
+
+            let covered: Vec<u16> = sets.iter().map(|&(glyph, _)| glyph).collect();
+            p.write::<u16>(1);
+            p.link(write_coverage(&covered));
+            p.write::<u16>(sets.len() as u16);
+            for (_, set) in sets {
+                p.link(set);
+            }
+        }
+        2 => {
+            let covered = retained_coverage(offset(subtable, 2)?, glyphs)?;
+            if covered.is_empty() {
+                return Ok(None);
+            }
+
+            // Backtrack, input and lookahead class definitions.
+            let defs = if chained { 3 } else { 1 };
+            let mut class_defs = vec![];
+            let mut present: Vec<HashSet<u16>> = vec![];
+            for k in 0..defs {
+                let classes = retained_classes(offset(subtable, 4 + 2 * k)?, glyphs)?;
+                // Class 0 holds all glyphs not otherwise classified.
+                present
+                    .push(classes.iter().map(|&(_, class)| class).chain([0]).collect());
+                class_defs.push(write_class_def(&classes));
+            }
+            let in_class = |k: usize| {
+                let present = &present[if chained { k } else { 0 }];
+                move |class| present.contains(&class).then_some(class)
+            };
+            let (backtrack, input, lookahead) = (in_class(0), in_class(1), in_class(2));
+            let present: Present = [&backtrack, &input, &lookahead];
+
+            let sets_at = 4 + 2 * defs;
+            let mut sets = vec![];
+            for class in 0..u16::read_at(subtable, sets_at)? {
+                let at = sets_at + 2 + 2 * usize::from(class);
+                let set = match u16::read_at(subtable, at)? {
+                    0 => None,
+                    _ if input(class).is_none() => None,
+                    _ => {
+                        subset_rule_set(chained, offset(subtable, at)?, present, lookups)?
+                    }
+                };
+                sets.push(set);
+            }
+            while sets.last().is_some_and(Option::is_none) {
+                sets.pop();
+            }
+            if sets.is_empty() {
+                return Ok(None);
+            }
+
+            p.write::<u16>(2);
+            p.link(write_coverage(&covered));
+            for class_def in class_defs {
+                p.link(class_def);
+            }
+            p.write::<u16>(sets.len() as u16);
+            for set in sets {
+                match set {
+                    Some(set) => p.link(set),
+                    None => p.null(),
+                }
+            }
+        }
+        3 => {
+            let mut r = Reader::new(subtable);
+            r.read::<u16>()?;
+            p.write::<u16>(3);
+            if chained {
+                for _ in 0..3 {
+                    let Some(coverages) = subset_coverages(subtable, &mut r, glyphs)?
+                    else {
+                        return Ok(None);
+                    };
+                    p.write::<u16>(coverages.len() as u16);
+                    for coverage in coverages {
+                        p.link(coverage);
+                    }
+                }
+                let records = read_records(&mut r, lookups)?;
+                p.write::<u16>(records.len() as u16);
+                write_records(&mut p, &records);
+            } else {
+                let count = r.read::<u16>()?;
+                let records = r.read::<u16>()?;
+                let mut coverages = vec![];
+                for _ in 0..count {
+                    let covered =
+                        retained_coverage(offset_from(subtable, &mut r)?, glyphs)?;
+                    if covered.is_empty() {
+                        return Ok(None);
+                    }
+                    coverages.push(write_coverage(&covered));
+                }
+                let records = read_records_counted(&mut r, records, lookups)?;
+                p.write::<u16>(count);
+                p.write::<u16>(records.len() as u16);
+                for coverage in coverages {
+                    p.link(coverage);
+                }
+                write_records(&mut p, &records);
+            }
+        }
+        _ => return Err(Error::InvalidData),
+    }
+    p.finish().map(Some)
+}
+
+/// Maps a glyph or class of a rule to its value in the output, or `None` if it
+/// isn't retained.
+type Present<'a> = [&'a dyn Fn(u16) -> Option<u16>; 3];
+
+/// A rule of a (chained) sequence context subtable. The input sequence starts
+/// after the first glyph or class, which the coverage or rule set determines.
+struct Rule {
+    backtrack: Vec<u16>,
+    input: Vec<u16>,
+    lookahead: Vec<u16>,
+    records: Vec<(u16, u16)>,
+}
+
+/// Subset a rule set to the rules that only match present glyphs or classes,
+/// given for the backtrack, input and lookahead sequences, and map them to
+/// their values in the output.
+fn subset_rule_set(
+    chained: bool,
+    set: &[u8],
+    present: Present,
+    lookups: LookupMap,
+) -> Result<Option<Vec<u8>>> {
+    let mut rules = vec![];
+    for i in 0..usize::from(u16::read_at(set, 0)?) {
+        let mut rule = read_rule(chained, offset(set, 2 + 2 * i)?, lookups)?;
+        let matches = [&mut rule.backtrack, &mut rule.input, &mut rule.lookahead]
+            .into_iter()
+            .zip(present)
+            .all(|(sequence, present)| {
+                sequence.iter_mut().all(|v| present(*v).map(|new| *v = new).is_some())
+            });
+        if matches {
+            rules.push(write_rule(chained, &rule));
+        }
+    }
+
+    if rules.is_empty() {
+        return Ok(None);
+    }
+
+    let mut p = Packer::new();
+    p.write::<u16>(rules.len() as u16);
+    for rule in rules {
+        p.link(rule);
+    }
+    p.finish().map(Some)
+}
+
+/// Read a rule, keeping only the lookup records of kept lookups.
+fn read_rule(chained: bool, data: &[u8], lookups: LookupMap) -> Result<Rule> {
+    let mut r = Reader::new(data);
+    let rule = if chained {
+        let backtrack = read_array(&mut r)?;
+        let count = r.read::<u16>()?;
+        let input = read_values(&mut r, count.saturating_sub(1))?;
+        let lookahead = read_array(&mut r)?;
+        let records = read_records(&mut r, lookups)?;
+        Rule { backtrack, input, lookahead, records }
+    } else {
+        let count = r.read::<u16>()?;
+        let records = r.read::<u16>()?;
+        let input = read_values(&mut r, count.saturating_sub(1))?;
+        let records = read_records_counted(&mut r, records, lookups)?;
+        Rule {
+            backtrack: vec![],
+            input,
+            lookahead: vec![],
+            records,
+        }
+    };
+    Ok(rule)
+}
+
+/// Write a rule.
+fn write_rule(chained: bool, rule: &Rule) -> Vec<u8> {
+    let mut p = Packer::new();
+    if chained {
+        p.write::<u16>(rule.backtrack.len() as u16);
+        rule.backtrack.iter().for_each(|&v| p.write::<u16>(v));
+        p.write::<u16>(rule.input.len() as u16 + 1);
+        rule.input.iter().for_each(|&v| p.write::<u16>(v));
+        p.write::<u16>(rule.lookahead.len() as u16);
+        rule.lookahead.iter().for_each(|&v| p.write::<u16>(v));
+        p.write::<u16>(rule.records.len() as u16);
+    } else {
+        p.write::<u16>(rule.input.len() as u16 + 1);
+        p.write::<u16>(rule.records.len() as u16);
+        rule.input.iter().for_each(|&v| p.write::<u16>(v));
+    }
+    write_records(&mut p, &rule.records);
+    p.head.finish()
+}
+
+/// Read a `u16` count followed by as many `u16` values.
+fn read_array(r: &mut Reader) -> Result<Vec<u16>> {
+    let count = r.read::<u16>()?;
+    read_values(r, count)
+}
+
+/// Read the given number of `u16` values.
+fn read_values(r: &mut Reader, count: u16) -> Result<Vec<u16>> {
+    (0..count).map(|_| r.read::<u16>()).collect()
+}
+
+/// Read a `u16` count followed by as many sequence lookup records.
+fn read_records(r: &mut Reader, lookups: LookupMap) -> Result<Vec<(u16, u16)>> {
+    let count = r.read::<u16>()?;
+    read_records_counted(r, count, lookups)
+}
+
+/// Read sequence lookup records, keeping those of kept lookups.
+fn read_records_counted(
+    r: &mut Reader,
+    count: u16,
+    lookups: LookupMap,
+) -> Result<Vec<(u16, u16)>> {
+    let mut records = vec![];
+    for _ in 0..count {
+        let index = r.read::<u16>()?;
+        if let Some(lookup) = lookups(r.read::<u16>()?) {
+            records.push((index, lookup));
+        }
+    }
+    Ok(records)
+}
+
+/// Write sequence lookup records without their count.
+fn write_records(p: &mut Packer, records: &[(u16, u16)]) {
+    for &(index, lookup) in records {
+        p.write::<u16>(index);
+        p.write::<u16>(lookup);
+    }
+}
+
+/// Read a `u16` count followed by as many offsets to coverage tables and
+/// subset them. Returns `None` if one of them has no retained glyphs left.
+pub(crate) fn subset_coverages(
+    subtable: &[u8],
+    r: &mut Reader,
+    glyphs: &Retained,
+) -> Result<Option<Vec<Vec<u8>>>> {
+    let mut coverages = vec![];
+    let mut empty = false;
+    for _ in 0..r.read::<u16>()? {
+        let covered = retained_coverage(offset_from(subtable, r)?, glyphs)?;
+        empty |= covered.is_empty();
+        coverages.push(write_coverage(&covered));
+    }
+    Ok((!empty).then_some(coverages))
+}
+
+/// Read a 16-bit offset and follow it from the start of the subtable.
+fn offset_from<'a>(subtable: &'a [u8], r: &mut Reader) -> Result<&'a [u8]> {
+    let offset = usize::from(r.read::<u16>()?);
+    subtable.get(offset..).ok_or(Error::InvalidOffset)
+}
+
+/// Serializes a table whose fixed-size part is followed by the subtables it
+/// references with 16-bit offsets. Identical subtables are stored once.
+///
+/// The subtables are placed in the order they were linked, unless that makes
+/// an offset overflow. Then they are placed from the smallest to the largest,
+/// which keeps the offset to the last one as small as possible.
+pub(crate) struct Packer {
+    head: Writer,
+    links: Vec<(usize, Vec<u8>)>,
+}
+
+impl Packer {
+    /// Create a new packer.
+    pub(crate) fn new() -> Self {
+        Self { head: Writer::new(), links: vec![] }
+    }
+
+    /// Write a value to the fixed-size part.
+    pub(crate) fn write<'a, T: Structure<'a>>(&mut self, data: T) {
+        self.head.write(data);
+    }
+
+    /// Write an offset to the given subtable.
+    pub(crate) fn link(&mut self, subtable: Vec<u8>) {
+        self.links.push((self.head.len(), subtable));
+        self.head.write::<u16>(0);
+    }
+
+    /// Write a null offset.
+    pub(crate) fn null(&mut self) {
+        self.head.write::<u16>(0);
+    }
+
+    /// Append the subtables and fill in the offsets to them.
+    pub(crate) fn finish(self) -> Result<Vec<u8>> {
+        let head = self.head.finish();
+        Self::place(&self.links, head.clone(), false)
+            .or_else(|_| Self::place(&self.links, head, true))
+    }
+
+    /// Append the linked subtables, optionally sorted by size, to the
+    /// fixed-size part.
+    fn place(
+        links: &[(usize, Vec<u8>)],
+        mut data: Vec<u8>,
+        by_size: bool,
+    ) -> Result<Vec<u8>> {
+        let mut links: Vec<_> = links.iter().collect();
+        if by_size {
+            links.sort_by_key(|(_, subtable)| subtable.len());
+        }
+        let mut placed: HashMap<&[u8], usize> = HashMap::new();
+        for (at, subtable) in links {
+            let offset = *placed.entry(subtable).or_insert_with(|| {
+                data.extend_from_slice(subtable);
+                data.len() - subtable.len()
+            });
+            data[*at..*at + 2].copy_from_slice(&to_offset(offset)?.to_be_bytes());
+        }
+        Ok(data)
+    }
+}
+
+/// Convert a position into a 16-bit offset.
+fn to_offset(position: usize) -> Result<u16> {
+    u16::try_from(position).map_err(|_| Error::OffsetOverflow)
+}
+
+/// Follow a 16-bit offset stored at the given position.
+pub(crate) fn offset(data: &[u8], at: usize) -> Result<&[u8]> {
+    let offset = usize::from(u16::read_at(data, at)?);
+    data.get(offset..).ok_or(Error::InvalidOffset)
+}
+
+/// Follow a 16-bit offset stored at the given position, unless it is null.
+fn optional(data: &[u8], at: usize) -> Result<Option<&[u8]>> {
+    match u16::read_at(data, at)? {
+        0 => Ok(None),
+        _ => offset(data, at).map(Some),
+    }
+}
+
+/// Read a `u16` count followed by as many glyph IDs.
+pub(crate) fn glyph_array(data: &[u8]) -> Result<Vec<u16>> {
+    (0..usize::from(u16::read_at(data, 0)?))
+        .map(|i| u16::read_at(data, 2 + 2 * i))
+        .collect()
+}
+
+/// Write a `u16` count followed by the glyph IDs.
+pub(crate) fn write_glyph_array(glyphs: &[u16]) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.write::<u16>(glyphs.len() as u16);
+    for &glyph in glyphs {
+        w.write::<u16>(glyph);
+    }
+    w.finish()
+}
+
+/// The glyphs of a coverage table with their coverage index.
+pub(crate) fn coverage(data: &[u8]) -> Result<Vec<(u16, usize)>> {
+    match u16::read_at(data, 0)? {
+        1 => Ok(glyph_array(data.get(2..).ok_or(Error::MissingData)?)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, glyph)| (glyph, i))
+            .collect()),
+        2 => {
+            let mut glyphs = vec![];
+            for i in 0..usize::from(u16::read_at(data, 2)?) {
+                let start = u16::read_at(data, 4 + 6 * i)?;
+                let end = u16::read_at(data, 6 + 6 * i)?;
+                let index = usize::from(u16::read_at(data, 8 + 6 * i)?);
+                for glyph in start..=end {
+                    glyphs.push((glyph, index + usize::from(glyph - start)));
+                }
+            }
+            Ok(glyphs)
+        }
+        _ => Err(Error::InvalidData),
+    }
+}
+
+/// The output IDs of the retained glyphs of a coverage table, sorted.
+pub(crate) fn retained_coverage(data: &[u8], glyphs: &Retained) -> Result<Vec<u16>> {
+    Ok(retained(data, glyphs)?.into_iter().map(|(glyph, _)| glyph).collect())
+}
+
+/// The output IDs of the retained glyphs of a coverage table with their
+/// coverage index, sorted by the output ID.
+pub(crate) fn retained(data: &[u8], glyphs: &Retained) -> Result<Vec<(u16, usize)>> {
+    let mut covered: Vec<(u16, usize)> = coverage(data)?
+        .into_iter()
+        .filter_map(|(glyph, i)| Some((*glyphs.get(&glyph)?, i)))
+        .collect();
+    covered.sort_unstable();
+    covered.dedup_by_key(|&mut (glyph, _)| glyph);
+    Ok(covered)
+}
+
+/// Extract a device or variation index table.
+pub(crate) fn device(data: &[u8]) -> Result<Vec<u8>> {
+    let start = u16::read_at(data, 0)?;
+    let end = u16::read_at(data, 2)?;
+    let len = match u16::read_at(data, 4)? {
+        // Deltas of 2, 4 or 8 bits, packed into 16-bit words.
+        format @ 1..=3 => {
+            let count = usize::from(end.saturating_sub(start)) + 1;
+            6 + 2 * (count * (1 << format)).div_ceil(16)
+        }
+        _ => 6,
+    };
+    data.get(..len).map(<[u8]>::to_vec).ok_or(Error::MissingData)
+}
+
+/// Write a coverage table for sorted glyphs in the smaller format.
+pub(crate) fn write_coverage(glyphs: &[u16]) -> Vec<u8> {
+    let ranges = ranges(glyphs.iter().map(|&glyph| (glyph, 0)));
+    let mut w = Writer::new();
+    if 6 * ranges.len() < 2 * glyphs.len() {
+        w.write::<u16>(2);
+        w.write::<u16>(ranges.len() as u16);
+        let mut index = 0;
+        for (start, end, _) in ranges {
+            w.write::<u16>(start);
+            w.write::<u16>(end);
+            w.write::<u16>(index);
+            index += end - start + 1;
+        }
+    } else {
+        w.write::<u16>(1);
+        w.give(&write_glyph_array(glyphs));
+    }
+    w.finish()
+}
+
+/// Glyphs with their class.
+pub(crate) type Classes = Vec<(u16, u16)>;
+
+/// The glyphs of a class definition table that are in a class other than 0,
+/// with their class.
+pub(crate) fn class_def(data: &[u8]) -> Result<Classes> {
+    let mut classes = vec![];
+    match u16::read_at(data, 0)? {
+        1 => {
+            let start = u16::read_at(data, 2)?;
+            for i in 0..u16::read_at(data, 4)? {
+                let class = u16::read_at(data, 6 + 2 * usize::from(i))?;
+                classes.push((start.checked_add(i).ok_or(Error::InvalidData)?, class));
+            }
+        }
+        2 => {
+            for i in 0..usize::from(u16::read_at(data, 2)?) {
+                let start = u16::read_at(data, 4 + 6 * i)?;
+                let end = u16::read_at(data, 6 + 6 * i)?;
+                let class = u16::read_at(data, 8 + 6 * i)?;
+                classes.extend((start..=end).map(|glyph| (glyph, class)));
+            }
+        }
+        _ => return Err(Error::InvalidData),
+    }
+    classes.retain(|&(_, class)| class != 0);
+    Ok(classes)
+}
+
+/// The output IDs of the retained glyphs of a class definition table that are
+/// in a class other than 0, with their class, sorted by the output ID.
+pub(crate) fn retained_classes(data: &[u8], glyphs: &Retained) -> Result<Classes> {
+    let mut classes: Classes = class_def(data)?
+        .into_iter()
+        .filter_map(|(glyph, class)| Some((*glyphs.get(&glyph)?, class)))
+        .collect();
+    classes.sort_unstable();
+    classes.dedup_by_key(|&mut (glyph, _)| glyph);
+    Ok(classes)
+}
+
+/// Write a class definition table for glyphs sorted by ID with their class
+/// in the smaller format.
+pub(crate) fn write_class_def(classes: &[(u16, u16)]) -> Vec<u8> {
+    let ranges = ranges(classes.iter().copied());
+    let mut w = Writer::new();
+    match (classes.first(), classes.last()) {
+        (Some(&(first, _)), Some(&(last, _)))
+            if 4 + usize::from(last - first) <= 2 + 3 * ranges.len() =>
+        {
+            w.write::<u16>(1);
+            w.write::<u16>(first);
+            w.write::<u16>(last - first + 1);
+            let mut classes = classes.iter().peekable();
+            for glyph in first..=last {
+                let class = classes.next_if(|&&(g, _)| g == glyph).map_or(0, |&(_, c)| c);
+                w.write::<u16>(class);
+            }
+        }
+        _ => {
+            w.write::<u16>(2);
+            w.write::<u16>(ranges.len() as u16);
+            for (start, end, class) in ranges {
+                w.write::<u16>(start);
+                w.write::<u16>(end);
+                w.write::<u16>(class);
+            }
+        }
+    }
+    w.finish()
+}
+
+/// Group glyphs sorted by ID with a value into ranges of consecutive glyphs
+/// with the same value.
+fn ranges(glyphs: impl Iterator<Item = (u16, u16)>) -> Vec<(u16, u16, u16)> {
+    let mut ranges: Vec<(u16, u16, u16)> = vec![];
+    for (glyph, value) in glyphs {
+        match ranges.last_mut() {
+            Some((_, end, v)) if end.checked_add(1) == Some(glyph) && *v == value => {
+                *end = glyph;
+            }
+            _ => ranges.push((glyph, glyph, value)),
+        }
+    }
+    ranges
+}
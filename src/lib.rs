@@ -1,5 +1,5 @@
 /*!
-Reduces the size and coverage of OpenType fonts with TrueType or CFF outlines.
+Reduces the size and coverage of OpenType fonts with TrueType, CFF or CFF2 outlines.
 
 # Example
 In the example below, we remove all glyphs except the ones with IDs 68, 69, 70.
@@ -29,7 +29,9 @@ means that you don't have to worry about changed glyphs IDs. However, it also
 means that the resulting font won't always be as small as possible. To somewhat
 remedy this, this crate sometimes at least zeroes out unused data that it cannot
 fully remove. This helps if the font gets compressed, for example when embedding
-it in a PDF file.
+it in a PDF file. If a small font matters more than stable glyph IDs,
+[`Profile::remap_glyphs`] renumbers the retained glyphs and removes the others
+entirely.
 
 In the above example, the original font was 375 KB (188 KB zipped) while the
 resulting font is 36 KB (5 KB zipped).
@@ -38,35 +40,218 @@ resulting font is 36 KB (5 KB zipped).
 #![deny(unsafe_code)]
 #![deny(missing_docs)]
 
+mod bitmap;
+mod cache;
 mod cff;
 mod cmap;
+mod collection;
+mod colr;
+mod dump;
+mod gdef;
 mod glyf;
+mod gpos;
+mod gsub;
+mod gvar;
+mod gzip;
 mod head;
 mod hmtx;
+mod hvar;
+mod instance;
+mod integrity;
+mod isolate;
+mod json;
+mod layout;
+mod maxp;
+mod morx;
+mod name;
+mod obfuscate;
+mod os2;
+mod plan;
 mod post;
+mod provenance;
+mod provider;
+mod pua;
+mod remap;
+mod report;
+mod result;
+mod space;
+mod stamp;
 mod stream;
+mod svg;
+mod variation;
+mod vmtx;
+mod warning;
+#[cfg(feature = "woff2")]
+mod woff;
+mod woff1;
 
 use std::borrow::Cow;
 use std::collections::HashSet;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
 
+pub use crate::cache::{CacheStats, SubsetCache};
+pub use crate::cmap::ReverseCmap;
+pub use crate::collection::{
+    build_collection, face_count, replace_faces, subset_collection, subset_faces,
+    subset_in_collection,
+};
+pub use crate::dump::debug_dump;
+pub use crate::glyf::closure_by_composite;
+pub use crate::gsub::{alternates, closure_by_feature, variant_glyphs, Alternates};
+pub use crate::hmtx::MetricsOverride;
+pub use crate::instance::{
+    named_instances, variation_axes, Instancer, NamedInstance, VariationAxis,
+};
+pub use crate::integrity::Integrity;
+pub use crate::isolate::isolate_glyph;
+pub use crate::layout::{layout_features, layout_scripts, LayoutFeature, LayoutScript};
+pub use crate::obfuscate::{obfuscation_map, Obfuscation};
+pub use crate::plan::{Plan, TableAction};
+pub use crate::provenance::{
+    subset_with_provenance, Origin, Provenance, TableProvenance,
+};
+pub use crate::provider::{subset_from_provider, TableProvider};
+pub use crate::remap::{subset_with_glyph_map, GlyphMap, GlyphOrder};
+pub use crate::report::{subset_with_report, SubsetReport, TableSize};
+pub use crate::result::{subset_to_result, SubsetResult};
+pub use crate::stamp::{read_stamp, SubsetVersion};
 use crate::stream::{Reader, Structure, Writer};
+pub use crate::warning::{subset_with_warnings, Severity, Warning};
+#[cfg(any(feature = "brotli", feature = "woff-convert"))]
+pub use crate::woff::ttf_to_woff2;
+#[cfg(feature = "brotli")]
+pub use crate::woff::RustBrotli;
+#[cfg(feature = "woff2")]
+pub use crate::woff::{
+    subset_to_woff2, ttf_to_woff2_with, ttf_to_woff2_with_blocks, woff2_blocks,
+    woff2_to_ttf, woff2_to_ttf_with_limits, Compressor, Woff2Limits,
+};
+pub use crate::woff1::{ttf_to_woff, woff_blocks, woff_to_ttf, WoffBlocks};
 
 /// Defines which things to keep in the font.
 ///
 /// #### Possible Future Work
-/// - A setter for variation coordinates which would make the subsetter create a
-///   static instance of a variable font.
-/// - A profile which keeps and subsets bitmap, color and SVG tables.
-/// - A profile which takes a char set instead of a glyph set and subsets the
-///   layout tables.
+/// - Instancing of `CFF2` outlines and `MVAR` metrics with an [`Instancer`],
+///   and moving the defaults of values in item variation stores.
+/// - A profile which takes a char set instead of a glyph set.
 pub struct Profile<'a> {
     glyphs: &'a [u16],
     /// Whether or not to map each glyph to a codepoint in Unicode PUAs.
     map_glyphs: bool,
+    /// Whether to record the subsetter version in the output.
+    version_stamp: bool,
+    /// Overridden horizontal metrics of glyphs.
+    metrics: Vec<(u16, MetricsOverride)>,
+    /// Whether to make sure that U+0020 is mapped.
+    ensure_space: bool,
+    /// The advance of a synthesized space glyph.
+    space_advance: Option<u16>,
+    /// How to obfuscate the character mapping.
+    obfuscation: Option<Obfuscation>,
+    /// Whether to keep only the tables needed to render glyphs.
+    minimal: bool,
+    /// Whether to keep the `OS/2`, `name` and `post` tables in minimal fonts.
+    minimal_names: bool,
+    /// Whether to drop the name records that fonts don't need to work.
+    prune_names: bool,
+    /// The language IDs of the name records to keep, or all if empty.
+    name_languages: Vec<u16>,
+    /// A suffix to append to the family names.
+    family_suffix: Option<&'a str>,
+    /// Whether to drop the glyph names of the `post` table.
+    strip_glyph_names: bool,
+    /// How to pad the tables of the output.
+    padding: Padding,
+    /// Whether to remove TrueType hinting.
+    strip_hinting: bool,
+    /// Whether to fail on malformed data instead of repairing it.
+    strict: bool,
+    /// Whether to keep the glyphs produced by the `ccmp` feature.
+    ccmp_closure: bool,
+    /// Further features whose produced glyphs are kept.
+    layout_closure: Vec<Tag>,
+    /// Features whose closure only follows the requested glyphs.
+    strict_closure: Vec<Tag>,
+    /// Whether to keep or synthesize vertical metrics.
+    vertical: bool,
+    /// The advance height of synthesized vertical metrics.
+    vertical_advance: Option<u16>,
+    /// Whether to keep the glyph substitution table.
+    keep_gsub: bool,
+    /// Whether to keep the glyph positioning table.
+    keep_gpos: bool,
+    /// What to do with layout lookups the subsetter doesn't support.
+    lookup_policy: LookupPolicy,
+    /// Whether to keep the Apple Advanced Typography tables.
+    keep_aat: bool,
+    /// Whether to convert basic `morx` substitutions to a `GSUB` table.
+    convert_morx: bool,
+    /// Whether to keep the Graphite tables.
+    keep_graphite: bool,
+    /// Whether to keep the variation tables of variable fonts.
+    keep_variations: bool,
+    /// Whether to keep the color tables.
+    keep_color: bool,
+    /// Whether to keep the embedded bitmap tables.
+    keep_bitmaps: bool,
+    /// Tables to drop in addition to those the profile drops.
+    dropped: Vec<Tag>,
+    /// Private tables that are safe to copy unmodified.
+    passthrough: Vec<Tag>,
+    /// Whether to renumber the retained glyphs.
+    remap_glyphs: bool,
+    /// The glyphs that get the first new IDs when renumbering, in order.
+    glyph_order: &'a [u16],
+    /// Orders the glyphs after those in `glyph_order` when renumbering.
+    glyph_order_by: Option<&'a dyn GlyphOrder>,
+    /// How to assign PUA codepoints to glyphs.
+    pua_key: PuaKey,
 }
 
 impl<'a> Profile<'a> {
+    fn new(glyphs: &'a [u16], map_glyphs: bool) -> Self {
+        Self {
+            glyphs,
+            map_glyphs,
+            version_stamp: false,
+            metrics: vec![],
+            ensure_space: false,
+            space_advance: None,
+            obfuscation: None,
+            minimal: false,
+            minimal_names: false,
+            prune_names: false,
+            name_languages: vec![],
+            family_suffix: None,
+            strip_glyph_names: false,
+            padding: Padding::Aligned,
+            strip_hinting: false,
+            strict: false,
+            ccmp_closure: map_glyphs,
+            layout_closure: vec![],
+            strict_closure: vec![],
+            vertical: false,
+            vertical_advance: None,
+            keep_gsub: false,
+            keep_gpos: false,
+            lookup_policy: LookupPolicy::Fail,
+            keep_aat: false,
+            convert_morx: false,
+            keep_graphite: false,
+            keep_variations: false,
+            keep_color: false,
+            keep_bitmaps: false,
+            dropped: vec![],
+            passthrough: vec![],
+            remap_glyphs: false,
+            glyph_order: &[],
+            glyph_order_by: None,
+            pua_key: PuaKey::GlyphId,
+        }
+    }
+
     /// Reduces the font to the subset needed for PDF embedding.
     ///
     /// Keeps only the basic required tables plus either the TrueType-related or
@@ -83,71 +268,607 @@ impl<'a> Profile<'a> {
     /// - For TrueType outlines: You can embed it as a `FontFile2`.
     /// - For CFF outlines: You can extract the CFF table and embed just the
     ///   table as a `FontFile3` with Subtype `Type1C`
+    ///
+    /// Glyph IDs are preserved, so a PDF generator that runs its own shaping
+    /// can pass the IDs of the variant glyphs it selected (e.g. small caps
+    /// through `smcp`, see [`variant_glyphs`]) and reference them with the
+    /// same IDs in the output.
     pub fn pdf(glyphs: &'a [u16]) -> Self {
-        Self { glyphs, map_glyphs: false }
+        Self::new(glyphs, false)
     }
 
     /// Reduces the font to the subset needed for web embedding.
     ///
+    /// Also keeps the glyphs produced by the `ccmp` feature, see
+    /// [`ccmp_closure`](Self::ccmp_closure).
+    ///
     /// Also map each glyph to a codepoint in the Unicode PUA, so they can be
-    /// referenced in HTML.
+    /// referenced in HTML. The glyph with ID `x` in the output is mapped to
+    /// `U+F0000 + x`, so this range always covers exactly the `numGlyphs` of
+    /// the output's `maxp` table, including glyphs whose outlines were
+    /// removed, and uses the new IDs if the glyphs are renumbered. Codepoints that don't shift when glyphs are
+    /// added to the source font can be chosen with [`pua_key`](Self::pua_key).
     pub fn web(glyphs: &'a [u16]) -> Self {
-        Self { glyphs, map_glyphs: true }
+        Self::new(glyphs, true)
+    }
+
+    /// Whether to embed a private `SUBS` table that records the version of
+    /// this crate and a hash of the subsetting plan, so that the pipeline
+    /// which produced a font can be audited later with [`read_stamp`].
+    pub fn version_stamp(mut self, stamp: bool) -> Self {
+        self.version_stamp = stamp;
+        self
+    }
+
+    /// Override the advance width or left side bearing of a glyph in the
+    /// output, e.g. to fix known metric bugs in third-party fonts.
+    ///
+    /// Overriding the same glyph twice merges both overrides.
+    pub fn override_metrics(mut self, glyph: u16, metrics: MetricsOverride) -> Self {
+        match self.metrics.iter_mut().find(|(g, _)| *g == glyph) {
+            Some((_, prev)) => {
+                prev.advance = metrics.advance.or(prev.advance);
+                prev.lsb = metrics.lsb.or(prev.lsb);
+            }
+            None => self.metrics.push((glyph, metrics)),
+        }
+        self
+    }
+
+    /// Make sure that the space character U+0020 is mapped in the output.
+    ///
+    /// Icon fonts sometimes lack a space glyph, which breaks CSS fallbacks and
+    /// copy & paste. If the font doesn't map U+0020, an unused glyph is turned
+    /// into an empty glyph with the given advance and mapped to it. Without an
+    /// explicit advance, the font's average advance width is used.
+    pub fn ensure_space(mut self, advance: Option<u16>) -> Self {
+        self.ensure_space = true;
+        self.space_advance = advance;
+        self
+    }
+
+    /// Keep only the tables needed to render the glyphs: `cmap`, `head`,
+    /// `hhea`, `hmtx`, `maxp` and the outlines (`glyf` and `loca` or `CFF`).
+    ///
+    /// Everything else is dropped, including hinting and layout tables.
+    /// TrueType instructions are removed from the glyphs as well, since the
+    /// programs they depend on are gone.
+    ///
+    /// The result works with consumers that only read outlines, metrics and
+    /// the character map, like FreeType, ttf-parser and PDF viewers (for which
+    /// [`pdf`](Self::pdf) would keep a few more tables). Browsers and
+    /// operating system font APIs also expect the `OS/2`, `name` and `post`
+    /// tables. With `keep_names`, those are kept as well, but `name` is
+    /// reduced to the family, style, unique ID, full name, version and
+    /// PostScript name, plus the names of the features of kept layout tables,
+    /// and `post` is reduced to its header.
+    pub fn minimal(mut self, keep_names: bool) -> Self {
+        self.minimal = true;
+        self.minimal_names = keep_names;
+        self
+    }
+
+    /// Drop the name records that fonts don't need to work: the description,
+    /// the vendor and designer URLs, the license description and URL and the
+    /// sample text.
+    ///
+    /// License texts are often the largest records, but some licenses require
+    /// that they are kept with the font.
+    pub fn prune_names(mut self, prune: bool) -> Self {
+        self.prune_names = prune;
+        self
+    }
+
+    /// Keep only the name records in the given languages, given as the
+    /// language IDs of the records, e.g. `[0, 0x409]` for English on the
+    /// Unicode and Macintosh platforms and US English on Windows. By default,
+    /// all languages are kept.
+    ///
+    /// Whenever the `name` table is rewritten, records with language tags
+    /// (language IDs from `0x8000`) are dropped.
+    pub fn name_languages(mut self, languages: &[u16]) -> Self {
+        self.name_languages.extend_from_slice(languages);
+        self
+    }
+
+    /// Append a suffix to the family names, e.g. "Subset" to turn "Noto Sans"
+    /// into "Noto Sans Subset", so that the subset doesn't clash with the full
+    /// font when both are installed.
+    ///
+    /// The full names get the suffix after the family, the PostScript names
+    /// get it without spaces and other invalid characters before the style,
+    /// like in "NotoSansSubset-Bold", and the unique identifiers refer to the
+    /// new PostScript names. Records in encodings other than UTF-16 or ASCII
+    /// stay as they are.
+    pub fn family_suffix(mut self, suffix: &'a str) -> Self {
+        self.family_suffix = Some(suffix);
+        self
+    }
+
+    /// Drop the glyph names by rewriting the `post` table to version 3.
+    ///
+    /// Otherwise, a version 2 table keeps the names of the retained glyphs
+    /// only, but still has an index entry for every glyph of the font, which
+    /// is significant for large CJK fonts.
+    pub fn strip_glyph_names(mut self, strip: bool) -> Self {
+        self.strip_glyph_names = strip;
+        self
+    }
+
+    /// Remove TrueType hinting: the `cvt `, `fpgm`, `prep` and `gasp` tables
+    /// and the instructions of the glyphs. The limits in `maxp` that only the
+    /// instructions need are reset, and so is the `head` flag that says that
+    /// instructions may alter advance widths.
+    ///
+    /// Browsers mostly ignore hinting, so it is often stripped from web fonts,
+    /// while fonts for desktop use keep it. CFF hints are part of the glyph
+    /// programs and kept either way.
+    pub fn strip_hinting(mut self, strip: bool) -> Self {
+        self.strip_hinting = strip;
+        self
+    }
+
+    /// Fail with [`Error::InvalidData`] on malformed data that would otherwise
+    /// be repaired or tolerated, like unsorted or overlapping `cmap` groups or
+    /// composite glyphs that reference glyphs beyond the font's glyphs. By
+    /// default, overlapping groups are resolved in favor of the later one and
+    /// such components are reported as [`Warning::ComponentMissing`].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Keep the glyphs that the `ccmp` (glyph composition and decomposition)
+    /// feature of the `GSUB` table can produce from the requested glyphs, like
+    /// the precomposed glyph for a base letter and a combining mark. Without
+    /// them, basic diacritics break in many fonts when text is shaped with the
+    /// original font. Enabled by default for [`web`](Self::web), but not for
+    /// [`pdf`](Self::pdf), where the glyphs were already shaped.
+    pub fn ccmp_closure(mut self, close: bool) -> Self {
+        self.ccmp_closure = close;
+        self
+    }
+
+    /// Keep the glyphs that the given features of the `GSUB` table can produce
+    /// from the requested glyphs, like ligatures from `liga` and contextual
+    /// alternates from `calt`.
+    ///
+    /// Like with [`ccmp_closure`](Self::ccmp_closure), this is for text that
+    /// is shaped with the original font, since the layout tables themselves
+    /// are dropped, unless [`keep_gsub`](Self::keep_gsub) keeps them. Browsers
+    /// enable `ccmp`, `locl`, `rlig`, `liga`, `clig`,
+    /// `calt` and `rclt` by default.
+    pub fn layout_closure(mut self, features: &[Tag]) -> Self {
+        self.layout_closure.extend_from_slice(features);
+        self
+    }
+
+    /// Close over the given features strictly: their substitutions only apply
+    /// to the requested glyphs, not to glyphs other features produce, and
+    /// their contextual rules only count if all glyphs of the input sequence
+    /// are requested.
+    ///
+    /// Fonts with large contextual systems, like Arabic or Indic fonts, can
+    /// otherwise pull in most of their glyphs through a single feature. This
+    /// keeps the closure small at the cost of shaping sequences which involve
+    /// glyphs that weren't requested. Applies to the features of
+    /// [`layout_closure`](Self::layout_closure), `ccmp` and the vertical
+    /// features.
+    pub fn strict_closure(mut self, features: &[Tag]) -> Self {
+        self.strict_closure.extend_from_slice(features);
+        self
+    }
+
+    /// Keep the vertical metrics (`vhea` and `vmtx`) for vertical layout, and
+    /// synthesize them if the font lacks them.
+    ///
+    /// Synthesized metrics give all glyphs the given advance height, or by
+    /// default the distance between the typographic ascender and descender
+    /// from `OS/2` (usually the em box of CJK fonts). The glyphs the `vert`
+    /// and `vrt2` features substitute for the requested glyphs, like rotated
+    /// punctuation, are kept as well.
+    pub fn synthesize_vertical(mut self, advance: Option<u16>) -> Self {
+        self.vertical = true;
+        self.vertical_advance = advance;
+        self
+    }
+
+    /// How to pad the tables of the output. Defaults to
+    /// [`Padding::Aligned`].
+    pub fn padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Keep the `GSUB` table, subsetted to the retained glyphs, and the `GDEF`
+    /// table its lookups consult for glyph classes and mark sets.
+    ///
+    /// Substitutions that read or produce glyphs which aren't retained are
+    /// removed, and so are lookups, features and scripts that are left empty.
+    /// To keep the glyphs that features produce, like ligatures, combine this
+    /// with [`layout_closure`](Self::layout_closure). Feature variations of
+    /// variable fonts are dropped. `GDEF` is subsetted to the retained glyphs
+    /// as well, and dropped if it has nothing left to say about them.
+    pub fn keep_gsub(mut self, keep: bool) -> Self {
+        self.keep_gsub = keep;
+        self
+    }
+
+    /// Keep the `GPOS` table, subsetted to the retained glyphs, and the `GDEF`
+    /// table, like [`keep_gsub`](Self::keep_gsub) does for `GSUB`.
+    ///
+    /// Kerning pairs, mark attachments and other adjustments that involve a
+    /// glyph which isn't retained are removed, so kerning and the placement
+    /// of diacritics keep working for the retained glyphs. Classes of class
+    /// based kerning that have no retained glyphs are removed from the class
+    /// matrix.
+    pub fn keep_gpos(mut self, keep: bool) -> Self {
+        self.keep_gpos = keep;
+        self
+    }
+
+    /// What to do when a `GSUB` or `GPOS` table kept with
+    /// [`keep_gsub`](Self::keep_gsub) or [`keep_gpos`](Self::keep_gpos) has a
+    /// lookup of a type or subtable format the subsetter doesn't support.
+    /// Defaults to [`LookupPolicy::Fail`].
+    ///
+    /// Unless subsetting fails, each such lookup is reported as a
+    /// [`Warning::UnsupportedLookup`].
+    pub fn unsupported_lookups(mut self, policy: LookupPolicy) -> Self {
+        self.lookup_policy = policy;
+        self
+    }
+
+    /// Keep the Apple Advanced Typography tables (`morx`, `kerx`, `trak` and
+    /// so on) verbatim.
+    ///
+    /// These tables aren't subsetted, so to keep every glyph they may
+    /// substitute, all glyphs are kept when the font has one of them. This
+    /// makes the output much larger, which is reported as a
+    /// [`Warning::AllGlyphsKept`].
+    pub fn keep_aat(mut self, keep: bool) -> Self {
+        self.keep_aat = keep;
+        self
+    }
+
+    /// Convert the ligature and noncontextual substitutions of a `morx` table
+    /// that are enabled by default to a `GSUB` table, if the font has no
+    /// `GSUB` table of its own.
+    ///
+    /// This preserves the basic shaping of fonts that only implement their
+    /// ligatures with the Apple Advanced Typography tables when these are
+    /// dropped, e.g. for browsers, which ignore them. The glyphs that the
+    /// converted substitutions produce from the retained glyphs are retained
+    /// as well. Contextual, insertion and rearrangement subtables aren't
+    /// converted.
+    pub fn convert_morx(mut self, convert: bool) -> Self {
+        self.convert_morx = convert;
+        self
+    }
+
+    /// Keep the Graphite tables (`Silf`, `Glat`, `Gloc`, `Feat` and `Sill`)
+    /// verbatim.
+    ///
+    /// Like with [`keep_aat`](Self::keep_aat), all glyphs are kept when the
+    /// font has one of them.
+    pub fn keep_graphite(mut self, keep: bool) -> Self {
+        self.keep_graphite = keep;
+        self
+    }
+
+    /// Keep the variation tables of variable fonts, so that the subset stays
+    /// variable.
+    ///
+    /// The `gvar` and `HVAR` tables are subsetted to the retained glyphs, and
+    /// so is `VVAR` along with the vertical metrics. `fvar`, `avar`, `STAT`
+    /// and `MVAR`, which don't reference glyphs, are kept verbatim, and so is
+    /// `cvar` along with the hinting.
+    pub fn keep_variations(mut self, keep: bool) -> Self {
+        self.keep_variations = keep;
+        self
+    }
+
+    /// Keep the layered color glyphs of the `COLR` table and their palettes
+    /// in `CPAL`, as well as the SVG glyphs of the `SVG ` table, subsetted to
+    /// the retained glyphs.
+    ///
+    /// The glyphs that the layers of retained color glyphs are filled with
+    /// are retained as well, and so are the color glyphs that `COLRv1` paint
+    /// graphs reuse. Palette entries that no retained color glyph uses are
+    /// removed from every palette, unless SVG glyphs may use them.
+    pub fn keep_color(mut self, keep: bool) -> Self {
+        self.keep_color = keep;
+        self
+    }
+
+    /// Keep the embedded bitmaps of the `CBDT`/`CBLC`, `EBDT`/`EBLC` and
+    /// `sbix` tables, subsetted to the retained glyphs.
+    ///
+    /// The bitmaps of removed glyphs are dropped, and so are strikes without
+    /// any retained bitmap. The glyphs that composite bitmaps are made of and
+    /// whose images `sbix` glyphs reuse are retained as well.
+    pub fn keep_bitmaps(mut self, keep: bool) -> Self {
+        self.keep_bitmaps = keep;
+        self
+    }
+
+    /// Drop the given tables from the output, even if the profile would keep
+    /// them, e.g. `name` or `post` when the consumer doesn't need them.
+    ///
+    /// Dropping a required table like `head` or `cmap` produces a font that
+    /// only some consumers accept.
+    pub fn drop_tables(mut self, tags: &[Tag]) -> Self {
+        self.dropped.extend_from_slice(tags);
+        self
+    }
+
+    /// Copy the given private tables unmodified, e.g. foundry-specific data
+    /// that doesn't depend on the glyphs or other tables.
+    ///
+    /// Tables the subsetter knows are not affected, and neither are tables
+    /// dropped with [`drop_tables`](Self::drop_tables). Each copied table is
+    /// reported with [`Warning::TablePassedThrough`] and listed in
+    /// [`SubsetReport::passthrough`].
+    pub fn passthrough_tables(mut self, tags: &[Tag]) -> Self {
+        self.passthrough.extend_from_slice(tags);
+        self
+    }
+
+    /// Renumber the retained glyphs to the IDs `0..n` in their original order,
+    /// instead of preserving their IDs, and remove the other glyphs entirely.
+    ///
+    /// This shrinks the `loca`, `hmtx` and `post` tables and the charstring
+    /// INDEX of fonts with many glyphs. The `cmap` table, the components of
+    /// composite glyphs and the subsetted layout tables are updated, but
+    /// tables that reference glyph IDs and can't be renumbered, like the Apple
+    /// Advanced Typography tables or any table copied verbatim, are dropped. The PUA
+    /// codepoints of [`web`](Self::web) use the new IDs. Use
+    /// [`subset_with_glyph_map`] to find out the new IDs.
+    pub fn remap_glyphs(mut self, remap: bool) -> Self {
+        self.remap_glyphs = remap;
+        self
+    }
+
+    /// Choose the new IDs that [`remap_glyphs`](Self::remap_glyphs) assigns.
+    ///
+    /// The retained glyphs in the given list get the IDs after `.notdef` in
+    /// the order of the list, and the other retained glyphs follow in their
+    /// original order. Glyphs that aren't retained are ignored. This way, the
+    /// glyphs that tables with ranges of glyph IDs, like the `cmap` table or
+    /// embedded bitmaps, treat alike can be given consecutive IDs.
+    pub fn glyph_order(mut self, order: &'a [u16]) -> Self {
+        self.glyph_order = order;
+        self
+    }
+
+    /// Choose the new IDs that [`remap_glyphs`](Self::remap_glyphs) assigns
+    /// by a key per glyph.
+    ///
+    /// The retained glyphs with a key follow those of the
+    /// [`glyph_order`](Self::glyph_order) list, sorted by their key and then
+    /// their original ID. The key is evaluated for all glyphs of the font when
+    /// the plan is computed, so the plan records the resulting order.
+    pub fn glyph_order_by(mut self, order: &'a dyn GlyphOrder) -> Self {
+        self.glyph_order_by = Some(order);
+        self
+    }
+
+    /// How [`web`](Self::web) assigns PUA codepoints to glyphs. Defaults to
+    /// [`PuaKey::GlyphId`].
+    pub fn pua_key(mut self, key: PuaKey) -> Self {
+        self.pua_key = key;
+        self
+    }
+
+    /// Scramble the character mapping of the output.
+    ///
+    /// The permutation can be retrieved with [`obfuscation_map`] from the
+    /// [`plan`] of the profile.
+    pub fn obfuscate(mut self, obfuscation: Obfuscation) -> Self {
+        self.obfuscation = Some(obfuscation);
+        self
+    }
+}
+
+/// How the tables of a subsetted font are padded.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Padding {
+    /// Pad every table to a multiple of four bytes, as the OpenType
+    /// specification recommends.
+    #[default]
+    Aligned,
+    /// Don't pad tables at all, for the smallest possible file. Some strict
+    /// consumers reject fonts with unaligned tables.
+    Minimal,
+    /// Use the same alignment as the source font, which eases binary diffing
+    /// against the original. Falls back to four bytes for faces supplied
+    /// through a [`TableProvider`].
+    Source,
+}
+
+/// How [`Profile::web`] assigns codepoints in the Unicode PUA to glyphs.
+///
+/// With keys other than [`GlyphId`](Self::GlyphId), only the retained glyphs
+/// are mapped, each to `U+F0000` plus an offset derived from its key. If two
+/// glyphs claim the same codepoint, the one with the smaller key keeps it and
+/// the other takes the next free one. A codepoint thus stays the same across
+/// revisions of the source font as long as the glyph's key does, unless a new
+/// glyph claims it first.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum PuaKey {
+    /// The glyph with ID `x` is mapped to `U+F0000 + x`, for all glyphs.
+    /// Adding a glyph to the source font shifts the codepoints of all glyphs
+    /// after it.
+    #[default]
+    GlyphId,
+    /// Derive the codepoint from a hash of the glyph's name in the `post` or
+    /// `CFF` table. Glyphs without a name are keyed by their ID.
+    GlyphName,
+    /// Derive the codepoint from the smallest codepoint that the source font
+    /// maps to the glyph, so that `U+0041` becomes `U+F0041`. Glyphs without
+    /// a character are keyed by their name, like with
+    /// [`GlyphName`](Self::GlyphName).
+    Codepoint,
+}
+
+/// What to do with a `GSUB` or `GPOS` lookup of a type or subtable format
+/// that the subsetter doesn't support, see [`Profile::unsupported_lookups`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum LookupPolicy {
+    /// Fail with [`Error::Unsupported`].
+    #[default]
+    Fail,
+    /// Copy the whole table verbatim instead of subsetting it. This keeps all
+    /// lookups working, but the table still references the removed glyphs,
+    /// and it is dropped when the glyphs are renumbered.
+    KeepTable,
+    /// Drop the lookup and subset the rest of the table. Features and
+    /// contextual rules no longer apply it.
+    DropLookup,
+}
+
+impl Padding {
+    /// The alignment of tables in the output of a face with this padding.
+    fn alignment(self, face: &Face) -> usize {
+        match self {
+            Self::Aligned => 4,
+            Self::Minimal => 1,
+            Self::Source => face.alignment,
+        }
     }
 }
 
 /// Subset a font face to include less glyphs and tables.
 ///
-/// - The `data` must be in the OpenType font format.
+/// - The `data` must be in the OpenType font format, with TrueType (`glyf`)
+///   or CFF and CFF2 outlines. For CFF, the charstrings of unused glyphs are
+///   replaced by empty ones and the Top and Private DICTs are reduced.
 /// - The `index` is only relevant if the data contains a font collection
-///   (`.ttc` or `.otc` file). Otherwise, it should be 0.
+///   (`.ttc` or `.otc` file), whose faces [`face_count`] counts. Otherwise,
+///   it should be 0.
 pub fn subset(data: &[u8], index: u32, profile: Profile) -> Result<Vec<u8>> {
     let face = parse(data, index)?;
-    let kind = match face.table(Tag::CFF).or(face.table(Tag::CFF2)) {
-        Some(_) => FontKind::Cff,
-        None => FontKind::TrueType,
-    };
+    let plan = Plan::new(&face, &profile);
+    prepare(face, plan)?.build()
+}
+
+/// Compute the subsetting plan for a font face without building the font.
+///
+/// The plan can be serialized with [`Plan::to_json`] and applied later with
+/// [`subset_with_plan`]. Requested glyphs that don't exist in the font are
+/// ignored, both here and by [`subset`], but [`subset_with_warnings`] reports
+/// them.
+pub fn plan(data: &[u8], index: u32, profile: Profile) -> Result<Plan> {
+    let face = parse(data, index)?;
+    let plan = Plan::new(&face, &profile);
+    let ctx = prepare(face, plan)?;
+    let mut glyphs: Vec<u16> = ctx.subset.into_iter().collect();
+
+    // Missing glyphs stay in the plan, so that applying it warns about them.
+    glyphs.extend(ctx.warnings.iter().filter_map(|warning| match warning {
+        Warning::GlyphMissing(glyph) => Some(*glyph),
+        _ => None,
+    }));
+
+    glyphs.sort_unstable();
+    Ok(Plan { glyphs, ..ctx.plan })
+}
+
+/// Subset a font face according to a previously computed plan.
+///
+/// The glyph closure is recomputed for the given face, so applying a plan to a
+/// newer revision of a font still keeps all components of retained composite
+/// glyphs. Tables the plan doesn't mention are dropped.
+pub fn subset_with_plan(data: &[u8], index: u32, plan: &Plan) -> Result<Vec<u8>> {
+    let face = parse(data, index)?;
+    prepare(face, plan.clone())?.build()
+}
 
+/// Set up the subsetting context and discover the glyph closure.
+fn prepare(face: Face, plan: Plan) -> Result<Context> {
+    prepare_with_loca(face, plan, None)
+}
+
+/// Set up the subsetting context like [`prepare`], reusing previously parsed
+/// offsets of the loca table.
+fn prepare_with_loca(
+    face: Face,
+    plan: Plan,
+    loca: Option<Arc<Vec<u32>>>,
+) -> Result<Context> {
+    let kind = face.kind();
+    let outlines = face.outlines();
     let maxp = face.table(Tag::MAXP).ok_or(Error::MissingTable(Tag::MAXP))?;
     let num_glyphs = u16::read_at(maxp, 4)?;
 
+    let mut plan = plan;
+    let mut warnings = vec![];
+    plan.glyphs.retain(|&glyph| {
+        let exists = glyph < num_glyphs;
+        if !exists {
+            warnings.push(Warning::GlyphMissing(glyph));
+        }
+        exists
+    });
+
+    // Shaping tables that are copied verbatim may substitute any glyph. When
+    // the glyphs are renumbered, they are dropped instead.
+    if let Some(&(tag, _)) = plan.tables.iter().find(|&&(tag, action)| {
+        action == TableAction::Copy && plan::is_shaping(tag) && !plan.remap_glyphs
+    }) {
+        plan.glyphs = (0..num_glyphs).collect();
+        warnings.push(Warning::AllGlyphsKept(tag));
+    }
+
     let mut ctx = Context {
         face,
         num_glyphs,
         subset: HashSet::new(),
-        profile,
+        plan,
         kind,
+        outlines,
         tables: vec![],
         long_loca: true,
+        space: None,
+        notdef: None,
+        warnings,
+        mappings: None,
+        loca,
+        remapped: None,
+        closure: vec![],
+        components: vec![],
     };
 
-    if ctx.kind == FontKind::TrueType {
-        glyf::discover(&mut ctx)?;
-        ctx.process(Tag::GLYF)?;
-        ctx.process(Tag::CVT)?;
-        ctx.process(Tag::FPGM)?;
-        ctx.process(Tag::PREP)?;
-        ctx.process(Tag::GASP)?;
+    let mut features = vec![];
+    if ctx.plan.ccmp_closure {
+        features.push(*gsub::CCMP);
     }
+    if ctx.plan.vertical {
+        features.extend([*gsub::VERT, *gsub::VRT2]);
+    }
+    features.extend(ctx.plan.layout_closure.iter().map(|tag| tag.0));
+    gsub::close(&mut ctx, &features);
+    morx::close(&mut ctx);
+    colr::discover(&mut ctx)?;
+    bitmap::discover(&mut ctx)?;
 
-    if ctx.kind == FontKind::Cff {
-        cff::discover(&mut ctx);
-        ctx.process(Tag::CFF)?;
-        ctx.process(Tag::CFF2)?;
-        ctx.process(Tag::VORG)?;
+    match ctx.outlines {
+        Outlines::TrueType => glyf::discover(&mut ctx)?,
+        Outlines::Cff => cff::discover(&mut ctx),
+        // Without outlines, no glyph references another one beyond what the
+        // bitmap and color closures found.
+        Outlines::None => {
+            ctx.subset = ctx.plan.glyphs.iter().copied().collect();
+            ctx.subset.insert(0);
+        }
     }
 
-    // Required tables.
-    ctx.process(Tag::CMAP)?;
-    ctx.process(Tag::HEAD)?;
-    ctx.process(Tag::HHEA)?;
-    ctx.process(Tag::HMTX)?;
-    ctx.process(Tag::MAXP)?;
-    ctx.process(Tag::NAME)?;
-    ctx.process(Tag::OS2)?;
-    ctx.process(Tag::POST)?;
+    space::ensure(&mut ctx)?;
 
-    Ok(construct(ctx))
+    Ok(ctx)
 }
 
 /// Parse a font face from OpenType data.
@@ -157,6 +878,9 @@ fn parse(data: &[u8], index: u32) -> Result<Face<'_>> {
 
     // Parse font collection header if necessary.
     if kind == FontKind::Collection {
+        if index >= u32::read_at(data, 8)? {
+            return Err(Error::InvalidOffset);
+        }
         let offset = u32::read_at(data, 12 + 4 * (index as usize))?;
         let subdata = data.get(offset as usize..).ok_or(Error::InvalidOffset)?;
         r = Reader::new(subdata);
@@ -173,27 +897,39 @@ fn parse(data: &[u8], index: u32) -> Result<Face<'_>> {
     r.read::<u16>()?;
 
     // Read table records.
-    let mut records = vec![];
+    let mut tables = vec![];
+    let mut alignment = 4;
     for _ in 0..count {
-        records.push(r.read::<TableRecord>()?);
+        let record = r.read::<TableRecord>()?;
+        alignment = alignment.min(1 << record.offset.trailing_zeros().min(2));
+        let start = record.offset as usize;
+        let end = start + (record.length as usize);
+        if let Some(table) = data.get(start..end) {
+            tables.push((record.tag, table));
+        }
     }
 
-    Ok(Face { data, records })
+    let mut face = Face::new(tables);
+    face.alignment = alignment;
+    Ok(face)
 }
 
-/// Construct a brand new font.
-fn construct(mut ctx: Context) -> Vec<u8> {
+/// Construct a brand new font, aligning each table to the given number of
+/// bytes.
+fn construct(
+    kind: FontKind,
+    mut tables: Vec<(Tag, Cow<'_, [u8]>)>,
+    align: usize,
+) -> Vec<u8> {
     let mut w = Writer::new();
-    w.write::<FontKind>(ctx.kind);
+    w.write::<FontKind>(kind);
 
     // Tables shall be sorted by tag.
-    ctx.tables.sort_by_key(|&(tag, _)| tag);
+    tables.sort_by_key(|&(tag, _)| tag);
 
     // Write table directory.
-    let count = ctx.tables.len() as u16;
-    let entry_selector = (count as f32).log2().floor() as u16;
-    let search_range = 2u16.pow(u32::from(entry_selector)) * 16;
-    let range_shift = count * 16 - search_range;
+    let count = tables.len() as u16;
+    let (search_range, entry_selector, range_shift) = search_params(count);
     w.write(count);
     w.write(search_range);
     w.write(entry_selector);
@@ -205,8 +941,8 @@ fn construct(mut ctx: Context) -> Vec<u8> {
     let mut checksum_adjustment_offset = None;
 
     // Write table records.
-    let mut offset = 12 + ctx.tables.len() * 16;
-    for (tag, data) in &mut ctx.tables {
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, data) in &mut tables {
         if *tag == Tag::HEAD {
             // Zero out checksum field in head table.
             data.to_mut()[8..12].fill(0);
@@ -224,18 +960,15 @@ fn construct(mut ctx: Context) -> Vec<u8> {
         #[cfg(test)]
         eprintln!("{}: {}", tag, len);
 
-        // Increase offset, plus padding zeros to align to 4 bytes.
-        offset += len;
-        while offset % 4 != 0 {
-            offset += 1;
-        }
+        // Increase offset, plus padding zeros for alignment.
+        offset = (offset + len).next_multiple_of(align);
     }
 
     // Write tables.
-    for (_, data) in &ctx.tables {
-        // Write data plus padding zeros to align to 4 bytes.
+    for (_, data) in &tables {
+        // Write data plus padding zeros for alignment.
         w.give(data);
-        w.align(4);
+        w.align(align);
     }
 
     // Write checksum adjustment field in head table.
@@ -249,6 +982,22 @@ fn construct(mut ctx: Context) -> Vec<u8> {
     data
 }
 
+/// The search range, entry selector and range shift of a table directory
+/// with the given number of records.
+///
+/// The search range and range shift don't fit into 16 bits for directories
+/// with 4096 or more records and are then clamped.
+fn search_params(count: u16) -> (u16, u16, u16) {
+    if count == 0 {
+        return (0, 0, 0);
+    }
+    let entry_selector = count.ilog2();
+    let search_range = 16u32 << entry_selector;
+    let range_shift = 16 * u32::from(count) - search_range;
+    let clamp = |v: u32| v.min(u32::from(u16::MAX)) as u16;
+    (clamp(search_range), entry_selector as u16, clamp(range_shift))
+}
+
 /// Calculate a checksum over the sliced data as a sum of u32s. If the data
 /// length is not a multiple of four, it is treated as if padded with zero to a
 /// length that is a multiple of four.
@@ -268,26 +1017,128 @@ struct Context<'a> {
     face: Face<'a>,
     /// The number of glyphs in the original and subsetted face.
     ///
-    /// Subsetting doesn't actually delete glyphs, just their outlines.
+    /// Subsetting doesn't actually delete glyphs, just their outlines, so this
+    /// is the `numGlyphs` of the `maxp` table in both and the source of truth
+    /// for everything sized by the glyph count, like the PUA range. Only
+    /// renumbering the glyphs changes the count, as the very last step.
     num_glyphs: u16,
     /// The kept glyphs.
     subset: HashSet<u16>,
-    /// The subsetting profile.
-    profile: Profile<'a>,
+    /// The subsetting plan.
+    plan: Plan,
     /// The kind of face.
     kind: FontKind,
+    /// The format of the glyph outlines.
+    outlines: Outlines,
     /// Subsetted tables.
     tables: Vec<(Tag, Cow<'a, [u8]>)>,
     /// Whether the long loca format was chosen.
     long_loca: bool,
+    /// The glyph and advance of a synthesized space glyph.
+    space: Option<(u16, u16)>,
+    /// A synthesized .notdef glyph, if the font lacks an outline for it.
+    notdef: Option<glyf::Notdef>,
+    /// Warnings collected while subsetting.
+    warnings: Vec<Warning>,
+    /// The parsed character mappings of the cmap table, once needed.
+    mappings: Option<Arc<Vec<(u32, u16)>>>,
+    /// The parsed glyph offsets of the loca table, once needed.
+    loca: Option<Arc<Vec<u32>>>,
+    /// The original IDs of the output glyphs, if they were renumbered.
+    remapped: Option<Vec<u16>>,
+    /// The glyphs the `GSUB` closure added, by the feature that produced them.
+    closure: Vec<(Tag, Vec<u16>)>,
+    /// The glyphs the composite glyph closure added, as pairs of the
+    /// composite and the component.
+    components: Vec<(u16, u16)>,
 }
 
 impl<'a> Context<'a> {
+    /// Process all tables according to the plan and construct the new font.
+    fn build(mut self) -> Result<Vec<u8>> {
+        self.build_tables()?;
+        Ok(self.construct())
+    }
+
+    /// Process all tables according to the plan.
+    fn build_tables(&mut self) -> Result<()> {
+        // The plan's tables are sorted by tag, so `glyf` is processed before
+        // `head`, which needs to know the chosen `loca` format.
+        for (tag, action) in self.plan.tables.clone() {
+            match action {
+                TableAction::Subset => self.process(tag)?,
+                TableAction::Copy
+                    if self.plan.remap_glyphs && plan::references_glyphs(tag) =>
+                {
+                    if self.face.table(tag).is_some() {
+                        self.warnings.push(Warning::TableDropped(tag));
+                    }
+                }
+                TableAction::Copy => {
+                    if let Some(data) = self.face.table(tag) {
+                        if self.plan.passthrough.contains(&tag) {
+                            self.warnings.push(Warning::TablePassedThrough(tag));
+                        } else if !warning::is_known(tag) {
+                            self.warnings.push(Warning::UnknownTableCopied(tag));
+                        }
+                        self.push(tag, data);
+                    }
+                }
+                // Tables the profile drops on purpose aren't worth a warning.
+                TableAction::Drop => {
+                    if self.plan.remap_dropped.contains(&tag) {
+                        self.warnings.push(Warning::TableDropped(tag));
+                    }
+                }
+            }
+        }
+        morx::convert(self)?;
+        hmtx::finalize(self)?;
+        glyf::finalize(self)?;
+        vmtx::synthesize(self)?;
+        remap::remap(self)?;
+        if self.plan.version_stamp {
+            stamp::write(self);
+        }
+        Ok(())
+    }
+
+    /// Construct the new font from the processed tables.
+    fn construct(self) -> Vec<u8> {
+        let align = self.plan.padding.alignment(&self.face);
+        construct(self.kind, self.tables, align)
+    }
+
     /// Expect a table.
     fn expect_table(&self, tag: Tag) -> Result<&'a [u8]> {
         self.face.table(tag).ok_or(Error::MissingTable(tag))
     }
 
+    /// The codepoint to glyph mappings of the cmap table, parsed on first use.
+    fn mappings(&mut self) -> Result<Arc<Vec<(u32, u16)>>> {
+        if self.mappings.is_none() {
+            let cmap = self.expect_table(Tag::CMAP)?;
+            self.mappings = Some(Arc::new(cmap::mappings(cmap)?));
+        }
+        Ok(self.mappings.clone().unwrap_or_default())
+    }
+
+    /// The glyf and loca tables, with the offsets of the loca table parsed on
+    /// first use.
+    fn glyf(&mut self) -> Result<glyf::Table<'a>> {
+        let glyf = self.expect_table(Tag::GLYF)?;
+        let offsets = match &self.loca {
+            Some(offsets) => offsets.clone(),
+            None => {
+                let table = glyf::Table::new(&self.face)?;
+                let offsets = Arc::new(table.offsets(self.num_glyphs));
+                self.loca = Some(offsets.clone());
+                offsets
+            }
+        };
+        Ok(glyf::Table::from_offsets(offsets, glyf))
+    }
+
     /// Process a table.
     fn process(&mut self, tag: Tag) -> Result<()> {
         let data = match self.face.table(tag) {
@@ -299,10 +1150,27 @@ impl<'a> Context<'a> {
             Tag::GLYF => glyf::subset(self)?,
             Tag::LOCA => panic!("handled by glyf"),
             Tag::CFF => cff::subset(self)?,
+            Tag::CFF2 => cff::cff2::subset(self)?,
             Tag::HEAD => head::subset(self)?,
+            Tag::MAXP => maxp::subset(self)?,
+            Tag::HHEA => hmtx::subset_hhea(self)?,
             Tag::HMTX => hmtx::subset(self)?,
+            Tag::OS2 => os2::subset(self)?,
+            Tag::NAME => name::subset(self)?,
             Tag::POST => post::subset(self)?,
             Tag::CMAP => cmap::map_glyphs(self)?,
+            Tag::GDEF => gdef::subset(self)?,
+            Tag::GSUB => gsub::subset(self)?,
+            Tag::GPOS => gpos::subset(self)?,
+            Tag::COLR => colr::subset(self)?,
+            Tag::CPAL => colr::subset_cpal(self)?,
+            Tag::SVG => svg::subset(self)?,
+            Tag::CBLC | Tag::EBLC => bitmap::subset(self, tag)?,
+            // Written along with their location tables.
+            Tag::CBDT | Tag::EBDT => {}
+            Tag::SBIX => bitmap::subset_sbix(self)?,
+            Tag::GVAR => gvar::subset(self)?,
+            Tag::HVAR | Tag::VVAR => hvar::subset(self, tag)?,
             _ => self.push(tag, data),
         }
 
@@ -321,17 +1189,64 @@ impl<'a> Context<'a> {
 
 /// A font face with OpenType tables.
 struct Face<'a> {
-    data: &'a [u8],
-    records: Vec<TableRecord>,
+    /// The tables of the face, sorted by tag.
+    tables: Vec<(Tag, &'a [u8])>,
+    /// The alignment of the tables in the source data, in bytes.
+    alignment: usize,
 }
 
 impl<'a> Face<'a> {
+    fn new(mut tables: Vec<(Tag, &'a [u8])>) -> Self {
+        tables.sort_by_key(|&(tag, _)| tag);
+        tables.dedup_by_key(|&mut (tag, _)| tag);
+        Self { tables, alignment: 4 }
+    }
+
+    /// The kind of the face, which selects the sfnt version of the output.
+    fn kind(&self) -> FontKind {
+        match self.table(Tag::CFF).or(self.table(Tag::CFF2)) {
+            Some(_) => FontKind::Cff,
+            None => FontKind::TrueType,
+        }
+    }
+
+    /// The format of the glyph outlines in the face.
+    fn outlines(&self) -> Outlines {
+        if self.table(Tag::CFF).or(self.table(Tag::CFF2)).is_some() {
+            Outlines::Cff
+        } else if self.table(Tag::GLYF).is_some() {
+            Outlines::TrueType
+        } else {
+            Outlines::None
+        }
+    }
+
     fn table(&self, tag: Tag) -> Option<&'a [u8]> {
-        let i = self.records.binary_search_by(|record| record.tag.cmp(&tag)).ok()?;
-        let record = self.records.get(i)?;
-        let start = record.offset as usize;
-        let end = start + (record.length as usize);
-        self.data.get(start..end)
+        let i = self.tables.binary_search_by(|&(t, _)| t.cmp(&tag)).ok()?;
+        Some(self.tables[i].1)
+    }
+}
+
+/// The format of a face's glyph outlines, which selects the passes that find
+/// the glyph closure and subset the outlines.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Outlines {
+    /// `glyf` and `loca` tables.
+    TrueType,
+    /// A `CFF ` or `CFF2` table.
+    Cff,
+    /// No outline tables, like in fonts with only bitmap or SVG glyphs.
+    None,
+}
+
+impl Outlines {
+    /// The name of the format in dumps.
+    fn name(self) -> &'static str {
+        match self {
+            Self::TrueType => "truetype",
+            Self::Cff => "cff",
+            Self::None => "none",
+        }
     }
 }
 
@@ -367,6 +1282,7 @@ impl Structure<'_> for FontKind {
 
 /// A 4-byte OpenType tag.
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Tag(pub [u8; 4]);
 
 #[allow(unused)]
@@ -380,6 +1296,8 @@ impl Tag {
     const NAME: Self = Self(*b"name");
     const OS2: Self = Self(*b"OS/2");
     const POST: Self = Self(*b"post");
+    const VHEA: Self = Self(*b"vhea");
+    const VMTX: Self = Self(*b"vmtx");
 
     // TrueType.
     const GLYF: Self = Self(*b"glyf");
@@ -389,6 +1307,12 @@ impl Tag {
     const CVT: Self = Self(*b"cvt ");
     const GASP: Self = Self(*b"gasp");
 
+    // Layout.
+    const GSUB: Self = Self(*b"GSUB");
+    const GPOS: Self = Self(*b"GPOS");
+    const GDEF: Self = Self(*b"GDEF");
+    const KERN: Self = Self(*b"kern");
+
     // CFF.
     const CFF: Self = Self(*b"CFF ");
     const CFF2: Self = Self(*b"CFF2");
@@ -404,6 +1328,68 @@ impl Tag {
     const CBLC: Self = Self(*b"CBLC");
     const SBIX: Self = Self(*b"sbix");
     const SVG: Self = Self(*b"SVG ");
+
+    // Apple Advanced Typography.
+    const ANKR: Self = Self(*b"ankr");
+    const BSLN: Self = Self(*b"bsln");
+    const FEAT: Self = Self(*b"feat");
+    const JUST: Self = Self(*b"just");
+    const KERX: Self = Self(*b"kerx");
+    const LCAR: Self = Self(*b"lcar");
+    const MORT: Self = Self(*b"mort");
+    const MORX: Self = Self(*b"morx");
+    const OPBD: Self = Self(*b"opbd");
+    const PROP: Self = Self(*b"prop");
+    const TRAK: Self = Self(*b"trak");
+
+    // Graphite.
+    const SILF: Self = Self(*b"Silf");
+    const GLAT: Self = Self(*b"Glat");
+    const GLOC: Self = Self(*b"Gloc");
+    const FEAT_GRAPHITE: Self = Self(*b"Feat");
+    const SILL: Self = Self(*b"Sill");
+
+    // Rare Apple tables.
+    const ACNT: Self = Self(*b"acnt");
+    const BDAT: Self = Self(*b"bdat");
+    const BLOC: Self = Self(*b"bloc");
+    const GCID: Self = Self(*b"gcid");
+    const ZAPF: Self = Self(*b"Zapf");
+
+    // Other tables indexed by glyph ID.
+    const HDMX: Self = Self(*b"hdmx");
+    const LTSH: Self = Self(*b"LTSH");
+    const MATH: Self = Self(*b"MATH");
+    const JSTF: Self = Self(*b"JSTF");
+    const GVAR: Self = Self(*b"gvar");
+    const HVAR: Self = Self(*b"HVAR");
+    const VVAR: Self = Self(*b"VVAR");
+
+    // Variation tables.
+    const FVAR: Self = Self(*b"fvar");
+    const AVAR: Self = Self(*b"avar");
+    const CVAR: Self = Self(*b"cvar");
+    const MVAR: Self = Self(*b"MVAR");
+    const STAT: Self = Self(*b"STAT");
+
+    // Private.
+    const SUBS: Self = Self(*b"SUBS");
+}
+
+impl FromStr for Tag {
+    type Err = Error;
+
+    /// Parse a tag from up to four ASCII characters. Shorter tags are padded
+    /// with spaces, so that `cvt` yields the `cvt ` tag.
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes = s.as_bytes();
+        if bytes.is_empty() || bytes.len() > 4 || !s.is_ascii() {
+            return Err(Error::InvalidData);
+        }
+        let mut tag = [b' '; 4];
+        tag[..bytes.len()].copy_from_slice(bytes);
+        Ok(Self(tag))
+    }
 }
 
 impl Structure<'_> for Tag {
@@ -458,6 +1444,13 @@ impl Structure<'_> for TableRecord {
 /// A signed 16-bit fixed-point number.
 struct F2Dot14(u16);
 
+impl F2Dot14 {
+    /// The value of the number.
+    fn get(self) -> f32 {
+        self.0 as i16 as f32 / 16384.0
+    }
+}
+
 impl Structure<'_> for F2Dot14 {
     fn read(r: &mut Reader) -> Result<Self> {
         r.read::<u16>().map(Self)
@@ -489,6 +1482,18 @@ pub enum Error {
     /// depends on another table and that one is missing, e.g., `glyf` is
     /// present but `loca` is missing.
     MissingTable(Tag),
+    /// A subsetting plan could not be deserialized.
+    InvalidPlan,
+    /// A WOFF2 font is malformed or could not be converted.
+    Woff2,
+    /// Decoding a compressed font would exceed the configured limits.
+    LimitExceeded,
+    /// A glyph had to be synthesized, but all glyphs of the font are in use.
+    NoFreeGlyph,
+    /// A subsetted table is too large for the 16-bit offsets of its format.
+    OffsetOverflow,
+    /// The font uses a feature that the requested operation doesn't support.
+    Unsupported,
 }
 
 impl Display for Error {
@@ -499,6 +1504,12 @@ impl Display for Error {
             Self::MissingData => f.pad("missing more data"),
             Self::InvalidData => f.pad("invalid data"),
             Self::MissingTable(tag) => write!(f, "missing {tag} table"),
+            Self::InvalidPlan => f.pad("invalid subsetting plan"),
+            Self::Woff2 => f.pad("WOFF2 conversion failed"),
+            Self::LimitExceeded => f.pad("decompressed font exceeds limits"),
+            Self::NoFreeGlyph => f.pad("no unused glyph available"),
+            Self::OffsetOverflow => f.pad("table too large for 16-bit offsets"),
+            Self::Unsupported => f.pad("unsupported font feature"),
         }
     }
 }
@@ -507,12 +1518,50 @@ impl std::error::Error for Error {}
 
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+    use std::collections::HashMap;
     use std::path::Path;
 
-    use super::{subset, Profile};
+    use super::{
+        alternates, build_collection, closure_by_composite, closure_by_feature,
+        debug_dump, face_count, gzip, isolate_glyph, layout_features, layout_scripts,
+        named_instances, obfuscation_map, parse, plan, read_stamp, subset,
+        subset_collection, subset_faces, subset_from_provider, subset_to_result,
+        subset_with_glyph_map, subset_with_plan, subset_with_provenance,
+        subset_with_report, subset_with_warnings, variant_glyphs, variation_axes,
+        Alternates, Error, Instancer, Integrity, LookupPolicy, MetricsOverride,
+        Obfuscation, Origin, Padding, Plan, Profile, PuaKey, ReverseCmap, Severity,
+        Structure, SubsetCache, TableAction, Tag, Warning, Writer,
+    };
 
     const FEW: &str = "Hällo<.!ﬁ12";
 
+    /// Create a cmap subtable with format 12.
+    fn format_12(language: u32, groups: &[(u32, u32, u32)]) -> Vec<u8> {
+        let mut data = vec![0, 12, 0, 0];
+        data.extend((16 + 12 * groups.len() as u32).to_be_bytes());
+        data.extend(language.to_be_bytes());
+        data.extend((groups.len() as u32).to_be_bytes());
+        for &(start, end, glyph) in groups {
+            data.extend([start, end, glyph].iter().flat_map(|v| v.to_be_bytes()));
+        }
+        data
+    }
+
+    /// Replace the cmap table of Noto Sans.
+    fn with_cmap(cmap: Vec<u8>) -> Vec<u8> {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.iter_mut().find(|(tag, _)| *tag == Tag::CMAP).unwrap().1 =
+            Cow::Owned(cmap);
+        super::construct(face.kind(), tables, 4)
+    }
+
     #[test]
     fn test_subset_truetype() {
         test("NotoSans-Regular.ttf", FEW);
@@ -534,66 +1583,3644 @@ mod tests {
         test_full("NotoSansCJKsc-Regular.otf");
     }
 
-    fn test(path: &str, text: &str) {
-        test_impl(path, text, true);
+    #[test]
+    fn test_plan_roundtrip() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs: Vec<_> =
+            FEW.chars().filter_map(|c| Some(ttf.glyph_index(c)?.0)).collect();
+
+        let profile = Profile::pdf(&glyphs);
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        let parsed = Plan::from_json(&computed.to_json()).unwrap();
+        assert_eq!(computed, parsed);
+        assert_eq!(
+            subset(&data, 0, profile).unwrap(),
+            subset_with_plan(&data, 0, &parsed).unwrap()
+        );
     }
 
-    fn test_full(path: &str) {
-        let data = std::fs::read(Path::new("fonts").join(path)).unwrap();
-        let ttf = ttf_parser::Face::from_slice(&data, 0).unwrap();
-        let mut text = String::new();
-        for subtable in ttf.tables().cmap.unwrap().subtables {
-            if subtable.is_unicode() {
-                subtable.codepoints(|c| text.push(char::try_from(c).unwrap()));
-            }
-        }
-        test_impl(path, &text, false);
+    #[test]
+    fn test_reverse_cmap() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let reverse = ReverseCmap::new(&data, 0).unwrap();
+        let a = ttf.glyph_index('a').unwrap().0;
+        assert_eq!(reverse.chars_for_glyph(a).collect::<Vec<_>>(), ['a']);
+        let space = ttf.glyph_index(' ').unwrap().0;
+        assert!(reverse.chars_for_glyph(space).any(|c| c == ' '));
+        assert_eq!(reverse.chars_for_glyph(0).count(), 0);
     }
 
-    fn test_impl(path: &str, text: &str, write: bool) {
-        eprintln!("==============================================");
-        eprintln!("Testing {path}");
+    #[test]
+    fn test_integrity() {
+        // Data that isn't a font is hashed as is.
+        assert_eq!(
+            Integrity::compute(b"abc").hex(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            Integrity::compute(&[b'a'; 1000]).hex(),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
 
-        let data = std::fs::read(Path::new("fonts").join(path)).unwrap();
-        let ttf = ttf_parser::Face::from_slice(&data, 0).unwrap();
-        let glyphs: Vec<_> =
-            text.chars().filter_map(|c| Some(ttf.glyph_index(c)?.0)).collect();
+        // The checksum adjustment doesn't affect the hash of a font.
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let subs = subset(&data, 0, Profile::pdf(&[68, 69])).unwrap();
+        let integrity = Integrity::compute(&subs);
+        let offset = parse(&subs, 0).unwrap().table(Tag::HEAD).unwrap().as_ptr() as usize
+            - subs.as_ptr() as usize;
+        let mut patched = subs.clone();
+        patched[offset + 8..offset + 12].copy_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(Integrity::compute(&patched), integrity);
+        patched[offset + 12] ^= 1;
+        assert_ne!(Integrity::compute(&patched), integrity);
 
-        let profile = Profile::pdf(&glyphs);
-        let subs = subset(&data, 0, profile).unwrap();
-        let stem = Path::new(path).file_stem().unwrap().to_str().unwrap();
-        let out = Path::new("target").join(Path::new(stem)).with_extension("ttf");
+        let signed = integrity.sign(|hash| hash[..2].to_vec());
+        assert_eq!(signed.signature.as_deref(), Some(&signed.hash[..2]));
+        assert!(signed.to_json().contains(&format!(
+            "\"signature\": \"{:02x}{:02x}\"",
+            signed.hash[0], signed.hash[1]
+        )));
+    }
 
-        if write {
-            std::fs::write(out, &subs).unwrap();
+    #[test]
+    fn test_pua_range() {
+        for path in ["fonts/NotoSans-Regular.ttf", "fonts/LatinModernRoman-Regular.otf"] {
+            let data = std::fs::read(path).unwrap();
+            let subs = subset(&data, 0, Profile::web(&[68])).unwrap();
+            let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+            let pua = |id: u32| char::from_u32(0xF0000 + id).unwrap();
+            let n = u32::from(ttfs.number_of_glyphs());
+            assert_eq!(ttfs.glyph_index(pua(0)), Some(ttf_parser::GlyphId(0)));
+            assert_eq!(ttfs.glyph_index(pua(n - 1)).map(|id| id.0 as u32), Some(n - 1));
+            assert_eq!(ttfs.glyph_index(pua(n)), None);
         }
+    }
 
-        let ttfs = ttf_parser::Face::from_slice(&subs, 0).unwrap();
-        let cff = ttfs.tables().cff;
-        for c in text.chars() {
-            let id = ttf.glyph_index(c).unwrap();
-            let bbox = ttf.glyph_bounding_box(id);
+    #[test]
+    fn test_pua_key() {
+        for path in ["fonts/NotoSans-Regular.ttf", "fonts/LatinModernRoman-Regular.otf"] {
+            let data = std::fs::read(path).unwrap();
+            let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+            let a = ttf.glyph_index('a').unwrap();
+            let pua = |glyphs: &[u16], key| {
+                let profile = Profile::web(glyphs).pua_key(key);
+                let plan = plan(&data, 0, profile).unwrap();
+                assert_eq!(Plan::from_json(&plan.to_json()).unwrap(), plan);
+                let subs = subset_with_plan(&data, 0, &plan).unwrap();
+                let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+                let mut mapped = vec![];
+                ttfs.tables().cmap.unwrap().subtables.into_iter().for_each(|st| {
+                    st.codepoints(|c| {
+                        if (0xF0000..=0xFFFFD).contains(&c) {
+                            mapped.push((c, st.glyph_index(c).unwrap().0));
+                        }
+                    })
+                });
+                mapped.sort_unstable();
+                mapped.dedup();
+                mapped
+            };
 
-            if let Some(cff) = &cff {
-                if bbox.is_some() {
-                    cff.outline(id, &mut Sink::default()).unwrap();
-                }
-            }
+            // Only retained glyphs are mapped, and other glyphs don't shift them.
+            let few = pua(&[a.0], PuaKey::GlyphName);
+            assert_eq!(few.len(), 1);
+            assert_eq!(few[0].1, a.0);
+            let many: Vec<u16> = (0..100).collect();
+            assert!(pua(&many, PuaKey::GlyphName).contains(&few[0]));
 
-            let mut sink1 = Sink::default();
-            let mut sink2 = Sink::default();
-            ttf.outline_glyph(id, &mut sink1);
-            ttfs.outline_glyph(id, &mut sink2);
-            assert_eq!(sink1, sink2);
-            assert_eq!(ttf.glyph_hor_advance(id), ttfs.glyph_hor_advance(id));
-            assert_eq!(ttf.glyph_name(id), ttfs.glyph_name(id));
-            assert_eq!(ttf.glyph_hor_side_bearing(id), ttfs.glyph_hor_side_bearing(id));
+            let few = pua(&[a.0], PuaKey::Codepoint);
+            assert_eq!(few, [(0xF0061, a.0)]);
+            let mapped = pua(&many, PuaKey::Codepoint);
+            assert!(mapped.contains(&few[0]));
+            let mut glyphs: Vec<u16> = mapped.iter().map(|&(_, glyph)| glyph).collect();
+            glyphs.sort_unstable();
+            glyphs.dedup();
+            assert_eq!(glyphs.len(), mapped.len());
+            assert!(glyphs.len() >= 99 && glyphs[0] == 1);
+
+            let all = pua(&[a.0], PuaKey::GlyphId);
+            assert_eq!(all.len(), usize::from(ttf.number_of_glyphs()));
         }
     }
 
-    #[derive(Debug, Default, PartialEq)]
-    struct Sink(Vec<Inst>);
-
+    #[test]
+    fn test_debug_dump() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let subs = subset(&data, 0, Profile::web(&[68, 69, 70])).unwrap();
+        let dump = debug_dump(&subs, 0).unwrap();
+        assert!(dump.starts_with("kind: truetype\ntables:\n"));
+        assert!(dump.contains("    U+0020..U+007E -> 3\n"));
+        assert!(dump.contains("  68: advance 561, lsb 46, simple\n"));
+        assert_eq!(dump, debug_dump(&subs, 0).unwrap());
+    }
+
+    #[test]
+    fn test_cmap_language() {
+        // Give the format 4 subtable of the Unicode records a nonzero language,
+        // which only Macintosh subtables may have.
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        let cmap = tables.iter_mut().find(|(tag, _)| *tag == Tag::CMAP).unwrap();
+        let cmap = cmap.1.to_mut();
+        let num_tables = u16::from_be_bytes([cmap[2], cmap[3]]) as usize;
+        for i in 0..num_tables {
+            let at = 4 + 8 * i + 4;
+            let offset =
+                u32::from_be_bytes(cmap[at..at + 4].try_into().unwrap()) as usize;
+            if cmap[offset..offset + 2] == [0, 4] {
+                cmap[offset + 4..offset + 6].copy_from_slice(&[0, 5]);
+            }
+        }
+        let data = super::construct(face.kind(), tables, 4);
+        assert!(debug_dump(&data, 0).unwrap().contains("language 5"));
+
+        // The format 12 subtable synthesized for the PUA mapping must not
+        // inherit it either.
+        let subs = subset(&data, 0, Profile::web(&[68, 69])).unwrap();
+        let dump = debug_dump(&subs, 0).unwrap();
+        assert!(dump.contains("format 4, language 0, records 0/3 3/1"));
+        assert!(dump.contains("format 12, language 0, records 0/4"));
+        assert!(!dump.contains("language 5"));
+    }
+
+    #[test]
+    fn test_cmap_multiple_format_12() {
+        // A language-specific Macintosh subtable comes first, but the Unicode
+        // one must be modified and read.
+        let mac = format_12(1, &[(0x61, 0x61, 5)]);
+        let unicode = format_12(0, &[(0x61, 0x62, 68)]);
+        let mut cmap = vec![0, 0, 0, 2];
+        cmap.extend([0, 1, 0, 0].into_iter().chain(20u32.to_be_bytes()));
+        cmap.extend(
+            [0, 3, 0, 10].into_iter().chain((20 + mac.len() as u32).to_be_bytes()),
+        );
+        cmap.extend(mac);
+        cmap.extend(unicode);
+
+        let data = with_cmap(cmap);
+        let num_glyphs = ttf_parser::Face::parse(&data, 0).unwrap().number_of_glyphs();
+
+        let subs = subset(&data, 0, Profile::web(&[68, 69])).unwrap();
+        let dump = debug_dump(&subs, 0).unwrap();
+        assert!(dump
+            .contains("format 12, language 1, records 1/0\n    U+0061..U+0061 -> 5\n"));
+        assert!(dump.contains(&format!(
+            "format 12, language 0, records 0/4 3/10\n    \
+             U+0061..U+0062 -> 68\n    U+F0000..U+{:X} -> 0\n",
+            0xF0000 + u32::from(num_glyphs) - 1
+        )));
+
+        let obfuscation = Obfuscation { seed: 1, strip_names: false };
+        let profile = Profile::pdf(&[68, 69]).obfuscate(obfuscation);
+        let map = obfuscation_map(&data, 0, &plan(&data, 0, profile).unwrap()).unwrap();
+        let chars: Vec<_> = map.iter().map(|&(from, _)| from).collect();
+        assert_eq!(chars, ['a', 'b']);
+    }
+
+    #[test]
+    fn test_cmap_overlapping_groups() {
+        // The second group overrides part of the first one.
+        let unicode = format_12(0, &[(0x61, 0x63, 68), (0x62, 0x62, 100)]);
+        let mut cmap = vec![0, 0, 0, 1];
+        cmap.extend([0, 3, 0, 10].into_iter().chain(12u32.to_be_bytes()));
+        cmap.extend(unicode);
+        let data = with_cmap(cmap);
+
+        let subs = subset(&data, 0, Profile::web(&[68, 70, 100])).unwrap();
+        let dump = debug_dump(&subs, 0).unwrap();
+        assert!(dump.contains(
+            "records 0/4 3/10\n    U+0061..U+0061 -> 68\n    \
+             U+0062..U+0062 -> 100\n    U+0063..U+0063 -> 70\n    U+F0000"
+        ));
+
+        let strict = Profile::web(&[68, 70, 100]).strict(true);
+        assert_eq!(subset(&data, 0, strict), Err(Error::InvalidData));
+    }
+
+    #[test]
+    fn test_cmap_shared_subtable() {
+        // The Windows and Unicode full repertoire records share a subtable,
+        // and a second Windows record repeats the combination.
+        let shared = format_12(0, &[(0x61, 0x62, 68)]);
+        let other = format_12(0, &[(0x61, 0x61, 70)]);
+        let mut cmap = vec![0, 0, 0, 3];
+        let shared_at = 28 + other.len() as u32;
+        cmap.extend([0, 3, 0, 10].into_iter().chain(shared_at.to_be_bytes()));
+        cmap.extend([0, 0, 0, 4].into_iter().chain(shared_at.to_be_bytes()));
+        cmap.extend([0, 3, 0, 10].into_iter().chain(28u32.to_be_bytes()));
+        cmap.extend(other);
+        cmap.extend(shared);
+        let data = with_cmap(cmap);
+
+        // Each combination is written once, and the shared subtable as well.
+        let records = |subs: &[u8]| {
+            let ttf = ttf_parser::Face::parse(subs, 0).unwrap();
+            let cmap =
+                ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"cmap")).unwrap();
+            let count = usize::from(u16::from_be_bytes([cmap[2], cmap[3]]));
+            (0..count)
+                .map(|i| cmap[4 + 8 * i..12 + 8 * i].to_vec())
+                .collect::<Vec<_>>()
+        };
+        for profile in
+            [Profile::pdf(&[68, 69]).ensure_space(None), Profile::web(&[68, 69])]
+        {
+            let subs = subset(&data, 0, profile).unwrap();
+            assert_eq!(
+                records(&subs),
+                [[0, 0, 0, 4, 0, 0, 0, 20], [0, 3, 0, 10, 0, 0, 0, 20]]
+            );
+            let dump = debug_dump(&subs, 0).unwrap();
+            assert!(
+                dump.contains("subtable 0: format 12, language 0, records 0/4 3/10\n")
+            );
+            assert!(dump.contains("    U+0061..U+0062 -> 68\n"));
+            assert!(!dump.contains("subtable 1"));
+        }
+    }
+
+    #[test]
+    fn test_cmap_many_to_one() {
+        // Like last resort fonts, the font maps a large range to a single
+        // glyph with a group per character.
+        let mut groups: Vec<_> = (0x4E00..=0x9FFF).map(|c| (c, c, 5)).collect();
+        groups.insert(0, (0x61, 0x61, 68));
+        let mut cmap = vec![0, 0, 0, 1];
+        cmap.extend([0, 3, 0, 10].into_iter().chain(12u32.to_be_bytes()));
+        cmap.extend(format_12(0, &groups));
+        let data = with_cmap(cmap);
+
+        let subs = subset(&data, 0, Profile::web(&[5, 68])).unwrap();
+        let dump = debug_dump(&subs, 0).unwrap();
+        assert!(dump.contains("format 13, language 0, records 0/6\n"));
+        assert!(dump.contains("    U+4E00..U+9FFF -> 5 (all)\n"));
+        assert!(dump.contains("    U+0061..U+0061 -> 68\n"));
+
+        // Shapers only look at the preferred subtable, which still maps the
+        // range on its own.
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let subtables = ttf.tables().cmap.unwrap().subtables;
+        let windows = subtables
+            .into_iter()
+            .find(|st| {
+                st.platform_id == ttf_parser::PlatformId::Windows && st.encoding_id == 10
+            })
+            .unwrap();
+        assert_eq!(windows.glyph_index(0x7000).map(|g| g.0), Some(5));
+        assert_eq!(windows.glyph_index(0x61).map(|g| g.0), Some(68));
+    }
+
+    #[test]
+    fn test_subset_from_provider() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::RawFace::parse(&data, 0).unwrap();
+        let tables: Vec<_> = ttf
+            .table_records
+            .into_iter()
+            .map(|record| (Tag(record.tag.to_bytes()), ttf.table(record.tag).unwrap()))
+            .collect();
+
+        let glyphs = [68, 69, 70];
+        assert_eq!(
+            subset(&data, 0, Profile::pdf(&glyphs)).unwrap(),
+            subset_from_provider(tables.as_slice(), Profile::pdf(&glyphs)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_collection() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let latin = std::fs::read("fonts/LatinModernRoman-Regular.otf").unwrap();
+        let regular = subset(&data, 0, Profile::pdf(&[68, 69])).unwrap();
+        let small = subset(&data, 0, Profile::pdf(&[70])).unwrap();
+        let cff = subset(&latin, 0, Profile::pdf(&[1, 2])).unwrap();
+        let ttc = build_collection(&[&regular, &small, &cff]).unwrap();
+
+        // Each face has the tables of its font, and the naming table that
+        // both Noto subsets have is only stored once.
+        assert_eq!(face_count(&ttc), Ok(3));
+        assert_eq!(face_count(&regular), Ok(1));
+        let faces: Vec<_> = (0..3).map(|i| parse(&ttc, i).unwrap()).collect();
+        for (i, font) in [&regular, &small, &cff].into_iter().enumerate() {
+            assert_eq!(faces[i].tables, parse(font, 0).unwrap().tables);
+            assert!(ttf_parser::Face::parse(&ttc, i as u32).is_ok());
+        }
+        let name = |face: &super::Face| face.table(Tag::NAME).unwrap().as_ptr();
+        assert_eq!(name(&faces[0]), name(&faces[1]));
+        assert!(ttc.len() < regular.len() + small.len() + cff.len());
+        assert_eq!(parse(&ttc, 3).err(), Some(Error::InvalidOffset));
+        assert_eq!(build_collection(&[&ttc]), Err(Error::Unsupported));
+
+        // Subsetting the faces of the collection gives the same fonts as
+        // subsetting the original fonts.
+        let faces = [(1, Profile::pdf(&[70])), (2, Profile::pdf(&[1, 2]))];
+        let subs = subset_faces(&ttc, &faces).unwrap();
+        let again = subset(&small, 0, Profile::pdf(&[70])).unwrap();
+        assert_eq!(subs, [again, subset(&cff, 0, Profile::pdf(&[1, 2])).unwrap()]);
+
+        // Subsetting into a collection builds it from the subsets.
+        let fonts =
+            [(&data[..], Profile::pdf(&[68, 69])), (&data[..], Profile::pdf(&[70]))];
+        let built = build_collection(&[&regular, &small]).unwrap();
+        assert_eq!(subset_collection(&fonts).unwrap(), built);
+        let fonts = [(&ttc[..], Profile::pdf(&[70]))];
+        assert_eq!(subset_collection(&fonts), Err(Error::Unsupported));
+    }
+
+    #[test]
+    fn test_mixed_collection() {
+        use super::{replace_faces, subset_in_collection, FontKind};
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let latin = std::fs::read("fonts/LatinModernRoman-Regular.otf").unwrap();
+        let ttc = build_collection(&[&data, &latin, &data]).unwrap();
+        assert!(ttc.len() < 2 * data.len() + latin.len());
+
+        // Subsetting the first face leaves the third one intact, although the
+        // two shared all their tables, and the CFF face keeps its kind.
+        let profile = || Profile::pdf(&[3, 68, 69]);
+        let out = subset_in_collection(&ttc, &[(0, profile())]).unwrap();
+        let faces: Vec<_> = (0..3).map(|i| parse(&out, i).unwrap()).collect();
+        let sub = subset(&data, 0, profile()).unwrap();
+        assert_eq!(faces[0].tables, parse(&sub, 0).unwrap().tables);
+        assert_eq!(faces[1].tables, parse(&latin, 0).unwrap().tables);
+        assert_eq!(faces[2].tables, parse(&data, 0).unwrap().tables);
+        assert_eq!(faces[1].kind(), FontKind::Cff);
+        for i in 0..3 {
+            let offset = u32::read_at(&out, 12 + 4 * i).unwrap() as usize;
+            let kind = FontKind::read_at(&out, offset).unwrap();
+            assert_eq!(kind, [FontKind::TrueType, FontKind::Cff, FontKind::TrueType][i]);
+            assert!(ttf_parser::Face::parse(&out, i as u32).is_ok());
+        }
+
+        // Replacing a face with its own data gives back the collection.
+        let same = replace_faces(&ttc, &[(2, &data)]).unwrap();
+        assert_eq!(same, ttc);
+        assert_eq!(replace_faces(&ttc, &[(3, &data)]), Err(Error::InvalidOffset));
+        assert_eq!(replace_faces(&ttc, &[(0, &ttc)]), Err(Error::Unsupported));
+        assert_eq!(replace_faces(&data, &[]), Err(Error::Unsupported));
+    }
+
+    #[test]
+    fn test_collection_directory() {
+        use super::search_params;
+
+        // A face without tables has an empty directory.
+        let ttc = build_collection(&[&[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]]).unwrap();
+        assert_eq!(ttc.len(), 28);
+        assert_eq!(&ttc[16..28], &[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(search_params(0), (0, 0, 0));
+        assert_eq!(search_params(1), (16, 0, 0));
+        assert_eq!(search_params(15), (128, 3, 112));
+        assert_eq!(search_params(16), (256, 4, 0));
+        assert_eq!(search_params(4095), (32768, 11, 32752));
+        assert_eq!(search_params(4096), (65535, 12, 0));
+        assert_eq!(search_params(u16::MAX), (65535, 15, 65535));
+    }
+
+    #[test]
+    fn test_override_metrics() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let last = ttf.number_of_glyphs() - 1;
+        let glyphs = [3, 68, last];
+
+        let profile = Profile::pdf(&glyphs)
+            .override_metrics(3, MetricsOverride { advance: Some(500), lsb: None })
+            .override_metrics(last, MetricsOverride { advance: Some(700), lsb: Some(5) });
+        let subs = subset(&data, 0, profile).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+
+        let id = ttf_parser::GlyphId;
+        assert_eq!(ttfs.glyph_hor_advance(id(3)), Some(500));
+        assert_eq!(ttfs.glyph_hor_side_bearing(id(3)), ttf.glyph_hor_side_bearing(id(3)));
+        assert_eq!(ttfs.glyph_hor_advance(id(68)), ttf.glyph_hor_advance(id(68)));
+        assert_eq!(ttfs.glyph_hor_advance(id(last)), Some(700));
+        assert_eq!(ttfs.glyph_hor_side_bearing(id(last)), Some(5));
+    }
+
+    #[test]
+    fn test_ensure_space() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let space = ttf.glyph_index(' ').unwrap();
+
+        // The font has a space glyph, so it is kept.
+        let subs = subset(&data, 0, Profile::pdf(&[68]).ensure_space(None)).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert_eq!(ttfs.glyph_index(' '), Some(space));
+        assert_eq!(ttfs.glyph_hor_advance(space), ttf.glyph_hor_advance(space));
+
+        // Without a space, an unused glyph is repurposed. The replacement
+        // cmap only maps a-c.
+        let cmap: Vec<u8> = [
+            0, 1, 3, 1, 0, 12, 4, 32, 0, 4, 4, 1, 0, 0x63, 0xFFFF, 0, 0x61, 0xFFFF,
+            0xFFE3, 1, 0, 0,
+        ]
+        .iter()
+        .flat_map(|n: &u16| n.to_be_bytes())
+        .collect();
+        let mut tables = parse(&data, 0).unwrap().tables;
+        tables.iter_mut().find(|(tag, _)| *tag == Tag::CMAP).unwrap().1 = &cmap;
+        let profile = Profile::pdf(&[68]).ensure_space(Some(321));
+        let subs = subset_from_provider(tables.as_slice(), profile).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let id = ttfs.glyph_index(' ').unwrap();
+        assert_ne!(id, space);
+        assert_eq!(ttfs.glyph_hor_advance(id), Some(321));
+        assert_eq!(ttfs.glyph_name(id), Some("space"));
+        assert_eq!(ttfs.glyph_index('a'), Some(ttf_parser::GlyphId(68)));
+        assert_eq!(ttfs.glyph_index('d'), None);
+    }
+
+    #[test]
+    fn test_synthesize_notdef() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let mut tables = parse(&data, 0).unwrap().tables;
+
+        // Let glyph 1 start where glyph 0 starts, leaving glyph 0 empty.
+        let long = tables.iter().any(|&(tag, t)| tag == Tag::HEAD && t[51] == 1);
+        let mut loca =
+            tables.iter().find(|(tag, _)| *tag == Tag::LOCA).unwrap().1.to_vec();
+        let n = if long { 4 } else { 2 };
+        loca.copy_within(0..n, n);
+        tables.iter_mut().find(|(tag, _)| *tag == Tag::LOCA).unwrap().1 = &loca;
+
+        let subs = subset_from_provider(tables.as_slice(), Profile::pdf(&[68])).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let notdef = ttf_parser::GlyphId(0);
+        let bbox = ttfs.glyph_bounding_box(notdef).unwrap();
+        assert_eq!((bbox.x_min, bbox.y_min, bbox.x_max, bbox.y_max), (50, 0, 450, 700));
+        assert_eq!(ttfs.glyph_hor_advance(notdef), Some(600));
+        assert_eq!(ttfs.glyph_hor_side_bearing(notdef), Some(50));
+        assert!(ttfs.glyph_bounding_box(ttf_parser::GlyphId(68)).is_some());
+    }
+
+    #[test]
+    fn test_version_stamp() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        assert_eq!(read_stamp(&data, 0).unwrap(), None);
+
+        let profile = Profile::pdf(&[68, 69, 70]).version_stamp(true);
+        let subs = subset(&data, 0, profile).unwrap();
+        let stamp = read_stamp(&subs, 0).unwrap().unwrap();
+        assert_eq!(stamp.version, env!("CARGO_PKG_VERSION"));
+
+        let other = subset(&data, 0, Profile::pdf(&[70, 69]).version_stamp(true));
+        let other = read_stamp(&other.unwrap(), 0).unwrap().unwrap();
+        assert_ne!(stamp.plan_hash, other.plan_hash);
+    }
+
+    #[test]
+    fn test_obfuscate() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs: Vec<_> =
+            FEW.chars().filter_map(|c| Some(ttf.glyph_index(c)?.0)).collect();
+
+        let obfuscation = Obfuscation { seed: 42, strip_names: true };
+        let profile = Profile::pdf(&glyphs).obfuscate(obfuscation);
+        let plan = plan(&data, 0, profile).unwrap();
+        assert_eq!(Plan::from_json(&plan.to_json()).unwrap(), plan);
+
+        let map = obfuscation_map(&data, 0, &plan).unwrap();
+        let subs = subset_with_plan(&data, 0, &plan).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        for c in FEW.chars() {
+            let k = map.binary_search_by_key(&c, |&(from, _)| from).unwrap();
+            let obfuscated = map[k].1;
+            assert!(('\u{E000}'..='\u{F8FF}').contains(&obfuscated));
+            assert_eq!(ttfs.glyph_index(obfuscated), ttf.glyph_index(c));
+            assert_eq!(ttfs.glyph_index(c), None);
+        }
+
+        assert_eq!(ttfs.names().len(), 0);
+        assert_eq!(ttfs.glyph_name(ttf.glyph_index('H').unwrap()), None);
+        assert_eq!(map, obfuscation_map(&data, 0, &plan).unwrap());
+    }
+
+    #[test]
+    fn test_cache() {
+        let noto = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let clicker = std::fs::read("fonts/ClickerScript-Regular.ttf").unwrap();
+        let obfuscation = Obfuscation { seed: 7, strip_names: false };
+        let profile = || Profile::pdf(&[68, 69, 70]).obfuscate(obfuscation);
+
+        let mut cache = SubsetCache::new(1024 * 1024);
+        for _ in 0..2 {
+            assert_eq!(
+                cache.subset(&noto, 0, profile()).unwrap(),
+                subset(&noto, 0, profile()).unwrap()
+            );
+        }
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+        assert!(stats.bytes > 0);
+
+        // Fonts identified by the caller are keyed apart from hashed ones.
+        for _ in 0..2 {
+            assert_eq!(
+                cache.subset_by_id(1, &noto, 0, profile()).unwrap(),
+                subset(&noto, 0, profile()).unwrap()
+            );
+        }
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 2, 2));
+
+        // With room for only one font's artifacts, the least recently used one
+        // is evicted.
+        let capacity = stats.bytes / 2;
+        let mut cache = SubsetCache::new(capacity);
+        cache.subset(&noto, 0, profile()).unwrap();
+        cache.subset(&clicker, 0, profile()).unwrap();
+        cache.subset(&noto, 0, profile()).unwrap();
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 3));
+        assert!(stats.evictions >= 2);
+        assert!(stats.bytes <= capacity);
+    }
+
+    #[test]
+    fn test_drop_tables() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let profile = Profile::pdf(&[68, 69]).drop_tables(&[Tag::NAME, Tag::POST]);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(computed.table(Tag::NAME), TableAction::Drop);
+        assert_eq!(computed.table(Tag::POST), TableAction::Drop);
+        assert_eq!(computed.table(Tag::OS2), TableAction::Subset);
+
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        let face = parse(&subs, 0).unwrap();
+        assert!(face.table(Tag::NAME).is_none());
+        assert!(face.table(Tag::POST).is_none());
+        assert!(face.table(Tag::OS2).is_some());
+    }
+
+    #[test]
+    fn test_convert_morx() {
+        fn words(w: &mut Writer, values: impl IntoIterator<Item = u16>) {
+            values.into_iter().for_each(|value| w.write::<u16>(value));
+        }
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let id = |c| ttf.glyph_index(c).unwrap().0;
+        let [f, i, fi, a, b] = ['f', 'i', '\u{FB01}', 'a', 'b'].map(id);
+
+        // A ligature subtable that forms `fi`, with the classes, states,
+        // entries, actions, components and ligatures.
+        let mut lig = Writer::new();
+        for offset in [6, 28, 48, 84, 104, 112, 114] {
+            lig.write::<u32>(offset);
+        }
+        words(&mut lig, [6, 4, 2, 8, 1, 0, f, 4, i, 5]);
+        words(&mut lig, [0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 2]);
+        words(&mut lig, [0, 0, 0, 2, 0x8000, 0, 0, 0xA000, 0, 0]);
+        lig.write::<u32>(0u32.wrapping_sub(i.into()) & 0x3FFF_FFFF);
+        lig.write::<u32>(0x8000_0000 | (0u32.wrapping_sub(f.into()) & 0x3FFF_FFFF));
+        words(&mut lig, [0, fi]);
+        let lig = lig.finish();
+
+        // A chain whose feature enables the ligatures as common ligatures,
+        // followed by a noncontextual subtable from `a` to `b`.
+        let mut w = Writer::new();
+        words(&mut w, [2, 0]);
+        for value in [1, 3, 16 + 12 + 12 + lig.len() as u32 + 28, 1, 2] {
+            w.write::<u32>(value);
+        }
+        words(&mut w, [1, 2]);
+        for value in [1, !1, 12 + lig.len() as u32, 2, 1] {
+            w.write::<u32>(value);
+        }
+        w.give(&lig);
+        for value in [28, 4, 2] {
+            w.write::<u32>(value);
+        }
+        words(&mut w, [6, 4, 1, 4, 0, 0, a, b]);
+
+        let face = parse(&data, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .filter(|&&(tag, _)| tag != Tag::GSUB)
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.push((Tag::MORX, Cow::Owned(w.finish())));
+        let data = super::construct(face.kind(), tables, 4);
+
+        let glyphs = [f, i, a];
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        assert_eq!(computed.glyphs(), [0, a, f, i]);
+        let computed = plan(&data, 0, Profile::pdf(&glyphs).convert_morx(true)).unwrap();
+        assert_eq!(computed.glyphs(), [0, a, b, f, i, fi]);
+        assert_eq!(Plan::from_json(&computed.to_json()), Ok(computed.clone()));
+
+        // The converted features produce the same glyphs, also when the glyphs
+        // are renumbered.
+        let features = [Tag(*b"ccmp"), Tag(*b"liga")];
+        for remap in [false, true] {
+            let profile = Profile::pdf(&glyphs).convert_morx(true).remap_glyphs(remap);
+            let computed = plan(&data, 0, profile).unwrap();
+            let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+            let new = |old| map.iter().find(|&&(o, _)| o == old).unwrap().1;
+            let glyphs = glyphs.map(new);
+            let profile = Profile::pdf(&glyphs).layout_closure(&features);
+            let added = closure_by_feature(&subs, 0, &profile).unwrap();
+            assert_eq!(
+                added,
+                [(features[0], vec![new(b)]), (features[1], vec![new(fi)])]
+            );
+        }
+    }
+
+    #[test]
+    fn test_keep_aat() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.push((Tag::MORX, Cow::Owned(vec![0, 2, 0, 0, 0, 0, 0, 0])));
+        let data = super::construct(face.kind(), tables, 4);
+
+        let glyphs = [68, 69, 70];
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        assert_eq!(computed.table(Tag::MORX), TableAction::Drop);
+        assert!(computed.glyphs().len() < 10);
+
+        let computed = plan(&data, 0, Profile::pdf(&glyphs).keep_aat(true)).unwrap();
+        assert_eq!(computed.table(Tag::MORX), TableAction::Copy);
+        let (subs, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+        assert_eq!(warnings[0], Warning::AllGlyphsKept(Tag::MORX));
+        assert_eq!(warnings[0].severity(), Severity::Warning);
+
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert_eq!(computed.glyphs().len(), ttf.number_of_glyphs() as usize);
+        assert_eq!(
+            ttfs.raw_face()
+                .table(ttf_parser::Tag::from_bytes(b"morx"))
+                .unwrap()
+                .len(),
+            8
+        );
+        for id in 0..ttf.number_of_glyphs() {
+            let id = ttf_parser::GlyphId(id);
+            assert_eq!(ttfs.glyph_bounding_box(id), ttf.glyph_bounding_box(id));
+        }
+    }
+
+    #[test]
+    fn test_passthrough_tables() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        let mytb = Tag(*b"MYTB");
+        tables.push((mytb, Cow::Owned(vec![1, 2, 3, 4, 5])));
+        let data = super::construct(face.kind(), tables, 4);
+
+        let glyphs = [68, 69, 70];
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        assert_eq!(computed.table(mytb), TableAction::Drop);
+
+        // Known tables can't be passed through.
+        let profile = Profile::pdf(&glyphs).passthrough_tables(&[mytb, Tag::GSUB]);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(computed.table(mytb), TableAction::Copy);
+        assert_eq!(computed.table(Tag::GSUB), TableAction::Drop);
+        assert_eq!(Plan::from_json(&computed.to_json()).unwrap(), computed);
+
+        let (subs, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+        assert!(warnings.contains(&Warning::TablePassedThrough(mytb)));
+        assert!(!warnings.iter().any(|w| matches!(w, Warning::UnknownTableCopied(_))));
+        let (_, report) = subset_with_report(&data, 0, &computed).unwrap();
+        assert_eq!(report.passthrough, [mytb]);
+        assert!(report.to_json().contains(r#""passthrough": ["MYTB"]"#));
+
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let table = ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"MYTB"));
+        assert_eq!(table, Some(&[1, 2, 3, 4, 5][..]));
+
+        // The table doesn't depend on glyph IDs, so renumbering keeps it.
+        let profile =
+            Profile::pdf(&glyphs).passthrough_tables(&[mytb]).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+        assert!(!warnings.contains(&Warning::TableDropped(mytb)));
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert_eq!(ttf.number_of_glyphs(), 4);
+        let table = ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"MYTB"));
+        assert_eq!(table, Some(&[1, 2, 3, 4, 5][..]));
+
+        // Dropping wins over passing through.
+        let profile =
+            Profile::pdf(&glyphs).passthrough_tables(&[mytb]).drop_tables(&[mytb]);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(computed.table(mytb), TableAction::Drop);
+    }
+
+    #[test]
+    fn test_glyph_referencing_tables() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.push((Tag::MORX, Cow::Owned(vec![0, 2, 0, 0, 0, 0, 0, 0])));
+        tables.push((Tag::ZAPF, Cow::Owned(vec![0, 2, 0, 0, 0, 0, 0, 0])));
+        let data = super::construct(face.kind(), tables, 4);
+        let glyphs = [68, 69, 70];
+
+        // Shaping tables are dropped when the glyphs are renumbered, so they
+        // don't keep all glyphs either.
+        let profile = Profile::pdf(&glyphs).keep_aat(true).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(computed.table(Tag::MORX), TableAction::Drop);
+        let (_, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+        assert!(warnings.contains(&Warning::TableDropped(Tag::MORX)));
+        assert!(computed.glyphs().len() < 10);
+
+        // Plans that copy a glyph referencing table are only honored if the
+        // glyph IDs are preserved.
+        for remap in [false, true] {
+            let profile = Profile::pdf(&glyphs).remap_glyphs(remap);
+            let json = plan(&data, 0, profile).unwrap().to_json();
+            let json = json.replace(r#""Zapf": "drop""#, r#""Zapf": "copy""#);
+            let computed = Plan::from_json(&json).unwrap();
+            assert_eq!(computed.table(Tag::ZAPF), TableAction::Copy);
+            let (subs, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+            let zapf = parse(&subs, 0).unwrap().table(Tag::ZAPF).is_some();
+            assert_eq!(zapf, !remap);
+            assert_eq!(warnings.contains(&Warning::TableDropped(Tag::ZAPF)), remap);
+        }
+    }
+
+    #[test]
+    fn test_minimal() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs: Vec<_> =
+            FEW.chars().filter_map(|c| Some(ttf.glyph_index(c)?.0)).collect();
+        let tags = |data| -> Vec<Tag> {
+            parse(data, 0).unwrap().tables.iter().map(|&(tag, _)| tag).collect()
+        };
+
+        let subs = subset(&data, 0, Profile::pdf(&glyphs).minimal(false)).unwrap();
+        assert_eq!(
+            tags(&subs),
+            [Tag::CMAP, Tag::GLYF, Tag::HEAD, Tag::HHEA, Tag::HMTX, Tag::LOCA, Tag::MAXP]
+        );
+
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        for c in FEW.chars() {
+            let id = ttf.glyph_index(c).unwrap();
+            assert_eq!(ttfs.glyph_index(c), Some(id));
+            assert_eq!(ttfs.glyph_bounding_box(id), ttf.glyph_bounding_box(id));
+            assert_eq!(ttfs.glyph_hor_advance(id), ttf.glyph_hor_advance(id));
+        }
+
+        let subs = subset(&data, 0, Profile::pdf(&glyphs).minimal(true)).unwrap();
+        assert!(tags(&subs).contains(&Tag::OS2));
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let ids: Vec<_> = ttfs.names().into_iter().map(|name| name.name_id).collect();
+        assert!(ids.contains(&1) && ids.iter().all(|id| (1..=6).contains(id)));
+        assert_eq!(ttfs.glyph_name(ttf.glyph_index('H').unwrap()), None);
+    }
+
+    #[test]
+    fn test_name_options() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let profile = Profile::pdf(&[68])
+            .prune_names(true)
+            .name_languages(&[0x409])
+            .family_suffix("Subset");
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(Plan::from_json(&computed.to_json()).unwrap(), computed);
+        assert!(computed.to_json().contains(r#""family_suffix": "Subset""#));
+
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let names: Vec<_> = ttfs.names().into_iter().collect();
+        assert!(names.iter().all(|name| name.language_id == 0x409));
+        assert!(!names.iter().any(|name| (10..=14).contains(&name.name_id)));
+        let name = |id| names.iter().find(|name| name.name_id == id)?.to_string();
+        assert_eq!(name(1).unwrap(), "Noto Sans Subset");
+        assert_eq!(name(2).unwrap(), "Regular");
+        assert_eq!(name(3).unwrap(), "2.007;GOOG;NotoSansSubset-Regular");
+        assert_eq!(name(4).unwrap(), "Noto Sans Subset Regular");
+        assert_eq!(name(6).unwrap(), "NotoSansSubset-Regular");
+        assert!(name(7).is_some() && name(256).is_some());
+
+        // Without any name options, the table is copied.
+        let table =
+            |data: &[u8]| parse(data, 0).unwrap().table(Tag::NAME).unwrap().to_vec();
+        let plain = subset(&data, 0, Profile::pdf(&[68])).unwrap();
+        assert_eq!(table(&plain), table(&data));
+        assert!(table(&subs).len() < table(&data).len());
+    }
+
+    #[test]
+    fn test_feature_names() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let ids = |glyphs: &[u16]| {
+            let profile = Profile::pdf(glyphs).minimal(true).keep_gsub(true);
+            let subs = subset(&data, 0, profile).unwrap();
+            let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+            let mut ids: Vec<_> =
+                ttfs.names().into_iter().map(|name| name.name_id).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            ids
+        };
+
+        // The stylistic sets keep their names along with the essential ones.
+        let all: Vec<u16> = (0..ttf.number_of_glyphs()).collect();
+        assert_eq!(ids(&all), [1, 2, 3, 4, 5, 6, 256, 257, 258, 259]);
+
+        // Without the glyphs that the stylistic sets substitute, the sets and
+        // their names are dropped.
+        assert_eq!(ids(&[ttf.glyph_index('A').unwrap().0]), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_strip_glyph_names() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let h = ttf.glyph_index('H').unwrap();
+        let post =
+            |data: &[u8]| parse(data, 0).unwrap().table(Tag::POST).unwrap().to_vec();
+
+        let plain = subset(&data, 0, Profile::pdf(&[h.0])).unwrap();
+        let ttfs = ttf_parser::Face::parse(&plain, 0).unwrap();
+        assert_eq!(ttfs.glyph_name(h), Some("H"));
+        assert_eq!(post(&plain)[..4], [0, 2, 0, 0]);
+
+        let computed =
+            plan(&data, 0, Profile::pdf(&[h.0]).strip_glyph_names(true)).unwrap();
+        assert_eq!(Plan::from_json(&computed.to_json()).unwrap(), computed);
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert_eq!(ttfs.glyph_name(h), None);
+        assert_eq!(post(&subs)[..4], [0, 3, 0, 0]);
+        assert_eq!(post(&subs)[4..], post(&data)[4..32]);
+    }
+
+    #[test]
+    fn test_strip_hinting() {
+        let data = std::fs::read("fonts/ClickerScript-Regular.ttf").unwrap();
+        let hinted = subset(&data, 0, Profile::pdf(&[68, 69])).unwrap();
+        let profile = Profile::pdf(&[68, 69]).strip_hinting(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(Plan::from_json(&computed.to_json()).unwrap(), computed);
+
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        let tables = parse(&subs, 0).unwrap().tables;
+        let has = |tag| tables.iter().any(|&(t, _)| t == tag);
+        assert!(has(Tag::NAME) && has(Tag::GLYF));
+        assert!(![Tag::CVT, Tag::FPGM, Tag::PREP, Tag::GASP].into_iter().any(has));
+        assert!(subs.len() < hinted.len());
+
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        for id in [68, 69].map(ttf_parser::GlyphId) {
+            assert_eq!(ttfs.glyph_bounding_box(id), ttf.glyph_bounding_box(id));
+        }
+
+        // The instruction limits are reset, the glyph limits are kept.
+        let table =
+            |data: &[u8], tag| parse(data, 0).unwrap().table(tag).unwrap().to_vec();
+        let (maxp, hinted_maxp) = (table(&subs, Tag::MAXP), table(&hinted, Tag::MAXP));
+        assert_ne!(hinted_maxp[14..28], [0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(maxp[14..28], [0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(maxp[..14], hinted_maxp[..14]);
+        assert_eq!(maxp[28..], hinted_maxp[28..]);
+        assert_eq!(table(&subs, Tag::HEAD)[17] & 0x10, 0);
+    }
+
+    #[test]
+    fn test_padding() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let glyphs = [3, 68, 69];
+        let aligned = subset(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        let source = Profile::pdf(&glyphs).padding(Padding::Source);
+        assert_eq!(subset(&data, 0, source).unwrap(), aligned);
+
+        let minimal = Profile::pdf(&glyphs).padding(Padding::Minimal);
+        let minimal = subset(&data, 0, minimal).unwrap();
+        assert!(minimal.len() < aligned.len());
+        let ttfs = ttf_parser::Face::parse(&minimal, 0).unwrap();
+        assert!(ttfs.glyph_bounding_box(ttf_parser::GlyphId(68)).is_some());
+
+        // Re-subsetting an unpadded font keeps it unpadded.
+        let source = Profile::pdf(&glyphs).padding(Padding::Source);
+        assert_eq!(subset(&minimal, 0, source).unwrap(), minimal);
+    }
+
+    #[test]
+    fn test_provenance() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let profile = Profile::pdf(&[68, 69, 70]).version_stamp(true);
+        let plan = plan(&data, 0, profile).unwrap();
+        let (subs, provenance) = subset_with_provenance(&data, 0, &plan).unwrap();
+        assert_eq!(subs, subset_with_plan(&data, 0, &plan).unwrap());
+
+        let origin = |tag| {
+            let table = provenance.tables.iter().find(|t| t.tag == tag).unwrap();
+            assert_eq!(
+                &subs[table.output.clone()],
+                parse(&subs, 0).unwrap().table(tag).unwrap()
+            );
+            table.origin.clone()
+        };
+        let source = |tag| {
+            let table = parse(&data, 0).unwrap().table(tag).unwrap();
+            let start = table.as_ptr() as usize - data.as_ptr() as usize;
+            start..start + table.len()
+        };
+
+        assert_eq!(origin(Tag::CMAP), Origin::Verbatim(source(Tag::CMAP)));
+        assert_eq!(origin(Tag::GLYF), Origin::Rewritten(source(Tag::GLYF)));
+        assert_eq!(origin(Tag::SUBS), Origin::Synthesized);
+        assert!(provenance.to_json().contains(r#""origin": "synthesized""#));
+
+        let glyphs: Vec<_> = provenance.glyphs.iter().map(|&(glyph, _)| glyph).collect();
+        assert_eq!(glyphs, [0, 68, 69, 70]);
+        let glyf = super::glyf::Table::new(&parse(&subs, 0).unwrap()).unwrap();
+        for &(glyph, size) in &provenance.glyphs {
+            assert_eq!(size, glyf.glyph_data(glyph).unwrap().len());
+            assert!(size > 0);
+        }
+        assert!(provenance.to_json().contains(r#""68": "#));
+
+        let data = std::fs::read("fonts/LatinModernRoman-Regular.otf").unwrap();
+        let plan = super::plan(&data, 0, Profile::pdf(&[68, 69])).unwrap();
+        let (_, provenance) = subset_with_provenance(&data, 0, &plan).unwrap();
+        let glyphs: Vec<_> = provenance.glyphs.iter().map(|&(glyph, _)| glyph).collect();
+        assert_eq!(glyphs, [0, 68, 69]);
+        assert!(provenance.glyphs.iter().all(|&(_, size)| size > 1));
+    }
+
+    #[test]
+    fn test_ccmp_closure() {
+        // In Noto Sans, `ccmp` replaces i with a dotless i before a combining
+        // acute accent.
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let glyphs = [76, 2663];
+        let computed = plan(&data, 0, Profile::web(&glyphs)).unwrap();
+        assert!(computed.glyphs().contains(&1766));
+        assert!(computed.to_json().contains(r#""ccmp_closure": true"#));
+        assert_eq!(Plan::from_json(&computed.to_json()).unwrap(), computed);
+
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        assert!(!computed.glyphs().contains(&1766));
+        let profile = Profile::web(&glyphs).ccmp_closure(false);
+        assert!(!plan(&data, 0, profile).unwrap().glyphs().contains(&1766));
+        let profile = Profile::pdf(&glyphs).ccmp_closure(true);
+        assert!(plan(&data, 0, profile).unwrap().glyphs().contains(&1766));
+
+        // A truncated GSUB table ends the closure early with a warning.
+        let gsub = parse(&data, 0).unwrap().table(Tag::GSUB).unwrap()[..64].to_vec();
+        let data = with_tables(vec![(Tag::GSUB, gsub)]);
+        let profile = Profile::pdf(&glyphs).ccmp_closure(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (_, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+        assert_eq!(warnings, [Warning::ClosureIncomplete(Tag::GSUB)]);
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        let (_, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_layout_closure() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs = ['f', 'i', 'l'].map(|c| ttf.glyph_index(c).unwrap().0);
+        let liga = Tag(*b"liga");
+        let profile = Profile::pdf(&glyphs).layout_closure(&[liga, liga]);
+        let computed = plan(&data, 0, profile).unwrap();
+        let names: Vec<_> = computed
+            .glyphs()
+            .iter()
+            .filter_map(|&glyph| ttf.glyph_name(ttf_parser::GlyphId(glyph)))
+            .collect();
+        assert_eq!(
+            names,
+            [".notdef", "f", "i", "l", "f_f", "fi", "fl", "f_f_i", "f_f_l"]
+        );
+        assert!(computed.to_json().contains(r#""layout_closure": ["liga"]"#));
+        assert_eq!(Plan::from_json(&computed.to_json()).unwrap(), computed);
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        assert_eq!(computed.glyphs().len(), 4);
+
+        // The added glyphs are grouped by the feature that produced them.
+        let smcp = Tag(*b"smcp");
+        let profile = Profile::pdf(&glyphs).layout_closure(&[smcp, liga]);
+        let added = closure_by_feature(&data, 0, &profile).unwrap();
+        let names: Vec<(Tag, Vec<_>)> = added
+            .iter()
+            .map(|(tag, glyphs)| {
+                let names = glyphs
+                    .iter()
+                    .filter_map(|&glyph| ttf.glyph_name(ttf_parser::GlyphId(glyph)))
+                    .collect();
+                (*tag, names)
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (liga, vec!["f_f", "fi", "fl", "f_f_i", "f_f_l"]),
+                (smcp, vec!["f.sc", "i.sc", "l.sc"]),
+            ]
+        );
+
+        // Stylistic alternates of small capitals are only kept if `salt`
+        // may follow `smcp`.
+        let glyphs = ['i', 'j'].map(|c| ttf.glyph_index(c).unwrap().0);
+        let salt = Tag(*b"salt");
+        let profile = Profile::pdf(&glyphs).layout_closure(&[smcp, salt]);
+        let added = closure_by_feature(&data, 0, &profile).unwrap();
+        assert_eq!(added.iter().map(|&(tag, _)| tag).collect::<Vec<_>>(), [salt, smcp]);
+        let profile = profile.strict_closure(&[salt]);
+        let added = closure_by_feature(&data, 0, &profile).unwrap();
+        assert_eq!(added.iter().map(|&(tag, _)| tag).collect::<Vec<_>>(), [smcp]);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert!(computed.to_json().contains(r#""strict_closure": ["salt"]"#));
+        assert_eq!(Plan::from_json(&computed.to_json()).unwrap(), computed);
+    }
+
+    #[test]
+    fn test_layout_features() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let features = layout_features(&data, 0).unwrap();
+        let face = parse(&data, 0).unwrap();
+        let find = |table, tag: &[u8; 4]| {
+            features
+                .iter()
+                .find(|f| f.table == table && f.tag == Tag(*tag))
+                .unwrap()
+        };
+        let (liga, kern) = (find(Tag::GSUB, b"liga"), find(Tag::GPOS, b"kern"));
+        assert!(liga.lookups >= 1 && kern.lookups >= 1);
+        assert!(kern.size > liga.size);
+        for feature in &features {
+            assert!(feature.size <= face.table(feature.table).unwrap().len());
+        }
+        assert!(features
+            .windows(2)
+            .all(|w| (w[0].table != Tag::GSUB, w[0].tag)
+                < (w[1].table != Tag::GSUB, w[1].tag)));
+
+        // Every feature that a language system enables is listed.
+        let scripts = layout_scripts(&data, 0).unwrap();
+        let latin = scripts
+            .iter()
+            .find(|s| {
+                s.table == Tag::GSUB && s.script == Tag(*b"latn") && s.language.is_none()
+            })
+            .unwrap();
+        assert!(latin.features.contains(&Tag(*b"liga")));
+        assert!(scripts.iter().any(|s| s.language == Some(Tag(*b"ROM "))));
+        for script in &scripts {
+            for tag in &script.features {
+                assert!(features
+                    .iter()
+                    .any(|f| f.table == script.table && f.tag == *tag));
+            }
+        }
+
+        let subs = subset(&data, 0, Profile::pdf(&[68])).unwrap();
+        assert_eq!(layout_features(&subs, 0).unwrap(), []);
+    }
+
+    #[test]
+    fn test_variant_glyphs() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let name = |glyph: Option<u16>| {
+            glyph.map(|glyph| ttf.glyph_name(ttf_parser::GlyphId(glyph)).unwrap())
+        };
+        let smcp = [Tag(*b"smcp")];
+        let salt = [Tag(*b"salt"), Tag(*b"ss04")];
+        let requests: [(char, &[Tag]); 5] =
+            [('i', &[]), ('i', &smcp), ('I', &salt), ('I', &smcp), ('\u{E000}', &smcp)];
+        let variants = variant_glyphs(&data, 0, &requests).unwrap();
+        assert_eq!(
+            variants.into_iter().map(name).collect::<Vec<_>>(),
+            [Some("i"), Some("i.sc"), Some("I.salt"), Some("I"), None]
+        );
+    }
+
+    #[test]
+    fn test_alternates() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let name = |glyph| ttf.glyph_name(ttf_parser::GlyphId(glyph)).unwrap();
+        let names = |alternates: &Alternates| -> Vec<(String, Vec<&str>)> {
+            alternates
+                .features
+                .iter()
+                .map(|(tag, glyphs)| {
+                    (tag.to_string(), glyphs.iter().map(|&glyph| name(glyph)).collect())
+                })
+                .collect()
+        };
+
+        let all = alternates(&data, 0).unwrap();
+        assert!(all.windows(2).all(|w| w[0].codepoint < w[1].codepoint));
+        let i = all.iter().find(|a| a.codepoint == 'I' as u32).unwrap();
+        assert_eq!(i.glyph, ttf.glyph_index('I').unwrap().0);
+        assert_eq!(
+            names(i),
+            [
+                ("aalt".into(), vec!["I.salt", "i.sc"]),
+                ("salt".into(), vec!["I.salt"]),
+                ("ss04".into(), vec!["I.salt"]),
+            ]
+        );
+        assert!(all.iter().all(|a| a.codepoint != ' ' as u32));
+
+        // Only the alternates that survived subsetting are listed.
+        let glyphs = ['I', 'K'].map(|c| ttf.glyph_index(c).unwrap().0);
+        let profile =
+            Profile::web(&glyphs).keep_gsub(true).layout_closure(&[Tag(*b"salt")]);
+        let subs = subset(&data, 0, profile).unwrap();
+        let all = alternates(&subs, 0).unwrap();
+        assert!(all.iter().all(|a| a.glyph == glyphs[0]));
+        let i = all.iter().find(|a| a.codepoint == 'I' as u32).unwrap();
+        assert_eq!(
+            names(i),
+            [
+                ("aalt".into(), vec!["I.salt"]),
+                ("salt".into(), vec!["I.salt"]),
+                ("ss04".into(), vec!["I.salt"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keep_gsub() {
+        use ttf_parser::gsub::SubstitutionSubtable;
+
+        // The ligatures of a subsetted font's `liga` feature, as the glyph IDs
+        // of their components and the ligature glyph.
+        fn ligatures(data: &[u8]) -> Vec<(Vec<u16>, u16)> {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            let gsub = ttf.tables().gsub.unwrap();
+            let liga = gsub.features.find(ttf_parser::Tag::from_bytes(b"liga")).unwrap();
+            let mut ligatures = vec![];
+            for index in liga.lookup_indices {
+                let lookup = gsub.lookups.get(index).unwrap();
+                for i in 0..lookup.subtables.len() {
+                    let Some(SubstitutionSubtable::Ligature(subtable)) =
+                        lookup.subtables.get(i)
+                    else {
+                        continue;
+                    };
+                    for first in 0..ttf.number_of_glyphs() {
+                        let Some(set) = subtable
+                            .coverage
+                            .get(ttf_parser::GlyphId(first))
+                            .and_then(|i| subtable.ligature_sets.get(i))
+                        else {
+                            continue;
+                        };
+                        for ligature in set {
+                            let mut glyphs = vec![first];
+                            glyphs.extend(ligature.components.into_iter().map(|g| g.0));
+                            ligatures.push((glyphs, ligature.glyph.0));
+                        }
+                    }
+                }
+            }
+            ligatures.sort();
+            ligatures
+        }
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let id = |c| ttf.glyph_index(c).unwrap().0;
+        let glyphs = ['f', 'i', 'l'].map(id);
+        let profile =
+            Profile::pdf(&glyphs).layout_closure(&[Tag(*b"liga")]).keep_gsub(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(computed.table(Tag::GSUB), TableAction::Subset);
+        assert_eq!(computed.table(Tag::GDEF), TableAction::Subset);
+
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        let before = ligatures(&data);
+        let after = ligatures(&subs);
+        let retained = |glyph| computed.glyphs().contains(&glyph);
+        let expected: Vec<_> = before
+            .into_iter()
+            .filter(|(glyphs, ligature)| {
+                glyphs.iter().all(|&g| retained(g)) && retained(*ligature)
+            })
+            .collect();
+        assert_eq!(after, expected);
+        assert!(after.iter().any(|(components, _)| *components == [id('f'), id('i')]));
+
+        // Renumbered glyphs keep their ligatures under the new IDs.
+        let profile = Profile::pdf(&glyphs)
+            .layout_closure(&[Tag(*b"liga")])
+            .keep_gsub(true)
+            .remap_glyphs(true);
+        let remap_plan = plan(&data, 0, profile).unwrap();
+        let (remapped, map) = subset_with_glyph_map(&data, 0, &remap_plan).unwrap();
+        let new = |old| map.iter().find(|&&(o, _)| o == old).unwrap().1;
+        let mut renumbered: Vec<_> = expected
+            .iter()
+            .map(|(components, ligature)| {
+                (components.iter().map(|&g| new(g)).collect::<Vec<_>>(), new(*ligature))
+            })
+            .collect();
+        renumbered.sort();
+        assert_eq!(ligatures(&remapped), renumbered);
+
+        // Only the features that still apply remain.
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let gsub = ttfs.tables().gsub.unwrap();
+        assert!(gsub.features.len() < ttf.tables().gsub.unwrap().features.len());
+        let size = |ttf: &ttf_parser::Face| {
+            ttf.raw_face()
+                .table(ttf_parser::Tag::from_bytes(b"GSUB"))
+                .unwrap()
+                .len()
+        };
+        assert!(size(&ttfs) * 10 < size(&ttf));
+        for script in gsub.scripts {
+            for lang in script.default_language.into_iter().chain(script.languages) {
+                assert!(lang
+                    .feature_indices
+                    .into_iter()
+                    .all(|i| i < gsub.features.len()));
+            }
+        }
+
+        // Larger tables with contextual and extension lookups stay readable.
+        for (path, chars) in [
+            ("fonts/NotoSansCJKsc-Regular.otf", "一二三（）「」"),
+            ("fonts/LatinModernRoman-Regular.otf", "fifl"),
+            ("fonts/NewCMMath-Regular.otf", "x+y"),
+        ] {
+            let data = std::fs::read(path).unwrap();
+            let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+            let glyphs: Vec<u16> =
+                chars.chars().map(|c| ttf.glyph_index(c).unwrap().0).collect();
+            let profile = Profile::web(&glyphs).keep_gsub(true);
+            let subs = subset(&data, 0, profile).unwrap();
+            let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+            let gsub = ttfs.tables().gsub.unwrap();
+            for i in 0..gsub.lookups.len() {
+                let lookup = gsub.lookups.get(i).unwrap();
+                for j in 0..lookup.subtables.len() {
+                    assert!(lookup.subtables.get::<SubstitutionSubtable>(j).is_some());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_lookups() {
+        // Give the first subtable of the first `liga` lookup of Noto Sans an
+        // unknown format.
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let liga = ttf.tables().gsub.unwrap().features;
+        let liga = liga.find(ttf_parser::Tag::from_bytes(b"liga")).unwrap();
+        let index = liga.lookup_indices.get(0).unwrap();
+        let face = parse(&data, 0).unwrap();
+        let mut gsub = face.table(Tag::GSUB).unwrap().to_vec();
+        let read = |gsub: &[u8], at: usize| usize::from(u16::read_at(gsub, at).unwrap());
+        let list = read(&gsub, 8);
+        let lookup = list + read(&gsub, list + 2 + 2 * usize::from(index));
+        let mut at = lookup + read(&gsub, lookup + 6);
+        if read(&gsub, lookup) == 7 {
+            at += u32::read_at(&gsub, at + 4).unwrap() as usize;
+        }
+        gsub[at..at + 2].copy_from_slice(&9_u16.to_be_bytes());
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.iter_mut().find(|(tag, _)| *tag == Tag::GSUB).unwrap().1 =
+            gsub.clone().into();
+        let data = super::construct(face.kind(), tables, 4);
+
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs: Vec<u16> =
+            ('0'..='z').filter_map(|c| Some(ttf.glyph_index(c)?.0)).collect();
+        let subset = |policy| {
+            let profile = Profile::pdf(&glyphs)
+                .layout_closure(&[Tag(*b"liga")])
+                .keep_gsub(true)
+                .unsupported_lookups(policy);
+            let plan = plan(&data, 0, profile)?;
+            assert_eq!(Plan::from_json(&plan.to_json()), Ok(plan.clone()));
+            subset_with_warnings(&data, 0, &plan)
+        };
+        let warning = Warning::UnsupportedLookup(Tag::GSUB, index, 4);
+        let gsub_of =
+            |data: &[u8]| parse(data, 0).unwrap().table(Tag::GSUB).unwrap().to_vec();
+
+        assert_eq!(subset(LookupPolicy::Fail), Err(Error::Unsupported));
+
+        let (subs, warnings) = subset(LookupPolicy::KeepTable).unwrap();
+        assert!(warnings.contains(&warning));
+        assert_eq!(gsub_of(&subs), gsub);
+
+        // The ligature lookup is gone, but the other lookups are subsetted.
+        let (subs, warnings) = subset(LookupPolicy::DropLookup).unwrap();
+        assert!(warnings.contains(&warning));
+        let sub_gsub = gsub_of(&subs);
+        assert!(sub_gsub.len() * 10 < gsub.len());
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let gsubs = ttfs.tables().gsub.unwrap();
+        assert!(!gsubs.lookups.is_empty());
+        assert!(gsubs.lookups.into_iter().all(|lookup| {
+            (0..lookup.subtables.len()).all(|i| {
+                !matches!(
+                    lookup.subtables.get(i),
+                    Some(ttf_parser::gsub::SubstitutionSubtable::Ligature(_))
+                )
+            })
+        }));
+        assert_eq!(
+            warning.to_string(),
+            format!("lookup {index} of table GSUB has unsupported type 4")
+        );
+    }
+
+    #[test]
+    fn test_merge_single_substitutions() {
+        // A `GSUB` table without scripts and features with a lookup of two
+        // single substitution subtables that both cover glyph 5.
+        let mut w = Writer::new();
+        for value in [1, 0, 10, 12, 14, 0, 0, 1, 4, 1, 0, 2, 10, 22] {
+            w.write::<u16>(value);
+        }
+        for value in [1, 6, 100, 1, 1, 5, 1, 6, 200, 1, 2, 5, 6] {
+            w.write::<u16>(value);
+        }
+        let data = with_tables(vec![(Tag::GSUB, w.finish())]);
+        let glyphs: Vec<u16> = (0..300).collect();
+        let subs = subset(&data, 0, Profile::pdf(&glyphs).keep_gsub(true)).unwrap();
+
+        // The subtables are merged, and the first one still takes precedence.
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let lookup = ttf.tables().gsub.unwrap().lookups.get(0).unwrap();
+        assert_eq!(lookup.subtables.len(), 1);
+        let Some(ttf_parser::gsub::SubstitutionSubtable::Single(
+            ttf_parser::gsub::SingleSubstitution::Format2 { coverage, substitutes },
+        )) = lookup.subtables.get(0)
+        else {
+            panic!("expected a single substitution with format 2");
+        };
+        let output = |glyph| substitutes.get(coverage.get(ttf_parser::GlyphId(glyph))?);
+        assert_eq!(output(5), Some(ttf_parser::GlyphId(105)));
+        assert_eq!(output(6), Some(ttf_parser::GlyphId(206)));
+    }
+
+    #[test]
+    fn test_layout_overflow() {
+        fn words(w: &mut Writer, values: impl IntoIterator<Item = u16>) {
+            values.into_iter().for_each(|value| w.write::<u16>(value));
+        }
+
+        // A `GSUB` table without scripts and features whose lookups of the
+        // given types have one subtable each, behind extension subtables.
+        fn gsub(lookups: &[(u16, Vec<u8>)]) -> Vec<u8> {
+            let mut w = Writer::new();
+            words(&mut w, [1, 0, 10, 12, 14]);
+            words(&mut w, [0, 0]);
+            let n = lookups.len();
+            w.write::<u16>(n as u16);
+            for i in 0..n {
+                w.write::<u16>((2 + 2 * n + 8 * i) as u16);
+            }
+            for i in 0..n {
+                words(&mut w, [7, 0, 1, (8 * (n - i)) as u16]);
+            }
+            let mut at = 8 * n;
+            for (i, (kind, subtable)) in lookups.iter().enumerate() {
+                words(&mut w, [1, *kind]);
+                w.write::<u32>((at - 8 * i) as u32);
+                at += subtable.len();
+            }
+            for (_, subtable) in lookups {
+                w.give(subtable);
+            }
+            w.finish()
+        }
+
+        // The types of the lookups of a `GSUB` table.
+        fn kinds(gsub: &[u8]) -> Vec<u16> {
+            let list = usize::from(u16::read_at(gsub, 8).unwrap());
+            (0..usize::from(u16::read_at(gsub, list).unwrap()))
+                .map(|i| {
+                    let lookup =
+                        usize::from(u16::read_at(gsub, list + 2 + 2 * i).unwrap());
+                    u16::read_at(gsub, list + lookup).unwrap()
+                })
+                .collect()
+        }
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let n = ttf_parser::Face::parse(&data, 0).unwrap().number_of_glyphs();
+        let glyphs: Vec<u16> = (0..n).collect();
+        let subset = |gsub: Vec<u8>| {
+            let data = with_tables(vec![(Tag::GSUB, gsub)]);
+            let profile = Profile::pdf(&glyphs).keep_gsub(true);
+            subset(&data, 0, profile).unwrap()
+        };
+        let gsub_of =
+            |data: &[u8]| parse(data, 0).unwrap().table(Tag::GSUB).unwrap().to_vec();
+
+        // Twelve lookups that reverse the glyphs, which need more than 64 KiB
+        // together. Only the lookups from the first one that is out of reach
+        // of 16-bit offsets on are promoted.
+        let mut w = Writer::new();
+        words(&mut w, [2, 6 + 2 * n, n]);
+        words(&mut w, (0..n).rev());
+        words(&mut w, [2, 1, 0, n - 1]);
+        w.write::<u16>(0);
+        let single = w.finish();
+        let subs = subset(gsub(&vec![(1, single); 12]));
+        let promoted = kinds(&gsub_of(&subs));
+        assert!(promoted.contains(&1) && promoted.contains(&7));
+        assert!(promoted.is_sorted());
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        for lookup in ttf.tables().gsub.unwrap().lookups {
+            let Some(ttf_parser::gsub::SubstitutionSubtable::Single(single)) =
+                lookup.subtables.get(0)
+            else {
+                panic!("expected a single substitution");
+            };
+            let ttf_parser::gsub::SingleSubstitution::Format2 { coverage, substitutes } =
+                single
+            else {
+                panic!("expected format 2");
+            };
+            let index = coverage.get(ttf_parser::GlyphId(5)).unwrap();
+            assert_eq!(substitutes.get(index), Some(ttf_parser::GlyphId(n - 6)));
+        }
+
+        // A ligature subtable whose first ligature set takes almost 64 KiB,
+        // so that the many small ones after it are only in reach if they are
+        // placed first.
+        let sets = 501;
+        let mut big = Writer::new();
+        big.write::<u16>(1800);
+        for j in 0..1800 {
+            big.write::<u16>(2 + 2 * 1800 + 32 * j);
+        }
+        for j in 0..1800 {
+            words(&mut big, [j, 15]);
+            words(&mut big, (0..14).map(|k| 1 + (j + k) % 500));
+        }
+        let big = big.finish();
+        let mut w = Writer::new();
+        words(&mut w, [1, 6 + 2 * sets, sets]);
+        for i in 0..sets {
+            let at = match i {
+                0 => 6 + 2 * sets + 10 + 10 * (sets - 1),
+                _ => 6 + 2 * sets + 10 + 10 * (i - 1),
+            };
+            w.write::<u16>(at);
+        }
+        words(&mut w, [2, 1, 1, sets, 0]);
+        for glyph in 2..=sets {
+            words(&mut w, [1, 4, glyph + 1000, 2, glyph]);
+        }
+        w.give(&big);
+        let subs = subset(gsub(&[(4, w.finish())]));
+        assert_eq!(kinds(&gsub_of(&subs)), [4]);
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let lookup = ttf.tables().gsub.unwrap().lookups.get(0).unwrap();
+        let Some(ttf_parser::gsub::SubstitutionSubtable::Ligature(ligatures)) =
+            lookup.subtables.get(0)
+        else {
+            panic!("expected a ligature substitution");
+        };
+        for (glyph, count, ligature) in [(1, 1800, 1799), (2, 1, 1002), (sets, 1, 1501)] {
+            let i = ligatures.coverage.get(ttf_parser::GlyphId(glyph)).unwrap();
+            let set = ligatures.ligature_sets.get(i).unwrap();
+            assert_eq!(set.len(), count);
+            assert_eq!(set.get(count - 1).unwrap().glyph.0, ligature);
+        }
+    }
+
+    #[test]
+    fn test_keep_gpos() {
+        use ttf_parser::gpos::{PairAdjustment, PositioningSubtable};
+        use ttf_parser::GlyphId;
+
+        type Pairs<T> = Vec<(u16, u16, T)>;
+
+        // The horizontal adjustments of the first kerning subtable that covers
+        // a pair, and the anchors of the first mark-to-base attachment.
+        fn positioning(data: &[u8], glyphs: &[u16]) -> (Pairs<i16>, Pairs<[i16; 4]>) {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            let gpos = ttf.tables().gpos.unwrap();
+            let lookups = |tag: &[u8; 4]| {
+                let feature =
+                    gpos.features.find(ttf_parser::Tag::from_bytes(tag)).unwrap();
+                feature.lookup_indices.into_iter().flat_map(|index| {
+                    let lookup = gpos.lookups.get(index).unwrap();
+                    (0..lookup.subtables.len()).map(move |i| {
+                        lookup.subtables.get::<PositioningSubtable>(i).unwrap()
+                    })
+                })
+            };
+
+            let mut kerning = vec![];
+            let mut marks = vec![];
+            for &first in glyphs {
+                for &second in glyphs {
+                    let (a, b) = (GlyphId(first), GlyphId(second));
+                    let kern = lookups(b"kern").find_map(|subtable| match subtable {
+                        PositioningSubtable::Pair(PairAdjustment::Format1 {
+                            coverage,
+                            sets,
+                        }) => sets.get(coverage.get(a)?)?.get(b),
+                        PositioningSubtable::Pair(PairAdjustment::Format2 {
+                            coverage,
+                            classes,
+                            matrix,
+                        }) => {
+                            coverage.get(a)?;
+                            matrix.get((classes.0.get(a), classes.1.get(b)))
+                        }
+                        _ => None,
+                    });
+                    if let Some((value, _)) = kern.filter(|(v, _)| v.x_advance != 0) {
+                        kerning.push((first, second, value.x_advance));
+                    }
+
+                    let mark = lookups(b"mark").find_map(|subtable| match subtable {
+                        PositioningSubtable::MarkToBase(attach) => {
+                            let base = attach.base_coverage.get(a)?;
+                            let (class, mark) =
+                                attach.marks.get(attach.mark_coverage.get(b)?)?;
+                            let anchor = attach.anchors.get(base, class)?;
+                            Some([anchor.x, anchor.y, mark.x, mark.y])
+                        }
+                        _ => None,
+                    });
+                    if let Some(anchors) = mark {
+                        marks.push((first, second, anchors));
+                    }
+                }
+            }
+            (kerning, marks)
+        }
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs: Vec<u16> = "AVTaoy\u{301}\u{323}"
+            .chars()
+            .map(|c| ttf.glyph_index(c).unwrap().0)
+            .collect();
+        let profile = Profile::pdf(&glyphs).keep_gpos(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(computed.table(Tag::GPOS), TableAction::Subset);
+        assert_eq!(computed.table(Tag::GDEF), TableAction::Subset);
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+
+        let (kerning, marks) = positioning(&subs, computed.glyphs());
+        assert_eq!(
+            (kerning.clone(), marks.clone()),
+            positioning(&data, computed.glyphs())
+        );
+        assert!(kerning.len() > 5);
+        assert!(marks.len() >= 4);
+
+        let size = |data: &[u8]| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            ttf.raw_face()
+                .table(ttf_parser::Tag::from_bytes(b"GPOS"))
+                .unwrap()
+                .len()
+        };
+        assert!(size(&subs) * 10 < size(&data));
+
+        // Renumbered glyphs, here in reverse order, keep their positioning.
+        let reverse = |glyph: u16| Some(u32::from(u16::MAX - glyph));
+        let profile = Profile::pdf(&glyphs)
+            .keep_gpos(true)
+            .remap_glyphs(true)
+            .glyph_order_by(&reverse);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        let new = |old| map.iter().find(|&&(o, _)| o == old).unwrap().1;
+        assert!(map.windows(2).all(|w| w[0].1 == 0 || w[0].1 > w[1].1));
+        // The pairs are listed in the order of the glyphs they are given.
+        let new_ids: Vec<u16> = computed.glyphs().iter().map(|&g| new(g)).collect();
+        fn renumber<T: Copy>(pairs: &Pairs<T>, new: impl Fn(u16) -> u16) -> Pairs<T> {
+            pairs.iter().map(|&(a, b, v)| (new(a), new(b), v)).collect()
+        }
+        assert_eq!(
+            positioning(&subs, &new_ids),
+            (renumber(&kerning, new), renumber(&marks, new))
+        );
+    }
+
+    #[test]
+    fn test_subset_gdef() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs: Vec<u16> = "Afi\u{301}\u{323}"
+            .chars()
+            .map(|c| ttf.glyph_index(c).unwrap().0)
+            .collect();
+        let computed = plan(&data, 0, Profile::pdf(&glyphs).keep_gpos(true)).unwrap();
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+
+        // The retained glyphs keep their classes, the others lose them.
+        let classes = |data: &[u8]| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            let gdef = ttf.tables().gdef.unwrap();
+            (0..ttf.number_of_glyphs())
+                .map(|glyph| {
+                    let glyph = ttf_parser::GlyphId(glyph);
+                    (gdef.glyph_class(glyph), gdef.glyph_mark_attachment_class(glyph))
+                })
+                .collect::<Vec<_>>()
+        };
+        let before = classes(&data);
+        let after = classes(&subs);
+        assert!(after.iter().any(|&(class, _)| class.is_some()));
+        for (glyph, (before, after)) in before.into_iter().zip(after).enumerate() {
+            if computed.glyphs().contains(&(glyph as u16)) {
+                assert_eq!(before, after);
+            } else {
+                assert_eq!(after, (None, 0));
+            }
+        }
+
+        // Without any classified glyphs, the table is dropped.
+        let computed = plan(&data, 0, Profile::pdf(&[]).keep_gpos(true)).unwrap();
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert!(ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"GDEF")).is_none());
+    }
+
+    #[test]
+    fn test_synthesize_vertical() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let profile = Profile::pdf(&[36]).synthesize_vertical(None);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+        assert!(warnings.contains(&Warning::VerticalMetricsSynthesized));
+
+        // The advance spans the typographic ascender (1069) and descender
+        // (-293), and the glyph's top (717) is placed below the ascender.
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let glyph = ttf_parser::GlyphId(36);
+        assert_eq!(ttfs.glyph_ver_advance(glyph), Some(1362));
+        assert_eq!(ttfs.glyph_ver_side_bearing(glyph), Some(352));
+        assert_eq!(Plan::from_json(&computed.to_json()).unwrap(), computed);
+
+        let profile = Profile::pdf(&[36]).synthesize_vertical(Some(1000));
+        let subs = subset(&data, 0, profile).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert_eq!(ttfs.glyph_ver_advance(glyph), Some(1000));
+
+        // CFF fonts get a vertical origin.
+        let data = std::fs::read("fonts/LatinModernRoman-Regular.otf").unwrap();
+        let subs =
+            subset(&data, 0, Profile::pdf(&[27]).synthesize_vertical(None)).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert_eq!(ttfs.glyph_y_origin(ttf_parser::GlyphId(27)), Some(806));
+
+        // Existing vertical metrics are kept, together with the glyphs of the
+        // vertical alternates feature for the fullwidth parenthesis and comma.
+        let data = std::fs::read("fonts/NotoSansCJKsc-Regular.otf").unwrap();
+        let glyphs = [59054, 59058];
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        assert_eq!(computed.table(Tag::VMTX), TableAction::Drop);
+        let computed =
+            plan(&data, 0, Profile::pdf(&glyphs).synthesize_vertical(None)).unwrap();
+        assert_eq!(computed.table(Tag::VMTX), TableAction::Copy);
+        assert_eq!(computed.glyphs(), [0, 58979, 58994, 59054, 59058]);
+        let (_, warnings) = subset_with_warnings(&data, 0, &computed).unwrap();
+        assert!(!warnings.contains(&Warning::VerticalMetricsSynthesized));
+    }
+
+    #[test]
+    fn test_max_context() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let max_context = |data: &[u8]| {
+            let os2 = parse(data, 0).unwrap().table(Tag::OS2).unwrap();
+            u16::from_be_bytes([os2[94], os2[95]])
+        };
+        assert_ne!(max_context(&data), 0);
+
+        // Without layout tables, there is no context.
+        let plan = plan(&data, 0, Profile::pdf(&[68])).unwrap();
+        let subs = subset_with_plan(&data, 0, &plan).unwrap();
+        assert_eq!(max_context(&subs), 0);
+
+        // A plan that keeps GSUB keeps the value.
+        let json = plan.to_json().replace(r#""GSUB": "drop""#, r#""GSUB": "copy""#);
+        let plan = Plan::from_json(&json).unwrap();
+        let subs = subset_with_plan(&data, 0, &plan).unwrap();
+        assert_eq!(max_context(&subs), max_context(&data));
+    }
+
+    #[test]
+    fn test_os2_ranges() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let field = |data: &[u8], offset: usize| {
+            let os2 = parse(data, 0).unwrap().table(Tag::OS2).unwrap();
+            u32::from_be_bytes(os2[offset..offset + 4].try_into().unwrap())
+        };
+
+        let glyphs = ['A', 'Б'].map(|c| ttf.glyph_index(c).unwrap().0);
+        let subs = subset(&data, 0, Profile::pdf(&glyphs)).unwrap();
+
+        // Basic Latin and Cyrillic remain, the other blocks are dropped.
+        assert_ne!(field(&data, 42) & 0b1010_0010, 0);
+        assert_eq!(field(&subs, 42), field(&data, 42) & (1 << 0 | 1 << 9));
+        assert_eq!([field(&subs, 46), field(&subs, 50), field(&subs, 54)], [0; 3]);
+        assert_eq!(field(&subs, 64), 0x0041_0411);
+
+        // Without all of ASCII, only the Cyrillic code page is supported.
+        assert_ne!(field(&data, 78) & 1, 0);
+        assert_eq!(field(&subs, 78), field(&data, 78) & 1 << 2);
+        assert_eq!(field(&subs, 82), 0);
+    }
+
+    #[test]
+    fn test_head_maxp_aggregates() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let field = |data: &[u8], tag, offset: usize| {
+            let table = parse(data, 0).unwrap().table(tag).unwrap();
+            i16::from_be_bytes([table[offset], table[offset + 1]])
+        };
+
+        // The composite keeps its components.
+        let glyphs = ['a', 'é'].map(|c| ttf.glyph_index(c).unwrap().0);
+        let plan = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        assert!(plan.glyphs.len() > 3);
+        let subs = subset_with_plan(&data, 0, &plan).unwrap();
+
+        let mut bbox = [i16::MAX, i16::MAX, i16::MIN, i16::MIN];
+        for &glyph in &plan.glyphs {
+            let Some(rect) = ttf.glyph_bounding_box(ttf_parser::GlyphId(glyph)) else {
+                continue;
+            };
+            bbox = [
+                bbox[0].min(rect.x_min),
+                bbox[1].min(rect.y_min),
+                bbox[2].max(rect.x_max),
+                bbox[3].max(rect.y_max),
+            ];
+        }
+        let head = [36, 38, 40, 42].map(|offset| field(&subs, Tag::HEAD, offset));
+        assert_eq!(head, bbox);
+        assert!(head[3] < field(&data, Tag::HEAD, 42));
+
+        // Points, contours, component elements and depth.
+        let maxp = [6, 8, 10, 12, 28, 30].map(|offset| field(&subs, Tag::MAXP, offset));
+        let source = [6, 8, 10, 12, 28, 30].map(|offset| field(&data, Tag::MAXP, offset));
+        assert!(maxp.iter().zip(&source).all(|(sub, full)| sub <= full));
+        assert!(maxp[0] < source[0] && maxp[0] > 0 && maxp[1] > 0);
+        assert!(maxp[2] > 0 && maxp[3] >= 2);
+        assert_eq!(&maxp[4..], [2, 1]);
+    }
+
+    #[test]
+    fn test_hhea_extremes() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let field = |data: &[u8], offset: usize| {
+            let hhea = parse(data, 0).unwrap().table(Tag::HHEA).unwrap();
+            i16::from_be_bytes([hhea[offset], hhea[offset + 1]])
+        };
+
+        let plan = plan(&data, 0, Profile::pdf(&[68])).unwrap();
+        let subs = subset_with_plan(&data, 0, &plan).unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs = plan.glyphs.iter().map(|&glyph| ttf_parser::GlyphId(glyph));
+        let metrics: Vec<_> = glyphs
+            .map(|glyph| {
+                let advance = ttf.glyph_hor_advance(glyph).unwrap() as i16;
+                (advance, ttf.glyph_bounding_box(glyph))
+            })
+            .collect();
+
+        let max_advance = metrics.iter().map(|&(advance, _)| advance).max();
+        let bounds = metrics.iter().filter_map(|&(advance, bbox)| Some((advance, bbox?)));
+        let min_lsb = bounds.clone().map(|(_, bbox)| bbox.x_min).min();
+        let min_rsb = bounds.clone().map(|(advance, bbox)| advance - bbox.x_max).min();
+        let max_extent = bounds.map(|(_, bbox)| bbox.x_max).max();
+        assert!(max_advance < Some(field(&data, 10)));
+        assert_eq!(Some(field(&subs, 10)), max_advance);
+        assert_eq!(Some(field(&subs, 12)), min_lsb);
+        assert_eq!(Some(field(&subs, 14)), min_rsb);
+        assert_eq!(Some(field(&subs, 16)), max_extent);
+    }
+
+    /// Create an INDEX with the 32-bit count of CFF2 tables.
+    fn cff2_index(items: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = (items.len() as u32).to_be_bytes().to_vec();
+        if items.is_empty() {
+            return data;
+        }
+        data.push(4);
+        let mut offset = 1u32;
+        data.extend(offset.to_be_bytes());
+        for item in items {
+            offset += item.len() as u32;
+            data.extend(offset.to_be_bytes());
+        }
+        data.extend(items.concat());
+        data
+    }
+
+    /// Replace the CFF table of Latin Modern with a CFF2 table. The first half
+    /// of the glyphs uses the first Font DICT and calls one of its local
+    /// subroutines and the second half uses the second one.
+    fn with_cff2() -> Vec<u8> {
+        let data = std::fs::read("fonts/LatinModernRoman-Regular.otf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let num_glyphs = ttf_parser::Face::parse(&data, 0).unwrap().number_of_glyphs();
+        let half = num_glyphs / 2;
+
+        let int =
+            |op: &[u8], v: usize| [&[29][..], &(v as i32).to_be_bytes(), op].concat();
+        let short = |v: i16| [&[28][..], &v.to_be_bytes()].concat();
+        let char_strings: Vec<_> = (0..num_glyphs)
+            .map(|glyph| {
+                let mut string = [short(100), short(0), vec![21]].concat();
+                if glyph < half {
+                    string.extend([short(-107), vec![10]].concat());
+                }
+                string.extend([short(glyph as i16 + 1), short(10), vec![5]].concat());
+                string
+            })
+            .collect();
+        let subrs = [short(0), short(500), vec![5]].concat();
+
+        // The Item Variation Store has a single empty Item Variation Data
+        // without regions, so a blend has no deltas.
+        let store = [22u16, 1, 0, 18, 1, 0, 12, 0, 0, 0, 0, 0]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<_>>();
+        let select = [vec![3, 0, 2, 0, 0, 0], half.to_be_bytes().to_vec()].concat();
+        let select = [select, vec![1], num_glyphs.to_be_bytes().to_vec()].concat();
+        let private_len = 18;
+
+        // Top DICT with CharStrings, vstore, FDSelect and FDArray.
+        let top_len = 4 * 5 + 1 + 1 + 2 + 2;
+        let char_strings_at = 5 + top_len + 4;
+        let char_strings = cff2_index(&char_strings);
+        let store_at = char_strings_at + char_strings.len();
+        let select_at = store_at + store.len();
+        let array_at = select_at + select.len();
+        let array_len = cff2_index(&[vec![0; 11], vec![0; 11]]).len();
+        let subrs = cff2_index(&[subrs]);
+        let private_at =
+            |i: usize| array_at + array_len + i * (private_len + subrs.len());
+
+        let mut cff2 = vec![2, 0, 5];
+        cff2.extend((top_len as u16).to_be_bytes());
+        cff2.extend(int(&[17], char_strings_at));
+        cff2.extend(int(&[24], store_at));
+        cff2.extend(int(&[12, 37], select_at));
+        cff2.extend(int(&[12, 36], array_at));
+        cff2.extend(cff2_index(&[]));
+        cff2.extend(char_strings);
+        cff2.extend(store);
+        cff2.extend(select);
+        let font_dict = |i| [int(&[], private_len), int(&[18], private_at(i))].concat();
+        cff2.extend(cff2_index(&[font_dict(0), font_dict(1)]));
+        for _ in 0..2 {
+            // StdHW as a blend of a single value.
+            cff2.extend([int(&[], 50), int(&[23, 10], 1)].concat());
+            cff2.extend(int(&[19], private_len));
+            cff2.extend(&subrs);
+        }
+
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .filter(|&&(tag, _)| tag != Tag::CFF)
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.push((Tag::CFF2, Cow::Owned(cff2)));
+        super::construct(face.kind(), tables, 4)
+    }
+
+    #[test]
+    fn test_subset_cff2() {
+        let data = with_cff2();
+        let plan = plan(&data, 0, Profile::pdf(&[68])).unwrap();
+        let subs = subset_with_plan(&data, 0, &plan).unwrap();
+
+        let cff2 = |data: &[u8]| parse(data, 0).unwrap().table(Tag::CFF2).unwrap().len();
+        assert!(cff2(&subs) < cff2(&data) / 10);
+
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let outline = |face: &ttf_parser::Face, glyph| {
+            let mut sink = Sink::default();
+            face.outline_glyph(ttf_parser::GlyphId(glyph), &mut sink)
+                .map(|_| sink)
+        };
+        assert!(outline(&ttfs, 68).is_some());
+        assert_eq!(outline(&ttfs, 68), outline(&ttf, 68));
+        assert!(outline(&ttf, 69).is_some());
+        assert_eq!(outline(&ttfs, 69), None);
+
+        // The subsetted table can be subsetted again.
+        let again = subset_with_plan(&subs, 0, &plan).unwrap();
+        assert_eq!(cff2(&again), cff2(&subs));
+    }
+
+    #[test]
+    fn test_remap_glyphs() {
+        let noto = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let latin = std::fs::read("fonts/LatinModernRoman-Regular.otf").unwrap();
+        for (data, reverse) in
+            [(noto.clone(), false), (latin, false), (with_cff2(), false), (noto, true)]
+        {
+            let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+            let chars = ['a', 'Ä', 'ß'];
+            let glyphs: Vec<u16> =
+                chars.iter().map(|&c| ttf.glyph_index(c).unwrap().0).collect();
+            let order: Vec<u16> = glyphs.iter().rev().copied().collect();
+            let mut profile = Profile::pdf(&glyphs).remap_glyphs(true);
+            if reverse {
+                profile = profile.glyph_order(&order);
+            }
+            let plan = plan(&data, 0, profile).unwrap();
+            assert_eq!(Plan::from_json(&plan.to_json()), Ok(plan.clone()));
+            let (subs, map) = subset_with_glyph_map(&data, 0, &plan).unwrap();
+            assert_eq!(map.iter().map(|&(old, _)| old).collect::<Vec<_>>(), plan.glyphs);
+
+            // The listed glyphs come first, and the others keep their order.
+            let mut new_ids: Vec<u16> = map.iter().map(|&(_, new)| new).collect();
+            if reverse {
+                let new = |old| map.iter().find(|&&(o, _)| o == old).unwrap().1;
+                assert_eq!(
+                    order.iter().map(|&old| new(old)).collect::<Vec<_>>(),
+                    [1, 2, 3]
+                );
+                new_ids.retain(|&new| new == 0 || new > 3);
+            }
+            assert!(new_ids.is_sorted());
+
+            let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+            assert_eq!(usize::from(ttfs.number_of_glyphs()), map.len());
+            let outline = |face: &ttf_parser::Face, glyph| {
+                let mut sink = Sink::default();
+                face.outline_glyph(ttf_parser::GlyphId(glyph), &mut sink)
+                    .map(|_| sink)
+            };
+            for &(old, new) in &map {
+                let (old, new) = (ttf_parser::GlyphId(old), ttf_parser::GlyphId(new));
+                assert_eq!(outline(&ttfs, new.0), outline(&ttf, old.0));
+                assert_eq!(ttfs.glyph_hor_advance(new), ttf.glyph_hor_advance(old));
+                assert_eq!(ttfs.glyph_name(new), ttf.glyph_name(old));
+            }
+            for c in chars {
+                let old = ttf.glyph_index(c).unwrap().0;
+                let new = map.iter().find(|&&(o, _)| o == old).unwrap().1;
+                assert_eq!(ttfs.glyph_index(c), Some(ttf_parser::GlyphId(new)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_remap_pua() {
+        // The PUA range follows the new IDs, so it covers all output glyphs.
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let computed = plan(&data, 0, Profile::web(&[68, 70, 300]).remap_glyphs(true));
+        let result = subset_to_result(&data, 0, &computed.unwrap()).unwrap();
+        let num_glyphs = result.num_glyphs().unwrap();
+        let ttf = ttf_parser::Face::parse(result.font(), 0).unwrap();
+        assert_eq!(ttf.number_of_glyphs(), num_glyphs);
+        assert!(num_glyphs < 68);
+        let pua = |glyph: u16| {
+            let c = char::from_u32(0xF0000 + u32::from(glyph)).unwrap();
+            ttf.glyph_index(c).map(|glyph| glyph.0)
+        };
+        for glyph in 1..num_glyphs {
+            assert_eq!(pua(glyph), Some(glyph));
+        }
+        assert_eq!(pua(num_glyphs), None);
+        assert_eq!(pua(68), None);
+        assert_eq!(ttf.glyph_index('a').map(|glyph| glyph.0), Some(1));
+    }
+
+    #[test]
+    fn test_subset_report() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs = [68, 69, 70];
+        for remap in [false, true] {
+            let profile = Profile::pdf(&glyphs).remap_glyphs(remap);
+            let plan = plan(&data, 0, profile).unwrap();
+            let (subs, report) = subset_with_report(&data, 0, &plan).unwrap();
+            let (glyph_subs, glyph_map) = subset_with_glyph_map(&data, 0, &plan).unwrap();
+            assert_eq!(subs, glyph_subs);
+            assert_eq!(report.glyph_map, glyph_map);
+            assert_eq!(report.retained, [0, 68, 69, 70]);
+            assert_eq!(
+                report.retained.len() + report.dropped.len(),
+                usize::from(ttf.number_of_glyphs())
+            );
+
+            // Only the retained glyphs stay mapped.
+            let new = |c| {
+                let old = ttf.glyph_index(c).unwrap().0;
+                report.glyph_map.iter().find(|&&(o, _)| o == old).unwrap().1
+            };
+            let mappings = ['a', 'b', 'c'].map(|c| (c as u32, new(c)));
+            assert_eq!(report.cmap, mappings);
+            assert_eq!(new('a'), if remap { 1 } else { 68 });
+            let num_glyphs = if remap { 4 } else { ttf.number_of_glyphs() };
+            assert_eq!(report.num_glyphs, num_glyphs);
+            assert!(report.to_json().contains(&format!(r#""num_glyphs": {num_glyphs}"#)));
+
+            let table = |tag| *report.tables.iter().find(|t| t.tag == tag).unwrap();
+            let glyf = parse(&subs, 0).unwrap().table(Tag::GLYF).unwrap();
+            assert_eq!(table(Tag::GLYF).after, Some(glyf.len()));
+            assert!(table(Tag::GLYF).before > table(Tag::GLYF).after);
+            assert_eq!(table(Tag::GSUB).after, None);
+            assert!(report.to_json().contains(r#""97": "#));
+
+            // The names of the glyphs survive under their new IDs.
+            let names: Vec<_> =
+                report.glyph_names.iter().map(|(_, name)| name.as_str()).collect();
+            assert_eq!(names, ["a", "b", "c"]);
+            assert_eq!(report.glyph_names[0].0, new('a'));
+            assert!(report.to_json().contains(&format!(r#""{}": "a""#, new('a'))));
+        }
+
+        // Standard and custom names resolve like in ttf-parser.
+        let glyphs: Vec<u16> = (0..ttf.number_of_glyphs()).collect();
+        let computed = plan(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        let (_, report) = subset_with_report(&data, 0, &computed).unwrap();
+        assert_eq!(report.glyph_names.len(), glyphs.len() - 1);
+        for (glyph, name) in &report.glyph_names {
+            assert_eq!(ttf.glyph_name(ttf_parser::GlyphId(*glyph)), Some(name.as_str()));
+        }
+
+        // Without names in the output, there are none to report.
+        let profile =
+            Profile::pdf(&[68]).obfuscate(Obfuscation { seed: 1, strip_names: true });
+        let computed = plan(&data, 0, profile).unwrap();
+        assert!(subset_with_report(&data, 0, &computed)
+            .unwrap()
+            .1
+            .glyph_names
+            .is_empty());
+    }
+
+    #[test]
+    fn test_subset_result() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let plan = plan(&data, 0, Profile::pdf(&[68, 60000])).unwrap();
+        let result = subset_to_result(&data, 0, &plan).unwrap();
+        let (subs, warnings) = subset_with_warnings(&data, 0, &plan).unwrap();
+        assert_eq!(result.font(), subs);
+        assert_eq!(result.warnings(), warnings);
+        let (_, glyph_map) = subset_with_glyph_map(&data, 0, &plan).unwrap();
+        assert_eq!(result.glyph_map(), Some(&glyph_map));
+        let (_, report) = subset_with_report(&data, 0, &plan).unwrap();
+        assert_eq!(result.report(), Some(&report));
+        let (_, provenance) = subset_with_provenance(&data, 0, &plan).unwrap();
+        assert_eq!(result.provenance(), Some(&provenance));
+        assert_eq!(super::SubsetResult::new(subs.clone()).report(), None);
+
+        #[cfg(any(feature = "brotli", feature = "woff-convert"))]
+        {
+            let woff2 = result.to_woff2(11).unwrap();
+            assert_eq!(woff2, super::ttf_to_woff2(&subs, 11).unwrap());
+            assert_eq!(result.to_woff2(11).unwrap(), woff2);
+        }
+        assert_eq!(result.into_font(), subs);
+    }
+
+    #[test]
+    fn test_warnings() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let plan = plan(&data, 0, Profile::pdf(&[68, 60000])).unwrap();
+        let (subs, warnings) = subset_with_warnings(&data, 0, &plan).unwrap();
+        assert_eq!(subs, subset(&data, 0, Profile::pdf(&[68])).unwrap());
+
+        assert!(warnings.contains(&Warning::GlyphMissing(60000)));
+        assert!(!warnings.iter().any(|w| matches!(w, Warning::TableDropped(_))));
+        assert!(warnings.windows(2).all(|w| w[0].severity() >= w[1].severity()));
+        assert_eq!(Warning::TableDropped(Tag::GPOS).severity(), Severity::Warning);
+        assert_eq!(
+            Warning::GlyphMissing(60000).to_string(),
+            "glyph 60000 does not exist"
+        );
+    }
+
+    #[test]
+    fn test_closure_by_composite() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let id = |c| ttf.glyph_index(c).unwrap().0;
+        let (a, a_umlaut) = (id('a'), id('ä'));
+
+        // The base letter is only listed if it wasn't requested.
+        let added = closure_by_composite(&data, 0, &Profile::pdf(&[a_umlaut])).unwrap();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].0, a_umlaut);
+        assert!(added[0].1.contains(&a));
+        let added =
+            closure_by_composite(&data, 0, &Profile::pdf(&[a, a_umlaut])).unwrap();
+        assert!(!added[0].1.contains(&a));
+
+        // Point the first component of `ä` beyond the font's glyphs.
+        let face = parse(&data, 0).unwrap();
+        let long = u16::read_at(face.table(Tag::HEAD).unwrap(), 50).unwrap() != 0;
+        let loca = face.table(Tag::LOCA).unwrap();
+        let start = match long {
+            true => u32::read_at(loca, 4 * a_umlaut as usize).unwrap() as usize,
+            false => 2 * u16::read_at(loca, 2 * a_umlaut as usize).unwrap() as usize,
+        };
+        let mut glyf = face.table(Tag::GLYF).unwrap().to_vec();
+        assert!(i16::read_at(&glyf, start).unwrap() < 0);
+        glyf[start + 12..start + 14].copy_from_slice(&60000u16.to_be_bytes());
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.iter_mut().find(|(tag, _)| *tag == Tag::GLYF).unwrap().1 =
+            Cow::Owned(glyf);
+        let broken = super::construct(face.kind(), tables, 4);
+
+        let glyphs = [a_umlaut];
+        let computed = plan(&broken, 0, Profile::pdf(&glyphs)).unwrap();
+        let (_, warnings) = subset_with_warnings(&broken, 0, &computed).unwrap();
+        assert!(warnings.contains(&Warning::ComponentMissing(a_umlaut, 60000)));
+        assert_eq!(
+            Warning::ComponentMissing(a_umlaut, 60000).to_string(),
+            format!("composite glyph {a_umlaut} references missing glyph 60000")
+        );
+        assert_eq!(
+            subset(&broken, 0, Profile::pdf(&glyphs).strict(true)),
+            Err(Error::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_isolate_glyph() {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let (subs, trace) = isolate_glyph(&data, 0, 68).unwrap();
+        assert_eq!(subs, subset(&data, 0, Profile::pdf(&[68])).unwrap());
+        assert!(trace.starts_with("glyph: 68\nclosure: [0, 68]\n"));
+        assert!(trace.contains("  cmap: subset, verbatim from "));
+        assert!(trace.contains("  GSUB: drop\n"));
+        assert!(trace.contains("  metrics: advance 561, lsb 46\n  codepoints: U+0061\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "arbitrary")]
+    fn test_arbitrary_plans() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let mut state = 1u64;
+        for _ in 0..200 {
+            let bytes: Vec<u8> = (0..256)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    (state >> 56) as u8
+                })
+                .collect();
+            let mut plan = Plan::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+
+            // Random glyphs and tags rarely exist, so also use the font's.
+            for glyph in &mut plan.glyphs {
+                *glyph %= 2000;
+            }
+            plan.glyphs.sort_unstable();
+            plan.glyphs.dedup();
+
+            for (i, &(tag, _)) in face.tables.iter().enumerate() {
+                if tag != Tag::LOCA && plan.tables.iter().all(|&(t, _)| t != tag) {
+                    let actions =
+                        [TableAction::Subset, TableAction::Copy, TableAction::Drop];
+                    plan.tables.push((tag, actions[(bytes[i] % 3) as usize]));
+                }
+            }
+            plan.tables.sort_by_key(|&(tag, _)| tag);
+            let _ = subset_with_plan(&data, 0, &plan);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "woff2")]
+    fn test_woff2_limits() {
+        use super::{woff2_to_ttf, woff2_to_ttf_with_limits, Error, Woff2Limits};
+
+        let mut header = vec![];
+        header.extend(b"wOF2\x00\x01\x00\x00");
+        header.extend(48u32.to_be_bytes());
+        header.extend(20u16.to_be_bytes());
+        header.resize(48, 0);
+
+        let limits = Woff2Limits { max_tables: 10, ..Woff2Limits::default() };
+        assert_eq!(woff2_to_ttf_with_limits(&header, limits), Err(Error::LimitExceeded));
+        assert_eq!(woff2_to_ttf(&header), Err(Error::MissingData));
+        assert_eq!(woff2_to_ttf(b"wOFF\x00\x01\x00\x00"), Err(Error::Woff2));
+    }
+
+    #[test]
+    fn test_woff() {
+        use super::{ttf_to_woff, woff_blocks, woff_to_ttf, WoffBlocks};
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let subs = subset(&data, 0, Profile::pdf(&[3, 68, 69])).unwrap();
+        let woff = ttf_to_woff(&subs, &WoffBlocks::default()).unwrap();
+        assert!(woff.len() < subs.len() / 2);
+        assert_eq!(woff_to_ttf(&woff).unwrap(), subs);
+        assert_eq!(woff_blocks(&woff).unwrap(), WoffBlocks::default());
+
+        // The metadata and private data survive a round trip, and the private
+        // data ends the file.
+        let blocks = WoffBlocks {
+            metadata: b"<?xml version=\"1.0\"?><metadata version=\"1.0\"/>".to_vec(),
+            private: b"vendor".to_vec(),
+        };
+        let woff = ttf_to_woff(&subs, &blocks).unwrap();
+        assert_eq!(woff_blocks(&woff).unwrap(), blocks);
+        assert!(woff.ends_with(b"vendor"));
+        assert_eq!(woff_to_ttf(&woff).unwrap(), subs);
+
+        let ttc = super::build_collection(&[&subs]).unwrap();
+        assert_eq!(ttf_to_woff(&ttc, &blocks), Err(Error::Unsupported));
+        assert_eq!(woff_to_ttf(&subs), Err(Error::UnknownKind));
+    }
+
+    #[test]
+    #[cfg(feature = "brotli")]
+    fn test_woff2_compressor() {
+        use super::{ttf_to_woff2_with, woff2_to_ttf, RustBrotli};
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let subs = subset(&data, 0, Profile::pdf(&[3, 68, 69])).unwrap();
+        for threads in [1, 4] {
+            let compressor = RustBrotli { quality: 9, threads };
+            let woff2 = ttf_to_woff2_with(&subs, &compressor).unwrap();
+            assert!(woff2.len() < subs.len() / 2);
+            let ttf = woff2_to_ttf(&woff2).unwrap();
+            assert_same_outlines(&ttf, &subs);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "brotli")]
+    fn test_woff2_transform() {
+        use super::{subset_to_woff2, ttf_to_woff2_with, woff2_to_ttf, RustBrotli};
+
+        let compressor = RustBrotli::default();
+        for path in [
+            "NotoSans-Regular.ttf",
+            "ClickerScript-Regular.ttf",
+            "COLRv1StaticTestGlyphs-Regular.ttf",
+            "LatinModernRoman-Regular.otf",
+        ] {
+            let data = std::fs::read(Path::new("fonts").join(path)).unwrap();
+            let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+            let glyphs: Vec<u16> = "Hämbürgefonts Ǻ½"
+                .chars()
+                .filter_map(|c| Some(ttf.glyph_index(c)?.0))
+                .collect();
+
+            let profile = || Profile::pdf(&glyphs);
+            let subs = subset(&data, 0, profile()).unwrap();
+            let woff2 = ttf_to_woff2_with(&subs, &compressor).unwrap();
+            let decoded = woff2_to_ttf(&woff2).unwrap();
+            assert_same_outlines(&decoded, &subs);
+
+            // Encoding the subsetted tables directly gives the same font.
+            let direct = subset_to_woff2(&data, 0, profile(), &compressor).unwrap();
+            assert_eq!(woff2_to_ttf(&direct).unwrap(), decoded);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "brotli")]
+    fn test_woff2_blocks() {
+        use super::{
+            ttf_to_woff2_with, ttf_to_woff2_with_blocks, woff2_blocks, woff2_to_ttf,
+            RustBrotli, WoffBlocks,
+        };
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let subs = subset(&data, 0, Profile::pdf(&[3, 68, 69])).unwrap();
+        let compressor = RustBrotli { quality: 5, threads: 1 };
+        let plain = ttf_to_woff2_with(&subs, &compressor).unwrap();
+        assert_eq!(woff2_blocks(&plain).unwrap(), WoffBlocks::default());
+
+        // The blocks survive a round trip and don't affect the font data.
+        let blocks = WoffBlocks {
+            metadata: b"<?xml version=\"1.0\"?><metadata version=\"1.0\"/>".to_vec(),
+            private: b"vendor".to_vec(),
+        };
+        let woff2 = ttf_to_woff2_with_blocks(&subs, &compressor, &blocks).unwrap();
+        assert_eq!(woff2_blocks(&woff2).unwrap(), blocks);
+        assert_eq!(woff2.len() % 4, 0);
+        assert_eq!(u32::read_at(&woff2, 8).unwrap() as usize, woff2.len());
+        assert_eq!(woff2_to_ttf(&woff2).unwrap(), woff2_to_ttf(&plain).unwrap());
+        assert_eq!(woff2_blocks(&subs), Err(Error::Woff2));
+    }
+
+    /// Assert that two fonts have the same glyph outlines and bounding boxes
+    /// and that all tables but `glyf`, `loca` and the checksum adjustment in
+    /// `head` are identical.
+    #[cfg(feature = "brotli")]
+    fn assert_same_outlines(a: &[u8], b: &[u8]) {
+        let (face_a, face_b) = (parse(a, 0).unwrap(), parse(b, 0).unwrap());
+        let tags = |face: &super::Face| {
+            face.tables.iter().map(|&(tag, _)| tag).collect::<Vec<_>>()
+        };
+        assert_eq!(tags(&face_a), tags(&face_b));
+        for (&(tag, x), &(_, y)) in face_a.tables.iter().zip(&face_b.tables) {
+            match tag {
+                Tag::GLYF | Tag::LOCA => {}
+                Tag::HEAD => assert_eq!((&x[..8], &x[12..]), (&y[..8], &y[12..])),
+                _ => assert_eq!(x, y, "{tag:?}"),
+            }
+        }
+
+        let (ttf_a, ttf_b) = (
+            ttf_parser::Face::parse(a, 0).unwrap(),
+            ttf_parser::Face::parse(b, 0).unwrap(),
+        );
+        for id in 0..ttf_b.number_of_glyphs() {
+            let id = ttf_parser::GlyphId(id);
+            let (mut sink_a, mut sink_b) = (Sink::default(), Sink::default());
+            let bbox_a = ttf_a.outline_glyph(id, &mut sink_a);
+            let bbox_b = ttf_b.outline_glyph(id, &mut sink_b);
+            assert_eq!(bbox_a, bbox_b);
+            assert_eq!(sink_a, sink_b);
+        }
+    }
+
+    fn test(path: &str, text: &str) {
+        test_impl(path, text, true);
+    }
+
+    fn test_full(path: &str) {
+        let data = std::fs::read(Path::new("fonts").join(path)).unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let mut text = String::new();
+        for subtable in ttf.tables().cmap.unwrap().subtables {
+            if subtable.is_unicode() {
+                subtable.codepoints(|c| text.push(char::try_from(c).unwrap()));
+            }
+        }
+        test_impl(path, &text, false);
+    }
+
+    fn test_impl(path: &str, text: &str, write: bool) {
+        eprintln!("==============================================");
+        eprintln!("Testing {path}");
+
+        let data = std::fs::read(Path::new("fonts").join(path)).unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let glyphs: Vec<_> =
+            text.chars().filter_map(|c| Some(ttf.glyph_index(c)?.0)).collect();
+
+        let profile = Profile::pdf(&glyphs);
+        let subs = subset(&data, 0, profile).unwrap();
+        let stem = Path::new(path).file_stem().unwrap().to_str().unwrap();
+        let out = Path::new("target").join(Path::new(stem)).with_extension("ttf");
+
+        if write {
+            std::fs::write(out, &subs).unwrap();
+        }
+
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let cff = ttfs.tables().cff;
+        for c in text.chars() {
+            let id = ttf.glyph_index(c).unwrap();
+            let bbox = ttf.glyph_bounding_box(id);
+
+            if let Some(cff) = &cff {
+                if bbox.is_some() {
+                    cff.outline(id, &mut Sink::default()).unwrap();
+                }
+            }
+
+            let mut sink1 = Sink::default();
+            let mut sink2 = Sink::default();
+            ttf.outline_glyph(id, &mut sink1);
+            ttfs.outline_glyph(id, &mut sink2);
+            assert_eq!(sink1, sink2);
+            assert_eq!(ttf.glyph_hor_advance(id), ttfs.glyph_hor_advance(id));
+            assert_eq!(ttf.glyph_name(id), ttfs.glyph_name(id));
+            assert_eq!(ttf.glyph_hor_side_bearing(id), ttfs.glyph_hor_side_bearing(id));
+        }
+    }
+
+    /// Make Noto Sans variable with a weight axis from 100 to 900, a bent
+    /// `avar` mapping, outline and advance deltas for a few glyphs, and control
+    /// values with `cvar` deltas.
+    fn variable_noto() -> Vec<u8> {
+        /// Pack 16-bit deltas in runs of words.
+        fn words(deltas: &[i16]) -> Vec<u8> {
+            let mut data = vec![];
+            for run in deltas.chunks(64) {
+                data.push(0x40 | (run.len() as u8 - 1));
+                data.extend(run.iter().flat_map(|v| v.to_be_bytes()));
+            }
+            data
+        }
+
+        /// A tuple variation with its tuple index, embedded tuples, point
+        /// numbers and deltas.
+        type Tuple = (u16, Vec<i16>, Vec<u8>, Vec<i16>);
+
+        /// Serialize tuple variations whose store starts at `base`.
+        fn store(tuples: &[Tuple], base: usize) -> Vec<u8> {
+            let mut headers = vec![];
+            let mut data = vec![];
+            for (index, embedded, points, deltas) in tuples {
+                let body = [points.clone(), words(deltas)].concat();
+                headers.extend((body.len() as u16).to_be_bytes());
+                headers.extend(index.to_be_bytes());
+                headers.extend(embedded.iter().flat_map(|v| v.to_be_bytes()));
+                data.extend(body);
+            }
+            let mut store = (tuples.len() as u16).to_be_bytes().to_vec();
+            store.extend(((base + 4 + headers.len()) as u16).to_be_bytes());
+            [store, headers, data].concat()
+        }
+
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let num_glyphs = 3748;
+
+        // The name table has the names of the axis and instances of Noto
+        // Sans' variable version.
+        let mut fvar = vec![0, 1, 0, 0, 0, 16, 0, 2, 0, 1, 0, 20, 0, 3, 0, 8];
+        fvar.extend(b"wght");
+        for value in [100_i32, 400, 900] {
+            fvar.extend((value << 16).to_be_bytes());
+        }
+        fvar.extend(0_u16.to_be_bytes());
+        fvar.extend(261_u16.to_be_bytes());
+        for (name, value) in [(262_u16, 100_i32), (265, 400), (268, 700)] {
+            fvar.extend(name.to_be_bytes());
+            fvar.extend(0_u16.to_be_bytes());
+            fvar.extend((value << 16).to_be_bytes());
+        }
+
+        let mut avar = vec![0, 1, 0, 0, 0, 0, 0, 1, 0, 4];
+        for value in [-16384_i16, -16384, 0, 0, 6554, 8192, 16384, 16384] {
+            avar.extend(value.to_be_bytes());
+        }
+
+        // The deltas of all points come first, followed by those of the
+        // sparse points.
+        let all = |n: usize, f: &dyn Fn(usize) -> (i16, i16)| {
+            let (xs, ys): (Vec<i16>, Vec<i16>) = (0..n).map(f).unzip();
+            [xs, ys].concat()
+        };
+        let mut variations = vec![vec![]; num_glyphs];
+        variations[68] = store(
+            &[
+                (
+                    0x2000,
+                    vec![],
+                    vec![0],
+                    all(44, &|i| (i as i16 - 20, (3 * i % 7) as i16)),
+                ),
+                (
+                    0xA000,
+                    vec![-16384],
+                    vec![4, 3, 0, 10, 18, 13],
+                    vec![-30, 12, 8, -20, 4, 9, -6, -15],
+                ),
+            ],
+            0,
+        );
+        variations[70] = store(
+            &[(
+                0xE000,
+                vec![8192, 0, 16384],
+                vec![0],
+                all(31, &|i| (5, -(i as i16 % 3))),
+            )],
+            0,
+        );
+        variations[171] = store(
+            &[(
+                0x2000,
+                vec![],
+                vec![0],
+                all(6, &|i| {
+                    [(0, 0), (150, 200), (0, 0), (30, 0)]
+                        .get(i)
+                        .copied()
+                        .unwrap_or((0, 0))
+                }),
+            )],
+            0,
+        );
+
+        let shared = 20 + 4 * (num_glyphs + 1);
+        let mut gvar = vec![0, 1, 0, 0, 0, 1, 0, 1];
+        gvar.extend((shared as u32).to_be_bytes());
+        gvar.extend((num_glyphs as u16).to_be_bytes());
+        gvar.extend([0, 1]);
+        gvar.extend((shared as u32 + 2).to_be_bytes());
+        let mut offset = 0_u32;
+        for variation in &variations {
+            gvar.extend(offset.to_be_bytes());
+            offset += variation.len() as u32;
+        }
+        gvar.extend(offset.to_be_bytes());
+        gvar.extend(16384_i16.to_be_bytes());
+        gvar.extend(variations.concat());
+
+        let cvt: Vec<u8> =
+            [100_i16, 200, -50, 0].iter().flat_map(|v| v.to_be_bytes()).collect();
+        let mut cvar = vec![0, 1, 0, 0];
+        cvar.extend(store(&[(0xA000, vec![16384], vec![2, 1, 1, 2], vec![20, -8])], 4));
+
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        for (tag, table) in [
+            (Tag::FVAR, fvar),
+            (Tag::AVAR, avar),
+            (Tag::GVAR, gvar),
+            (Tag::CVT, cvt),
+            (Tag::CVAR, cvar),
+        ] {
+            tables.push((tag, Cow::Owned(table)));
+        }
+        super::construct(face.kind(), tables, 4)
+    }
+
+    #[test]
+    fn test_instance() {
+        let data = variable_noto();
+        let wght = ttf_parser::Tag::from_bytes(b"wght");
+        for value in [100.0, 250.0, 400.0, 650.0, 900.0, 2000.0] {
+            let instance =
+                Instancer::new().pin(Tag(*b"wght"), value).instantiate(&data, 0);
+            let instance = instance.unwrap();
+            let mut ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+            ttf.set_variation(wght, value);
+            let ttfi = ttf_parser::Face::parse(&instance, 0).unwrap();
+            assert!(!ttfi.is_variable());
+            assert!(ttfi.tables().gvar.is_none());
+            assert_eq!(ttfi.weight().to_number(), value.min(900.0) as u16);
+
+            for id in [0, 68, 69, 70, 171] {
+                let id = ttf_parser::GlyphId(id);
+                let (mut sink1, mut sink2) = (Sink::default(), Sink::default());
+                let bbox1 = ttf.outline_glyph(id, &mut sink1).unwrap();
+                let bbox2 = ttfi.outline_glyph(id, &mut sink2).unwrap();
+                let (points1, points2) = (sink1.points(), sink2.points());
+                assert_eq!(points1.len(), points2.len());
+                for (a, b) in points1.iter().zip(&points2) {
+                    assert!((a - b).abs() <= 1.0, "{a} != {b} for {id:?} at {value}");
+                }
+                let bbox = ttfi.glyph_bounding_box(id).unwrap();
+                assert_eq!((bbox.x_min, bbox.x_max), (bbox2.x_min, bbox2.x_max));
+                assert!((bbox1.y_max - bbox.y_max).abs() <= 1);
+                let (advance1, advance2) = (
+                    ttf.glyph_hor_advance(id).unwrap(),
+                    ttfi.glyph_hor_advance(id).unwrap(),
+                );
+                assert!(
+                    advance1.abs_diff(advance2) <= 1,
+                    "{advance1} != {advance2} {id:?} {value}"
+                );
+            }
+        }
+
+        // 650 maps to 0.5 and then to 0.5833 with avar.
+        let instance = Instancer::new().pin(Tag(*b"wght"), 650.0).instantiate(&data, 0);
+        let instance = instance.unwrap();
+        let ttfi = ttf_parser::Face::parse(&instance, 0).unwrap();
+        let cvt = ttfi.raw_face().table(ttf_parser::Tag::from_bytes(b"cvt ")).unwrap();
+        assert_eq!(cvt, [0, 100, 0, 212, 255, 206, 255, 251]);
+
+        // Static fonts keep their tables.
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let instance = Instancer::new().pin(Tag(*b"wght"), 700.0).instantiate(&data, 0);
+        let instance = instance.unwrap();
+        let (face, facei) = (parse(&data, 0).unwrap(), parse(&instance, 0).unwrap());
+        for (&(tag, table), &(tagi, tablei)) in face.tables.iter().zip(&facei.tables) {
+            assert_eq!(tag, tagi);
+            assert!(tag == Tag::HEAD || table == tablei);
+        }
+    }
+
+    #[test]
+    fn test_instance_slant() {
+        // Turn the weight axis of the variable Noto Sans into a slant axis from
+        // -15 to 0 degrees.
+        let data = variable_noto();
+        let face = parse(&data, 0).unwrap();
+        let mut fvar = face.table(Tag::FVAR).unwrap().to_vec();
+        fvar[16..20].copy_from_slice(b"slnt");
+        for (i, value) in [-15_i32, 0, 0].into_iter().enumerate() {
+            fvar[20 + 4 * i..24 + 4 * i].copy_from_slice(&(value << 16).to_be_bytes());
+        }
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.iter_mut().find(|(tag, _)| *tag == Tag::FVAR).unwrap().1 = fvar.into();
+        let data = super::construct(face.kind(), tables, 4);
+
+        let regular = |ttf: &ttf_parser::Face| {
+            let os2 = ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"OS/2")).unwrap();
+            os2[63] & 1 << 6 != 0
+        };
+        let caret = |ttf: &ttf_parser::Face| {
+            let hhea =
+                ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"hhea")).unwrap();
+            let rise = i16::from_be_bytes([hhea[18], hhea[19]]);
+            (rise, i16::from_be_bytes([hhea[20], hhea[21]]))
+        };
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        assert!(!ttf.is_italic() && regular(&ttf));
+
+        let instance = Instancer::new()
+            .pin(Tag(*b"slnt"), -12.0)
+            .instantiate(&data, 0)
+            .unwrap();
+        let ttfi = ttf_parser::Face::parse(&instance, 0).unwrap();
+        assert_eq!(ttfi.italic_angle(), Some(-12.0));
+        assert_eq!(caret(&ttfi), (1000, 213));
+        assert!(ttfi.is_italic() && !regular(&ttfi));
+
+        // Positions beyond the axis are clamped, and upright instances are
+        // regular.
+        let instance = Instancer::new()
+            .pin(Tag(*b"slnt"), 10.0)
+            .instantiate(&data, 0)
+            .unwrap();
+        let ttfi = ttf_parser::Face::parse(&instance, 0).unwrap();
+        assert_eq!(ttfi.italic_angle(), Some(0.0));
+        assert_eq!(caret(&ttfi), (1, 0));
+        assert!(!ttfi.is_italic() && regular(&ttfi));
+
+        // Fonts without the axis keep their style.
+        let data = variable_noto();
+        let instance = Instancer::new()
+            .pin(Tag(*b"slnt"), -12.0)
+            .instantiate(&data, 0)
+            .unwrap();
+        let (ttf, ttfi) = (
+            ttf_parser::Face::parse(&data, 0).unwrap(),
+            ttf_parser::Face::parse(&instance, 0).unwrap(),
+        );
+        assert_eq!(ttfi.italic_angle(), ttf.italic_angle());
+        assert_eq!(caret(&ttfi), caret(&ttf));
+    }
+
+    #[test]
+    fn test_subset_gvar() {
+        let data = variable_noto();
+        let wght = ttf_parser::Tag::from_bytes(b"wght");
+        let shared_count = |ttf: &ttf_parser::Face| {
+            let gvar =
+                ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"gvar")).unwrap();
+            u16::from_be_bytes([gvar[6], gvar[7]])
+        };
+        // Compare the outlines of the source glyphs with those of the subset.
+        let check = |subs: &[u8], pairs: &[(u16, u16)]| {
+            for value in [100.0, 650.0, 900.0] {
+                let mut ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+                let mut ttfs = ttf_parser::Face::parse(subs, 0).unwrap();
+                ttf.set_variation(wght, value);
+                ttfs.set_variation(wght, value);
+                for &(id, new) in pairs {
+                    let (mut sink1, mut sink2) = (Sink::default(), Sink::default());
+                    ttf.outline_glyph(ttf_parser::GlyphId(id), &mut sink1);
+                    ttfs.outline_glyph(ttf_parser::GlyphId(new), &mut sink2);
+                    assert_eq!(sink1.points(), sink2.points(), "{id} at {value}");
+                }
+            }
+        };
+
+        let profile = Profile::pdf(&[68, 70]).keep_variations(true);
+        let subs = subset(&data, 0, profile).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert!(ttfs.is_variable());
+        assert_eq!(shared_count(&ttfs), 1);
+        assert!(ttfs.raw_face().table(ttf_parser::Tag::from_bytes(b"cvar")).is_some());
+        assert!(ttfs
+            .outline_glyph(ttf_parser::GlyphId(171), &mut Sink::default())
+            .is_none());
+        check(&subs, &[(68, 68), (70, 70)]);
+
+        // Glyph 70 embeds its tuples, so the shared tuple is removed.
+        let profile = Profile::pdf(&[70]).keep_variations(true);
+        let subs = subset(&data, 0, profile).unwrap();
+        let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert_eq!(shared_count(&ttfs), 0);
+        check(&subs, &[(70, 70)]);
+
+        let profile =
+            Profile::pdf(&[68, 70, 171]).keep_variations(true).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        assert_eq!(ttf_parser::Face::parse(&subs, 0).unwrap().number_of_glyphs(), 6);
+        check(&subs, &map);
+
+        // Without the variation tables, the subset is static.
+        let subs = subset(&data, 0, Profile::pdf(&[68, 70])).unwrap();
+        assert!(!ttf_parser::Face::parse(&subs, 0).unwrap().is_variable());
+    }
+
+    #[test]
+    fn test_subset_hvar() {
+        let data = variable_noto();
+        let num_glyphs = 3748;
+        let delta = |glyph: usize| (glyph % 97) as i8 - 48;
+
+        // An implicit advance map and an explicit side bearing map into a
+        // store with a delta set for each glyph.
+        let store_len = 22 + 8 + num_glyphs;
+        let mut hvar = vec![0, 1, 0, 0, 0, 0, 0, 20, 0, 0, 0, 0];
+        hvar.extend((20 + store_len as u32).to_be_bytes());
+        hvar.extend([0, 0, 0, 0]);
+        hvar.extend([0, 1, 0, 0, 0, 12, 0, 1, 0, 0, 0, 22]);
+        hvar.extend([0, 1, 0, 1, 0, 0, 64, 0, 64, 0]);
+        hvar.extend((num_glyphs as u16).to_be_bytes());
+        hvar.extend([0, 0, 0, 1, 0, 0]);
+        hvar.extend((0..num_glyphs).map(|glyph| delta(glyph) as u8));
+        hvar.extend([0, 0x1B]);
+        hvar.extend((num_glyphs as u16).to_be_bytes());
+        hvar.extend((0..num_glyphs).flat_map(|glyph| (glyph as u16 % 10).to_be_bytes()));
+
+        let face = parse(&data, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.push((Tag::HVAR, Cow::Owned(hvar.clone())));
+        let data = super::construct(face.kind(), tables, 4);
+
+        let wght = ttf_parser::Tag::from_bytes(b"wght");
+        let hvar_len = |subs: &[u8]| {
+            let ttf = ttf_parser::Face::parse(subs, 0).unwrap();
+            ttf.raw_face()
+                .table(ttf_parser::Tag::from_bytes(b"HVAR"))
+                .unwrap()
+                .len()
+        };
+        let check = |subs: &[u8], pairs: &[(u16, u16)]| {
+            for value in [100.0, 650.0, 900.0] {
+                let mut ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+                let mut ttfs = ttf_parser::Face::parse(subs, 0).unwrap();
+                ttf.set_variation(wght, value);
+                ttfs.set_variation(wght, value);
+                for &(id, new) in pairs {
+                    assert_eq!(
+                        ttf.glyph_hor_advance(ttf_parser::GlyphId(id)),
+                        ttfs.glyph_hor_advance(ttf_parser::GlyphId(new)),
+                        "{id} at {value}"
+                    );
+                }
+            }
+        };
+
+        let glyphs = [68, 70, 171];
+        let profile = Profile::pdf(&glyphs).keep_variations(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        assert_eq!(computed.table(Tag::HVAR), TableAction::Subset);
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        assert!(hvar_len(&subs) < hvar.len() / 10);
+        check(&subs, &map);
+        let mut ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let advance = ttfs.glyph_hor_advance(ttf_parser::GlyphId(68)).unwrap();
+        ttfs.set_variation(wght, 900.0);
+        let heavy = ttfs.glyph_hor_advance(ttf_parser::GlyphId(68)).unwrap();
+        assert_eq!(heavy, advance + delta(68) as u16);
+
+        let profile = Profile::pdf(&glyphs).keep_variations(true).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        assert!(hvar_len(&subs) < hvar.len() / 10);
+        check(&subs, &map);
+
+        // A second region that only the deltas of glyph 500 vary in.
+        let mut hvar = vec![0, 1, 0, 0, 0, 0, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        hvar.extend([0, 1, 0, 0, 0, 12, 0, 1, 0, 0, 0, 28]);
+        hvar.extend([0, 1, 0, 2, 0, 0, 64, 0, 64, 0, 0, 0, 32, 0, 64, 0]);
+        hvar.extend((num_glyphs as u16).to_be_bytes());
+        hvar.extend([0, 0, 0, 2, 0, 0, 0, 1]);
+        hvar.extend(
+            (0..num_glyphs)
+                .flat_map(|glyph| [delta(glyph) as u8, (glyph == 500) as u8 * 9]),
+        );
+        let variable = variable_noto();
+        let face = parse(&variable, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.push((Tag::HVAR, Cow::Owned(hvar)));
+        let data = super::construct(face.kind(), tables, 4);
+        let regions = |subs: &[u8]| {
+            let ttf = ttf_parser::Face::parse(subs, 0).unwrap();
+            let hvar =
+                ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"HVAR")).unwrap();
+            let store = u32::read_at(hvar, 4).unwrap() as usize;
+            let list = u32::read_at(hvar, store + 2).unwrap() as usize;
+            u16::read_at(hvar, store + list + 2).unwrap()
+        };
+        assert_eq!(regions(&data), 2);
+        for (glyphs, count) in [(&[68, 70, 171][..], 1), (&[68, 500][..], 2)] {
+            let profile = Profile::pdf(glyphs).keep_variations(true).remap_glyphs(true);
+            let computed = plan(&data, 0, profile).unwrap();
+            let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+            assert_eq!(regions(&subs), count);
+            for value in [100.0, 400.0, 650.0, 900.0] {
+                let mut ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+                let mut ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+                ttf.set_variation(wght, value);
+                ttfs.set_variation(wght, value);
+                for &(id, new) in &map {
+                    assert_eq!(
+                        ttf.glyph_hor_advance(ttf_parser::GlyphId(id)),
+                        ttfs.glyph_hor_advance(ttf_parser::GlyphId(new)),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Records the drawing commands of a color glyph, with the glyph IDs of
+    /// the original font.
+    struct Recorder {
+        original: HashMap<u16, u16>,
+        palette: u16,
+        commands: Vec<String>,
+    }
+
+    impl Recorder {
+        fn paint(
+            ttf: &ttf_parser::Face,
+            glyph: u16,
+            palette: u16,
+            map: &[(u16, u16)],
+        ) -> Vec<String> {
+            let original = map.iter().map(|&(old, new)| (new, old)).collect();
+            let mut recorder = Self { original, palette, commands: vec![] };
+            let black = ttf_parser::RgbaColor::new(0, 0, 0, 255);
+            ttf.paint_color_glyph(
+                ttf_parser::GlyphId(glyph),
+                palette,
+                black,
+                &mut recorder,
+            )
+            .unwrap();
+            recorder.commands
+        }
+    }
+
+    impl<'a> ttf_parser::colr::Painter<'a> for Recorder {
+        fn outline_glyph(&mut self, glyph: ttf_parser::GlyphId) {
+            let glyph = self.original.get(&glyph.0).copied();
+            self.commands.push(format!("outline {glyph:?}"));
+        }
+
+        fn paint(&mut self, paint: ttf_parser::colr::Paint<'a>) {
+            use ttf_parser::colr::Paint;
+            let stops: Vec<_> = match &paint {
+                Paint::Solid(_) => vec![],
+                Paint::LinearGradient(g) => g.stops(self.palette, &[]).collect(),
+                Paint::RadialGradient(g) => g.stops(self.palette, &[]).collect(),
+                Paint::SweepGradient(g) => g.stops(self.palette, &[]).collect(),
+            };
+            self.commands.push(format!("paint {paint:?} {stops:?}"));
+        }
+
+        fn push_clip(&mut self) {
+            self.commands.push("clip".into());
+        }
+
+        fn push_clip_box(&mut self, clip: ttf_parser::colr::ClipBox) {
+            self.commands.push(format!("clip {clip:?}"));
+        }
+
+        fn pop_clip(&mut self) {
+            self.commands.push("pop clip".into());
+        }
+
+        fn push_layer(&mut self, mode: ttf_parser::colr::CompositeMode) {
+            self.commands.push(format!("layer {mode:?}"));
+        }
+
+        fn pop_layer(&mut self) {
+            self.commands.push("pop layer".into());
+        }
+
+        fn push_translate(&mut self, tx: f32, ty: f32) {
+            self.commands.push(format!("translate {tx} {ty}"));
+        }
+
+        fn push_scale(&mut self, sx: f32, sy: f32) {
+            self.commands.push(format!("scale {sx} {sy}"));
+        }
+
+        fn push_rotate(&mut self, angle: f32) {
+            self.commands.push(format!("rotate {angle}"));
+        }
+
+        fn push_skew(&mut self, skew_x: f32, skew_y: f32) {
+            self.commands.push(format!("skew {skew_x} {skew_y}"));
+        }
+
+        fn push_transform(&mut self, t: ttf_parser::Transform) {
+            let ttf_parser::Transform { a, b, c, d, e, f } = t;
+            self.commands.push(format!("transform {a} {b} {c} {d} {e} {f}"));
+        }
+
+        fn pop_transform(&mut self) {
+            self.commands.push("pop transform".into());
+        }
+    }
+
+    #[test]
+    fn test_subset_colr() {
+        let data = std::fs::read("fonts/COLRv1StaticTestGlyphs-Regular.ttf").unwrap();
+        let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+        let table_len = |data: &[u8], tag: &[u8; 4]| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            ttf.raw_face()
+                .table(ttf_parser::Tag::from_bytes(tag))
+                .map(<[u8]>::len)
+        };
+        let identity: Vec<(u16, u16)> =
+            (0..ttf.number_of_glyphs()).map(|glyph| (glyph, glyph)).collect();
+        let color: Vec<u16> = (0..ttf.number_of_glyphs())
+            .filter(|&glyph| ttf.is_color_glyph(ttf_parser::GlyphId(glyph)))
+            .collect();
+        let palettes = ttf.color_palettes().unwrap().get();
+
+        // Every seventh color glyph, the COLRv0 glyph 168 and glyph 166, which
+        // reuses another color glyph.
+        let mut glyphs: Vec<u16> = color.iter().copied().step_by(7).collect();
+        glyphs.extend([166, 168]);
+        for remap in [false, true] {
+            let profile = Profile::pdf(&glyphs).keep_color(true).remap_glyphs(remap);
+            let computed = plan(&data, 0, profile).unwrap();
+            assert_eq!(computed.table(Tag::COLR), TableAction::Subset);
+            assert_eq!(computed.table(Tag::CPAL), TableAction::Subset);
+            let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+            assert!(table_len(&subs, b"COLR") < table_len(&data, b"COLR"));
+            assert!(table_len(&subs, b"CPAL") < table_len(&data, b"CPAL"));
+
+            let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+            assert_eq!(ttfs.color_palettes().unwrap().get(), palettes);
+            for &(old, new) in &map {
+                let glyph = ttf_parser::GlyphId(new);
+                assert_eq!(ttfs.is_color_glyph(glyph), color.contains(&old), "{old}");
+                if !color.contains(&old) {
+                    continue;
+                }
+                for palette in 0..palettes {
+                    assert_eq!(
+                        Recorder::paint(&ttf, old, palette, &identity),
+                        Recorder::paint(&ttfs, new, palette, &map),
+                        "{old} in palette {palette}"
+                    );
+                }
+            }
+        }
+
+        // Without color glyphs, both tables are dropped.
+        let profile = Profile::pdf(&[1, 2]).keep_color(true);
+        let subs = subset(&data, 0, profile).unwrap();
+        assert_eq!(table_len(&subs, b"COLR"), None);
+        assert_eq!(table_len(&subs, b"CPAL"), None);
+
+        let subs = subset(&data, 0, Profile::pdf(&glyphs)).unwrap();
+        assert_eq!(table_len(&subs, b"COLR"), None);
+    }
+
+    #[test]
+    fn test_inflate() {
+        // "hello hello hello" in a stored block and with fixed Huffman codes,
+        // compressed by zlib.
+        let hello = b"hello hello hello";
+        let stored = [
+            0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x01, 0x11, 0x00,
+            0xEE, 0xFF, 0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x20, 0x68, 0x65, 0x6C, 0x6C, 0x6F,
+            0x20, 0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x80, 0x88, 0xF9, 0xE5, 0x11, 0x00, 0x00,
+            0x00,
+        ];
+        let fixed = [
+            0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xCB, 0x48, 0xCD,
+            0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x90, 0x00, 0x80, 0x88, 0xF9, 0xE5, 0x11, 0x00,
+            0x00, 0x00,
+        ];
+        // Pseudo-random DNA letters, which zlib compresses with dynamic codes.
+        let letters: Vec<u8> = (0..60u32)
+            .map(|i| b"acgt"[((i * i * i + 7 * i) % 251 % 4) as usize])
+            .collect();
+        let dynamic = [
+            0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x15, 0x8A, 0xC1,
+            0x0D, 0x00, 0x30, 0x10, 0x82, 0x66, 0x25, 0x3E, 0x58, 0x80, 0xFD, 0xD3, 0xEB,
+            0x47, 0x0C, 0x0A, 0x02, 0xCD, 0x5C, 0xDB, 0x60, 0x71, 0x4C, 0x8E, 0x16, 0xA5,
+            0xE7, 0x6F, 0xE7, 0xC7, 0xF5, 0xE9, 0x7F, 0xF5, 0x00, 0x3B, 0xFA, 0x8A, 0x7B,
+            0x3C, 0x00, 0x00, 0x00,
+        ];
+
+        for (stream, data) in
+            [(&stored[..], &hello[..]), (&fixed, hello), (&dynamic, &letters)]
+        {
+            assert_eq!(gzip::decompress(stream).unwrap(), data);
+            assert_eq!(gzip::decompress(&gzip::compress(data)).unwrap(), data);
+
+            // Truncated streams fail without panicking.
+            for len in 0..stream.len() {
+                assert!(gzip::decompress(&stream[..len]).is_err());
+            }
+
+            // So do mismatches of the checksum and of the size.
+            for at in [stream.len() - 8, stream.len() - 4] {
+                let mut corrupt = stream.to_vec();
+                corrupt[at] ^= 1;
+                assert_eq!(gzip::decompress(&corrupt), Err(Error::InvalidData));
+            }
+        }
+
+        // A stored block whose length doesn't match its complement.
+        let mut corrupt = stored.to_vec();
+        corrupt[13] ^= 1;
+        assert_eq!(gzip::decompress(&corrupt), Err(Error::InvalidData));
+
+        // A match of distance 1 at the start of the output, with fixed codes.
+        let header = [0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF];
+        let mut before = header.to_vec();
+        before.extend([0x03, 0x02, 0x00, 0, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(gzip::decompress(&before), Err(Error::InvalidData));
+
+        // zlib streams end with an Adler-32 checksum instead.
+        let mut zlib = gzip::zlib_compress(hello);
+        assert_eq!(gzip::zlib_decompress(&zlib).unwrap(), hello);
+        *zlib.last_mut().unwrap() ^= 1;
+        assert_eq!(gzip::zlib_decompress(&zlib), Err(Error::InvalidData));
+
+        // The reserved block type.
+        let mut reserved = header.to_vec();
+        reserved.extend([0x07, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(gzip::decompress(&reserved), Err(Error::InvalidData));
+    }
+
+    /// Add an `SVG ` table to Noto Sans with a plain document for the glyphs
+    /// 68 to 71, of which 70 uses 71, and a compressed one for 72 and 73.
+    fn with_svg() -> Vec<u8> {
+        let shared = concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg">"##,
+            r##"<g id="glyph68"><rect width="10" height="10"/></g>"##,
+            r##"<g id="glyph69"><g><rect width="20" height="20"/></g></g>"##,
+            r##"<g id="glyph70"><use href="#glyph71"/></g>"##,
+            r##"<path id="glyph71" d="M0 0h30v30z"/>"##,
+            "</svg>",
+        );
+        let compressed = gzip::compress(
+            concat!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg">"##,
+                r##"<circle id="glyph72" r="40" fill="var(--color0)"/>"##,
+                r##"<circle id="glyph73" r="50"/>"##,
+                "</svg>",
+            )
+            .as_bytes(),
+        );
+
+        let mut svg = vec![0, 0, 0, 0, 0, 10, 0, 0, 0, 0, 0, 2];
+        let mut offset = 2 + 2 * 12;
+        for (start, end, document) in
+            [(68u16, 71u16, shared.as_bytes()), (72, 73, &compressed)]
+        {
+            svg.extend(start.to_be_bytes());
+            svg.extend(end.to_be_bytes());
+            svg.extend((offset as u32).to_be_bytes());
+            svg.extend((document.len() as u32).to_be_bytes());
+            offset += document.len();
+        }
+        svg.extend(shared.as_bytes());
+        svg.extend(&compressed);
+
+        with_tables(vec![(Tag::SVG, svg)])
+    }
+
+    /// Add tables to Noto Sans, replacing those it already has.
+    fn with_tables(extra: Vec<(Tag, Vec<u8>)>) -> Vec<u8> {
+        let data = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&data, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .filter(|&&(tag, _)| extra.iter().all(|&(t, _)| t != tag))
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.extend(extra.into_iter().map(|(tag, data)| (tag, Cow::Owned(data))));
+        super::construct(face.kind(), tables, 4)
+    }
+
+    #[test]
+    fn test_subset_svg() {
+        let data = with_svg();
+        let document = |data: &[u8], glyph: u16| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            let svg = ttf.glyph_svg_image(ttf_parser::GlyphId(glyph))?;
+            let range = svg.start_glyph_id.0..=svg.end_glyph_id.0;
+            let text = match gzip::is_gzip(svg.data) {
+                true => gzip::decompress(svg.data).unwrap(),
+                false => svg.data.to_vec(),
+            };
+            Some((range, String::from_utf8(text).unwrap()))
+        };
+
+        let profile = Profile::pdf(&[68, 70, 72, 80]).keep_color(true);
+        let subs = subset(&data, 0, profile).unwrap();
+        let (range, shared) = document(&subs, 68).unwrap();
+        assert_eq!(range, 68..=68);
+        assert_eq!(document(&subs, 70).unwrap(), (70..=70, shared.clone()));
+        assert_eq!(document(&subs, 69), None);
+        assert!(shared.contains(r#"<g id="glyph68">"#));
+        assert!(!shared.contains("glyph69") && !shared.contains(r#"height="20""#));
+        assert!(shared.contains(r#"<path id="glyph71""#));
+        let (range, circle) = document(&subs, 72).unwrap();
+        assert_eq!(range, 72..=72);
+        assert!(circle.contains("glyph72") && !circle.contains("glyph73"));
+        assert_eq!(document(&subs, 80), None);
+
+        // Renumbered glyphs are renamed in the documents.
+        let profile = Profile::pdf(&[68, 70, 72]).keep_color(true).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        assert_eq!(map, [(0, 0), (68, 1), (70, 2), (72, 3)]);
+        let (range, shared) = document(&subs, 1).unwrap();
+        assert_eq!(range, 1..=2);
+        assert!(shared.contains(r#"<g id="glyph1">"#));
+        assert!(shared.contains(r##"<g id="glyph2"><use href="#glyph71"/></g>"##));
+        let (range, circle) = document(&subs, 3).unwrap();
+        assert_eq!(range, 3..=3);
+        assert!(circle.contains(r#"<circle id="glyph3" r="40""#));
+
+        // The same with a chosen glyph order.
+        let profile = Profile::pdf(&[68, 70, 72])
+            .keep_color(true)
+            .remap_glyphs(true)
+            .glyph_order(&[72, 68]);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        assert_eq!(map, [(0, 0), (68, 2), (70, 3), (72, 1)]);
+        assert_eq!(document(&subs, 2).unwrap().0, 2..=3);
+        let (range, circle) = document(&subs, 1).unwrap();
+        assert_eq!(range, 1..=1);
+        assert!(circle.contains(r#"<circle id="glyph1" r="40""#));
+
+        // Without SVG glyphs, the table is dropped.
+        let profile = Profile::pdf(&[1, 2]).keep_color(true);
+        let subs = subset(&data, 0, profile).unwrap();
+        assert_eq!(document(&subs, 68), None);
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        assert!(ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"SVG ")).is_none());
+    }
+
+    /// The start of a PNG image whose width is the glyph ID.
+    fn png(glyph: u16) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend([u32::from(glyph), 1].iter().flat_map(|v| v.to_be_bytes()));
+        png
+    }
+
+    /// Create a bitmap location table and its data table with a strike for
+    /// each list of index subtables. A subtable is given by its index and
+    /// image format, the big metrics that its glyphs share (if any) and the
+    /// glyphs with their images.
+    type Subtable = (u16, u16, Option<[u8; 8]>, Vec<(u16, Vec<u8>)>);
+    fn bitmap_tables(version: u16, strikes: &[Vec<Subtable>]) -> (Vec<u8>, Vec<u8>) {
+        let mut data = [version.to_be_bytes(), [0, 0]].concat();
+        let mut arrays = vec![];
+        for subtables in strikes {
+            let mut array = vec![];
+            let mut bodies = vec![];
+            for (index_format, image_format, metrics, glyphs) in subtables {
+                let first = glyphs[0].0;
+                let last = glyphs[glyphs.len() - 1].0;
+                let mut body =
+                    [index_format.to_be_bytes(), image_format.to_be_bytes()].concat();
+                body.extend((data.len() as u32).to_be_bytes());
+                let start = data.len();
+                match index_format {
+                    1 => {
+                        for glyph in first..=last + 1 {
+                            body.extend(((data.len() - start) as u32).to_be_bytes());
+                            if let Some((_, image)) = glyphs.iter().find(|g| g.0 == glyph)
+                            {
+                                data.extend(image);
+                            }
+                        }
+                    }
+                    2 => {
+                        body.extend((glyphs[0].1.len() as u32).to_be_bytes());
+                        body.extend(metrics.unwrap());
+                        glyphs.iter().for_each(|(_, image)| data.extend(image));
+                    }
+                    _ => {
+                        body.extend((glyphs.len() as u32).to_be_bytes());
+                        for (glyph, image) in glyphs.iter().chain([&(0, vec![])]) {
+                            body.extend(glyph.to_be_bytes());
+                            body.extend(((data.len() - start) as u16).to_be_bytes());
+                            data.extend(image);
+                        }
+                    }
+                }
+                array.push((first, last));
+                bodies.push(body);
+            }
+            let mut at = 8 * array.len();
+            let mut table = vec![];
+            for (&(first, last), body) in array.iter().zip(&bodies) {
+                table.extend([first.to_be_bytes(), last.to_be_bytes()].concat());
+                table.extend((at as u32).to_be_bytes());
+                at += body.len();
+            }
+            table.extend(bodies.concat());
+            let glyphs = subtables.iter().flat_map(|subtable| &subtable.3);
+            let first = glyphs.clone().map(|g| g.0).min().unwrap();
+            let last = glyphs.map(|g| g.0).max().unwrap();
+            arrays.push((table, subtables.len(), first, last));
+        }
+
+        let mut location = [version.to_be_bytes(), [0, 0]].concat();
+        location.extend((strikes.len() as u32).to_be_bytes());
+        let mut at = 8 + 48 * strikes.len();
+        for (table, count, first, last) in &arrays {
+            location.extend((at as u32).to_be_bytes());
+            location.extend((table.len() as u32).to_be_bytes());
+            location.extend((*count as u32).to_be_bytes());
+            location.extend([0; 28]);
+            location.extend([first.to_be_bytes(), last.to_be_bytes()].concat());
+            // Color bitmaps have 32 bits per pixel, the others one.
+            let depth = if version == 3 { 32 } else { 1 };
+            location.extend([109, 109, depth, 1]);
+            at += table.len();
+        }
+        location.extend(arrays.iter().flat_map(|(table, ..)| table.clone()));
+        (location, data)
+    }
+
+    #[test]
+    fn test_subset_bitmaps() {
+        let table_count = |data: &[u8], tag: &[u8; 4]| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            let table = ttf.raw_face().table(ttf_parser::Tag::from_bytes(tag));
+            table.map(|table| u32::from_be_bytes(table[4..8].try_into().unwrap()))
+        };
+
+        // Color bitmaps with their own metrics and with shared metrics, and a
+        // second strike with only removed glyphs.
+        let image =
+            |glyph| [&[1, 1, 0, 1, 1][..], &24u32.to_be_bytes(), &png(glyph)].concat();
+        let shared = |glyph| [&24u32.to_be_bytes()[..], &png(glyph)].concat();
+        let (cblc, cbdt) = bitmap_tables(
+            3,
+            &[
+                vec![
+                    (1, 17, None, [68, 69, 71].map(|g| (g, image(g))).to_vec()),
+                    (
+                        2,
+                        19,
+                        Some([1, 1, 0, 1, 1, 0, 0, 1]),
+                        (72..=74).map(|g| (g, shared(g))).collect(),
+                    ),
+                ],
+                vec![(1, 17, None, vec![(100, image(100))])],
+            ],
+        );
+        let data = with_tables(vec![(Tag::CBLC, cblc), (Tag::CBDT, cbdt)]);
+        let width = |data: &[u8], glyph: u16| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            ttf.glyph_raster_image(ttf_parser::GlyphId(glyph), 109).map(|image| {
+                u32::from_be_bytes(image.data[16..20].try_into().unwrap()) as u16
+            })
+        };
+        for glyph in [68, 69, 71, 72, 73, 74, 100] {
+            assert_eq!(width(&data, glyph), Some(glyph));
+        }
+
+        let profile = Profile::pdf(&[68, 71, 73, 74]).keep_bitmaps(true);
+        let subs = subset(&data, 0, profile).unwrap();
+        assert_eq!(table_count(&subs, b"CBLC"), Some(1));
+        for glyph in [68, 71, 73, 74] {
+            assert_eq!(width(&subs, glyph), Some(glyph));
+        }
+        for glyph in [69, 72, 100] {
+            assert_eq!(width(&subs, glyph), None);
+        }
+
+        let profile =
+            Profile::pdf(&[68, 71, 73, 74]).keep_bitmaps(true).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        for (old, new) in map.into_iter().skip(1) {
+            assert_eq!(width(&subs, new), Some(old));
+        }
+
+        let subs = subset(&data, 0, Profile::pdf(&[1, 2]).keep_bitmaps(true)).unwrap();
+        assert_eq!(table_count(&subs, b"CBLC"), None);
+        assert_eq!(table_count(&subs, b"CBDT"), None);
+        let subs = subset(&data, 0, Profile::pdf(&[68])).unwrap();
+        assert_eq!(table_count(&subs, b"CBLC"), None);
+
+        // A composite bitmap pulls in its component.
+        let bits = |glyph: u16| [&[1, 8][..], &[0; 6], &[glyph as u8]].concat();
+        let composite = |glyph: u16| {
+            [&[2, 8][..], &[0; 6], &[0, 1], &glyph.to_be_bytes(), &[0, 0]].concat()
+        };
+        let (eblc, ebdt) = bitmap_tables(
+            2,
+            &[vec![
+                (4, 7, None, vec![(80, bits(80)), (81, bits(81)), (82, bits(82))]),
+                (4, 9, None, vec![(90, composite(82))]),
+            ]],
+        );
+        let data = with_tables(vec![(Tag::EBLC, eblc), (Tag::EBDT, ebdt)]);
+        let computed =
+            plan(&data, 0, Profile::pdf(&[80, 90]).keep_bitmaps(true)).unwrap();
+        assert_eq!(computed.glyphs(), [0, 80, 82, 90]);
+        let pixels = |data: &[u8], glyph: u16| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            let image = ttf.glyph_raster_image(ttf_parser::GlyphId(glyph), 109)?;
+            Some(image.data.to_vec())
+        };
+        assert_eq!(pixels(&data, 81), Some(vec![81]));
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        assert_eq!(pixels(&subs, 80), Some(vec![80]));
+        assert_eq!(pixels(&subs, 81), None);
+        assert_eq!(pixels(&subs, 82), Some(vec![82]));
+
+        let profile = Profile::pdf(&[80, 90]).keep_bitmaps(true).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        assert_eq!(map, [(0, 0), (80, 1), (82, 2), (90, 3)]);
+        assert_eq!(pixels(&subs, 2), Some(vec![82]));
+        let ttf = ttf_parser::Face::parse(&subs, 0).unwrap();
+        let ebdt = ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"EBDT")).unwrap();
+        assert!(ebdt.ends_with(&composite(2)));
+
+        // A `dupe` image pulls in the glyph whose image it reuses.
+        let record =
+            |kind: &[u8; 4], data: &[u8]| [&[0, 0, 0, 0][..], kind, data].concat();
+        let images = [
+            (68, record(b"png ", &png(68))),
+            (69, record(b"dupe", &75u16.to_be_bytes())),
+            (75, record(b"png ", &png(75))),
+        ];
+        let num_glyphs = ttf_parser::Face::parse(&data, 0).unwrap().number_of_glyphs();
+        let mut strike =
+            [64u16, 72].iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<_>>();
+        let mut offset = 4 + 4 * (u32::from(num_glyphs) + 1);
+        let mut bodies: Vec<u8> = vec![];
+        for glyph in 0..=num_glyphs {
+            strike.extend(offset.to_be_bytes());
+            if let Some((_, image)) = images.iter().find(|(g, _)| *g == glyph) {
+                offset += image.len() as u32;
+                bodies.extend(image);
+            }
+        }
+        strike.extend(bodies);
+        let sbix = [&[0, 1, 0, 1, 0, 0, 0, 1, 0, 0, 0, 12][..], &strike].concat();
+        let data = with_tables(vec![(Tag::SBIX, sbix)]);
+        assert_eq!(width(&data, 69), Some(75));
+
+        let computed = plan(&data, 0, Profile::pdf(&[69]).keep_bitmaps(true)).unwrap();
+        assert_eq!(computed.glyphs(), [0, 69, 75]);
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        assert_eq!(width(&subs, 68), None);
+        assert_eq!(width(&subs, 69), Some(75));
+
+        let profile = Profile::pdf(&[69]).keep_bitmaps(true).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let subs = subset_with_plan(&data, 0, &computed).unwrap();
+        assert_eq!(width(&subs, 1), Some(75));
+        assert_eq!(width(&subs, 2), Some(75));
+    }
+
+    #[test]
+    fn test_subset_bitmap_only() {
+        // Noto Sans with its outlines replaced by color bitmaps.
+        let image =
+            |glyph| [&[1, 1, 0, 1, 1][..], &24u32.to_be_bytes(), &png(glyph)].concat();
+        let (cblc, cbdt) = bitmap_tables(
+            3,
+            &[vec![(1, 17, None, [68, 69, 71].map(|g| (g, image(g))).to_vec())]],
+        );
+        let source = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        let face = parse(&source, 0).unwrap();
+        let mut tables: Vec<_> = face
+            .tables
+            .iter()
+            .filter(|&&(tag, _)| tag != Tag::GLYF && tag != Tag::LOCA && tag != Tag::GVAR)
+            .map(|&(tag, data)| (tag, Cow::Borrowed(data)))
+            .collect();
+        tables.extend([(Tag::CBLC, Cow::Owned(cblc)), (Tag::CBDT, Cow::Owned(cbdt))]);
+        let data = super::construct(face.kind(), tables, 4);
+        assert!(debug_dump(&data, 0).unwrap().starts_with("kind: none\n"));
+
+        let has = |data: &[u8], tag: &[u8; 4]| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            ttf.raw_face().table(ttf_parser::Tag::from_bytes(tag)).is_some()
+        };
+        let width = |data: &[u8], glyph: u16| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            ttf.glyph_raster_image(ttf_parser::GlyphId(glyph), 109).map(|image| {
+                u32::from_be_bytes(image.data[16..20].try_into().unwrap()) as u16
+            })
+        };
+
+        let subs = subset(&data, 0, Profile::pdf(&[68, 71]).keep_bitmaps(true)).unwrap();
+        assert!(!has(&subs, b"glyf") && !has(&subs, b"loca"));
+        assert_eq!(width(&subs, 68), Some(68));
+        assert_eq!(width(&subs, 69), None);
+        let head = |data: &[u8]| {
+            let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+            ttf.raw_face().table(ttf_parser::Tag::from_bytes(b"head")).unwrap()[50..52]
+                .to_vec()
+        };
+        assert_eq!(head(&subs), head(&data));
+
+        let profile = Profile::pdf(&[68, 71]).keep_bitmaps(true).remap_glyphs(true);
+        let computed = plan(&data, 0, profile).unwrap();
+        let (subs, map) = subset_with_glyph_map(&data, 0, &computed).unwrap();
+        assert_eq!(map, [(0, 0), (68, 1), (71, 2)]);
+        assert_eq!(width(&subs, 2), Some(71));
+    }
+
+    #[test]
+    fn test_partial_instance() {
+        let data = variable_noto();
+        let wght = Tag(*b"wght");
+        let cvt = |data: &[u8], value: f32| {
+            let instance =
+                Instancer::new().pin(wght, value).instantiate(data, 0).unwrap();
+            let face = parse(&instance, 0).unwrap();
+            face.table(Tag::CVT).unwrap().to_vec()
+        };
+
+        // The default stays inside of the first range and moves to 500 with
+        // the second.
+        for (min, max) in [(300.0, 700.0), (500.0, 800.0)] {
+            let instance = Instancer::new().limit(wght, min, max).instantiate(&data, 0);
+            let instance = instance.unwrap();
+            let ttfi = ttf_parser::Face::parse(&instance, 0).unwrap();
+            let axis = ttfi.variation_axes().get(0).unwrap();
+            assert_eq!(
+                (axis.min_value, axis.def_value, axis.max_value),
+                (min, min.max(400.0), max)
+            );
+
+            for value in [min, (min + max) / 2.0, 620.0, max] {
+                let mut ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+                let mut ttfi = ttf_parser::Face::parse(&instance, 0).unwrap();
+                ttf.set_variation(ttf_parser::Tag::from_bytes(b"wght"), value);
+                ttfi.set_variation(ttf_parser::Tag::from_bytes(b"wght"), value);
+                for id in [0, 68, 69, 70, 171] {
+                    let id = ttf_parser::GlyphId(id);
+                    let (mut sink1, mut sink2) = (Sink::default(), Sink::default());
+                    ttf.outline_glyph(id, &mut sink1);
+                    ttfi.outline_glyph(id, &mut sink2);
+                    let (points1, points2) = (sink1.points(), sink2.points());
+                    assert_eq!(points1.len(), points2.len());
+                    for (a, b) in points1.iter().zip(&points2) {
+                        assert!((a - b).abs() <= 1.0, "{a} != {b} for {id:?} at {value}");
+                    }
+                    let (advance1, advance2) = (
+                        ttf.glyph_hor_advance(id).unwrap(),
+                        ttfi.glyph_hor_advance(id).unwrap(),
+                    );
+                    assert!(advance1.abs_diff(advance2) <= 1, "{advance1} != {advance2}");
+                }
+
+                let (cvt1, cvt2) = (cvt(&data, value), cvt(&instance, value));
+                for (a, b) in cvt1.chunks(2).zip(cvt2.chunks(2)) {
+                    let (a, b) = (
+                        i16::from_be_bytes([a[0], a[1]]),
+                        i16::from_be_bytes([b[0], b[1]]),
+                    );
+                    assert!(a.abs_diff(b) <= 1, "{a} != {b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_variation_axes() {
+        let data = variable_noto();
+        let wght = Tag(*b"wght");
+        let axes = variation_axes(&data, 0).unwrap();
+        assert_eq!(axes.len(), 1);
+        let axis = &axes[0];
+        assert_eq!(
+            (axis.tag, axis.min, axis.default, axis.max),
+            (wght, 100.0, 400.0, 900.0)
+        );
+        assert_eq!((axis.hidden, axis.name.as_deref()), (false, Some("Weight")));
+
+        let instances = named_instances(&data, 0).unwrap();
+        let listed: Vec<_> = instances
+            .iter()
+            .map(|instance| {
+                (instance.name.as_deref().unwrap(), instance.coordinates.clone())
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("Thin", vec![(wght, 100.0)]),
+                ("Regular", vec![(wght, 400.0)]),
+                ("Bold", vec![(wght, 700.0)])
+            ]
+        );
+
+        // Limiting the axis drops the instances outside of the range.
+        let limited = Instancer::new().limit(wght, 300.0, 700.0).instantiate(&data, 0);
+        let limited = named_instances(&limited.unwrap(), 0).unwrap();
+        assert_eq!(limited, instances[1..]);
+
+        let fixed = std::fs::read("fonts/NotoSans-Regular.ttf").unwrap();
+        assert!(variation_axes(&fixed, 0).unwrap().is_empty());
+        assert!(named_instances(&fixed, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_referenced_names() {
+        let data = variable_noto();
+        let ids = |profile: Profile| {
+            let subs = subset(&data, 0, profile).unwrap();
+            let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+            let mut ids: Vec<_> =
+                ttfs.names().into_iter().map(|name| name.name_id).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            ids
+        };
+
+        // The axis and instances of fvar and the axis values of STAT keep
+        // their names along with the essential ones.
+        let referenced: Vec<u16> = (260..=278).collect();
+        let profile = Profile::pdf(&[0, 1]).minimal(true).keep_variations(true);
+        assert_eq!(ids(profile), [&[1, 2, 3, 4, 5, 6], referenced.as_slice()].concat());
+
+        // Even stripped names keep those that the variations refer to.
+        let obfuscation = Obfuscation { seed: 7, strip_names: true };
+        let profile = Profile::pdf(&[0, 1]).keep_variations(true).obfuscate(obfuscation);
+        assert_eq!(ids(profile), referenced);
+
+        // Without the variations, nothing refers to them.
+        assert_eq!(ids(Profile::pdf(&[0, 1]).minimal(true)), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Sink(Vec<Inst>);
+
     #[derive(Debug, PartialEq)]
     enum Inst {
         MoveTo(f32, f32),
@@ -603,6 +5230,21 @@ mod tests {
         Close,
     }
 
+    impl Sink {
+        /// The coordinates of all points, for comparisons with a tolerance.
+        fn points(&self) -> Vec<f32> {
+            self.0
+                .iter()
+                .flat_map(|inst| match *inst {
+                    Inst::MoveTo(x, y) | Inst::LineTo(x, y) => vec![x, y],
+                    Inst::QuadTo(x1, y1, x, y) => vec![x1, y1, x, y],
+                    Inst::CurveTo(x1, y1, x2, y2, x, y) => vec![x1, y1, x2, y2, x, y],
+                    Inst::Close => vec![],
+                })
+                .collect()
+        }
+    }
+
     impl ttf_parser::OutlineBuilder for Sink {
         fn move_to(&mut self, x: f32, y: f32) {
             self.0.push(Inst::MoveTo(x, y));
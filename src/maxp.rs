@@ -0,0 +1,27 @@
+use super::*;
+
+/// Subset the maxp table.
+///
+/// When the hinting of TrueType outlines is stripped, the limits that only
+/// the instructions need are reset: a single zone, and no twilight points,
+/// storage, function and instruction definitions, stack elements or
+/// instructions. The glyph count is updated when the glyphs are renumbered.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let data = ctx.expect_table(Tag::MAXP)?;
+
+    // Version 0.5, used with CFF outlines, only has the glyph count.
+    if ctx.outlines != Outlines::TrueType
+        || !ctx.plan.strips_hinting()
+        || u32::read_at(data, 0)? != 0x00010000
+    {
+        ctx.push(Tag::MAXP, data);
+        return Ok(());
+    }
+
+    let mut maxp = data.to_vec();
+    let limits = maxp.get_mut(14..28).ok_or(Error::MissingData)?;
+    limits.fill(0);
+    limits[1] = 1;
+    ctx.push(Tag::MAXP, maxp);
+    Ok(())
+}
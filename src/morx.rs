@@ -0,0 +1,478 @@
+use std::collections::BTreeSet;
+
+use super::*;
+use crate::layout::{self, Packer};
+
+/// The AAT feature type of ligatures.
+const LIGATURES: u16 = 1;
+/// The AAT feature selector that enables common ligatures.
+const COMMON_LIGATURES_ON: u16 = 2;
+
+/// The glyph that deleted glyphs are replaced with.
+const DELETED: u16 = 0xFFFF;
+/// The most components of a converted ligature.
+const MAX_COMPONENTS: usize = 4;
+/// The most glyph sequences that are run through a ligature subtable when
+/// looking for ligatures.
+const MAX_SEQUENCES: usize = 100_000;
+
+/// The flag of a ligature subtable entry that marks the current glyph as a
+/// component.
+const SET_COMPONENT: u16 = 0x8000;
+/// The flag of a ligature subtable entry that stays on the current glyph.
+const DONT_ADVANCE: u16 = 0x4000;
+/// The flag of a ligature subtable entry that performs its ligature actions.
+const PERFORM_ACTION: u16 = 0x2000;
+/// The flag of a ligature action that ends the action list.
+const LAST: u32 = 0x8000_0000;
+/// The flag of a ligature action that stores the ligature glyph.
+const STORE: u32 = 0x4000_0000;
+
+/// A `morx` subtable that can be converted to a `GSUB` lookup.
+enum Subtable<'a> {
+    /// A noncontextual subtable, with its substitutions sorted by glyph.
+    Noncontextual(Vec<(u16, u16)>),
+    /// A ligature subtable, and whether it implements common ligatures.
+    Ligature(Ligatures<'a>, bool),
+}
+
+/// Add the glyphs that the converted `morx` substitutions produce from the
+/// retained glyphs to the subset, until nothing changes anymore.
+///
+/// Malformed data ends the closure early, which is reported as a warning.
+pub(crate) fn close(ctx: &mut Context) {
+    if !converts(ctx) {
+        return;
+    }
+    let Some(morx) = ctx.face.table(Tag::MORX) else { return };
+    let Ok(subtables) = subtables(morx, ctx.num_glyphs) else {
+        ctx.warnings.push(Warning::ClosureIncomplete(Tag::MORX));
+        return;
+    };
+
+    let mut glyphs: BTreeSet<u16> = ctx.plan.glyphs.iter().copied().collect();
+    if closure(&subtables, &mut glyphs).is_err() {
+        ctx.warnings.push(Warning::ClosureIncomplete(Tag::MORX));
+    }
+    ctx.plan.glyphs = glyphs.into_iter().collect();
+}
+
+/// Extend the glyphs with the outputs of the subtables until nothing changes
+/// anymore.
+fn closure(subtables: &[Subtable], glyphs: &mut BTreeSet<u16>) -> Result<()> {
+    loop {
+        let before = glyphs.len();
+        for subtable in subtables {
+            let candidates: Vec<u16> = glyphs.iter().copied().collect();
+            match subtable {
+                Subtable::Noncontextual(pairs) => {
+                    glyphs.extend(pairs.iter().filter_map(|&(glyph, output)| {
+                        candidates.binary_search(&glyph).is_ok().then_some(output)
+                    }));
+                }
+                Subtable::Ligature(ligatures, _) => {
+                    let found = ligatures.find(&candidates)?;
+                    glyphs.extend(found.into_iter().map(|(_, ligature)| ligature));
+                }
+            }
+        }
+        if glyphs.len() == before {
+            return Ok(());
+        }
+    }
+}
+
+/// Write a `GSUB` table with a lookup for each convertible `morx` subtable,
+/// restricted to the retained glyphs.
+///
+/// Ligatures go into the `liga` feature if the `morx` table enables them as
+/// common ligatures and into `rlig` otherwise, and noncontextual
+/// substitutions go into `ccmp`, since the converted subtables are enabled by
+/// default.
+pub(crate) fn convert(ctx: &mut Context) -> Result<()> {
+    if !converts(ctx) {
+        return Ok(());
+    }
+    let Some(morx) = ctx.face.table(Tag::MORX) else { return Ok(()) };
+    let Ok(subtables) = subtables(morx, ctx.num_glyphs) else { return Ok(()) };
+
+    let glyphs = layout::retained_glyphs(ctx);
+    let mut candidates: Vec<u16> = glyphs.keys().copied().collect();
+    candidates.sort_unstable();
+
+    let mut features: Vec<([u8; 4], Vec<u16>)> = vec![];
+    let mut lookups = vec![];
+    for subtable in &subtables {
+        let (tag, kind, data) = match subtable {
+            Subtable::Noncontextual(pairs) => {
+                let mut pairs: Vec<(u16, u16)> = pairs
+                    .iter()
+                    .filter_map(|(glyph, output)| {
+                        Some((*glyphs.get(glyph)?, *glyphs.get(output)?))
+                    })
+                    .collect();
+                pairs.sort_unstable();
+                if pairs.is_empty() {
+                    continue;
+                }
+                (*b"ccmp", 1, gsub::write_single(&pairs)?)
+            }
+            Subtable::Ligature(ligatures, common) => {
+                // The closure already reported malformed data.
+                let Ok(found) = ligatures.find(&candidates) else { continue };
+                let found: Vec<(Vec<u16>, u16)> = found
+                    .into_iter()
+                    .filter_map(|(components, ligature)| {
+                        let components = components
+                            .iter()
+                            .map(|glyph| glyphs.get(glyph).copied())
+                            .collect::<Option<_>>()?;
+                        Some((components, *glyphs.get(&ligature)?))
+                    })
+                    .collect();
+                if found.is_empty() {
+                    continue;
+                }
+                let tag = if *common { *b"liga" } else { *b"rlig" };
+                (tag, 4, write_ligatures(found)?)
+            }
+        };
+
+        let index = lookups.len() as u16;
+        match features.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, indices)) => indices.push(index),
+            None => features.push((tag, vec![index])),
+        }
+        lookups.push((kind, vec![data]));
+    }
+
+    if !lookups.is_empty() {
+        ctx.push(Tag::GSUB, layout::write_table(&features, lookups, 7)?);
+    }
+    Ok(())
+}
+
+/// Whether the plan converts the `morx` table of the face.
+fn converts(ctx: &Context) -> bool {
+    ctx.plan.convert_morx && ctx.face.table(Tag::GSUB).is_none()
+}
+
+/// The convertible subtables of a `morx` table that are enabled by default
+/// and apply to horizontal text, in the order they are applied.
+fn subtables(morx: &[u8], num_glyphs: u16) -> Result<Vec<Subtable<'_>>> {
+    if u16::read_at(morx, 0)? < 2 {
+        return Err(Error::Unsupported);
+    }
+
+    let mut subtables = vec![];
+    let mut at = 8;
+    for _ in 0..u32::read_at(morx, 4)? {
+        let chain = morx.get(at..).ok_or(Error::InvalidOffset)?;
+        let default = u32::read_at(chain, 0)?;
+        let length = u32::read_at(chain, 4)? as usize;
+        let features = u32::read_at(chain, 8)? as usize;
+        let count = u32::read_at(chain, 12)?;
+
+        // The subtable flags that the common ligatures feature enables.
+        let mut common = 0;
+        for i in 0..features {
+            let entry = 16 + 12 * i;
+            let kind = u16::read_at(chain, entry)?;
+            let setting = u16::read_at(chain, entry + 2)?;
+            if (kind, setting) == (LIGATURES, COMMON_LIGATURES_ON) {
+                common |= u32::read_at(chain, entry + 4)?;
+            }
+        }
+
+        let mut sub_at = 16 + 12 * features;
+        for _ in 0..count {
+            let sub_length = u32::read_at(chain, sub_at)? as usize;
+            let coverage = u32::read_at(chain, sub_at + 4)?;
+            let flags = u32::read_at(chain, sub_at + 8)?;
+            if sub_length < 12 {
+                return Err(Error::InvalidData);
+            }
+            let data = chain
+                .get(sub_at + 12..sub_at + sub_length)
+                .ok_or(Error::MissingData)?;
+            sub_at += sub_length;
+
+            // Subtables for vertical text only are skipped.
+            let vertical = coverage & 0x8000_0000 != 0 && coverage & 0x2000_0000 == 0;
+            if flags & default == 0 || vertical {
+                continue;
+            }
+            match coverage & 0xFF {
+                2 => subtables.push(Subtable::Ligature(
+                    Ligatures::new(data, num_glyphs)?,
+                    flags & common != 0,
+                )),
+                4 => {
+                    let pairs = lookup(data, num_glyphs)?
+                        .into_iter()
+                        .filter(|&(glyph, output)| output != glyph && output < num_glyphs)
+                        .collect();
+                    subtables.push(Subtable::Noncontextual(pairs));
+                }
+                _ => {}
+            }
+        }
+
+        if length == 0 {
+            return Err(Error::InvalidData);
+        }
+        at += length;
+    }
+    Ok(subtables)
+}
+
+/// The values of an AAT lookup table by glyph, sorted by glyph. Only 16-bit
+/// values are supported.
+fn lookup(data: &[u8], num_glyphs: u16) -> Result<Vec<(u16, u16)>> {
+    let mut values = vec![];
+    match u16::read_at(data, 0)? {
+        0 => {
+            for glyph in 0..num_glyphs {
+                values.push((glyph, u16::read_at(data, 2 + 2 * usize::from(glyph))?));
+            }
+        }
+        format @ (2 | 4 | 6) => {
+            let size = usize::from(u16::read_at(data, 2)?);
+            let count = usize::from(u16::read_at(data, 4)?);
+            for i in 0..count {
+                let unit = 12 + size * i;
+                let last = u16::read_at(data, unit)?;
+                // The units may end with a terminator.
+                if last == 0xFFFF {
+                    break;
+                }
+                if format == 6 {
+                    values.push((last, u16::read_at(data, unit + 2)?));
+                    continue;
+                }
+                let first = u16::read_at(data, unit + 2)?;
+                let value = u16::read_at(data, unit + 4)?;
+                for (k, glyph) in (first..=last).enumerate() {
+                    values.push(match format {
+                        2 => (glyph, value),
+                        _ => (glyph, u16::read_at(data, usize::from(value) + 2 * k)?),
+                    });
+                }
+            }
+        }
+        8 => {
+            let first = u16::read_at(data, 2)?;
+            for i in 0..u16::read_at(data, 4)? {
+                let glyph = first.checked_add(i).ok_or(Error::InvalidData)?;
+                values.push((glyph, u16::read_at(data, 6 + 2 * usize::from(i))?));
+            }
+        }
+        10 => {
+            let size = usize::from(u16::read_at(data, 2)?);
+            let first = u16::read_at(data, 4)?;
+            for i in 0..u16::read_at(data, 6)? {
+                let glyph = first.checked_add(i).ok_or(Error::InvalidData)?;
+                let at = 8 + size * usize::from(i);
+                let value = match size {
+                    1 => u16::from(u8::read_at(data, at)?),
+                    2 => u16::read_at(data, at)?,
+                    _ => return Err(Error::Unsupported),
+                };
+                values.push((glyph, value));
+            }
+        }
+        _ => return Err(Error::Unsupported),
+    }
+    values.sort_unstable();
+    values.dedup_by_key(|&mut (glyph, _)| glyph);
+    Ok(values)
+}
+
+/// The extended state table of a ligature subtable.
+struct Ligatures<'a> {
+    /// The class of each glyph that isn't out of bounds, sorted by glyph.
+    classes: Vec<(u16, u16)>,
+    /// The number of classes.
+    count: usize,
+    /// The state array, the entry table, the ligature actions, the component
+    /// table and the ligature table.
+    tables: [&'a [u8]; 5],
+}
+
+impl<'a> Ligatures<'a> {
+    /// Read the state table of a ligature subtable without its header.
+    fn new(data: &'a [u8], num_glyphs: u16) -> Result<Self> {
+        let count = u32::read_at(data, 0)? as usize;
+        let at = |i: usize| -> Result<&'a [u8]> {
+            let offset = u32::read_at(data, 4 + 4 * i)? as usize;
+            data.get(offset..).ok_or(Error::InvalidOffset)
+        };
+        let classes = lookup(at(0)?, num_glyphs)?;
+        Ok(Self {
+            classes,
+            count,
+            tables: [at(1)?, at(2)?, at(3)?, at(4)?, at(5)?],
+        })
+    }
+
+    /// The class of a glyph.
+    fn class(&self, glyph: u16) -> u16 {
+        match self.classes.binary_search_by_key(&glyph, |&(glyph, _)| glyph) {
+            Ok(i) => self.classes[i].1,
+            // Glyphs without a class are out of bounds.
+            Err(_) => 1,
+        }
+    }
+
+    /// The next state, the flags and the action index of the entry for a
+    /// class in a state.
+    fn entry(&self, state: u16, class: u16) -> Result<(u16, u16, u16)> {
+        let [states, entries, ..] = self.tables;
+        let index = usize::from(state) * self.count + usize::from(class);
+        let entry = 6 * usize::from(u16::read_at(states, 2 * index)?);
+        Ok((
+            u16::read_at(entries, entry)?,
+            u16::read_at(entries, entry + 2)?,
+            u16::read_at(entries, entry + 4)?,
+        ))
+    }
+
+    /// The ligatures with up to [`MAX_COMPONENTS`] components from the
+    /// candidates, which are sorted. At most [`MAX_SEQUENCES`] sequences are
+    /// tried, following the states of the subtable.
+    fn find(&self, candidates: &[u16]) -> Result<Vec<(Vec<u16>, u16)>> {
+        let mut found = vec![];
+        let mut budget = MAX_SEQUENCES;
+        self.search(candidates, &mut vec![], 0, &mut budget, &mut found)?;
+        Ok(found)
+    }
+
+    /// Extend the sequence with each candidate that the subtable marks as a
+    /// component in the given state and record the ligatures formed.
+    fn search(
+        &self,
+        candidates: &[u16],
+        sequence: &mut Vec<u16>,
+        state: u16,
+        budget: &mut usize,
+        found: &mut Vec<(Vec<u16>, u16)>,
+    ) -> Result<()> {
+        for &glyph in candidates {
+            let (next, flags, _) = self.entry(state, self.class(glyph))?;
+            if flags & SET_COMPONENT == 0 {
+                continue;
+            }
+            if *budget == 0 {
+                return Ok(());
+            }
+            *budget -= 1;
+
+            sequence.push(glyph);
+            if sequence.len() > 1 {
+                if let Some(ligature) = self.apply(sequence)? {
+                    found.push((sequence.clone(), ligature));
+                }
+            }
+            if sequence.len() < MAX_COMPONENTS {
+                self.search(candidates, sequence, next, budget, found)?;
+            }
+            sequence.pop();
+        }
+        Ok(())
+    }
+
+    /// Run the glyphs through the subtable. Returns the ligature glyph if they
+    /// form a single one.
+    fn apply(&self, glyphs: &[u16]) -> Result<Option<u16>> {
+        let [_, _, actions, components, ligatures] = self.tables;
+        let mut glyphs = glyphs.to_vec();
+        let mut stack: Vec<usize> = vec![];
+        let mut state = 0;
+        let mut i = 0;
+        // Entries that don't advance could loop forever.
+        for _ in 0..4 * (glyphs.len() + 1) {
+            // Class 0 is the end of the text.
+            let class = glyphs.get(i).map_or(0, |&glyph| self.class(glyph));
+            let (next, flags, action) = self.entry(state, class)?;
+            if flags & SET_COMPONENT != 0 && i < glyphs.len() && stack.last() != Some(&i)
+            {
+                stack.push(i);
+            }
+            if flags & PERFORM_ACTION != 0 {
+                let mut index = usize::from(action);
+                let mut sum: u32 = 0;
+                let mut stored = vec![];
+                while let Some(position) = stack.pop() {
+                    let action = u32::read_at(actions, 4 * index)?;
+                    index += 1;
+                    // The offset is a signed 30-bit number.
+                    let offset = (((action & 0x3FFF_FFFF) << 2) as i32) >> 2;
+                    let component = i64::from(glyphs[position]) + i64::from(offset);
+                    let component =
+                        usize::try_from(component).map_err(|_| Error::InvalidData)?;
+                    sum += u32::from(u16::read_at(components, 2 * component)?);
+                    if action & (STORE | LAST) != 0 {
+                        glyphs[position] = u16::read_at(ligatures, 2 * sum as usize)?;
+                        stored.push(position);
+                        sum = 0;
+                    } else {
+                        glyphs[position] = DELETED;
+                    }
+                    if action & LAST != 0 {
+                        break;
+                    }
+                }
+                stack.extend(stored.into_iter().rev());
+            }
+            state = next;
+            if i == glyphs.len() {
+                let mut kept = glyphs.into_iter().filter(|&glyph| glyph != DELETED);
+                return Ok(match (kept.next(), kept.next()) {
+                    (Some(ligature), None) => Some(ligature),
+                    _ => None,
+                });
+            }
+            if flags & DONT_ADVANCE == 0 {
+                i += 1;
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Write a ligature substitution subtable. The ligatures of a first glyph are
+/// ordered from the longest to the shortest, so that the longest one matches.
+fn write_ligatures(mut found: Vec<(Vec<u16>, u16)>) -> Result<Vec<u8>> {
+    found.sort_by(|(a, _), (b, _)| {
+        a[0].cmp(&b[0]).then(b.len().cmp(&a.len())).then(a.cmp(b))
+    });
+    found.dedup_by(|(a, _), (b, _)| a == b);
+
+    let mut covered: Vec<u16> =
+        found.iter().map(|(components, _)| components[0]).collect();
+    covered.dedup();
+
+    let mut p = Packer::new();
+    p.write::<u16>(1);
+    p.link(layout::write_coverage(&covered));
+    p.write::<u16>(covered.len() as u16);
+    for &first in &covered {
+        let set: Vec<_> = found
+            .iter()
+            .filter(|(components, _)| components[0] == first)
+            .collect();
+        let mut q = Packer::new();
+        q.write::<u16>(set.len() as u16);
+        for (components, ligature) in set {
+            let mut w = Writer::new();
+            w.write::<u16>(*ligature);
+            w.write::<u16>(components.len() as u16);
+            for &component in &components[1..] {
+                w.write::<u16>(component);
+            }
+            q.link(w.finish());
+        }
+        p.link(q.finish()?);
+    }
+    p.finish()
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// The name IDs kept in minimal fonts: family, subfamily, unique identifier,
+/// full name, version and PostScript name.
+const MINIMAL_NAME_IDS: [u16; 6] = [1, 2, 3, 4, 5, 6];
+
+/// The name IDs of records that fonts don't need to work: description,
+/// vendor URL, designer URL, license description, license URL and sample
+/// text.
+const PRUNED_NAME_IDS: [u16; 6] = [10, 11, 12, 13, 14, 19];
+
+/// The name IDs of the family names: family, typographic family and WWS
+/// family.
+const FAMILY_NAME_IDS: [u16; 3] = [1, 16, 21];
+
+/// A name record: platform, encoding, language and name ID and the string.
+type Record<'a> = (u16, u16, u16, u16, Cow<'a, [u8]>);
+
+/// Subset the name table.
+///
+/// Removes all records if names are stripped for obfuscation and all but the
+/// essential records for minimal fonts. Prunes records and languages and
+/// renames the family as the plan says.
+///
+/// The records that other tables of the output [refer to](referenced) are
+/// kept whatever the names are stripped or pruned for, so that variation
+/// axes, stylistic sets and palettes keep their names. Only the language
+/// filter applies to them.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let name = ctx.expect_table(Tag::NAME)?;
+    let tables = ctx.tables.iter().map(|(tag, data)| (*tag, data.as_ref()));
+    let referenced = referenced(tables)?;
+    let plan = &ctx.plan;
+    let strip = plan.obfuscation.is_some_and(|o| o.strip_names);
+    if !strip
+        && !plan.minimal
+        && !plan.prune_names
+        && plan.name_languages.is_empty()
+        && plan.family_suffix.is_none()
+    {
+        ctx.push(Tag::NAME, name);
+        return Ok(());
+    }
+
+    let mut r = Reader::new(name);
+    r.read::<u16>()?; // version
+    let count = r.read::<u16>()?;
+    let storage_offset = r.read::<u16>()? as usize;
+
+    let mut records: Vec<Record> = vec![];
+    for _ in 0..count {
+        let platform_id = r.read::<u16>()?;
+        let encoding_id = r.read::<u16>()?;
+        let language_id = r.read::<u16>()?;
+        let name_id = r.read::<u16>()?;
+        let length = r.read::<u16>()? as usize;
+        let offset = r.read::<u16>()? as usize;
+
+        // Language IDs from 0x8000 refer to language tags of version 1, which
+        // are not kept.
+        let stripped = strip
+            || (plan.minimal && !MINIMAL_NAME_IDS.contains(&name_id))
+            || (plan.prune_names && PRUNED_NAME_IDS.contains(&name_id));
+        if (stripped && referenced.binary_search(&name_id).is_err())
+            || (!plan.name_languages.is_empty()
+                && plan.name_languages.binary_search(&language_id).is_err())
+            || language_id >= 0x8000
+        {
+            continue;
+        }
+
+        let start = storage_offset + offset;
+        let string = name.get(start..start + length).ok_or(Error::InvalidOffset)?;
+        records.push((
+            platform_id,
+            encoding_id,
+            language_id,
+            name_id,
+            Cow::Borrowed(string),
+        ));
+    }
+
+    if let Some(suffix) = &plan.family_suffix {
+        rename(&mut records, suffix);
+    }
+
+    let mut sub_name = Writer::new();
+    sub_name.write::<u16>(0);
+    sub_name.write::<u16>(records.len() as u16);
+    sub_name.write::<u16>(6 + 12 * records.len() as u16);
+
+    let mut storage = Writer::new();
+    for (platform_id, encoding_id, language_id, name_id, string) in &records {
+        sub_name.write::<u16>(*platform_id);
+        sub_name.write::<u16>(*encoding_id);
+        sub_name.write::<u16>(*language_id);
+        sub_name.write::<u16>(*name_id);
+        // Longer family names may overflow the 16-bit offsets.
+        let length = u16::try_from(string.len()).map_err(|_| Error::OffsetOverflow)?;
+        let offset = u16::try_from(storage.len()).map_err(|_| Error::OffsetOverflow)?;
+        sub_name.write::<u16>(length);
+        sub_name.write::<u16>(offset);
+        storage.give(string);
+    }
+
+    sub_name.give(&storage.finish());
+    ctx.push(Tag::NAME, sub_name.finish());
+    Ok(())
+}
+
+/// The name IDs that the given tables refer to, sorted: the names of the axes
+/// and named instances in `fvar`, of the design axes and axis values in
+/// `STAT`, of the palettes and palette entries in `CPAL`, and those in the
+/// parameters of `GSUB` and `GPOS` features.
+pub(crate) fn referenced<'a>(
+    tables: impl IntoIterator<Item = (Tag, &'a [u8])>,
+) -> Result<Vec<u16>> {
+    let mut ids = vec![];
+    for (tag, data) in tables {
+        match tag {
+            Tag::GSUB | Tag::GPOS => ids.extend(layout::feature_name_ids(data)?),
+            Tag::FVAR => fvar_ids(data, &mut ids)?,
+            Tag::STAT => stat_ids(data, &mut ids)?,
+            Tag::CPAL => cpal_ids(data, &mut ids)?,
+            _ => {}
+        }
+    }
+    // Zero and 0xFFFF stand for no name.
+    ids.retain(|&id| id != 0 && id != 0xFFFF);
+    ids.sort_unstable();
+    ids.dedup();
+    Ok(ids)
+}
+
+/// Collect the names of the axes and named instances of an `fvar` table,
+/// including the PostScript names of the instances if they have them.
+fn fvar_ids(fvar: &[u8], ids: &mut Vec<u16>) -> Result<()> {
+    let offset = usize::from(u16::read_at(fvar, 4)?);
+    let count = usize::from(u16::read_at(fvar, 8)?);
+    let size = usize::from(u16::read_at(fvar, 10)?);
+    let instance_count = usize::from(u16::read_at(fvar, 12)?);
+    let instance_size = usize::from(u16::read_at(fvar, 14)?);
+    for i in 0..count {
+        ids.push(u16::read_at(fvar, offset + i * size + 18)?);
+    }
+    let instances = offset + count * size;
+    for i in 0..instance_count {
+        let at = instances + i * instance_size;
+        ids.push(u16::read_at(fvar, at)?);
+        if instance_size >= 6 + 4 * count {
+            ids.push(u16::read_at(fvar, at + 4 + 4 * count)?);
+        }
+    }
+    Ok(())
+}
+
+/// Collect the names of the design axes and axis values of a `STAT` table,
+/// and its elided fallback name since version 1.1.
+fn stat_ids(stat: &[u8], ids: &mut Vec<u16>) -> Result<()> {
+    let axis_size = usize::from(u16::read_at(stat, 4)?);
+    let axis_count = usize::from(u16::read_at(stat, 6)?);
+    let axes = u32::read_at(stat, 8)? as usize;
+    for i in 0..axis_count {
+        ids.push(u16::read_at(stat, axes + i * axis_size + 4)?);
+    }
+    // All formats of axis values have their name at the same position.
+    let value_count = usize::from(u16::read_at(stat, 12)?);
+    let values = u32::read_at(stat, 14)? as usize;
+    for i in 0..value_count {
+        let value = values + usize::from(u16::read_at(stat, values + 2 * i)?);
+        ids.push(u16::read_at(stat, value + 6)?);
+    }
+    if u16::read_at(stat, 2)? >= 1 {
+        ids.push(u16::read_at(stat, 18)?);
+    }
+    Ok(())
+}
+
+/// Collect the labels of the palettes and palette entries of a `CPAL` table,
+/// which exist since version 1.
+fn cpal_ids(cpal: &[u8], ids: &mut Vec<u16>) -> Result<()> {
+    if u16::read_at(cpal, 0)? < 1 {
+        return Ok(());
+    }
+    let entries = usize::from(u16::read_at(cpal, 2)?);
+    let palettes = usize::from(u16::read_at(cpal, 4)?);
+    let header = 12 + 2 * palettes;
+    for (at, count) in [(header + 4, palettes), (header + 8, entries)] {
+        let labels = u32::read_at(cpal, at)? as usize;
+        if labels == 0 {
+            continue;
+        }
+        for i in 0..count {
+            ids.push(u16::read_at(cpal, labels + 2 * i)?);
+        }
+    }
+    Ok(())
+}
+
+/// Append the suffix to the family names and update the full names,
+/// PostScript names and unique identifiers to match.
+fn rename(records: &mut [Record], suffix: &str) {
+    // The original strings by platform, encoding, language and name ID.
+    let names: HashMap<(u16, u16, u16, u16), String> = records
+        .iter()
+        .filter_map(|(platform, encoding, language, id, string)| {
+            Some((
+                (*platform, *encoding, *language, *id),
+                decode(*platform, *encoding, string)?,
+            ))
+        })
+        .collect();
+
+    // The PostScript name allows only printable ASCII without a few
+    // delimiters.
+    let compact: String = suffix
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+        .collect();
+    let postscript = |name: &str| match name.find('-') {
+        Some(i) => format!("{}{compact}{}", &name[..i], &name[i..]),
+        None => format!("{name}{compact}"),
+    };
+
+    for (platform, encoding, language, id, string) in records.iter_mut() {
+        let key = |id| (*platform, *encoding, *language, id);
+        let Some(old) = names.get(&key(*id)) else { continue };
+        let new = match *id {
+            id if FAMILY_NAME_IDS.contains(&id) => format!("{old} {suffix}"),
+            4 => {
+                // The full name usually starts with the typographic family.
+                let family = [16, 1]
+                    .iter()
+                    .filter_map(|&id| names.get(&key(id)))
+                    .find(|family| old.starts_with(family.as_str()));
+                match family {
+                    Some(family) => {
+                        format!("{family} {suffix}{}", &old[family.len()..])
+                    }
+                    None => format!("{old} {suffix}"),
+                }
+            }
+            6 => postscript(old),
+            3 => match names.get(&key(6)) {
+                Some(name) if old.contains(name.as_str()) => {
+                    old.replace(name.as_str(), &postscript(name))
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if let Some(encoded) = encode(*platform, *encoding, &new) {
+            *string = Cow::Owned(encoded);
+        }
+    }
+}
+
+/// Find the string of a name ID, preferring English records on the Windows
+/// platform, then on the Macintosh platform, then any that can be decoded.
+pub(crate) fn lookup(name: &[u8], id: u16) -> Result<Option<String>> {
+    let count = usize::from(u16::read_at(name, 2)?);
+    let storage_offset = usize::from(u16::read_at(name, 4)?);
+    let mut best: Option<(u8, String)> = None;
+    for i in 0..count {
+        let at = 6 + 12 * i;
+        if u16::read_at(name, at + 6)? != id {
+            continue;
+        }
+        let platform = u16::read_at(name, at)?;
+        let encoding = u16::read_at(name, at + 2)?;
+        let language = u16::read_at(name, at + 4)?;
+        let length = usize::from(u16::read_at(name, at + 8)?);
+        let start = storage_offset + usize::from(u16::read_at(name, at + 10)?);
+        let string = name.get(start..start + length).ok_or(Error::InvalidOffset)?;
+        let rank = match (platform, language) {
+            (3, 0x409) => 0,
+            (1, 0) => 1,
+            _ => 2,
+        };
+        if best.as_ref().is_some_and(|&(best, _)| best <= rank) {
+            continue;
+        }
+        if let Some(string) = decode(platform, encoding, string) {
+            best = Some((rank, string));
+        }
+    }
+    Ok(best.map(|(_, string)| string))
+}
+
+/// Decode a name string in UTF-16 or, on the Macintosh platform, ASCII.
+fn decode(platform: u16, encoding: u16, string: &[u8]) -> Option<String> {
+    match (platform, encoding) {
+        (0, _) | (3, 0 | 1 | 10) if string.len().is_multiple_of(2) => {
+            let units: Vec<u16> = string
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16(&units).ok()
+        }
+        (1, 0) if string.is_ascii() => Some(String::from_utf8_lossy(string).into_owned()),
+        _ => None,
+    }
+}
+
+/// Encode a name string like [`decode`] reads it.
+fn encode(platform: u16, encoding: u16, string: &str) -> Option<Vec<u8>> {
+    match (platform, encoding) {
+        (0, _) | (3, 0 | 1 | 10) => {
+            Some(string.encode_utf16().flat_map(u16::to_be_bytes).collect())
+        }
+        (1, 0) if string.is_ascii() => Some(string.as_bytes().to_vec()),
+        _ => None,
+    }
+}
@@ -0,0 +1,82 @@
+use super::*;
+
+/// Options for scrambling the character mapping of a font.
+///
+/// Publishers of paywalled content use obfuscated fonts so that scraped text
+/// is gibberish, while it still renders correctly when the text is encoded with
+/// the matching permutation (see [`obfuscation_map`]).
+///
+/// Every codepoint mapped to a retained glyph is moved to a randomly chosen
+/// codepoint in a Unicode Private Use Area. The permutation only depends on the
+/// seed, the font and the glyph set, so it is reproducible.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Obfuscation {
+    /// The seed for the permutation.
+    pub seed: u64,
+    /// Whether to also remove the naming table's records and the glyph names
+    /// in the `post` table, which could otherwise reveal the mapping.
+    ///
+    /// Glyph names in the `CFF` table are kept.
+    pub strip_names: bool,
+}
+
+/// Compute the permutation that a plan with [`Profile::obfuscate`] applies to
+/// the font's character mapping.
+///
+/// Returns pairs of original and obfuscated characters, sorted by the
+/// original character. Returns an empty map if the plan doesn't obfuscate.
+pub fn obfuscation_map(
+    data: &[u8],
+    index: u32,
+    plan: &Plan,
+) -> Result<Vec<(char, char)>> {
+    let face = parse(data, index)?;
+    let mut ctx = prepare(face, plan.clone())?;
+    Ok(mappings(&mut ctx)?
+        .into_iter()
+        .filter_map(|(from, to, _)| Some((char::from_u32(from)?, char::from_u32(to)?)))
+        .collect())
+}
+
+/// Determine the obfuscated mappings as triples of original codepoint,
+/// obfuscated codepoint and glyph, sorted by the original codepoint.
+pub(crate) fn mappings(ctx: &mut Context) -> Result<Vec<(u32, u32, u16)>> {
+    let Some(obfuscation) = ctx.plan.obfuscation else { return Ok(vec![]) };
+    let mappings: Vec<_> = ctx
+        .mappings()?
+        .iter()
+        .copied()
+        .filter(|(_, glyph)| *glyph != 0 && ctx.subset.contains(glyph))
+        .collect();
+
+    // Private Use Areas in the BMP and in plane 16.
+    let mut pool: Vec<u32> = (0xE000..=0xF8FF).collect();
+    if mappings.len() > pool.len() {
+        pool.extend(0x100000..=0x10FFFD);
+    }
+
+    // A partial Fisher-Yates shuffle.
+    let mut rng = SplitMix64(obfuscation.seed);
+    let mut result = vec![];
+    for (i, (c, glyph)) in mappings.into_iter().enumerate().take(pool.len()) {
+        let j = i + (rng.next() % (pool.len() - i) as u64) as usize;
+        pool.swap(i, j);
+        result.push((c, pool[i], glyph));
+    }
+
+    Ok(result)
+}
+
+/// A small, fast and portable pseudorandom number generator.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,339 @@
+use super::*;
+
+/// The Unicode blocks that the bits of `ulUnicodeRange1` to `ulUnicodeRange4`
+/// stand for, sorted by their first codepoint. Bit 57 is set for any
+/// codepoint outside of the Basic Multilingual Plane instead.
+const UNICODE_RANGES: [(u32, u32, u8); 168] = [
+    (0x0000, 0x007F, 0),
+    (0x0080, 0x00FF, 1),
+    (0x0100, 0x017F, 2),
+    (0x0180, 0x024F, 3),
+    (0x0250, 0x02AF, 4),
+    (0x02B0, 0x02FF, 5),
+    (0x0300, 0x036F, 6),
+    (0x0370, 0x03FF, 7),
+    (0x0400, 0x04FF, 9),
+    (0x0500, 0x052F, 9),
+    (0x0530, 0x058F, 10),
+    (0x0590, 0x05FF, 11),
+    (0x0600, 0x06FF, 13),
+    (0x0700, 0x074F, 71),
+    (0x0750, 0x077F, 13),
+    (0x0780, 0x07BF, 72),
+    (0x07C0, 0x07FF, 14),
+    (0x0900, 0x097F, 15),
+    (0x0980, 0x09FF, 16),
+    (0x0A00, 0x0A7F, 17),
+    (0x0A80, 0x0AFF, 18),
+    (0x0B00, 0x0B7F, 19),
+    (0x0B80, 0x0BFF, 20),
+    (0x0C00, 0x0C7F, 21),
+    (0x0C80, 0x0CFF, 22),
+    (0x0D00, 0x0D7F, 23),
+    (0x0D80, 0x0DFF, 73),
+    (0x0E00, 0x0E7F, 24),
+    (0x0E80, 0x0EFF, 25),
+    (0x0F00, 0x0FFF, 70),
+    (0x1000, 0x109F, 74),
+    (0x10A0, 0x10FF, 26),
+    (0x1100, 0x11FF, 28),
+    (0x1200, 0x137F, 75),
+    (0x1380, 0x139F, 75),
+    (0x13A0, 0x13FF, 76),
+    (0x1400, 0x167F, 77),
+    (0x1680, 0x169F, 78),
+    (0x16A0, 0x16FF, 79),
+    (0x1700, 0x171F, 84),
+    (0x1720, 0x173F, 84),
+    (0x1740, 0x175F, 84),
+    (0x1760, 0x177F, 84),
+    (0x1780, 0x17FF, 80),
+    (0x1800, 0x18AF, 81),
+    (0x1900, 0x194F, 93),
+    (0x1950, 0x197F, 94),
+    (0x1980, 0x19DF, 95),
+    (0x19E0, 0x19FF, 80),
+    (0x1A00, 0x1A1F, 96),
+    (0x1B00, 0x1B7F, 27),
+    (0x1B80, 0x1BBF, 112),
+    (0x1C00, 0x1C4F, 113),
+    (0x1C50, 0x1C7F, 114),
+    (0x1D00, 0x1D7F, 4),
+    (0x1D80, 0x1DBF, 4),
+    (0x1DC0, 0x1DFF, 6),
+    (0x1E00, 0x1EFF, 29),
+    (0x1F00, 0x1FFF, 30),
+    (0x2000, 0x206F, 31),
+    (0x2070, 0x209F, 32),
+    (0x20A0, 0x20CF, 33),
+    (0x20D0, 0x20FF, 34),
+    (0x2100, 0x214F, 35),
+    (0x2150, 0x218F, 36),
+    (0x2190, 0x21FF, 37),
+    (0x2200, 0x22FF, 38),
+    (0x2300, 0x23FF, 39),
+    (0x2400, 0x243F, 40),
+    (0x2440, 0x245F, 41),
+    (0x2460, 0x24FF, 42),
+    (0x2500, 0x257F, 43),
+    (0x2580, 0x259F, 44),
+    (0x25A0, 0x25FF, 45),
+    (0x2600, 0x26FF, 46),
+    (0x2700, 0x27BF, 47),
+    (0x27C0, 0x27EF, 38),
+    (0x27F0, 0x27FF, 37),
+    (0x2800, 0x28FF, 82),
+    (0x2900, 0x297F, 37),
+    (0x2980, 0x29FF, 38),
+    (0x2A00, 0x2AFF, 38),
+    (0x2B00, 0x2BFF, 37),
+    (0x2C00, 0x2C5F, 97),
+    (0x2C60, 0x2C7F, 29),
+    (0x2C80, 0x2CFF, 8),
+    (0x2D00, 0x2D2F, 26),
+    (0x2D30, 0x2D7F, 98),
+    (0x2D80, 0x2DDF, 75),
+    (0x2DE0, 0x2DFF, 9),
+    (0x2E00, 0x2E7F, 31),
+    (0x2E80, 0x2EFF, 59),
+    (0x2F00, 0x2FDF, 59),
+    (0x2FF0, 0x2FFF, 59),
+    (0x3000, 0x303F, 48),
+    (0x3040, 0x309F, 49),
+    (0x30A0, 0x30FF, 50),
+    (0x3100, 0x312F, 51),
+    (0x3130, 0x318F, 52),
+    (0x3190, 0x319F, 59),
+    (0x31A0, 0x31BF, 51),
+    (0x31C0, 0x31EF, 61),
+    (0x31F0, 0x31FF, 50),
+    (0x3200, 0x32FF, 54),
+    (0x3300, 0x33FF, 55),
+    (0x3400, 0x4DBF, 59),
+    (0x4DC0, 0x4DFF, 99),
+    (0x4E00, 0x9FFF, 59),
+    (0xA000, 0xA48F, 83),
+    (0xA490, 0xA4CF, 83),
+    (0xA500, 0xA63F, 12),
+    (0xA640, 0xA69F, 9),
+    (0xA700, 0xA71F, 5),
+    (0xA720, 0xA7FF, 29),
+    (0xA800, 0xA82F, 100),
+    (0xA840, 0xA87F, 53),
+    (0xA880, 0xA8DF, 115),
+    (0xA900, 0xA92F, 116),
+    (0xA930, 0xA95F, 117),
+    (0xAA00, 0xAA5F, 118),
+    (0xAC00, 0xD7AF, 56),
+    (0xE000, 0xF8FF, 60),
+    (0xF900, 0xFAFF, 61),
+    (0xFB00, 0xFB4F, 62),
+    (0xFB50, 0xFDFF, 63),
+    (0xFE00, 0xFE0F, 91),
+    (0xFE10, 0xFE1F, 65),
+    (0xFE20, 0xFE2F, 64),
+    (0xFE30, 0xFE4F, 65),
+    (0xFE50, 0xFE6F, 66),
+    (0xFE70, 0xFEFF, 67),
+    (0xFF00, 0xFFEF, 68),
+    (0xFFF0, 0xFFFF, 69),
+    (0x10000, 0x1007F, 101),
+    (0x10080, 0x100FF, 101),
+    (0x10100, 0x1013F, 101),
+    (0x10140, 0x1018F, 102),
+    (0x10190, 0x101CF, 119),
+    (0x101D0, 0x101FF, 120),
+    (0x10280, 0x1029F, 121),
+    (0x102A0, 0x102DF, 121),
+    (0x10300, 0x1032F, 85),
+    (0x10330, 0x1034F, 86),
+    (0x10380, 0x1039F, 103),
+    (0x103A0, 0x103DF, 104),
+    (0x10400, 0x1044F, 87),
+    (0x10450, 0x1047F, 105),
+    (0x10480, 0x104AF, 106),
+    (0x10800, 0x1083F, 107),
+    (0x10900, 0x1091F, 58),
+    (0x10920, 0x1093F, 121),
+    (0x10A00, 0x10A5F, 108),
+    (0x12000, 0x123FF, 110),
+    (0x12400, 0x1247F, 110),
+    (0x1D000, 0x1D0FF, 88),
+    (0x1D100, 0x1D1FF, 88),
+    (0x1D200, 0x1D24F, 88),
+    (0x1D300, 0x1D35F, 109),
+    (0x1D360, 0x1D37F, 111),
+    (0x1D400, 0x1D7FF, 89),
+    (0x1F000, 0x1F02F, 122),
+    (0x1F030, 0x1F09F, 122),
+    (0x20000, 0x2A6DF, 59),
+    (0x2F800, 0x2FA1F, 61),
+    (0xE0000, 0xE007F, 92),
+    (0xE0100, 0xE01EF, 91),
+    (0xF0000, 0xFFFFD, 90),
+    (0x100000, 0x10FFFD, 90),
+];
+
+/// Subset the OS/2 table.
+///
+/// The Unicode ranges, the code page ranges and the first and last character
+/// indices are recomputed from the characters that map to retained glyphs in
+/// the final `cmap`. Like in `pyftsubset`, range bits are only cleared and
+/// never set, since fonts may claim support for blocks and code pages that
+/// aren't derivable from the characters.
+///
+/// `usMaxContext` is recomputed too, since shapers may size their buffers
+/// with it. It is the longest glyph context of the layout lookups, so it
+/// becomes zero when both `GSUB` and `GPOS` are dropped. A plan that keeps one
+/// of them keeps the source's value, which is still an upper bound for the
+/// subsetted lookups.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let os2 = ctx.expect_table(Tag::OS2)?;
+    let version = u16::read_at(os2, 0)?;
+    let keeps_layout = [Tag::GSUB, Tag::GPOS].into_iter().any(|tag| {
+        ctx.face.table(tag).is_some() && ctx.plan.table(tag) != TableAction::Drop
+    });
+
+    let mut data = os2.to_vec();
+    let chars = codepoints(ctx)?;
+    if data.len() >= 68 && !chars.is_empty() {
+        let mut ranges = u128::from(u32::read_at(os2, 42)?)
+            | u128::from(u32::read_at(os2, 46)?) << 32
+            | u128::from(u32::read_at(os2, 50)?) << 64
+            | u128::from(u32::read_at(os2, 54)?) << 96;
+        ranges &= unicode_ranges(&chars);
+        for i in 0..4 {
+            let at = 42 + 4 * i;
+            data[at..at + 4]
+                .copy_from_slice(&((ranges >> (32 * i)) as u32).to_be_bytes());
+        }
+
+        let first = chars[0].min(0xFFFF) as u16;
+        let last = chars[chars.len() - 1].min(0xFFFF) as u16;
+        data[64..66].copy_from_slice(&first.to_be_bytes());
+        data[66..68].copy_from_slice(&last.to_be_bytes());
+    }
+
+    // The code page ranges exist since version 1.
+    if version >= 1 && data.len() >= 86 && !chars.is_empty() {
+        let mut pages =
+            u64::from(u32::read_at(os2, 78)?) | u64::from(u32::read_at(os2, 82)?) << 32;
+        pages &= code_pages(&chars);
+        data[78..82].copy_from_slice(&(pages as u32).to_be_bytes());
+        data[82..86].copy_from_slice(&((pages >> 32) as u32).to_be_bytes());
+    }
+
+    // The maximum context exists since version 2.
+    if version >= 2 && data.len() >= 96 && !keeps_layout {
+        data[94..96].copy_from_slice(&0_u16.to_be_bytes());
+    }
+
+    ctx.push(Tag::OS2, data);
+    Ok(())
+}
+
+/// The codepoints that the final `cmap` maps to retained glyphs, sorted.
+///
+/// These are the source's characters of retained glyphs, or their obfuscated
+/// replacements, along with a synthesized space and the Private Use Area
+/// codepoints of mapped glyphs.
+fn codepoints(ctx: &mut Context) -> Result<Vec<u32>> {
+    let mut chars: Vec<u32> = if ctx.plan.obfuscation.is_some() {
+        obfuscate::mappings(ctx)?.into_iter().map(|(_, c, _)| c).collect()
+    } else {
+        ctx.mappings()?
+            .iter()
+            .filter(|(_, glyph)| *glyph != 0 && ctx.subset.contains(glyph))
+            .map(|&(c, _)| c)
+            .collect()
+    };
+    if ctx.space.is_some() {
+        chars.push(0x20);
+    }
+    if ctx.plan.map_glyphs {
+        match ctx.plan.pua_key {
+            PuaKey::GlyphId => chars.extend(
+                ctx.subset
+                    .iter()
+                    .filter(|&&glyph| glyph != 0)
+                    .map(|&g| 0xF0000 + u32::from(g)),
+            ),
+            _ => chars.extend(pua::assign(ctx)?.into_iter().map(|(c, _)| c)),
+        }
+    }
+    chars.sort_unstable();
+    chars.dedup();
+    Ok(chars)
+}
+
+/// The Unicode range bits of the blocks that the sorted codepoints touch.
+fn unicode_ranges(chars: &[u32]) -> u128 {
+    let mut bits = 0;
+    for &c in chars {
+        if c > 0xFFFF {
+            bits |= 1 << 57;
+        }
+        let i = UNICODE_RANGES.partition_point(|&(_, end, _)| end < c);
+        if let Some(&(start, _, bit)) = UNICODE_RANGES.get(i) {
+            if start <= c {
+                bits |= 1 << bit;
+            }
+        }
+    }
+    bits
+}
+
+/// The code page bits that the sorted codepoints support, decided by key
+/// characters of each code page like `fontTools` does. Without any, the
+/// Latin 1 bit is set so that the font still works in Microsoft Word.
+fn code_pages(chars: &[u32]) -> u64 {
+    let has = |c: char| chars.binary_search(&u32::from(c)).is_ok();
+    let ascii = (0x20..0x7E).all(|c| chars.binary_search(&c).is_ok());
+    let line_art = has('┤');
+    let root = has('√');
+
+    let mut bits = 0_u64;
+    let mut set = |bit: u32, condition: bool| {
+        if condition {
+            bits |= 1 << bit;
+        }
+    };
+    set(0, ascii && has('Þ'));
+    set(1, ascii && has('Ľ'));
+    set(58, ascii && has('Ľ') && line_art);
+    set(2, has('Б'));
+    set(57, has('Б') && has('Ѕ') && line_art);
+    set(49, has('Б') && has('╜') && line_art);
+    set(3, has('Ά'));
+    set(48, has('Ά') && has('½') && line_art);
+    set(60, has('Ά') && root && line_art);
+    set(4, ascii && has('İ'));
+    set(56, ascii && has('İ') && line_art);
+    set(5, has('א'));
+    set(53, has('א') && root && line_art);
+    set(6, has('ر'));
+    set(51, has('ر') && root);
+    set(61, has('ر') && line_art);
+    set(7, ascii && has('ŗ'));
+    set(59, ascii && has('ŗ') && line_art);
+    set(8, ascii && has('₫'));
+    set(16, has('ๅ'));
+    set(17, has('エ'));
+    set(18, has('ㄅ'));
+    set(19, has('ㄱ'));
+    set(20, has('央'));
+    set(21, has('곴'));
+    set(29, ascii && has('‰') && has('∑'));
+    set(30, ascii && has('♥'));
+    set(54, ascii && has('þ') && line_art);
+    set(62, ascii && has('╚'));
+    set(63, ascii && has('╚'));
+    let dos = ascii && root && line_art;
+    set(50, dos && has('Å'));
+    set(52, dos && has('é'));
+    set(55, dos && has('õ'));
+    if bits == 0 {
+        bits = 1;
+    }
+    bits
+}
@@ -0,0 +1,855 @@
+use super::*;
+use crate::json::{JsonWriter, Value};
+
+/// The version of the plan format written by [`Plan::to_json`].
+const PLAN_VERSION: u32 = 1;
+
+/// The Apple Advanced Typography tables.
+const AAT: [Tag; 11] = [
+    Tag::ANKR,
+    Tag::BSLN,
+    Tag::FEAT,
+    Tag::JUST,
+    Tag::KERX,
+    Tag::LCAR,
+    Tag::MORT,
+    Tag::MORX,
+    Tag::OPBD,
+    Tag::PROP,
+    Tag::TRAK,
+];
+
+/// The Graphite tables.
+const GRAPHITE: [Tag; 5] =
+    [Tag::SILF, Tag::GLAT, Tag::GLOC, Tag::FEAT_GRAPHITE, Tag::SILL];
+
+/// Further tables that reference glyphs by ID and aren't subsetted. Copying
+/// them verbatim is only consistent if the glyph IDs are preserved.
+const GLYPH_REFERENCING: [Tag; 11] = [
+    Tag::ACNT,
+    Tag::BDAT,
+    Tag::BLOC,
+    Tag::EBSC,
+    Tag::GCID,
+    Tag::HDMX,
+    Tag::JSTF,
+    Tag::KERN,
+    Tag::LTSH,
+    Tag::MATH,
+    Tag::ZAPF,
+];
+
+/// Whether the table is an AAT or Graphite table, which reference glyphs by
+/// ID and aren't subsetted.
+pub(crate) fn is_shaping(tag: Tag) -> bool {
+    AAT.contains(&tag) || GRAPHITE.contains(&tag)
+}
+
+/// Whether the table references glyphs by ID without being subsetted, so that
+/// it must be dropped when the glyphs are renumbered.
+pub(crate) fn references_glyphs(tag: Tag) -> bool {
+    GLYPH_REFERENCING.contains(&tag) || is_shaping(tag)
+}
+
+/// A computed subsetting plan.
+///
+/// The plan records every decision the subsetter made for a font: which
+/// glyphs are retained (including those pulled in through composite glyphs)
+/// and what happens to each table. It can be exported as JSON with
+/// [`to_json`](Self::to_json), reviewed or stored alongside the font, and later
+/// re-applied with [`subset_with_plan`](crate::subset_with_plan), also to a
+/// newer revision of the same font.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Plan {
+    /// The retained glyphs, sorted and deduplicated.
+    pub(crate) glyphs: Vec<u16>,
+    /// Whether to map each glyph to a codepoint in Unicode PUAs.
+    pub(crate) map_glyphs: bool,
+    /// How to assign PUA codepoints to glyphs.
+    pub(crate) pua_key: PuaKey,
+    /// Whether to record the subsetter version in the output.
+    pub(crate) version_stamp: bool,
+    /// Whether to make sure that U+0020 is mapped.
+    pub(crate) ensure_space: bool,
+    /// The advance of a synthesized space glyph.
+    pub(crate) space_advance: Option<u16>,
+    /// Whether to keep only the tables needed to render glyphs.
+    pub(crate) minimal: bool,
+    /// Whether to remove TrueType hinting.
+    pub(crate) strip_hinting: bool,
+    /// Whether to drop the name records that fonts don't need to work.
+    pub(crate) prune_names: bool,
+    /// The language IDs of the name records to keep, sorted, or all if empty.
+    pub(crate) name_languages: Vec<u16>,
+    /// A suffix to append to the family names.
+    pub(crate) family_suffix: Option<String>,
+    /// Whether to drop the glyph names of the `post` table.
+    pub(crate) strip_glyph_names: bool,
+    /// Whether to fail on malformed data instead of repairing it.
+    pub(crate) strict: bool,
+    /// Whether to keep the glyphs produced by the `ccmp` feature.
+    pub(crate) ccmp_closure: bool,
+    /// Whether to convert basic `morx` substitutions to a `GSUB` table.
+    pub(crate) convert_morx: bool,
+    /// Further features whose produced glyphs are kept, sorted.
+    pub(crate) layout_closure: Vec<Tag>,
+    /// Features whose closure only follows the requested glyphs, sorted.
+    pub(crate) strict_closure: Vec<Tag>,
+    /// Whether to keep or synthesize vertical metrics.
+    pub(crate) vertical: bool,
+    /// The advance height of synthesized vertical metrics.
+    pub(crate) vertical_advance: Option<u16>,
+    /// How to pad the tables of the output.
+    pub(crate) padding: Padding,
+    /// What to do with layout lookups the subsetter doesn't support.
+    pub(crate) lookup_policy: LookupPolicy,
+    /// How to obfuscate the character mapping.
+    pub(crate) obfuscation: Option<Obfuscation>,
+    /// Whether to renumber the retained glyphs.
+    pub(crate) remap_glyphs: bool,
+    /// The glyphs that get the first new IDs when renumbering, in order and
+    /// without duplicates.
+    pub(crate) glyph_order: Vec<u16>,
+    /// Private tables that are copied unmodified, sorted.
+    pub(crate) passthrough: Vec<Tag>,
+    /// Tables the profile keeps that are dropped because renumbering the
+    /// glyphs would invalidate them, sorted.
+    pub(crate) remap_dropped: Vec<Tag>,
+    /// Overridden horizontal metrics, sorted by glyph ID.
+    pub(crate) metrics: Vec<(u16, MetricsOverride)>,
+    /// What to do with each table of the source font, sorted by tag.
+    pub(crate) tables: Vec<(Tag, TableAction)>,
+}
+
+/// What a plan does with a table.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum TableAction {
+    /// The table is rewritten to only cover the retained glyphs.
+    Subset,
+    /// The table is copied into the output verbatim.
+    Copy,
+    /// The table is not included in the output.
+    Drop,
+}
+
+impl TableAction {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Subset => "subset",
+            Self::Copy => "copy",
+            Self::Drop => "drop",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "subset" => Self::Subset,
+            "copy" => Self::Copy,
+            "drop" => Self::Drop,
+            _ => return None,
+        })
+    }
+}
+
+impl PuaKey {
+    fn name(self) -> &'static str {
+        match self {
+            Self::GlyphId => "glyph_id",
+            Self::GlyphName => "glyph_name",
+            Self::Codepoint => "codepoint",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "glyph_id" => Self::GlyphId,
+            "glyph_name" => Self::GlyphName,
+            "codepoint" => Self::Codepoint,
+            _ => return None,
+        })
+    }
+}
+
+impl Padding {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Aligned => "aligned",
+            Self::Minimal => "minimal",
+            Self::Source => "source",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "aligned" => Self::Aligned,
+            "minimal" => Self::Minimal,
+            "source" => Self::Source,
+            _ => return None,
+        })
+    }
+}
+
+impl LookupPolicy {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Fail => "fail",
+            Self::KeepTable => "keep_table",
+            Self::DropLookup => "drop_lookup",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "fail" => Self::Fail,
+            "keep_table" => Self::KeepTable,
+            "drop_lookup" => Self::DropLookup,
+            _ => return None,
+        })
+    }
+}
+
+impl Plan {
+    /// Create the initial plan for a profile. The glyph closure is computed
+    /// later, when the plan is prepared for a face.
+    pub(crate) fn new(face: &Face, profile: &Profile) -> Self {
+        let mut keep = vec![];
+        match face.outlines() {
+            Outlines::Cff => {
+                keep.extend([Tag::CFF, Tag::CFF2]);
+                if !profile.minimal {
+                    keep.push(Tag::VORG);
+                }
+            }
+            Outlines::TrueType => {
+                keep.push(Tag::GLYF);
+                if !profile.minimal && !profile.strip_hinting {
+                    keep.extend([Tag::CVT, Tag::FPGM, Tag::PREP, Tag::GASP]);
+                }
+            }
+            Outlines::None => {}
+        }
+
+        // Required tables.
+        keep.extend([Tag::CMAP, Tag::HEAD, Tag::HHEA, Tag::HMTX, Tag::MAXP]);
+        if !profile.minimal || profile.minimal_names {
+            keep.extend([Tag::NAME, Tag::OS2, Tag::POST]);
+        }
+        if profile.vertical {
+            keep.extend([Tag::VHEA, Tag::VMTX]);
+        }
+        if profile.keep_gsub {
+            keep.extend([Tag::GSUB, Tag::GDEF]);
+        }
+        if profile.keep_gpos {
+            keep.extend([Tag::GPOS, Tag::GDEF]);
+        }
+        if profile.keep_aat {
+            keep.extend(AAT);
+        }
+        if profile.keep_graphite {
+            keep.extend(GRAPHITE);
+        }
+        if profile.keep_variations {
+            keep.extend([
+                Tag::FVAR,
+                Tag::AVAR,
+                Tag::STAT,
+                Tag::GVAR,
+                Tag::HVAR,
+                Tag::MVAR,
+            ]);
+            if keep.contains(&Tag::CVT) {
+                keep.push(Tag::CVAR);
+            }
+            if profile.vertical {
+                keep.push(Tag::VVAR);
+            }
+        }
+        if profile.keep_color {
+            keep.extend([Tag::COLR, Tag::CPAL, Tag::SVG]);
+        }
+        if profile.keep_bitmaps {
+            keep.extend([Tag::CBDT, Tag::CBLC, Tag::EBDT, Tag::EBLC, Tag::SBIX]);
+        }
+
+        // Minimal fonts without names still keep the names that their other
+        // tables refer to.
+        if !keep.contains(&Tag::NAME) {
+            let kept = face.tables.iter().copied().filter(|(tag, _)| keep.contains(tag));
+            if name::referenced(kept).is_ok_and(|ids| !ids.is_empty()) {
+                keep.push(Tag::NAME);
+            }
+        }
+
+        let mut remap_dropped = vec![];
+        let tables = face
+            .tables
+            .iter()
+            .map(|&(tag, _)| tag)
+            .filter(|&tag| tag != Tag::LOCA)
+            .map(|tag| {
+                let passthrough =
+                    !warning::is_known(tag) && profile.passthrough.contains(&tag);
+                let kept = (keep.contains(&tag) || passthrough)
+                    && !profile.dropped.contains(&tag);
+                if kept && profile.remap_glyphs && references_glyphs(tag) {
+                    remap_dropped.push(tag);
+                }
+                let action = if !kept || (profile.remap_glyphs && references_glyphs(tag))
+                {
+                    TableAction::Drop
+                } else if has_subsetter(tag) {
+                    TableAction::Subset
+                } else {
+                    TableAction::Copy
+                };
+                (tag, action)
+            })
+            .collect();
+
+        let mut metrics = profile.metrics.clone();
+        metrics.sort_by_key(|&(glyph, _)| glyph);
+
+        let mut layout_closure = profile.layout_closure.clone();
+        layout_closure.sort_unstable();
+        layout_closure.dedup();
+
+        let mut strict_closure = profile.strict_closure.clone();
+        strict_closure.sort_unstable();
+        strict_closure.dedup();
+
+        // Only private tables that are in the font are passed through.
+        let mut passthrough: Vec<Tag> = profile
+            .passthrough
+            .iter()
+            .copied()
+            .filter(|&tag| !warning::is_known(tag) && face.table(tag).is_some())
+            .collect();
+        passthrough.sort_unstable();
+        passthrough.dedup();
+
+        let mut name_languages = profile.name_languages.clone();
+        name_languages.sort_unstable();
+        name_languages.dedup();
+
+        Self {
+            glyphs: profile.glyphs.to_vec(),
+            map_glyphs: profile.map_glyphs,
+            pua_key: profile.pua_key,
+            version_stamp: profile.version_stamp,
+            ensure_space: profile.ensure_space,
+            space_advance: profile.space_advance,
+            minimal: profile.minimal,
+            strip_hinting: profile.strip_hinting,
+            prune_names: profile.prune_names,
+            name_languages,
+            family_suffix: profile.family_suffix.map(str::to_string),
+            strip_glyph_names: profile.strip_glyph_names,
+            strict: profile.strict,
+            ccmp_closure: profile.ccmp_closure,
+            convert_morx: profile.convert_morx,
+            layout_closure,
+            strict_closure,
+            vertical: profile.vertical,
+            vertical_advance: profile.vertical_advance,
+            padding: profile.padding,
+            lookup_policy: profile.lookup_policy,
+            obfuscation: profile.obfuscation,
+            remap_glyphs: profile.remap_glyphs,
+            glyph_order: first_occurrences(
+                profile.glyph_order.iter().copied().chain(keyed_order(face, profile)),
+            ),
+            passthrough,
+            remap_dropped,
+            metrics,
+            tables,
+        }
+    }
+
+    /// Whether the TrueType hinting is removed, which minimal subsets also do.
+    pub(crate) fn strips_hinting(&self) -> bool {
+        self.minimal || self.strip_hinting
+    }
+
+    /// Whether the glyph names are removed, which minimal subsets and
+    /// obfuscation with stripped names also do.
+    pub(crate) fn strips_glyph_names(&self) -> bool {
+        self.minimal
+            || self.strip_glyph_names
+            || self.obfuscation.is_some_and(|o| o.strip_names)
+    }
+
+    /// The retained glyphs, sorted by glyph ID.
+    pub fn glyphs(&self) -> &[u16] {
+        &self.glyphs
+    }
+
+    /// What the plan does with the given table.
+    ///
+    /// Tables that are not mentioned in the plan are dropped.
+    pub fn table(&self, tag: Tag) -> TableAction {
+        self.tables
+            .iter()
+            .find(|&&(t, _)| t == tag)
+            .map_or(TableAction::Drop, |&(_, action)| action)
+    }
+
+    /// All tables mentioned in the plan and what happens to them.
+    pub fn tables(&self) -> &[(Tag, TableAction)] {
+        &self.tables
+    }
+
+    /// Serialize the plan to JSON.
+    pub fn to_json(&self) -> String {
+        let mut w = JsonWriter::new();
+        w.begin_object(None);
+        w.number(Some("version"), PLAN_VERSION);
+        w.bool(Some("map_glyphs"), self.map_glyphs);
+        w.string(Some("pua_key"), self.pua_key.name());
+        w.bool(Some("version_stamp"), self.version_stamp);
+        w.numbers(Some("glyphs"), self.glyphs.iter().copied());
+        w.bool(Some("ensure_space"), self.ensure_space);
+        if let Some(advance) = self.space_advance {
+            w.number(Some("space_advance"), advance);
+        }
+        w.bool(Some("minimal"), self.minimal);
+        w.bool(Some("strip_hinting"), self.strip_hinting);
+        w.bool(Some("prune_names"), self.prune_names);
+        w.numbers(Some("name_languages"), self.name_languages.iter().copied());
+        if let Some(suffix) = &self.family_suffix {
+            w.string(Some("family_suffix"), suffix);
+        }
+        w.bool(Some("strip_glyph_names"), self.strip_glyph_names);
+        w.bool(Some("strict"), self.strict);
+        w.bool(Some("ccmp_closure"), self.ccmp_closure);
+        w.bool(Some("convert_morx"), self.convert_morx);
+        let features: Vec<String> =
+            self.layout_closure.iter().map(|tag| tag.to_string()).collect();
+        w.strings(Some("layout_closure"), features.iter().map(String::as_str));
+        let features: Vec<String> =
+            self.strict_closure.iter().map(|tag| tag.to_string()).collect();
+        w.strings(Some("strict_closure"), features.iter().map(String::as_str));
+        w.bool(Some("vertical"), self.vertical);
+        if let Some(advance) = self.vertical_advance {
+            w.number(Some("vertical_advance"), advance);
+        }
+        w.string(Some("padding"), self.padding.name());
+        w.string(Some("unsupported_lookups"), self.lookup_policy.name());
+        if let Some(obfuscation) = self.obfuscation {
+            // The seed is a string because JSON numbers can't hold all 64 bits.
+            w.begin_object(Some("obfuscation"));
+            w.string(Some("seed"), &obfuscation.seed.to_string());
+            w.bool(Some("strip_names"), obfuscation.strip_names);
+            w.end_object();
+        }
+        w.bool(Some("remap_glyphs"), self.remap_glyphs);
+        w.numbers(Some("glyph_order"), self.glyph_order.iter().copied());
+        let tags: Vec<String> =
+            self.passthrough.iter().map(|tag| tag.to_string()).collect();
+        w.strings(Some("passthrough"), tags.iter().map(String::as_str));
+        let tags: Vec<String> =
+            self.remap_dropped.iter().map(|tag| tag.to_string()).collect();
+        w.strings(Some("remap_dropped"), tags.iter().map(String::as_str));
+        w.begin_object(Some("metrics"));
+        for (glyph, metrics) in &self.metrics {
+            w.begin_object(Some(&glyph.to_string()));
+            if let Some(advance) = metrics.advance {
+                w.number(Some("advance"), advance);
+            }
+            if let Some(lsb) = metrics.lsb {
+                w.number(Some("lsb"), lsb);
+            }
+            w.end_object();
+        }
+        w.end_object();
+        w.begin_object(Some("tables"));
+        for &(tag, action) in &self.tables {
+            w.string(Some(&tag.to_string()), action.name());
+        }
+        w.end_object();
+        w.end_object();
+        w.finish()
+    }
+
+    /// Deserialize a plan previously written by [`to_json`](Self::to_json).
+    pub fn from_json(json: &str) -> Result<Self> {
+        let root = json::parse(json).map_err(|_| Error::InvalidPlan)?;
+        if root.get("version").and_then(Value::as_u32) != Some(PLAN_VERSION) {
+            return Err(Error::InvalidPlan);
+        }
+
+        let map_glyphs = root
+            .get("map_glyphs")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let version_stamp = root
+            .get("version_stamp")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let mut glyphs = root
+            .get("glyphs")
+            .and_then(Value::as_array)
+            .ok_or(Error::InvalidPlan)?
+            .iter()
+            .map(|v| v.as_u32().and_then(|n| u16::try_from(n).ok()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::InvalidPlan)?;
+        glyphs.sort_unstable();
+        glyphs.dedup();
+
+        let ensure_space = root
+            .get("ensure_space")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let pua_key = root
+            .get("pua_key")
+            .map_or(Some(PuaKey::GlyphId), |v| v.as_str().and_then(PuaKey::from_name))
+            .ok_or(Error::InvalidPlan)?;
+
+        let space_advance = root
+            .get("space_advance")
+            .map(|v| {
+                v.as_u32()
+                    .and_then(|n| u16::try_from(n).ok())
+                    .ok_or(Error::InvalidPlan)
+            })
+            .transpose()?;
+
+        let minimal = root
+            .get("minimal")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let strip_hinting = root
+            .get("strip_hinting")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let prune_names = root
+            .get("prune_names")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let mut name_languages = root
+            .get("name_languages")
+            .map_or(Some(&[][..]), Value::as_array)
+            .ok_or(Error::InvalidPlan)?
+            .iter()
+            .map(|v| v.as_u32().and_then(|n| u16::try_from(n).ok()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::InvalidPlan)?;
+        name_languages.sort_unstable();
+        name_languages.dedup();
+
+        let family_suffix = root
+            .get("family_suffix")
+            .map(|v| v.as_str().map(str::to_string).ok_or(Error::InvalidPlan))
+            .transpose()?;
+
+        let strip_glyph_names = root
+            .get("strip_glyph_names")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let strict = root
+            .get("strict")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let ccmp_closure = root
+            .get("ccmp_closure")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let convert_morx = root
+            .get("convert_morx")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let mut layout_closure = vec![];
+        let features = root.get("layout_closure").map_or(Some(&[][..]), Value::as_array);
+        for feature in features.ok_or(Error::InvalidPlan)? {
+            let feature = feature.as_str().ok_or(Error::InvalidPlan)?;
+            layout_closure.push(Tag::from_str(feature).map_err(|_| Error::InvalidPlan)?);
+        }
+        layout_closure.sort_unstable();
+        layout_closure.dedup();
+
+        let mut strict_closure = vec![];
+        let features = root.get("strict_closure").map_or(Some(&[][..]), Value::as_array);
+        for feature in features.ok_or(Error::InvalidPlan)? {
+            let feature = feature.as_str().ok_or(Error::InvalidPlan)?;
+            strict_closure.push(Tag::from_str(feature).map_err(|_| Error::InvalidPlan)?);
+        }
+        strict_closure.sort_unstable();
+        strict_closure.dedup();
+
+        let vertical = root
+            .get("vertical")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let vertical_advance = root
+            .get("vertical_advance")
+            .map(|v| {
+                v.as_i32()
+                    .and_then(|n| u16::try_from(n).ok())
+                    .ok_or(Error::InvalidPlan)
+            })
+            .transpose()?;
+
+        let padding = root
+            .get("padding")
+            .map_or(Some(Padding::Aligned), |v| v.as_str().and_then(Padding::from_name))
+            .ok_or(Error::InvalidPlan)?;
+
+        let lookup_policy = root
+            .get("unsupported_lookups")
+            .map_or(Some(LookupPolicy::Fail), |v| {
+                v.as_str().and_then(LookupPolicy::from_name)
+            })
+            .ok_or(Error::InvalidPlan)?;
+
+        let obfuscation = root
+            .get("obfuscation")
+            .map(|v| {
+                let seed = v.get("seed")?.as_str()?.parse().ok()?;
+                let strip_names =
+                    v.get("strip_names").map_or(Some(false), Value::as_bool)?;
+                Some(Obfuscation { seed, strip_names })
+            })
+            .map(|o| o.ok_or(Error::InvalidPlan))
+            .transpose()?;
+
+        let remap_glyphs = root
+            .get("remap_glyphs")
+            .map_or(Some(false), Value::as_bool)
+            .ok_or(Error::InvalidPlan)?;
+
+        let glyph_order = root
+            .get("glyph_order")
+            .map_or(Some(&[][..]), Value::as_array)
+            .ok_or(Error::InvalidPlan)?
+            .iter()
+            .map(|v| v.as_u32().and_then(|n| u16::try_from(n).ok()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::InvalidPlan)?;
+        let glyph_order = first_occurrences(glyph_order);
+
+        let mut passthrough = vec![];
+        let tags = root.get("passthrough").map_or(Some(&[][..]), Value::as_array);
+        for tag in tags.ok_or(Error::InvalidPlan)? {
+            let tag = tag.as_str().ok_or(Error::InvalidPlan)?;
+            passthrough.push(Tag::from_str(tag).map_err(|_| Error::InvalidPlan)?);
+        }
+        passthrough.sort_unstable();
+        passthrough.dedup();
+
+        let mut remap_dropped = vec![];
+        let tags = root.get("remap_dropped").map_or(Some(&[][..]), Value::as_array);
+        for tag in tags.ok_or(Error::InvalidPlan)? {
+            let tag = tag.as_str().ok_or(Error::InvalidPlan)?;
+            remap_dropped.push(Tag::from_str(tag).map_err(|_| Error::InvalidPlan)?);
+        }
+        remap_dropped.sort_unstable();
+        remap_dropped.dedup();
+
+        let mut metrics = vec![];
+        let pairs = root.get("metrics").map_or(Some(&[][..]), Value::as_object);
+        for (key, value) in pairs.ok_or(Error::InvalidPlan)? {
+            let glyph = key.parse::<u16>().map_err(|_| Error::InvalidPlan)?;
+            let field = |name| match value.get(name) {
+                Some(v) => v.as_i32().map(Some).ok_or(Error::InvalidPlan),
+                None => Ok(None),
+            };
+            let advance = field("advance")?
+                .map(|n| u16::try_from(n).map_err(|_| Error::InvalidPlan))
+                .transpose()?;
+            let lsb = field("lsb")?
+                .map(|n| i16::try_from(n).map_err(|_| Error::InvalidPlan))
+                .transpose()?;
+            metrics.push((glyph, MetricsOverride { advance, lsb }));
+        }
+        metrics.sort_by_key(|&(glyph, _)| glyph);
+        metrics.dedup_by_key(|&mut (glyph, _)| glyph);
+
+        let mut tables = vec![];
+        let pairs = root
+            .get("tables")
+            .and_then(Value::as_object)
+            .ok_or(Error::InvalidPlan)?;
+        for (key, value) in pairs {
+            let tag = Tag::from_str(key).map_err(|_| Error::InvalidPlan)?;
+            let action = value
+                .as_str()
+                .and_then(TableAction::from_name)
+                .ok_or(Error::InvalidPlan)?;
+            if tag == Tag::LOCA || tables.iter().any(|&(t, _)| t == tag) {
+                return Err(Error::InvalidPlan);
+            }
+            tables.push((tag, action));
+        }
+        tables.sort_by_key(|&(tag, _)| tag);
+
+        Ok(Self {
+            glyphs,
+            map_glyphs,
+            pua_key,
+            version_stamp,
+            ensure_space,
+            space_advance,
+            minimal,
+            strip_hinting,
+            prune_names,
+            name_languages,
+            family_suffix,
+            strip_glyph_names,
+            strict,
+            ccmp_closure,
+            convert_morx,
+            layout_closure,
+            strict_closure,
+            vertical,
+            vertical_advance,
+            padding,
+            lookup_policy,
+            obfuscation,
+            remap_glyphs,
+            glyph_order,
+            passthrough,
+            remap_dropped,
+            metrics,
+            tables,
+        })
+    }
+}
+
+/// Plans with arbitrary options and table actions, for fuzzing.
+///
+/// The generated plans uphold the same invariants as those parsed with
+/// [`Plan::from_json`], so subsetting with them must never panic.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Plan {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut glyphs: Vec<u16> = u.arbitrary()?;
+        glyphs.sort_unstable();
+        glyphs.dedup();
+
+        let mut metrics: Vec<(u16, MetricsOverride)> = u.arbitrary()?;
+        metrics.sort_by_key(|&(glyph, _)| glyph);
+        metrics.dedup_by_key(|&mut (glyph, _)| glyph);
+
+        let mut layout_closure: Vec<Tag> = u.arbitrary()?;
+        layout_closure.sort_unstable();
+        layout_closure.dedup();
+
+        let mut strict_closure: Vec<Tag> = u.arbitrary()?;
+        strict_closure.sort_unstable();
+        strict_closure.dedup();
+
+        let mut passthrough: Vec<Tag> = u.arbitrary()?;
+        passthrough.sort_unstable();
+        passthrough.dedup();
+
+        let mut remap_dropped: Vec<Tag> = u.arbitrary()?;
+        remap_dropped.sort_unstable();
+        remap_dropped.dedup();
+
+        let mut name_languages: Vec<u16> = u.arbitrary()?;
+        name_languages.sort_unstable();
+        name_languages.dedup();
+
+        let mut tables: Vec<(Tag, TableAction)> = u.arbitrary()?;
+        tables.retain(|&(tag, _)| tag != Tag::LOCA);
+        tables.sort_by_key(|&(tag, _)| tag);
+        tables.dedup_by_key(|&mut (tag, _)| tag);
+
+        Ok(Self {
+            glyphs,
+            map_glyphs: u.arbitrary()?,
+            pua_key: u.arbitrary()?,
+            version_stamp: u.arbitrary()?,
+            ensure_space: u.arbitrary()?,
+            space_advance: u.arbitrary()?,
+            minimal: u.arbitrary()?,
+            strip_hinting: u.arbitrary()?,
+            prune_names: u.arbitrary()?,
+            name_languages,
+            family_suffix: u.arbitrary()?,
+            strip_glyph_names: u.arbitrary()?,
+            strict: u.arbitrary()?,
+            ccmp_closure: u.arbitrary()?,
+            convert_morx: u.arbitrary()?,
+            layout_closure,
+            strict_closure,
+            vertical: u.arbitrary()?,
+            vertical_advance: u.arbitrary()?,
+            padding: u.arbitrary()?,
+            lookup_policy: u.arbitrary()?,
+            obfuscation: u.arbitrary()?,
+            remap_glyphs: u.arbitrary()?,
+            glyph_order: first_occurrences(u.arbitrary::<Vec<u16>>()?),
+            passthrough,
+            remap_dropped,
+            metrics,
+            tables,
+        })
+    }
+}
+
+/// The first occurrence of each glyph, in order.
+fn first_occurrences(glyphs: impl IntoIterator<Item = u16>) -> Vec<u16> {
+    let mut seen = HashSet::new();
+    glyphs.into_iter().filter(|&glyph| seen.insert(glyph)).collect()
+}
+
+/// The glyphs of the font that the profile's glyph order hook gives a key,
+/// sorted by their key.
+fn keyed_order(face: &Face, profile: &Profile) -> Vec<u16> {
+    let Some(order) = profile.glyph_order_by else { return vec![] };
+    let num_glyphs = face
+        .table(Tag::MAXP)
+        .and_then(|maxp| u16::read_at(maxp, 4).ok())
+        .unwrap_or(0);
+    let mut keyed: Vec<(u32, u16)> = (0..num_glyphs)
+        .filter_map(|glyph| Some((order.key(glyph)?, glyph)))
+        .collect();
+    keyed.sort_unstable();
+    keyed.into_iter().map(|(_, glyph)| glyph).collect()
+}
+
+/// Whether the subsetter has a dedicated pass for the table.
+fn has_subsetter(tag: Tag) -> bool {
+    matches!(
+        tag,
+        Tag::GLYF
+            | Tag::CFF
+            | Tag::CFF2
+            | Tag::HEAD
+            | Tag::MAXP
+            | Tag::HHEA
+            | Tag::HMTX
+            | Tag::NAME
+            | Tag::OS2
+            | Tag::POST
+            | Tag::CMAP
+            | Tag::GDEF
+            | Tag::GSUB
+            | Tag::GPOS
+            | Tag::GVAR
+            | Tag::HVAR
+            | Tag::VVAR
+            | Tag::COLR
+            | Tag::CPAL
+            | Tag::SVG
+            | Tag::CBDT
+            | Tag::CBLC
+            | Tag::EBDT
+            | Tag::EBLC
+            | Tag::SBIX
+    )
+}
@@ -1,10 +1,20 @@
 use super::*;
 
-/// Subset the glyf and loca tables by removing glyph data for unused glyphs.
+/// Subset the post table by removing the names of unused glyphs, or all names
+/// if the plan strips them.
 pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
     let post = ctx.expect_table(Tag::POST)?;
     let mut r = Reader::new(post);
 
+    // Without glyph names, only the header of version 3 remains.
+    if ctx.plan.strips_glyph_names() {
+        let mut sub_post = Writer::new();
+        sub_post.write::<u32>(0x00030000);
+        sub_post.give(post.get(4..32).ok_or(Error::MissingData)?);
+        ctx.push(Tag::POST, sub_post.finish());
+        return Ok(());
+    }
+
     // Version 2 is the only one worth subsetting.
     let version = r.read::<u32>()?;
     if version != 0x00020000 {
@@ -43,6 +53,11 @@ pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
             index = 0;
         }
 
+        // Name a synthesized space glyph with the standard name `space`.
+        if ctx.space.is_some_and(|(glyph, _)| usize::from(glyph) == i) {
+            index = 3;
+        }
+
         if index <= 257 {
             sub_post.write::<u16>(index);
             continue;
@@ -61,3 +76,320 @@ pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
 
     Ok(())
 }
+
+/// The names of the standard Macintosh glyph set, which version 2 tables
+/// reference by index.
+const MAC_NAMES: [&str; 258] = [
+    ".notdef",
+    ".null",
+    "nonmarkingreturn",
+    "space",
+    "exclam",
+    "quotedbl",
+    "numbersign",
+    "dollar",
+    "percent",
+    "ampersand",
+    "quotesingle",
+    "parenleft",
+    "parenright",
+    "asterisk",
+    "plus",
+    "comma",
+    "hyphen",
+    "period",
+    "slash",
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "colon",
+    "semicolon",
+    "less",
+    "equal",
+    "greater",
+    "question",
+    "at",
+    "A",
+    "B",
+    "C",
+    "D",
+    "E",
+    "F",
+    "G",
+    "H",
+    "I",
+    "J",
+    "K",
+    "L",
+    "M",
+    "N",
+    "O",
+    "P",
+    "Q",
+    "R",
+    "S",
+    "T",
+    "U",
+    "V",
+    "W",
+    "X",
+    "Y",
+    "Z",
+    "bracketleft",
+    "backslash",
+    "bracketright",
+    "asciicircum",
+    "underscore",
+    "grave",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "braceleft",
+    "bar",
+    "braceright",
+    "asciitilde",
+    "Adieresis",
+    "Aring",
+    "Ccedilla",
+    "Eacute",
+    "Ntilde",
+    "Odieresis",
+    "Udieresis",
+    "aacute",
+    "agrave",
+    "acircumflex",
+    "adieresis",
+    "atilde",
+    "aring",
+    "ccedilla",
+    "eacute",
+    "egrave",
+    "ecircumflex",
+    "edieresis",
+    "iacute",
+    "igrave",
+    "icircumflex",
+    "idieresis",
+    "ntilde",
+    "oacute",
+    "ograve",
+    "ocircumflex",
+    "odieresis",
+    "otilde",
+    "uacute",
+    "ugrave",
+    "ucircumflex",
+    "udieresis",
+    "dagger",
+    "degree",
+    "cent",
+    "sterling",
+    "section",
+    "bullet",
+    "paragraph",
+    "germandbls",
+    "registered",
+    "copyright",
+    "trademark",
+    "acute",
+    "dieresis",
+    "notequal",
+    "AE",
+    "Oslash",
+    "infinity",
+    "plusminus",
+    "lessequal",
+    "greaterequal",
+    "yen",
+    "mu",
+    "partialdiff",
+    "summation",
+    "product",
+    "pi",
+    "integral",
+    "ordfeminine",
+    "ordmasculine",
+    "Omega",
+    "ae",
+    "oslash",
+    "questiondown",
+    "exclamdown",
+    "logicalnot",
+    "radical",
+    "florin",
+    "approxequal",
+    "Delta",
+    "guillemotleft",
+    "guillemotright",
+    "ellipsis",
+    "nonbreakingspace",
+    "Agrave",
+    "Atilde",
+    "Otilde",
+    "OE",
+    "oe",
+    "endash",
+    "emdash",
+    "quotedblleft",
+    "quotedblright",
+    "quoteleft",
+    "quoteright",
+    "divide",
+    "lozenge",
+    "ydieresis",
+    "Ydieresis",
+    "fraction",
+    "currency",
+    "guilsinglleft",
+    "guilsinglright",
+    "fi",
+    "fl",
+    "daggerdbl",
+    "periodcentered",
+    "quotesinglbase",
+    "quotedblbase",
+    "perthousand",
+    "Acircumflex",
+    "Ecircumflex",
+    "Aacute",
+    "Edieresis",
+    "Egrave",
+    "Iacute",
+    "Icircumflex",
+    "Idieresis",
+    "Igrave",
+    "Oacute",
+    "Ocircumflex",
+    "apple",
+    "Ograve",
+    "Uacute",
+    "Ucircumflex",
+    "Ugrave",
+    "dotlessi",
+    "circumflex",
+    "tilde",
+    "macron",
+    "breve",
+    "dotaccent",
+    "ring",
+    "cedilla",
+    "hungarumlaut",
+    "ogonek",
+    "caron",
+    "Lslash",
+    "lslash",
+    "Scaron",
+    "scaron",
+    "Zcaron",
+    "zcaron",
+    "brokenbar",
+    "Eth",
+    "eth",
+    "Yacute",
+    "yacute",
+    "Thorn",
+    "thorn",
+    "minus",
+    "multiply",
+    "onesuperior",
+    "twosuperior",
+    "threesuperior",
+    "onehalf",
+    "onequarter",
+    "threequarters",
+    "franc",
+    "Gbreve",
+    "gbreve",
+    "Idotaccent",
+    "Scedilla",
+    "scedilla",
+    "Cacute",
+    "cacute",
+    "Ccaron",
+    "ccaron",
+    "dcroat",
+];
+
+/// The names of the glyphs by glyph ID, as returned by [`names`].
+pub(crate) type GlyphNames<'a> = Vec<Option<Cow<'a, [u8]>>>;
+
+/// Read the glyph names of a version 2 post table, or `None` for other
+/// versions.
+///
+/// Names from the standard Macintosh set are identified by their index, as
+/// two bytes after a zero byte, which can't start a real name. Glyphs named
+/// `.notdef` have no name.
+pub(crate) fn names(post: &[u8]) -> Result<Option<GlyphNames<'_>>> {
+    let mut r = Reader::new(post);
+    if r.read::<u32>()? != 0x00020000 {
+        return Ok(None);
+    }
+
+    r.skip(28)?;
+    let num_glyphs = r.read::<u16>()?;
+    let mut indices = vec![];
+    for _ in 0..num_glyphs {
+        indices.push(r.read::<u16>()?);
+    }
+
+    let mut strings = vec![];
+    while !r.eof() {
+        let len = r.read::<u8>()?;
+        strings.push(r.take(len as usize)?);
+    }
+
+    let mut names = vec![];
+    for index in indices {
+        names.push(match index {
+            0 => None,
+            1..=257 => Some(Cow::Owned(vec![0, (index >> 8) as u8, index as u8])),
+            _ => {
+                let name = strings.get(usize::from(index - 258));
+                Some(Cow::Borrowed(*name.ok_or(Error::InvalidOffset)?))
+            }
+        });
+    }
+
+    Ok(Some(names))
+}
+
+/// Resolve a glyph name returned by [`names`] to a string, looking up the
+/// names from the standard Macintosh set.
+pub(crate) fn name_string(name: &[u8]) -> String {
+    match *name {
+        [0, hi, lo] => MAC_NAMES
+            .get(usize::from(u16::from_be_bytes([hi, lo])))
+            .map_or_else(String::new, |name| name.to_string()),
+        _ => String::from_utf8_lossy(name).into_owned(),
+    }
+}
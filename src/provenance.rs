@@ -0,0 +1,157 @@
+use std::ops::Range;
+
+use super::*;
+use crate::json::JsonWriter;
+
+/// Where the tables of a subsetted font came from.
+///
+/// Produced by [`subset_to_result`] to help find the pass that
+/// introduced a corruption: a broken table that was passed through verbatim
+/// was already broken in the source, while a rewritten one points to the pass
+/// for that table.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Provenance {
+    /// The tables of the output, sorted by tag.
+    pub tables: Vec<TableProvenance>,
+    /// The size of each retained glyph's outline in the output, sorted by
+    /// original glyph ID, to check glyphs against a size budget. For CFF fonts, this
+    /// is the size of the charstring without the subroutines it calls. Fonts
+    /// with other outlines have no entries.
+    ///
+    /// Other per-glyph data, like `gvar` variations and bitmap or color
+    /// glyphs, is always dropped by the subsetter.
+    pub glyphs: Vec<(u16, usize)>,
+}
+
+/// Where a table of a subsetted font came from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TableProvenance {
+    /// The table's tag.
+    pub tag: Tag,
+    /// The byte range of the table in the output, without padding.
+    pub output: Range<usize>,
+    /// How the table was produced.
+    pub origin: Origin,
+}
+
+/// How a table of a subsetted font was produced.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Origin {
+    /// Copied from the given byte range of the source data.
+    Verbatim(Range<usize>),
+    /// Rewritten from the table at the given byte range of the source data.
+    Rewritten(Range<usize>),
+    /// Created by the subsetter without a counterpart in the source.
+    Synthesized,
+}
+
+impl Provenance {
+    /// Serialize the provenance to JSON.
+    pub fn to_json(&self) -> String {
+        let mut w = JsonWriter::new();
+        w.begin_object(None);
+        w.begin_object(Some("tables"));
+        for table in &self.tables {
+            w.begin_object(Some(&table.tag.to_string()));
+            w.number(Some("offset"), table.output.start as u32);
+            w.number(Some("length"), table.output.len() as u32);
+            let (origin, source) = match &table.origin {
+                Origin::Verbatim(source) => ("verbatim", Some(source)),
+                Origin::Rewritten(source) => ("rewritten", Some(source)),
+                Origin::Synthesized => ("synthesized", None),
+            };
+            w.string(Some("origin"), origin);
+            if let Some(source) = source {
+                w.number(Some("source_offset"), source.start as u32);
+                w.number(Some("source_length"), source.len() as u32);
+            }
+            w.end_object();
+        }
+        w.end_object();
+        w.begin_object(Some("glyphs"));
+        for &(glyph, size) in &self.glyphs {
+            w.number(Some(&glyph.to_string()), size as u32);
+        }
+        w.end_object();
+        w.end_object();
+        w.finish()
+    }
+}
+
+/// Subset a font face with a plan like [`subset_with_plan`] and also report
+/// where each table of the output came from.
+///
+/// A shorthand for the provenance of [`subset_to_result`].
+pub fn subset_with_provenance(
+    data: &[u8],
+    index: u32,
+    plan: &Plan,
+) -> Result<(Vec<u8>, Provenance)> {
+    let (font, _, _, provenance) = result::subset_all(data, index, plan)?;
+    Ok((font, provenance))
+}
+
+/// Construct the font from the built tables of a context for the source
+/// `data` and record where its tables came from.
+pub(crate) fn construct(data: &[u8], ctx: Context) -> Result<(Vec<u8>, Provenance)> {
+    // Source tables are slices of `data`, so their position follows from
+    // their address.
+    let range = |slice: &[u8]| {
+        let start = slice.as_ptr() as usize - data.as_ptr() as usize;
+        start..start + slice.len()
+    };
+
+    let origins: Vec<(Tag, Origin)> = ctx
+        .tables
+        .iter()
+        .map(|(tag, table)| {
+            let origin = match (ctx.face.table(*tag), table) {
+                (Some(source), Cow::Borrowed(b)) if std::ptr::eq(source, *b) => {
+                    Origin::Verbatim(range(source))
+                }
+                (Some(source), _) => Origin::Rewritten(range(source)),
+                (None, _) => Origin::Synthesized,
+            };
+            (*tag, origin)
+        })
+        .collect();
+
+    // Pairs of the retained glyphs and their IDs in the output, which differ
+    // if the glyphs were renumbered.
+    let new_ids = ctx.remapped.as_deref().map(remap::new_ids);
+    let mut retained: Vec<(u16, u16)> = ctx
+        .subset
+        .iter()
+        .map(|&glyph| match &new_ids {
+            Some(new_ids) => (glyph, new_ids.get(&glyph).copied().unwrap_or(0)),
+            None => (glyph, glyph),
+        })
+        .collect();
+    retained.sort();
+
+    let output = ctx.construct();
+    let face = parse(&output, 0)?;
+    let mut tables = vec![];
+    for (tag, origin) in origins {
+        let table = face.table(tag).ok_or(Error::MissingTable(tag))?;
+        let start = table.as_ptr() as usize - output.as_ptr() as usize;
+        tables.push(TableProvenance { tag, output: start..start + table.len(), origin });
+    }
+    tables.sort_by_key(|table| table.tag);
+
+    let mut glyphs = vec![];
+    if face.table(Tag::GLYF).is_some() {
+        let table = glyf::Table::new(&face)?;
+        for (glyph, id) in retained {
+            glyphs.push((glyph, table.glyph_data(id)?.len()));
+        }
+    } else if let Some(cff) = face.table(Tag::CFF) {
+        let sizes = cff::char_string_sizes(cff)?;
+        for (glyph, id) in retained {
+            let size = sizes.get(usize::from(id)).ok_or(Error::MissingData)?;
+            glyphs.push((glyph, *size));
+        }
+    }
+
+    Ok((output, Provenance { tables, glyphs }))
+}
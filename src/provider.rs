@@ -0,0 +1,70 @@
+use super::*;
+
+/// Provides the tables of a font face that is already held in memory.
+///
+/// Applications with their own index of the tables in a font (e.g. a PDF
+/// renderer) can implement this trait to subset the font with
+/// [`subset_from_provider`] without reassembling a full font file first.
+pub trait TableProvider<'a> {
+    /// The tags of all tables in the face.
+    fn tags(&self) -> Vec<Tag>;
+
+    /// The data of the table with the given tag, if it exists.
+    fn table(&self, tag: Tag) -> Option<&'a [u8]>;
+}
+
+impl<'a> TableProvider<'a> for [(Tag, &'a [u8])] {
+    fn tags(&self) -> Vec<Tag> {
+        self.iter().map(|&(tag, _)| tag).collect()
+    }
+
+    fn table(&self, tag: Tag) -> Option<&'a [u8]> {
+        self.iter().find(|&&(t, _)| t == tag).map(|&(_, data)| data)
+    }
+}
+
+/// Available with the `ttf-parser` feature.
+#[cfg(feature = "ttf-parser")]
+impl<'a> TableProvider<'a> for ttf_parser::RawFace<'a> {
+    fn tags(&self) -> Vec<Tag> {
+        self.table_records
+            .into_iter()
+            .map(|record| Tag(record.tag.to_bytes()))
+            .collect()
+    }
+
+    fn table(&self, tag: Tag) -> Option<&'a [u8]> {
+        ttf_parser::RawFace::table(self, ttf_parser::Tag::from_bytes(&tag.0))
+    }
+}
+
+/// Available with the `ttf-parser` feature.
+#[cfg(feature = "ttf-parser")]
+impl<'a> TableProvider<'a> for ttf_parser::Face<'a> {
+    fn tags(&self) -> Vec<Tag> {
+        self.raw_face().tags()
+    }
+
+    fn table(&self, tag: Tag) -> Option<&'a [u8]> {
+        self.raw_face().table(ttf_parser::Tag::from_bytes(&tag.0))
+    }
+}
+
+/// Subset a font face whose tables are supplied by a [`TableProvider`].
+///
+/// This behaves exactly like [`subset`], but doesn't require the face to be
+/// available as a single OpenType font file.
+pub fn subset_from_provider<'a>(
+    provider: &(impl TableProvider<'a> + ?Sized),
+    profile: Profile,
+) -> Result<Vec<u8>> {
+    let face = Face::new(
+        provider
+            .tags()
+            .into_iter()
+            .filter_map(|tag| Some((tag, provider.table(tag)?)))
+            .collect(),
+    );
+    let plan = Plan::new(&face, &profile);
+    prepare(face, plan)?.build()
+}
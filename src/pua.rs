@@ -0,0 +1,90 @@
+use super::*;
+
+/// The number of codepoints in the Supplementary Private Use Area-A, from
+/// U+F0000 to U+FFFFD.
+const SLOTS: u32 = 0xFFFE;
+
+/// What the PUA codepoint of a glyph is derived from. Glyphs with smaller
+/// keys win collisions, so glyphs keyed by codepoints come first.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+enum Key<'a> {
+    Codepoint(u32),
+    Name(Cow<'a, [u8]>),
+    Id(u16),
+}
+
+impl Key<'_> {
+    /// The preferred offset of the glyph's codepoint from U+F0000.
+    fn slot(&self) -> u32 {
+        match self {
+            Self::Codepoint(c) => c % SLOTS,
+            Self::Name(name) => (stamp::fnv1a(name) % u64::from(SLOTS)) as u32,
+            Self::Id(glyph) => u32::from(*glyph),
+        }
+    }
+}
+
+/// Assign PUA codepoints to the retained glyphs according to the plan's
+/// [`PuaKey`], for all keys but [`PuaKey::GlyphId`], which maps a whole range
+/// instead.
+///
+/// Returns the mappings sorted by codepoint. A glyph whose preferred codepoint
+/// is taken gets the next free one.
+pub(crate) fn assign(ctx: &mut Context) -> Result<Vec<(u32, u16)>> {
+    let mut glyphs: Vec<u16> = ctx.subset.iter().copied().filter(|&g| g != 0).collect();
+    glyphs.sort_unstable();
+
+    // The smallest codepoint of each glyph.
+    let mut codepoints = vec![None; usize::from(ctx.num_glyphs)];
+    if ctx.plan.pua_key == PuaKey::Codepoint {
+        for &(c, glyph) in ctx.mappings()?.iter() {
+            if let Some(slot @ None) = codepoints.get_mut(usize::from(glyph)) {
+                *slot = Some(c);
+            }
+        }
+    }
+
+    let names = glyph_names(ctx)?;
+    let mut keyed: Vec<(Key, u16)> = glyphs
+        .into_iter()
+        .map(|glyph| {
+            let i = usize::from(glyph);
+            let key = if let Some(&Some(c)) = codepoints.get(i) {
+                Key::Codepoint(c)
+            } else if let Some(Some(name)) = names.get(i) {
+                Key::Name(name.clone())
+            } else {
+                Key::Id(glyph)
+            };
+            (key, glyph)
+        })
+        .collect();
+    keyed.sort();
+
+    let mut taken = vec![false; SLOTS as usize];
+    let mut mappings = vec![];
+    for (key, glyph) in keyed {
+        let mut slot = key.slot() as usize;
+        while taken[slot] {
+            slot = (slot + 1) % taken.len();
+        }
+        taken[slot] = true;
+        mappings.push((0xF0000 + slot as u32, glyph));
+    }
+
+    mappings.sort_unstable();
+    Ok(mappings)
+}
+
+/// The glyph names of the source font from the `post` table, or from the `CFF`
+/// table if the former has none. Empty if the font has neither.
+fn glyph_names<'a>(ctx: &Context<'a>) -> Result<post::GlyphNames<'a>> {
+    if let Some(names) = ctx.face.table(Tag::POST).map(post::names).transpose()?.flatten()
+    {
+        return Ok(names);
+    }
+    match ctx.face.table(Tag::CFF) {
+        Some(cff) => cff::glyph_names(ctx, cff),
+        None => Ok(vec![]),
+    }
+}
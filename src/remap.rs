@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// Renumber the retained glyphs of the subsetted tables, so that they occupy
+/// the glyph IDs `0..n` in their original order, except for those the plan's
+/// glyph order puts first.
+///
+/// Glyph data of the removed glyphs is dropped from the outline and metrics
+/// tables instead of being emptied. Tables that reference glyph IDs but can't
+/// be renumbered are dropped.
+pub(crate) fn remap(ctx: &mut Context) -> Result<()> {
+    if !ctx.plan.remap_glyphs {
+        return Ok(());
+    }
+
+    let order = new_order(ctx);
+    // Nothing to do if all glyphs are kept in their order.
+    if order.len() == usize::from(ctx.num_glyphs) && order.is_sorted() {
+        return Ok(());
+    }
+
+    let new_ids = new_ids(&order);
+    let new_id = |glyph: u16| new_ids.get(&glyph).copied();
+    let tables = std::mem::take(&mut ctx.tables);
+    let table =
+        |tag| tables.iter().find(|&&(t, _)| t == tag).map(|(_, data)| data.as_ref());
+
+    let mut remapped = vec![];
+    for (tag, data) in &tables {
+        let data = match *tag {
+            Tag::GLYF => {
+                let loca = table(Tag::LOCA).ok_or(Error::MissingTable(Tag::LOCA))?;
+                let (loca, glyf) = remap_glyf(ctx, loca, data, &order, &new_ids)?;
+                remapped.push((Tag::LOCA, Cow::Owned(loca)));
+                glyf
+            }
+            Tag::LOCA => continue,
+            Tag::CFF => cff::remap(ctx, data, &order)?,
+            Tag::CFF2 => cff::cff2::remap(data, &order)?,
+            Tag::HHEA | Tag::VHEA => continue,
+            Tag::HMTX | Tag::VMTX => {
+                let header_tag = if *tag == Tag::HMTX { Tag::HHEA } else { Tag::VHEA };
+                let header = table(header_tag)
+                    .or_else(|| ctx.face.table(header_tag))
+                    .ok_or(Error::MissingTable(header_tag))?;
+                let (header, metrics) = remap_metrics(header, data, &order)?;
+                if table(header_tag).is_some() {
+                    remapped.push((header_tag, Cow::Owned(header)));
+                }
+                metrics
+            }
+            Tag::MAXP => {
+                let mut maxp = data.to_vec();
+                maxp.get_mut(4..6)
+                    .ok_or(Error::MissingData)?
+                    .copy_from_slice(&(order.len() as u16).to_be_bytes());
+                maxp
+            }
+            Tag::CMAP => {
+                let pua = (ctx.plan.map_glyphs && ctx.plan.pua_key == PuaKey::GlyphId)
+                    .then_some((ctx.num_glyphs, order.len() as u16));
+                cmap::remap(data, new_id, pua)?
+            }
+            Tag::POST => remap_post(data, &order)?,
+            Tag::VORG => remap_vorg(data, new_id)?,
+            Tag::GVAR => gvar::remap(data, &order)?,
+            Tag::HVAR | Tag::VVAR => hvar::remap(data, *tag == Tag::VVAR, &order)?,
+            Tag::COLR => colr::remap(data, new_id)?,
+            Tag::SVG => svg::remap(data, new_id)?,
+            Tag::CBLC | Tag::EBLC => {
+                let data_tag = bitmap::data_tag(*tag);
+                let bitmaps = table(data_tag).ok_or(Error::MissingTable(data_tag))?;
+                let (location, bitmaps) = bitmap::remap(data, bitmaps, new_id)?;
+                remapped.push((data_tag, Cow::Owned(bitmaps)));
+                location
+            }
+            Tag::CBDT | Tag::EBDT => continue,
+            Tag::SBIX => bitmap::remap_sbix(data, ctx.num_glyphs, &order, new_id)?,
+            Tag::HEAD
+            | Tag::OS2
+            | Tag::NAME
+            | Tag::CVT
+            | Tag::FPGM
+            | Tag::PREP
+            | Tag::GASP
+            | Tag::FVAR
+            | Tag::AVAR
+            | Tag::STAT
+            | Tag::CVAR
+            | Tag::MVAR
+            | Tag::CPAL
+            // The layout tables were subsetted to the new IDs already.
+            | Tag::GDEF
+            | Tag::GSUB
+            | Tag::GPOS => {
+                remapped.push((*tag, data.clone()));
+                continue;
+            }
+            // Passed through tables were declared not to depend on glyph IDs.
+            _ if ctx.plan.passthrough.contains(tag) => {
+                remapped.push((*tag, data.clone()));
+                continue;
+            }
+            _ => {
+                ctx.warnings.push(Warning::TableDropped(*tag));
+                continue;
+            }
+        };
+        remapped.push((*tag, Cow::Owned(data)));
+    }
+
+    // The metrics headers without their metrics are kept as they are.
+    for tag in [Tag::HHEA, Tag::VHEA] {
+        if let Some(data) =
+            table(tag).filter(|_| !remapped.iter().any(|&(t, _)| t == tag))
+        {
+            remapped.push((tag, Cow::Owned(data.to_vec())));
+        }
+    }
+
+    ctx.tables = remapped;
+    ctx.remapped = Some(order);
+    Ok(())
+}
+
+/// The glyphs that remain in the output in the order of their new IDs:
+/// `.notdef`, then those in the plan's glyph order and then the others by
+/// their original ID.
+pub(crate) fn new_order(ctx: &Context) -> Vec<u16> {
+    let mut glyphs = retained(ctx);
+    let mut order = vec![0];
+    for &glyph in &ctx.plan.glyph_order {
+        if glyph != 0 && glyphs.binary_search(&glyph).is_ok() {
+            order.push(glyph);
+        }
+    }
+    let listed: HashSet<u16> = order.iter().copied().collect();
+    glyphs.retain(|glyph| !listed.contains(glyph));
+    order.extend(glyphs);
+    order
+}
+
+/// Map the original IDs of the glyphs in the given order to their new IDs.
+pub(crate) fn new_ids(order: &[u16]) -> HashMap<u16, u16> {
+    order.iter().zip(0..).map(|(&glyph, i)| (glyph, i)).collect()
+}
+
+/// The glyphs that remain in the output, sorted by their original ID.
+fn retained(ctx: &Context) -> Vec<u16> {
+    let mut glyphs: Vec<u16> = ctx.subset.iter().copied().collect();
+    glyphs.push(0);
+    glyphs.extend(ctx.space.map(|(glyph, _)| glyph));
+    glyphs.sort_unstable();
+    glyphs.dedup();
+    glyphs
+}
+
+/// Renumber the glyphs of the subsetted glyf and loca tables.
+fn remap_glyf(
+    ctx: &Context,
+    loca: &[u8],
+    glyf: &[u8],
+    order: &[u16],
+    new_ids: &HashMap<u16, u16>,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let table = glyf::Table::from_loca(loca, glyf, ctx.long_loca);
+    let new_id = |glyph: u16| new_ids.get(&glyph).copied().ok_or(Error::InvalidData);
+
+    let mut sub_glyf = Writer::new();
+    let mut sub_loca = Writer::new();
+    let mut write_offset = |offset: usize| {
+        if ctx.long_loca {
+            sub_loca.write::<u32>(offset as u32);
+        } else {
+            sub_loca.write::<u16>((offset / 2) as u16);
+        }
+    };
+
+    for &glyph in order {
+        write_offset(sub_glyf.len());
+        let data = table.glyph_data(glyph)?;
+        sub_glyf.give(&glyf::remap_components(data, new_id)?);
+        if !ctx.long_loca {
+            sub_glyf.align(2);
+        }
+    }
+
+    write_offset(sub_glyf.len());
+
+    Ok((sub_loca.finish(), sub_glyf.finish()))
+}
+
+/// Renumber the glyphs of a metrics table (`hmtx` or `vmtx`) and update the
+/// number of long metrics in its header (`hhea` or `vhea`).
+///
+/// Trailing glyphs with the same advance share the last long metric.
+fn remap_metrics(
+    header: &[u8],
+    metrics: &[u8],
+    order: &[u16],
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let num_long = usize::from(u16::read_at(header, 34)?);
+    let mut entries = vec![];
+    for &glyph in order {
+        let glyph = usize::from(glyph);
+        let (advance, bearing) = if glyph < num_long {
+            (u16::read_at(metrics, 4 * glyph)?, i16::read_at(metrics, 4 * glyph + 2)?)
+        } else {
+            let advance = match num_long {
+                0 => 0,
+                _ => u16::read_at(metrics, 4 * (num_long - 1))?,
+            };
+            (advance, i16::read_at(metrics, 4 * num_long + 2 * (glyph - num_long))?)
+        };
+        entries.push((advance, bearing));
+    }
+
+    let mut new_num_long = entries.len();
+    while new_num_long > 1 && entries[new_num_long - 1].0 == entries[new_num_long - 2].0 {
+        new_num_long -= 1;
+    }
+
+    let mut sub_metrics = Writer::new();
+    for (i, (advance, bearing)) in entries.into_iter().enumerate() {
+        if i < new_num_long {
+            sub_metrics.write::<u16>(advance);
+        }
+        sub_metrics.write::<i16>(bearing);
+    }
+
+    let mut header = header.to_vec();
+    header
+        .get_mut(34..36)
+        .ok_or(Error::MissingData)?
+        .copy_from_slice(&(new_num_long as u16).to_be_bytes());
+
+    Ok((header, sub_metrics.finish()))
+}
+
+/// Renumber the glyph names of the subsetted post table.
+///
+/// Version 1 names are converted to version 2 with the same standard names.
+/// Version 3 has no names, and other versions are reduced to it.
+fn remap_post(post: &[u8], order: &[u16]) -> Result<Vec<u8>> {
+    let mut r = Reader::new(post);
+    let version = r.read::<u32>()?;
+    let header = r.take(28)?;
+
+    let mut sub_post = Writer::new();
+    match version {
+        0x00010000 => {
+            sub_post.write::<u32>(0x00020000);
+            sub_post.give(header);
+            sub_post.write::<u16>(order.len() as u16);
+            for &glyph in order {
+                sub_post.write::<u16>(if glyph <= 257 { glyph } else { 0 });
+            }
+        }
+        0x00020000 => {
+            let num_glyphs = r.read::<u16>()?;
+            let mut indices = vec![];
+            for _ in 0..num_glyphs {
+                indices.push(r.read::<u16>()?);
+            }
+
+            sub_post.write::<u32>(0x00020000);
+            sub_post.give(header);
+            sub_post.write::<u16>(order.len() as u16);
+            for &glyph in order {
+                let index = indices.get(usize::from(glyph)).ok_or(Error::MissingData)?;
+                sub_post.write::<u16>(*index);
+            }
+
+            // The names of the removed glyphs were already dropped, so the
+            // strings stay in the same order.
+            sub_post.give(r.data());
+        }
+        _ => {
+            sub_post.write::<u32>(0x00030000);
+            sub_post.give(header);
+        }
+    }
+
+    Ok(sub_post.finish())
+}
+
+/// Renumber the glyphs of the VORG table.
+fn remap_vorg(vorg: &[u8], new_id: impl Fn(u16) -> Option<u16>) -> Result<Vec<u8>> {
+    let mut r = Reader::new(vorg);
+    let header = r.take(6)?;
+    let count = r.read::<u16>()?;
+
+    let mut records = vec![];
+    for _ in 0..count {
+        let glyph = r.read::<u16>()?;
+        let origin = r.read::<i16>()?;
+        if let Some(glyph) = new_id(glyph) {
+            records.push((glyph, origin));
+        }
+    }
+    records.sort_by_key(|&(glyph, _)| glyph);
+
+    let mut sub_vorg = Writer::new();
+    sub_vorg.give(header);
+    sub_vorg.write::<u16>(records.len() as u16);
+    for (glyph, origin) in records {
+        sub_vorg.write::<u16>(glyph);
+        sub_vorg.write::<i16>(origin);
+    }
+
+    Ok(sub_vorg.finish())
+}
+
+/// Pairs of original and new glyph IDs, sorted by the original ID.
+pub type GlyphMap = Vec<(u16, u16)>;
+
+/// Chooses the new IDs that [`Profile::remap_glyphs`] assigns by a key per
+/// glyph.
+///
+/// Implemented for closures, so that e.g. glyphs can be ordered by how often
+/// they are used without listing them all up front.
+pub trait GlyphOrder {
+    /// The sort key of a glyph in the source font, or `None` to leave the
+    /// glyph in its original order after the keyed glyphs.
+    fn key(&self, glyph: u16) -> Option<u32>;
+}
+
+impl<F: Fn(u16) -> Option<u32>> GlyphOrder for F {
+    fn key(&self, glyph: u16) -> Option<u32> {
+        self(glyph)
+    }
+}
+
+/// Subset a font face with a plan like [`subset_with_plan`] and also return
+/// the glyph IDs of the output, as pairs of the original and the new ID sorted
+/// by the original one.
+///
+/// The new IDs only differ from the original ones if the plan renumbers the
+/// glyphs, see [`Profile::remap_glyphs`]. Otherwise, the pairs map each
+/// retained glyph to itself.
+///
+/// A shorthand for the glyph map of [`subset_to_result`].
+pub fn subset_with_glyph_map(
+    data: &[u8],
+    index: u32,
+    plan: &Plan,
+) -> Result<(Vec<u8>, GlyphMap)> {
+    let (font, _, report, _) = result::subset_all(data, index, plan)?;
+    Ok((font, report.glyph_map))
+}
+
+/// The original and new IDs of the glyphs in the output.
+pub(crate) fn glyph_map(ctx: &Context) -> GlyphMap {
+    match &ctx.remapped {
+        Some(order) => {
+            let mut map: GlyphMap = new_ids(order).into_iter().collect();
+            map.sort_unstable();
+            map
+        }
+        None => retained(ctx).into_iter().map(|glyph| (glyph, glyph)).collect(),
+    }
+}
@@ -0,0 +1,173 @@
+use super::*;
+use crate::json::JsonWriter;
+
+/// What a subsetted font contains compared to its source.
+///
+/// Produced by [`subset_to_result`] for tools that build on the output, like
+/// PDF generators writing `/Differences` arrays or CSS generators writing
+/// `unicode-range` declarations.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SubsetReport {
+    /// The original IDs of the glyphs in the output, sorted.
+    pub retained: Vec<u16>,
+    /// The original IDs of the glyphs whose data was removed, sorted.
+    pub dropped: Vec<u16>,
+    /// The number of glyphs in the output, as in the `numGlyphs` of its `maxp`
+    /// table. Smaller than in the source only if the glyphs were renumbered.
+    pub num_glyphs: u16,
+    /// The original and new IDs of the retained glyphs, sorted by the
+    /// original ID. The IDs only differ if the glyphs were renumbered, see
+    /// [`Profile::remap_glyphs`].
+    pub glyph_map: GlyphMap,
+    /// The codepoints that the `cmap` table of the output maps to retained
+    /// glyphs and those glyphs (with new IDs), sorted by codepoint. Mappings
+    /// to glyphs whose data was removed are left out.
+    pub cmap: Vec<(u32, u16)>,
+    /// The size of each table in the source and in the output, sorted by tag.
+    pub tables: Vec<TableSize>,
+    /// The private tables that were copied unmodified because the profile
+    /// declared them safe, see [`Profile::passthrough_tables`]. Sorted.
+    pub passthrough: Vec<Tag>,
+    /// The original names of the retained glyphs by their new ID, sorted by
+    /// the new ID. Only present if the output keeps the glyph names of its
+    /// `post` table. Glyphs named `.notdef` are left out.
+    pub glyph_names: Vec<(u16, String)>,
+}
+
+/// The size of a table before and after subsetting.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TableSize {
+    /// The table's tag.
+    pub tag: Tag,
+    /// The size in the source font, if the table exists there.
+    pub before: Option<usize>,
+    /// The size in the output without padding, if the table exists there.
+    pub after: Option<usize>,
+}
+
+impl SubsetReport {
+    /// Serialize the report to JSON.
+    pub fn to_json(&self) -> String {
+        let mut w = JsonWriter::new();
+        w.begin_object(None);
+        w.numbers(Some("retained"), self.retained.iter().copied());
+        w.numbers(Some("dropped"), self.dropped.iter().copied());
+        w.number(Some("num_glyphs"), self.num_glyphs);
+        w.begin_object(Some("glyph_map"));
+        for &(old, new) in &self.glyph_map {
+            w.number(Some(&old.to_string()), new);
+        }
+        w.end_object();
+        w.begin_object(Some("cmap"));
+        for &(c, glyph) in &self.cmap {
+            w.number(Some(&c.to_string()), glyph);
+        }
+        w.end_object();
+        w.begin_object(Some("tables"));
+        for table in &self.tables {
+            w.begin_object(Some(&table.tag.to_string()));
+            if let Some(before) = table.before {
+                w.number(Some("before"), before as u32);
+            }
+            if let Some(after) = table.after {
+                w.number(Some("after"), after as u32);
+            }
+            w.end_object();
+        }
+        w.end_object();
+        let tags: Vec<String> =
+            self.passthrough.iter().map(|tag| tag.to_string()).collect();
+        w.strings(Some("passthrough"), tags.iter().map(String::as_str));
+        w.begin_object(Some("glyph_names"));
+        for (glyph, name) in &self.glyph_names {
+            w.string(Some(&glyph.to_string()), name);
+        }
+        w.end_object();
+        w.end_object();
+        w.finish()
+    }
+}
+
+/// Subset a font face with a plan like [`subset_with_plan`] and also report
+/// which glyphs and characters remain and how large the tables became.
+///
+/// A shorthand for the report of [`subset_to_result`].
+pub fn subset_with_report(
+    data: &[u8],
+    index: u32,
+    plan: &Plan,
+) -> Result<(Vec<u8>, SubsetReport)> {
+    let (font, _, report, _) = result::subset_all(data, index, plan)?;
+    Ok((font, report))
+}
+
+/// Report on the built tables of a context.
+pub(crate) fn report(ctx: &Context) -> Result<SubsetReport> {
+    let glyph_map = remap::glyph_map(ctx);
+    let retained: Vec<u16> = glyph_map.iter().map(|&(glyph, _)| glyph).collect();
+    let dropped = (0..ctx.num_glyphs)
+        .filter(|glyph| retained.binary_search(glyph).is_err())
+        .collect();
+
+    let mut cmap = match ctx.tables.iter().find(|&&(tag, _)| tag == Tag::CMAP) {
+        Some((_, data)) => cmap::mappings(data)?,
+        None => vec![],
+    };
+    let mut glyphs: Vec<u16> = glyph_map.iter().map(|&(_, glyph)| glyph).collect();
+    glyphs.sort_unstable();
+    cmap.retain(|&(_, glyph)| glyph != 0 && glyphs.binary_search(&glyph).is_ok());
+
+    let mut tables: Vec<TableSize> = ctx
+        .face
+        .tables
+        .iter()
+        .map(|&(tag, data)| TableSize { tag, before: Some(data.len()), after: None })
+        .collect();
+    for (tag, data) in &ctx.tables {
+        match tables.iter_mut().find(|table| table.tag == *tag) {
+            Some(table) => table.after = Some(data.len()),
+            None => tables.push(TableSize {
+                tag: *tag,
+                before: None,
+                after: Some(data.len()),
+            }),
+        }
+    }
+    tables.sort_by_key(|table| table.tag);
+
+    let passthrough = ctx
+        .plan
+        .passthrough
+        .iter()
+        .copied()
+        .filter(|&tag| ctx.tables.iter().any(|&(t, _)| t == tag))
+        .collect();
+
+    // The output's glyph names are already renumbered.
+    let names = match ctx.tables.iter().find(|&&(tag, _)| tag == Tag::POST) {
+        Some((_, data)) => post::names(data)?.unwrap_or_default(),
+        None => vec![],
+    };
+    let glyph_names = glyphs
+        .iter()
+        .filter_map(|&glyph| {
+            let name = names.get(usize::from(glyph))?.as_ref()?;
+            Some((glyph, post::name_string(name)))
+        })
+        .collect();
+
+    let num_glyphs = ctx
+        .remapped
+        .as_ref()
+        .map_or(ctx.num_glyphs, |order| order.len() as u16);
+    Ok(SubsetReport {
+        retained,
+        dropped,
+        num_glyphs,
+        glyph_map,
+        cmap,
+        tables,
+        passthrough,
+        glyph_names,
+    })
+}
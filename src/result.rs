@@ -0,0 +1,135 @@
+#[cfg(any(feature = "brotli", feature = "woff-convert"))]
+use std::sync::Mutex;
+
+use super::*;
+
+/// A subsetted font with everything known about how it was produced, only
+/// converted to other formats on demand.
+///
+/// Produced by [`subset_to_result`]. The OpenType data is available right away
+/// together with the warnings, the glyph map, the report and the provenance,
+/// and [`to_woff2`](Self::to_woff2) only encodes it when called. Encodings are
+/// kept per quality, so callers that need several formats, or want to inspect
+/// the OpenType data before shipping WOFF2, never encode twice or decompress
+/// what they just compressed.
+#[derive(Debug)]
+pub struct SubsetResult {
+    font: Vec<u8>,
+    warnings: Vec<Warning>,
+    report: Option<SubsetReport>,
+    provenance: Option<Provenance>,
+    /// The WOFF2 encodings produced so far, by quality.
+    #[cfg(any(feature = "brotli", feature = "woff-convert"))]
+    woff2: Mutex<Vec<(u8, Vec<u8>)>>,
+}
+
+impl SubsetResult {
+    /// Wrap an already subsetted font, which has no warnings, report or
+    /// provenance.
+    pub fn new(font: Vec<u8>) -> Self {
+        Self {
+            font,
+            warnings: vec![],
+            report: None,
+            provenance: None,
+            #[cfg(any(feature = "brotli", feature = "woff-convert"))]
+            woff2: Mutex::new(vec![]),
+        }
+    }
+
+    /// The subsetted font in the OpenType format.
+    pub fn font(&self) -> &[u8] {
+        &self.font
+    }
+
+    /// Take the subsetted font in the OpenType format.
+    pub fn into_font(self) -> Vec<u8> {
+        self.font
+    }
+
+    /// The issues found while subsetting, sorted by severity, most severe
+    /// first.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// The original and new IDs of the glyphs in the output, see
+    /// [`SubsetReport::glyph_map`]. `None` for a wrapped font.
+    pub fn glyph_map(&self) -> Option<&GlyphMap> {
+        self.report.as_ref().map(|report| &report.glyph_map)
+    }
+
+    /// The number of glyphs in the font, see [`SubsetReport::num_glyphs`].
+    /// `None` for a wrapped font.
+    pub fn num_glyphs(&self) -> Option<u16> {
+        self.report.as_ref().map(|report| report.num_glyphs)
+    }
+
+    /// What the font contains compared to its source. `None` for a wrapped
+    /// font.
+    pub fn report(&self) -> Option<&SubsetReport> {
+        self.report.as_ref()
+    }
+
+    /// Where the tables of the font came from. `None` for a wrapped font.
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+
+    /// Encode the font as WOFF2 with the given Brotli quality, like
+    /// [`ttf_to_woff2`], or return the encoding from an earlier call with the
+    /// same quality.
+    ///
+    /// Available with the `brotli` or the `woff-convert` feature.
+    #[cfg(any(feature = "brotli", feature = "woff-convert"))]
+    pub fn to_woff2(&self, quality: u8) -> Result<Vec<u8>> {
+        let mut encodings = self.woff2.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some((_, woff2)) = encodings.iter().find(|(q, _)| *q == quality) {
+            return Ok(woff2.clone());
+        }
+        let woff2 = ttf_to_woff2(&self.font, quality)?;
+        encodings.push((quality, woff2.clone()));
+        Ok(woff2)
+    }
+}
+
+impl From<Vec<u8>> for SubsetResult {
+    fn from(font: Vec<u8>) -> Self {
+        Self::new(font)
+    }
+}
+
+/// Subset a font face with a plan like [`subset_with_plan`] and return
+/// everything known about the output at once: the warnings, the glyph map, the
+/// report and the provenance. The conversion to other formats is deferred
+/// until it is requested from the result.
+///
+/// The other `subset_with_*` functions are shorthands for parts of the
+/// result, so callers that need several of them should subset once with this
+/// function instead.
+pub fn subset_to_result(data: &[u8], index: u32, plan: &Plan) -> Result<SubsetResult> {
+    let (font, warnings, report, provenance) = subset_all(data, index, plan)?;
+    Ok(SubsetResult {
+        warnings,
+        report: Some(report),
+        provenance: Some(provenance),
+        ..SubsetResult::new(font)
+    })
+}
+
+/// Subset a font face with a plan and return the output together with the
+/// sorted warnings, the report and the provenance.
+pub(crate) fn subset_all(
+    data: &[u8],
+    index: u32,
+    plan: &Plan,
+) -> Result<(Vec<u8>, Vec<Warning>, SubsetReport, Provenance)> {
+    let face = parse(data, index)?;
+    let mut ctx = prepare(face, plan.clone())?;
+    ctx.build_tables()?;
+    let mut warnings = std::mem::take(&mut ctx.warnings);
+    warnings.sort_by_key(|warning| std::cmp::Reverse(warning.severity()));
+    let report = report::report(&ctx)?;
+    let (font, provenance) = provenance::construct(data, ctx)?;
+    Ok((font, warnings, report, provenance))
+}
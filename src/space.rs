@@ -0,0 +1,63 @@
+use super::*;
+
+/// Make sure that U+0020 is mapped to a glyph in the subsetted font.
+///
+/// If the font already maps the space character, its glyph is simply kept.
+/// Otherwise, because glyph IDs must not change, an unused glyph is turned into
+/// an empty glyph with the requested advance and mapped to U+0020.
+pub(crate) fn ensure(ctx: &mut Context) -> Result<()> {
+    if !ctx.plan.ensure_space {
+        return Ok(());
+    }
+
+    let cmap = ctx.expect_table(Tag::CMAP)?;
+    if let Some(glyph) = cmap::lookup(cmap, 0x20)? {
+        ctx.subset.insert(glyph);
+        return Ok(());
+    }
+
+    // Prefer a glyph with its own advance width, so that the long horizontal
+    // metrics don't have to grow.
+    let hhea = ctx.expect_table(Tag::HHEA)?;
+    let num_h_metrics = u16::read_at(hhea, 34)?.min(ctx.num_glyphs);
+    let free = |range: std::ops::Range<u16>| {
+        range.rev().find(|glyph| !ctx.subset.contains(glyph))
+    };
+    let glyph = free(1..num_h_metrics)
+        .or_else(|| free(1..ctx.num_glyphs))
+        .ok_or(Error::NoFreeGlyph)?;
+
+    let advance = match ctx.plan.space_advance {
+        Some(advance) => advance,
+        None => average_advance(ctx, num_h_metrics)?,
+    };
+
+    ctx.space = Some((glyph, advance));
+    ctx.warnings.push(Warning::SpaceSynthesized(glyph));
+    Ok(())
+}
+
+/// The average advance width of the font's glyphs.
+///
+/// Uses `xAvgCharWidth` from the OS/2 table if available and otherwise averages
+/// the non-zero advances in `hmtx`.
+fn average_advance(ctx: &Context, num_h_metrics: u16) -> Result<u16> {
+    if let Some(os2) = ctx.face.table(Tag::OS2) {
+        let avg = i16::read_at(os2, 2)?;
+        if avg > 0 {
+            return Ok(avg as u16);
+        }
+    }
+
+    let hmtx = ctx.expect_table(Tag::HMTX)?;
+    let (mut sum, mut count) = (0_u64, 0_u64);
+    for i in 0..num_h_metrics {
+        let advance = u16::read_at(hmtx, 4 * i as usize)?;
+        if advance > 0 {
+            sum += u64::from(advance);
+            count += 1;
+        }
+    }
+
+    Ok(sum.checked_div(count).unwrap_or(0) as u16)
+}
@@ -0,0 +1,70 @@
+use super::*;
+
+/// The version of the `SUBS` table format.
+const STAMP_VERSION: u16 = 1;
+
+/// Information about the subsetter run that produced a font, as recorded in
+/// its private `SUBS` table.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SubsetVersion {
+    /// The version of this crate that produced the font.
+    pub version: String,
+    /// A hash of the JSON representation of the subsetting [`Plan`], with the
+    /// full glyph closure.
+    ///
+    /// Two fonts with the same hash were subsetted with the same options
+    /// and glyph set.
+    pub plan_hash: u64,
+}
+
+/// Read the version stamp written when subsetting with
+/// [`Profile::version_stamp`].
+///
+/// Returns `None` if the font doesn't contain a stamp.
+pub fn read_stamp(data: &[u8], index: u32) -> Result<Option<SubsetVersion>> {
+    let face = parse(data, index)?;
+    let Some(table) = face.table(Tag::SUBS) else { return Ok(None) };
+
+    let mut r = Reader::new(table);
+    if r.read::<u16>()? != STAMP_VERSION {
+        return Err(Error::InvalidData);
+    }
+
+    let plan_hash = u64::from(r.read::<u32>()?) << 32 | u64::from(r.read::<u32>()?);
+    let len = r.read::<u16>()?;
+    let version = std::str::from_utf8(r.take(len as usize)?)
+        .map_err(|_| Error::InvalidData)?
+        .into();
+
+    Ok(Some(SubsetVersion { version, plan_hash }))
+}
+
+/// Write the `SUBS` table for the current subsetting run.
+pub(crate) fn write(ctx: &mut Context) {
+    let mut glyphs: Vec<u16> = ctx.subset.iter().copied().collect();
+    glyphs.sort_unstable();
+    let plan = Plan { glyphs, ..ctx.plan.clone() };
+    let plan_hash = fnv1a(plan.to_json().as_bytes());
+
+    let version = env!("CARGO_PKG_VERSION");
+    let mut w = Writer::new();
+    w.write::<u16>(STAMP_VERSION);
+    w.write::<u32>((plan_hash >> 32) as u32);
+    w.write::<u32>(plan_hash as u32);
+    w.write::<u16>(version.len() as u16);
+    w.give(version.as_bytes());
+
+    // A stamp copied from the source font is replaced by the new one.
+    ctx.tables.retain(|&(tag, _)| tag != Tag::SUBS);
+    ctx.push(Tag::SUBS, w.finish());
+}
+
+/// The 64-bit FNV-1a hash, which is stable across platforms and releases.
+pub(crate) fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325_u64;
+    for &byte in data {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
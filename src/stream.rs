@@ -76,7 +76,7 @@ impl Writer {
 
     /// Align the contents to a byte boundary.
     pub fn align(&mut self, to: usize) {
-        while self.0.len() % to != 0 {
+        while !self.0.len().is_multiple_of(to) {
             self.0.push(0);
         }
     }
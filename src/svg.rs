@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use super::*;
+
+/// Subset the `SVG ` table to the retained glyphs.
+///
+/// Only the documents of retained glyphs are kept. When a document describes
+/// several glyphs, the elements of the removed ones are cut out of it, unless
+/// the remaining content references them, and its records are split into
+/// ranges of retained glyphs that share the document. Changed documents that
+/// were compressed are compressed with gzip again. The table is dropped if no
+/// SVG glyph is retained.
+pub(crate) fn subset(ctx: &mut Context) -> Result<()> {
+    let data = ctx.expect_table(Tag::SVG)?;
+    let subset = &ctx.subset;
+    let table = rewrite(data, |glyph| subset.contains(&glyph).then_some(glyph))?;
+    if let Some(table) = table {
+        ctx.push(Tag::SVG, table);
+    }
+    Ok(())
+}
+
+/// Renumber the glyphs of a subsetted `SVG ` table, renaming the elements of
+/// the glyphs in the documents.
+pub(crate) fn remap(data: &[u8], new_id: impl Fn(u16) -> Option<u16>) -> Result<Vec<u8>> {
+    rewrite(data, new_id)?.ok_or(Error::InvalidData)
+}
+
+/// Write a table with the documents of the glyphs that have a new ID, or none
+/// if there are no such glyphs.
+fn rewrite(data: &[u8], new_id: impl Fn(u16) -> Option<u16>) -> Result<Option<Vec<u8>>> {
+    let list = u32::read_at(data, 2)? as usize;
+    let mut r = Reader::new(data.get(list..).ok_or(Error::InvalidOffset)?);
+    let count = r.read::<u16>()?;
+
+    // The glyphs of each document, which several records may share.
+    let mut documents: BTreeMap<(u32, u32), Vec<u16>> = BTreeMap::new();
+    for _ in 0..count {
+        let start = r.read::<u16>()?;
+        let end = r.read::<u16>()?;
+        let offset = r.read::<u32>()?;
+        let len = r.read::<u32>()?;
+        documents.entry((offset, len)).or_default().extend(start..=end);
+    }
+
+    let mut records = vec![];
+    let mut written: Vec<Vec<u8>> = vec![];
+    for (&(offset, len), glyphs) in &documents {
+        let map: BTreeMap<u16, u16> = glyphs
+            .iter()
+            .filter_map(|&glyph| Some((glyph, new_id(glyph)?)))
+            .collect();
+        if map.is_empty() {
+            continue;
+        }
+
+        let start = list + offset as usize;
+        let source = data.get(start..start + len as usize).ok_or(Error::InvalidOffset)?;
+        let document = match rewrite_document(source, glyphs, &map)? {
+            Some(document) => document,
+            None => source.to_vec(),
+        };
+        let index = written.len();
+        written.push(document);
+
+        let mut new: Vec<u16> = map.into_values().collect();
+        new.sort_unstable();
+        for run in new.chunk_by(|a, b| a + 1 == *b) {
+            records.push((run[0], run[run.len() - 1], index));
+        }
+    }
+    if records.is_empty() {
+        return Ok(None);
+    }
+    records.sort_unstable();
+
+    let mut offsets = vec![];
+    let mut offset = 2 + 12 * records.len();
+    for document in &written {
+        offsets.push(offset);
+        offset += document.len();
+    }
+
+    let mut w = Writer::new();
+    w.write::<u16>(0);
+    w.write::<u32>(10);
+    w.write::<u32>(0);
+    w.write::<u16>(records.len() as u16);
+    for &(start, end, index) in &records {
+        w.write::<u16>(start);
+        w.write::<u16>(end);
+        w.write::<u32>(offsets[index] as u32);
+        w.write::<u32>(written[index].len() as u32);
+    }
+    for document in &written {
+        w.give(document);
+    }
+    Ok(Some(w.finish()))
+}
+
+/// Cut the elements of the document's removed glyphs out of it and rename
+/// the elements of the others to their new IDs. Returns `None` if the
+/// document stays the same.
+fn rewrite_document(
+    source: &[u8],
+    glyphs: &[u16],
+    map: &BTreeMap<u16, u16>,
+) -> Result<Option<Vec<u8>>> {
+    let compressed = gzip::is_gzip(source);
+    let mut document = match compressed {
+        true => gzip::decompress(source)?,
+        false => source.to_vec(),
+    };
+
+    let mut changed = false;
+    for &glyph in glyphs {
+        if map.contains_key(&glyph) {
+            continue;
+        }
+        if let Some(range) = glyph_element(&document, glyph) {
+            document.drain(range);
+            changed = true;
+        }
+    }
+
+    let renamed = rename(&document, map);
+    if renamed != document {
+        document = renamed;
+        changed = true;
+    }
+
+    Ok(changed.then(|| match compressed {
+        true => gzip::compress(&document),
+        false => document,
+    }))
+}
+
+/// The byte range of the element of a glyph, if it isn't referenced from
+/// elsewhere in the document.
+fn glyph_element(document: &[u8], glyph: u16) -> Option<std::ops::Range<usize>> {
+    let name = format!("glyph{glyph}");
+    let mut id = None;
+    for (at, prefix) in names(document) {
+        if document[at..].starts_with(name.as_bytes())
+            && !is_name_byte(document.get(at + name.len()).copied().unwrap_or(b'"'))
+        {
+            match prefix {
+                Prefix::Id => id = Some(at),
+                Prefix::Reference => return None,
+            }
+        }
+    }
+
+    // Find the start tag that the `id` attribute belongs to, and from there
+    // the end of the element.
+    let id = id?;
+    let start = document[..id].iter().rposition(|&b| b == b'<')?;
+    let tag_name = |at: usize| {
+        let len = document[at..].iter().position(|&b| !is_name_byte(b))?;
+        Some(&document[at..at + len])
+    };
+    let element = tag_name(start + 1)?;
+    let mut depth = 0;
+    let mut at = start;
+    while let Some(open) = document[at..].iter().position(|&b| b == b'<') {
+        let open = at + open;
+        let rest = &document[open..];
+        let (skip_to, closing): (&[u8], _) = if rest.starts_with(b"<!--") {
+            (b"-->", None)
+        } else if rest.starts_with(b"<![CDATA[") {
+            (b"]]>", None)
+        } else if rest.starts_with(b"</") {
+            (b">", Some(tag_name(open + 2)? == element))
+        } else if rest.starts_with(b"<?") || rest.starts_with(b"<!") {
+            (b">", None)
+        } else {
+            let end = tag_end(document, open)?;
+            if tag_name(open + 1)? == element && document[end - 2] != b'/' {
+                depth += 1;
+            } else if open == start {
+                return Some(start..end);
+            }
+            at = end;
+            continue;
+        };
+        let end = open + find(rest, skip_to)? + skip_to.len();
+        if closing == Some(true) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(start..end);
+            }
+        }
+        at = end;
+    }
+    None
+}
+
+/// The position after the `>` that ends the tag starting at the given
+/// position, skipping quoted attribute values.
+fn tag_end(document: &[u8], start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, &b) in document.iter().enumerate().skip(start) {
+        match quote {
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None if b == b'"' || b == b'\'' => quote = Some(b),
+            None if b == b'>' => return Some(i + 1),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Rename the glyph names in `id` attributes and references to the new IDs
+/// of the glyphs. The names of glyphs without a new ID are prefixed if they
+/// would clash with the new names.
+fn rename(document: &[u8], map: &BTreeMap<u16, u16>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(document.len());
+    let mut copied = 0;
+    for (at, _) in names(document) {
+        let digits = document[at + 5..].iter().take_while(|b| b.is_ascii_digit()).count();
+        let end = at + 5 + digits;
+        let Some(glyph) = std::str::from_utf8(&document[at + 5..end])
+            .ok()
+            .and_then(|digits| digits.parse::<u16>().ok())
+        else {
+            continue;
+        };
+        if digits == 0 || document.get(end).is_some_and(|&b| is_name_byte(b)) {
+            continue;
+        }
+        let name = match map.get(&glyph) {
+            Some(&new) if new == glyph => continue,
+            Some(&new) => format!("glyph{new}"),
+            None if map.values().any(|&new| new == glyph) => {
+                format!("removed-glyph{glyph}")
+            }
+            None => continue,
+        };
+        out.extend_from_slice(&document[copied..at]);
+        out.extend_from_slice(name.as_bytes());
+        copied = end;
+    }
+    out.extend_from_slice(&document[copied..]);
+    out
+}
+
+/// How a glyph name is used in a document.
+enum Prefix {
+    /// As the value of an `id` attribute.
+    Id,
+    /// In a fragment reference like `href="#glyph2"` or `url(#glyph2)`.
+    Reference,
+}
+
+/// The positions of the names that start with `glyph` and appear as an `id`
+/// or a fragment reference.
+fn names(document: &[u8]) -> impl Iterator<Item = (usize, Prefix)> + '_ {
+    (1..document.len()).filter_map(|at| {
+        if !document[at..].starts_with(b"glyph") {
+            return None;
+        }
+        match document[at - 1] {
+            b'#' => Some((at, Prefix::Reference)),
+            b'"' | b'\'' => {
+                let before = document[..at - 1].trim_ascii_end().strip_suffix(b"=")?;
+                let before = before.trim_ascii_end().strip_suffix(b"id")?;
+                let separated = before.last().is_some_and(u8::is_ascii_whitespace);
+                separated.then_some((at, Prefix::Id))
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Whether the byte can be part of an XML name.
+fn is_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':') || b >= 0x80
+}
+
+/// The position of the needle in the haystack.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
@@ -0,0 +1,599 @@
+//! Tuple and item variation stores, and rebasing their regions onto a limited
+//! design space.
+
+use super::*;
+
+/// The start, peak and end of a region on one axis, in normalized coordinates.
+pub(crate) type Tent = (f32, f32, f32);
+
+/// A tuple variation: deltas that apply in a region of the design space.
+#[derive(Debug, Clone)]
+pub(crate) struct Tuple {
+    /// The region on each axis.
+    pub region: Vec<Tent>,
+    /// The points or values the deltas belong to, or `None` for all of them.
+    pub points: Option<Vec<u16>>,
+    /// The deltas of each dimension, in the order of the points.
+    pub deltas: Vec<Vec<f32>>,
+}
+
+/// Read the tuple variations of a tuple variation store with its header at
+/// the given position. The offset to the serialized data is relative to the
+/// start of `data`.
+pub(crate) fn read_tuples(
+    data: &[u8],
+    at: usize,
+    axis_count: usize,
+    shared: &[Vec<f32>],
+    dimensions: usize,
+    count: usize,
+) -> Result<Vec<Tuple>> {
+    let mut r = Reader::new(data.get(at..).ok_or(Error::InvalidOffset)?);
+    let tuples = r.read::<u16>()?;
+    let offset = usize::from(r.read::<u16>()?);
+    let mut serialized = Reader::new(data.get(offset..).ok_or(Error::InvalidOffset)?);
+    let shared_points = match tuples & 0x8000 {
+        0 => None,
+        _ => Some(point_numbers(&mut serialized)?),
+    };
+
+    let tuple = |r: &mut Reader| -> Result<Vec<f32>> {
+        (0..axis_count)
+            .map(|_| r.read::<F2Dot14>().map(F2Dot14::get))
+            .collect()
+    };
+
+    let mut result = vec![];
+    for _ in 0..tuples & 0x0FFF {
+        let size = usize::from(r.read::<u16>()?);
+        let index = r.read::<u16>()?;
+        let peak = match index & 0x8000 {
+            0 => shared
+                .get(usize::from(index & 0x0FFF))
+                .ok_or(Error::InvalidData)?
+                .clone(),
+            _ => tuple(&mut r)?,
+        };
+        let region = match index & 0x4000 {
+            0 => peak
+                .iter()
+                .map(|&peak| (peak.min(0.0), peak, peak.max(0.0)))
+                .collect(),
+            _ => {
+                let (start, end) = (tuple(&mut r)?, tuple(&mut r)?);
+                (0..axis_count).map(|i| (start[i], peak[i], end[i])).collect()
+            }
+        };
+
+        let mut body = Reader::new(serialized.take(size)?);
+        let points = match index & 0x2000 {
+            0 => shared_points.clone().ok_or(Error::InvalidData)?,
+            _ => point_numbers(&mut body)?,
+        };
+        let len = points.as_ref().map_or(count, Vec::len);
+        // The deltas of all dimensions form a single stream, whose runs may
+        // cross from one dimension to the next.
+        let stream = packed_deltas(&mut body, dimensions * len)?;
+        let deltas = (0..dimensions)
+            .map(|i| stream[i * len..(i + 1) * len].iter().map(|&v| v as f32).collect())
+            .collect();
+        result.push(Tuple { region, points, deltas });
+    }
+
+    Ok(result)
+}
+
+/// Read packed point numbers. Returns `None` if they refer to all points.
+fn point_numbers(r: &mut Reader) -> Result<Option<Vec<u16>>> {
+    let first = r.read::<u8>()?;
+    let count = match first & 0x80 {
+        0 => usize::from(first),
+        _ => usize::from(first & 0x7F) << 8 | usize::from(r.read::<u8>()?),
+    };
+    if count == 0 {
+        return Ok(None);
+    }
+
+    // Each point number is stored as the difference to the previous one.
+    let mut points = Vec::with_capacity(count);
+    let mut point = 0_u16;
+    while points.len() < count {
+        let control = r.read::<u8>()?;
+        for _ in 0..=control & 0x7F {
+            let delta = match control & 0x80 {
+                0 => u16::from(r.read::<u8>()?),
+                _ => r.read::<u16>()?,
+            };
+            point = point.wrapping_add(delta);
+            points.push(point);
+        }
+    }
+    points.truncate(count);
+    Ok(Some(points))
+}
+
+/// Read the given number of packed deltas.
+fn packed_deltas(r: &mut Reader, count: usize) -> Result<Vec<i32>> {
+    let mut deltas = Vec::with_capacity(count);
+    while deltas.len() < count {
+        let control = r.read::<u8>()?;
+        for _ in 0..=control & 0x3F {
+            deltas.push(match control & 0xC0 {
+                0x80 => 0,
+                0x40 => i32::from(r.read::<i16>()?),
+                0xC0 => r.read::<i32>()?,
+                _ => i32::from(r.read::<u8>()? as i8),
+            });
+        }
+    }
+    deltas.truncate(count);
+    Ok(deltas)
+}
+
+/// Write tuple variations to a tuple variation store, leaving out those
+/// without effect. The offset to the serialized data is relative to `base`
+/// bytes before the store. Peaks that are among the shared tuples refer to
+/// them.
+pub(crate) fn write_tuples(
+    tuples: &[Tuple],
+    shared: &[Vec<f32>],
+    base: usize,
+) -> Result<Vec<u8>> {
+    // Tuples whose deltas round to zero have no effect.
+    let tuples: Vec<&Tuple> = tuples
+        .iter()
+        .filter(|tuple| tuple.deltas.iter().flatten().any(|&v| round(v) != 0))
+        .collect();
+
+    let mut headers = Writer::new();
+    let mut data = Writer::new();
+    for tuple in &tuples {
+        let mut body = Writer::new();
+        write_points(&mut body, tuple.points.as_deref());
+        for deltas in &tuple.deltas {
+            let deltas: Vec<i32> = deltas.iter().map(|&v| round(v)).collect();
+            write_deltas(&mut body, &deltas);
+        }
+        let body = body.finish();
+
+        let peak: Vec<f32> = tuple.region.iter().map(|&(_, peak, _)| peak).collect();
+        let intermediate = tuple
+            .region
+            .iter()
+            .any(|&(start, peak, end)| start != peak.min(0.0) || end != peak.max(0.0));
+        let mut index = 0x2000;
+        let shared_index = shared.iter().position(|tuple| *tuple == peak);
+        match shared_index {
+            Some(i) => index |= i as u16,
+            None => index |= 0x8000,
+        }
+        if intermediate {
+            index |= 0x4000;
+        }
+
+        headers
+            .write::<u16>(u16::try_from(body.len()).map_err(|_| Error::OffsetOverflow)?);
+        headers.write::<u16>(index);
+        if shared_index.is_none() {
+            write_tuple(&mut headers, peak.iter().copied());
+        }
+        if intermediate {
+            write_tuple(&mut headers, tuple.region.iter().map(|&(start, _, _)| start));
+            write_tuple(&mut headers, tuple.region.iter().map(|&(_, _, end)| end));
+        }
+        data.give(&body);
+    }
+
+    let mut w = Writer::new();
+    w.write::<u16>(tuples.len() as u16);
+    let offset = base + 4 + headers.len();
+    w.write::<u16>(u16::try_from(offset).map_err(|_| Error::OffsetOverflow)?);
+    w.give(&headers.finish());
+    w.give(&data.finish());
+    Ok(w.finish())
+}
+
+/// Write a tuple of normalized coordinates.
+pub(crate) fn write_tuple(w: &mut Writer, tuple: impl Iterator<Item = f32>) {
+    for value in tuple {
+        w.write::<i16>((value * 16384.0).round() as i16);
+    }
+}
+
+/// Write packed point numbers, or a zero count for all points.
+fn write_points(w: &mut Writer, points: Option<&[u16]>) {
+    let Some(points) = points else {
+        w.write::<u8>(0);
+        return;
+    };
+    match points.len() {
+        len @ 0..=0x7F => w.write::<u8>(len as u8),
+        len => w.write::<u16>(0x8000 | len as u16),
+    }
+
+    let mut differences = vec![];
+    let mut previous = 0;
+    for &point in points {
+        differences.push(point.wrapping_sub(previous));
+        previous = point;
+    }
+    for run in differences.chunk_by(|a, b| (*a <= 0xFF) == (*b <= 0xFF)) {
+        for run in run.chunks(128) {
+            let words = run[0] > 0xFF;
+            w.write::<u8>(if words { 0x80 } else { 0 } | (run.len() - 1) as u8);
+            for &difference in run {
+                match words {
+                    true => w.write::<u16>(difference),
+                    false => w.write::<u8>(difference as u8),
+                }
+            }
+        }
+    }
+}
+
+/// Write packed deltas in runs of zeros, bytes, words and longs.
+fn write_deltas(w: &mut Writer, deltas: &[i32]) {
+    let kind = |v: i32| match v {
+        0 => 0x80,
+        -128..=127 => 0x00,
+        -32768..=32767 => 0x40,
+        _ => 0xC0,
+    };
+    for run in deltas.chunk_by(|&a, &b| kind(a) == kind(b)) {
+        for run in run.chunks(64) {
+            let kind = kind(run[0]);
+            w.write::<u8>(kind | (run.len() - 1) as u8);
+            for &delta in run {
+                match kind {
+                    0x00 => w.write::<u8>(delta as u8),
+                    0x40 => w.write::<i16>(delta as i16),
+                    0xC0 => w.write::<i32>(delta),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Round a delta like font tools do, with halves rounded up.
+pub(crate) fn round(value: f32) -> i32 {
+    (value + 0.5).floor() as i32
+}
+
+/// How much the deltas of a region apply on one axis at a normalized
+/// coordinate.
+pub(crate) fn tent_scalar(value: f32, (start, peak, end): Tent) -> f32 {
+    // Invalid regions don't restrict the axis.
+    if peak == 0.0
+        || value == peak
+        || start > peak
+        || peak > end
+        || (start < 0.0 && end > 0.0)
+    {
+        return 1.0;
+    }
+    if value <= start || value >= end {
+        return 0.0;
+    }
+    if value < peak {
+        (value - start) / (peak - start)
+    } else {
+        (end - value) / (end - peak)
+    }
+}
+
+/// The range that an axis is limited to, in normalized coordinates of the
+/// variable font.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Limit {
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+}
+
+impl Limit {
+    /// Map a normalized coordinate of the variable font to the limited axis,
+    /// whose minimum, default and maximum are at -1, 0 and 1.
+    pub fn rebase(self, value: f32) -> f32 {
+        let value = value.max(self.min).min(self.max);
+        if value > self.default {
+            (value - self.default) / (self.max - self.default)
+        } else if value < self.default {
+            (value - self.default) / (self.default - self.min)
+        } else {
+            0.0
+        }
+    }
+
+    /// Map a normalized coordinate of the limited axis back to the variable
+    /// font.
+    pub fn position(self, value: f32) -> f32 {
+        if value > 0.0 {
+            self.default + value * (self.max - self.default)
+        } else {
+            self.default + value * (self.default - self.min)
+        }
+    }
+}
+
+/// Express the deltas of a region on the limited axes.
+///
+/// Returns the factor with which the deltas apply at the new default, and
+/// the regions of the limited design space that take the rest of the deltas
+/// with their factors. On each axis, the scalar minus its value at the
+/// default is a piecewise linear function, which is a sum of tents peaking at
+/// its breakpoints. Regions that span several axes become the products of
+/// these sums.
+pub(crate) fn rebase(region: &[Tent], limits: &[Limit]) -> (f32, Vec<(f32, Vec<Tent>)>) {
+    let none = (0.0, 0.0, 0.0);
+    let mut terms = vec![(1.0, vec![none; limits.len()])];
+    for (i, (&tent, &limit)) in region.iter().zip(limits).enumerate() {
+        let base = tent_scalar(limit.default, tent);
+
+        let mut breakpoints = vec![-1.0, 0.0, 1.0];
+        for value in [tent.0, tent.1, tent.2] {
+            if limit.min <= value && value <= limit.max {
+                breakpoints.push(f2dot14(limit.rebase(value)));
+            }
+        }
+        breakpoints.sort_by(f32::total_cmp);
+        breakpoints.dedup();
+
+        let mut tents = vec![];
+        for (k, &peak) in breakpoints.iter().enumerate() {
+            let factor = tent_scalar(limit.position(peak), tent) - base;
+            if peak == 0.0 || factor.abs() < 1e-6 {
+                continue;
+            }
+            let start = breakpoints[k.saturating_sub(1)];
+            let end = breakpoints.get(k + 1).copied().unwrap_or(peak);
+            tents.push((factor, (start, peak, end)));
+        }
+
+        let mut expanded = vec![];
+        for (factor, region) in terms {
+            if base != 0.0 {
+                expanded.push((factor * base, region.clone()));
+            }
+            for &(scale, tent) in &tents {
+                let mut region = region.clone();
+                region[i] = tent;
+                expanded.push((factor * scale, region));
+            }
+        }
+        terms = expanded;
+    }
+
+    let mut constant = 0.0;
+    terms.retain(|(factor, region)| {
+        let default = region.iter().all(|&tent| tent == none);
+        if default {
+            constant += factor;
+        }
+        !default
+    });
+    (constant, terms)
+}
+
+/// Round a normalized coordinate to the precision of an `F2Dot14` number.
+pub(crate) fn f2dot14(value: f32) -> f32 {
+    (value * 16384.0).round() / 16384.0
+}
+
+/// Rebase the regions of an item variation store onto the limited axes.
+///
+/// The deltas that apply at a moved default are dropped, since the values
+/// they vary live in other tables.
+pub(crate) fn rebase_store(store: &[u8], limits: &[Limit]) -> Result<Vec<u8>> {
+    let (axis_count, source) = read_regions(store)?;
+    let rebased: Vec<_> = source.iter().map(|region| rebase(region, limits).1).collect();
+
+    let mut regions: Vec<Vec<Tent>> = vec![];
+    let mut tables = vec![];
+    let count = usize::from(u16::read_at(store, 6)?);
+    for i in 0..count {
+        let at = u32::read_at(store, 8 + 4 * i)? as usize;
+        let (indices, deltas) = read_item_variation_data(store, at)?;
+
+        // Each old column spreads over the new regions it rebases into.
+        let mut columns: Vec<usize> = vec![];
+        let mut spread = vec![];
+        for &index in &indices {
+            let mut targets = vec![];
+            for (factor, region) in rebased.get(index).ok_or(Error::InvalidData)? {
+                let region_index = match regions.iter().position(|r| r == region) {
+                    Some(k) => k,
+                    None => {
+                        regions.push(region.clone());
+                        regions.len() - 1
+                    }
+                };
+                let column = match columns.iter().position(|&k| k == region_index) {
+                    Some(c) => c,
+                    None => {
+                        columns.push(region_index);
+                        columns.len() - 1
+                    }
+                };
+                targets.push((column, *factor));
+            }
+            spread.push(targets);
+        }
+
+        let mut rows = vec![];
+        for deltas in &deltas {
+            let mut row = vec![0.0; columns.len()];
+            for (&delta, targets) in deltas.iter().zip(&spread) {
+                for &(column, factor) in targets {
+                    row[column] += factor * delta as f32;
+                }
+            }
+            rows.push(row.into_iter().map(round).collect::<Vec<i32>>());
+        }
+        tables.push(write_item_variation_data(&columns, &rows));
+    }
+
+    Ok(write_store(axis_count, &regions, &tables))
+}
+
+/// Drop the regions of an item variation store that no delta set uses.
+///
+/// Columns whose deltas are all zero are removed from their item variation
+/// data, then the regions that no column refers to anymore are removed from
+/// the region list and the others renumbered in their original order. The
+/// delta sets keep their outer and inner indices.
+pub(crate) fn prune_regions(store: &[u8]) -> Result<Vec<u8>> {
+    let (axis_count, mut regions) = read_regions(store)?;
+
+    let mut datas = vec![];
+    let mut used = vec![false; regions.len()];
+    for i in 0..usize::from(u16::read_at(store, 6)?) {
+        let (indices, rows) = match u32::read_at(store, 8 + 4 * i)? as usize {
+            0 => (vec![], vec![]),
+            at => read_item_variation_data(store, at)?,
+        };
+        let kept: Vec<usize> = (0..indices.len())
+            .filter(|&c| rows.iter().any(|row| row[c] != 0))
+            .collect();
+        for &c in &kept {
+            *used.get_mut(indices[c]).ok_or(Error::InvalidData)? = true;
+        }
+        let rows: Vec<Vec<i32>> = rows
+            .iter()
+            .map(|row| kept.iter().map(|&c| row[c]).collect())
+            .collect();
+        let columns: Vec<usize> = kept.iter().map(|&c| indices[c]).collect();
+        datas.push((columns, rows));
+    }
+
+    // The new index of each used region is the number of used ones before it.
+    let new_index: Vec<usize> = used
+        .iter()
+        .scan(0, |next, &used| {
+            let index = *next;
+            *next += used as usize;
+            Some(index)
+        })
+        .collect();
+    let tables: Vec<Vec<u8>> = datas
+        .iter()
+        .map(|(columns, rows)| {
+            let columns: Vec<usize> = columns.iter().map(|&c| new_index[c]).collect();
+            write_item_variation_data(&columns, rows)
+        })
+        .collect();
+    let mut used = used.into_iter();
+    regions.retain(|_| used.next().unwrap_or(false));
+    Ok(write_store(axis_count, &regions, &tables))
+}
+
+/// Read the axis count and the regions of an item variation store's region
+/// list. A store without a list has no regions.
+fn read_regions(store: &[u8]) -> Result<(usize, Vec<Vec<Tent>>)> {
+    let list = u32::read_at(store, 2)? as usize;
+    if list == 0 {
+        return Ok((0, vec![]));
+    }
+    let axis_count = usize::from(u16::read_at(store, list)?);
+    let region_count = usize::from(u16::read_at(store, list + 2)?);
+    let regions = (0..region_count)
+        .map(|i| {
+            (0..axis_count)
+                .map(|j| {
+                    let at = list + 4 + 6 * (i * axis_count + j);
+                    let value = |at| F2Dot14::read_at(store, at).map(F2Dot14::get);
+                    Ok((value(at)?, value(at + 2)?, value(at + 4)?))
+                })
+                .collect()
+        })
+        .collect::<Result<_>>()?;
+    Ok((axis_count, regions))
+}
+
+/// Read the region indices and the rows of deltas of the item variation data
+/// at the given position in a store.
+fn read_item_variation_data(
+    store: &[u8],
+    at: usize,
+) -> Result<(Vec<usize>, Vec<Vec<i32>>)> {
+    let mut r = Reader::new(store.get(at..).ok_or(Error::InvalidOffset)?);
+    let item_count = usize::from(r.read::<u16>()?);
+    let words = r.read::<u16>()?;
+    let long = words & 0x8000 != 0;
+    let words = usize::from(words & 0x7FFF);
+    let indices: Vec<usize> = (0..r.read::<u16>()?)
+        .map(|_| r.read::<u16>().map(usize::from))
+        .collect::<Result<_>>()?;
+
+    let mut rows = vec![];
+    for _ in 0..item_count {
+        let row = (0..indices.len())
+            .map(|j| {
+                Ok(match (j < words, long) {
+                    (true, true) => r.read::<i32>()?,
+                    (true, false) | (false, true) => i32::from(r.read::<i16>()?),
+                    (false, false) => i32::from(r.read::<u8>()? as i8),
+                })
+            })
+            .collect::<Result<_>>()?;
+        rows.push(row);
+    }
+    Ok((indices, rows))
+}
+
+/// Write an item variation store with the given regions and item variation
+/// data tables.
+fn write_store(axis_count: usize, regions: &[Vec<Tent>], tables: &[Vec<u8>]) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.write::<u16>(1);
+    w.write::<u32>(8 + 4 * tables.len() as u32);
+    w.write::<u16>(tables.len() as u16);
+    let mut offset = 8 + 4 * tables.len() + 4 + 6 * axis_count * regions.len();
+    for table in tables {
+        w.write::<u32>(offset as u32);
+        offset += table.len();
+    }
+    w.write::<u16>(axis_count as u16);
+    w.write::<u16>(regions.len() as u16);
+    for region in regions {
+        for &(start, peak, end) in region {
+            write_tuple(&mut w, [start, peak, end].into_iter());
+        }
+    }
+    for table in tables {
+        w.give(table);
+    }
+    w.finish()
+}
+
+/// Write an item variation data table with the given region indices and rows
+/// of deltas, putting the columns that need more bytes first.
+fn write_item_variation_data(columns: &[usize], rows: &[Vec<i32>]) -> Vec<u8> {
+    let fits = |c: usize, bound: i32| {
+        rows.iter().all(|row| (-bound - 1..=bound).contains(&row[c]))
+    };
+    let long = (0..columns.len()).any(|c| !fits(c, i16::MAX.into()));
+    let bound = if long { i16::MAX.into() } else { i8::MAX.into() };
+    let wide: Vec<bool> = (0..columns.len()).map(|c| !fits(c, bound)).collect();
+    let mut order: Vec<usize> = (0..columns.len()).collect();
+    order.sort_by_key(|&c| !wide[c]);
+    let words = wide.iter().filter(|&&wide| wide).count() as u16;
+
+    let mut w = Writer::new();
+    w.write::<u16>(rows.len() as u16);
+    w.write::<u16>(if long { 0x8000 | words } else { words });
+    w.write::<u16>(columns.len() as u16);
+    for &c in &order {
+        w.write::<u16>(columns[c] as u16);
+    }
+    for row in rows {
+        for &c in &order {
+            match (wide[c], long) {
+                (true, true) => w.write::<i32>(row[c]),
+                (true, false) | (false, true) => w.write::<i16>(row[c] as i16),
+                (false, false) => w.write::<u8>(row[c] as u8),
+            }
+        }
+    }
+    w.finish()
+}
@@ -0,0 +1,124 @@
+use super::*;
+
+/// Add the vertical metrics tables `vhea` and `vmtx` to a font that lacks
+/// them, so that it can be laid out vertically.
+///
+/// All glyphs get the same advance height. The top of the vertical advance
+/// is at the typographic ascender, so the top side bearing of each glyph is
+/// the distance from there to the top of its bounding box. CFF fonts also get
+/// a `VORG` table with that vertical origin, since their glyph bounds aren't
+/// known here and the side bearings use the font's bounding box instead.
+pub(crate) fn synthesize(ctx: &mut Context) -> Result<()> {
+    if !ctx.plan.vertical {
+        return Ok(());
+    }
+
+    if ctx.face.table(Tag::VHEA).is_some() && ctx.face.table(Tag::VMTX).is_some() {
+        return Ok(());
+    }
+
+    let head = ctx.expect_table(Tag::HEAD)?;
+    let units_per_em = u16::read_at(head, 18)?;
+    let font_bounds = (i16::read_at(head, 38)?, i16::read_at(head, 42)?);
+
+    // Prefer the typographic metrics, which span the ideographic em box in
+    // CJK fonts.
+    let (ascender, descender) = match ctx.face.table(Tag::OS2) {
+        Some(os2) => (i16::read_at(os2, 68)?, i16::read_at(os2, 70)?),
+        None => {
+            let hhea = ctx.expect_table(Tag::HHEA)?;
+            (i16::read_at(hhea, 4)?, i16::read_at(hhea, 6)?)
+        }
+    };
+
+    let advance = match ctx.plan.vertical_advance {
+        Some(advance) => advance,
+        None => match u16::try_from(i32::from(ascender) - i32::from(descender)) {
+            Ok(advance) if advance > 0 => advance,
+            _ => units_per_em,
+        },
+    };
+
+    let glyf = match ctx.outlines {
+        Outlines::TrueType => Some(ctx.glyf()?),
+        _ => None,
+    };
+
+    // The vertical bounds of each retained glyph with an outline.
+    let bounds = |glyph: u16| -> Result<Option<(i16, i16)>> {
+        if !ctx.subset.contains(&glyph) {
+            return Ok(None);
+        }
+        match &glyf {
+            Some(glyf) => {
+                let data = glyf.glyph_data(glyph)?;
+                if data.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some((i16::read_at(data, 4)?, i16::read_at(data, 8)?)))
+            }
+            None => Ok(Some(font_bounds)),
+        }
+    };
+
+    let mut side_bearings = vec![];
+    let (mut min_top, mut min_bottom, mut max_extent) = (i16::MAX, i16::MAX, i16::MIN);
+    for glyph in 0..ctx.num_glyphs {
+        let Some((y_min, y_max)) = bounds(glyph)? else {
+            side_bearings.push(0);
+            continue;
+        };
+        let top = i32::from(ascender) - i32::from(y_max);
+        let height = i32::from(y_max) - i32::from(y_min);
+        let clamp = |v: i32| v.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+        side_bearings.push(clamp(top));
+        min_top = min_top.min(clamp(top));
+        min_bottom = min_bottom.min(clamp(i32::from(advance) - top - height));
+        max_extent = max_extent.max(clamp(top + height));
+    }
+    if max_extent == i16::MIN {
+        (min_top, min_bottom, max_extent) = (0, 0, 0);
+    }
+
+    let mut vhea = Writer::new();
+    vhea.write::<u32>(0x00011000);
+    // The ideographic em box is centered on the vertical baseline.
+    vhea.write::<i16>((advance / 2) as i16);
+    vhea.write::<i16>(-((advance - advance / 2) as i16));
+    vhea.write::<i16>(0); // line gap
+    vhea.write::<u16>(advance);
+    vhea.write::<i16>(min_top);
+    vhea.write::<i16>(min_bottom);
+    vhea.write::<i16>(max_extent);
+    vhea.write::<i16>(0); // caret slope rise
+    vhea.write::<i16>(1); // caret slope run
+    vhea.write::<i16>(0); // caret offset
+    vhea.give(&[0; 8]); // reserved
+    vhea.write::<i16>(0); // metric data format
+    vhea.write::<u16>(1); // number of long vertical metrics
+
+    let mut vmtx = Writer::new();
+    vmtx.write::<u16>(advance);
+    for side_bearing in side_bearings {
+        vmtx.write::<i16>(side_bearing);
+    }
+
+    // Incomplete vertical metrics of the source font are replaced.
+    ctx.tables.retain(|&(tag, _)| tag != Tag::VHEA && tag != Tag::VMTX);
+    ctx.push(Tag::VHEA, vhea.finish());
+    ctx.push(Tag::VMTX, vmtx.finish());
+
+    if ctx.outlines == Outlines::Cff
+        && !ctx.tables.iter().any(|&(tag, _)| tag == Tag::VORG)
+    {
+        let mut vorg = Writer::new();
+        vorg.write::<u16>(1); // major version
+        vorg.write::<u16>(0); // minor version
+        vorg.write::<i16>(ascender);
+        vorg.write::<u16>(0); // number of vertical origin metrics
+        ctx.push(Tag::VORG, vorg.finish());
+    }
+
+    ctx.warnings.push(Warning::VerticalMetricsSynthesized);
+    Ok(())
+}
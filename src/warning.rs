@@ -0,0 +1,165 @@
+use super::*;
+
+/// How severe a [`Warning`] is.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    /// Expected behavior that is worth knowing about.
+    Info,
+    /// Something that may make the output unsuitable for some uses.
+    Warning,
+}
+
+/// Something noteworthy that happened while subsetting, returned by
+/// [`subset_to_result`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Warning {
+    /// A table of the source font that the profile keeps was dropped, e.g.
+    /// because renumbering the glyphs would invalidate it. Tables the profile
+    /// drops on purpose aren't reported.
+    TableDropped(Tag),
+    /// A table the subsetter doesn't know was copied verbatim, including its
+    /// checksum. It may be invalid if it depends on other tables.
+    UnknownTableCopied(Tag),
+    /// A private table was copied unmodified because the profile declared it
+    /// safe to pass through.
+    TablePassedThrough(Tag),
+    /// A requested glyph doesn't exist in the font and was ignored.
+    GlyphMissing(u16),
+    /// The font lacks an outline for the .notdef glyph, so a box was
+    /// synthesized.
+    NotdefSynthesized,
+    /// The font lacks a space glyph, so the given unused glyph was turned into
+    /// one.
+    SpaceSynthesized(u16),
+    /// All glyphs were kept because the given table is copied verbatim and
+    /// may reference any of them.
+    AllGlyphsKept(Tag),
+    /// The font lacks vertical metrics, so they were synthesized.
+    VerticalMetricsSynthesized,
+    /// The given composite glyph references the given component glyph, which
+    /// doesn't exist in the font. The composite is kept as is, so it renders
+    /// incorrectly, and renumbering its glyphs fails.
+    ComponentMissing(u16, u16),
+    /// The lookup with the given index in the given `GSUB` or `GPOS` table has
+    /// a type or subtable format the subsetter doesn't support. It was
+    /// handled according to [`Profile::unsupported_lookups`].
+    UnsupportedLookup(Tag, u16, u16),
+    /// The glyph closure through the given table ended early because the
+    /// table is malformed, so some glyphs its features produce may be missing.
+    ClosureIncomplete(Tag),
+}
+
+impl Warning {
+    /// How severe the warning is.
+    pub fn severity(&self) -> Severity {
+        match self {
+            // Layout tables hold the font's features.
+            Self::TableDropped(Tag::GSUB | Tag::GPOS | Tag::GDEF | Tag::KERN) => {
+                Severity::Warning
+            }
+            Self::TableDropped(_) => Severity::Info,
+            Self::UnknownTableCopied(_) => Severity::Warning,
+            Self::TablePassedThrough(_) => Severity::Info,
+            Self::GlyphMissing(_) => Severity::Warning,
+            Self::NotdefSynthesized => Severity::Info,
+            Self::SpaceSynthesized(_) => Severity::Info,
+            Self::AllGlyphsKept(_) => Severity::Warning,
+            Self::VerticalMetricsSynthesized => Severity::Info,
+            Self::ComponentMissing(..) => Severity::Warning,
+            Self::UnsupportedLookup(..) => Severity::Warning,
+            Self::ClosureIncomplete(_) => Severity::Warning,
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::TableDropped(tag) => write!(f, "dropped table {tag}"),
+            Self::UnknownTableCopied(tag) => {
+                write!(f, "copied unknown table {tag} verbatim")
+            }
+            Self::TablePassedThrough(tag) => {
+                write!(f, "passed private table {tag} through unmodified")
+            }
+            Self::GlyphMissing(glyph) => write!(f, "glyph {glyph} does not exist"),
+            Self::NotdefSynthesized => f.pad("synthesized a .notdef glyph"),
+            Self::SpaceSynthesized(glyph) => {
+                write!(f, "turned glyph {glyph} into a space glyph")
+            }
+            Self::AllGlyphsKept(tag) => {
+                write!(f, "kept all glyphs because table {tag} may reference them")
+            }
+            Self::VerticalMetricsSynthesized => f.pad("synthesized vertical metrics"),
+            Self::ComponentMissing(glyph, component) => {
+                write!(f, "composite glyph {glyph} references missing glyph {component}")
+            }
+            Self::UnsupportedLookup(tag, index, kind) => {
+                write!(f, "lookup {index} of table {tag} has unsupported type {kind}")
+            }
+            Self::ClosureIncomplete(tag) => {
+                write!(f, "glyph closure through malformed table {tag} ended early")
+            }
+        }
+    }
+}
+
+/// Subset a font face with a plan like [`subset_with_plan`] and also return
+/// warnings about things that happened silently, sorted by severity, most
+/// severe first.
+///
+/// A shorthand for the warnings of [`subset_to_result`].
+pub fn subset_with_warnings(
+    data: &[u8],
+    index: u32,
+    plan: &Plan,
+) -> Result<(Vec<u8>, Vec<Warning>)> {
+    let (font, warnings, ..) = result::subset_all(data, index, plan)?;
+    Ok((font, warnings))
+}
+
+/// Whether the subsetter knows the table, i.e. whether it is kept by some
+/// profile.
+pub(crate) fn is_known(tag: Tag) -> bool {
+    matches!(
+        tag,
+        Tag::CFF
+            | Tag::CFF2
+            | Tag::VORG
+            | Tag::GLYF
+            | Tag::CVT
+            | Tag::FPGM
+            | Tag::PREP
+            | Tag::GASP
+            | Tag::CMAP
+            | Tag::HEAD
+            | Tag::HHEA
+            | Tag::HMTX
+            | Tag::MAXP
+            | Tag::NAME
+            | Tag::OS2
+            | Tag::POST
+            | Tag::VHEA
+            | Tag::VMTX
+            | Tag::GSUB
+            | Tag::GPOS
+            | Tag::GDEF
+            | Tag::FVAR
+            | Tag::AVAR
+            | Tag::STAT
+            | Tag::GVAR
+            | Tag::CVAR
+            | Tag::HVAR
+            | Tag::VVAR
+            | Tag::MVAR
+            | Tag::COLR
+            | Tag::CPAL
+            | Tag::SVG
+            | Tag::CBDT
+            | Tag::CBLC
+            | Tag::EBDT
+            | Tag::EBLC
+            | Tag::SBIX
+            | Tag::SUBS
+    ) || plan::is_shaping(tag)
+}
@@ -0,0 +1,1054 @@
+use std::io::Read;
+
+use super::*;
+
+/// Limits on the resources used to decode a WOFF2 font.
+///
+/// WOFF2 fonts are Brotli-compressed and their glyph data is stored in a
+/// transformed, more compact form. A small malicious file can thus expand into
+/// a huge font. Services accepting untrusted WOFF2 files should keep these
+/// limits at a size they are prepared to handle.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Woff2Limits {
+    /// The maximum size of the decompressed and the reconstructed font data in
+    /// bytes.
+    pub max_size: usize,
+    /// The maximum number of tables in the font.
+    pub max_tables: u16,
+}
+
+impl Default for Woff2Limits {
+    fn default() -> Self {
+        Self { max_size: 64 * 1024 * 1024, max_tables: 256 }
+    }
+}
+
+/// Convert a WOFF2 font into an OpenType font that can be subsetted, using the
+/// default [`Woff2Limits`].
+///
+/// Available with the `woff2` feature.
+pub fn woff2_to_ttf(data: &[u8]) -> Result<Vec<u8>> {
+    woff2_to_ttf_with_limits(data, Woff2Limits::default())
+}
+
+/// Convert a WOFF2 font into an OpenType font that can be subsetted.
+///
+/// Fails with [`Error::LimitExceeded`] if the font would exceed the given
+/// limits. WOFF2 font collections are not supported.
+///
+/// Available with the `woff2` feature.
+pub fn woff2_to_ttf_with_limits(data: &[u8], limits: Woff2Limits) -> Result<Vec<u8>> {
+    let mut r = Reader::new(data);
+    if r.read::<u32>()? != 0x774F4632 {
+        return Err(Error::Woff2);
+    }
+
+    let kind = r.read::<FontKind>()?;
+    if kind == FontKind::Collection {
+        return Err(Error::UnknownKind);
+    }
+
+    r.skip(4)?; // length
+    let num_tables = r.read::<u16>()?;
+    if num_tables > limits.max_tables {
+        return Err(Error::LimitExceeded);
+    }
+
+    r.skip(2)?; // reserved
+    let total_sfnt_size = r.read::<u32>()?;
+    if total_sfnt_size as usize > limits.max_size {
+        return Err(Error::LimitExceeded);
+    }
+
+    let total_compressed_size = r.read::<u32>()?;
+    r.skip(24)?; // version, metadata and private data
+
+    let mut entries = vec![];
+    for _ in 0..num_tables {
+        entries.push(read_entry(&mut r)?);
+    }
+
+    // All tables are compressed together into a single Brotli stream.
+    let mut expected = 0_usize;
+    for entry in &entries {
+        expected = expected.checked_add(entry.stored_len()).ok_or(Error::Woff2)?;
+    }
+    if expected > limits.max_size {
+        return Err(Error::LimitExceeded);
+    }
+
+    let compressed = r.take(total_compressed_size as usize)?;
+    let mut stream = vec![];
+    brotli_decompressor::Decompressor::new(compressed, 4096)
+        .take(expected as u64 + 1)
+        .read_to_end(&mut stream)
+        .map_err(|_| Error::Woff2)?;
+    if stream.len() != expected {
+        return Err(Error::Woff2);
+    }
+
+    let mut offset = 0;
+    let mut stored = vec![];
+    for entry in &entries {
+        stored.push(&stream[offset..offset + entry.stored_len()]);
+        offset += entry.stored_len();
+    }
+
+    let find = |tag| entries.iter().position(|entry| entry.tag == tag);
+    let mut glyf = None;
+    if let Some(i) = find(Tag::GLYF).filter(|&i| entries[i].transformed) {
+        let loca = find(Tag::LOCA).ok_or(Error::MissingTable(Tag::LOCA))?;
+        if !entries[loca].transformed || !stored[loca].is_empty() {
+            return Err(Error::Woff2);
+        }
+        glyf = Some(reconstruct_glyf(stored[i], limits.max_size)?);
+    }
+
+    let mut tables: Vec<(Tag, Cow<[u8]>)> = vec![];
+    for (entry, &data) in entries.iter().zip(&stored) {
+        let data = match entry.tag {
+            Tag::GLYF | Tag::LOCA if entry.transformed => continue,
+            Tag::HMTX if entry.transformed => {
+                let glyf = glyf.as_ref().ok_or(Error::Woff2)?;
+                let table = |tag| {
+                    let i = find(tag).ok_or(Error::MissingTable(tag))?;
+                    Ok(stored[i])
+                };
+                let num_glyphs = u16::read_at(table(Tag::MAXP)?, 4)?;
+                let num_h_metrics = u16::read_at(table(Tag::HHEA)?, 34)?;
+                Cow::Owned(reconstruct_hmtx(data, num_glyphs, num_h_metrics, glyf)?)
+            }
+            Tag::HEAD if data.len() < 12 => return Err(Error::Woff2),
+            _ if entry.transformed => return Err(Error::Woff2),
+            _ => Cow::Borrowed(data),
+        };
+        tables.push((entry.tag, data));
+    }
+
+    if let Some(glyf) = glyf {
+        tables.push((Tag::GLYF, Cow::Owned(glyf.glyf)));
+        tables.push((Tag::LOCA, Cow::Owned(glyf.loca)));
+    }
+
+    let size = tables.iter().map(|(_, data)| data.len()).sum::<usize>();
+    if size > limits.max_size {
+        return Err(Error::LimitExceeded);
+    }
+
+    Ok(construct(kind, tables, 4))
+}
+
+/// Compress an OpenType font into a WOFF2 font.
+///
+/// The `quality` ranges from 0 to 11 and is passed on to the Brotli
+/// compressor. With the `brotli` feature, the font is encoded natively with
+/// [`RustBrotli`], like [`ttf_to_woff2_with`] does. Otherwise, it's encoded
+/// by the `woff-convert` crate. Available with the `brotli` or the
+/// `woff-convert` feature.
+#[cfg(any(feature = "brotli", feature = "woff-convert"))]
+pub fn ttf_to_woff2(data: &[u8], quality: u8) -> Result<Vec<u8>> {
+    #[cfg(feature = "brotli")]
+    return ttf_to_woff2_with(data, &RustBrotli { quality, threads: 1 });
+    #[cfg(not(feature = "brotli"))]
+    woff_convert::convert_ttf_to_woff2(data, quality.into()).map_err(|_| Error::Woff2)
+}
+
+/// A Brotli compressor for the table data of WOFF2 fonts.
+///
+/// Implement this to use a different Brotli implementation than the built-in
+/// one, e.g. bindings to the system library. Available with the `woff2`
+/// feature.
+pub trait Compressor {
+    /// Compress the data into a single Brotli stream.
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// The pure-Rust Brotli compressor from the `brotli` crate, optionally
+/// splitting the work across multiple threads.
+///
+/// Available with the `brotli` feature.
+#[cfg(feature = "brotli")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RustBrotli {
+    /// The compression quality from 0 to 11.
+    pub quality: u8,
+    /// The number of threads to compress with. With more than one thread, the
+    /// data is split into blocks that are compressed in parallel, which is
+    /// faster but compresses slightly worse.
+    pub threads: usize,
+}
+
+#[cfg(feature = "brotli")]
+impl Default for RustBrotli {
+    fn default() -> Self {
+        Self { quality: 11, threads: 1 }
+    }
+}
+
+#[cfg(feature = "brotli")]
+impl Compressor for RustBrotli {
+    fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        use brotli::enc::{BrotliEncoderParams, Owned, SendAlloc, UnionHasher};
+
+        /// Owned input data that can be shared with the worker threads.
+        struct Input(Vec<u8>);
+
+        impl brotli::SliceWrapper<u8> for Input {
+            fn slice(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        let params = BrotliEncoderParams {
+            quality: i32::from(self.quality.min(11)),
+            ..BrotliEncoderParams::default()
+        };
+
+        if self.threads <= 1 {
+            let mut out = vec![];
+            brotli::BrotliCompress(&mut &data[..], &mut out, &params)
+                .map_err(|_| Error::Woff2)?;
+            return Ok(out);
+        }
+
+        let mut allocs: Vec<_> = (0..self.threads)
+            .map(|_| {
+                SendAlloc::new(brotli::enc::StandardAlloc::default(), UnionHasher::Uninit)
+            })
+            .collect();
+        let mut out = vec![
+            0;
+            brotli::enc::BrotliEncoderMaxCompressedSizeMulti(
+                data.len(),
+                self.threads
+            )
+        ];
+        let len = brotli::enc::compress_multi(
+            &params,
+            &mut Owned::new(Input(data.to_vec())),
+            &mut out,
+            &mut allocs,
+        )
+        .map_err(|_| Error::Woff2)?;
+        out.truncate(len);
+        Ok(out)
+    }
+}
+
+/// Compress an OpenType font into a WOFF2 font with a custom Brotli
+/// compressor.
+///
+/// Like [`ttf_to_woff2`], this transforms the `glyf` and `loca` tables into
+/// their more compact WOFF2 representation and omits the left side bearings in
+/// `hmtx` that equal the glyphs' minimum x coordinates. Font collections are
+/// not supported.
+///
+/// Available with the `woff2` feature.
+pub fn ttf_to_woff2_with(data: &[u8], compressor: &dyn Compressor) -> Result<Vec<u8>> {
+    let kind = Reader::new(data).read::<FontKind>()?;
+    if kind == FontKind::Collection {
+        return Err(Error::UnknownKind);
+    }
+
+    let face = parse(data, 0)?;
+    encode(kind, face.tables, compressor, &WoffBlocks::default())
+}
+
+/// Compress an OpenType font into a WOFF2 font with a custom Brotli
+/// compressor, like [`ttf_to_woff2_with`], and attach the given metadata and
+/// private data.
+///
+/// The metadata is compressed with the same compressor as the tables.
+///
+/// Available with the `woff2` feature.
+pub fn ttf_to_woff2_with_blocks(
+    data: &[u8],
+    compressor: &dyn Compressor,
+    blocks: &WoffBlocks,
+) -> Result<Vec<u8>> {
+    let kind = Reader::new(data).read::<FontKind>()?;
+    if kind == FontKind::Collection {
+        return Err(Error::UnknownKind);
+    }
+
+    let face = parse(data, 0)?;
+    encode(kind, face.tables, compressor, blocks)
+}
+
+/// Read the metadata and private data blocks of a WOFF2 font.
+///
+/// The metadata is decompressed up to the size limit of the default
+/// [`Woff2Limits`]. Available with the `woff2` feature.
+pub fn woff2_blocks(data: &[u8]) -> Result<WoffBlocks> {
+    if u32::read_at(data, 0)? != 0x774F4632 {
+        return Err(Error::Woff2);
+    }
+    let block = |at: usize| {
+        let offset = u32::read_at(data, at)? as usize;
+        let length = u32::read_at(data, at + 4)? as usize;
+        data.get(offset..offset + length).ok_or(Error::InvalidOffset)
+    };
+
+    let mut blocks = WoffBlocks::default();
+    let metadata = block(28)?;
+    if !metadata.is_empty() {
+        let length = u32::read_at(data, 36)? as usize;
+        if length > Woff2Limits::default().max_size {
+            return Err(Error::LimitExceeded);
+        }
+        brotli_decompressor::Decompressor::new(metadata, 4096)
+            .take(length as u64 + 1)
+            .read_to_end(&mut blocks.metadata)
+            .map_err(|_| Error::Woff2)?;
+        if blocks.metadata.len() != length {
+            return Err(Error::Woff2);
+        }
+    }
+    blocks.private = block(40)?.to_vec();
+    Ok(blocks)
+}
+
+/// Subset a font face like [`subset`] and compress the result into a WOFF2
+/// font with a custom Brotli compressor, like [`ttf_to_woff2_with`].
+///
+/// The subsetted tables are encoded directly, without building and parsing the
+/// OpenType font in between.
+///
+/// Available with the `woff2` feature.
+pub fn subset_to_woff2(
+    data: &[u8],
+    index: u32,
+    profile: Profile,
+    compressor: &dyn Compressor,
+) -> Result<Vec<u8>> {
+    let face = parse(data, index)?;
+    let plan = Plan::new(&face, &profile);
+    let mut ctx = prepare(face, plan)?;
+    ctx.build_tables()?;
+    let tables = ctx.tables.iter().map(|(tag, data)| (*tag, data.as_ref())).collect();
+    encode(ctx.kind, tables, compressor, &WoffBlocks::default())
+}
+
+/// Compress the tables of a font into a WOFF2 font with the given metadata
+/// and private data.
+fn encode(
+    kind: FontKind,
+    mut tables: Vec<(Tag, &[u8])>,
+    compressor: &dyn Compressor,
+    blocks: &WoffBlocks,
+) -> Result<Vec<u8>> {
+    tables.sort_by_key(|&(tag, _)| tag);
+
+    // Fonts whose glyphs can't be transformed, e.g. because of a malformed
+    // glyph, are stored with the null transform instead.
+    let table = |tag| tables.iter().find(|&&(t, _)| t == tag).map(|&(_, data)| data);
+    let transformed = transform(table).ok();
+
+    // The transformed `loca` table must directly follow `glyf`.
+    if transformed.is_some() {
+        if let Some(i) = tables.iter().position(|&(tag, _)| tag == Tag::LOCA) {
+            let loca = tables.remove(i);
+            let glyf = tables.iter().position(|&(tag, _)| tag == Tag::GLYF).unwrap_or(0);
+            tables.insert(glyf + 1, loca);
+        }
+    }
+
+    let mut directory = Writer::new();
+    let mut stream = Writer::new();
+    let mut sfnt_size = 12 + 16 * tables.len();
+    for &(tag, table) in &tables {
+        let stored = match (tag, &transformed) {
+            (Tag::GLYF, Some(transformed)) => Some(transformed.glyf.as_slice()),
+            (Tag::LOCA, Some(_)) => Some([].as_slice()),
+            (Tag::HMTX, Some(transformed)) => transformed.hmtx.as_deref(),
+            _ => None,
+        };
+
+        // For `glyf` and `loca`, version 0 is the transformed one and version 3
+        // the null transform. For all other tables it's the other way around.
+        let version = match tag {
+            Tag::GLYF | Tag::LOCA if stored.is_none() => 3 << 6,
+            Tag::GLYF | Tag::LOCA => 0,
+            _ if stored.is_some() => 1 << 6,
+            _ => 0,
+        };
+        match KNOWN_TAGS.iter().position(|&known| *known == tag.0) {
+            Some(i) => directory.write::<u8>(version | i as u8),
+            None => {
+                directory.write::<u8>(version | 0x3F);
+                directory.write::<Tag>(tag);
+            }
+        }
+        write_base_128(&mut directory, table.len() as u32);
+        if let Some(stored) = stored {
+            write_base_128(&mut directory, stored.len() as u32);
+        }
+        stream.give(stored.unwrap_or(table));
+        sfnt_size += table.len().next_multiple_of(4);
+    }
+
+    let directory = directory.finish();
+    let compressed = compressor.compress(&stream.finish())?;
+
+    // The metadata and the private data follow the font data, each starting
+    // on a four-byte boundary.
+    let metadata = match blocks.metadata.is_empty() {
+        true => vec![],
+        false => compressor.compress(&blocks.metadata)?,
+    };
+    let mut offset = (48 + directory.len() + compressed.len()).next_multiple_of(4);
+    let meta_offset = if metadata.is_empty() { 0 } else { offset };
+    offset = (offset + metadata.len()).next_multiple_of(4);
+    let private_offset = if blocks.private.is_empty() { 0 } else { offset };
+    offset = (offset + blocks.private.len()).next_multiple_of(4);
+
+    let mut w = Writer::new();
+    w.write::<u32>(0x774F4632);
+    w.write::<FontKind>(kind);
+    w.write::<u32>(offset as u32);
+    w.write::<u16>(tables.len() as u16);
+    w.write::<u16>(0); // reserved
+    w.write::<u32>(sfnt_size as u32);
+    w.write::<u32>(compressed.len() as u32);
+    w.write::<u16>(1); // major version
+    w.write::<u16>(0); // minor version
+    w.write::<u32>(meta_offset as u32);
+    w.write::<u32>(metadata.len() as u32);
+    w.write::<u32>(blocks.metadata.len() as u32);
+    w.write::<u32>(private_offset as u32);
+    w.write::<u32>(blocks.private.len() as u32);
+    w.give(&directory);
+    w.give(&compressed);
+    w.align(4);
+    w.give(&metadata);
+    w.align(4);
+    w.give(&blocks.private);
+    w.align(4);
+    Ok(w.finish())
+}
+
+/// The transformed glyph data of a font.
+struct Transformed {
+    /// The transformed `glyf` table, which replaces `glyf` and `loca`.
+    glyf: Vec<u8>,
+    /// The transformed `hmtx` table, if some of its left side bearings can be
+    /// restored from the glyphs.
+    hmtx: Option<Vec<u8>>,
+}
+
+/// Transform the `glyf` and `loca` tables, and `hmtx` if possible.
+fn transform<'a>(table: impl Fn(Tag) -> Option<&'a [u8]>) -> Result<Transformed> {
+    let expect = |tag| table(tag).ok_or(Error::MissingTable(tag));
+    let num_glyphs = u16::read_at(expect(Tag::MAXP)?, 4)?;
+    let (glyf, x_mins) = transform_glyf(
+        expect(Tag::GLYF)?,
+        expect(Tag::LOCA)?,
+        expect(Tag::HEAD)?,
+        num_glyphs,
+    )?;
+
+    let hmtx = match (table(Tag::HHEA), table(Tag::HMTX)) {
+        (Some(hhea), Some(hmtx)) => {
+            transform_hmtx(hmtx, u16::read_at(hhea, 34)?, num_glyphs, &x_mins)
+        }
+        _ => None,
+    };
+
+    Ok(Transformed { glyf, hmtx })
+}
+
+/// Transform the `glyf` and `loca` tables into their WOFF2 representation.
+///
+/// Also returns the minimum x coordinate of each glyph as decoders will
+/// reconstruct it.
+fn transform_glyf(
+    glyf: &[u8],
+    loca: &[u8],
+    head: &[u8],
+    num_glyphs: u16,
+) -> Result<(Vec<u8>, Vec<i16>)> {
+    let long = i16::read_at(head, 50)? != 0;
+    let table = glyf::Table::from_loca(loca, glyf, long);
+
+    let mut n_contours = Writer::new();
+    let mut n_points = Writer::new();
+    let mut flags = Writer::new();
+    let mut glyphs = Writer::new();
+    let mut composites = Writer::new();
+    let mut bboxes = Writer::new();
+    let mut instructions = Writer::new();
+    let mut bbox_bitmap = vec![0; 4 * usize::from(num_glyphs).div_ceil(32)];
+    let mut overlap_bitmap = vec![0; usize::from(num_glyphs).div_ceil(8)];
+    let mut overlap = false;
+    let set =
+        |bitmap: &mut [u8], id: u16| bitmap[usize::from(id / 8)] |= 0x80 >> (id % 8);
+
+    let mut x_mins = vec![];
+    // The size of the `glyf` table that decoders will reconstruct, which a
+    // short `loca` table must still be able to address.
+    let mut size = 0;
+    for id in 0..num_glyphs {
+        let data = table.glyph_data(id)?;
+        let mut r = Reader::new(data);
+        let num_contours = if data.is_empty() { 0 } else { r.read::<i16>()? };
+        if num_contours == 0 {
+            n_contours.write::<i16>(0);
+            x_mins.push(0);
+            continue;
+        }
+
+        let bbox = r.take(8)?;
+        x_mins.push(i16::read_at(bbox, 0)?);
+        if num_contours < 0 {
+            // A composite glyph, which always has an explicit bounding box.
+            let (len, have_instructions) = composite_len(r.data())?;
+            n_contours.write::<i16>(-1);
+            set(&mut bbox_bitmap, id);
+            bboxes.give(bbox);
+            composites.give(r.take(len)?);
+            let mut glyph_size = 10 + len;
+            if have_instructions {
+                let len = r.read::<u16>()?;
+                write_255_u16(&mut glyphs, len);
+                instructions.give(r.take(usize::from(len))?);
+                glyph_size += 2 + usize::from(len);
+            }
+            size += glyph_size.next_multiple_of(4);
+            continue;
+        }
+
+        // A simple glyph.
+        let mut end_points = vec![];
+        for _ in 0..num_contours {
+            end_points.push(r.read::<u16>()?);
+        }
+        let num_instructions = r.read::<u16>()?;
+        let glyph_instructions = r.take(usize::from(num_instructions))?;
+        let count = end_points.last().map_or(0, |&end| usize::from(end) + 1);
+        let (points, glyph_overlap) = glyf::read_points(&mut r, count)?;
+
+        n_contours.write::<i16>(num_contours);
+        let mut start = 0;
+        for &end in &end_points {
+            let len = (i32::from(end) + 1 - start)
+                .try_into()
+                .map_err(|_| Error::InvalidData)?;
+            write_255_u16(&mut n_points, len);
+            start = i32::from(end) + 1;
+        }
+
+        let (mut x, mut y) = (0, 0);
+        for &(px, py, on_curve) in &points {
+            flags.write::<u8>(write_triplet(&mut glyphs, px - x, py - y, on_curve));
+            (x, y) = (px, py);
+        }
+        write_255_u16(&mut glyphs, num_instructions);
+        instructions.give(glyph_instructions);
+
+        // The bounding box is only stored if it differs from the one computed
+        // from the points.
+        let (xs, ys) = (points.iter().map(|p| p.0), points.iter().map(|p| p.1));
+        let clamp = |v: Option<i32>| v.unwrap_or(0) as i16;
+        let computed = [
+            clamp(xs.clone().min()),
+            clamp(ys.clone().min()),
+            clamp(xs.max()),
+            clamp(ys.max()),
+        ];
+        if [0, 2, 4, 6].map(|i| i16::read_at(bbox, i).unwrap_or(0)) != computed {
+            set(&mut bbox_bitmap, id);
+            bboxes.give(bbox);
+        }
+
+        if glyph_overlap {
+            set(&mut overlap_bitmap, id);
+            overlap = true;
+        }
+
+        if !long {
+            let mut w = Writer::new();
+            glyf::write_points(&mut w, &points, glyph_overlap.then_some(0x40));
+            let glyph_size = 12 + 2 * end_points.len() + w.len();
+            size += (glyph_size + usize::from(num_instructions)).next_multiple_of(4);
+        }
+    }
+
+    if !long && size / 2 > usize::from(u16::MAX) {
+        return Err(Error::Unsupported);
+    }
+
+    let mut bbox_stream = bbox_bitmap;
+    bbox_stream.extend(bboxes.finish());
+    let streams = [
+        n_contours.finish(),
+        n_points.finish(),
+        flags.finish(),
+        glyphs.finish(),
+        composites.finish(),
+        bbox_stream,
+        instructions.finish(),
+    ];
+
+    let mut w = Writer::new();
+    w.write::<u16>(0); // reserved
+    w.write::<u16>(u16::from(overlap)); // option flags
+    w.write::<u16>(num_glyphs);
+    w.write::<u16>(u16::from(long));
+    for stream in &streams {
+        w.write::<u32>(stream.len() as u32);
+    }
+    for stream in &streams {
+        w.give(stream);
+    }
+    if overlap {
+        w.give(&overlap_bitmap);
+    }
+
+    Ok((w.finish(), x_mins))
+}
+
+/// Transform the `hmtx` table by omitting the left side bearings that equal
+/// the minimum x coordinates of the glyphs, either those of the glyphs with
+/// their own advance or those of the remaining ones, or both.
+fn transform_hmtx(
+    data: &[u8],
+    num_h_metrics: u16,
+    num_glyphs: u16,
+    x_mins: &[i16],
+) -> Option<Vec<u8>> {
+    if num_h_metrics > num_glyphs {
+        return None;
+    }
+
+    let mut r = Reader::new(data);
+    let mut advances = vec![];
+    let mut lsbs = vec![];
+    for id in 0..num_glyphs {
+        if id < num_h_metrics {
+            advances.push(r.read::<u16>().ok()?);
+        }
+        lsbs.push(r.read::<i16>().ok()?);
+    }
+
+    // Trailing data wouldn't survive the transform.
+    if !r.data().is_empty() {
+        return None;
+    }
+
+    let split = usize::from(num_h_metrics);
+    let proportional = lsbs[..split] == x_mins[..split];
+    let monospaced = lsbs[split..] == x_mins[split..];
+    if !proportional && !monospaced {
+        return None;
+    }
+
+    let mut w = Writer::new();
+    w.write::<u8>(u8::from(proportional) | u8::from(monospaced) << 1);
+    for advance in advances {
+        w.write::<u16>(advance);
+    }
+    let kept = [(!proportional, &lsbs[..split]), (!monospaced, &lsbs[split..])];
+    for lsb in kept.into_iter().filter(|(keep, _)| *keep).flat_map(|(_, lsbs)| lsbs) {
+        w.write::<i16>(*lsb);
+    }
+    Some(w.finish())
+}
+
+/// An entry in the WOFF2 table directory.
+struct Entry {
+    tag: Tag,
+    orig_length: u32,
+    transform_length: u32,
+    transformed: bool,
+}
+
+impl Entry {
+    /// The length of the table in the decompressed stream.
+    fn stored_len(&self) -> usize {
+        if self.transformed {
+            self.transform_length as usize
+        } else {
+            self.orig_length as usize
+        }
+    }
+}
+
+/// Read an entry of the table directory.
+fn read_entry(r: &mut Reader) -> Result<Entry> {
+    let flags = r.read::<u8>()?;
+    let tag = match KNOWN_TAGS.get(usize::from(flags & 0x3F)) {
+        Some(&tag) => Tag(*tag),
+        None => r.read::<Tag>()?,
+    };
+
+    // For `glyf` and `loca`, version 0 is the transformed one. For all other
+    // tables it's the null transform.
+    let version = flags >> 6;
+    let transformed = match tag {
+        Tag::GLYF | Tag::LOCA => version == 0,
+        _ => version != 0,
+    };
+
+    let orig_length = read_base_128(r)?;
+    let transform_length = if transformed { read_base_128(r)? } else { 0 };
+    Ok(Entry { tag, orig_length, transform_length, transformed })
+}
+
+/// The tags that can be referenced by index in the table directory.
+const KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ",
+    b"fpgm", b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp",
+    b"hdmx", b"kern", b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF",
+    b"GPOS", b"GSUB", b"EBSC", b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL",
+    b"SVG ", b"sbix", b"acnt", b"avar", b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc",
+    b"feat", b"fmtx", b"fvar", b"gvar", b"hsty", b"just", b"lcar", b"mort", b"morx",
+    b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat", b"Gloc", b"Feat", b"Sill",
+];
+
+/// Read a variable-length `UIntBase128` number.
+fn read_base_128(r: &mut Reader) -> Result<u32> {
+    let mut value = 0_u32;
+    for i in 0..5 {
+        let byte = r.read::<u8>()?;
+        // Leading zeros and overflows are invalid.
+        if (i == 0 && byte == 0x80) || value & 0xFE000000 != 0 {
+            return Err(Error::Woff2);
+        }
+        value = (value << 7) | u32::from(byte & 0x7F);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::Woff2)
+}
+
+/// Write a variable-length `UIntBase128` number.
+fn write_base_128(w: &mut Writer, value: u32) {
+    let len = (32 - value.leading_zeros()).div_ceil(7).max(1);
+    for i in (0..len).rev() {
+        let byte = ((value >> (7 * i)) & 0x7F) as u8;
+        w.write::<u8>(if i > 0 { byte | 0x80 } else { byte });
+    }
+}
+
+/// Write a variable-length `255UInt16` number.
+fn write_255_u16(w: &mut Writer, value: u16) {
+    match value {
+        0..=252 => w.write::<u8>(value as u8),
+        253..=505 => {
+            w.write::<u8>(255);
+            w.write::<u8>((value - 253) as u8);
+        }
+        506..=761 => {
+            w.write::<u8>(254);
+            w.write::<u8>((value - 506) as u8);
+        }
+        _ => {
+            w.write::<u8>(253);
+            w.write::<u16>(value);
+        }
+    }
+}
+
+/// Read a variable-length `255UInt16` number.
+fn read_255_u16(r: &mut Reader) -> Result<u16> {
+    Ok(match r.read::<u8>()? {
+        253 => r.read::<u16>()?,
+        254 => 506 + u16::from(r.read::<u8>()?),
+        255 => 253 + u16::from(r.read::<u8>()?),
+        code => u16::from(code),
+    })
+}
+
+/// The `glyf` and `loca` tables reconstructed from the transformed `glyf`
+/// table.
+struct Glyf {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    /// The minimum x coordinate of each glyph, needed to reconstruct the left
+    /// side bearings in `hmtx`.
+    x_mins: Vec<i16>,
+}
+
+/// Reconstruct the `glyf` and `loca` tables.
+fn reconstruct_glyf(data: &[u8], max_size: usize) -> Result<Glyf> {
+    const OVERLAP_SIMPLE: u8 = 0x40;
+
+    let mut r = Reader::new(data);
+    r.skip(2)?; // reserved
+    let option_flags = r.read::<u16>()?;
+    let num_glyphs = r.read::<u16>()?;
+    let long = r.read::<u16>()? != 0;
+
+    let mut streams = vec![];
+    for _ in 0..7 {
+        streams.push(r.read::<u32>()? as usize);
+    }
+
+    let mut take = |len| r.take(len).map(Reader::new);
+    let mut n_contours = take(streams[0])?;
+    let mut n_points = take(streams[1])?;
+    let mut flags = take(streams[2])?;
+    let mut glyphs = take(streams[3])?;
+    let mut composites = take(streams[4])?;
+    let mut bboxes = take(streams[5])?;
+    let mut instructions = take(streams[6])?;
+    let overlap = match option_flags & 1 {
+        0 => None,
+        _ => Some(take(usize::from(num_glyphs).div_ceil(8))?.data()),
+    };
+
+    let bbox_bitmap = bboxes.take(4 * usize::from(num_glyphs).div_ceil(32))?;
+    let bit =
+        |bitmap: &[u8], id: u16| bitmap[usize::from(id / 8)] & (0x80 >> (id % 8)) != 0;
+
+    let mut glyf = Writer::new();
+    let mut offsets = vec![];
+    let mut x_mins = vec![];
+    for id in 0..num_glyphs {
+        offsets.push(glyf.len());
+        let has_bbox = bit(bbox_bitmap, id);
+        let num_contours = n_contours.read::<i16>()?;
+
+        if num_contours == 0 {
+            // An empty glyph.
+            if has_bbox {
+                return Err(Error::Woff2);
+            }
+            x_mins.push(0);
+            continue;
+        }
+
+        if num_contours < 0 {
+            // A composite glyph, which always has an explicit bounding box.
+            if !has_bbox {
+                return Err(Error::Woff2);
+            }
+            let bbox = bboxes.take(8)?;
+            let (len, have_instructions) = composite_len(composites.data())?;
+            glyf.write::<i16>(-1);
+            glyf.give(bbox);
+            glyf.give(composites.take(len)?);
+            if have_instructions {
+                let len = read_255_u16(&mut glyphs)?;
+                glyf.write::<u16>(len);
+                glyf.give(instructions.take(usize::from(len))?);
+            }
+            x_mins.push(i16::read_at(bbox, 0)?);
+        } else {
+            // A simple glyph.
+            let mut end_points = vec![];
+            let mut total = 0_u32;
+            for _ in 0..num_contours {
+                total += u32::from(read_255_u16(&mut n_points)?);
+                if total == 0 || total > 0x10000 {
+                    return Err(Error::Woff2);
+                }
+                end_points.push((total - 1) as u16);
+            }
+
+            let mut points = vec![];
+            let (mut x, mut y) = (0_i32, 0_i32);
+            for _ in 0..total {
+                let flag = flags.read::<u8>()?;
+                let (dx, dy) = read_triplet(flag & 0x7F, &mut glyphs)?;
+                x += dx;
+                y += dy;
+                points.push((x, y, flag & 0x80 == 0));
+            }
+
+            let bbox = if has_bbox {
+                let bbox = bboxes.take(8)?;
+                [0, 2, 4, 6].map(|i| i16::read_at(bbox, i).unwrap_or(0))
+            } else {
+                let (xs, ys) = (points.iter().map(|p| p.0), points.iter().map(|p| p.1));
+                let clamp = |v: Option<i32>| v.unwrap_or(0) as i16;
+                [
+                    clamp(xs.clone().min()),
+                    clamp(ys.clone().min()),
+                    clamp(xs.max()),
+                    clamp(ys.max()),
+                ]
+            };
+
+            let overlap = overlap.is_some_and(|bitmap| bit(bitmap, id));
+            let num_instructions = read_255_u16(&mut glyphs)?;
+            let instructions = instructions.take(usize::from(num_instructions))?;
+
+            glyf.write::<i16>(num_contours);
+            for v in bbox {
+                glyf.write::<i16>(v);
+            }
+            for end in end_points {
+                glyf.write::<u16>(end);
+            }
+            glyf.write::<u16>(num_instructions);
+            glyf.give(instructions);
+            glyf::write_points(&mut glyf, &points, overlap.then_some(OVERLAP_SIMPLE));
+            x_mins.push(bbox[0]);
+        }
+
+        glyf.align(4);
+        if glyf.len() > max_size {
+            return Err(Error::LimitExceeded);
+        }
+    }
+    offsets.push(glyf.len());
+
+    let mut loca = Writer::new();
+    for offset in offsets {
+        if long {
+            loca.write::<u32>(offset as u32);
+        } else if offset / 2 <= u16::MAX as usize {
+            loca.write::<u16>((offset / 2) as u16);
+        } else {
+            return Err(Error::Woff2);
+        }
+    }
+
+    Ok(Glyf { glyf: glyf.finish(), loca: loca.finish(), x_mins })
+}
+
+/// Determine the length of a composite glyph description and whether it is
+/// followed by instructions.
+fn composite_len(data: &[u8]) -> Result<(usize, bool)> {
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+    const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+    let mut r = Reader::new(data);
+    let mut have_instructions = false;
+    loop {
+        let flags = r.read::<u16>()?;
+        have_instructions |= flags & WE_HAVE_INSTRUCTIONS != 0;
+        let mut len = 2;
+        len += if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & WE_HAVE_A_SCALE != 0 {
+            len += 2;
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            len += 4;
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            len += 8;
+        }
+        r.skip(len)?;
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+
+    Ok((data.len() - r.data().len(), have_instructions))
+}
+
+/// Decode the coordinate deltas of a point from a triplet encoding.
+fn read_triplet(flag: u8, r: &mut Reader) -> Result<(i32, i32)> {
+    let with_sign = |flag: u8, value: i32| if flag & 1 != 0 { value } else { -value };
+    let flag_i = i32::from(flag);
+    let mut byte = || r.read::<u8>().map(i32::from);
+    Ok(match flag {
+        0..=9 => (0, with_sign(flag, ((flag_i & 14) << 7) + byte()?)),
+        10..=19 => (with_sign(flag, (((flag_i - 10) & 14) << 7) + byte()?), 0),
+        20..=83 => {
+            let b0 = flag_i - 20;
+            let b1 = byte()?;
+            (
+                with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+                with_sign(flag >> 1, 1 + ((b0 & 0x0C) << 2) + (b1 & 0x0F)),
+            )
+        }
+        84..=119 => {
+            let b0 = flag_i - 84;
+            let (b1, b2) = (byte()?, byte()?);
+            (
+                with_sign(flag, 1 + ((b0 / 12) << 8) + b1),
+                with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b2),
+            )
+        }
+        120..=123 => {
+            let (b1, b2, b3) = (byte()?, byte()?, byte()?);
+            (
+                with_sign(flag, (b1 << 4) + (b2 >> 4)),
+                with_sign(flag >> 1, ((b2 & 0x0F) << 8) + b3),
+            )
+        }
+        _ => {
+            let (b1, b2, b3, b4) = (byte()?, byte()?, byte()?, byte()?);
+            (with_sign(flag, (b1 << 8) + b2), with_sign(flag >> 1, (b3 << 8) + b4))
+        }
+    })
+}
+
+/// Encode the coordinate deltas of a point as a triplet, writing the data
+/// bytes and returning the flag.
+fn write_triplet(w: &mut Writer, dx: i32, dy: i32, on_curve: bool) -> u8 {
+    let on = if on_curve { 0 } else { 0x80 };
+    let (x, y) = (dx.unsigned_abs(), dy.unsigned_abs());
+    let x_sign = u8::from(dx >= 0);
+    let y_sign = u8::from(dy >= 0);
+    let xy_sign = x_sign + 2 * y_sign;
+
+    if dx == 0 && y < 1280 {
+        w.write::<u8>(y as u8);
+        on + ((y & 0xF00) >> 7) as u8 + y_sign
+    } else if dy == 0 && x < 1280 {
+        w.write::<u8>(x as u8);
+        on + 10 + ((x & 0xF00) >> 7) as u8 + x_sign
+    } else if x < 65 && y < 65 {
+        let (x, y) = (x - 1, y - 1);
+        w.write::<u8>((((x & 0xF) << 4) | (y & 0xF)) as u8);
+        on + 20 + (x & 0x30) as u8 + ((y & 0x30) >> 2) as u8 + xy_sign
+    } else if x < 769 && y < 769 {
+        let (x, y) = (x - 1, y - 1);
+        w.write::<u8>(x as u8);
+        w.write::<u8>(y as u8);
+        on + 84 + 12 * ((x & 0x300) >> 8) as u8 + ((y & 0x300) >> 6) as u8 + xy_sign
+    } else if x < 4096 && y < 4096 {
+        w.write::<u8>((x >> 4) as u8);
+        w.write::<u8>((((x & 0xF) << 4) | (y >> 8)) as u8);
+        w.write::<u8>(y as u8);
+        on + 120 + xy_sign
+    } else {
+        w.write::<u16>(x as u16);
+        w.write::<u16>(y as u16);
+        on + 124 + xy_sign
+    }
+}
+
+/// Reconstruct the `hmtx` table, restoring left side bearings from the glyph
+/// bounding boxes where they were omitted.
+fn reconstruct_hmtx(
+    data: &[u8],
+    num_glyphs: u16,
+    num_h_metrics: u16,
+    glyf: &Glyf,
+) -> Result<Vec<u8>> {
+    let mut r = Reader::new(data);
+    let flags = r.read::<u8>()?;
+    if flags & 0xFC != 0 || flags & 0x03 == 0 || num_h_metrics > num_glyphs {
+        return Err(Error::Woff2);
+    }
+
+    let x_min = |id: u16| glyf.x_mins.get(usize::from(id)).copied().ok_or(Error::Woff2);
+
+    let mut advances = vec![];
+    for _ in 0..num_h_metrics {
+        advances.push(r.read::<u16>()?);
+    }
+
+    let mut lsbs = vec![];
+    for id in 0..num_glyphs {
+        let omitted = if id < num_h_metrics { flags & 0x01 } else { flags & 0x02 };
+        lsbs.push(if omitted != 0 { x_min(id)? } else { r.read::<i16>()? });
+    }
+
+    let mut w = Writer::new();
+    for (id, lsb) in lsbs.into_iter().enumerate() {
+        if let Some(&advance) = advances.get(id) {
+            w.write::<u16>(advance);
+        }
+        w.write::<i16>(lsb);
+    }
+    Ok(w.finish())
+}
@@ -0,0 +1,162 @@
+//! Reading and writing WOFF 1.0 fonts, whose tables are compressed with zlib.
+
+use super::*;
+
+/// The signature of WOFF 1.0 files.
+const SIGNATURE: u32 = 0x774F4646;
+
+/// The optional blocks of a WOFF font besides its tables.
+///
+/// They are carried over from WOFF input with [`woff_blocks`] and written by
+/// [`ttf_to_woff`], so that converting a WOFF font keeps its license metadata.
+/// WOFF2 fonts have the same blocks, which the `woff2` feature reads with
+/// `woff2_blocks` and writes with `ttf_to_woff2_with_blocks`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct WoffBlocks {
+    /// The extended metadata, an XML document, decompressed. Empty if there is
+    /// none.
+    pub metadata: Vec<u8>,
+    /// The private data, in a format of the font vendor's choice. Empty if
+    /// there is none.
+    pub private: Vec<u8>,
+}
+
+/// Convert a WOFF 1.0 font into an OpenType font that can be subsetted.
+pub fn woff_to_ttf(data: &[u8]) -> Result<Vec<u8>> {
+    let mut r = Reader::new(data);
+    if r.read::<u32>()? != SIGNATURE {
+        return Err(Error::UnknownKind);
+    }
+    let kind = r.read::<FontKind>()?;
+    if kind == FontKind::Collection {
+        return Err(Error::Unsupported);
+    }
+    r.skip(4)?;
+    let count = r.read::<u16>()?;
+    r.skip(30)?;
+
+    let mut tables = vec![];
+    for _ in 0..count {
+        let tag = r.read::<Tag>()?;
+        let offset = r.read::<u32>()? as usize;
+        let compressed = r.read::<u32>()? as usize;
+        let length = r.read::<u32>()? as usize;
+        r.skip(4)?;
+
+        let stored = data.get(offset..offset + compressed).ok_or(Error::InvalidOffset)?;
+        let table = match compressed.cmp(&length) {
+            std::cmp::Ordering::Less => gzip::zlib_decompress(stored)?,
+            std::cmp::Ordering::Equal => stored.to_vec(),
+            std::cmp::Ordering::Greater => return Err(Error::InvalidData),
+        };
+        if table.len() != length {
+            return Err(Error::InvalidData);
+        }
+        tables.push((tag, Cow::Owned(table)));
+    }
+
+    Ok(construct(kind, tables, 4))
+}
+
+/// Read the metadata and private data blocks of a WOFF 1.0 font.
+pub fn woff_blocks(data: &[u8]) -> Result<WoffBlocks> {
+    if u32::read_at(data, 0)? != SIGNATURE {
+        return Err(Error::UnknownKind);
+    }
+    let block = |at: usize, length: usize| {
+        let offset = u32::read_at(data, at)? as usize;
+        let length = u32::read_at(data, length)? as usize;
+        data.get(offset..offset + length).ok_or(Error::InvalidOffset)
+    };
+
+    let mut blocks = WoffBlocks::default();
+    let metadata = block(24, 28)?;
+    if !metadata.is_empty() {
+        blocks.metadata = gzip::zlib_decompress(metadata)?;
+        if blocks.metadata.len() != u32::read_at(data, 32)? as usize {
+            return Err(Error::InvalidData);
+        }
+    }
+    blocks.private = block(36, 40)?.to_vec();
+    Ok(blocks)
+}
+
+/// Compress an OpenType font into a WOFF 1.0 font with the given metadata and
+/// private data.
+///
+/// Each table is compressed with zlib unless that doesn't make it smaller.
+/// Font collections are not supported.
+pub fn ttf_to_woff(data: &[u8], blocks: &WoffBlocks) -> Result<Vec<u8>> {
+    let kind = Reader::new(data).read::<FontKind>()?;
+    if kind == FontKind::Collection {
+        return Err(Error::Unsupported);
+    }
+
+    let face = parse(data, 0)?;
+    let mut directory = Writer::new();
+    let mut stream = Writer::new();
+    let mut offset = 44 + 20 * face.tables.len();
+    let mut sfnt_size = 12 + 16 * face.tables.len();
+    for &(tag, table) in &face.tables {
+        let compressed = gzip::zlib_compress(table);
+        let stored = if compressed.len() < table.len() { &compressed } else { table };
+
+        // The checksum of `head` is that of the table in the font, whose
+        // checksum adjustment is zeroed while checksumming.
+        let sum = match tag {
+            Tag::HEAD if table.len() >= 12 => {
+                let mut head = table.to_vec();
+                head[8..12].fill(0);
+                checksum(&head)
+            }
+            _ => checksum(table),
+        };
+
+        directory.write::<Tag>(tag);
+        directory.write::<u32>(offset as u32);
+        directory.write::<u32>(stored.len() as u32);
+        directory.write::<u32>(table.len() as u32);
+        directory.write::<u32>(sum);
+        stream.give(stored);
+        stream.align(4);
+        offset += stored.len().next_multiple_of(4);
+        sfnt_size += table.len().next_multiple_of(4);
+    }
+
+    // The metadata follows the tables and the private data starts on a
+    // four-byte boundary after it.
+    let metadata = match blocks.metadata.is_empty() {
+        true => vec![],
+        false => gzip::zlib_compress(&blocks.metadata),
+    };
+    let meta_offset = if metadata.is_empty() { 0 } else { offset };
+    offset += metadata.len();
+    stream.give(&metadata);
+    let private_offset = if blocks.private.is_empty() {
+        0
+    } else {
+        stream.align(4);
+        offset = offset.next_multiple_of(4);
+        offset
+    };
+    offset += blocks.private.len();
+    stream.give(&blocks.private);
+
+    let mut w = Writer::new();
+    w.write::<u32>(SIGNATURE);
+    w.write::<FontKind>(kind);
+    w.write::<u32>(offset as u32);
+    w.write::<u16>(face.tables.len() as u16);
+    w.write::<u16>(0); // reserved
+    w.write::<u32>(sfnt_size as u32);
+    w.write::<u16>(1); // major version
+    w.write::<u16>(0); // minor version
+    w.write::<u32>(meta_offset as u32);
+    w.write::<u32>(metadata.len() as u32);
+    w.write::<u32>(blocks.metadata.len() as u32);
+    w.write::<u32>(private_offset as u32);
+    w.write::<u32>(blocks.private.len() as u32);
+    w.give(&directory.finish());
+    w.give(&stream.finish());
+    Ok(w.finish())
+}
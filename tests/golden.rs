@@ -0,0 +1,324 @@
+//! Behavioral snapshot tests against the fonts in `fonts/`.
+//!
+//! Each case subsets a font with fixed options, checks invariants that every
+//! subsetted font must uphold and compares a [`debug_dump`] of the result with
+//! the snapshot in `tests/golden`. Run with `UPDATE_GOLDEN=1` to accept
+//! intended changes and review the snapshot diff.
+//!
+//! The corpus lacks a variable font, so one is made from Noto Sans by adding
+//! a weight axis that moves a few glyphs.
+
+use std::path::Path;
+
+use subsetter::{debug_dump, subset, Instancer, Profile, Tag};
+use ttf_parser::{GlyphId, OutlineBuilder};
+
+const FEW: &str = "Hällo<.!ﬁ12";
+
+/// How a case subsets its font.
+#[derive(Copy, Clone)]
+enum Options {
+    Pdf,
+    Web,
+    Minimal,
+    /// Like `Pdf`, keeping the color tables.
+    Color,
+    /// Like `Pdf`, keeping the variation tables.
+    Variable,
+}
+
+#[test]
+fn golden_noto_sans() {
+    check("noto-sans-pdf", &font("NotoSans-Regular.ttf"), FEW, Options::Pdf);
+    check("noto-sans-web", &font("NotoSans-Regular.ttf"), FEW, Options::Web);
+    check("noto-sans-minimal", &font("NotoSans-Regular.ttf"), FEW, Options::Minimal);
+}
+
+#[test]
+fn golden_clicker_script() {
+    check("clicker-script-pdf", &font("ClickerScript-Regular.ttf"), FEW, Options::Pdf);
+}
+
+#[test]
+fn golden_latin_modern() {
+    check("latin-modern-pdf", &font("LatinModernRoman-Regular.otf"), FEW, Options::Pdf);
+    check(
+        "latin-modern-minimal",
+        &font("LatinModernRoman-Regular.otf"),
+        FEW,
+        Options::Minimal,
+    );
+}
+
+#[test]
+fn golden_new_cm_math() {
+    check("new-cm-math-pdf", &font("NewCMMath-Regular.otf"), "1+2=3;π∫∑", Options::Pdf);
+}
+
+#[test]
+fn golden_noto_sans_cjk() {
+    // The full cmap would make the snapshot huge, so only check invariants.
+    subset_checked(
+        "noto-sans-cjk-web",
+        &font("NotoSansCJKsc-Regular.otf"),
+        "ABC你好",
+        Options::Web,
+    );
+}
+
+#[test]
+fn golden_colr() {
+    // Color glyphs from several sections of the test font, which paint
+    // further glyphs that the subset must keep.
+    let text = "\u{F0200}\u{F0601}\u{F0A00}\u{F0B00}";
+    check(
+        "colr-v1-color",
+        &font("COLRv1StaticTestGlyphs-Regular.ttf"),
+        text,
+        Options::Color,
+    );
+}
+
+#[test]
+fn golden_variable() {
+    let data = variable_noto();
+    check("noto-sans-variable", &data, FEW, Options::Variable);
+
+    // An instance has the outlines of the variable font at its position.
+    let instance = Instancer::new().pin(Tag(*b"wght"), 650.0).instantiate(&data, 0);
+    let instance = instance.unwrap();
+    let subs = subset_checked("noto-sans-instance", &instance, FEW, Options::Pdf);
+    let mut ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+    let l = ttf.glyph_index('l').unwrap();
+    let default = outline(&ttf, l);
+    ttf.set_variation(ttf_parser::Tag::from_bytes(b"wght"), 650.0);
+    assert_ne!(outline(&ttf, l), default);
+    let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+    assert!(!ttfs.is_variable());
+    for c in FEW.chars() {
+        let id = ttf.glyph_index(c).unwrap();
+        assert_eq!(outline(&ttfs, id), outline(&ttf, id), "instance: {c:?}");
+    }
+}
+
+/// Read a font of the corpus.
+fn font(name: &str) -> Vec<u8> {
+    std::fs::read(Path::new("fonts").join(name)).unwrap()
+}
+
+fn check(name: &str, data: &[u8], text: &str, options: Options) {
+    let subs = subset_checked(name, data, text, options);
+    let dump = debug_dump(&subs, 0).unwrap();
+    let path = Path::new("tests/golden").join(name).with_extension("txt");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &dump).unwrap();
+    } else {
+        let expected = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(dump == expected, "{name}: output differs from {}", path.display());
+    }
+}
+
+/// Subset a font and check the invariants of the result.
+fn subset_checked(name: &str, data: &[u8], text: &str, options: Options) -> Vec<u8> {
+    let ttf = ttf_parser::Face::parse(data, 0).unwrap();
+    let glyphs: Vec<_> =
+        text.chars().filter_map(|c| Some(ttf.glyph_index(c)?.0)).collect();
+    let profile = match options {
+        Options::Pdf => Profile::pdf(&glyphs),
+        Options::Web => Profile::web(&glyphs),
+        Options::Minimal => Profile::pdf(&glyphs).minimal(true),
+        Options::Color => Profile::pdf(&glyphs).keep_color(true),
+        Options::Variable => Profile::pdf(&glyphs).keep_variations(true),
+    };
+
+    let subs = subset(data, 0, profile).unwrap();
+    check_directory(data, &subs);
+
+    let ttfs = ttf_parser::Face::parse(&subs, 0).unwrap();
+    assert_eq!(ttfs.number_of_glyphs(), ttf.number_of_glyphs());
+    for c in text.chars() {
+        let id = ttf.glyph_index(c).unwrap();
+        assert_eq!(ttfs.glyph_index(c), Some(id), "{name}: {c:?} is not mapped");
+        if let Options::Web = options {
+            let pua = char::from_u32(0xF0000 + u32::from(id.0)).unwrap();
+            assert_eq!(ttfs.glyph_index(pua), Some(id));
+        }
+        assert_eq!(outline(&ttfs, id), outline(&ttf, id), "{name}: {c:?}");
+        assert_eq!(ttfs.glyph_hor_advance(id), ttf.glyph_hor_advance(id));
+        assert_eq!(ttfs.glyph_hor_side_bearing(id), ttf.glyph_hor_side_bearing(id));
+        assert_eq!(ttfs.is_color_glyph(id), ttf.is_color_glyph(id), "{name}: {c:?}");
+    }
+
+    // Variable fonts keep their variations.
+    if let Options::Variable = options {
+        let wght = ttf_parser::Tag::from_bytes(b"wght");
+        for value in [100.0, 650.0, 900.0] {
+            let (mut ttf, mut ttfs) = (ttf.clone(), ttfs.clone());
+            ttf.set_variation(wght, value);
+            ttfs.set_variation(wght, value);
+            for c in text.chars() {
+                let id = ttf.glyph_index(c).unwrap();
+                assert_eq!(outline(&ttfs, id), outline(&ttf, id), "{name}: {c:?}");
+            }
+        }
+    }
+
+    // Glyphs that weren't requested lose their outlines.
+    let unused = GlyphId(ttf.number_of_glyphs() - 1);
+    if !glyphs.contains(&unused.0)
+        && ttf.outline_glyph(unused, &mut Sink(vec![])).is_some()
+    {
+        assert_eq!(outline(&ttfs, unused), None, "{name}: unused glyph kept");
+    }
+
+    subs
+}
+
+/// Make Noto Sans variable with a weight axis from 100 to 900. At 900, the
+/// first contours of "l", "o" and "H" move 40 units to the right and 80 up.
+fn variable_noto() -> Vec<u8> {
+    let data = font("NotoSans-Regular.ttf");
+    let ttf = ttf_parser::Face::parse(&data, 0).unwrap();
+    let num_glyphs = ttf.number_of_glyphs();
+
+    // The name table has the name of the axis of Noto Sans' variable version.
+    let mut fvar = vec![0, 1, 0, 0, 0, 16, 0, 2, 0, 1, 0, 20, 0, 0, 0, 8];
+    fvar.extend(b"wght");
+    for value in [100_i32, 400, 900] {
+        fvar.extend((value << 16).to_be_bytes());
+    }
+    fvar.extend([0, 0, 1, 5]);
+
+    // A tuple with an embedded peak at the maximum and deltas for the first
+    // point, which the other points of its contour follow, padded to an even
+    // length.
+    let moved: Vec<u16> = "loH".chars().map(|c| ttf.glyph_index(c).unwrap().0).collect();
+    let variation =
+        [0, 1, 0, 10, 0, 9, 0xA0, 0, 0x40, 0, 1, 0, 0, 0x40, 0, 40, 0x40, 0, 80, 0];
+    let start = 20 + 4 * (usize::from(num_glyphs) + 1);
+    let mut gvar = vec![0, 1, 0, 0, 0, 1, 0, 0];
+    gvar.extend((start as u32).to_be_bytes());
+    gvar.extend(num_glyphs.to_be_bytes());
+    gvar.extend([0, 1]);
+    gvar.extend((start as u32).to_be_bytes());
+    let mut offset = 0_u32;
+    for glyph in 0..=num_glyphs {
+        gvar.extend(offset.to_be_bytes());
+        if moved.contains(&glyph) {
+            offset += variation.len() as u32;
+        }
+    }
+    for _ in &moved {
+        gvar.extend(variation);
+    }
+
+    with_tables(&data, &[(*b"fvar", &fvar), (*b"gvar", &gvar)])
+}
+
+/// Add tables to a font, keeping the tables sorted and four-byte aligned.
+fn with_tables(data: &[u8], extra: &[([u8; 4], &[u8])]) -> Vec<u8> {
+    let ttf = ttf_parser::RawFace::parse(data, 0).unwrap();
+    let mut tables: Vec<([u8; 4], &[u8])> = ttf
+        .table_records
+        .into_iter()
+        .map(|record| (record.tag.to_bytes(), ttf.table(record.tag).unwrap()))
+        .collect();
+    tables.extend_from_slice(extra);
+    tables.sort_by_key(|&(tag, _)| tag);
+
+    let count = tables.len() as u16;
+    let selector = count.ilog2() as u16;
+    let mut font = data[..4].to_vec();
+    for value in [count, 16 << selector, selector, 16 * count - (16 << selector)] {
+        font.extend(value.to_be_bytes());
+    }
+    let mut body = vec![];
+    let mut head = 0;
+    for (tag, table) in &tables {
+        let offset = 12 + 16 * tables.len() + body.len();
+        let mut table = table.to_vec();
+        if tag == b"head" {
+            table[8..12].fill(0);
+            head = offset;
+        }
+        font.extend(tag);
+        font.extend(checksum(&table).to_be_bytes());
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((table.len() as u32).to_be_bytes());
+        body.extend(&table);
+        body.resize(body.len().next_multiple_of(4), 0);
+    }
+    font.extend(body);
+    let adjustment = 0xB1B0AFBA_u32.wrapping_sub(checksum(&font));
+    font[head + 8..head + 12].copy_from_slice(&adjustment.to_be_bytes());
+    font
+}
+
+/// Check the table directory and checksums of a subsetted font.
+fn check_directory(source: &[u8], data: &[u8]) {
+    let read_u32 =
+        |d: &[u8], i: usize| u32::from_be_bytes(d[i..i + 4].try_into().unwrap());
+    let tags = |d: &[u8]| {
+        let count = u16::from_be_bytes([d[4], d[5]]) as usize;
+        (0..count).map(|i| read_u32(d, 12 + 16 * i)).collect::<Vec<_>>()
+    };
+
+    let source_tags = tags(source);
+    let tags = tags(data);
+    assert!(tags.windows(2).all(|w| w[0] < w[1]), "tables are not sorted");
+    for (i, tag) in tags.iter().enumerate() {
+        let loca = u32::from_be_bytes(*b"loca");
+        assert!(source_tags.contains(tag) || *tag == loca, "new table {tag:x}");
+
+        let record = 12 + 16 * i;
+        let offset = read_u32(data, record + 8) as usize;
+        let length = read_u32(data, record + 12) as usize;
+        assert_eq!(offset % 4, 0, "table is not aligned");
+
+        let mut table = data[offset..offset + length].to_vec();
+        if *tag == u32::from_be_bytes(*b"head") {
+            table[8..12].fill(0);
+        }
+        assert_eq!(checksum(&table), read_u32(data, record + 4), "wrong checksum");
+    }
+
+    assert_eq!(checksum(data), 0xB1B0AFBA, "wrong checksum adjustment");
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut bytes = [0; 4];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(bytes))
+    })
+}
+
+fn outline(face: &ttf_parser::Face, id: GlyphId) -> Option<Vec<String>> {
+    let mut sink = Sink(vec![]);
+    face.outline_glyph(id, &mut sink)?;
+    Some(sink.0)
+}
+
+struct Sink(Vec<String>);
+
+impl OutlineBuilder for Sink {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.push(format!("M {x} {y}"));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.push(format!("L {x} {y}"));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.push(format!("Q {x1} {y1} {x} {y}"));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.push(format!("C {x1} {y1} {x2} {y2} {x} {y}"));
+    }
+
+    fn close(&mut self) {
+        self.0.push("Z".into());
+    }
+}